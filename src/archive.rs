@@ -0,0 +1,401 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+use crate::azure::AzureClient;
+use crate::blob_reader::{BlobReader, ReadaheadConfig};
+use crate::compression::{Compression, Encoder};
+
+// ============================================================================
+// Archive Transfers - Directories streamed to and from a single tar blob
+// ============================================================================
+
+/// Size of the chunks an archive is uploaded in (one block each)
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Chunks buffered between the archive and the network
+const CHANNEL_DEPTH: usize = 4;
+
+/// What an archive upload or extraction moved
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    /// Regular files archived or extracted
+    pub files: u64,
+    /// Their total size, before compression
+    pub bytes: u64,
+    /// Size of the archive blob
+    pub archive_bytes: u64,
+}
+
+/// Compression of a tar archive, from its name: .tar.gz and .tgz are gzip,
+/// .tar.zst and .tzst are zstd
+pub fn archive_compression(name: &str) -> Option<Compression> {
+    let name = name.to_ascii_lowercase();
+    if name.ends_with(".tgz") {
+        Some(Compression::Gzip)
+    } else if name.ends_with(".tzst") {
+        Some(Compression::Zstd)
+    } else {
+        Compression::from_extension(Path::new(&name))
+    }
+}
+
+/// Archive a local directory into a block blob as it is read, without
+/// staging the archive on disk
+///
+/// Files are stored relative to `source`, so extracting the blob recreates
+/// the directory's contents. A file that can't be read aborts the upload
+/// before the blob is committed.
+pub async fn upload_tar(
+    mut client: AzureClient,
+    container: &str,
+    blob: &str,
+    source: &Path,
+    compression: Option<Compression>,
+    content_type: Option<&str>,
+) -> Result<ArchiveSummary> {
+    let (sender, receiver) = mpsc::channel(CHANNEL_DEPTH);
+    let source = source.to_path_buf();
+    let writer = tokio::task::spawn_blocking(move || {
+        let errors = sender.clone();
+        match write_archive(&source, compression, sender) {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                // Fails the upload; if the upload failed first, nobody listens
+                let _ = errors.blocking_send(Err(e));
+                None
+            }
+        }
+    });
+
+    let archive_bytes = client
+        .upload_stream(container, blob, receiver, content_type)
+        .await?;
+    let summary = writer
+        .await
+        .map_err(|e| anyhow!("Archive task failed: {}", e))?
+        .ok_or_else(|| anyhow!("Archiving stopped before the upload finished"))?;
+
+    Ok(ArchiveSummary {
+        archive_bytes,
+        ..summary
+    })
+}
+
+/// Download a tar blob and extract it into `destination` as it arrives
+///
+/// The archive is decompressed by the blob's Content-Encoding or, failing
+/// that, by its name (see `archive_compression`). Entries that would land
+/// outside `destination` are refused.
+pub async fn download_untar(
+    client: AzureClient,
+    container: &str,
+    blob: &str,
+    destination: &Path,
+) -> Result<ArchiveSummary> {
+    let config = ReadaheadConfig::configured()?;
+    let mut reader = BlobReader::open(client, container, blob, config).await?;
+    let compression = reader
+        .content_encoding()
+        .and_then(Compression::from_content_encoding)
+        .or_else(|| archive_compression(blob));
+
+    let (sender, receiver) = mpsc::channel(CHANNEL_DEPTH);
+    let destination = destination.to_path_buf();
+    let extractor = tokio::task::spawn_blocking(move || {
+        extract_tar(ChunkReader::new(receiver), compression, &destination)
+    });
+
+    let mut offset = 0;
+    while offset < reader.size() {
+        let chunk = reader.read_at(offset, config.block_size).await?;
+        if chunk.is_empty() {
+            break;
+        }
+        offset += chunk.len() as u64;
+        if sender.send(chunk).await.is_err() {
+            // The extractor stopped, at the end of the archive or on an error
+            break;
+        }
+    }
+    drop(sender);
+
+    let summary = extractor
+        .await
+        .map_err(|e| anyhow!("Extraction task failed: {}", e))??;
+    Ok(ArchiveSummary {
+        archive_bytes: offset,
+        ..summary
+    })
+}
+
+/// Write `source` as a tar archive, optionally compressed, to `sender` in chunks
+fn write_archive(
+    source: &Path,
+    compression: Option<Compression>,
+    sender: mpsc::Sender<Result<Vec<u8>>>,
+) -> Result<ArchiveSummary> {
+    let chunks = ChunkWriter::new(sender);
+    let (chunks, summary) = match compression {
+        Some(compression) => {
+            let (encoder, summary) = write_tar(source, Encoder::new(compression, chunks)?)?;
+            (encoder.finish()?, summary)
+        }
+        None => write_tar(source, chunks)?,
+    };
+    chunks.finish()?;
+    Ok(summary)
+}
+
+/// Write the contents of a directory as a tar archive, returning the writer
+///
+/// Entries are named relative to `source`, in sorted order within each
+/// directory. Symbolic links are stored as links.
+fn write_tar<W: Write>(source: &Path, writer: W) -> Result<(W, ArchiveSummary)> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    let mut summary = ArchiveSummary::default();
+
+    let mut stack = vec![source.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let mut paths = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read directory {}", dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        for path in paths {
+            let relative = path.strip_prefix(source)?;
+            let metadata = std::fs::symlink_metadata(&path)?;
+            if metadata.is_dir() {
+                builder
+                    .append_dir(relative, &path)
+                    .with_context(|| format!("Failed to archive {}", path.display()))?;
+                stack.push(path);
+            } else {
+                builder
+                    .append_path_with_name(&path, relative)
+                    .with_context(|| format!("Failed to archive {}", path.display()))?;
+                if metadata.is_file() {
+                    summary.files += 1;
+                    summary.bytes += metadata.len();
+                }
+            }
+        }
+    }
+
+    Ok((builder.into_inner()?, summary))
+}
+
+/// Extract a tar archive, optionally compressed, into `destination`
+fn extract_tar<'a, R: Read + 'a>(
+    reader: R,
+    compression: Option<Compression>,
+    destination: &Path,
+) -> Result<ArchiveSummary> {
+    let input: Box<dyn Read + 'a> = match compression {
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        None => Box::new(reader),
+    };
+    std::fs::create_dir_all(destination)
+        .with_context(|| format!("Failed to create directory {}", destination.display()))?;
+
+    let mut archive = tar::Archive::new(input);
+    archive.set_preserve_mtime(true);
+    let mut summary = ArchiveSummary::default();
+    for entry in archive.entries().context("Failed to read the archive")? {
+        let mut entry = entry.context("Failed to read the archive")?;
+        let path = entry.path()?.into_owned();
+        let extracted = entry
+            .unpack_in(destination)
+            .with_context(|| format!("Failed to extract {}", path.display()))?;
+        if !extracted {
+            return Err(anyhow!(
+                "Refusing to extract {} outside of {}",
+                path.display(),
+                destination.display()
+            ));
+        }
+        if entry.header().entry_type().is_file() {
+            summary.files += 1;
+            summary.bytes += entry.size();
+        }
+    }
+
+    Ok(summary)
+}
+
+/// A writer that hands what is written to it to an async task in
+/// `CHUNK_SIZE` pieces
+struct ChunkWriter {
+    sender: mpsc::Sender<Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+}
+
+impl ChunkWriter {
+    fn new(sender: mpsc::Sender<Result<Vec<u8>>>) -> Self {
+        Self {
+            sender,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.sender
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the upload stopped"))
+    }
+
+    /// Send whatever is left in the buffer
+    fn finish(mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A reader over chunks sent by an async task, ending when the sender is dropped
+struct ChunkReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+impl ChunkReader {
+    fn new(receiver: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            offset: 0,
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset >= self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.offset = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.offset);
+        buf[..len].copy_from_slice(&self.chunk[self.offset..self.offset + len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_compression() {
+        assert_eq!(
+            archive_compression("backups/site.tar.gz"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(archive_compression("site.TGZ"), Some(Compression::Gzip));
+        assert_eq!(archive_compression("site.tar.zst"), Some(Compression::Zstd));
+        assert_eq!(archive_compression("site.tzst"), Some(Compression::Zstd));
+        assert_eq!(archive_compression("site.tar"), None);
+    }
+
+    #[test]
+    fn test_tar_round_trip() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("dataset");
+        std::fs::create_dir_all(source.join("nested/empty")).unwrap();
+        std::fs::write(source.join("a.csv"), "a,b\n1,2\n").unwrap();
+        std::fs::write(source.join("nested/b.txt"), "hello").unwrap();
+
+        for compression in [None, Some(Compression::Gzip), Some(Compression::Zstd)] {
+            let (archive, written) = match compression {
+                Some(compression) => {
+                    let encoder = Encoder::new(compression, Vec::new()).unwrap();
+                    let (encoder, written) = write_tar(&source, encoder).unwrap();
+                    (encoder.finish().unwrap(), written)
+                }
+                None => write_tar(&source, Vec::new()).unwrap(),
+            };
+            assert_eq!(written.files, 2);
+            assert_eq!(written.bytes, 13);
+
+            let target = temp.path().join(format!("extracted-{:?}", compression));
+            let extracted = extract_tar(archive.as_slice(), compression, &target).unwrap();
+            assert_eq!(extracted, written);
+            assert_eq!(
+                std::fs::read_to_string(target.join("nested/b.txt")).unwrap(),
+                "hello"
+            );
+            assert!(target.join("nested/empty").is_dir());
+        }
+    }
+
+    #[test]
+    fn test_extract_refuses_paths_outside_destination() {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::Regular);
+        // set_path rejects "..", so write the name into the header directly
+        header.as_old_mut().name[..9].copy_from_slice(b"../escape");
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append(&header, &b"oops"[..]).unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let temp = tempfile::tempdir().unwrap();
+        let target = temp.path().join("target");
+        assert!(extract_tar(archive.as_slice(), None, &target).is_err());
+        assert!(!temp.path().join("escape").exists());
+    }
+
+    #[test]
+    fn test_chunks_cross_threads() {
+        let (sender, mut receiver) = mpsc::channel(CHANNEL_DEPTH);
+        let producer = std::thread::spawn(move || {
+            let mut writer = ChunkWriter::new(sender);
+            writer.write_all(&vec![7; CHUNK_SIZE + 10]).unwrap();
+            writer.finish().unwrap();
+        });
+        let mut sizes = Vec::new();
+        while let Some(chunk) = receiver.blocking_recv() {
+            sizes.push(chunk.unwrap().len());
+        }
+        producer.join().unwrap();
+        assert_eq!(sizes, vec![CHUNK_SIZE + 10]);
+
+        let (sender, receiver) = mpsc::channel(CHANNEL_DEPTH);
+        sender.try_send(b"hello ".to_vec()).unwrap();
+        sender.try_send(b"world".to_vec()).unwrap();
+        drop(sender);
+        let mut text = String::new();
+        ChunkReader::new(receiver)
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "hello world");
+    }
+}
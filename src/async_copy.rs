@@ -0,0 +1,257 @@
+use anyhow::{anyhow, Result};
+use azure_core::Url;
+use azure_storage_blobs::blob::CopyStatus;
+use colored::*;
+use std::time::Duration;
+
+use crate::audit_log;
+use crate::azcopy_output::FailedTransfer;
+use crate::azure::{AzureClient, BlobItem};
+use crate::cancel;
+use crate::dry_run::{DryRunPlan, PlannedAction, PlannedTransfer};
+use crate::error::http_status;
+use crate::utils::{format_size, get_filename, parse_azure_uri, uri_base};
+
+// ============================================================================
+// Async Copies - Server-side copies between Azure locations, left running
+// ============================================================================
+
+/// How long the signed source URL of a copy between accounts stays valid, the
+/// longest Azure allows; a copy still reading its source after that fails
+const SOURCE_URL_VALIDITY: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Blob a source blob is copied to
+///
+/// A single blob goes to `dest_path`, or keeps its name under it when that
+/// ends with a slash. Recursive copies keep each blob's path below
+/// `source_prefix`, under a folder named after the prefix when `as_subdir`,
+/// as azcopy places them.
+pub fn destination_blob(
+    source_prefix: &str,
+    blob: &str,
+    dest_path: Option<&str>,
+    recursive: bool,
+    as_subdir: bool,
+) -> String {
+    let dest = dest_path.unwrap_or_default();
+    let dest_dir = if dest.is_empty() || dest.ends_with('/') {
+        dest.to_string()
+    } else {
+        format!("{}/", dest)
+    };
+    if !recursive {
+        return if dest.is_empty() || dest.ends_with('/') {
+            format!("{}{}", dest, get_filename(blob))
+        } else {
+            dest.to_string()
+        };
+    }
+
+    let prefix = source_prefix.trim_end_matches('/');
+    let relative = if prefix.is_empty() {
+        blob
+    } else {
+        blob.strip_prefix(prefix)
+            .map(|rest| rest.trim_start_matches('/'))
+            .unwrap_or(blob)
+    };
+    match prefix
+        .rsplit('/')
+        .next()
+        .filter(|name| as_subdir && !name.is_empty())
+    {
+        Some(name) => format!("{}{}/{}", dest_dir, name, relative),
+        None => format!("{}{}", dest_dir, relative),
+    }
+}
+
+/// Start server-side copies of the blob at `source`, or every blob under it
+/// when `recursive`, to `destination`, and return without waiting for them
+///
+/// Copies within an account usually finish as they start; the others are
+/// left to the service, for `cp-status` to follow. Blobs in the archive tier
+/// are rehydrated into the copy. Returns the copies that couldn't be started.
+pub async fn start_copies(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    as_subdir: bool,
+    dry_run: bool,
+) -> Result<Vec<FailedTransfer>> {
+    let (source_account, source_container, source_path) = parse_azure_uri(source)?;
+    let (dest_account, dest_container, dest_path) = parse_azure_uri(destination)?;
+    let client_for = |account: &Option<String>| match account {
+        Some(account) => AzureClient::new().with_storage_account(account),
+        None => AzureClient::new(),
+    };
+    let mut source_client = client_for(&source_account);
+    let mut dest_client = client_for(&dest_account);
+
+    let prefix = source_path.clone().unwrap_or_default();
+    let blobs: Vec<(String, u64)> = if recursive {
+        let prefix = if prefix.is_empty() || prefix.ends_with('/') {
+            prefix.clone()
+        } else {
+            format!("{}/", prefix)
+        };
+        source_client
+            .list_blobs(
+                &source_container,
+                Some(prefix.as_str()).filter(|p| !p.is_empty()),
+                None,
+            )
+            .await?
+            .into_iter()
+            .filter_map(|item| match item {
+                BlobItem::Blob(blob) => Some((blob.name, blob.properties.content_length)),
+                BlobItem::Prefix(_) => None,
+            })
+            .collect()
+    } else {
+        let blob = source_path
+            .clone()
+            .filter(|path| !path.ends_with('/'))
+            .ok_or_else(|| anyhow!("Source is a prefix. Use -r flag for recursive copy"))?;
+        let properties = source_client
+            .get_blob_properties(&source_container, &blob)
+            .await?;
+        vec![(blob, properties.properties.content_length)]
+    };
+    if blobs.is_empty() {
+        return Err(anyhow!("No blobs found under {}", source));
+    }
+
+    let dest_base = uri_base(destination, dest_path.as_deref());
+    let source_base = uri_base(source, source_path.as_deref());
+    let copies: Vec<(String, String, u64)> = blobs
+        .into_iter()
+        .map(|(blob, size)| {
+            let target =
+                destination_blob(&prefix, &blob, dest_path.as_deref(), recursive, as_subdir);
+            (blob, target, size)
+        })
+        .collect();
+
+    if dry_run {
+        let planned = copies
+            .iter()
+            .map(|(blob, target, size)| PlannedTransfer {
+                action: PlannedAction::Copy,
+                source: format!("{}/{}", source_base, blob),
+                destination: Some(format!("{}/{}", dest_base, target)),
+                size: Some(*size),
+            })
+            .collect();
+        DryRunPlan::new(planned).print();
+        return Ok(Vec::new());
+    }
+
+    // The service reads the source with the destination's credentials
+    // within an account; other accounts are read through a signed URL
+    let same_account = source_client.get_storage_account() == dest_client.get_storage_account();
+    let signer = if same_account {
+        None
+    } else {
+        Some(source_client.url_signer(SOURCE_URL_VALIDITY).await?)
+    };
+
+    let (mut started, mut finished) = (0, 0);
+    let mut bytes = 0;
+    let mut failures = Vec::new();
+    for (blob, target, size) in &copies {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let source_uri = format!("{}/{}", source_base, blob);
+        let dest_uri = format!("{}/{}", dest_base, target);
+        let source_url = match &signer {
+            Some(signer) => signer.sign(&source_container, blob)?,
+            None => source_client.blob_url(&source_container, blob)?,
+        };
+        let state = dest_client
+            .copy_from_url(&Url::parse(&source_url)?, &dest_container, target, None)
+            .await
+            .map_err(anyhow::Error::from);
+        match state {
+            Ok(state) if state.status == CopyStatus::Success => {
+                finished += 1;
+                bytes += size;
+            }
+            Ok(state) if state.status == CopyStatus::Pending => started += 1,
+            Ok(state) => {
+                eprintln!(
+                    "{} Copy of {} to {} {}",
+                    "⚠".yellow(),
+                    source_uri,
+                    dest_uri,
+                    state.status
+                );
+                failures.push(failure(&source_uri, &dest_uri, 0));
+            }
+            Err(e) => {
+                eprintln!("{} {:#}", "⚠".yellow(), e);
+                let code = http_status(&e).map_or(0, |status| u16::from(status) as i32);
+                failures.push(failure(&source_uri, &dest_uri, code));
+            }
+        }
+    }
+    audit_log::add_uri(destination);
+    audit_log::record(finished, bytes);
+    audit_log::record_failed(failures.len() as u64);
+
+    if finished > 0 {
+        status!(
+            "{} {} copy(ies) finished as they started ({})",
+            "✓".green(),
+            finished,
+            format_size(bytes)
+        );
+    }
+    if started > 0 {
+        status!(
+            "{} {} copy(ies) running server-side; follow them with: azst cp-status{} {}",
+            "ℹ".blue(),
+            started,
+            if recursive { " -r" } else { "" },
+            destination
+        );
+    }
+    Ok(failures)
+}
+
+fn failure(source: &str, destination: &str, error_code: i32) -> FailedTransfer {
+    FailedTransfer {
+        source: source.to_string(),
+        destination: destination.to_string(),
+        error_code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination_blob() {
+        assert_eq!(
+            destination_blob("data/a.csv", "data/a.csv", Some("copy/"), false, true),
+            "copy/a.csv"
+        );
+        assert_eq!(
+            destination_blob("data/a.csv", "data/a.csv", Some("copy/b.csv"), false, true),
+            "copy/b.csv"
+        );
+        assert_eq!(
+            destination_blob("logs/", "logs/2024/x.log", Some("archive"), true, true),
+            "archive/logs/2024/x.log"
+        );
+        assert_eq!(
+            destination_blob("logs", "logs/2024/x.log", None, true, false),
+            "2024/x.log"
+        );
+        assert_eq!(
+            destination_blob("", "x.log", Some("all/"), true, true),
+            "all/x.log"
+        );
+    }
+}
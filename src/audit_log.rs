@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::azcopy_output::JobSummary;
+use crate::azure::AzureClient;
+use crate::error;
+
+// ============================================================================
+// Audit Log - One JSON line per invocation that changed something
+// ============================================================================
+
+/// What this invocation changed so far, filled in by the operations as they
+/// complete and written out once the command ends
+static TALLY: Mutex<Tally> = Mutex::new(Tally {
+    files: 0,
+    bytes: 0,
+    failed: 0,
+    uris: Vec::new(),
+});
+
+struct Tally {
+    files: u64,
+    bytes: u64,
+    failed: u64,
+    /// Locations only known once the command runs, e.g. paths read by `rm -I`
+    uris: Vec<String>,
+}
+
+/// Count files written or removed, and bytes written
+pub fn record(files: u64, bytes: u64) {
+    if let Ok(mut tally) = TALLY.lock() {
+        tally.files += files;
+        tally.bytes += bytes;
+    }
+}
+
+/// Count what a finished azcopy job transferred or removed
+pub fn record_job(summary: &JobSummary) {
+    if let Ok(mut tally) = TALLY.lock() {
+        tally.files += summary.completed_count;
+        tally.bytes += summary.bytes_transferred;
+        tally.failed += u64::from(summary.failed_count);
+    }
+}
+
+/// Count transfers that failed outside azcopy jobs
+pub fn record_failed(count: u64) {
+    if let Ok(mut tally) = TALLY.lock() {
+        tally.failed += count;
+    }
+}
+
+/// Transfers azcopy reported as failed so far in this invocation
+pub fn failed_transfers() -> u64 {
+    TALLY.lock().map(|tally| tally.failed).unwrap_or(0)
+}
+
+/// Files and bytes changed and transfers failed so far in this invocation
+pub fn totals() -> (u64, u64, u64) {
+    TALLY
+        .lock()
+        .map(|tally| (tally.files, tally.bytes, tally.failed))
+        .unwrap_or_default()
+}
+
+/// Start a new tally, for the next command of an interactive shell
+pub fn reset() {
+    if let Ok(mut tally) = TALLY.lock() {
+        tally.files = 0;
+        tally.bytes = 0;
+        tally.failed = 0;
+        tally.uris.clear();
+    }
+}
+
+/// Note a location the command line didn't name
+pub fn add_uri(uri: &str) {
+    if let Ok(mut tally) = TALLY.lock() {
+        tally.uris.push(uri.to_string());
+    }
+}
+
+/// One key=value line about a transfer that didn't fully succeed, printed
+/// by --quiet in place of everything else; None when it succeeded
+///
+/// ```text
+/// azst cp: files=118 bytes=52428800 failed=2 exit=5 error="2 transfers failed"
+/// ```
+pub fn summary_line(command: &str, result: &Result<()>) -> Option<String> {
+    let (files, bytes, failed) = totals();
+    if result.is_ok() && failed == 0 {
+        return None;
+    }
+    let mut line = format!(
+        "azst {}: files={} bytes={} failed={} exit={}",
+        command,
+        files,
+        bytes,
+        failed,
+        result.as_ref().map_or_else(error::exit_code, |_| 0)
+    );
+    if let Err(e) = result {
+        // Debug formatting quotes the message and escapes its newlines
+        line.push_str(&format!(" error={:?}", format!("{:#}", e)));
+    }
+    Some(line)
+}
+
+/// One line of the audit log
+#[derive(Debug, Serialize)]
+struct AuditEntry {
+    /// When the command ended, RFC 3339 in UTC
+    time: String,
+    command: String,
+    uris: Vec<String>,
+    /// Files written or removed
+    files: u64,
+    /// Bytes written
+    bytes: u64,
+    /// Transfers that failed
+    failed: u64,
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// The credential the command authenticated with
+    identity: String,
+    /// Local account that ran azst
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+}
+
+/// Append a line for a finished command to the audit log at `path`
+pub fn append(path: &Path, command: &str, uris: Vec<String>, result: &Result<()>) -> Result<()> {
+    let tally = TALLY
+        .lock()
+        .map_err(|_| anyhow!("The audit tally is unavailable"))?;
+    let entry = AuditEntry {
+        time: time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)?,
+        command: command.to_string(),
+        uris: uris.into_iter().chain(tally.uris.iter().cloned()).collect(),
+        files: tally.files,
+        bytes: tally.bytes,
+        failed: tally.failed,
+        exit_code: result.as_ref().map_or_else(error::exit_code, |_| 0),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        identity: AzureClient::credential_source(),
+        user: std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok(),
+    };
+
+    let path = expand_home(path);
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    // A single write keeps lines whole when several azst processes share a log
+    file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Resolve a leading `~/` against the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_line_quotes_error() {
+        let result = Err(anyhow!("Blob \"a\" not found\nin c"));
+        let line = summary_line("rm", &result).unwrap();
+        assert!(line.starts_with("azst rm: files="));
+        assert!(line.ends_with(r#"exit=1 error="Blob \"a\" not found\nin c""#));
+    }
+
+    #[test]
+    fn test_append_writes_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/audit.jsonl");
+        record(2, 2048);
+
+        append(
+            &path,
+            "cp",
+            vec!["/data".to_string(), "az://acct/c/".to_string()],
+            &Ok(()),
+        )
+        .unwrap();
+        append(&path, "rm", Vec::new(), &Err(anyhow!("denied"))).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["command"], "cp");
+        assert_eq!(lines[0]["uris"][1], "az://acct/c/");
+        assert_eq!(lines[0]["exit_code"], 0);
+        assert!(lines[0]["files"].as_u64().unwrap() >= 2);
+        assert!(lines[0].get("error").is_none());
+        assert_eq!(lines[1]["exit_code"], 1);
+        assert_eq!(lines[1]["error"], "denied");
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(
+            expand_home(Path::new("~/audit.jsonl")),
+            home.join("audit.jsonl")
+        );
+        assert_eq!(
+            expand_home(Path::new("/var/log/azst.jsonl")),
+            PathBuf::from("/var/log/azst.jsonl")
+        );
+    }
+}
@@ -1,10 +1,20 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
+use crate::azure::convert_url_to_az_uri;
+use crate::dry_run::{parse_dry_run_message, PlannedTransfer};
+use crate::output::{is_deterministic, progress_mode, ProgressMode};
+use crate::utils::format_size;
+
+/// Weight of the newest sample in the smoothed transfer rate
+const RATE_SMOOTHING: f64 = 0.3;
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct AzCopyLogEntry {
@@ -70,6 +80,103 @@ pub struct ProgressMessage {
     pub total_bytes_enumerated: Option<String>,
 }
 
+/// Progress of `azcopy sync`, which counts copies and deletions separately
+/// and reports no percentage
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct SyncProgressMessage {
+    pub error_msg: String,
+    #[serde(rename = "JobID")]
+    pub job_id: String,
+    pub active_connections: String,
+    pub complete_job_ordered: bool,
+    pub job_status: String,
+    pub copy_total_transfers: String,
+    pub copy_transfers_completed: String,
+    pub copy_transfers_failed: String,
+    pub delete_total_transfers: String,
+    pub delete_transfers_completed: String,
+    pub delete_transfers_failed: String,
+    pub bytes_over_wire: String,
+    pub total_bytes_transferred: Option<String>,
+    pub total_bytes_enumerated: Option<String>,
+    pub server_busy_percentage: Option<String>,
+    pub network_error_percentage: Option<String>,
+    pub failed_transfers: Option<Value>,
+    pub perf_constraint: Option<i32>,
+    pub performance_advice: Option<Value>,
+    pub is_cleanup_job: Option<bool>,
+    #[serde(default)]
+    pub skipped_symlink_count: Option<String>,
+}
+
+impl From<SyncProgressMessage> for ProgressMessage {
+    fn from(sync: SyncProgressMessage) -> Self {
+        let sum = |a: &str, b: &str| a.parse::<u64>().unwrap_or(0) + b.parse::<u64>().unwrap_or(0);
+        let total = sum(&sync.copy_total_transfers, &sync.delete_total_transfers);
+        let completed = sum(
+            &sync.copy_transfers_completed,
+            &sync.delete_transfers_completed,
+        );
+        let failed = sum(&sync.copy_transfers_failed, &sync.delete_transfers_failed);
+        let percent = if total > 0 {
+            (completed + failed) as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+
+        ProgressMessage {
+            error_msg: sync.error_msg,
+            job_id: sync.job_id,
+            active_connections: sync.active_connections,
+            complete_job_ordered: sync.complete_job_ordered,
+            job_status: sync.job_status,
+            total_transfers: total.to_string(),
+            file_transfers: total.to_string(),
+            transfers_completed: completed.to_string(),
+            transfers_failed: failed.to_string(),
+            transfers_skipped: "0".to_string(),
+            total_bytes_transferred: sync
+                .total_bytes_transferred
+                .unwrap_or_else(|| sync.bytes_over_wire.clone()),
+            bytes_over_wire: sync.bytes_over_wire,
+            total_bytes_expected: sync.total_bytes_enumerated.clone().unwrap_or_default(),
+            percent_complete: format!("{:.1}", percent),
+            average_iops: "0".to_string(),
+            average_e2e_milliseconds: "0".to_string(),
+            server_busy_percentage: sync.server_busy_percentage.unwrap_or_default(),
+            network_error_percentage: sync.network_error_percentage.unwrap_or_default(),
+            failed_transfers: sync.failed_transfers,
+            skipped_transfers: None,
+            perf_constraint: sync.perf_constraint,
+            performance_advice: sync.performance_advice,
+            is_cleanup_job: sync.is_cleanup_job,
+            skipped_symlink_count: sync.skipped_symlink_count,
+            hardlinks_converted_count: None,
+            skipped_special_file_count: None,
+            folders_completed: None,
+            folder_property_transfers: None,
+            symlink_transfers: None,
+            folders_failed: None,
+            folders_skipped: None,
+            total_bytes_enumerated: sync.total_bytes_enumerated,
+        }
+    }
+}
+
+/// Parse a progress message in the shape `operation` reports it
+fn parse_progress(
+    content: &str,
+    operation: AzCopyOperation,
+) -> serde_json::Result<ProgressMessage> {
+    match operation {
+        AzCopyOperation::Sync => {
+            serde_json::from_str::<SyncProgressMessage>(content).map(Into::into)
+        }
+        _ => serde_json::from_str::<ProgressMessage>(content),
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct InitMessage {
@@ -79,33 +186,139 @@ pub struct InitMessage {
     pub is_cleanup_job: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum AzCopyOperation {
     Copy,
+    Sync,
     Remove,
 }
 
-/// Parse and display AzCopy JSON output with a progress bar
-/// Returns the number of failed transfers
-pub async fn handle_azcopy_output<R: AsyncRead + Unpin>(stream: R) -> Result<u32> {
-    handle_azcopy_output_with_operation(stream, AzCopyOperation::Copy).await
+/// A transfer AzCopy gave up on, as listed in the `FailedTransfers` of a
+/// job's final progress message
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FailedTransfer {
+    #[serde(alias = "Src")]
+    pub source: String,
+    #[serde(alias = "Dst")]
+    pub destination: String,
+    /// HTTP status of the last attempt, 0 when AzCopy didn't report one
+    #[serde(alias = "ErrorCode", default)]
+    pub error_code: i32,
+}
+
+/// Failed transfers of a job, written by `--failures-out` so they can be
+/// retried without re-running the whole job
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FailureReport {
+    pub operation: AzCopyOperation,
+    pub failures: Vec<FailedTransfer>,
+}
+
+impl FailureReport {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("{} is not a failures file", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// A tuning hint azcopy attaches to a job's final progress message, e.g.
+/// that the network or the source disk was the bottleneck
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PerformanceAdvice {
+    pub code: String,
+    pub title: String,
+    pub reason: String,
+    /// Whether azcopy ranks this above its other advice
+    #[serde(default)]
+    pub priority_advice: bool,
+}
+
+/// Outcome of an AzCopy job, read from its JSON output
+#[derive(Debug, Default)]
+pub struct JobSummary {
+    pub failed_count: u32,
+    /// Highest share of requests the service answered with 503/429
+    pub server_busy_percent: f64,
+    pub failed_transfers: Vec<FailedTransfer>,
+    /// Transfers azcopy left out, e.g. because the destination already existed
+    pub skipped_transfers: Vec<FailedTransfer>,
+    /// ID azcopy gave the job, to resume it with `azcopy jobs resume`
+    pub job_id: Option<String>,
+    /// Whether the job stopped because it was cancelled
+    pub cancelled: bool,
+    /// What a --dry-run job would transfer or delete
+    pub planned: Vec<PlannedTransfer>,
+    /// Files transferred or removed, once the job completed
+    pub completed_count: u64,
+    pub bytes_transferred: u64,
+    /// Operations per second over the job, as azcopy measured them
+    pub average_iops: f64,
+    pub performance_advice: Vec<PerformanceAdvice>,
+    /// Symbolic links under a local source that azcopy left out
+    pub skipped_links: u64,
+}
+
+/// Percentage progress bar for an AzCopy job, hidden under --quiet and
+/// --deterministic; azcopy has no per-file progress to show instead
+fn new_progress_bar() -> ProgressBar {
+    if crate::logging::is_quiet() || is_deterministic() {
+        return ProgressBar::hidden();
+    }
+    if progress_mode() == ProgressMode::PerFile {
+        static NOTED: std::sync::Once = std::sync::Once::new();
+        NOTED.call_once(|| {
+            status!(
+                "{} azcopy reports progress for the whole job; per-file bars are for transfers azst makes itself",
+                "ℹ".blue()
+            )
+        });
+    }
+    let progress_bar = ProgressBar::new(100);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {percent}% {msg}")
+            .expect("Invalid progress bar template")
+            .progress_chars("#>-"),
+    );
+    progress_bar
 }
 
 /// Parse and display AzCopy JSON output with a progress bar for a specific operation
-/// Returns the number of failed transfers
 pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
     stream: R,
     operation: AzCopyOperation,
-) -> Result<u32> {
+) -> Result<JobSummary> {
     let reader = BufReader::new(stream);
     let mut lines = reader.lines();
     let mut pb: Option<ProgressBar> = None;
     let mut failed_count: u32 = 0;
+    let mut failed_transfers = Vec::new();
+    let mut skipped_transfers = Vec::new();
+    let mut server_busy_percent: f64 = 0.0;
     let mut log_file_location: Option<String> = None;
+    let mut job_id: Option<String> = None;
+    let mut cancelled = false;
+    let mut planned = Vec::new();
+    let mut completed_count: u64 = 0;
+    let mut bytes_transferred: u64 = 0;
+    let mut average_iops: f64 = 0.0;
+    let mut performance_advice = Vec::new();
+    let mut skipped_links: u64 = 0;
+    let mut throughput = Throughput::new();
 
     // Determine the verb to use based on operation
     let verb_past = match operation {
         AzCopyOperation::Copy => "transferred",
+        AzCopyOperation::Sync => "synced",
         AzCopyOperation::Remove => "removed",
     };
 
@@ -117,12 +330,18 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
                     // Print info messages, stripping "INFO: " prefix
                     let msg = entry.message_content.trim();
                     let msg = msg.strip_prefix("INFO: ").unwrap_or(msg);
-                    println!("{} {}", "ℹ".blue(), msg);
+                    // They carry job IDs, log paths and timings that differ per run
+                    if !is_deterministic() {
+                        status!("{} {}", "ℹ".blue(), msg);
+                    }
                 }
                 "Progress" => {
                     // Parse the nested JSON in MessageContent
-                    match serde_json::from_str::<ProgressMessage>(&entry.message_content) {
+                    match parse_progress(&entry.message_content, operation) {
                         Ok(progress) => {
+                            server_busy_percent = server_busy_percent.max(busy_percent(&progress));
+                            cancelled = progress.job_status == "Cancelled";
+
                             // Check if job is completed or completed with errors
                             if progress.job_status == "Completed"
                                 || progress.job_status == "CompletedWithErrors"
@@ -132,53 +351,24 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
                                     pb = None;
                                 }
 
-                                // Print completion summary
-                                let completed = &progress.transfers_completed;
-                                let total = &progress.total_transfers;
-                                let bytes_transferred =
-                                    format_bytes(&progress.total_bytes_transferred);
-                                let failed = &progress.transfers_failed;
-
-                                // Track failed count
-                                failed_count = failed.parse::<u32>().unwrap_or(0);
-
-                                if failed_count > 0 {
-                                    println!(
-                                        "{} {} of {} files {} ({}) - {} failed",
-                                        "⚠".yellow(),
-                                        completed,
-                                        total,
-                                        verb_past,
-                                        bytes_transferred,
-                                        failed
-                                    );
-                                    if let Some(ref log_path) = log_file_location {
-                                        println!("{} Log file: {}", "ℹ".blue(), log_path.dimmed());
-                                    }
-                                } else {
-                                    println!(
-                                        "{} {} files {} ({})",
-                                        "✓".green(),
-                                        completed,
-                                        verb_past,
-                                        bytes_transferred
-                                    );
-                                }
+                                (failed_count, failed_transfers) = report_completion(
+                                    &progress,
+                                    verb_past,
+                                    log_file_location.as_deref(),
+                                    &throughput,
+                                );
+                                skipped_transfers =
+                                    listed_transfers(progress.skipped_transfers.as_ref());
+                                completed_count = progress.transfers_completed.parse().unwrap_or(0);
+                                bytes_transferred = bytes_of(&progress);
+                                average_iops = progress.average_iops.parse().unwrap_or(0.0);
+                                performance_advice = advice_of(&progress);
                                 continue;
                             }
 
                             // Create progress bar on first progress message
                             if pb.is_none() {
-                                let progress_bar = ProgressBar::new(100);
-                                progress_bar.set_style(
-                                ProgressStyle::default_bar()
-                                    .template(
-                                        "{spinner:.green} [{bar:40.cyan/blue}] {percent}% {msg}",
-                                    )
-                                    .expect("Invalid progress bar template")
-                                    .progress_chars("#>-"),
-                            );
-                                pb = Some(progress_bar);
+                                pb = Some(new_progress_bar());
                             }
 
                             // Update progress bar
@@ -186,21 +376,8 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
                                 let percent: f64 = progress.percent_complete.parse().unwrap_or(0.0);
                                 progress_bar.set_position(percent as u64);
 
-                                // Format additional info
-                                let completed = &progress.transfers_completed;
-                                let total = &progress.total_transfers;
-                                let bytes_transferred =
-                                    format_bytes(&progress.total_bytes_transferred);
-                                let bytes_total = format_bytes(&progress.total_bytes_expected);
-
-                                progress_bar.set_message(format!(
-                                    "{}/{} files | {}/{} | {} IOPS",
-                                    completed,
-                                    total,
-                                    bytes_transferred,
-                                    bytes_total,
-                                    progress.average_iops
-                                ));
+                                progress_bar
+                                    .set_message(progress_details(&progress, &mut throughput));
                             }
                         }
                         Err(_e) => {
@@ -219,13 +396,20 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
                     // Job initialization - extract log file location
                     if let Ok(init) = serde_json::from_str::<InitMessage>(&entry.message_content) {
                         log_file_location = Some(init.log_file_location);
+                        job_id = Some(init.job_id);
+                    }
+                }
+                "Dryrun" => {
+                    // Collected to print as a plan once the job ends
+                    match parse_dry_run_message(&entry.message_content) {
+                        Some(transfer) => planned.push(transfer),
+                        None => println!("{}", entry.message_content),
                     }
                 }
                 "EndOfJob" => {
                     // End of job message - parse to show final status
-                    if let Ok(_progress) =
-                        serde_json::from_str::<ProgressMessage>(&entry.message_content)
-                    {
+                    if let Ok(progress) = parse_progress(&entry.message_content, operation) {
+                        cancelled = progress.job_status == "Cancelled";
                         if let Some(ref progress_bar) = pb {
                             progress_bar.finish_and_clear();
                             pb = None;
@@ -236,11 +420,13 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
                 }
                 _ => {
                     // Unknown message type, print as-is
-                    println!("{}", entry.message_content);
+                    status!("{}", entry.message_content);
                 }
             }
-        } else if let Ok(progress) = serde_json::from_str::<ProgressMessage>(&line) {
+        } else if let Ok(progress) = parse_progress(&line, operation) {
             // Sometimes AzCopy prints raw ProgressMessage JSON without wrapper
+            server_busy_percent = server_busy_percent.max(busy_percent(&progress));
+            cancelled = progress.job_status == "Cancelled";
 
             // Check if job is completed or completed with errors
             if progress.job_status == "Completed" || progress.job_status == "CompletedWithErrors" {
@@ -249,48 +435,28 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
                     pb = None;
                 }
 
-                // Print completion summary
-                let completed = &progress.transfers_completed;
-                let total = &progress.total_transfers;
-                let bytes_transferred = format_bytes(&progress.total_bytes_transferred);
-                let failed = &progress.transfers_failed;
-
-                // Track failed count
-                failed_count = failed.parse::<u32>().unwrap_or(0);
-
-                if failed_count > 0 {
-                    println!(
-                        "{} {} of {} files transferred ({}) - {} failed",
-                        "⚠".yellow(),
-                        completed,
-                        total,
-                        bytes_transferred,
-                        failed
-                    );
-                    if let Some(ref log_path) = log_file_location {
-                        println!("{} Log file: {}", "ℹ".blue(), log_path.dimmed());
-                    }
-                } else {
-                    println!(
-                        "{} {} files transferred ({})",
-                        "✓".green(),
-                        completed,
-                        bytes_transferred
-                    );
-                }
+                (failed_count, failed_transfers) = report_completion(
+                    &progress,
+                    verb_past,
+                    log_file_location.as_deref(),
+                    &throughput,
+                );
+                skipped_transfers = listed_transfers(progress.skipped_transfers.as_ref());
+                completed_count = progress.transfers_completed.parse().unwrap_or(0);
+                bytes_transferred = bytes_of(&progress);
+                average_iops = progress.average_iops.parse().unwrap_or(0.0);
+                performance_advice = advice_of(&progress);
+                skipped_links = progress
+                    .skipped_symlink_count
+                    .as_deref()
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or(0);
                 continue;
             }
 
             // Create progress bar on first progress message
             if pb.is_none() {
-                let progress_bar = ProgressBar::new(100);
-                progress_bar.set_style(
-                    ProgressStyle::default_bar()
-                        .template("{spinner:.green} [{bar:40.cyan/blue}] {percent}% {msg}")
-                        .expect("Invalid progress bar template")
-                        .progress_chars("#>-"),
-                );
-                pb = Some(progress_bar);
+                pb = Some(new_progress_bar());
             }
 
             // Update progress bar
@@ -298,16 +464,7 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
                 let percent: f64 = progress.percent_complete.parse().unwrap_or(0.0);
                 progress_bar.set_position(percent as u64);
 
-                // Format additional info
-                let completed = &progress.transfers_completed;
-                let total = &progress.total_transfers;
-                let bytes_transferred = format_bytes(&progress.total_bytes_transferred);
-                let bytes_total = format_bytes(&progress.total_bytes_expected);
-
-                progress_bar.set_message(format!(
-                    "{}/{} files | {}/{} | {} IOPS",
-                    completed, total, bytes_transferred, bytes_total, progress.average_iops
-                ));
+                progress_bar.set_message(progress_details(&progress, &mut throughput));
             }
         }
     }
@@ -317,7 +474,261 @@ pub async fn handle_azcopy_output_with_operation<R: AsyncRead + Unpin>(
         progress_bar.finish_and_clear();
     }
 
-    Ok(failed_count)
+    Ok(JobSummary {
+        failed_count,
+        server_busy_percent,
+        failed_transfers,
+        skipped_transfers,
+        job_id,
+        cancelled,
+        planned,
+        completed_count,
+        bytes_transferred,
+        average_iops,
+        performance_advice,
+        skipped_links,
+    })
+}
+
+/// Transfer rate and ETA estimated from successive progress samples
+struct Throughput {
+    started: Instant,
+    last_sample: Option<(Instant, u64)>,
+    /// Exponentially smoothed bytes per second
+    rate: Option<f64>,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_sample: None,
+            rate: None,
+        }
+    }
+
+    /// Record that `bytes` have been transferred so far
+    fn record(&mut self, bytes: u64) {
+        self.record_at(Instant::now(), bytes);
+    }
+
+    fn record_at(&mut self, now: Instant, bytes: u64) {
+        if let Some((then, previous)) = self.last_sample {
+            let seconds = now.duration_since(then).as_secs_f64();
+            if seconds <= 0.0 {
+                return;
+            }
+            let sample = bytes.saturating_sub(previous) as f64 / seconds;
+            self.rate = Some(match self.rate {
+                Some(rate) => rate + RATE_SMOOTHING * (sample - rate),
+                None => sample,
+            });
+        }
+        self.last_sample = Some((now, bytes));
+    }
+
+    /// Time left to move `remaining` bytes at the current rate
+    fn eta(&self, remaining: u64) -> Option<Duration> {
+        self.rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    /// ", in 1m 05s at 12.3 MB/s" for the completion line, empty under
+    /// --deterministic since timings differ per run
+    fn summary(&self, bytes: u64) -> String {
+        if is_deterministic() {
+            return String::new();
+        }
+        let elapsed = self.started.elapsed();
+        let seconds = elapsed.as_secs_f64();
+        if seconds <= 0.0 {
+            return String::new();
+        }
+        format!(
+            ", in {} at {}/s",
+            format_duration(elapsed),
+            format_size((bytes as f64 / seconds) as u64)
+        )
+    }
+}
+
+/// Files, bytes, rate, ETA and IOPS shown next to the progress bar
+fn progress_details(progress: &ProgressMessage, throughput: &mut Throughput) -> String {
+    let transferred = bytes_of(progress);
+    throughput.record(transferred);
+
+    let mut details = format!(
+        "{}/{} files | {}/{}",
+        progress.transfers_completed,
+        progress.total_transfers,
+        format_bytes(&progress.total_bytes_transferred),
+        format_bytes(&progress.total_bytes_expected)
+    );
+    if let Some(rate) = throughput.rate {
+        details.push_str(&format!(" | {}/s", format_size(rate as u64)));
+    }
+    let expected = progress.total_bytes_expected.parse::<u64>().unwrap_or(0);
+    if let Some(eta) = throughput.eta(expected.saturating_sub(transferred)) {
+        details.push_str(&format!(" | ETA {}", format_duration(eta)));
+    }
+    details.push_str(&format!(" | {} IOPS", progress.average_iops));
+    details
+}
+
+fn bytes_of(progress: &ProgressMessage) -> u64 {
+    progress.total_bytes_transferred.parse().unwrap_or(0)
+}
+
+/// Format a duration as "1h 02m", "3m 05s" or "12s"
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Print the summary line of a finished job, followed by each failed
+/// transfer, and return the failures
+fn report_completion(
+    progress: &ProgressMessage,
+    verb_past: &str,
+    log_file_location: Option<&str>,
+    throughput: &Throughput,
+) -> (u32, Vec<FailedTransfer>) {
+    let completed = &progress.transfers_completed;
+    let total = &progress.total_transfers;
+    let bytes_transferred = format!(
+        "{}{}",
+        format_bytes(&progress.total_bytes_transferred),
+        throughput.summary(bytes_of(progress))
+    );
+    let failed_count = progress.transfers_failed.parse::<u32>().unwrap_or(0);
+    let failures = failed_transfers(progress);
+
+    // Under --quiet the command's summary line reports the failures instead
+    if failed_count > 0 && !crate::logging::is_quiet() {
+        println!(
+            "{} {} of {} files {} ({}) - {} failed",
+            "⚠".yellow(),
+            completed,
+            total,
+            verb_past,
+            bytes_transferred,
+            failed_count
+        );
+        for failure in &failures {
+            if failure.error_code != 0 {
+                println!(
+                    "  {} {} (error {})",
+                    "×".red(),
+                    failure.source,
+                    failure.error_code
+                );
+            } else {
+                println!("  {} {}", "×".red(), failure.source);
+            }
+        }
+        if let Some(log_path) = log_file_location.filter(|_| !is_deterministic()) {
+            status!("{} Log file: {}", "ℹ".blue(), log_path.dimmed());
+        }
+    } else if failed_count == 0 {
+        status!(
+            "{} {} files {} ({})",
+            "✓".green(),
+            completed,
+            verb_past,
+            bytes_transferred
+        );
+    }
+    // Timings and bottlenecks differ per run
+    if !is_deterministic() {
+        for line in advice_lines(progress) {
+            status!("{}", line);
+        }
+    }
+    (failed_count, failures)
+}
+
+/// What limited a job, by the `PerfConstraint` azcopy reports
+fn constraint_name(perf_constraint: Option<i32>) -> Option<&'static str> {
+    match perf_constraint? {
+        1 => Some("the local disk"),
+        2 => Some("the storage service"),
+        3 => Some("page blob throughput limits of the service"),
+        4 => Some("CPU"),
+        _ => None,
+    }
+}
+
+/// Lines describing what limited a finished job and azcopy's advice for
+/// the next one; none when it had nothing to say
+fn advice_lines(progress: &ProgressMessage) -> Vec<String> {
+    let constraint = constraint_name(progress.perf_constraint);
+    let advice = advice_of(progress);
+    if constraint.is_none() && advice.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = vec![format!("{} Performance advice:", "ℹ".blue())];
+    if let Some(constraint) = constraint {
+        lines.push(format!(
+            "  {} Throughput was limited by {}",
+            "•".yellow(),
+            constraint
+        ));
+    }
+    for advice in advice {
+        lines.push(format!(
+            "  {} {}: {}",
+            "•".yellow(),
+            advice.title,
+            advice.reason
+        ));
+    }
+    lines
+}
+
+/// Failed transfers listed in a final progress message, with blob URLs
+/// turned back into az:// URIs
+fn failed_transfers(progress: &ProgressMessage) -> Vec<FailedTransfer> {
+    listed_transfers(progress.failed_transfers.as_ref())
+}
+
+/// Transfers listed under FailedTransfers or SkippedTransfers, which share
+/// a layout
+fn listed_transfers(value: Option<&Value>) -> Vec<FailedTransfer> {
+    let Some(value) = value else {
+        return Vec::new();
+    };
+    let mut transfers: Vec<FailedTransfer> =
+        serde_json::from_value(value.clone()).unwrap_or_default();
+    for transfer in &mut transfers {
+        for location in [&mut transfer.source, &mut transfer.destination] {
+            if let Some(uri) = convert_url_to_az_uri(location) {
+                *location = uri;
+            }
+        }
+    }
+    transfers
+}
+
+/// Performance advice of a final progress message, priority advice first
+fn advice_of(progress: &ProgressMessage) -> Vec<PerformanceAdvice> {
+    let mut advice: Vec<PerformanceAdvice> = progress
+        .performance_advice
+        .clone()
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default();
+    advice.sort_by_key(|advice| !advice.priority_advice);
+    advice
+}
+
+fn busy_percent(progress: &ProgressMessage) -> f64 {
+    progress.server_busy_percentage.parse().unwrap_or(0.0)
 }
 
 /// Format bytes into human-readable format
@@ -337,3 +748,125 @@ fn format_bytes(bytes_str: &str) -> String {
         bytes_str.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_rate_and_eta() {
+        let mut throughput = Throughput::new();
+        let start = Instant::now();
+        throughput.record_at(start, 0);
+        assert_eq!(throughput.eta(100), None);
+
+        throughput.record_at(start + Duration::from_secs(2), 2_000_000);
+        assert_eq!(throughput.rate, Some(1_000_000.0));
+        assert_eq!(throughput.eta(3_000_000), Some(Duration::from_secs(3)));
+
+        // A stalled sample pulls the rate down gradually
+        throughput.record_at(start + Duration::from_secs(3), 2_000_000);
+        assert_eq!(throughput.rate, Some(700_000.0));
+    }
+
+    #[test]
+    fn test_failed_transfers() {
+        let mut message = serde_json::json!({
+            "ErrorMsg": "", "JobID": "1", "ActiveConnections": "0",
+            "CompleteJobOrdered": true, "JobStatus": "CompletedWithErrors",
+            "TotalTransfers": "2", "FileTransfers": "2", "TransfersCompleted": "1",
+            "TransfersFailed": "1", "TransfersSkipped": "0", "BytesOverWire": "0",
+            "TotalBytesTransferred": "0", "TotalBytesExpected": "0",
+            "PercentComplete": "100", "AverageIOPS": "0", "AverageE2EMilliseconds": "0",
+            "ServerBusyPercentage": "0", "NetworkErrorPercentage": "0",
+        });
+        message["FailedTransfers"] = serde_json::json!([{
+            "Src": "/data/a b.txt",
+            "Dst": "https://acct.blob.core.windows.net/data/a%20b.txt?sv=redacted",
+            "TransferStatus": "Failed",
+            "ErrorCode": 403,
+        }]);
+        let progress: ProgressMessage = serde_json::from_value(message).unwrap();
+
+        assert_eq!(
+            failed_transfers(&progress),
+            vec![FailedTransfer {
+                source: "/data/a b.txt".to_string(),
+                destination: "az://acct/data/a%20b.txt".to_string(),
+                error_code: 403,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_performance_advice() {
+        let content = r#"{"ErrorMsg": "", "JobID": "1", "ActiveConnections": "0",
+            "CompleteJobOrdered": true, "JobStatus": "Completed",
+            "TotalTransfers": "1", "FileTransfers": "1", "TransfersCompleted": "1",
+            "TransfersFailed": "0", "TransfersSkipped": "0", "BytesOverWire": "0",
+            "TotalBytesTransferred": "0", "TotalBytesExpected": "0",
+            "PercentComplete": "100", "AverageIOPS": "52", "AverageE2EMilliseconds": "0",
+            "ServerBusyPercentage": "0", "NetworkErrorPercentage": "0",
+            "PerformanceAdvice": [
+                {"Code": "FileShareOrNetwork", "Title": "Throughput may have been limited",
+                 "Reason": "The network may have been the bottleneck", "PriorityAdvice": false},
+                {"Code": "ConcurrencyNotEnoughTime", "Title": "Not enough time",
+                 "Reason": "Run for longer to tune concurrency", "PriorityAdvice": true}
+            ]}"#;
+        let progress = parse_progress(content, AzCopyOperation::Copy).unwrap();
+
+        let advice = advice_of(&progress);
+        assert_eq!(advice.len(), 2);
+        assert_eq!(advice[0].code, "ConcurrencyNotEnoughTime");
+        assert_eq!(advice[1].reason, "The network may have been the bottleneck");
+
+        let lines = advice_lines(&progress);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains("Not enough time: Run for longer to tune concurrency"));
+    }
+
+    #[test]
+    fn test_advice_lines_with_constraint_only() {
+        let mut progress = parse_progress(
+            r#"{"ErrorMsg": "", "JobID": "1", "ActiveConnections": "0",
+            "CompleteJobOrdered": true, "JobStatus": "Completed",
+            "TotalTransfers": "1", "FileTransfers": "1", "TransfersCompleted": "1",
+            "TransfersFailed": "0", "TransfersSkipped": "0", "BytesOverWire": "0",
+            "TotalBytesTransferred": "0", "TotalBytesExpected": "0",
+            "PercentComplete": "100", "AverageIOPS": "0", "AverageE2EMilliseconds": "0",
+            "ServerBusyPercentage": "0", "NetworkErrorPercentage": "0"}"#,
+            AzCopyOperation::Copy,
+        )
+        .unwrap();
+        assert!(advice_lines(&progress).is_empty());
+
+        progress.perf_constraint = Some(1);
+        let lines = advice_lines(&progress);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains("limited by the local disk"));
+    }
+
+    #[test]
+    fn test_sync_progress_message() {
+        let content = r#"{"JobID": "1", "JobStatus": "InProgress",
+            "CopyTotalTransfers": "3", "CopyTransfersCompleted": "1", "CopyTransfersFailed": "0",
+            "DeleteTotalTransfers": "1", "DeleteTransfersCompleted": "1", "DeleteTransfersFailed": "0",
+            "BytesOverWire": "2048", "TotalBytesEnumerated": "8192"}"#;
+        let progress = parse_progress(content, AzCopyOperation::Sync).unwrap();
+
+        assert_eq!(progress.total_transfers, "4");
+        assert_eq!(progress.transfers_completed, "2");
+        assert_eq!(progress.percent_complete, "50.0");
+        assert_eq!(bytes_of(&progress), 2048);
+        assert_eq!(progress.total_bytes_expected, "8192");
+        // Copy progress has a different shape
+        assert!(parse_progress(content, AzCopyOperation::Copy).is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(12)), "12s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h 02m");
+    }
+}
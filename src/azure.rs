@@ -1,14 +1,47 @@
 use anyhow::{anyhow, Context, Result};
-use serde::Deserialize;
-use std::path::PathBuf;
-use std::sync::Arc;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use tokio::process::Command as AsyncCommand;
+use tokio_util::sync::CancellationToken;
 
 use azure_core::auth::{AccessToken, TokenCredential};
 use azure_core::error::Error as AzureError;
+use azure_core::request_options::{LeaseDuration, LeaseId, NextMarker};
+use azure_core::StatusCode;
+use azure_core::{ExponentialRetryOptions, RetryOptions, TransportOptions, Url};
+use azure_mgmt_storage::models::immutability_policy_property::State as PolicyState;
+use azure_mgmt_storage::models::{
+    DeleteRetentionPolicy, ImmutabilityPolicy, ImmutabilityPolicyProperty, LegalHold,
+    ManagementPolicy, ManagementPolicyProperties, ManagementPolicySchema,
+};
+use azure_storage::shared_access_signature::service_sas::{
+    BlobSasPermissions, BlobSharedAccessSignature, BlobSignedResource, UserDeligationKey,
+};
+use azure_storage::shared_access_signature::SasToken;
 use azure_storage::StorageCredentials;
+use azure_storage_blobs::blob::CopyStatus;
 use azure_storage_blobs::prelude::*;
+use futures::stream::BoxStream;
 use futures::StreamExt;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::audit_log;
+use crate::azcopy_output::{AzCopyOperation, JobSummary};
+use crate::bandwidth::{follow_job, run_job, BandwidthSchedule};
+use crate::cancel;
+use crate::checksum::to_hex;
+use crate::config::{self, Config, RetryConfig, TransferConfig};
+use crate::error::AzstError;
+use crate::login::{LoginCredential, StoredLogin, STORAGE_SCOPE};
+use crate::network;
+use crate::output;
+use crate::symlinks::{self, SymlinkPolicy};
+use crate::throttle::{account_from_url, apply_learned_concurrency, record_server_busy};
+use crate::utils::{matches_pattern, native_path, write_private_file};
 
 // ============================================================================
 // Azure ML MSI Credential - Custom credential for Azure ML Compute Instances
@@ -53,7 +86,8 @@ impl TokenCredential for AzureMLMsiCredential {
             self.endpoint, resource
         );
 
-        let client = reqwest::Client::new();
+        let client = network::http_client()
+            .map_err(|e| AzureError::new(azure_core::error::ErrorKind::Credential, e))?;
         let response = client
             .get(&url)
             .header("secret", &self.secret)
@@ -100,6 +134,295 @@ impl TokenCredential for AzureMLMsiCredential {
     }
 }
 
+// ============================================================================
+// Refreshing Credential - Keeps tokens valid through multi-hour jobs
+// ============================================================================
+
+/// Tokens are renewed this long before they expire
+const TOKEN_REFRESH_MARGIN: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Shortest wait between background refreshes, so a credential that hands
+/// out short-lived tokens (or fails) isn't polled in a tight loop
+const MIN_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+type TokenCache = std::sync::Mutex<std::collections::HashMap<Vec<String>, AccessToken>>;
+
+/// Wraps a credential so its tokens are cached and renewed in the background
+/// ahead of expiry
+///
+/// The SDK only refreshes a token when a request finds it about to expire,
+/// stalling the transfer while `az` runs and occasionally letting a token
+/// expire mid-request on 10+ hour jobs. Here the first request for a scope
+/// starts a task that renews the token `TOKEN_REFRESH_MARGIN` before it
+/// expires, for as long as the credential is in use.
+#[derive(Debug)]
+struct RefreshingCredential {
+    inner: Arc<dyn TokenCredential>,
+    tokens: Arc<TokenCache>,
+}
+
+impl RefreshingCredential {
+    fn new(inner: Arc<dyn TokenCredential>) -> Self {
+        Self {
+            inner,
+            tokens: Arc::new(TokenCache::default()),
+        }
+    }
+
+    /// Renew the token for `scopes` shortly before each expiry until the
+    /// credential is dropped
+    fn spawn_refresher(&self, scopes: Vec<String>) {
+        let inner = self.inner.clone();
+        let tokens = Arc::downgrade(&self.tokens);
+
+        tokio::spawn(async move {
+            loop {
+                let expires_on = match tokens.upgrade() {
+                    Some(tokens) => tokens.lock().unwrap().get(&scopes).map(|t| t.expires_on),
+                    None => return,
+                };
+                let Some(expires_on) = expires_on else {
+                    return;
+                };
+                tokio::time::sleep(refresh_delay(expires_on, time::OffsetDateTime::now_utc()))
+                    .await;
+
+                let scope_refs: Vec<&str> = scopes.iter().map(String::as_str).collect();
+                // A failed refresh keeps the old token; requests fall back to
+                // fetching one themselves once it is within the margin
+                if let Ok(token) = inner.get_token(&scope_refs).await {
+                    match tokens.upgrade() {
+                        Some(tokens) => {
+                            tokens.lock().unwrap().insert(scopes.clone(), token);
+                        }
+                        None => return,
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// How long to wait before renewing a token that expires at `expires_on`
+fn refresh_delay(
+    expires_on: time::OffsetDateTime,
+    now: time::OffsetDateTime,
+) -> std::time::Duration {
+    let remaining = std::time::Duration::try_from(expires_on - now).unwrap_or_default();
+    remaining
+        .saturating_sub(TOKEN_REFRESH_MARGIN)
+        .max(MIN_REFRESH_INTERVAL)
+}
+
+/// Whether a token is too close to expiry to hand out
+fn needs_refresh(token: &AccessToken, now: time::OffsetDateTime) -> bool {
+    token.expires_on - now < TOKEN_REFRESH_MARGIN
+}
+
+#[async_trait::async_trait]
+impl TokenCredential for RefreshingCredential {
+    async fn get_token(&self, scopes: &[&str]) -> Result<AccessToken, AzureError> {
+        let key: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        let now = time::OffsetDateTime::now_utc();
+
+        let cached = self.tokens.lock().unwrap().get(&key).cloned();
+        if let Some(token) = &cached {
+            if !needs_refresh(token, now) {
+                return Ok(token.clone());
+            }
+        }
+
+        let token = self.inner.get_token(scopes).await?;
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(key.clone(), token.clone());
+        if cached.is_none() {
+            self.spawn_refresher(key);
+        }
+        Ok(token)
+    }
+
+    async fn clear_cache(&self) -> Result<(), AzureError> {
+        self.tokens.lock().unwrap().clear();
+        self.inner.clear_cache().await
+    }
+}
+
+// ============================================================================
+// Persistent Token Cache - Reuses tokens across invocations
+// ============================================================================
+
+/// A token as stored in the cache file
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredToken {
+    token: String,
+    /// Expiry as a Unix timestamp (seconds)
+    expires_on: i64,
+}
+
+/// Credential identity and space-separated scopes -> token
+type StoredTokens = std::collections::HashMap<String, StoredToken>;
+
+/// Location of the token cache, readable only by the current user
+fn token_cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("azst").join("tokens.json"))
+}
+
+/// The credential resolved by the first client, reused by every client
+/// after it so a process (and each command of `azst shell`) resolves the
+/// credential chain once
+static SHARED_CREDENTIAL: Mutex<Option<Arc<dyn TokenCredential>>> = Mutex::new(None);
+
+/// The subscription `az account show` reported, which takes seconds to ask
+static CLI_SUBSCRIPTION: Mutex<Option<String>> = Mutex::new(None);
+
+/// Remove cached tokens, returning whether there were any
+///
+/// The credential and subscription kept in memory are dropped as well, so
+/// the next client picks up a new sign-in.
+pub fn clear_token_cache() -> Result<bool> {
+    if let Ok(mut credential) = SHARED_CREDENTIAL.lock() {
+        *credential = None;
+    }
+    if let Ok(mut subscription) = CLI_SUBSCRIPTION.lock() {
+        *subscription = None;
+    }
+    match token_cache_path() {
+        Some(path) if path.exists() => {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Wraps a credential so tokens outlive the process that acquired them
+///
+/// Acquiring a token through the Azure CLI spawns `az`, which adds seconds to
+/// every command. Tokens are written to a file only the current user can read
+/// and handed out until they are within `TOKEN_REFRESH_MARGIN` of expiry.
+/// Tokens are kept per `identity`, so a token acquired as one principal is
+/// never handed out after switching to another.
+#[derive(Debug)]
+struct PersistentCredential {
+    inner: Arc<dyn TokenCredential>,
+    /// Who `inner` signs in as: its source, tenant and client or user
+    identity: String,
+    path: Option<PathBuf>,
+}
+
+impl PersistentCredential {
+    fn new(inner: Arc<dyn TokenCredential>, identity: String) -> Self {
+        Self {
+            inner,
+            identity,
+            path: token_cache_path(),
+        }
+    }
+
+    /// A missing or unreadable cache is treated as empty
+    fn load(&self) -> StoredTokens {
+        self.path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("No cache directory"))?;
+        write_private_file(path, &serde_json::to_string(tokens)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenCredential for PersistentCredential {
+    async fn get_token(&self, scopes: &[&str]) -> Result<AccessToken, AzureError> {
+        let key = format!("{}|{}", self.identity, scopes.join(" "));
+        let now = time::OffsetDateTime::now_utc();
+
+        let mut tokens = self.load();
+        let cached = tokens.get(&key).and_then(|stored| {
+            let expires_on = time::OffsetDateTime::from_unix_timestamp(stored.expires_on).ok()?;
+            Some(AccessToken::new(stored.token.clone(), expires_on))
+        });
+        if let Some(token) = cached.filter(|token| !needs_refresh(token, now)) {
+            return Ok(token);
+        }
+
+        let token = self.inner.get_token(scopes).await?;
+        tokens.retain(|_, stored| stored.expires_on > now.unix_timestamp());
+        tokens.insert(
+            key,
+            StoredToken {
+                token: token.token.secret().to_string(),
+                expires_on: token.expires_on.unix_timestamp(),
+            },
+        );
+        if let Err(e) = self.save(&tokens) {
+            tracing::debug!("Failed to save token cache: {:#}", e);
+        }
+        Ok(token)
+    }
+
+    async fn clear_cache(&self) -> Result<(), AzureError> {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+        self.inner.clear_cache().await
+    }
+}
+
+// ============================================================================
+// Retry Policy - Exponential backoff for SDK requests
+// ============================================================================
+
+/// `--retries` from the command line, which wins over the config file
+static MAX_RETRIES_OVERRIDE: OnceLock<u32> = OnceLock::new();
+
+/// Override the number of retries for every SDK client created afterwards
+pub fn set_max_retries(retries: u32) {
+    let _ = MAX_RETRIES_OVERRIDE.set(retries);
+}
+
+/// Retry options for SDK clients, from `[retry]` in the config file and `--retries`
+///
+/// Timeouts, connection errors, 408, 429 and 5xx responses are retried. When a
+/// throttled response carries Retry-After, the SDK waits at least that long
+/// before the next attempt.
+fn retry_options() -> Result<RetryOptions> {
+    let settings = Config::load()?.retry;
+    Ok(RetryOptions::exponential(exponential_retry_options(
+        &settings,
+        MAX_RETRIES_OVERRIDE.get().copied(),
+    )))
+}
+
+fn exponential_retry_options(
+    settings: &RetryConfig,
+    max_retries: Option<u32>,
+) -> ExponentialRetryOptions {
+    let mut options = ExponentialRetryOptions::default();
+    if let Some(retries) = max_retries.or(settings.max_retries) {
+        options = options.max_retries(retries);
+    }
+    if let Some(ms) = settings.initial_delay_ms {
+        options = options.initial_delay(Duration::from_millis(ms));
+    }
+    if let Some(secs) = settings.max_delay_secs {
+        options = options.max_delay(Duration::from_secs(secs));
+    }
+    if let Some(secs) = settings.max_elapsed_secs {
+        options = options.max_total_elapsed(Duration::from_secs(secs));
+    }
+    options
+}
+
 // ============================================================================
 // AzCopy Configuration
 // ============================================================================
@@ -111,16 +434,303 @@ pub const AZCOPY_PINNED_VERSION: &str = "10.30.1";
 // AzCopy Options - Common options for azcopy operations
 // ============================================================================
 
+/// What to do when a copy destination already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OverwritePolicy {
+    /// Always replace existing files
+    #[default]
+    Always,
+    /// Never replace existing files
+    Never,
+    /// Replace only if the source is newer than the destination
+    IfNewer,
+    /// Ask before replacing each existing file (local copies only)
+    Prompt,
+}
+
+impl OverwritePolicy {
+    /// Value accepted by azcopy's --overwrite flag
+    pub fn as_azcopy_value(&self) -> &'static str {
+        match self {
+            OverwritePolicy::Always => "true",
+            OverwritePolicy::Never => "false",
+            OverwritePolicy::IfNewer => "ifSourceNewer",
+            OverwritePolicy::Prompt => "prompt",
+        }
+    }
+}
+
+/// How to react when a transferred file's MD5 doesn't match the blob's Content-MD5
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Md5Check {
+    /// Don't verify hashes
+    Never,
+    /// Report mismatches but don't fail
+    Log,
+    /// Fail the copy when hashes differ
+    Fail,
+}
+
+impl Md5Check {
+    /// Value accepted by azcopy's --check-md5 flag
+    pub fn as_azcopy_value(&self) -> &'static str {
+        match self {
+            Md5Check::Never => "NoCheck",
+            Md5Check::Log => "LogOnly",
+            Md5Check::Fail => "FailIfDifferent",
+        }
+    }
+}
+
+/// Access tier for uploaded block blobs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BlobTier {
+    Hot,
+    Cool,
+    Archive,
+}
+
+impl BlobTier {
+    /// Value accepted by azcopy's --block-blob-tier flag
+    pub fn as_azcopy_value(&self) -> &'static str {
+        match self {
+            BlobTier::Hot => "Hot",
+            BlobTier::Cool => "Cool",
+            BlobTier::Archive => "Archive",
+        }
+    }
+}
+
+/// Kind of blob an upload creates; without one, azcopy makes page blobs of
+/// .vhd and .vhdx files and block blobs of the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BlobType {
+    Block,
+    /// Random-access 512-byte pages, as disks (VHDs) are stored
+    Page,
+    /// Blocks that can only be added at the end, for logs
+    Append,
+}
+
+impl BlobType {
+    /// Value accepted by azcopy's --blob-type flag
+    pub fn as_azcopy_value(&self) -> &'static str {
+        match self {
+            BlobType::Block => "BlockBlob",
+            BlobType::Page => "PageBlob",
+            BlobType::Append => "AppendBlob",
+        }
+    }
+}
+
+/// Page blobs are written and sized in pages of this many bytes
+pub const PAGE_SIZE: u64 = 512;
+
+/// HTTP headers, metadata and tier to set on blobs written by a copy
+#[derive(Debug, Clone, Default)]
+pub struct BlobHeaders {
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_encoding: Option<String>,
+    pub metadata: Vec<(String, String)>,
+    pub tier: Option<BlobTier>,
+}
+
+impl BlobHeaders {
+    pub fn is_empty(&self) -> bool {
+        self.content_type.is_none()
+            && self.cache_control.is_none()
+            && self.content_encoding.is_none()
+            && self.metadata.is_empty()
+            && self.tier.is_none()
+    }
+
+    /// Arguments for azcopy copy that set these headers
+    pub fn azcopy_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(content_type) = &self.content_type {
+            args.push(format!("--content-type={}", content_type));
+        }
+
+        if let Some(cache_control) = &self.cache_control {
+            args.push(format!("--cache-control={}", cache_control));
+        }
+
+        if let Some(content_encoding) = &self.content_encoding {
+            args.push(format!("--content-encoding={}", content_encoding));
+        }
+
+        if !self.metadata.is_empty() {
+            let pairs: Vec<String> = self
+                .metadata
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            args.push(format!("--metadata={}", pairs.join(";")));
+        }
+
+        if let Some(tier) = &self.tier {
+            args.push(format!("--block-blob-tier={}", tier.as_azcopy_value()));
+        }
+
+        args
+    }
+}
+
+/// An AES-256 key for blobs encrypted with a customer-provided key (CPK)
+///
+/// Azure keeps only the key's SHA-256 hash, so the key has to accompany every
+/// request that writes or reads the content of such a blob.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CustomerKey {
+    /// The key, base64-encoded
+    key: String,
+    /// SHA-256 hash of the key, base64-encoded
+    sha256: String,
+}
+
+impl std::str::FromStr for CustomerKey {
+    type Err = String;
+
+    /// Parse a base64-encoded 256-bit key
+    fn from_str(value: &str) -> Result<Self, String> {
+        use base64::engine::general_purpose::STANDARD;
+        use base64::Engine;
+        use sha2::{Digest, Sha256};
+
+        let key = STANDARD
+            .decode(value.trim())
+            .map_err(|_| "invalid customer key: expected base64".to_string())?;
+        if key.len() != 32 {
+            return Err(format!(
+                "invalid customer key: expected 32 bytes (AES-256), got {}",
+                key.len()
+            ));
+        }
+        Ok(Self {
+            key: STANDARD.encode(&key),
+            sha256: STANDARD.encode(Sha256::digest(&key)),
+        })
+    }
+}
+
+/// Only the hash is shown, so keys don't end up in debug logs
+impl std::fmt::Debug for CustomerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CustomerKey(sha256={})", self.sha256)
+    }
+}
+
+impl CustomerKey {
+    /// Request headers that present the key
+    fn headers(&self) -> [(&'static str, &str); 3] {
+        [
+            ("x-ms-encryption-key", &self.key),
+            ("x-ms-encryption-key-sha256", &self.sha256),
+            ("x-ms-encryption-algorithm", "AES256"),
+        ]
+    }
+
+    fn cpk_info(&self) -> CPKInfo {
+        CPKInfo::from((self.key.clone(), self.sha256.clone()))
+    }
+
+    /// Hand the key to azcopy, which reads it from its environment when
+    /// given --cpk-by-value (and again when resuming the job)
+    fn apply_env(&self, cmd: &mut AsyncCommand) {
+        cmd.env("CPK_ENCRYPTION_KEY", &self.key);
+        cmd.env("CPK_ENCRYPTION_KEY_SHA256", &self.sha256);
+    }
+}
+
+/// Filters on paths relative to the root of a copy or sync, on top of the
+/// file name patterns. Each is a `;`-separated list, as azcopy takes them.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilters {
+    /// Files or directories to transfer, e.g. `images;docs/a.txt`
+    pub include_path: Option<String>,
+    /// Files or directories to skip, e.g. `node_modules;.git`
+    pub exclude_path: Option<String>,
+    pub include_regex: Option<String>,
+    pub exclude_regex: Option<String>,
+}
+
+impl PathFilters {
+    pub fn is_empty(&self) -> bool {
+        self.include_path.is_none()
+            && self.exclude_path.is_none()
+            && self.include_regex.is_none()
+            && self.exclude_regex.is_none()
+    }
+
+    /// Whether a relative path passes the filters, for transfers azst
+    /// plans itself rather than leaving to azcopy
+    pub fn matches(&self, relative: &str) -> bool {
+        let under_any = |paths: &str| {
+            paths
+                .split(';')
+                .map(|path| path.trim_matches('/'))
+                .filter(|path| !path.is_empty())
+                .any(|path| {
+                    relative == path
+                        || relative
+                            .strip_prefix(path)
+                            .is_some_and(|rest| rest.starts_with('/'))
+                })
+        };
+        let matches_any = |patterns: &str| {
+            patterns.split(';').any(|pattern| {
+                regex::Regex::new(pattern).is_ok_and(|regex| regex.is_match(relative))
+            })
+        };
+
+        self.include_path.as_deref().is_none_or(under_any)
+            && !self.exclude_path.as_deref().is_some_and(under_any)
+            && self.include_regex.as_deref().is_none_or(matches_any)
+            && !self.exclude_regex.as_deref().is_some_and(matches_any)
+    }
+}
+
 /// Options for azcopy copy operations
 #[derive(Debug, Clone, Default)]
 pub struct AzCopyOptions {
     pub recursive: bool,
     pub dry_run: bool,
     pub cap_mbps: Option<f64>,
+    /// Rate limits by time of day, overriding `cap_mbps` within its windows
+    pub bandwidth_schedule: Option<BandwidthSchedule>,
     pub block_size_mb: Option<f64>,
     pub put_md5: bool,
     pub include_pattern: Option<String>,
     pub exclude_pattern: Option<String>,
+    pub include_path: Option<String>,
+    pub exclude_path: Option<String>,
+    pub include_regex: Option<String>,
+    pub exclude_regex: Option<String>,
+    pub overwrite: Option<OverwritePolicy>,
+    pub preserve_properties: bool,
+    pub preserve_last_modified_time: bool,
+    pub headers: BlobHeaders,
+    pub no_guess_mime_type: bool,
+    pub check_md5: Option<Md5Check>,
+    /// Copy only: place a directory's contents directly in the destination
+    /// instead of under a folder named after it
+    pub no_as_subdir: bool,
+    /// Sync only: compare MD5 hashes instead of modification times
+    pub compare_md5: bool,
+    /// Key for blobs encrypted with a customer-provided key
+    pub customer_key: Option<CustomerKey>,
+    /// Copy only: kind of blob to create, instead of azcopy's guess
+    pub blob_type: Option<BlobType>,
+    /// What to do with symbolic links under a local source
+    pub symlinks: SymlinkPolicy,
+    /// Concurrency tuning chose for the destination's kind of account, for
+    /// jobs against accounts nothing was learned about
+    pub tuned_concurrency: Option<u32>,
+    /// `--concurrency`, `--buffer-gb` and `--scan-concurrency` from the
+    /// command line, over the environment and the config file
+    pub transfer_flags: TransferConfig,
 }
 
 impl AzCopyOptions {
@@ -143,6 +753,11 @@ impl AzCopyOptions {
         self
     }
 
+    pub fn with_bandwidth_schedule(mut self, schedule: Option<BandwidthSchedule>) -> Self {
+        self.bandwidth_schedule = schedule;
+        self
+    }
+
     pub fn with_block_size_mb(mut self, block_size_mb: Option<f64>) -> Self {
         self.block_size_mb = block_size_mb;
         self
@@ -163,6 +778,103 @@ impl AzCopyOptions {
         self
     }
 
+    pub fn with_include_path(mut self, path: Option<String>) -> Self {
+        self.include_path = path;
+        self
+    }
+
+    pub fn with_path_filters(mut self, filters: &PathFilters) -> Self {
+        self.include_path = filters.include_path.clone();
+        self.exclude_path = filters.exclude_path.clone();
+        self.include_regex = filters.include_regex.clone();
+        self.exclude_regex = filters.exclude_regex.clone();
+        self
+    }
+
+    /// Exclude more relative paths, keeping those already excluded
+    pub fn with_extra_exclude_paths(mut self, paths: &[&str]) -> Self {
+        let mut excluded: Vec<&str> = self.exclude_path.iter().map(String::as_str).collect();
+        excluded.extend_from_slice(paths);
+        if !excluded.is_empty() {
+            self.exclude_path = Some(excluded.join(";"));
+        }
+        self
+    }
+
+    pub fn with_overwrite(mut self, overwrite: Option<OverwritePolicy>) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn with_preserve_properties(mut self, preserve: bool) -> Self {
+        self.preserve_properties = preserve;
+        self
+    }
+
+    pub fn with_preserve_last_modified_time(mut self, preserve: bool) -> Self {
+        self.preserve_last_modified_time = preserve;
+        self
+    }
+
+    pub fn with_headers(mut self, headers: BlobHeaders) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_no_guess_mime_type(mut self, no_guess: bool) -> Self {
+        self.no_guess_mime_type = no_guess;
+        self
+    }
+
+    pub fn with_check_md5(mut self, check_md5: Option<Md5Check>) -> Self {
+        self.check_md5 = check_md5;
+        self
+    }
+
+    pub fn with_no_as_subdir(mut self, no_as_subdir: bool) -> Self {
+        self.no_as_subdir = no_as_subdir;
+        self
+    }
+
+    pub fn with_compare_md5(mut self, compare_md5: bool) -> Self {
+        self.compare_md5 = compare_md5;
+        self
+    }
+
+    pub fn with_customer_key(mut self, customer_key: Option<CustomerKey>) -> Self {
+        self.customer_key = customer_key;
+        self
+    }
+
+    pub fn with_blob_type(mut self, blob_type: Option<BlobType>) -> Self {
+        self.blob_type = blob_type;
+        self
+    }
+
+    pub fn with_symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    pub fn with_tuned_concurrency(mut self, concurrency: Option<u32>) -> Self {
+        self.tuned_concurrency = concurrency;
+        self
+    }
+
+    pub fn with_transfer_flags(mut self, flags: TransferConfig) -> Self {
+        self.transfer_flags = flags;
+        self
+    }
+
+    /// The azcopy flag that preserves file properties on this platform
+    pub fn preserve_properties_flag() -> &'static str {
+        if cfg!(windows) {
+            "--preserve-smb-info"
+        } else {
+            "--preserve-posix-properties"
+        }
+    }
+
     /// Apply common options to a command
     pub fn apply_to_command(&self, cmd: &mut AsyncCommand) {
         if self.recursive {
@@ -192,47 +904,128 @@ impl AzCopyOptions {
         if let Some(pattern) = &self.exclude_pattern {
             cmd.arg(format!("--exclude-pattern={}", pattern));
         }
-    }
 
-    /// Apply environment variable tuning settings
-    pub fn apply_env_vars(cmd: &mut AsyncCommand) {
-        // Pass through performance-related environment variables if set
-        let env_vars = [
-            "AZCOPY_CONCURRENCY_VALUE",
-            "AZCOPY_CONCURRENT_FILES",
-            "AZCOPY_CONCURRENT_SCAN",
-            "AZCOPY_BUFFER_GB",
-            "AZCOPY_LOG_LOCATION",
-            "AZCOPY_JOB_PLAN_LOCATION",
-            "AZCOPY_DISABLE_HIERARCHICAL_SCAN",
-            "AZCOPY_PARALLEL_STAT_FILES",
-        ];
+        if let Some(path) = &self.include_path {
+            cmd.arg(format!("--include-path={}", path));
+        }
 
-        for var in &env_vars {
-            if let Ok(val) = std::env::var(var) {
-                cmd.env(var, val);
-            }
+        if let Some(path) = &self.exclude_path {
+            cmd.arg(format!("--exclude-path={}", path));
         }
-    }
-}
 
-// ============================================================================
-// Azure Configuration and Data Structures
-// ============================================================================
+        if let Some(regex) = &self.include_regex {
+            cmd.arg(format!("--include-regex={}", regex));
+        }
 
-#[derive(Debug, Clone)]
-pub struct AzureConfig {
+        if let Some(regex) = &self.exclude_regex {
+            cmd.arg(format!("--exclude-regex={}", regex));
+        }
+
+        if let Some(overwrite) = &self.overwrite {
+            cmd.arg(format!("--overwrite={}", overwrite.as_azcopy_value()));
+        }
+
+        if self.preserve_properties {
+            cmd.arg(Self::preserve_properties_flag());
+        }
+
+        if self.preserve_last_modified_time {
+            cmd.arg("--preserve-last-modified-time");
+        }
+
+        cmd.args(self.headers.azcopy_args());
+
+        if self.no_guess_mime_type {
+            cmd.arg("--no-guess-mime-type");
+        }
+
+        if let Some(check_md5) = &self.check_md5 {
+            cmd.arg(format!("--check-md5={}", check_md5.as_azcopy_value()));
+        }
+
+        if self.no_as_subdir {
+            cmd.arg("--as-subdir=false");
+        }
+
+        if let Some(customer_key) = &self.customer_key {
+            cmd.arg("--cpk-by-value");
+            customer_key.apply_env(cmd);
+        }
+
+        if let Some(blob_type) = &self.blob_type {
+            cmd.arg(format!("--blob-type={}", blob_type.as_azcopy_value()));
+        }
+
+        if let Some(flag) = self.symlinks.azcopy_flag() {
+            cmd.arg(flag);
+        }
+    }
+
+    /// Apply environment variable tuning settings
+    ///
+    /// Command-line flags win over AZCOPY_* variables the user exported,
+    /// which win over the config file's profile and `[transfer]` section.
+    pub fn apply_env_vars(&self, cmd: &mut AsyncCommand) {
+        // Pass through performance-related environment variables if set
+        let env_vars = [
+            "AZCOPY_CONCURRENCY_VALUE",
+            "AZCOPY_CONCURRENT_FILES",
+            "AZCOPY_CONCURRENT_SCAN",
+            "AZCOPY_BUFFER_GB",
+            "AZCOPY_LOG_LOCATION",
+            "AZCOPY_JOB_PLAN_LOCATION",
+            "AZCOPY_DISABLE_HIERARCHICAL_SCAN",
+            "AZCOPY_PARALLEL_STAT_FILES",
+        ];
+
+        for var in &env_vars {
+            if let Ok(val) = std::env::var(var) {
+                cmd.env(var, val);
+            }
+        }
+
+        match Config::load().and_then(|config| config.transfer_for(config::profile())) {
+            Ok(configured) => {
+                for (var, value) in configured.env_vars() {
+                    if std::env::var_os(var).is_none() {
+                        cmd.env(var, value);
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Ignoring the [transfer] settings: {:#}", e),
+        }
+        for (var, value) in self.transfer_flags.env_vars() {
+            cmd.env(var, value);
+        }
+        network::apply_to_azcopy(cmd);
+    }
+}
+
+// ============================================================================
+// Azure Configuration and Data Structures
+// ============================================================================
+
+#[derive(Debug, Clone)]
+pub struct AzureConfig {
     pub storage_account: Option<String>,
+    /// Presented when reading or writing blob content, for CPK-encrypted blobs
+    pub customer_key: Option<CustomerKey>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BlobInfo {
     pub name: String,
     #[serde(rename = "properties")]
     pub properties: BlobProperties,
+    /// Number of versions, when counted with `count_blob_versions`
+    #[serde(default)]
+    pub version_count: Option<usize>,
+    /// Index tags, when listed with `inventory_pages`
+    #[serde(default)]
+    pub tags: Option<BTreeMap<String, String>>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BlobProperties {
     #[serde(rename = "contentLength")]
     pub content_length: u64,
@@ -240,15 +1033,46 @@ pub struct BlobProperties {
     pub last_modified: String,
     #[serde(rename = "contentType")]
     pub content_type: Option<String>,
+    /// Last modified time as a Unix timestamp (seconds), used for comparisons
+    #[serde(default)]
+    pub last_modified_unix: i64,
+    /// Content-MD5 as a lowercase hex string, when the blob has one
+    #[serde(default)]
+    pub content_md5: Option<String>,
+    #[serde(rename = "contentEncoding", default)]
+    pub content_encoding: Option<String>,
+    /// Entity tag, which changes whenever the blob is written
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(rename = "accessTier", default)]
+    pub access_tier: Option<String>,
+    /// BlockBlob, PageBlob or AppendBlob
+    #[serde(rename = "blobType", default)]
+    pub blob_type: Option<String>,
+    #[serde(rename = "leaseState", default)]
+    pub lease_state: Option<String>,
 }
 
 /// Represents either a blob or a blob prefix (virtual directory)
+// Listings are mostly blobs, so boxing them would only add allocations
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum BlobItem {
     Blob(BlobInfo),
     Prefix(String),
 }
 
+/// Where a server-side copy into a blob stands
+#[derive(Debug, Clone)]
+pub struct CopyState {
+    pub id: Option<String>,
+    pub status: CopyStatus,
+    /// Bytes copied so far and the size of the source
+    pub progress: Option<(u64, u64)>,
+    /// Why the copy failed, as the service put it
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ContainerInfo {
     pub name: String,
@@ -268,12 +1092,174 @@ pub struct StorageAccountInfo {
     pub location: String,
     #[serde(rename = "resourceGroup")]
     pub resource_group: String,
+    /// Account kind, e.g. StorageV2 or BlockBlobStorage
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// SKU name, e.g. Standard_LRS or Premium_ZRS
+    #[serde(default)]
+    pub sku: Option<String>,
+}
+
+/// Which storage accounts a listing returns
+#[derive(Debug, Clone, Default)]
+pub struct AccountFilter {
+    /// Subscription to list instead of the current one
+    pub subscription: Option<String>,
+    /// Every enabled subscription the credential can see
+    pub all_subscriptions: bool,
+    /// Only accounts in this resource group
+    pub resource_group: Option<String>,
+    /// Only accounts whose name matches this wildcard pattern, or contains it
+    /// when it has no wildcards
+    pub name: Option<String>,
+}
+
+impl AccountFilter {
+    /// Whether the current subscription is listed unfiltered
+    pub fn is_empty(&self) -> bool {
+        self.subscription.is_none()
+            && !self.all_subscriptions
+            && self.resource_group.is_none()
+            && self.name.is_none()
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        match &self.name {
+            None => true,
+            Some(pattern) if pattern.contains(['*', '?', '[']) => {
+                crate::utils::matches_pattern(name, pattern)
+            }
+            Some(pattern) => name.contains(pattern.as_str()),
+        }
+    }
+}
+
+/// Version-level immutability settings of a blob
+#[derive(Debug, Default)]
+pub struct BlobImmutability {
+    /// When the retention policy expires, as reported by the service
+    pub until: Option<String>,
+    /// "unlocked" or "locked"
+    pub mode: Option<String>,
+    pub legal_hold: bool,
+}
+
+/// Time-based retention policy and legal hold of a container
+#[derive(Debug, Default)]
+pub struct ContainerImmutability {
+    /// Days blobs are kept after they're created, if a policy is set
+    pub retention_days: Option<i64>,
+    pub locked: bool,
+    /// ETag of the retention policy, needed to lock or delete it
+    pub etag: Option<String>,
+    pub legal_hold_tags: Vec<String>,
+}
+
+/// Properties and access settings of a container
+#[derive(Debug, Serialize)]
+pub struct ContainerSettings {
+    pub name: String,
+    /// "off", "blob" or "container"
+    pub public_access: String,
+    pub last_modified: String,
+    pub lease_state: String,
+    pub has_immutability_policy: bool,
+    pub has_legal_hold: bool,
+    pub metadata: std::collections::BTreeMap<String, String>,
+    pub access_policies: Vec<AccessPolicyInfo>,
+}
+
+/// Account-wide settings that decide how exposed its data is
+#[derive(Debug)]
+pub struct AccountSecurity {
+    pub allow_shared_key_access: bool,
+    pub allow_blob_public_access: bool,
+    /// Days deleted blobs are kept, if blob soft delete is on
+    pub blob_soft_delete_days: Option<i64>,
+    /// Days deleted containers are kept, if container soft delete is on
+    pub container_soft_delete_days: Option<i64>,
+    pub versioning: bool,
+}
+
+/// A stored access policy, which SAS tokens can refer to by ID
+#[derive(Debug, Serialize)]
+pub struct AccessPolicyInfo {
+    pub id: String,
+    pub start: String,
+    pub expiry: String,
+    pub permission: String,
+}
+
+/// Who the ambient credential chain signs in as, as far as can be told
+/// without asking for a token: the variables its links read, and the
+/// account the Azure CLI is signed in to
+fn ambient_identity() -> String {
+    const VARS: [&str; 7] = [
+        "AZURE_CREDENTIAL_KIND",
+        "AZURE_TENANT_ID",
+        "AZURE_CLIENT_ID",
+        "AZURE_CLIENT_CERTIFICATE_PATH",
+        "AZURE_FEDERATED_TOKEN_FILE",
+        "IDENTITY_ENDPOINT",
+        "MSI_ENDPOINT",
+    ];
+    let mut parts: Vec<String> = VARS
+        .iter()
+        .filter_map(|var| Some(format!("{}={}", var, std::env::var(var).ok()?)))
+        .collect();
+    parts.extend(cli_account().map(|account| format!("az={}", account)));
+    format!("ambient;{}", parts.join(";"))
+}
+
+/// `user@tenant` of the Azure CLI's default subscription, read from its
+/// profile file rather than by running `az`
+fn cli_account() -> Option<String> {
+    let dir = std::env::var_os("AZURE_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".azure")))?;
+    let content = std::fs::read_to_string(dir.join("azureProfile.json")).ok()?;
+    // The CLI writes the file with a byte order mark
+    let profile: serde_json::Value =
+        serde_json::from_str(content.trim_start_matches('\u{feff}')).ok()?;
+    let default = profile["subscriptions"]
+        .as_array()?
+        .iter()
+        .find(|subscription| subscription["isDefault"].as_bool() == Some(true))?;
+    Some(format!(
+        "{}@{}",
+        default["user"]["name"].as_str()?,
+        default["tenantId"].as_str()?
+    ))
+}
+
+/// azure_identity's `create_credential`, with requests going through the
+/// configured proxy and CA bundle
+fn ambient_credential() -> Result<Arc<dyn TokenCredential>> {
+    let options = azure_identity::TokenCredentialOptions::from(network::sdk_http_client()?);
+    let credential: Arc<dyn TokenCredential> = if std::env::var("AZURE_CREDENTIAL_KIND").is_ok() {
+        Arc::new(azure_identity::SpecificAzureCredential::create(options)?)
+    } else {
+        Arc::new(
+            azure_identity::DefaultAzureCredentialBuilder::default()
+                .with_options(options)
+                .build()?,
+        )
+    };
+    Ok(credential)
 }
 
 #[derive(Clone)]
 pub struct AzureClient {
     config: AzureConfig,
     credential: Option<Arc<dyn TokenCredential>>,
+    /// Ends listings early when the command is cancelled
+    cancel: CancellationToken,
+}
+
+impl Default for AzureClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AzureClient {
@@ -281,8 +1267,10 @@ impl AzureClient {
         Self {
             config: AzureConfig {
                 storage_account: None,
+                customer_key: None,
             },
             credential: None,
+            cancel: cancel::token(),
         }
     }
 
@@ -291,6 +1279,12 @@ impl AzureClient {
         self
     }
 
+    /// Present `customer_key` when reading or writing blob content
+    pub fn with_customer_key(mut self, customer_key: Option<CustomerKey>) -> Self {
+        self.config.customer_key = customer_key;
+        self
+    }
+
     /// Get the configured storage account name
     pub fn get_storage_account(&self) -> Option<&str> {
         self.config.storage_account.as_deref()
@@ -299,6 +1293,7 @@ impl AzureClient {
     /// Get or create the Azure credential using a fallback chain
     ///
     /// Credential chain (in priority order):
+    /// 0. A sign-in from `azst auth login`, which wins over ambient credentials
     /// 1. Environment Variables (Service Principal)
     ///    - AZURE_TENANT_ID, AZURE_CLIENT_ID, AZURE_CLIENT_SECRET
     ///    - Or AZURE_FEDERATED_TOKEN / AZURE_FEDERATED_TOKEN_FILE for Workload Identity
@@ -316,15 +1311,40 @@ impl AzureClient {
         if let Some(ref cred) = self.credential {
             return Ok(cred.clone());
         }
+        if let Some(cred) = SHARED_CREDENTIAL.lock().ok().and_then(|cred| cred.clone()) {
+            self.credential = Some(cred.clone());
+            return Ok(cred);
+        }
+
+        let credential = Self::resolve_credential()?;
+        if let Ok(mut shared) = SHARED_CREDENTIAL.lock() {
+            *shared = Some(credential.clone());
+        }
+        self.credential = Some(credential.clone());
+        Ok(credential)
+    }
+
+    fn resolve_credential() -> Result<Arc<dyn TokenCredential>> {
+        if let Some(login) = StoredLogin::load() {
+            let identity = format!("login;{};tenant={}", login.flow, login.tenant);
+            let login = Arc::new(LoginCredential::new(login));
+            let credential: Arc<dyn TokenCredential> = Arc::new(RefreshingCredential::new(
+                Arc::new(PersistentCredential::new(login, identity)),
+            ));
+            return Ok(credential);
+        }
 
         // Check for Azure ML MSI environment variables first
         // Azure ML compute instances use MSI_ENDPOINT and MSI_SECRET
         if let (Ok(endpoint), Ok(secret)) =
             (std::env::var("MSI_ENDPOINT"), std::env::var("MSI_SECRET"))
         {
-            let credential = Arc::new(AzureMLMsiCredential::new(endpoint, secret));
-            self.credential = Some(credential.clone());
-            return Ok(credential as Arc<dyn TokenCredential>);
+            let identity = format!("azureml-msi;{}", endpoint);
+            let msi = Arc::new(AzureMLMsiCredential::new(endpoint, secret));
+            let credential: Arc<dyn TokenCredential> = Arc::new(RefreshingCredential::new(
+                Arc::new(PersistentCredential::new(msi, identity)),
+            ));
+            return Ok(credential);
         }
 
         // Fall back to standard Azure credential chain
@@ -335,13 +1355,38 @@ impl AzureClient {
         // 2. WorkloadIdentityCredential (AZURE_FEDERATED_TOKEN_FILE for AKS workload identity)
         // 3. ManagedIdentityCredential (for Azure VMs, App Service, Container Instances)
         // 4. AzureCliCredential (az login for local development)
-        let credential = azure_identity::create_credential()
+        let credential = ambient_credential()
             .context("Failed to create Azure credential. Please ensure you have authenticated with 'az login', or are running on an Azure VM with Managed Identity, or have set service principal environment variables (AZURE_TENANT_ID, AZURE_CLIENT_ID, AZURE_CLIENT_SECRET).")?;
-
-        self.credential = Some(credential.clone());
+        let credential: Arc<dyn TokenCredential> = Arc::new(RefreshingCredential::new(Arc::new(
+            PersistentCredential::new(credential, ambient_identity()),
+        )));
         Ok(credential)
     }
 
+    /// Access token for `scope` from the credential chain, for `azst auth`
+    pub async fn access_token(&mut self, scope: &str) -> Result<AccessToken, AzstError> {
+        let credential = self.get_credential().await?;
+        Ok(credential
+            .get_token(&[scope])
+            .await
+            .context("Failed to acquire an Azure access token")?)
+    }
+
+    /// Which link of the credential chain commands authenticate with
+    pub fn credential_source() -> String {
+        if let Some(login) = StoredLogin::load() {
+            return format!("azst auth login ({}, tenant {})", login.flow, login.tenant);
+        }
+        if std::env::var_os("MSI_ENDPOINT").is_some() && std::env::var_os("MSI_SECRET").is_some() {
+            return "Azure ML managed identity".to_string();
+        }
+        match std::env::var("AZURE_CREDENTIAL_KIND") {
+            Ok(kind) => format!("AZURE_CREDENTIAL_KIND={}", kind),
+            Err(_) => "default chain (environment, workload identity, managed identity, Azure CLI)"
+                .to_string(),
+        }
+    }
+
     /// Create a BlobServiceClient for the configured storage account
     async fn get_blob_service_client(&mut self) -> Result<BlobServiceClient> {
         let account_name = self
@@ -354,21 +1399,26 @@ impl AzureClient {
         let credential = self.get_credential().await?;
 
         // Create BlobServiceClient with token credential
-        let client = BlobServiceClient::new(
+        let client = ClientBuilder::new(
             &account_name,
             StorageCredentials::token_credential(credential as Arc<dyn TokenCredential>),
-        );
+        )
+        .retry(retry_options()?)
+        .transport(TransportOptions::new(network::sdk_http_client()?))
+        .blob_service_client();
 
         Ok(client)
     }
 
     /// Check if Azure credentials are available
-    pub async fn check_prerequisites(&mut self) -> Result<()> {
+    pub async fn check_prerequisites(&mut self) -> Result<(), AzstError> {
         // Try to get a credential - this will validate authentication
-        let _credential = self
-            .get_credential()
-            .await
-            .context("Failed to authenticate with Azure. Please run 'az login' to authenticate.")?;
+        let _credential = self.get_credential().await.map_err(|e| {
+            AzstError::Auth(format!(
+                "Failed to authenticate with Azure. Please run 'az login' to authenticate: {:#}",
+                e
+            ))
+        })?;
 
         // Note: We use Azure CLI credentials via the SDK
         // The user must have run `az login` for this to work
@@ -376,13 +1426,21 @@ impl AzureClient {
     }
 
     /// Get the current subscription ID
-    /// First tries the AZURE_SUBSCRIPTION_ID environment variable,
-    /// then falls back to using Azure CLI to get the default subscription
+    /// First tries the AZURE_SUBSCRIPTION_ID environment variable and the
+    /// subscription given to `azst auth login`, then falls back to using Azure
+    /// CLI to get the default subscription
     async fn get_subscription_id(&mut self) -> Result<String> {
         // Try environment variable first
         if let Ok(sub_id) = std::env::var("AZURE_SUBSCRIPTION_ID") {
             return Ok(sub_id);
         }
+        if let Some(sub_id) = StoredLogin::load().and_then(|login| login.subscription) {
+            return Ok(sub_id);
+        }
+
+        if let Some(sub_id) = CLI_SUBSCRIPTION.lock().ok().and_then(|sub| sub.clone()) {
+            return Ok(sub_id);
+        }
 
         // Fall back to using Azure CLI to get the current subscription
         let output = AsyncCommand::new("az")
@@ -407,34 +1465,141 @@ impl AzureClient {
             ));
         }
 
+        if let Ok(mut cached) = CLI_SUBSCRIPTION.lock() {
+            *cached = Some(subscription_id.clone());
+        }
         Ok(subscription_id)
     }
 
+    /// IDs of the enabled subscriptions the credential can see
+    async fn list_subscriptions(&mut self) -> Result<Vec<String>> {
+        use azure_mgmt_resources::package_subscriptions_2021_01::models::subscription::State;
+
+        let client = azure_mgmt_resources::package_subscriptions_2021_01::Client::builder(
+            self.get_credential().await?,
+        )
+        .retry(retry_options()?)
+        .transport(TransportOptions::new(network::sdk_http_client()?))
+        .build()?;
+
+        let mut subscriptions = Vec::new();
+        let mut stream = client.subscriptions_client().list().into_stream();
+        while let Some(response) = stream.next().await {
+            let response = response.context("Failed to list subscriptions")?;
+            subscriptions.extend(
+                response
+                    .value
+                    .into_iter()
+                    .filter(|subscription| {
+                        matches!(
+                            subscription.state,
+                            None | Some(State::Enabled | State::Warned)
+                        )
+                    })
+                    .filter_map(|subscription| subscription.subscription_id),
+            );
+        }
+        Ok(subscriptions)
+    }
+
     /// List storage accounts in the current subscription
     /// Uses Azure Management SDK to query storage accounts
     ///
     /// Automatically detects subscription ID from:
     /// 1. AZURE_SUBSCRIPTION_ID environment variable (if set)
-    /// 2. Azure CLI default subscription (via `az account show`)
-    pub async fn list_storage_accounts(&mut self) -> Result<Vec<StorageAccountInfo>> {
+    /// 2. The subscription given to `azst auth login --subscription`
+    /// 3. Azure CLI default subscription (via `az account show`)
+    pub async fn list_storage_accounts(&mut self) -> Result<Vec<StorageAccountInfo>, AzstError> {
+        self.list_storage_accounts_matching(&AccountFilter::default())
+            .await
+    }
+
+    /// List the storage accounts `filter` selects
+    ///
+    /// With `all_subscriptions`, a subscription that can't be listed is
+    /// skipped with a warning, and one without the resource group silently.
+    pub async fn list_storage_accounts_matching(
+        &mut self,
+        filter: &AccountFilter,
+    ) -> Result<Vec<StorageAccountInfo>, AzstError> {
         let credential = self.get_credential().await?;
 
-        // Get subscription ID (with automatic fallback)
-        let subscription_id = self.get_subscription_id().await?;
+        let subscriptions = if filter.all_subscriptions {
+            self.list_subscriptions().await?
+        } else {
+            match &filter.subscription {
+                Some(subscription_id) => vec![subscription_id.clone()],
+                // Get subscription ID (with automatic fallback)
+                None => vec![self.get_subscription_id().await?],
+            }
+        };
 
         // Create management client using ClientBuilder
-        let client = azure_mgmt_storage::Client::builder(credential).build()?;
+        let client = azure_mgmt_storage::Client::builder(credential)
+            .retry(retry_options()?)
+            .transport(TransportOptions::new(network::sdk_http_client()?))
+            .build()?;
 
         let mut all_accounts = Vec::new();
 
-        // List all storage accounts in the subscription using streaming API
-        let mut stream = client
-            .storage_accounts_client()
-            .list(subscription_id)
-            .into_stream();
+        for subscription_id in subscriptions {
+            let accounts = match self
+                .list_subscription_accounts(&client, &subscription_id, filter)
+                .await
+            {
+                Ok(accounts) => accounts,
+                Err(e) if filter.all_subscriptions => {
+                    let missing_group = filter.resource_group.is_some()
+                        && e.downcast_ref::<AzureError>()
+                            .and_then(|e| e.as_http_error())
+                            .is_some_and(|e| e.status() == StatusCode::NotFound);
+                    if !missing_group {
+                        eprintln!(
+                            "{} Skipping subscription {}: {:#}",
+                            "⚠".yellow(),
+                            subscription_id,
+                            e
+                        );
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e
+                        .context(format!(
+                            "Failed to list storage accounts in subscription {}",
+                            subscription_id
+                        ))
+                        .into())
+                }
+            };
+            all_accounts.extend(
+                accounts
+                    .into_iter()
+                    .filter(|account| filter.matches_name(&account.name)),
+            );
+        }
+
+        Ok(all_accounts)
+    }
 
+    /// Storage accounts of one subscription, or of a resource group in it
+    async fn list_subscription_accounts(
+        &self,
+        client: &azure_mgmt_storage::Client,
+        subscription_id: &str,
+        filter: &AccountFilter,
+    ) -> Result<Vec<StorageAccountInfo>> {
+        let storage_accounts = client.storage_accounts_client();
+        let mut stream = match &filter.resource_group {
+            Some(resource_group) => storage_accounts
+                .list_by_resource_group(resource_group, subscription_id)
+                .into_stream(),
+            None => storage_accounts.list(subscription_id).into_stream(),
+        };
+
+        let mut accounts = Vec::new();
         while let Some(response_result) = stream.next().await {
-            let response = response_result.context("Failed to list storage accounts")?;
+            let response = response_result?;
 
             // Extract storage accounts from the response
             for account in response.value {
@@ -448,19 +1613,20 @@ impl AzureClient {
                     .and_then(|id| id.split('/').nth(4).map(|s| s.to_string()))
                     .unwrap_or_default();
 
-                all_accounts.push(StorageAccountInfo {
+                accounts.push(StorageAccountInfo {
                     name: account.tracked_resource.resource.name.unwrap_or_default(),
                     location: account.tracked_resource.location,
                     resource_group,
+                    kind: account.kind.as_ref().and_then(enum_name),
+                    sku: account.sku.as_ref().and_then(|sku| enum_name(&sku.name)),
                 });
             }
         }
-
-        Ok(all_accounts)
+        Ok(accounts)
     }
 
     /// List containers in the storage account using Azure SDK
-    pub async fn list_containers(&mut self) -> Result<Vec<ContainerInfo>> {
+    pub async fn list_containers(&mut self) -> Result<Vec<ContainerInfo>, AzstError> {
         let blob_service = self.get_blob_service_client().await?;
 
         // List containers using the SDK
@@ -468,6 +1634,9 @@ impl AzureClient {
         let mut stream = blob_service.list_containers().into_stream();
 
         while let Some(result) = stream.next().await {
+            if self.cancel.is_cancelled() {
+                return Err(cancel::error());
+            }
             match result {
                 Ok(response) => {
                     for container in response.containers {
@@ -480,7 +1649,7 @@ impl AzureClient {
                     }
                 }
                 Err(e) => {
-                    return Err(anyhow!("Failed to list containers: {}", e));
+                    return Err(anyhow!("Failed to list containers: {}", e).into());
                 }
             }
         }
@@ -488,6 +1657,21 @@ impl AzureClient {
         Ok(containers)
     }
 
+    /// Containers whose name matches a glob pattern such as `logs-*`, by name
+    pub async fn list_containers_matching(
+        &mut self,
+        pattern: &str,
+    ) -> Result<Vec<ContainerInfo>, AzstError> {
+        let mut containers: Vec<ContainerInfo> = self
+            .list_containers()
+            .await?
+            .into_iter()
+            .filter(|container| matches_pattern(&container.name, pattern))
+            .collect();
+        containers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(containers)
+    }
+
     /// List blobs in a container with optional prefix
     /// This method automatically handles pagination to retrieve all results
     pub async fn list_blobs(
@@ -495,7 +1679,7 @@ impl AzureClient {
         container: &str,
         prefix: Option<&str>,
         delimiter: Option<&str>,
-    ) -> Result<Vec<BlobItem>> {
+    ) -> Result<Vec<BlobItem>, AzstError> {
         let mut all_items = Vec::new();
 
         self.list_blobs_with_callback(container, prefix, delimiter, |items| {
@@ -515,10 +1699,33 @@ impl AzureClient {
         prefix: Option<&str>,
         delimiter: Option<&str>,
         mut callback: F,
-    ) -> Result<()>
+    ) -> Result<(), AzstError>
     where
         F: FnMut(Vec<BlobItem>) -> Result<()>,
     {
+        let mut pages = self.blob_pages(container, prefix, delimiter).await?;
+        while let Some(items) = pages.next().await {
+            let items = items?;
+            // Call the callback with this page's items
+            if !items.is_empty() {
+                callback(items)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stream the blobs in a container a page at a time, fetching each page
+    /// only when the previous one has been consumed
+    ///
+    /// With a delimiter (e.g., "/"), only the immediate children of `prefix`
+    /// are listed, with `BlobItem::Prefix` items for "subdirectories".
+    pub async fn blob_pages(
+        &mut self,
+        container: &str,
+        prefix: Option<&str>,
+        delimiter: Option<&str>,
+    ) -> Result<BoxStream<'static, Result<Vec<BlobItem>, AzstError>>, AzstError> {
         let blob_service = self.get_blob_service_client().await?;
         let container_client = blob_service.container_client(container);
 
@@ -528,48 +1735,1567 @@ impl AzureClient {
         if let Some(prefix_val) = prefix {
             list_builder = list_builder.prefix(prefix_val.to_string());
         }
-
-        // Set delimiter for hierarchical listing (non-recursive)
-        // When delimiter is set (e.g., "/"), the API returns only immediate children
-        // and uses BlobPrefix items for "subdirectories"
         if let Some(delimiter_val) = delimiter {
             list_builder = list_builder.delimiter(delimiter_val.to_string());
         }
 
-        let mut stream = list_builder.into_stream();
-
-        while let Some(page_result) = stream.next().await {
+        let pages = list_builder.into_stream().map(|page_result| {
             let page = page_result.context("Failed to fetch blob page")?;
-            let mut items = Vec::new();
 
             // Process blobs and blob prefixes
+            let items = page
+                .blobs
+                .items
+                .iter()
+                .map(|item| match item {
+                    azure_storage_blobs::container::operations::BlobItem::Blob(blob) => {
+                        BlobItem::Blob(to_blob_info(blob))
+                    }
+                    azure_storage_blobs::container::operations::BlobItem::BlobPrefix(prefix) => {
+                        BlobItem::Prefix(prefix.name.clone())
+                    }
+                })
+                .collect();
+            Ok(items)
+        });
+        Ok(cancel::guard(pages, self.cancel.clone()).boxed())
+    }
+
+    /// Pages of blobs under a prefix with their index tags, starting after
+    /// `marker`, each with the marker to continue from (None after the last)
+    ///
+    /// Inventories save the marker so an interrupted export can pick up
+    /// where it stopped.
+    pub async fn inventory_pages(
+        &mut self,
+        container: &str,
+        prefix: Option<&str>,
+        marker: Option<String>,
+    ) -> Result<BoxStream<'static, Result<(Vec<BlobInfo>, Option<String>), AzstError>>, AzstError>
+    {
+        let blob_service = self.get_blob_service_client().await?;
+        let mut list_builder = blob_service
+            .container_client(container)
+            .list_blobs()
+            .include_tags(true);
+        if let Some(prefix_val) = prefix {
+            list_builder = list_builder.prefix(prefix_val.to_string());
+        }
+        if let Some(marker) = marker {
+            list_builder = list_builder.marker(NextMarker::new(marker));
+        }
+
+        let pages = list_builder.into_stream().map(|page_result| {
+            let page = page_result.context("Failed to fetch blob page")?;
+            let blobs = page.blobs.blobs().map(to_blob_info).collect();
+            let next_marker = page.next_marker.map(|marker| marker.as_str().to_string());
+            Ok((blobs, next_marker))
+        });
+        Ok(cancel::guard(pages, self.cancel.clone()).boxed())
+    }
+
+    /// Pages of every blob under a prefix, listed as up to `partitions`
+    /// concurrent streams
+    ///
+    /// A delimiter listing first finds the "directories" directly below
+    /// `prefix`; each is then listed on its own and the pages are merged as
+    /// they arrive, so they come in no particular order. Blobs directly under
+    /// `prefix` come first, from the delimiter listing itself.
+    pub async fn partitioned_blob_pages(
+        &mut self,
+        container: &str,
+        prefix: Option<&str>,
+        partitions: usize,
+        include_tags: bool,
+    ) -> Result<BoxStream<'static, Result<Vec<BlobInfo>, AzstError>>, AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let container_client = blob_service.container_client(container);
+        let listing = move |prefix: Option<String>, delimiter: Option<&str>| {
+            let mut list_builder = container_client.list_blobs().include_tags(include_tags);
+            if let Some(prefix_val) = prefix {
+                list_builder = list_builder.prefix(prefix_val);
+            }
+            if let Some(delimiter_val) = delimiter {
+                list_builder = list_builder.delimiter(delimiter_val.to_string());
+            }
+            list_builder.into_stream()
+        };
+
+        let mut direct = Vec::new();
+        let mut subdirectories = Vec::new();
+        let mut top_level = listing(prefix.map(str::to_string), Some("/"));
+        while let Some(page_result) = top_level.next().await {
+            let page = page_result.context("Failed to fetch blob page")?;
             for item in &page.blobs.items {
                 match item {
                     azure_storage_blobs::container::operations::BlobItem::Blob(blob) => {
-                        items.push(BlobItem::Blob(BlobInfo {
-                            name: blob.name.clone(),
-                            properties: BlobProperties {
-                                content_length: blob.properties.content_length,
-                                last_modified: blob.properties.last_modified.to_string(),
-                                content_type: Some(blob.properties.content_type.clone()),
-                            },
-                        }));
+                        direct.push(to_blob_info(blob))
                     }
                     azure_storage_blobs::container::operations::BlobItem::BlobPrefix(prefix) => {
-                        items.push(BlobItem::Prefix(prefix.name.clone()));
+                        subdirectories.push(prefix.name.clone())
                     }
                 }
             }
+        }
+
+        let direct = futures::stream::iter((!direct.is_empty()).then_some(Ok(direct)));
+        let partitioned = futures::stream::iter(subdirectories)
+            .map(move |subdirectory| {
+                listing(Some(subdirectory), None)
+                    .map(|page_result| {
+                        let page = page_result.context("Failed to fetch blob page")?;
+                        Ok(page.blobs.blobs().map(to_blob_info).collect())
+                    })
+                    .boxed()
+            })
+            .flatten_unordered(partitions.max(1));
+        Ok(cancel::guard(direct.chain(partitioned), self.cancel.clone()).boxed())
+    }
+
+    /// Count the versions of every blob under a prefix, including deleted blobs
+    /// whose versions remain; needs versioning enabled on the account
+    pub async fn count_blob_versions(
+        &mut self,
+        container: &str,
+        prefix: Option<&str>,
+    ) -> Result<std::collections::HashMap<String, usize>, AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let mut list_builder = blob_service
+            .container_client(container)
+            .list_blobs()
+            .include_versions(true);
+        if let Some(prefix_val) = prefix {
+            list_builder = list_builder.prefix(prefix_val.to_string());
+        }
+
+        let mut counts = std::collections::HashMap::new();
+        let mut stream = list_builder.into_stream();
+        while let Some(page_result) = stream.next().await {
+            if self.cancel.is_cancelled() {
+                return Err(cancel::error());
+            }
+            let page = page_result.context("Failed to fetch blob version page")?;
+            for blob in page.blobs.blobs() {
+                *counts.entry(blob.name.clone()).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Get the properties of a single blob (a HEAD request)
+    pub async fn get_blob_properties(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+    ) -> Result<BlobInfo, AzstError> {
+        // The SDK can't present a customer-provided key on a HEAD request
+        if let Some(customer_key) = self.config.customer_key.clone() {
+            return Ok(self
+                .blob_properties_with_key(container, blob_name, &customer_key)
+                .await?);
+        }
+
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+
+        let response = blob_client
+            .get_properties()
+            .await
+            .with_context(|| format!("Failed to get properties of blob '{}'", blob_name))?;
+
+        Ok(to_blob_info(&response.blob))
+    }
+
+    /// Change the Content-Type of a blob, keeping its other HTTP headers
+    ///
+    /// Set Blob Properties clears any header it isn't given, so the current
+    /// ones (including Content-MD5) are read first and sent back unchanged.
+    pub async fn set_blob_content_type(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        content_type: &str,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+
+        let mut properties = blob_client
+            .get_properties()
+            .await
+            .with_context(|| format!("Failed to get properties of blob '{}'", blob_name))?
+            .blob
+            .properties;
+        properties.content_type = content_type.to_string();
+
+        blob_client
+            .set_properties()
+            .set_from_blob_properties(properties)
+            .await
+            .with_context(|| format!("Failed to set content type of blob '{}'", blob_name))?;
+
+        Ok(())
+    }
+
+    /// Mark an uploaded blob as compressed: set its Content-Encoding and
+    /// record the algorithm in `metadata_key`, keeping its other headers and
+    /// metadata
+    pub async fn mark_blob_compressed(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        encoding: &str,
+        metadata_key: &str,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+
+        let blob = blob_client
+            .get_properties()
+            .await
+            .with_context(|| format!("Failed to get properties of blob '{}'", blob_name))?
+            .blob;
+        let mut properties = blob.properties;
+        properties.content_encoding = Some(encoding.to_string());
+        blob_client
+            .set_properties()
+            .set_from_blob_properties(properties)
+            .await
+            .with_context(|| format!("Failed to set content encoding of blob '{}'", blob_name))?;
+
+        let mut metadata = azure_core::request_options::Metadata::new();
+        for (key, value) in blob.metadata.unwrap_or_default() {
+            metadata.insert(key, value);
+        }
+        metadata.insert(metadata_key.to_string(), encoding.to_string());
+        blob_client
+            .set_metadata()
+            .metadata(metadata)
+            .await
+            .with_context(|| format!("Failed to set metadata of blob '{}'", blob_name))?;
+        Ok(())
+    }
+
+    /// Create a signer for read-only blob URLs valid for the given duration
+    ///
+    /// Uses a user delegation key, so the signed URLs carry the permissions of the
+    /// authenticated identity and never require an account key.
+    pub async fn url_signer(
+        &mut self,
+        valid_for: std::time::Duration,
+    ) -> Result<UrlSigner, AzstError> {
+        if valid_for > MAX_SIGNED_URL_VALIDITY {
+            return Err(anyhow!("Signed URLs can be valid for at most 7 days").into());
+        }
+
+        let account = self
+            .get_storage_account()
+            .ok_or_else(|| anyhow!("Storage account not configured"))?
+            .to_string();
+        let blob_service = self.get_blob_service_client().await?;
+
+        let start = time::OffsetDateTime::now_utc();
+        let expiry = start + valid_for;
+        let response = blob_service
+            .get_user_deligation_key(start, expiry)
+            .await
+            .context("Failed to get a user delegation key for signing URLs")?;
+
+        Ok(UrlSigner {
+            account,
+            key: response.user_deligation_key,
+        })
+    }
+
+    /// Build a block blob server-side from byte ranges of other blobs
+    ///
+    /// Each source is a URL the service can read (e.g. a signed URL) and its
+    /// size. Sources are split into blocks of at most `COMPOSE_BLOCK_SIZE`
+    /// bytes, staged with Put Block From URL and committed in order.
+    pub async fn compose_blob(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        sources: &[(String, u64)],
+        content_type: Option<&str>,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+
+        let mut blocks = Vec::new();
+        for (url, size) in sources {
+            let source_url =
+                Url::parse(url).with_context(|| format!("Invalid source URL '{}'", url))?;
+            let mut offset = 0;
+            while offset < *size {
+                let end = (offset + COMPOSE_BLOCK_SIZE).min(*size);
+                let block_id = BlockId::new(format!("azst-compose-{:08}", blocks.len()));
+
+                blob_client
+                    .put_block_url(block_id.clone(), source_url.clone())
+                    .range(offset..end)
+                    .await
+                    .with_context(|| format!("Failed to stage block from '{}'", url))?;
+
+                blocks.push(BlobBlockType::new_uncommitted(block_id));
+                offset = end;
+            }
+        }
+
+        let mut request = blob_client.put_block_list(BlockList { blocks });
+        if let Some(content_type) = content_type {
+            request = request.content_type(content_type.to_string());
+        }
+        request
+            .await
+            .with_context(|| format!("Failed to commit composed blob '{}'", blob_name))?;
+
+        Ok(())
+    }
+
+    /// Create (or replace) a zero-byte block blob
+    pub async fn put_empty_blob(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+
+        blob_client
+            .put_block_blob(Vec::<u8>::new())
+            .await
+            .with_context(|| format!("Failed to create blob '{}'", blob_name))?;
+
+        Ok(())
+    }
+
+    /// Create (or replace) the blob standing for a symbolic link to `target`:
+    /// it holds the target, as azcopy's --preserve-symlinks writes it, and
+    /// records it in metadata too
+    pub async fn put_symlink_blob(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        target: &Path,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let mut metadata = azure_core::request_options::Metadata::new();
+        for (key, value) in symlinks::link_metadata(target) {
+            metadata.insert(key, value);
+        }
+        blob_service
+            .container_client(container)
+            .blob_client(blob_name)
+            .put_block_blob(target.to_string_lossy().into_owned().into_bytes())
+            .metadata(metadata)
+            .await
+            .with_context(|| format!("Failed to create blob '{}'", blob_name))?;
+
+        Ok(())
+    }
+
+    /// Create (or replace) a block blob holding `data`, in a single request
+    pub async fn put_blob(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        data: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let mut request = blob_service
+            .container_client(container)
+            .blob_client(blob_name)
+            .put_block_blob(data);
+        if let Some(content_type) = content_type {
+            request = request.content_type(content_type.to_string());
+        }
+        request
+            .await
+            .with_context(|| format!("Failed to create blob '{}'", blob_name))?;
+
+        Ok(())
+    }
+
+    /// Copy a blob to another name in the same account, server-side
+    ///
+    /// Copies within an account finish almost at once; any that are still
+    /// pending are polled until they complete.
+    pub async fn copy_blob(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        to_container: &str,
+        to_blob_name: &str,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let source_url = blob_service
+            .container_client(container)
+            .blob_client(blob_name)
+            .url()?;
+        let target = blob_service
+            .container_client(to_container)
+            .blob_client(to_blob_name);
+
+        let mut status = target
+            .copy(source_url)
+            .await
+            .with_context(|| format!("Failed to copy blob '{}'", blob_name))?
+            .copy_status;
+        while status == CopyStatus::Pending {
+            tokio::time::sleep(COPY_POLL_INTERVAL).await;
+            status = target
+                .get_properties()
+                .await
+                .with_context(|| format!("Failed to check the copy of blob '{}'", blob_name))?
+                .blob
+                .properties
+                .copy_status
+                .unwrap_or(CopyStatus::Success);
+        }
+        if status != CopyStatus::Success {
+            return Err(anyhow!("Copy of blob '{}' {}", blob_name, status).into());
+        }
+        Ok(())
+    }
+
+    /// Copy the object at `source` into a blob, server-side
+    ///
+    /// Sources known to be no larger than [`SYNC_COPY_LIMIT`] are copied in
+    /// one request that returns once the blob is written. Larger sources, and
+    /// those of unknown size, start an asynchronous copy the service runs in
+    /// the background; its state is returned as the service accepted it.
+    pub async fn copy_from_url(
+        &mut self,
+        source: &Url,
+        container: &str,
+        blob_name: &str,
+        size: Option<u64>,
+    ) -> Result<CopyState, AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let target = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+
+        let context = || format!("Failed to copy {} to blob '{}'", source, blob_name);
+        let (id, status) = match size {
+            Some(size) if size <= SYNC_COPY_LIMIT => {
+                let response = target
+                    .copy_from_url(source.clone())
+                    .is_synchronous(true)
+                    .await
+                    .with_context(context)?;
+                (response.copy_id, response.copy_status)
+            }
+            _ => {
+                let response = target.copy(source.clone()).await.with_context(context)?;
+                (response.copy_id, response.copy_status)
+            }
+        };
+        Ok(CopyState {
+            id: Some(id.to_string()),
+            status,
+            progress: size
+                .filter(|_| status == CopyStatus::Success)
+                .map(|size| (size, size)),
+            description: None,
+        })
+    }
+
+    /// State of the last server-side copy into a blob, or None if the blob
+    /// wasn't written by one
+    pub async fn copy_state(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+    ) -> Result<Option<CopyState>, AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let properties = blob_service
+            .container_client(container)
+            .blob_client(blob_name)
+            .get_properties()
+            .await
+            .with_context(|| format!("Failed to check the copy of blob '{}'", blob_name))?
+            .blob
+            .properties;
+        Ok(properties.copy_status.map(|status| CopyState {
+            id: properties.copy_id.map(|id| id.to_string()),
+            status,
+            progress: properties
+                .copy_progress
+                .map(|progress| (progress.bytes_copied, progress.bytes_total)),
+            description: properties.copy_status_description,
+        }))
+    }
+
+    /// States of the server-side copies into the blobs under a prefix
+    pub async fn copy_states(
+        &mut self,
+        container: &str,
+        prefix: Option<&str>,
+    ) -> Result<Vec<(String, CopyState)>, AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let mut list_builder = blob_service
+            .container_client(container)
+            .list_blobs()
+            .include_copy(true);
+        if let Some(prefix) = prefix {
+            list_builder = list_builder.prefix(prefix.to_string());
+        }
+
+        let mut states = Vec::new();
+        let mut pages = cancel::guard(
+            list_builder
+                .into_stream()
+                .map(|page| page.context("Failed to fetch blob page")),
+            self.cancel.clone(),
+        )
+        .boxed();
+        while let Some(page) = pages.next().await {
+            for blob in page?.blobs.blobs() {
+                let properties = &blob.properties;
+                if let Some(status) = properties.copy_status {
+                    states.push((
+                        blob.name.clone(),
+                        CopyState {
+                            id: properties.copy_id.as_ref().map(|id| id.to_string()),
+                            status,
+                            progress: properties
+                                .copy_progress
+                                .as_ref()
+                                .map(|progress| (progress.bytes_copied, progress.bytes_total)),
+                            description: properties.copy_status_description.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+        Ok(states)
+    }
+
+    /// Stop a pending server-side copy, leaving an empty destination blob
+    pub async fn abort_copy(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        copy_id: &str,
+    ) -> Result<(), AzstError> {
+        let url = format!(
+            "{}?comp=copy&copyid={}",
+            self.blob_url(container, blob_name)?,
+            copy_id
+        );
+        let response = self
+            .storage_request(reqwest::Method::PUT, &url)
+            .await?
+            .header("x-ms-copy-action", "abort")
+            .header(reqwest::header::CONTENT_LENGTH, 0)
+            .send()
+            .await?;
+        check_storage_response(response, &format!("abort the copy into '{}'", blob_name)).await?;
+        Ok(())
+    }
+
+    /// Delete a single blob, presenting `lease_id` if the blob is leased
+    pub async fn delete_blob(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        lease_id: Option<LeaseId>,
+    ) -> Result<(), AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let mut request = blob_service
+            .container_client(container)
+            .blob_client(blob_name)
+            .delete();
+        if let Some(lease_id) = lease_id {
+            request = request.lease_id(lease_id);
+        }
+        request
+            .await
+            .with_context(|| format!("Failed to delete blob '{}'", blob_name))?;
+        Ok(())
+    }
+
+    /// Upload a local file as a block blob through the SDK, presenting
+    /// `lease_id` if the blob is leased (which azcopy can't do)
+    ///
+    /// Returns the number of bytes uploaded.
+    pub async fn upload_file(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        path: &std::path::Path,
+        content_type: Option<&str>,
+        lease_id: Option<LeaseId>,
+    ) -> Result<u64, AzstError> {
+        use tokio::io::AsyncReadExt;
+
+        // The SDK can't present a customer-provided key with blocks
+        if let Some(customer_key) = self.config.customer_key.clone() {
+            return Ok(self
+                .upload_file_with_key(
+                    container,
+                    blob_name,
+                    path,
+                    content_type,
+                    lease_id,
+                    &customer_key,
+                )
+                .await?);
+        }
+
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let size = file.metadata().await.ok().map(|metadata| metadata.len());
+        let progress_bar = output::file_progress_bar(blob_name, size);
+
+        let mut blocks = Vec::new();
+        let mut uploaded = 0;
+        loop {
+            let mut chunk = Vec::new();
+            (&mut file)
+                .take(UPLOAD_BLOCK_SIZE)
+                .read_to_end(&mut chunk)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            if chunk.is_empty() && !blocks.is_empty() {
+                break;
+            }
+            let block_id = BlockId::new(format!("azst-upload-{:08}", blocks.len()));
+            let length = chunk.len() as u64;
+            uploaded += length;
+            let last = length < UPLOAD_BLOCK_SIZE;
+
+            let mut request = blob_client.put_block(block_id.clone(), chunk);
+            if let Some(lease_id) = lease_id {
+                request = request.lease_id(lease_id);
+            }
+            request
+                .await
+                .with_context(|| format!("Failed to upload a block of '{}'", blob_name))?;
+            progress_bar.inc(length);
+            blocks.push(BlobBlockType::new_uncommitted(block_id));
+            if last {
+                break;
+            }
+        }
+
+        let mut request = blob_client.put_block_list(BlockList { blocks });
+        if let Some(content_type) = content_type {
+            request = request.content_type(content_type.to_string());
+        }
+        if let Some(lease_id) = lease_id {
+            request = request.lease_id(lease_id);
+        }
+        request
+            .await
+            .with_context(|| format!("Failed to commit blob '{}'", blob_name))?;
+        Ok(uploaded)
+    }
+
+    /// Upload a local file as a page blob through the SDK, zero-padding it to
+    /// a whole number of pages, which azcopy refuses to do
+    ///
+    /// Ranges that are all zeros are left unwritten, as a new page blob reads
+    /// as zeros anyway. Returns the size of the file.
+    pub async fn upload_page_blob(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        path: &Path,
+        headers: &BlobHeaders,
+    ) -> Result<u64, AzstError> {
+        use tokio::io::AsyncReadExt;
+
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let size = file.metadata().await?.len();
+        let progress_bar = output::file_progress_bar(blob_name, Some(size));
+
+        let mut request = blob_client.put_page_blob(size.next_multiple_of(PAGE_SIZE) as u128);
+        if let Some(content_type) = &headers.content_type {
+            request = request.content_type(content_type.clone());
+        }
+        if let Some(content_encoding) = &headers.content_encoding {
+            request = request.content_encoding(content_encoding.clone());
+        }
+        if !headers.metadata.is_empty() {
+            let mut metadata = azure_core::request_options::Metadata::new();
+            for (key, value) in &headers.metadata {
+                metadata.insert(key.clone(), value.clone());
+            }
+            request = request.metadata(metadata);
+        }
+        request
+            .await
+            .with_context(|| format!("Failed to create page blob '{}'", blob_name))?;
+
+        let mut offset = 0;
+        while offset < size {
+            let mut chunk = Vec::new();
+            (&mut file)
+                .take(PAGE_WRITE_SIZE)
+                .read_to_end(&mut chunk)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            if chunk.is_empty() {
+                break;
+            }
+            let length = chunk.len() as u64;
+            if chunk.iter().any(|byte| *byte != 0) {
+                chunk.resize(length.next_multiple_of(PAGE_SIZE) as usize, 0);
+                let range = BA512Range::new(offset, offset + chunk.len() as u64 - 1)?;
+                blob_client
+                    .put_page(range, chunk)
+                    .await
+                    .with_context(|| format!("Failed to write pages of '{}'", blob_name))?;
+            }
+            offset += length;
+            progress_bar.inc(length);
+        }
+        Ok(size)
+    }
+
+    /// Upload a block blob from chunks produced elsewhere (e.g. an archive
+    /// being written), staging each chunk as a block as it arrives
+    ///
+    /// The blob is committed once the sender is dropped; an error sent in
+    /// place of a chunk aborts the upload before that. Returns the number of
+    /// bytes uploaded.
+    pub async fn upload_stream(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        mut chunks: tokio::sync::mpsc::Receiver<Result<Vec<u8>>>,
+        content_type: Option<&str>,
+    ) -> Result<u64, AzstError> {
+        let blob_service = self.get_blob_service_client().await?;
+        let blob_client = blob_service
+            .container_client(container)
+            .blob_client(blob_name);
+
+        let progress_bar = output::file_progress_bar(blob_name, None);
+        let mut blocks = Vec::new();
+        let mut uploaded = 0;
+        while let Some(chunk) = chunks.recv().await {
+            let chunk = chunk?;
+            if chunk.is_empty() {
+                continue;
+            }
+            let block_id = BlockId::new(format!("azst-upload-{:08}", blocks.len()));
+            let length = chunk.len() as u64;
+            uploaded += length;
+            blob_client
+                .put_block(block_id.clone(), chunk)
+                .await
+                .with_context(|| format!("Failed to upload a block of '{}'", blob_name))?;
+            progress_bar.inc(length);
+            blocks.push(BlobBlockType::new_uncommitted(block_id));
+        }
+
+        let mut request = blob_client.put_block_list(BlockList { blocks });
+        if let Some(content_type) = content_type {
+            request = request.content_type(content_type.to_string());
+        }
+        request
+            .await
+            .with_context(|| format!("Failed to commit blob '{}'", blob_name))?;
+        Ok(uploaded)
+    }
+
+    /// Acquire a lease on a container, or on one of its blobs
+    ///
+    /// The service picks the lease ID unless one is proposed.
+    pub async fn acquire_lease(
+        &mut self,
+        container: &str,
+        blob_name: Option<&str>,
+        duration: LeaseDuration,
+        proposed_id: Option<LeaseId>,
+    ) -> Result<LeaseId, AzstError> {
+        let container_client = self
+            .get_blob_service_client()
+            .await?
+            .container_client(container);
+        let lease_id = match blob_name {
+            Some(blob_name) => {
+                let mut request = container_client
+                    .blob_client(blob_name)
+                    .acquire_lease(duration);
+                if let Some(proposed_id) = proposed_id {
+                    request = request.proposed_lease_id(proposed_id);
+                }
+                request.await.map(|response| response.lease_id)
+            }
+            None => {
+                let mut request = container_client.acquire_lease(duration);
+                if let Some(proposed_id) = proposed_id {
+                    request = request.proposed_lease_id(proposed_id);
+                }
+                request.await.map(|response| response.lease_id)
+            }
+        };
+        Ok(lease_id.with_context(|| format!("Failed to acquire a lease on '{}'", container))?)
+    }
+
+    /// Extend a lease by the duration it was acquired with
+    pub async fn renew_lease(
+        &mut self,
+        container: &str,
+        blob_name: Option<&str>,
+        lease_id: LeaseId,
+    ) -> Result<(), AzstError> {
+        let container_client = self
+            .get_blob_service_client()
+            .await?
+            .container_client(container);
+        let result = match blob_name {
+            Some(blob_name) => container_client
+                .blob_client(blob_name)
+                .blob_lease_client(lease_id)
+                .renew()
+                .await
+                .map(|_| ()),
+            None => container_client
+                .container_lease_client(lease_id)
+                .renew()
+                .await
+                .map(|_| ()),
+        };
+        Ok(result.with_context(|| format!("Failed to renew lease {}", lease_id))?)
+    }
+
+    /// Give up a lease so others can write (or lease) right away
+    pub async fn release_lease(
+        &mut self,
+        container: &str,
+        blob_name: Option<&str>,
+        lease_id: LeaseId,
+    ) -> Result<(), AzstError> {
+        let container_client = self
+            .get_blob_service_client()
+            .await?
+            .container_client(container);
+        let result = match blob_name {
+            Some(blob_name) => container_client
+                .blob_client(blob_name)
+                .blob_lease_client(lease_id)
+                .release()
+                .await
+                .map(|_| ()),
+            None => container_client
+                .container_lease_client(lease_id)
+                .release()
+                .await
+                .map(|_| ()),
+        };
+        Ok(result.with_context(|| format!("Failed to release lease {}", lease_id))?)
+    }
+
+    /// End a lease without knowing its ID, after at most `break_period`
+    ///
+    /// Returns the seconds left until the lease is broken.
+    pub async fn break_lease(
+        &mut self,
+        container: &str,
+        blob_name: Option<&str>,
+        break_period: Option<Duration>,
+    ) -> Result<u8, AzstError> {
+        let container_client = self
+            .get_blob_service_client()
+            .await?
+            .container_client(container);
+        let result = match blob_name {
+            Some(blob_name) => {
+                let mut request = container_client.blob_client(blob_name).break_lease();
+                if let Some(period) = break_period {
+                    request = request.lease_break_period(period);
+                }
+                request.await.map(|response| response.lease_time)
+            }
+            None => {
+                let mut request = container_client.break_lease();
+                if let Some(period) = break_period {
+                    request = request.lease_break_period(period);
+                }
+                request.await.map(|response| response.lease_time)
+            }
+        };
+        Ok(result.with_context(|| format!("Failed to break the lease on '{}'", container))?)
+    }
+
+    pub async fn container_settings(
+        &mut self,
+        container: &str,
+    ) -> Result<ContainerSettings, AzstError> {
+        let container_client = self
+            .get_blob_service_client()
+            .await?
+            .container_client(container);
+        let properties = container_client
+            .get_properties()
+            .await
+            .with_context(|| format!("Failed to get the properties of '{}'", container))?
+            .container;
+        let acl = container_client
+            .get_acl()
+            .await
+            .with_context(|| format!("Failed to get the access policy of '{}'", container))?;
+
+        let format_time = |time: time::OffsetDateTime| {
+            time.format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default()
+        };
+        Ok(ContainerSettings {
+            name: properties.name,
+            public_access: match acl.public_access {
+                PublicAccess::None => "off".to_string(),
+                level => <&str>::from(level).to_string(),
+            },
+            last_modified: format_time(properties.last_modified),
+            lease_state: <&str>::from(properties.lease_state).to_lowercase(),
+            has_immutability_policy: properties.has_immutability_policy,
+            has_legal_hold: properties.has_legal_hold,
+            metadata: properties.metadata.into_iter().collect(),
+            access_policies: acl
+                .stored_access_policy_list
+                .stored_access
+                .into_iter()
+                .map(|policy| AccessPolicyInfo {
+                    id: policy.id,
+                    start: format_time(policy.start),
+                    expiry: format_time(policy.expiry),
+                    permission: policy.permission,
+                })
+                .collect(),
+        })
+    }
+
+    /// Change anonymous read access to a container, keeping its stored
+    /// access policies (which the same request replaces)
+    pub async fn set_container_public_access(
+        &mut self,
+        container: &str,
+        public_access: PublicAccess,
+    ) -> Result<(), AzstError> {
+        let container_client = self
+            .get_blob_service_client()
+            .await?
+            .container_client(container);
+        let acl = container_client
+            .get_acl()
+            .await
+            .with_context(|| format!("Failed to get the access policy of '{}'", container))?;
+        container_client
+            .set_acl(public_access)
+            .stored_access_policy_list(acl.stored_access_policy_list)
+            .await
+            .with_context(|| format!("Failed to set the public access of '{}'", container))?;
+        Ok(())
+    }
+
+    /// Whether the account has a hierarchical namespace (Data Lake Storage
+    /// Gen2), which lets paths be renamed through the DFS endpoint
+    pub async fn is_hns_enabled(&mut self, container: &str) -> Result<bool, AzstError> {
+        let account = self.storage_account_name()?;
+        let url = format!(
+            "https://{}.blob.core.windows.net/{}?restype=account&comp=properties",
+            account, container
+        );
+        let response = self
+            .storage_request(reqwest::Method::GET, &url)
+            .await?
+            .send()
+            .await?;
+        let response = check_storage_response(response, "get account information").await?;
+
+        Ok(response
+            .headers()
+            .get("x-ms-is-hns-enabled")
+            .is_some_and(|value| value == "true"))
+    }
+
+    /// Rename a file or directory on an account with a hierarchical namespace
+    ///
+    /// A directory is renamed with everything under it in one atomic
+    /// operation. Missing parent directories of the new name are created.
+    pub async fn rename_path(
+        &mut self,
+        container: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<(), AzstError> {
+        let account = self.storage_account_name()?;
+        let dfs_url = |path: &str| {
+            format!(
+                "https://{}.dfs.core.windows.net/{}/{}",
+                account,
+                container,
+                utf8_percent_encode(path, PATH_ENCODE_SET)
+            )
+        };
+
+        if let Some((parent, _)) = to.rsplit_once('/') {
+            let response = self
+                .storage_request(
+                    reqwest::Method::PUT,
+                    &format!("{}?resource=directory", dfs_url(parent)),
+                )
+                .await?
+                .header("If-None-Match", "*")
+                .send()
+                .await?;
+            if response.status() != reqwest::StatusCode::CONFLICT {
+                check_storage_response(response, "create the parent directory").await?;
+            }
+        }
+
+        let response = self
+            .storage_request(
+                reqwest::Method::PUT,
+                &format!("{}?mode=legacy", dfs_url(to)),
+            )
+            .await?
+            .header(
+                "x-ms-rename-source",
+                format!(
+                    "/{}/{}",
+                    container,
+                    utf8_percent_encode(from, PATH_ENCODE_SET)
+                ),
+            )
+            .send()
+            .await?;
+        check_storage_response(response, &format!("rename '{}'", from)).await?;
+        Ok(())
+    }
+
+    /// HTTPS URL of a blob in the client's account, without credentials
+    pub fn blob_url(&self, container: &str, blob_name: &str) -> Result<String, AzstError> {
+        Ok(format!(
+            "https://{}.blob.core.windows.net/{}/{}",
+            self.storage_account_name()?,
+            container,
+            utf8_percent_encode(blob_name, PATH_ENCODE_SET)
+        ))
+    }
+
+    /// Keep the current version of a blob from being changed or deleted
+    /// until `until`. A locked policy can be extended but never shortened or
+    /// removed.
+    pub async fn set_blob_immutability_policy(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        until: chrono::DateTime<chrono::Utc>,
+        locked: bool,
+    ) -> Result<(), AzstError> {
+        let url = format!(
+            "{}?comp=immutabilityPolicies",
+            self.blob_url(container, blob_name)?
+        );
+        let response = self
+            .storage_request(reqwest::Method::PUT, &url)
+            .await?
+            .header(
+                "x-ms-immutability-policy-until-date",
+                until.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+            )
+            .header(
+                "x-ms-immutability-policy-mode",
+                if locked { "Locked" } else { "Unlocked" },
+            )
+            .send()
+            .await?;
+        check_storage_response(
+            response,
+            &format!("set the retention policy of '{}'", blob_name),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Remove the unlocked retention policy of a blob
+    pub async fn delete_blob_immutability_policy(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+    ) -> Result<(), AzstError> {
+        let url = format!(
+            "{}?comp=immutabilityPolicies",
+            self.blob_url(container, blob_name)?
+        );
+        let response = self
+            .storage_request(reqwest::Method::DELETE, &url)
+            .await?
+            .send()
+            .await?;
+        check_storage_response(
+            response,
+            &format!("remove the retention policy of '{}'", blob_name),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Place or lift a legal hold, which keeps a blob from being changed or
+    /// deleted regardless of its retention policy
+    pub async fn set_blob_legal_hold(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        hold: bool,
+    ) -> Result<(), AzstError> {
+        let url = format!("{}?comp=legalhold", self.blob_url(container, blob_name)?);
+        let response = self
+            .storage_request(reqwest::Method::PUT, &url)
+            .await?
+            .header("x-ms-legal-hold", hold.to_string())
+            .send()
+            .await?;
+        check_storage_response(response, &format!("set the legal hold of '{}'", blob_name)).await?;
+        Ok(())
+    }
+
+    pub async fn blob_immutability(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+    ) -> Result<BlobImmutability, AzstError> {
+        let url = self.blob_url(container, blob_name)?;
+        let response = self
+            .storage_request(reqwest::Method::HEAD, &url)
+            .await?
+            .send()
+            .await?;
+        let response =
+            check_storage_response(response, &format!("get the properties of '{}'", blob_name))
+                .await?;
+
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Ok(BlobImmutability {
+            until: header("x-ms-immutability-policy-until-date"),
+            mode: header("x-ms-immutability-policy-mode"),
+            legal_hold: header("x-ms-legal-hold").is_some_and(|hold| hold == "true"),
+        })
+    }
+
+    /// Get the properties of a blob encrypted with `customer_key`
+    async fn blob_properties_with_key(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        customer_key: &CustomerKey,
+    ) -> Result<BlobInfo> {
+        let url = self.blob_url(container, blob_name)?;
+        let mut request = self.storage_request(reqwest::Method::HEAD, &url).await?;
+        for (name, value) in customer_key.headers() {
+            request = request.header(name, value);
+        }
+        let response = check_storage_response(
+            request.send().await?,
+            &format!("get properties of blob '{}'", blob_name),
+        )
+        .await?;
+        blob_info_from_headers(blob_name, response.headers())
+    }
+
+    /// Upload a local file as a block blob encrypted with `customer_key`,
+    /// in blocks like `upload_file`
+    async fn upload_file_with_key(
+        &mut self,
+        container: &str,
+        blob_name: &str,
+        path: &std::path::Path,
+        content_type: Option<&str>,
+        lease_id: Option<LeaseId>,
+        customer_key: &CustomerKey,
+    ) -> Result<u64> {
+        use base64::Engine;
+        use tokio::io::AsyncReadExt;
+
+        let blob_url = self.blob_url(container, blob_name)?;
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let size = file.metadata().await.ok().map(|metadata| metadata.len());
+        let progress_bar = output::file_progress_bar(blob_name, size);
+
+        let mut block_ids = Vec::new();
+        let mut uploaded = 0;
+        loop {
+            let mut chunk = Vec::new();
+            (&mut file)
+                .take(UPLOAD_BLOCK_SIZE)
+                .read_to_end(&mut chunk)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            if chunk.is_empty() && !block_ids.is_empty() {
+                break;
+            }
+            let block_id = base64::engine::general_purpose::STANDARD
+                .encode(format!("azst-upload-{:08}", block_ids.len()));
+            let length = chunk.len() as u64;
+            uploaded += length;
+            let last = length < UPLOAD_BLOCK_SIZE;
+
+            let url = format!(
+                "{}?comp=block&blockid={}",
+                blob_url,
+                utf8_percent_encode(&block_id, NON_ALPHANUMERIC)
+            );
+            let mut request = self.storage_request(reqwest::Method::PUT, &url).await?;
+            for (name, value) in customer_key.headers() {
+                request = request.header(name, value);
+            }
+            if let Some(lease_id) = lease_id {
+                request = request.header("x-ms-lease-id", lease_id.to_string());
+            }
+            check_storage_response(
+                request.body(chunk).send().await?,
+                &format!("upload a block of '{}'", blob_name),
+            )
+            .await?;
+            progress_bar.inc(length);
+            block_ids.push(block_id);
+            if last {
+                break;
+            }
+        }
+
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?><BlockList>{}</BlockList>",
+            block_ids
+                .iter()
+                .map(|id| format!("<Latest>{}</Latest>", id))
+                .collect::<String>()
+        );
+        let url = format!("{}?comp=blocklist", blob_url);
+        let mut request = self.storage_request(reqwest::Method::PUT, &url).await?;
+        for (name, value) in customer_key.headers() {
+            request = request.header(name, value);
+        }
+        if let Some(content_type) = content_type {
+            request = request.header("x-ms-blob-content-type", content_type);
+        }
+        if let Some(lease_id) = lease_id {
+            request = request.header("x-ms-lease-id", lease_id.to_string());
+        }
+        check_storage_response(
+            request.body(body).send().await?,
+            &format!("commit blob '{}'", blob_name),
+        )
+        .await?;
+        Ok(uploaded)
+    }
+
+    /// Management client, subscription and resource group of the account,
+    /// for container settings only the resource provider exposes
+    async fn account_resource(&mut self) -> Result<(azure_mgmt_storage::Client, String, String)> {
+        let account = self.storage_account_name()?;
+        let subscription_id = self.get_subscription_id().await?;
+        let resource_group = self
+            .list_storage_accounts()
+            .await?
+            .into_iter()
+            .find(|info| info.name == account)
+            .map(|info| info.resource_group)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Storage account '{}' isn't in subscription {}",
+                    account,
+                    subscription_id
+                )
+            })?;
+        let client = azure_mgmt_storage::Client::builder(self.get_credential().await?)
+            .retry(retry_options()?)
+            .transport(TransportOptions::new(network::sdk_http_client()?))
+            .build()?;
+        Ok((client, subscription_id, resource_group))
+    }
+
+    pub async fn container_immutability(
+        &mut self,
+        container: &str,
+    ) -> Result<ContainerImmutability, AzstError> {
+        let account = self.storage_account_name()?;
+        let (client, subscription_id, resource_group) = self.account_resource().await?;
+        let properties = client
+            .blob_containers_client()
+            .get(resource_group, account, container, subscription_id)
+            .await
+            .with_context(|| format!("Failed to get the properties of '{}'", container))?
+            .properties
+            .unwrap_or_default();
+
+        let policy = properties.immutability_policy.unwrap_or_default();
+        let retention = policy.properties.unwrap_or_default();
+        Ok(ContainerImmutability {
+            retention_days: retention.immutability_period_since_creation_in_days,
+            locked: matches!(retention.state, Some(PolicyState::Locked)),
+            etag: policy.etag,
+            legal_hold_tags: properties
+                .legal_hold
+                .map(|hold| hold.tags.into_iter().filter_map(|tag| tag.tag).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Keep every blob in a container for `days` after it's created, locking
+    /// the policy for good if asked
+    pub async fn set_container_retention(
+        &mut self,
+        container: &str,
+        days: i64,
+        locked: bool,
+    ) -> Result<(), AzstError> {
+        let account = self.storage_account_name()?;
+        let (client, subscription_id, resource_group) = self.account_resource().await?;
+        let containers = client.blob_containers_client();
+
+        let mut property = ImmutabilityPolicyProperty::new();
+        property.immutability_period_since_creation_in_days = Some(days);
+        let policy = containers
+            .create_or_update_immutability_policy(
+                &resource_group,
+                &account,
+                container,
+                CONTAINER_POLICY_NAME,
+                &subscription_id,
+            )
+            .parameters(ImmutabilityPolicy::new(property))
+            .await
+            .with_context(|| format!("Failed to set the retention policy of '{}'", container))?;
+
+        if locked {
+            let etag = policy
+                .azure_entity_resource
+                .etag
+                .ok_or_else(|| anyhow!("The service returned no ETag for the new policy"))?;
+            containers
+                .lock_immutability_policy(
+                    &resource_group,
+                    &account,
+                    container,
+                    &subscription_id,
+                    etag,
+                )
+                .await
+                .with_context(|| {
+                    format!("Failed to lock the retention policy of '{}'", container)
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Remove the unlocked retention policy of a container, returning
+    /// whether there was one
+    pub async fn delete_container_retention(&mut self, container: &str) -> Result<bool, AzstError> {
+        let current = self.container_immutability(container).await?;
+        let Some(etag) = current.etag.filter(|_| current.retention_days.is_some()) else {
+            return Ok(false);
+        };
+        if current.locked {
+            return Err(anyhow!(
+                "The retention policy of '{}' is locked and can't be removed",
+                container
+            )
+            .into());
+        }
+
+        let account = self.storage_account_name()?;
+        let (client, subscription_id, resource_group) = self.account_resource().await?;
+        client
+            .blob_containers_client()
+            .delete_immutability_policy(
+                resource_group,
+                account,
+                container,
+                CONTAINER_POLICY_NAME,
+                subscription_id,
+                etag,
+            )
+            .await
+            .with_context(|| format!("Failed to remove the retention policy of '{}'", container))?;
+        Ok(true)
+    }
+
+    /// Add (or with `hold` false, remove) legal hold tags on a container,
+    /// returning the tags it holds afterwards
+    pub async fn set_container_legal_hold(
+        &mut self,
+        container: &str,
+        tags: &[String],
+        hold: bool,
+    ) -> Result<Vec<String>, AzstError> {
+        let account = self.storage_account_name()?;
+        let (client, subscription_id, resource_group) = self.account_resource().await?;
+        let containers = client.blob_containers_client();
+        let legal_hold = LegalHold::new(tags.to_vec());
+        let result = if hold {
+            containers
+                .set_legal_hold(
+                    resource_group,
+                    account,
+                    container,
+                    subscription_id,
+                    legal_hold,
+                )
+                .await
+        } else {
+            containers
+                .clear_legal_hold(
+                    resource_group,
+                    account,
+                    container,
+                    subscription_id,
+                    legal_hold,
+                )
+                .await
+        };
+        Ok(result
+            .with_context(|| format!("Failed to update the legal hold of '{}'", container))?
+            .tags)
+    }
 
-            // Call the callback with this page's items
-            if !items.is_empty() {
-                callback(items)?;
+    /// The account's lifecycle management policy, if it has one
+    pub async fn lifecycle_policy(&mut self) -> Result<Option<ManagementPolicySchema>, AzstError> {
+        let account = self.storage_account_name()?;
+        let (client, subscription_id, resource_group) = self.account_resource().await?;
+        let result = client
+            .management_policies_client()
+            .get(
+                resource_group,
+                &account,
+                subscription_id,
+                ACCOUNT_POLICY_NAME,
+            )
+            .await;
+        match result {
+            Ok(policy) => Ok(policy.properties.map(|properties| properties.policy)),
+            Err(e)
+                if e.as_http_error()
+                    .is_some_and(|e| e.status() == StatusCode::NotFound) =>
+            {
+                Ok(None)
             }
+            Err(e) => Err(anyhow::Error::from(e)
+                .context(format!(
+                    "Failed to get the lifecycle policy of '{}'",
+                    account
+                ))
+                .into()),
         }
+    }
 
+    /// Replace the account's lifecycle management policy
+    pub async fn set_lifecycle_policy(
+        &mut self,
+        policy: ManagementPolicySchema,
+    ) -> Result<(), AzstError> {
+        let account = self.storage_account_name()?;
+        let (client, subscription_id, resource_group) = self.account_resource().await?;
+        let policy = ManagementPolicy {
+            properties: Some(ManagementPolicyProperties::new(policy)),
+            ..Default::default()
+        };
+        client
+            .management_policies_client()
+            .create_or_update(
+                resource_group,
+                &account,
+                subscription_id,
+                ACCOUNT_POLICY_NAME,
+                policy,
+            )
+            .await
+            .with_context(|| format!("Failed to set the lifecycle policy of '{}'", account))?;
         Ok(())
     }
 
+    /// Authorization, anonymous access and data protection settings of the
+    /// account
+    pub async fn account_security(&mut self) -> Result<AccountSecurity, AzstError> {
+        let account = self.storage_account_name()?;
+        let (client, subscription_id, resource_group) = self.account_resource().await?;
+        let properties = client
+            .storage_accounts_client()
+            .get_properties(&resource_group, &account, &subscription_id)
+            .await
+            .with_context(|| format!("Failed to get the properties of '{}'", account))?
+            .properties
+            .unwrap_or_default();
+        let blob_service = client
+            .blob_services_client()
+            .get_service_properties(resource_group, &account, subscription_id, BLOB_SERVICE_NAME)
+            .await
+            .with_context(|| format!("Failed to get the blob service settings of '{}'", account))?
+            .properties
+            .unwrap_or_default();
+
+        let retention_days = |policy: Option<DeleteRetentionPolicy>| {
+            policy
+                .filter(|policy| policy.enabled == Some(true))
+                .map(|policy| policy.days.unwrap_or_default())
+        };
+        // Both default to allowed when the account has never set them
+        Ok(AccountSecurity {
+            allow_shared_key_access: properties.allow_shared_key_access.unwrap_or(true),
+            allow_blob_public_access: properties.allow_blob_public_access.unwrap_or(true),
+            blob_soft_delete_days: retention_days(blob_service.delete_retention_policy),
+            container_soft_delete_days: retention_days(
+                blob_service.container_delete_retention_policy,
+            ),
+            versioning: blob_service.is_versioning_enabled == Some(true),
+        })
+    }
+
+    fn storage_account_name(&self) -> Result<String> {
+        self.get_storage_account()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Storage account not configured"))
+    }
+
+    /// A REST request to the storage service, for operations the SDK lacks
+    async fn storage_request(
+        &mut self,
+        method: reqwest::Method,
+        url: &str,
+    ) -> Result<reqwest::RequestBuilder> {
+        let token = self.access_token(STORAGE_SCOPE).await?;
+        Ok(network::http_client()?
+            .request(method, url)
+            .bearer_auth(token.token.secret())
+            .header("x-ms-version", STORAGE_API_VERSION))
+    }
+
     /// Download a blob's content as bytes
     /// Returns the blob content and optionally a range of bytes
     pub async fn download_blob(
@@ -577,16 +3303,19 @@ impl AzureClient {
         container: &str,
         blob_name: &str,
         range: Option<(u64, u64)>,
-    ) -> Result<Vec<u8>> {
+    ) -> Result<Vec<u8>, AzstError> {
         let blob_service = self.get_blob_service_client().await?;
         let container_client = blob_service.container_client(container);
         let blob_client = container_client.blob_client(blob_name);
+        let mut request = blob_client.get();
+        if let Some(customer_key) = &self.config.customer_key {
+            request = request.encryption_key(customer_key.cpk_info());
+        }
 
         // Get the blob content
         let response = if let Some((start, end)) = range {
             // Download with range (exclusive end)
-            blob_client
-                .get()
+            request
                 .range(start..end + 1)
                 .into_stream()
                 .next()
@@ -601,8 +3330,7 @@ impl AzureClient {
                 })??
         } else {
             // Download entire blob
-            blob_client
-                .get()
+            request
                 .into_stream()
                 .next()
                 .await
@@ -615,25 +3343,203 @@ impl AzureClient {
     }
 }
 
+/// Convert an SDK blob into our BlobInfo representation
+/// The name a management API enum serializes to, e.g. "BlockBlobStorage"
+fn enum_name<T: serde::Serialize>(value: &T) -> Option<String> {
+    match serde_json::to_value(value).ok()? {
+        serde_json::Value::String(name) => Some(name),
+        _ => None,
+    }
+}
+
+fn to_blob_info(blob: &azure_storage_blobs::blob::Blob) -> BlobInfo {
+    BlobInfo {
+        name: blob.name.clone(),
+        properties: BlobProperties {
+            content_length: blob.properties.content_length,
+            last_modified: blob.properties.last_modified.to_string(),
+            content_type: Some(blob.properties.content_type.clone()),
+            last_modified_unix: blob.properties.last_modified.unix_timestamp(),
+            content_md5: blob
+                .properties
+                .content_md5
+                .as_ref()
+                .map(|md5| to_hex(md5.as_slice())),
+            content_encoding: blob.properties.content_encoding.clone(),
+            etag: Some(blob.properties.etag.to_string()),
+            access_tier: blob
+                .properties
+                .access_tier
+                .map(|tier| <&str>::from(tier).to_string()),
+            blob_type: Some(<&str>::from(blob.properties.blob_type).to_string()),
+            lease_state: blob
+                .properties
+                .lease_state
+                .map(|state| <&str>::from(state).to_string()),
+        },
+        version_count: None,
+        tags: blob.tags.as_ref().map(|tags| {
+            tags.tag_set
+                .tags
+                .iter()
+                .map(|tag| (tag.key.clone(), tag.value.clone()))
+                .collect()
+        }),
+    }
+}
+
+/// Build a BlobInfo from the response headers of a HEAD request
+fn blob_info_from_headers(name: &str, headers: &reqwest::header::HeaderMap) -> Result<BlobInfo> {
+    use base64::Engine;
+
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_length = header("content-length")
+        .and_then(|length| length.parse().ok())
+        .ok_or_else(|| anyhow!("No Content-Length for blob '{}'", name))?;
+    let last_modified = header("last-modified").unwrap_or_default();
+    Ok(BlobInfo {
+        name: name.to_string(),
+        properties: BlobProperties {
+            content_length,
+            last_modified_unix: chrono::DateTime::parse_from_rfc2822(&last_modified)
+                .map(|time| time.timestamp())
+                .unwrap_or_default(),
+            last_modified,
+            content_type: header("content-type"),
+            content_md5: header("content-md5").and_then(|md5| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(md5)
+                    .ok()
+                    .map(|md5| to_hex(&md5))
+            }),
+            content_encoding: header("content-encoding"),
+            etag: header("etag"),
+            access_tier: header("x-ms-access-tier"),
+            blob_type: header("x-ms-blob-type"),
+            lease_state: header("x-ms-lease-state"),
+        },
+        version_count: None,
+        tags: None,
+    })
+}
+
+/// Size of the blocks files are uploaded in through the SDK
+const UPLOAD_BLOCK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Largest range written at once to a page blob, the most Put Page accepts
+const PAGE_WRITE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Largest range staged per block when composing blobs
+const COMPOSE_BLOCK_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Largest source the service copies from a URL in a single request;
+/// larger ones are copied asynchronously
+pub const SYNC_COPY_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// How often to check on a server-side copy that hasn't finished yet
+const COPY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// REST API version for the requests azst makes without the SDK
+const STORAGE_API_VERSION: &str = "2021-08-06";
+
+/// Containers have a single time-based retention policy, always named this
+const CONTAINER_POLICY_NAME: &str = "default";
+
+/// Accounts have a single lifecycle management policy, always named this
+const ACCOUNT_POLICY_NAME: &str = "default";
+
+/// Accounts have a single blob service, always named this
+const BLOB_SERVICE_NAME: &str = "default";
+
+/// Characters escaped in blob and DFS paths; `/` separates path segments
+pub const PATH_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Turn an unsuccessful storage REST response into an error with the
+/// service's error code
+async fn check_storage_response(
+    response: reqwest::Response,
+    action: &str,
+) -> Result<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let code = response
+        .headers()
+        .get("x-ms-error-code")
+        .and_then(|code| code.to_str().ok())
+        .unwrap_or("no error code")
+        .to_string();
+    Err(anyhow!("Failed to {}: {} ({})", action, status, code))
+}
+
+/// Longest validity Azure accepts for a user delegation key
+const MAX_SIGNED_URL_VALIDITY: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 3600);
+
+/// Produces time-limited, read-only HTTPS URLs for blobs in one storage account
+pub struct UrlSigner {
+    account: String,
+    key: UserDeligationKey,
+}
+
+impl UrlSigner {
+    /// Signed URL granting read access to a blob until the key expires
+    pub fn sign(&self, container: &str, blob: &str) -> Result<String, AzstError> {
+        let mut url = Url::parse(&format!("https://{}.blob.core.windows.net/", self.account))
+            .with_context(|| format!("Invalid storage account name '{}'", self.account))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid blob URL for account '{}'", self.account))?
+            .pop_if_empty()
+            .push(container)
+            .extend(blob.split('/'));
+
+        let signature = BlobSharedAccessSignature::new(
+            self.key.clone(),
+            format!("/blob/{}/{}/{}", self.account, container, blob),
+            BlobSasPermissions {
+                read: true,
+                ..Default::default()
+            },
+            self.key.signed_expiry,
+            BlobSignedResource::Blob,
+        );
+        url.set_query(Some(&signature.token()?));
+
+        Ok(url.to_string())
+    }
+}
+
 // ============================================================================
 // AzCopy Client - High-performance operations
 // ============================================================================
 
 /// Convert az:// URI to AzCopy-compatible HTTPS URL
 /// Example: az://account/container/path -> https://account.blob.core.windows.net/container/path
-pub fn convert_az_uri_to_url(az_uri: &str) -> Result<String> {
+pub fn convert_az_uri_to_url(az_uri: &str) -> Result<String, AzstError> {
     if !az_uri.starts_with("az://") {
-        return Err(anyhow!("Invalid Azure URI format. Expected az://..."));
+        return Err(AzstError::InvalidUri(
+            "Invalid Azure URI format. Expected az://...".to_string(),
+        ));
     }
 
     let path = &az_uri[5..]; // Remove "az://"
     let parts: Vec<&str> = path.splitn(3, '/').collect();
 
     match parts.len() {
-        0 | 1 => Err(anyhow!(
+        0 | 1 => Err(AzstError::InvalidUri(format!(
             "Invalid Azure URI '{}'. Expected format: az://account/container/[path]",
             az_uri
-        )),
+        ))),
         2 => {
             // az://account/container
             Ok(format!(
@@ -648,10 +3554,23 @@ pub fn convert_az_uri_to_url(az_uri: &str) -> Result<String> {
                 parts[0], parts[1], parts[2]
             ))
         }
-        _ => Err(anyhow!("Failed to parse Azure URI '{}'", az_uri)),
+        _ => Err(AzstError::InvalidUri(format!(
+            "Failed to parse Azure URI '{}'",
+            az_uri
+        ))),
     }
 }
 
+/// Convert a blob URL back to an az:// URI, dropping any query string such
+/// as a SAS token
+pub fn convert_url_to_az_uri(url: &str) -> Option<String> {
+    let (account, path) = url
+        .strip_prefix("https://")?
+        .split_once(".blob.core.windows.net/")?;
+    let path = path.split('?').next().unwrap_or(path);
+    Some(format!("az://{}/{}", account, path))
+}
+
 // ============================================================================
 // AzCopy Path Utilities
 // ============================================================================
@@ -696,8 +3615,8 @@ fn parse_azcopy_version(version_output: &str) -> Option<String> {
         .map(|v| v.to_string())
 }
 
-/// Check if the given AzCopy executable matches our pinned version
-async fn check_azcopy_version(azcopy_path: &str) -> Result<bool> {
+/// Get the version reported by an AzCopy executable, if it runs
+pub async fn get_azcopy_version(azcopy_path: &str) -> Result<Option<String>> {
     let output = AsyncCommand::new(azcopy_path)
         .arg("--version")
         .output()
@@ -705,17 +3624,21 @@ async fn check_azcopy_version(azcopy_path: &str) -> Result<bool> {
         .context("Failed to get AzCopy version")?;
 
     if !output.status.success() {
-        return Ok(false);
+        return Ok(None);
     }
 
     let version_str = String::from_utf8_lossy(&output.stdout);
-    let version = parse_azcopy_version(&version_str);
+    Ok(parse_azcopy_version(&version_str))
+}
 
+/// Check if the given AzCopy executable matches our pinned version
+async fn check_azcopy_version(azcopy_path: &str) -> Result<bool> {
+    let version = get_azcopy_version(azcopy_path).await?;
     Ok(version.as_deref() == Some(AZCOPY_PINNED_VERSION))
 }
 
 /// Determine which AzCopy executable to use (system or bundled)
-async fn determine_azcopy_executable() -> Result<String> {
+pub async fn determine_azcopy_executable() -> Result<String> {
     // First, try system azcopy if it matches our pinned version
     if let Ok(true) = check_azcopy_version("azcopy").await {
         return Ok("azcopy".to_string());
@@ -733,15 +3656,34 @@ async fn determine_azcopy_executable() -> Result<String> {
     Ok("azcopy".to_string())
 }
 
+/// `azcopy jobs resume` for a job that was cancelled to pause or re-throttle it
+fn resume_command(azcopy_path: &str, job_id: &str, cap_mbps: Option<f64>) -> AsyncCommand {
+    let mut cmd = AsyncCommand::new(azcopy_path);
+    cmd.args(["jobs", "resume", job_id]);
+    if let Some(mbps) = cap_mbps {
+        cmd.arg(format!("--cap-mbps={}", mbps));
+    }
+    cmd
+}
+
 #[derive(Clone)]
 pub struct AzCopyClient {
     azcopy_executable: Option<String>,
+    /// Cancels running jobs when the command is cancelled
+    cancel: CancellationToken,
+}
+
+impl Default for AzCopyClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AzCopyClient {
     pub fn new() -> Self {
         Self {
             azcopy_executable: None,
+            cancel: cancel::token(),
         }
     }
 
@@ -754,7 +3696,7 @@ impl AzCopyClient {
     }
 
     /// Check if AzCopy is installed and Azure CLI is authenticated
-    pub async fn check_prerequisites(&mut self) -> Result<()> {
+    pub async fn check_prerequisites(&mut self) -> Result<(), AzstError> {
         // Determine which azcopy executable to use and test it
         let azcopy_path = self.get_azcopy_executable().await?;
 
@@ -762,12 +3704,17 @@ impl AzCopyClient {
             .arg("--version")
             .output()
             .await
-            .context(
-                "AzCopy not found. Run the installation script again to download AzCopy, or install it manually from https://aka.ms/downloadazcopy",
-            )?;
+            .map_err(|e| {
+                AzstError::AzCopyMissing(format!(
+                    "AzCopy not found. Run 'azst azcopy install' to download it, or install it manually from https://aka.ms/downloadazcopy: {}",
+                    e
+                ))
+            })?;
 
         if !output.status.success() {
-            return Err(anyhow!("AzCopy is not working properly"));
+            return Err(AzstError::AzCopyMissing(
+                "AzCopy is not working properly".to_string(),
+            ));
         }
 
         // Verify version if we're using system azcopy
@@ -775,7 +3722,7 @@ impl AzCopyClient {
             let version_str = String::from_utf8_lossy(&output.stdout);
             let version = parse_azcopy_version(&version_str);
             if version.as_deref() != Some(AZCOPY_PINNED_VERSION) {
-                eprintln!("Warning: System AzCopy version {:?} doesn't match pinned version {}. Consider running the installation script to download the tested version.", version, AZCOPY_PINNED_VERSION);
+                tracing::warn!("System AzCopy version {:?} doesn't match pinned version {}. Run 'azst azcopy install' to download the tested version.", version, AZCOPY_PINNED_VERSION);
             }
         }
 
@@ -789,60 +3736,89 @@ impl AzCopyClient {
     }
 
     /// Copy files/directories using AzCopy with additional options
+    ///
+    /// A job that completed with failed transfers succeeds; its failures are
+    /// in the returned summary.
     pub async fn copy_with_options(
         &mut self,
         source: &str,
         destination: &str,
         options: &AzCopyOptions,
-    ) -> Result<()> {
+    ) -> Result<JobSummary, AzstError> {
+        let cancel = self.cancel.clone();
         let azcopy_path = self.get_azcopy_executable().await?;
-        let mut cmd = AsyncCommand::new(azcopy_path);
-        cmd.args(["copy", source, destination]);
-
-        // Apply common options
-        options.apply_to_command(&mut cmd);
-
-        // Use JSON output for better parsing
-        cmd.args(["--output-type", "json"]);
-
-        // IMPORTANT: Tell AzCopy to use Azure CLI credentials for authentication
-        // This is set via environment variable
-        cmd.env("AZCOPY_AUTO_LOGIN_TYPE", "AZCLI");
 
-        // Apply environment variable tuning settings
-        AzCopyOptions::apply_env_vars(&mut cmd);
+        // Writes are what gets throttled, so learn from the destination when
+        // it's in Azure, otherwise from the source being downloaded
+        let account = account_from_url(destination).or_else(|| account_from_url(source));
+        let mut concurrency = None;
+
+        let (summary, status) = run_job(
+            AzCopyOperation::Copy,
+            &cancel,
+            options.cap_mbps,
+            options.bandwidth_schedule.as_ref(),
+            !options.dry_run,
+            |job_id, cap_mbps| {
+                let mut cmd = match job_id {
+                    Some(job_id) => {
+                        let mut cmd = resume_command(azcopy_path, job_id, cap_mbps);
+                        if let Some(customer_key) = &options.customer_key {
+                            customer_key.apply_env(&mut cmd);
+                        }
+                        cmd
+                    }
+                    None => {
+                        let mut cmd = AsyncCommand::new(azcopy_path);
+                        cmd.args(["copy", &*native_path(source), &*native_path(destination)]);
+                        // Apply common options, at the rate the job runs with now
+                        AzCopyOptions {
+                            cap_mbps,
+                            ..options.clone()
+                        }
+                        .apply_to_command(&mut cmd);
+                        cmd
+                    }
+                };
 
-        // Capture stdout to parse JSON output
-        // All azcopy output goes to stdout with --output-type json
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::null()); // Discard stderr
+                // Use JSON output for better parsing
+                cmd.args(["--output-type", "json"]);
 
-        let mut child = cmd.spawn().context("Failed to execute azcopy copy")?;
+                // IMPORTANT: Tell AzCopy to use Azure CLI credentials for authentication
+                // This is set via environment variable
+                cmd.env("AZCOPY_AUTO_LOGIN_TYPE", "AZCLI");
 
-        // Process stdout
-        let failed_count = if let Some(stdout) = child.stdout.take() {
-            crate::azcopy_output::handle_azcopy_output(stdout).await?
-        } else {
-            0
-        };
+                // Apply environment variable tuning settings
+                options.apply_env_vars(&mut cmd);
 
-        let status = child.wait().await.context("Failed to wait for azcopy")?;
+                concurrency = account.and_then(|account| {
+                    apply_learned_concurrency(&mut cmd, account, options.tuned_concurrency)
+                });
+                cmd
+            },
+        )
+        .await?;
+        if let (Some(account), Some(concurrency)) = (account, concurrency) {
+            record_server_busy(account, concurrency, summary.server_busy_percent);
+        }
+        audit_log::record_job(&summary);
+        symlinks::warn_skipped("the source", summary.skipped_links, options.symlinks);
 
         // Exit code 1 with failed transfers is expected - show warning but don't fail
         if !status.success() {
-            if failed_count > 0 {
-                // CompletedWithErrors - warning already shown, don't fail the operation
-                return Ok(());
+            if summary.failed_count > 0 {
+                // CompletedWithErrors - warning already shown; --strict fails the command later
+                return Ok(summary);
             } else {
                 // Actual failure
-                return Err(anyhow!(
+                return Err(AzstError::TransferFailed(format!(
                     "AzCopy operation failed with exit code: {}",
                     status.code().unwrap_or(-1)
-                ));
+                )));
             }
         }
 
-        Ok(())
+        Ok(summary)
     }
 
     /// Sync directories using AzCopy with additional options
@@ -852,63 +3828,117 @@ impl AzCopyClient {
         destination: &str,
         delete_destination: bool,
         options: &AzCopyOptions,
-    ) -> Result<()> {
+    ) -> Result<JobSummary, AzstError> {
+        let cancel = self.cancel.clone();
         let azcopy_path = self.get_azcopy_executable().await?;
-        let mut cmd = AsyncCommand::new(azcopy_path);
-        cmd.args(["sync", source, destination]);
-
-        if delete_destination {
-            cmd.arg("--delete-destination=true");
-        }
-
-        // Apply common options (excluding recursive as sync is always recursive)
-        if options.dry_run {
-            cmd.arg("--dry-run");
-        }
-
-        if let Some(mbps) = options.cap_mbps {
-            cmd.arg(format!("--cap-mbps={}", mbps));
-        }
+        let account = account_from_url(destination).or_else(|| account_from_url(source));
+        let mut concurrency = None;
+
+        let (summary, status) = run_job(
+            AzCopyOperation::Sync,
+            &cancel,
+            options.cap_mbps,
+            options.bandwidth_schedule.as_ref(),
+            !options.dry_run,
+            |job_id, cap_mbps| {
+                let mut cmd = match job_id {
+                    Some(job_id) => resume_command(azcopy_path, job_id, cap_mbps),
+                    None => {
+                        let mut cmd = AsyncCommand::new(azcopy_path);
+                        cmd.args(["sync", &*native_path(source), &*native_path(destination)]);
+
+                        if delete_destination {
+                            cmd.arg("--delete-destination=true");
+                        }
+
+                        // Apply common options (excluding recursive as sync is always recursive)
+                        if options.dry_run {
+                            cmd.arg("--dry-run");
+                        }
+
+                        if let Some(mbps) = cap_mbps {
+                            cmd.arg(format!("--cap-mbps={}", mbps));
+                        }
+
+                        if let Some(block_size) = options.block_size_mb {
+                            cmd.arg(format!("--block-size-mb={}", block_size));
+                        }
+
+                        if options.put_md5 {
+                            cmd.arg("--put-md5");
+                        }
+
+                        if options.preserve_properties {
+                            cmd.arg(AzCopyOptions::preserve_properties_flag());
+                        }
+
+                        if options.compare_md5 {
+                            cmd.arg("--compare-hash=MD5");
+                        }
+
+                        if let Some(flag) = options.symlinks.azcopy_flag() {
+                            cmd.arg(flag);
+                        }
+
+                        if let Some(pattern) = &options.include_pattern {
+                            cmd.arg(format!("--include-pattern={}", pattern));
+                        }
+
+                        if let Some(pattern) = &options.exclude_pattern {
+                            cmd.arg(format!("--exclude-pattern={}", pattern));
+                        }
+
+                        if let Some(path) = &options.include_path {
+                            cmd.arg(format!("--include-path={}", path));
+                        }
+
+                        if let Some(path) = &options.exclude_path {
+                            cmd.arg(format!("--exclude-path={}", path));
+                        }
+
+                        if let Some(regex) = &options.include_regex {
+                            cmd.arg(format!("--include-regex={}", regex));
+                        }
+
+                        if let Some(regex) = &options.exclude_regex {
+                            cmd.arg(format!("--exclude-regex={}", regex));
+                        }
+
+                        cmd
+                    }
+                };
 
-        if let Some(block_size) = options.block_size_mb {
-            cmd.arg(format!("--block-size-mb={}", block_size));
-        }
+                // Use Azure CLI credentials
+                cmd.env("AZCOPY_AUTO_LOGIN_TYPE", "AZCLI");
 
-        if options.put_md5 {
-            cmd.arg("--put-md5");
-        }
+                // Apply environment variable tuning settings
+                options.apply_env_vars(&mut cmd);
 
-        if let Some(pattern) = &options.include_pattern {
-            cmd.arg(format!("--include-pattern={}", pattern));
-        }
+                // Use JSON output for better parsing
+                cmd.args(["--output-type", "json"]);
 
-        if let Some(pattern) = &options.exclude_pattern {
-            cmd.arg(format!("--exclude-pattern={}", pattern));
+                concurrency = account.and_then(|account| {
+                    apply_learned_concurrency(&mut cmd, account, options.tuned_concurrency)
+                });
+                cmd
+            },
+        )
+        .await?;
+        if let (Some(account), Some(concurrency)) = (account, concurrency) {
+            record_server_busy(account, concurrency, summary.server_busy_percent);
         }
+        audit_log::record_job(&summary);
+        symlinks::warn_skipped("the source", summary.skipped_links, options.symlinks);
 
-        // Use Azure CLI credentials
-        cmd.env("AZCOPY_AUTO_LOGIN_TYPE", "AZCLI");
-
-        // Apply environment variable tuning settings
-        AzCopyOptions::apply_env_vars(&mut cmd);
-
-        // Inherit stdout/stderr so user sees real-time progress
-        cmd.stdout(std::process::Stdio::inherit());
-        cmd.stderr(std::process::Stdio::inherit());
-
-        let status = cmd
-            .status()
-            .await
-            .context("Failed to execute azcopy sync")?;
-
-        if !status.success() {
-            return Err(anyhow!(
+        // Exit code 1 with failed transfers is expected - show warning but don't fail
+        if !status.success() && summary.failed_count == 0 {
+            return Err(AzstError::TransferFailed(format!(
                 "AzCopy sync operation failed with exit code: {}",
                 status.code().unwrap_or(-1)
-            ));
+            )));
         }
 
-        Ok(())
+        Ok(summary)
     }
 
     /// Remove files/directories using AzCopy with additional options
@@ -916,7 +3946,7 @@ impl AzCopyClient {
         &mut self,
         target: &str,
         options: &AzCopyOptions,
-    ) -> Result<()> {
+    ) -> Result<JobSummary, AzstError> {
         let azcopy_path = self.get_azcopy_executable().await?;
         let mut cmd = AsyncCommand::new(azcopy_path);
         cmd.args(["remove", target]);
@@ -931,43 +3961,85 @@ impl AzCopyClient {
         cmd.env("AZCOPY_AUTO_LOGIN_TYPE", "AZCLI");
 
         // Apply environment variable tuning settings
-        AzCopyOptions::apply_env_vars(&mut cmd);
+        options.apply_env_vars(&mut cmd);
 
         // Capture stdout to parse JSON output
         // All azcopy output goes to stdout with --output-type json
         cmd.stdout(std::process::Stdio::piped());
         cmd.stderr(std::process::Stdio::null()); // Discard stderr
 
+        let account = account_from_url(target);
+        let concurrency = account.and_then(|account| {
+            apply_learned_concurrency(&mut cmd, account, options.tuned_concurrency)
+        });
+
+        cmd.kill_on_drop(true);
+
         let mut child = cmd.spawn().context("Failed to execute azcopy remove")?;
 
         // Process stdout
-        let failed_count = if let Some(stdout) = child.stdout.take() {
-            crate::azcopy_output::handle_azcopy_output_with_operation(
-                stdout,
-                crate::azcopy_output::AzCopyOperation::Remove,
-            )
-            .await?
-        } else {
-            0
-        };
+        let summary = follow_job(&mut child, AzCopyOperation::Remove, &self.cancel).await?;
+        if let (Some(account), Some(concurrency)) = (account, concurrency) {
+            record_server_busy(account, concurrency, summary.server_busy_percent);
+        }
+        audit_log::record_job(&summary);
 
         let status = child.wait().await.context("Failed to wait for azcopy")?;
 
         // Exit code 1 with failed transfers is expected - show warning but don't fail
         if !status.success() {
-            if failed_count > 0 {
-                // CompletedWithErrors - warning already shown, don't fail the operation
-                return Ok(());
+            if summary.failed_count > 0 {
+                // CompletedWithErrors - warning already shown; --strict fails the command later
+                return Ok(summary);
             } else {
                 // Actual failure
-                return Err(anyhow!(
+                return Err(AzstError::TransferFailed(format!(
                     "AzCopy remove operation failed with exit code: {}",
                     status.code().unwrap_or(-1)
-                ));
+                )));
             }
         }
 
-        Ok(())
+        Ok(summary)
+    }
+
+    /// Run `azcopy bench` against a container URL: upload generated files
+    /// to it, or download files it uploads first, and report on the job
+    ///
+    /// `args` are the benchmark's own flags (`--size-per-file`, `--mode`…);
+    /// only the transfer flags of `options` apply.
+    pub async fn bench(
+        &mut self,
+        target: &str,
+        args: &[String],
+        options: &AzCopyOptions,
+    ) -> Result<JobSummary, AzstError> {
+        let azcopy_path = self.get_azcopy_executable().await?;
+        let mut cmd = AsyncCommand::new(azcopy_path);
+        cmd.args(["bench", target]);
+        cmd.args(args);
+        cmd.args(["--output-type", "json"]);
+        cmd.env("AZCOPY_AUTO_LOGIN_TYPE", "AZCLI");
+        // Measured with the user's tuning, not the one learned from throttling
+        options.apply_env_vars(&mut cmd);
+
+        cmd.stdout(std::process::Stdio::piped());
+        cmd.stderr(std::process::Stdio::null());
+
+        cmd.kill_on_drop(true);
+
+        let mut child = cmd.spawn().context("Failed to execute azcopy bench")?;
+        let summary = follow_job(&mut child, AzCopyOperation::Copy, &self.cancel).await?;
+
+        let status = child.wait().await.context("Failed to wait for azcopy")?;
+        if !status.success() && summary.failed_count == 0 {
+            return Err(AzstError::TransferFailed(format!(
+                "AzCopy benchmark failed with exit code: {}",
+                status.code().unwrap_or(-1)
+            )));
+        }
+
+        Ok(summary)
     }
 }
 
@@ -981,6 +4053,53 @@ mod tests {
         assert!(client.config.storage_account.is_none());
     }
 
+    #[test]
+    fn test_customer_key_parse() {
+        // 32 bytes of 0x00..0x1f
+        let key: CustomerKey = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8="
+            .parse()
+            .unwrap();
+        assert_eq!(
+            key.headers()[1],
+            (
+                "x-ms-encryption-key-sha256",
+                "Yw3NKWbEM2aRElRIu7JbT/QSpJxzLbLIq8G4WBvXEN0="
+            )
+        );
+        // The key itself never shows up in debug output
+        assert!(!format!("{:?}", key).contains("AAECAwQF"));
+
+        assert!("not base64!".parse::<CustomerKey>().is_err());
+        assert!("AAECAwQFBgcICQoLDA0ODw=="
+            .parse::<CustomerKey>()
+            .unwrap_err()
+            .contains("32 bytes"));
+    }
+
+    #[test]
+    fn test_path_filters_matches() {
+        let filters = PathFilters {
+            exclude_path: Some("node_modules;build/".to_string()),
+            exclude_regex: Some(r"\.bak$".to_string()),
+            ..Default::default()
+        };
+        assert!(filters.matches("src/main.rs"));
+        assert!(filters.matches("node_modules.txt"));
+        assert!(!filters.matches("node_modules/left-pad/index.js"));
+        assert!(!filters.matches("build/out.o"));
+        assert!(!filters.matches("notes.bak"));
+
+        let filters = PathFilters {
+            include_path: Some("docs;README.md".to_string()),
+            include_regex: Some("^docs/.*\\.md$;README".to_string()),
+            ..Default::default()
+        };
+        assert!(filters.matches("docs/guide.md"));
+        assert!(filters.matches("README.md"));
+        assert!(!filters.matches("docs/logo.png"));
+        assert!(!filters.matches("src/docs/guide.md"));
+    }
+
     #[test]
     fn test_azure_client_with_storage_account() {
         let client = AzureClient::new().with_storage_account("myaccount");
@@ -998,6 +4117,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_overwrite_policy_azcopy_values() {
+        assert_eq!(OverwritePolicy::Always.as_azcopy_value(), "true");
+        assert_eq!(OverwritePolicy::Never.as_azcopy_value(), "false");
+        assert_eq!(OverwritePolicy::IfNewer.as_azcopy_value(), "ifSourceNewer");
+        assert_eq!(OverwritePolicy::Prompt.as_azcopy_value(), "prompt");
+    }
+
+    #[test]
+    fn test_md5_check_azcopy_values() {
+        assert_eq!(Md5Check::Never.as_azcopy_value(), "NoCheck");
+        assert_eq!(Md5Check::Log.as_azcopy_value(), "LogOnly");
+        assert_eq!(Md5Check::Fail.as_azcopy_value(), "FailIfDifferent");
+    }
+
+    #[test]
+    fn test_blob_type_azcopy_values() {
+        assert_eq!(BlobType::Block.as_azcopy_value(), "BlockBlob");
+        assert_eq!(BlobType::Page.as_azcopy_value(), "PageBlob");
+        assert_eq!(BlobType::Append.as_azcopy_value(), "AppendBlob");
+    }
+
+    #[test]
+    fn test_blob_headers_azcopy_args() {
+        assert!(BlobHeaders::default().is_empty());
+        assert!(BlobHeaders::default().azcopy_args().is_empty());
+
+        let headers = BlobHeaders {
+            content_type: Some("text/html".to_string()),
+            cache_control: Some("max-age=3600".to_string()),
+            content_encoding: None,
+            metadata: vec![
+                ("owner".to_string(), "web".to_string()),
+                ("build".to_string(), "42".to_string()),
+            ],
+            tier: Some(BlobTier::Cool),
+        };
+        assert!(!headers.is_empty());
+        assert_eq!(
+            headers.azcopy_args(),
+            vec![
+                "--content-type=text/html",
+                "--cache-control=max-age=3600",
+                "--metadata=owner=web;build=42",
+                "--block-blob-tier=Cool",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transfer_flags_win_over_environment() {
+        let options = AzCopyOptions::new().with_transfer_flags(TransferConfig {
+            concurrency: Some(64),
+            buffer_gb: None,
+            scan_concurrency: Some(8),
+        });
+        let mut cmd = AsyncCommand::new("azcopy");
+        options.apply_env_vars(&mut cmd);
+        let envs: std::collections::HashMap<_, _> = cmd.as_std().get_envs().collect();
+        assert_eq!(
+            envs.get(std::ffi::OsStr::new("AZCOPY_CONCURRENCY_VALUE")),
+            Some(&Some(std::ffi::OsStr::new("64")))
+        );
+        assert_eq!(
+            envs.get(std::ffi::OsStr::new("AZCOPY_CONCURRENT_SCAN")),
+            Some(&Some(std::ffi::OsStr::new("8")))
+        );
+    }
+
     #[test]
     fn test_blob_info_deserialization() {
         let json = r#"{
@@ -1141,10 +4329,115 @@ mod tests {
         assert_eq!(accounts[1].resource_group, "rg2");
     }
 
+    #[test]
+    fn test_account_filter_names() {
+        let filter = AccountFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches_name("anything"));
+
+        let filter = AccountFilter {
+            name: Some("prod*".to_string()),
+            ..Default::default()
+        };
+        assert!(!filter.is_empty());
+        assert!(filter.matches_name("prodlogs"));
+        assert!(!filter.matches_name("devprod"));
+
+        let filter = AccountFilter {
+            name: Some("logs".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches_name("prodlogs01"));
+        assert!(!filter.matches_name("proddata"));
+    }
+
+    #[test]
+    fn test_exponential_retry_options() {
+        let defaults = exponential_retry_options(&RetryConfig::default(), None);
+        assert_eq!(defaults.max_retries, 8);
+        assert_eq!(defaults.initial_delay, Duration::from_millis(200));
+
+        let settings = RetryConfig {
+            max_retries: Some(3),
+            initial_delay_ms: Some(1000),
+            max_delay_secs: None,
+            max_elapsed_secs: Some(300),
+        };
+        let options = exponential_retry_options(&settings, None);
+        assert_eq!(options.max_retries, 3);
+        assert_eq!(options.initial_delay, Duration::from_secs(1));
+        assert_eq!(options.max_total_elapsed, Duration::from_secs(300));
+
+        // --retries wins over the config file
+        assert_eq!(exponential_retry_options(&settings, Some(0)).max_retries, 0);
+    }
+
     // ========================================================================
     // Credential Chain Tests
     // ========================================================================
 
+    /// Hands out tokens with a fixed lifetime and counts how often it is asked
+    #[derive(Debug)]
+    struct CountingCredential {
+        lifetime: std::time::Duration,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenCredential for CountingCredential {
+        async fn get_token(&self, _scopes: &[&str]) -> Result<AccessToken, AzureError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(AccessToken::new(
+                "token".to_string(),
+                time::OffsetDateTime::now_utc() + self.lifetime,
+            ))
+        }
+
+        async fn clear_cache(&self) -> Result<(), AzureError> {
+            Ok(())
+        }
+    }
+
+    async fn calls_for_two_tokens(lifetime: std::time::Duration) -> usize {
+        let inner = Arc::new(CountingCredential {
+            lifetime,
+            calls: Default::default(),
+        });
+        let credential = RefreshingCredential::new(inner.clone());
+        credential.get_token(&["scope"]).await.unwrap();
+        credential.get_token(&["scope"]).await.unwrap();
+        inner.calls.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_refreshing_credential_caches_fresh_tokens() {
+        assert_eq!(
+            calls_for_two_tokens(std::time::Duration::from_secs(3600)).await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refreshing_credential_renews_expiring_tokens() {
+        assert_eq!(
+            calls_for_two_tokens(std::time::Duration::from_secs(60)).await,
+            2
+        );
+    }
+
+    #[test]
+    fn test_refresh_delay() {
+        let now = time::OffsetDateTime::now_utc();
+        let hour = std::time::Duration::from_secs(3600);
+        assert_eq!(refresh_delay(now + hour, now), hour - TOKEN_REFRESH_MARGIN);
+        // Tokens already inside the margin are retried after a short pause
+        assert_eq!(
+            refresh_delay(now + std::time::Duration::from_secs(60), now),
+            MIN_REFRESH_INTERVAL
+        );
+        assert_eq!(refresh_delay(now - hour, now), MIN_REFRESH_INTERVAL);
+    }
+
     #[tokio::test]
     async fn test_credential_caching() {
         // Test that credentials are cached after first creation
@@ -1259,6 +4552,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_credential_chain_documentation() {
         // This is a documentation test that verifies the expected credential chain order
         // The actual chain is:
@@ -1391,6 +4685,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_credential_chain_priority_order() {
         // Document and verify the credential chain priority
         // This test serves as documentation for the expected behavior
@@ -1423,4 +4718,69 @@ mod tests {
 
         assert!(true, "Credential chain documented");
     }
+
+    #[tokio::test]
+    async fn test_persistent_credential_reuses_tokens_across_instances() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("tokens.json");
+        let inner = Arc::new(CountingCredential {
+            lifetime: std::time::Duration::from_secs(3600),
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        });
+        let credential = |inner: Arc<CountingCredential>| PersistentCredential {
+            inner,
+            identity: "ambient;az=alice@tenant".to_string(),
+            path: Some(path.clone()),
+        };
+
+        credential(inner.clone())
+            .get_token(&["scope"])
+            .await
+            .unwrap();
+        let token = credential(inner.clone())
+            .get_token(&["scope"])
+            .await
+            .unwrap();
+        assert_eq!(token.token.secret(), "token");
+        assert_eq!(inner.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Signed in as someone else, the earlier token isn't reused
+        let other = PersistentCredential {
+            identity: "ambient;az=bob@tenant".to_string(),
+            ..credential(inner.clone())
+        };
+        other.get_token(&["scope"]).await.unwrap();
+        assert_eq!(inner.calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        credential(inner.clone()).clear_cache().await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_cli_account_reads_default_subscription() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("azureProfile.json"),
+            "\u{feff}{\"subscriptions\": [\
+             {\"isDefault\": false, \"tenantId\": \"t1\", \"user\": {\"name\": \"old@example.com\"}},\
+             {\"isDefault\": true, \"tenantId\": \"t2\", \"user\": {\"name\": \"new@example.com\"}}]}",
+        )
+        .unwrap();
+
+        let original = std::env::var_os("AZURE_CONFIG_DIR");
+        std::env::set_var("AZURE_CONFIG_DIR", dir.path());
+        let account = cli_account();
+        match original {
+            Some(value) => std::env::set_var("AZURE_CONFIG_DIR", value),
+            None => std::env::remove_var("AZURE_CONFIG_DIR"),
+        }
+        assert_eq!(account.as_deref(), Some("new@example.com@t2"));
+    }
 }
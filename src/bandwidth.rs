@@ -0,0 +1,400 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Timelike;
+use colored::*;
+use std::fmt;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command as AsyncCommand};
+use tokio_util::sync::CancellationToken;
+
+use crate::azcopy_output::{handle_azcopy_output_with_operation, AzCopyOperation, JobSummary};
+use crate::cancel;
+
+// ============================================================================
+// Bandwidth Control - Time-of-day rate limits and pausing running jobs
+// ============================================================================
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// How fast a transfer may go
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    Unlimited,
+    /// Megabits per second
+    Capped(f64),
+    Paused,
+}
+
+impl Rate {
+    fn from_cap(cap_mbps: Option<f64>) -> Self {
+        match cap_mbps {
+            Some(mbps) if mbps > 0.0 => Rate::Capped(mbps),
+            _ => Rate::Unlimited,
+        }
+    }
+
+    /// The --cap-mbps azcopy runs with
+    fn cap_mbps(self) -> Option<f64> {
+        match self {
+            Rate::Capped(mbps) => Some(mbps),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rate::Unlimited => write!(f, "full speed"),
+            Rate::Capped(mbps) => write!(f, "{} Mbps", mbps),
+            Rate::Paused => write!(f, "paused"),
+        }
+    }
+}
+
+/// A stretch of the day, in seconds since local midnight. Windows whose end
+/// comes before their start run past midnight.
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    start: u32,
+    end: u32,
+    rate: Rate,
+}
+
+impl Window {
+    fn contains(&self, second: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&second)
+        } else if self.start > self.end {
+            second >= self.start || second < self.end
+        } else {
+            true
+        }
+    }
+}
+
+/// Rate limits by local time of day, e.g. `09:00-18:00=50,18:00-09:00=0`
+///
+/// Rates are in megabits per second; 0 lifts the limit and `pause` stops
+/// the transfer until the window ends. The first window holding the current
+/// time applies, and `--cap-mbps` applies outside every window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthSchedule {
+    windows: Vec<Window>,
+}
+
+impl BandwidthSchedule {
+    pub fn parse(value: &str) -> Result<Self> {
+        let windows = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                parse_window(entry).ok_or_else(|| {
+                    anyhow!(
+                        "Invalid bandwidth schedule entry '{}': expected HH:MM-HH:MM=MBPS \
+                         (0 for no limit, or 'pause')",
+                        entry
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if windows.is_empty() {
+            return Err(anyhow!("The bandwidth schedule has no entries"));
+        }
+        Ok(Self { windows })
+    }
+
+    /// The rate at `second` past local midnight, if a window covers it
+    fn rate_at(&self, second: u32) -> Option<Rate> {
+        self.windows
+            .iter()
+            .find(|window| window.contains(second))
+            .map(|window| window.rate)
+    }
+
+    /// Time from `second` past midnight until the next window starts or ends
+    fn until_next_change(&self, second: u32) -> Duration {
+        let seconds = self
+            .windows
+            .iter()
+            .flat_map(|window| [window.start, window.end])
+            .map(|boundary| (boundary + SECONDS_PER_DAY - second) % SECONDS_PER_DAY)
+            .filter(|&delta| delta > 0)
+            .min()
+            .unwrap_or(SECONDS_PER_DAY);
+        Duration::from_secs(seconds.into())
+    }
+}
+
+/// `HH:MM-HH:MM=RATE`
+fn parse_window(entry: &str) -> Option<Window> {
+    let (range, rate) = entry.split_once('=')?;
+    let (start, end) = range.split_once('-')?;
+    let rate = match rate.trim() {
+        "pause" => Rate::Paused,
+        mbps => Rate::from_cap(Some(mbps.parse::<f64>().ok().filter(|m| *m >= 0.0)?)),
+    };
+    Some(Window {
+        start: parse_time_of_day(start.trim())?,
+        end: parse_time_of_day(end.trim())?,
+        rate,
+    })
+}
+
+/// `HH:MM` as seconds since midnight
+fn parse_time_of_day(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.split_once(':')?;
+    let hours: u32 = hours.parse().ok().filter(|h| *h < 24)?;
+    let minutes: u32 = minutes.parse().ok().filter(|m| *m < 60)?;
+    Some(hours * 3600 + minutes * 60)
+}
+
+fn seconds_since_midnight() -> u32 {
+    chrono::Local::now().num_seconds_from_midnight()
+}
+
+/// SIGUSR1 pauses a running transfer and SIGUSR2 resumes it
+#[cfg(unix)]
+struct PauseSignals {
+    pause: tokio::signal::unix::Signal,
+    resume: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl PauseSignals {
+    fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            pause: signal(SignalKind::user_defined1())?,
+            resume: signal(SignalKind::user_defined2())?,
+        })
+    }
+
+    /// Wait for a signal, returning whether it asks to pause
+    async fn recv(&mut self) -> bool {
+        tokio::select! {
+            _ = self.pause.recv() => true,
+            _ = self.resume.recv() => false,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct PauseSignals;
+
+#[cfg(not(unix))]
+impl PauseSignals {
+    fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    async fn recv(&mut self) -> bool {
+        std::future::pending().await
+    }
+}
+
+/// Read a spawned azcopy job's output to the end, killing azcopy if the
+/// command is cancelled first; what it reported until then is returned
+pub async fn follow_job(
+    child: &mut Child,
+    operation: AzCopyOperation,
+    cancel: &CancellationToken,
+) -> Result<JobSummary> {
+    let Some(stdout) = child.stdout.take() else {
+        return Ok(JobSummary::default());
+    };
+    let output = handle_azcopy_output_with_operation(stdout, operation);
+    tokio::pin!(output);
+    tokio::select! {
+        summary = &mut output => return summary,
+        _ = cancel.cancelled() => {}
+    }
+    child.start_kill().context("Failed to stop azcopy")?;
+    let mut summary = output.await?;
+    summary.cancelled = true;
+    Ok(summary)
+}
+
+/// Run an azcopy job to completion, following the schedule and pause signals
+///
+/// `launch` builds the command that starts the job (given no job ID) or
+/// resumes it, at the given --cap-mbps. To change its rate the job is
+/// cancelled and resumed, which picks up where it stopped. Jobs that aren't
+/// `controllable`, e.g. dry runs, run once at `cap_mbps`.
+pub async fn run_job<F>(
+    operation: AzCopyOperation,
+    cancel: &CancellationToken,
+    cap_mbps: Option<f64>,
+    schedule: Option<&BandwidthSchedule>,
+    controllable: bool,
+    mut launch: F,
+) -> Result<(JobSummary, ExitStatus)>
+where
+    F: FnMut(Option<&str>, Option<f64>) -> AsyncCommand,
+{
+    if !controllable {
+        let mut cmd = launch(None, cap_mbps);
+        cmd.stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        let mut child = cmd.spawn().context("Failed to execute azcopy")?;
+        let summary = follow_job(&mut child, operation, cancel).await?;
+        let status = child.wait().await.context("Failed to wait for azcopy")?;
+        return Ok((summary, status));
+    }
+
+    let current_rate = |paused: bool| match schedule {
+        _ if paused => Rate::Paused,
+        Some(schedule) => schedule
+            .rate_at(seconds_since_midnight())
+            .unwrap_or(Rate::from_cap(cap_mbps)),
+        None => Rate::from_cap(cap_mbps),
+    };
+    // Wake a moment after the boundary so the new window already applies
+    let until_next_change = || match schedule {
+        Some(schedule) => {
+            schedule.until_next_change(seconds_since_midnight()) + Duration::from_secs(1)
+        }
+        None => Duration::MAX,
+    };
+
+    let mut signals = PauseSignals::new()?;
+    let mut paused = false;
+    let mut job_id: Option<String> = None;
+    let mut waiting = false;
+
+    loop {
+        let rate = current_rate(paused);
+        if rate == Rate::Paused {
+            if job_id.is_none() && !waiting {
+                status!(
+                    "{} Waiting for the bandwidth schedule to allow transfers",
+                    "ℹ".blue()
+                );
+            }
+            waiting = true;
+            tokio::select! {
+                pause = signals.recv() => paused = pause,
+                _ = tokio::time::sleep(until_next_change()) => {}
+                _ = cancel.cancelled() => return Err(cancel::error().into()),
+            }
+            continue;
+        }
+        waiting = false;
+        if job_id.is_some() {
+            status!("{} Resuming transfer at {}", "→".green(), rate);
+        }
+
+        let mut cmd = launch(job_id.as_deref(), rate.cap_mbps());
+        cmd.arg("--cancel-from-stdin")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        let mut child = cmd.spawn().context("Failed to execute azcopy")?;
+        let mut stdin = child.stdin.take();
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to read azcopy output"))?;
+
+        let output = handle_azcopy_output_with_operation(stdout, operation);
+        tokio::pin!(output);
+        let mut stopping = false;
+        let summary = loop {
+            let changed = tokio::select! {
+                summary = &mut output => break summary?,
+                pause = signals.recv(), if !stopping => {
+                    paused = pause;
+                    current_rate(paused) != rate
+                }
+                _ = tokio::time::sleep(until_next_change()), if !stopping => {
+                    current_rate(paused) != rate
+                }
+                _ = cancel.cancelled(), if !stopping => true,
+            };
+            if changed {
+                match current_rate(paused) {
+                    _ if cancel.is_cancelled() => {}
+                    Rate::Paused => status!("{} Pausing transfer", "ℹ".blue()),
+                    new_rate => status!("{} Changing rate to {}", "ℹ".blue(), new_rate),
+                }
+                if let Some(stdin) = stdin.as_mut() {
+                    stdin.write_all(b"cancel\n").await?;
+                    stdin.flush().await?;
+                }
+                stopping = true;
+            }
+        };
+        let status = child.wait().await.context("Failed to wait for azcopy")?;
+
+        // A job that finished before the cancellation reached it is done,
+        // and one the command's cancellation stopped isn't resumed
+        if !stopping || !summary.cancelled || cancel.is_cancelled() {
+            return Ok((summary, status));
+        }
+        job_id = summary.job_id.or(job_id);
+        if job_id.is_none() {
+            return Err(anyhow!("AzCopy did not report a job ID to resume"));
+        }
+        if paused {
+            status!(
+                "{} Transfer paused; send SIGUSR2 (kill -USR2 {}) to resume",
+                "ℹ".blue(),
+                std::process::id()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hours: u32, minutes: u32) -> u32 {
+        hours * 3600 + minutes * 60
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule = BandwidthSchedule::parse("09:00-18:00=50, 18:00-09:00=0").unwrap();
+        assert_eq!(schedule.rate_at(at(9, 0)), Some(Rate::Capped(50.0)));
+        assert_eq!(schedule.rate_at(at(17, 59)), Some(Rate::Capped(50.0)));
+        assert_eq!(schedule.rate_at(at(18, 0)), Some(Rate::Unlimited));
+        assert_eq!(schedule.rate_at(at(3, 30)), Some(Rate::Unlimited));
+
+        let schedule = BandwidthSchedule::parse("12:00-13:00=pause").unwrap();
+        assert_eq!(schedule.rate_at(at(12, 30)), Some(Rate::Paused));
+        assert_eq!(schedule.rate_at(at(13, 0)), None);
+
+        for invalid in [
+            "",
+            "09:00-18:00",
+            "9-18=50",
+            "09:00-25:00=50",
+            "09:00-18:00=-1",
+        ] {
+            assert!(BandwidthSchedule::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_until_next_change() {
+        let schedule = BandwidthSchedule::parse("09:00-18:00=50").unwrap();
+        assert_eq!(
+            schedule.until_next_change(at(8, 0)),
+            Duration::from_secs(3600)
+        );
+        assert_eq!(
+            schedule.until_next_change(at(9, 0)),
+            Duration::from_secs(9 * 3600)
+        );
+        assert_eq!(
+            schedule.until_next_change(at(20, 0)),
+            Duration::from_secs(13 * 3600)
+        );
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::azure::AzureClient;
+use crate::checksum::{Digests, HashSelection, Hasher};
+use crate::config::{Config, ReadaheadSettings};
+use crate::error::AzstError;
+
+// ============================================================================
+// Blob Reader - Ranged reads through a block cache with sequential readahead
+// ============================================================================
+
+/// Tuning for block-cached blob reads
+#[derive(Debug, Clone, Copy)]
+pub struct ReadaheadConfig {
+    /// Size of each cached block in bytes
+    pub block_size: u64,
+    /// Number of blocks to prefetch ahead of a sequential reader
+    pub readahead_blocks: u64,
+    /// Maximum number of blocks kept in memory
+    pub cache_blocks: usize,
+}
+
+impl Default for ReadaheadConfig {
+    fn default() -> Self {
+        Self {
+            block_size: 4 * 1024 * 1024,
+            readahead_blocks: 4,
+            cache_blocks: 32,
+        }
+    }
+}
+
+impl ReadaheadConfig {
+    /// The defaults, with the `[readahead]` section of the config file over
+    /// them; fails if the config file can't be read
+    pub fn configured() -> Result<Self, AzstError> {
+        Ok(Self::from_settings(Config::load()?.readahead))
+    }
+
+    /// The defaults, with the values `settings` has over them
+    pub fn from_settings(settings: ReadaheadSettings) -> Self {
+        let defaults = Self::default();
+        let block_size = settings.block_size.unwrap_or(defaults.block_size).max(1);
+        let cache_size = settings
+            .cache_size
+            .unwrap_or(defaults.block_size * defaults.cache_blocks as u64);
+        Self {
+            block_size,
+            readahead_blocks: settings.blocks.unwrap_or(defaults.readahead_blocks).max(1),
+            cache_blocks: (cache_size / block_size).max(1) as usize,
+        }
+    }
+
+    /// Keep `ranges` ranged GETs in flight for a sequential reader, so large
+    /// blobs download over that many connections
+    pub fn with_parallel_ranges(mut self, ranges: u64) -> Self {
+        self.readahead_blocks = ranges.max(1);
+        self
+    }
+}
+
+/// Fixed-capacity cache of blocks, evicting the least recently used one
+struct BlockCache {
+    capacity: usize,
+    blocks: HashMap<u64, Arc<Vec<u8>>>,
+    order: VecDeque<u64>,
+}
+
+impl BlockCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            blocks: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn contains(&self, index: u64) -> bool {
+        self.blocks.contains_key(&index)
+    }
+
+    fn get(&mut self, index: u64) -> Option<Arc<Vec<u8>>> {
+        let block = self.blocks.get(&index)?.clone();
+        self.touch(index);
+        Some(block)
+    }
+
+    fn insert(&mut self, index: u64, data: Arc<Vec<u8>>) {
+        if self.blocks.insert(index, data).is_none() {
+            while self.blocks.len() > self.capacity {
+                match self.order.pop_front() {
+                    Some(evicted) => {
+                        self.blocks.remove(&evicted);
+                    }
+                    None => break,
+                }
+            }
+        }
+        self.touch(index);
+    }
+
+    fn touch(&mut self, index: u64) {
+        self.order.retain(|&i| i != index);
+        self.order.push_back(index);
+    }
+}
+
+/// Random-access reader for a single blob
+///
+/// Reads are served from fixed-size blocks. When the reader detects sequential
+/// access, the next blocks are fetched concurrently in the background so that
+/// streaming consumers are not bottlenecked by per-request latency.
+pub struct BlobReader {
+    client: AzureClient,
+    container: String,
+    blob: String,
+    size: u64,
+    content_encoding: Option<String>,
+    config: ReadaheadConfig,
+    cache: BlockCache,
+    inflight: HashMap<u64, JoinHandle<Result<Vec<u8>, AzstError>>>,
+    next_sequential: Option<u64>,
+}
+
+impl BlobReader {
+    /// Open a blob for reading, fetching its size up front
+    pub async fn open(
+        mut client: AzureClient,
+        container: &str,
+        blob: &str,
+        config: ReadaheadConfig,
+    ) -> Result<Self, AzstError> {
+        let info = client.get_blob_properties(container, blob).await?;
+
+        Ok(Self {
+            client,
+            container: container.to_string(),
+            blob: blob.to_string(),
+            size: info.properties.content_length,
+            content_encoding: info.properties.content_encoding,
+            config,
+            cache: BlockCache::new(config.cache_blocks),
+            inflight: HashMap::new(),
+            next_sequential: None,
+        })
+    }
+
+    /// Total size of the blob in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The blob's Content-Encoding, e.g. gzip
+    pub fn content_encoding(&self) -> Option<&str> {
+        self.content_encoding.as_deref()
+    }
+
+    /// Start fetching the blocks from `offset` on before the first read, e.g.
+    /// while an earlier blob is still being consumed
+    pub fn start_readahead(&mut self, offset: u64) {
+        if offset >= self.size {
+            return;
+        }
+        let first = offset / self.config.block_size;
+        self.prefetch(first);
+        self.next_sequential = Some(first);
+    }
+
+    /// Read up to `len` bytes starting at `offset` (short reads only happen at end of blob)
+    pub async fn read_at(&mut self, offset: u64, len: u64) -> Result<Vec<u8>, AzstError> {
+        if offset >= self.size || len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let end = (offset + len).min(self.size);
+        let (first, last) = block_span(offset, end, self.config.block_size);
+
+        // Prefetches outside this read and the readahead after it belong to
+        // a run that was left; they would only be held outside the cache
+        abort_outside(
+            &mut self.inflight,
+            first..last + 1 + self.config.readahead_blocks,
+        );
+
+        // Sequential access: reading from the start, or continuing where the previous read ended
+        let sequential = self
+            .next_sequential
+            .map_or(first == 0, |next| first <= next && next <= last + 1);
+        if sequential {
+            self.prefetch(last + 1);
+        }
+        self.next_sequential = Some(last + 1);
+
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        for index in first..=last {
+            let block = self.block(index).await?;
+            let block_start = index * self.config.block_size;
+            let from = offset.saturating_sub(block_start) as usize;
+            let to = ((end - block_start) as usize).min(block.len());
+            data.extend_from_slice(&block[from..to]);
+        }
+
+        Ok(data)
+    }
+
+    /// Get a block from the cache, an in-flight prefetch, or a direct fetch
+    async fn block(&mut self, index: u64) -> Result<Arc<Vec<u8>>, AzstError> {
+        if let Some(block) = self.cache.get(index) {
+            return Ok(block);
+        }
+
+        let data = match self.inflight.remove(&index) {
+            Some(handle) => handle
+                .await
+                .map_err(|e| anyhow!("Prefetch task failed: {}", e))??,
+            None => self.fetch_block(index).await?,
+        };
+
+        let block = Arc::new(data);
+        self.cache.insert(index, block.clone());
+        Ok(block)
+    }
+
+    /// Start background downloads for the blocks following `from`
+    fn prefetch(&mut self, from: u64) {
+        let block_count = self.size.div_ceil(self.config.block_size);
+        let until = (from + self.config.readahead_blocks).min(block_count);
+
+        for index in from..until {
+            if self.inflight.len() as u64 >= self.config.readahead_blocks {
+                break;
+            }
+            if self.cache.contains(index) || self.inflight.contains_key(&index) {
+                continue;
+            }
+
+            let mut client = self.client.clone();
+            let container = self.container.clone();
+            let blob = self.blob.clone();
+            let (start, end) = self.block_range(index);
+
+            let handle = tokio::spawn(async move {
+                client
+                    .download_blob(&container, &blob, Some((start, end)))
+                    .await
+            });
+            self.inflight.insert(index, handle);
+        }
+    }
+
+    async fn fetch_block(&mut self, index: u64) -> Result<Vec<u8>, AzstError> {
+        let (start, end) = self.block_range(index);
+        self.client
+            .download_blob(&self.container, &self.blob, Some((start, end)))
+            .await
+    }
+
+    /// Inclusive byte range covered by a block
+    fn block_range(&self, index: u64) -> (u64, u64) {
+        let start = index * self.config.block_size;
+        let end = (start + self.config.block_size).min(self.size) - 1;
+        (start, end)
+    }
+}
+
+/// Cancel the in-flight block downloads whose index isn't in `window`
+fn abort_outside<T>(inflight: &mut HashMap<u64, JoinHandle<T>>, window: Range<u64>) {
+    inflight.retain(|index, handle| {
+        let keep = window.contains(index);
+        if !keep {
+            handle.abort();
+        }
+        keep
+    });
+}
+
+impl Drop for BlobReader {
+    fn drop(&mut self) {
+        for handle in self.inflight.values() {
+            handle.abort();
+        }
+    }
+}
+
+/// Hash a blob's content by streaming it through a reader
+///
+/// When `cap_mbps` is set, reading is paced to stay under that rate (megabits
+/// per second), so large verification jobs don't saturate the network.
+pub async fn hash_blob(
+    client: AzureClient,
+    container: &str,
+    blob: &str,
+    selection: HashSelection,
+    cap_mbps: Option<f64>,
+) -> Result<Digests> {
+    let config = ReadaheadConfig::configured()?;
+    let mut reader = BlobReader::open(client, container, blob, config).await?;
+    let mut hasher = Hasher::new(selection);
+    let started = Instant::now();
+
+    let mut offset = 0;
+    while offset < reader.size() {
+        let chunk = reader.read_at(offset, config.block_size).await?;
+        if chunk.is_empty() {
+            break;
+        }
+        hasher.update(&chunk);
+        offset += chunk.len() as u64;
+
+        if let Some(mbps) = cap_mbps {
+            let target = Duration::from_secs_f64(offset as f64 * 8.0 / (mbps * 1_000_000.0));
+            if let Some(wait) = target.checked_sub(started.elapsed()) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+    }
+
+    Ok(hasher.finish())
+}
+
+/// First and last block indexes touched by the byte range `[offset, end)`
+fn block_span(offset: u64, end: u64, block_size: u64) -> (u64, u64) {
+    (offset / block_size, (end - 1) / block_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_span() {
+        assert_eq!(block_span(0, 10, 10), (0, 0));
+        assert_eq!(block_span(0, 11, 10), (0, 1));
+        assert_eq!(block_span(15, 35, 10), (1, 3));
+        assert_eq!(block_span(20, 21, 10), (2, 2));
+    }
+
+    #[test]
+    fn test_readahead_from_settings() {
+        let config = ReadaheadConfig::from_settings(ReadaheadSettings {
+            block_size: Some(16 * 1024 * 1024),
+            blocks: Some(8),
+            cache_size: None,
+        });
+        assert_eq!(config.block_size, 16 * 1024 * 1024);
+        assert_eq!(config.readahead_blocks, 8);
+        // The default 128 MiB of cache, in the larger blocks
+        assert_eq!(config.cache_blocks, 8);
+    }
+
+    #[tokio::test]
+    async fn test_abort_outside_drops_prefetches_left_behind() {
+        let mut inflight: HashMap<u64, JoinHandle<()>> = (0..8)
+            .map(|index| (index, tokio::spawn(std::future::pending())))
+            .collect();
+        let left: Vec<_> = [0, 1, 7]
+            .iter()
+            .map(|index| inflight[index].abort_handle())
+            .collect();
+
+        abort_outside(&mut inflight, 2..7);
+
+        let mut kept: Vec<u64> = inflight.keys().copied().collect();
+        kept.sort();
+        assert_eq!(kept, vec![2, 3, 4, 5, 6]);
+        tokio::task::yield_now().await;
+        assert!(left.iter().all(|handle| handle.is_finished()));
+    }
+
+    #[test]
+    fn test_block_cache_evicts_least_recently_used() {
+        let mut cache = BlockCache::new(2);
+        cache.insert(0, Arc::new(vec![0]));
+        cache.insert(1, Arc::new(vec![1]));
+
+        // Touch block 0 so block 1 becomes the eviction candidate
+        assert!(cache.get(0).is_some());
+        cache.insert(2, Arc::new(vec![2]));
+
+        assert!(cache.contains(0));
+        assert!(!cache.contains(1));
+        assert!(cache.contains(2));
+    }
+
+    #[test]
+    fn test_block_cache_reinsert_does_not_evict() {
+        let mut cache = BlockCache::new(2);
+        cache.insert(0, Arc::new(vec![0]));
+        cache.insert(1, Arc::new(vec![1]));
+        cache.insert(1, Arc::new(vec![1, 1]));
+
+        assert!(cache.contains(0));
+        assert_eq!(cache.get(1).unwrap().len(), 2);
+    }
+}
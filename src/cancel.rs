@@ -0,0 +1,204 @@
+use anyhow::Result;
+use colored::*;
+use futures::{Stream, StreamExt};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::audit_log;
+use crate::error::AzstError;
+use crate::utils::format_size;
+
+// ============================================================================
+// Cancellation - Ctrl-C and --timeout for the running command
+// ============================================================================
+
+/// How long a cancelled command gets to stop its azcopy jobs and listings
+/// before it's dropped, killing whatever is still running
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Why the running command was cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Interrupted,
+    TimedOut(Duration),
+}
+
+/// The running command's token, replaced for each command of an
+/// interactive shell
+static CURRENT: Mutex<Option<(CancellationToken, Option<Reason>)>> = Mutex::new(None);
+
+/// The token clients watch to stop what they're doing
+pub fn token() -> CancellationToken {
+    let mut current = CURRENT.lock().unwrap_or_else(|e| e.into_inner());
+    current
+        .get_or_insert_with(|| (CancellationToken::new(), None))
+        .0
+        .clone()
+}
+
+/// Start over with a token nothing has cancelled, for the next command of
+/// an interactive shell
+pub fn reset() {
+    *CURRENT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn is_cancelled() -> bool {
+    reason().is_some()
+}
+
+fn reason() -> Option<Reason> {
+    CURRENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(|(_, reason)| *reason)
+}
+
+fn cancel(reason: Reason) {
+    let token = token();
+    if let Some((_, current)) = CURRENT.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        current.get_or_insert(reason);
+    }
+    token.cancel();
+}
+
+/// The error a cancelled command ends with, or a plain one if nothing was
+/// cancelled
+pub fn error() -> AzstError {
+    match reason() {
+        Some(Reason::TimedOut(limit)) => {
+            AzstError::TimedOut(format!("Timed out after {}s", limit.as_secs()))
+        }
+        _ => AzstError::Interrupted("Interrupted".to_string()),
+    }
+}
+
+/// Stop yielding pages once the command is cancelled, ending with its error
+/// so a listing that was cut short isn't mistaken for a complete one
+pub fn guard<S, T, E>(stream: S, token: CancellationToken) -> impl Stream<Item = Result<T, E>>
+where
+    S: Stream<Item = Result<T, E>>,
+    E: From<AzstError>,
+{
+    let stopped = token.clone();
+    stream
+        .take_until(token.cancelled_owned())
+        .chain(futures::stream::iter(
+            std::iter::from_fn(move || stopped.is_cancelled().then(|| Err(error().into()))).take(1),
+        ))
+}
+
+/// Run a command until it finishes, `timeout` runs out or Ctrl-C is pressed
+///
+/// A cancelled command is told to stop through its token and given a grace
+/// period to cancel its azcopy jobs; a second Ctrl-C or the end of the grace
+/// period drops it, and azcopy children are killed with it. Either way what
+/// was done before is summarized and the command fails with the
+/// cancellation's exit code. Commands that stop on Ctrl-C by themselves,
+/// like `serve`, pass `interrupts: false` and only time out.
+pub async fn run<F>(command: F, timeout: Option<Duration>, interrupts: bool) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    tokio::pin!(command);
+    let deadline = async {
+        match timeout {
+            Some(limit) => {
+                tokio::time::sleep(limit).await;
+                Reason::TimedOut(limit)
+            }
+            None => std::future::pending().await,
+        }
+    };
+    let interrupt = async {
+        if !interrupts || tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        Reason::Interrupted
+    };
+
+    let reason = tokio::select! {
+        result = &mut command => return result,
+        reason = deadline => reason,
+        reason = interrupt => reason,
+    };
+    cancel(reason);
+    if reason == Reason::Interrupted {
+        eprintln!(
+            "{} Interrupted; stopping (press Ctrl-C again to quit now)",
+            "⚠".yellow()
+        );
+    }
+
+    let stopped = tokio::select! {
+        _ = &mut command => true,
+        _ = tokio::time::sleep(GRACE_PERIOD) => false,
+        _ = tokio::signal::ctrl_c() => false,
+    };
+    if !stopped {
+        tracing::debug!("Command didn't stop in time; dropping it");
+    }
+    print_partial_summary();
+    Err(error().into())
+}
+
+fn print_partial_summary() {
+    let (files, bytes, failed) = audit_log::totals();
+    // Under --quiet the command's summary line reports this instead
+    if (files == 0 && failed == 0) || crate::logging::is_quiet() {
+        return;
+    }
+    eprintln!(
+        "{} Stopped after {} file(s) ({}) were transferred or removed{}",
+        "ℹ".blue(),
+        files,
+        format_size(bytes),
+        if failed > 0 {
+            format!(", {} failed", failed)
+        } else {
+            String::new()
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_guard_ends_cancelled_stream_with_error() {
+        let token = CancellationToken::new();
+        let pages = guard(
+            futures::stream::iter(vec![Ok::<_, anyhow::Error>(1), Ok(2)]),
+            token.clone(),
+        );
+        let pages: Vec<_> = pages.collect().await;
+        assert_eq!(pages.len(), 2);
+
+        token.cancel();
+        let pages = guard(
+            futures::stream::iter(vec![Ok::<_, anyhow::Error>(1)]),
+            token,
+        );
+        let pages: Vec<_> = pages.collect().await;
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_times_out() {
+        reset();
+        let stops_when_cancelled = async {
+            token().cancelled().await;
+            Ok(())
+        };
+        let result = run(stops_when_cancelled, Some(Duration::from_millis(10)), false).await;
+        assert_eq!(
+            crate::error::exit_code(&result.unwrap_err()),
+            crate::error::EXIT_TIMED_OUT
+        );
+        reset();
+    }
+}
@@ -0,0 +1,152 @@
+// ============================================================================
+// Checksums - MD5 and Azure's CRC64, computed incrementally
+// ============================================================================
+
+/// Reflected polynomial of the CRC64 variant used by Azure Storage
+const CRC64_POLY: u64 = 0x9A6C_9329_AC4B_C9B5;
+
+const fn crc64_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC64_POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC64_TABLE: [u64; 256] = crc64_table();
+
+/// Incremental CRC64 as used by Azure Storage for transactional checksums
+pub struct Crc64 {
+    state: u64,
+}
+
+impl Default for Crc64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc64 {
+    pub fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            let index = ((self.state ^ byte as u64) & 0xff) as usize;
+            self.state = CRC64_TABLE[index] ^ (self.state >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        !self.state
+    }
+}
+
+/// Which hashes to compute
+#[derive(Debug, Clone, Copy)]
+pub struct HashSelection {
+    pub md5: bool,
+    pub crc64: bool,
+}
+
+/// Running MD5 and/or CRC64 over a stream of chunks
+pub struct Hasher {
+    md5: Option<md5::Context>,
+    crc64: Option<Crc64>,
+}
+
+/// Raw digests produced by a [`Hasher`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Digests {
+    pub md5: Option<Vec<u8>>,
+    pub crc64: Option<Vec<u8>>,
+}
+
+impl Hasher {
+    pub fn new(selection: HashSelection) -> Self {
+        Self {
+            md5: selection.md5.then(md5::Context::new),
+            crc64: selection.crc64.then(Crc64::new),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        if let Some(md5) = &mut self.md5 {
+            md5.consume(data);
+        }
+        if let Some(crc64) = &mut self.crc64 {
+            crc64.update(data);
+        }
+    }
+
+    pub fn finish(self) -> Digests {
+        Digests {
+            md5: self.md5.map(|md5| md5.finalize().0.to_vec()),
+            // Azure encodes CRC64 values little-endian
+            crc64: self.crc64.map(|crc| crc.finish().to_le_bytes().to_vec()),
+        }
+    }
+}
+
+/// Lowercase hex encoding of a digest
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc64_check_value() {
+        let mut crc = Crc64::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xAE8B_1486_0A79_9888);
+    }
+
+    #[test]
+    fn test_crc64_incremental() {
+        let mut whole = Crc64::new();
+        whole.update(b"hello world");
+
+        let mut parts = Crc64::new();
+        parts.update(b"hello ");
+        parts.update(b"world");
+
+        assert_eq!(whole.finish(), parts.finish());
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&[0x00, 0xab, 0x10]), "00ab10");
+    }
+
+    #[test]
+    fn test_hasher_selection() {
+        let mut hasher = Hasher::new(HashSelection {
+            md5: true,
+            crc64: false,
+        });
+        hasher.update(b"hello world");
+        let digests = hasher.finish();
+
+        assert!(digests.crc64.is_none());
+        assert_eq!(
+            digests.md5.unwrap(),
+            md5::compute(b"hello world").0.to_vec()
+        );
+    }
+}
@@ -1,7 +1,46 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use azure_core::request_options::{LeaseDuration, LeaseId};
+use chrono::{DateTime, Utc};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use colored::*;
 
-use crate::commands::{cat, cp, du, ls, mv, rm, sync};
+use crate::audit_log;
+
+use crate::azure::{
+    AccountFilter, BlobHeaders, BlobTier, BlobType, CustomerKey, Md5Check, OverwritePolicy,
+    PathFilters,
+};
+use crate::bandwidth::BandwidthSchedule;
+use crate::cancel;
+use crate::commands::bench::BenchMode;
+use crate::commands::cp::{CaseConflictPolicy, CopyOptions};
+use crate::commands::ctl::PublicAccessLevel;
+use crate::commands::du::{Breakdown, SortOrder};
+use crate::commands::immutability::{parse_until, MAX_RETENTION_DAYS};
+use crate::commands::lease::{parse_lease_duration, parse_lease_id};
+#[cfg(feature = "fuse")]
+use crate::commands::mount;
+use crate::commands::rm::BlobSelection;
+use crate::commands::{
+    account, alias, audit, auth, azcopy, bench, cache, cat, complete, compose, cp, cp_status, ctl,
+    du, hash, immutability, inventory, lease, lifecycle, ls, mv, parse, retry_failed, rm, security,
+    serve, shell, sync, watch,
+};
+use crate::compression::Compression;
+use crate::config::{self, Config, TransferConfig};
+use crate::error::AzstError;
+use crate::login::{LoginFlow, DEFAULT_TENANT, STORAGE_SCOPE};
+use crate::network::{self, NetworkOptions};
+use crate::output::{ColorChoice, Column, OutputFormat, ProgressMode};
+use crate::pack::DEFAULT_PACK_THRESHOLD;
+use crate::prompt;
+use crate::symlinks::SymlinkPolicy;
+use crate::sync_state::{CompareMode, ConflictPolicy, DeleteLimit};
+use crate::tuning::TuningMode;
+use crate::utils::{parse_duration, parse_location, parse_regex_list, parse_size};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "azst")]
@@ -10,13 +49,267 @@ use crate::commands::{cat, cp, du, ls, mv, rm, sync};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Log azcopy invocations, HTTP requests and retries to stderr (-vv for more)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Only print errors, warnings and requested output, without progress,
+    /// status lines or colors (unless --color always); cp, mv, rm and sync print nothing on success
+    /// and a single key=value summary line otherwise
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Retry failed Azure requests up to N times with exponential backoff (default: 8)
+    #[arg(long, value_name = "N", global = true)]
+    pub retries: Option<u32>,
+    /// Stop the command after this long (e.g. 30m, 2h), cancelling its
+    /// azcopy jobs and exiting with code 124; Ctrl-C exits with 130
+    #[arg(long, value_name = "DURATION", global = true, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+    /// Stable output for golden files: sorted listings, no colors, progress
+    /// bars or job-specific details
+    #[arg(long, global = true)]
+    pub deterministic: bool,
+    /// When to color output: auto colors on a terminal unless NO_COLOR or
+    /// CLICOLOR=0 is set (CLICOLOR_FORCE forces it)
+    #[arg(long, value_enum, global = true, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+    /// Fail with exit code 2 instead of asking for confirmation, as happens
+    /// anyway when stdin isn't a terminal; --force skips the confirmations
+    #[arg(long, global = true, env = "AZST_NON_INTERACTIVE")]
+    pub non_interactive: bool,
+    /// Output format for ls and du listings, dry-run plans, ctl show and
+    /// security audits
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// Transfer progress: one bar for the whole job, or a bar for each file
+    /// in flight when azst transfers files itself (leased uploads, --pack, --tar)
+    #[arg(long, value_enum, global = true, default_value_t = ProgressMode::Aggregate)]
+    pub progress: ProgressMode,
+    /// Append a JSON line for every command that changes files or blobs to
+    /// this file (default: `log` in the [audit] section of the config file)
+    #[arg(long, value_name = "PATH", global = true)]
+    pub audit_log: Option<PathBuf>,
+    /// Exit with code 5 when some transfers failed, instead of only warning
+    #[arg(long, global = true)]
+    pub strict: bool,
+    /// Use the settings of this [profiles.<NAME>] section of the config file
+    #[arg(long, value_name = "NAME", global = true, env = "AZST_PROFILE")]
+    pub profile: Option<String>,
+    /// Send requests through this HTTP(S) proxy, for azst and azcopy
+    /// (default: HTTPS_PROXY from the environment)
+    #[arg(long, value_name = "URL", global = true)]
+    pub proxy: Option<String>,
+    /// Also trust the root certificates in this PEM bundle, e.g. a
+    /// TLS-intercepting firewall's
+    #[arg(long, value_name = "PATH", global = true)]
+    pub ca_cert: Option<PathBuf>,
+    /// Don't verify TLS certificates of azst's own requests (azcopy still does)
+    #[arg(long, global = true, conflicts_with = "ca_cert")]
+    pub insecure: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Choose or show the default storage account
+    #[command(long_about = "Choose or show the default storage account
+
+The default account is saved as default_account in the config file. Afterwards
+'az:<container>/<path>' is short for 'az://<account>/<container>/<path>', legacy
+'az://<container>/<path>' URIs without an account use it, and 'azst ls <container>/'
+lists a container of it when no local path of that name exists.
+
+Examples:
+  # Work in one account without repeating its name
+  azst account set myaccount
+  azst ls az:data/2024/
+  azst cp report.csv az:data/reports/
+
+  # List a container of the default account
+  azst ls data/
+
+  # Which account is the default
+  azst account show")]
+    Account {
+        #[command(subcommand)]
+        action: AccountAction,
+    },
+    /// Name locations, so `name://path` stands for a long az:// URI
+    #[command(
+        long_about = "Name locations, so `name://path` stands for a long az:// URI
+
+Aliases are saved in the [aliases] section of the config file. Any argument of the
+form '<alias>://<path>' is expanded before the command runs, so aliases work with
+every command.
+
+Examples:
+  # Name a prefix, then use it wherever an az:// URI goes
+  azst alias add curated az://prodaccount/datasets/curated/
+  azst ls curated://2024/
+  azst cp -r curated://2024/03/ ./march/
+
+  # Show and remove aliases
+  azst alias list
+  azst alias remove curated"
+    )]
+    Alias {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+    /// Audit an account's security settings, or spot-check a migration
+    #[command(
+        long_about = "Audit an account's security settings, or spot-check a migration
+
+With only an account, reports settings that expose its data or make it hard to
+recover: containers open to anonymous reads, shared key authorization, and blob
+soft delete, container soft delete or versioning being off. Findings are sorted by
+severity; use --format json to feed them to a pipeline. Account settings need a
+role on the account, such as Reader; without one only containers are checked.
+
+With a destination, lists both locations, picks a deterministic sample of source
+objects and compares their sizes and MD5 hashes with the destination. Stored
+Content-MD5 values are used when present; otherwise content is streamed and hashed,
+optionally rate-limited. The report includes a 95% confidence upper bound on the
+share of differing objects.
+
+Examples:
+  # Security findings for an account
+  azst audit az://myaccount/
+
+  # The same, as JSON for a CI job
+  azst audit --format json az://myaccount/
+
+  # Check 1% of a migrated dataset
+  azst audit --sample 1 az://oldaccount/data/ az://newaccount/data/
+
+  # Re-run with a different sample, hashing at most 200 Mbps
+  azst audit --sample 0.5 --seed 42 --cap-mbps 200 az://old/data/ az://new/data/"
+    )]
+    Audit {
+        /// Account to audit (az://account/), or the source of a migration
+        /// (az://account/container/prefix)
+        source: String,
+        /// Destination of the migration to compare against
+        /// (az://account/container/prefix)
+        destination: Option<String>,
+        /// Percentage of source objects to check
+        #[arg(long, default_value_t = 1.0, requires = "destination")]
+        sample: f64,
+        /// Seed for choosing the sample (the same seed picks the same objects)
+        #[arg(long, default_value_t = 0, requires = "destination")]
+        seed: u64,
+        /// Limit hashing reads in megabits per second
+        #[arg(long, requires = "destination")]
+        cap_mbps: Option<f64>,
+    },
+    /// Sign in to Azure, inspect the active identity or print access tokens
+    #[command(
+        long_about = "Sign in to Azure, inspect the active identity or print access tokens
+
+'azst auth login' signs in with a Microsoft Entra ID account through a browser or a
+device code, so the Azure CLI isn't needed. The sign-in takes precedence over every
+other credential until 'azst auth logout'.
+
+Without it, azst uses the credential chain: service principal environment variables,
+workload identity, managed identity, then the Azure CLI. Tokens are cached in a file
+only readable by the current user and reused until they expire, so commands don't have
+to run 'az' every time.
+
+Examples:
+  # Sign in through the browser
+  azst auth login
+
+  # Sign in from an SSH session, entering a code on another device
+  azst auth login --device-code --tenant contoso.onmicrosoft.com
+
+  # Show which identity and tenant commands run as
+  azst auth status
+
+  # Call the Blob REST API with curl
+  curl -H \"Authorization: Bearer $(azst auth token)\" -H 'x-ms-version: 2021-08-06' \\
+    https://myaccount.blob.core.windows.net/mycontainer/file.txt
+
+  # Sign out and forget cached tokens, e.g. after 'az login' with another account
+  azst auth logout"
+    )]
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+    /// Install or inspect the AzCopy binary azst uses for transfers
+    #[command(
+        long_about = "Install or inspect the AzCopy binary azst uses for transfers
+
+azst runs a pinned AzCopy release, either from PATH or from its own bundled copy.
+These commands download that release for the current OS and architecture into the
+bundled location, verifying its SHA-256 checksum against the one published by GitHub.
+
+Examples:
+  # Download the pinned AzCopy release
+  azst azcopy install
+
+  # Replace an older bundled AzCopy with the pinned release
+  azst azcopy update
+
+  # Show which AzCopy binary will be used
+  azst azcopy which"
+    )]
+    Azcopy {
+        #[command(subcommand)]
+        action: AzcopyAction,
+    },
+    /// Measure upload or download throughput to a container
+    #[command(long_about = "Measure upload or download throughput to a container
+
+Runs AzCopy's benchmark: --file-count files of --size-per-file each are generated
+in memory and uploaded under the prefix (with --download, uploaded first and then
+timed downloading, to nowhere), and the test data is deleted afterwards unless
+--keep-data is given. Reports throughput, IOPS and the share of requests the
+service turned away, followed by AzCopy's performance advice and suggested
+concurrency or block size for real transfers.
+
+Examples:
+  # Upload 64 files of 256 MiB
+  azst bench --size-per-file 256M --file-count 64 az://myaccount/scratch/
+
+  # Time downloads instead
+  azst bench --download az://myaccount/scratch/
+
+  # Many small files, to see the IOPS the account sustains
+  azst bench --size-per-file 64K --file-count 10000 az://myaccount/scratch/
+
+  # Results as JSON, to compare runs
+  azst --format json bench az://myaccount/scratch/ > bench.json")]
+    Bench(Box<BenchArgs>),
+    /// Refresh or clear the listings kept for --cached
+    #[command(long_about = "Refresh or clear the listings kept for --cached
+
+Recursive listings (`ls -r`, `inventory`, and `cache refresh`) are kept in the user
+cache directory, one per container, so `ls --cached` and `du --cached` can answer
+without listing again. Cached answers older than a day come with a warning; the
+cache only changes when a listing is refreshed, never when blobs are written.
+
+Examples:
+  # List a container again and keep the listing
+  azst cache refresh az://myaccount/mycontainer/
+
+  # Every container of an account
+  azst cache refresh az://myaccount/
+
+  # Then ask as often as needed
+  azst du -H -d 2 --cached az://myaccount/mycontainer/
+
+  # Forget one account's listings, or all of them
+  azst cache clear az://myaccount/
+  azst cache clear")]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
     /// Concatenate object content to stdout (like gsutil cat)
     #[command(long_about = "Concatenate object content to stdout (like gsutil cat)
 
+Blobs stored with a gzip or zstd Content-Encoding are decompressed as they are
+written, unless --raw is given. Byte ranges (-r) always select stored bytes.
+
 Examples:
   # Output blob contents to stdout
   azst cat az://myaccount/mycontainer/file.txt
@@ -37,7 +330,25 @@ Examples:
   azst cat az://myaccount/mycontainer/file.txt > local_file.txt
 
   # Pipe to other commands
-  azst cat az://myaccount/mycontainer/data.csv | head -10")]
+  azst cat az://myaccount/mycontainer/data.csv | head -10
+
+  # Download a large blob over 16 concurrent range requests
+  azst cat --parallel-ranges 16 az://myaccount/mycontainer/large.bin > large.bin
+
+  # Fetch up to 8 upcoming blobs while the current one is written
+  azst cat --prefetch 8 az://myaccount/mycontainer/parts/*.csv
+
+  # Read a blob encrypted with a customer-provided key
+  azst cat --customer-key \"$KEY\" az://myaccount/mycontainer/secret.txt
+
+  # Blobs stored with Content-Encoding gzip or zstd are decompressed
+  azst cat az://myaccount/mycontainer/logs.json.gz | jq .
+
+  # Also decompress .gz/.zst blobs uploaded without a Content-Encoding
+  azst cat --decompress az://myaccount/mycontainer/archive.csv.zst
+
+  # Write the stored (compressed) bytes as they are
+  azst cat --raw az://myaccount/mycontainer/logs.json.gz > logs.json.gz")]
     Cat {
         /// URLs to read (az://container/path)
         urls: Vec<String>,
@@ -47,6 +358,52 @@ Examples:
         /// Output just the specified byte range (e.g., '256-5939', '256-', or '-5')
         #[arg(short, long)]
         range: Option<String>,
+        /// Number of upcoming blobs to start downloading while one is written (0 to disable)
+        #[arg(long, value_name = "N", default_value_t = 2)]
+        prefetch: usize,
+        /// Download each blob as N concurrent range requests (default: 4)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        parallel_ranges: Option<u64>,
+        /// Base64-encoded AES-256 key the blobs were encrypted with
+        /// (customer-provided key)
+        #[arg(long, env = "AZST_CUSTOMER_KEY", hide_env_values = true)]
+        customer_key: Option<CustomerKey>,
+        /// Also decompress blobs named .gz or .zst that have no Content-Encoding
+        #[arg(long, conflicts_with_all = ["raw", "range"])]
+        decompress: bool,
+        /// Write the stored bytes, without decompressing gzip or zstd blobs
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Concatenate blobs into a new blob server-side (like gsutil compose)
+    #[command(
+        long_about = "Concatenate blobs into a new blob server-side (like gsutil compose)
+
+Builds a block blob from an ordered list of existing blobs using Put Block From URL,
+so the data never leaves Azure. Wildcard sources expand to matching blobs sorted by
+name. Sources may live in other storage accounts you can read.
+
+Examples:
+  # Join sharded CSV outputs into one file
+  azst compose 'az://myaccount/results/part-*.csv' az://myaccount/results/all.csv
+
+  # Concatenate explicit blobs in a given order
+  azst compose az://myaccount/c/header.csv az://myaccount/c/body.csv az://myaccount/c/full.csv"
+    )]
+    Compose {
+        /// Source blobs in order, followed by the destination blob
+        #[arg(required = true, num_args = 2..)]
+        uris: Vec<String>,
+        /// Content-Type for the new blob (defaults to the first source's)
+        #[arg(long)]
+        content_type: Option<String>,
+    },
+    /// List completions for a partial az:// URI (used by shell completion scripts)
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// Partial URI typed so far, e.g. az://myaccount/mycontainer/da
+        #[arg(default_value = "")]
+        partial: String,
     },
     /// Copy files to/from Azure storage (like gsutil cp)
     #[command(long_about = "Copy files to/from Azure storage (like gsutil cp)
@@ -54,6 +411,24 @@ Examples:
 Uses AzCopy backend for blazing-fast parallel transfers. Supports local-to-Azure, 
 Azure-to-local, and Azure-to-Azure (server-side) operations.
 
+Directories copied with -r are placed under a folder named after them at the
+destination, with or without a trailing slash on the source: /local/dir/ to
+az://myaccount/c/prefix/ creates prefix/dir/. Use --as-subdir=false (or a
+dir/* source) to copy the contents straight into the destination, and
+--flatten to drop the directory structure altogether. A destination ending
+with a slash is a directory to copy into.
+
+With --async, copies between Azure locations are started as server-side Copy
+Blob operations and left running: copies of archive-tier blobs (which are
+rehydrated on the way) and between regions can take hours. Follow them with
+cp-status.
+
+An http(s):// source is copied into Azure by the service itself, without
+downloading it: files up to 256 MiB in a single request, larger ones (or ones
+whose size the server doesn't report) by an asynchronous copy that --poll
+waits for. A source of - reads a list of URLs from stdin, one per line, each
+copied under the destination prefix with the name from its URL.
+
 Examples:
   # Copy file to Azure
   azst cp /local/file.txt az://myaccount/mycontainer/
@@ -64,6 +439,15 @@ Examples:
   # Copy directory recursively
   azst cp -r /local/dir/ az://myaccount/mycontainer/prefix/
 
+  # Download the blobs a wildcard matches, keeping their paths below data/
+  azst cp 'az://myaccount/mycontainer/data/**/*.csv' /local/csv/
+
+  # Copy a directory's contents without nesting it under its name
+  azst cp -r --as-subdir=false /local/dir/ az://myaccount/mycontainer/prefix/
+
+  # Gather every file under a tree into one directory
+  azst cp -r --flatten az://myaccount/mycontainer/reports/ /local/reports/
+
   # Azure-to-Azure copy (server-side, no download/upload)
   azst cp -r az://account1/container1/data/ az://account2/container2/backup/
 
@@ -73,40 +457,152 @@ Examples:
   # Limit bandwidth usage (in megabits per second)
   azst cp -r --cap-mbps 100 /large/dataset/ az://myaccount/mycontainer/
 
+  # Tune azcopy with a [profiles.fast] section of the config file
+  azst --profile fast cp -r /large/dataset/ az://myaccount/mycontainer/
+
+  # Go easy on the office link during work hours, full speed at night.
+  # While it runs, kill -USR1 <pid> pauses the upload and kill -USR2 resumes it
+  azst cp -r --bandwidth-schedule '09:00-18:00=50,18:00-09:00=0' \\
+    /large/dataset/ az://myaccount/mycontainer/
+
   # Filter files by pattern (supports wildcards)
   azst cp -r --include-pattern '*.jpg;*.png' /photos/ az://myaccount/photos/
 
+  # Skip whole subtrees, and files whose relative path matches a regex
+  azst cp -r --exclude-path 'node_modules;.git' --exclude-regex '\\.bak$' \\
+    /project/ az://myaccount/src/
+
   # Create MD5 hashes during upload
   azst cp -r --put-md5 /important-data/ az://myaccount/backup/
 
   # Use larger block sizes for large files
-  azst cp -r --block-size-mb 32 /big-videos/ az://myaccount/media/")]
-    Cp {
-        /// Source path (local file or az://container/path)
-        source: String,
-        /// Destination path (local file or az://container/path)
-        destination: String,
-        /// Recursive copy for directories
+  azst cp -r --block-size-mb 32 /big-videos/ az://myaccount/media/
+
+  # Keep azcopy's defaults even for a premium block blob account
+  azst cp -r --tuning standard /big-videos/ az://premiumacct/media/
+
+  # Never replace files that already exist at the destination
+  azst cp -r --no-clobber /photos/ az://myaccount/photos/
+
+  # Only replace destination files that are older than the source
+  azst cp -r --overwrite if-newer az://myaccount/data/ /local/data/
+
+  # Keep file timestamps and permissions across an upload/download round trip
+  azst cp -r --preserve /local/project/ az://myaccount/backup/project/
+
+  # Deploy a static website with the right headers in one pass
+  azst cp -r --cache-control 'public, max-age=3600' /site/ az://myaccount/$web/
+
+  # Upload archives straight to the archive tier with metadata
+  azst cp -r --tier archive --metadata project=alpha /logs/ az://myaccount/archive/
+
+  # Verify the uploaded data against local MD5 hashes
+  azst cp -r --check-md5 /important-data/ az://myaccount/backup/
+
+  # On macOS/Windows, keep blobs like Data.csv and data.csv apart
+  azst cp -r --case-conflict rename az://myaccount/dataset/ ~/dataset/
+
+  # Keep empty folders across an upload/download round trip
+  azst cp -r --preserve-empty-dirs /local/project/ az://myaccount/backup/
+
+  # Refuse to upload more than 50 GiB in one go
+  azst cp -r --max-upload-bytes 50G /exports/ az://myaccount/exports/
+
+  # Store highly compressible data compressed; downloads restore it
+  azst cp -r --compress zstd /logs/ az://myaccount/archive/
+  azst cp -r az://myaccount/archive/logs/ /restore/
+
+  # Gzip only text assets of a static site, like gsutil cp -z
+  azst cp -r -z html,css,js,svg ./public/ 'az://myaccount/$web/'
+
+  # Copy between accounts in different regions (egress is billed)
+  azst cp -r --allow-cross-region az://eastaccount/data/ az://westaccount/data/
+
+  # Record the files that failed to copy, with their error codes
+  azst cp -r --failures-out failures.json /data/ az://myaccount/data/
+
+  # Encrypt uploads with your own AES-256 key; downloads need the same key
+  export AZST_CUSTOMER_KEY=$(openssl rand -base64 32)
+  azst cp -r /secret/ az://myaccount/secret/
+
+  # Store a tree of many small files as a single compressed blob, and back
+  azst cp --tar /data/tiles/ az://myaccount/archives/tiles.tar.zst
+  azst cp --untar az://myaccount/archives/tiles.tar.zst /restore/tiles/
+
+  # Pack files under 256K into large blobs behind an index, and unpack them
+  azst cp -r --pack 256K /data/thumbnails/ az://myaccount/media/
+  azst cp -r --unpack az://myaccount/media/thumbnails/ /restore/
+
+  # Record copied files, so rerunning after a failure skips them
+  azst cp -r --manifest job.json /data/ az://myaccount/data/
+
+  # Ingest a web asset server-side, waiting if the copy runs in the background
+  azst cp --poll https://example.com/data/dump.csv az://myaccount/raw/
+
+  # Ingest every URL of a list into a prefix
+  azst cp - az://myaccount/raw/assets/ < urls.txt
+
+  # Restore an archived tree into another account without waiting, then check on it
+  azst cp -r --async az://coldaccount/archive/2019/ az://myaccount/restore/
+  azst cp-status -r az://myaccount/restore/2019/
+
+  # Upload what symbolic links point to instead of skipping them
+  azst cp -r --follow-symlinks /srv/site/ az://myaccount/www/
+
+  # Upload a fixed-size VHD as a page blob, to import as a managed disk
+  azst cp --blob-type page disk.vhd az://myaccount/vhds/disk.vhd")]
+    Cp(Box<CpArgs>),
+    /// Show or abort the server-side copies started by cp --async
+    #[command(
+        long_about = "Show or abort the server-side copies started by cp --async
+
+Lists each copy into the blob (or with -r, every blob under the prefix) with its
+status (pending, success, aborted or failed) and progress. Fails with the
+transfer exit code when any copy failed. --abort stops the pending copies,
+leaving empty destination blobs behind.
+
+Examples:
+  # Check on the copies into a prefix
+  azst cp-status -r az://myaccount/restore/2019/
+
+  # Give up on a copy that is still running
+  azst cp-status --abort az://myaccount/restore/2019/big.tar"
+    )]
+    CpStatus {
+        /// Destination blob (az://account/container/path), or prefix with -r
+        uri: String,
+        /// Report the copies into every blob under the prefix
         #[arg(short, long)]
         recursive: bool,
-        /// Preview what would be copied without actually copying
-        #[arg(long)]
-        dry_run: bool,
-        /// Limit transfer rate in megabits per second
-        #[arg(long)]
-        cap_mbps: Option<f64>,
-        /// Block size in MiB for upload/download (e.g., 8, 16, 32)
-        #[arg(long)]
-        block_size_mb: Option<f64>,
-        /// Create MD5 hash for each file and save as Content-MD5 property
-        #[arg(long)]
-        put_md5: bool,
-        /// Include only files matching this pattern (supports wildcards like *.jpg;*.pdf)
-        #[arg(long)]
-        include_pattern: Option<String>,
-        /// Exclude files matching this pattern (supports wildcards like *.log;*.tmp)
+        /// Abort the copies that are still pending
         #[arg(long)]
-        exclude_pattern: Option<String>,
+        abort: bool,
+    },
+    /// Inspect containers and control anonymous access to them
+    #[command(long_about = "Inspect containers and control anonymous access to them
+
+`show` prints a container's public access level, lease, immutability,
+metadata and stored access policies; given only an account, it lists the public
+access level of every container in it. `set-public-access` changes who can read
+a container without credentials, keeping its stored access policies.
+
+Examples:
+  # Show a container's settings
+  azst ctl show az://myaccount/mycontainer/
+
+  # Public access level of every container in an account
+  azst ctl show az://myaccount/
+
+  # Disable anonymous access
+  azst ctl set-public-access az://myaccount/mycontainer/ off
+
+  # Public access of every container in every account, as JSON
+  for account in $(azst --format csv ls | tail -n +2); do
+    azst --format json ctl show $account
+  done")]
+    Ctl {
+        #[command(subcommand)]
+        action: CtlAction,
     },
     /// Display disk usage statistics (like gsutil du)
     #[command(long_about = "Display disk usage statistics (like gsutil du)
@@ -132,26 +628,185 @@ Examples:
   # Calculate usage for all containers in an account
   azst du az://myaccount/
 
+  # Only the containers matching a pattern, or the same prefix in each of them
+  azst du -c 'az://myaccount/logs-*/'
+  azst du -s 'az://myaccount/backup-2024-??/db/'
+
+  # Only the blobs a wildcard matches, at any depth with **
+  azst du -sH 'az://myaccount/mycontainer/data/**/*.csv'
+
+  # Only the top-level directories, largest first
+  azst du -H -d 1 --sort size az://myaccount/mycontainer/
+
+  # The ten heaviest prefixes at any depth
+  azst du -H --top 10 az://myaccount/mycontainer/
+
+  # Blob counts and bytes per access tier, for each container
+  azst du -H --objects --tier-breakdown az://myaccount/
+
+  # A container with hundreds of millions of blobs, listed 16 prefixes at a time
+  azst du -sH --partitions 16 az://myaccount/mycontainer/
+
+  # Instantly, from the listing the last `ls -r` or `azst cache refresh` kept
+  azst du -H -d 1 --cached az://myaccount/mycontainer/
+
   # Calculate usage for local directory
   azst du /local/path/
 
   # Summarize local directory
   azst du -s /local/path/")]
-    Du {
-        /// Path to analyze (az://container/path or local path)
-        path: Option<String>,
-        /// Display only total size for each argument
+    Du(Box<DuArgs>),
+    /// Export the full listing of a container or account to a file
+    #[command(
+        long_about = "Export the full listing of a container or account to a file
+
+Streams every blob under the location into a CSV, TSV, JSON Lines or Parquet file
+for offline analysis: container, name, size, last modified time (UTC), access
+tier, ETag, Content-MD5 (hex) and index tags. The format follows the file's
+extension; Parquet needs azst built with --features parquet.
+
+CSV, TSV and JSON Lines exports record their progress in FILE.resume after every
+page of the listing, so if one is interrupted, running the same command again
+continues where it stopped instead of listing everything again.
+
+For very large containers, --partitions N lists each first-level prefix on its
+own, N at a time. Records then come in no particular order, and such an export
+can't be resumed.
+
+Examples:
+  # Every blob in a container, for a spreadsheet or DataFrame
+  azst inventory az://myaccount/mycontainer/ --out inventory.csv
+
+  # Every container in an account, as JSON Lines
+  azst inventory az://myaccount/ --out inventory.jsonl
+
+  # Discard an interrupted export and list everything again
+  azst inventory az://myaccount/mycontainer/logs/ --out logs.csv --restart
+
+  # A container with hundreds of millions of blobs, 32 prefixes at a time
+  azst inventory az://myaccount/mycontainer/ --out inventory.parquet --partitions 32"
+    )]
+    Inventory {
+        /// Location to export (az://account/, az://account/container/ or a prefix)
+        uri: String,
+        /// File to write: .csv, .tsv, .jsonl or .parquet
+        #[arg(short, long, value_name = "FILE")]
+        out: PathBuf,
+        /// Start over instead of resuming an interrupted export to the same file
+        #[arg(long)]
+        restart: bool,
+        /// List the first-level prefixes as N concurrent listings (not resumable)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        partitions: Option<u16>,
+    },
+    /// Print checksums of local files or blobs (like gsutil hash)
+    #[command(
+        long_about = "Print checksums of local files or blobs (like gsutil hash)
+
+Computes MD5 and CRC64 hashes so local and cloud copies can be compared. For blobs,
+the stored Content-MD5 is used when present; otherwise the content is streamed and
+hashed. CRC64 uses the same variant Azure Storage uses for transactional checks.
+
+Examples:
+  # Both hashes of a local file, base64-encoded
+  azst hash /local/data.csv
+
+  # MD5 of a blob in hex, to compare with md5sum output
+  azst hash -m --hex az://myaccount/mycontainer/data.csv"
+    )]
+    Hash {
+        /// Files or blobs to hash (local path or az://account/container/path)
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Compute the MD5 hash
         #[arg(short, long)]
-        summarize: bool,
-        /// Show sizes in human readable format (KB, MB, GB)
-        #[arg(short = 'H', long)]
-        human_readable: bool,
-        /// Display grand total
-        #[arg(short = 'c', long)]
-        total: bool,
-        /// Storage account name
+        md5: bool,
+        /// Compute the CRC64 hash
         #[arg(short, long)]
-        account: Option<String>,
+        crc64: bool,
+        /// Print hashes in hex instead of base64
+        #[arg(long)]
+        hex: bool,
+    },
+    /// Take, renew, release or break exclusive leases on containers and blobs
+    #[command(
+        long_about = "Take, renew, release or break exclusive leases on containers and blobs
+
+A leased blob can only be written or deleted by whoever presents the lease ID,
+and a leased container can't be deleted, so backup scripts can use leases as
+locks. `acquire` prints the lease ID on stdout; azst also remembers it, so later
+`renew` and `release` calls, and cp and rm on the leased blob, use it without
+--lease-id.
+
+Examples:
+  # Lock a blob for a minute and keep the lease ID
+  LEASE=$(azst lease acquire az://myaccount/backups/latest.tar)
+
+  # Lock it until released
+  azst lease acquire --duration infinite az://myaccount/backups/latest.tar
+
+  # Overwrite and then delete the leased blob
+  azst cp latest.tar az://myaccount/backups/latest.tar
+  azst rm --lease-id \"$LEASE\" az://myaccount/backups/latest.tar
+
+  # Extend or give up a lease
+  azst lease renew az://myaccount/backups/latest.tar
+  azst lease release --lease-id \"$LEASE\" az://myaccount/backups/latest.tar
+
+  # Break a lease left behind by a crashed script, within 10 seconds
+  azst lease break --break-period 10 az://myaccount/backups/"
+    )]
+    Lease {
+        #[command(subcommand)]
+        action: LeaseAction,
+    },
+    /// Place or lift legal holds on containers and blobs
+    #[command(long_about = "Place or lift legal holds on containers and blobs
+
+A blob under legal hold can't be changed or deleted until the hold is lifted,
+whatever its retention policy says. Holds on a blob need version-level
+immutability to be enabled on its container or account. A container is held
+as long as any of its tags (e.g. case numbers) remain.
+
+Examples:
+  # Hold a single blob
+  azst legalhold set az://myaccount/records/2024/ledger.csv
+
+  # Hold a whole container for a case
+  azst legalhold set --tag case4711 az://myaccount/records/
+
+  # Lift one case's hold, or every hold on the container
+  azst legalhold clear --tag case4711 az://myaccount/records/
+  azst legalhold clear az://myaccount/records/")]
+    Legalhold {
+        #[command(subcommand)]
+        action: LegalHoldAction,
+    },
+    /// Read or replace an account's lifecycle management policy
+    #[command(long_about = "Read or replace an account's lifecycle management policy
+
+The policy's rules move blobs to cooler tiers or delete them once they reach
+an age, optionally only blobs under some prefixes. Files are in the format
+`az storage account management-policy create --policy` takes; the output of
+`management-policy show` is accepted too. Files ending in .yaml or .yml are
+YAML, others JSON. Policies are managed through Azure Resource Manager, so
+they need a role that can manage the storage account.
+
+Examples:
+  # Save the current policy for editing
+  azst lifecycle get az://myaccount/ policy.yaml
+
+  # Check a policy file without applying it
+  azst lifecycle set --dry-run az://myaccount/ policy.yaml
+
+  # Replace the policy
+  azst lifecycle set az://myaccount/ policy.yaml
+
+  # Copy one account's policy to another
+  azst lifecycle get az://prodaccount/ | azst lifecycle set az://stagingaccount/ -")]
+    Lifecycle {
+        #[command(subcommand)]
+        action: LifecycleAction,
     },
     /// List objects in Azure storage (like gsutil ls)
     #[command(long_about = "List objects in Azure storage (like gsutil ls)
@@ -174,32 +829,95 @@ Examples:
   # List with human-readable sizes
   azst ls -lH az://myaccount/mycontainer/
 
-  # Recursive listing
+  # Recursive listing, a section per directory (--flat for one URI per line)
   azst ls -r az://myaccount/mycontainer/prefix/
 
-  # List with wildcards
-  azst ls 'az://myaccount/mycontainer/*.txt'")]
-    Ls {
-        /// Path to list (az://account/container/ or az://account/container/prefix)
-        path: Option<String>,
-        /// Show detailed information
-        #[arg(short, long)]
-        long: bool,
-        /// Show file sizes in human readable format
-        #[arg(short = 'H', long)]
-        human_readable: bool,
-        /// Recursive listing
-        #[arg(short, long)]
-        recursive: bool,
-        /// Storage account name
-        #[arg(short, long)]
-        account: Option<String>,
+  # The directory itself rather than its contents
+  azst ls -d az://myaccount/mycontainer/prefix/
+
+  # A single blob (a path without a trailing slash that names no blob is a prefix)
+  azst ls -l az://myaccount/mycontainer/reports/q3.pdf
+
+  # List with wildcards: *, ? and [...] stay within a directory, ** spans any number
+  azst ls 'az://myaccount/mycontainer/*.txt'
+  azst ls 'az://myaccount/mycontainer/data/**/*.csv'
+
+  # List every container matching a pattern in turn (-d: the containers themselves)
+  azst ls 'az://myaccount/backup-2024-??/'
+  azst ls -d 'az://myaccount/logs-*'
+
+  # Generate download links that expire in one hour
+  azst ls --signed-urls 1h 'az://myaccount/mycontainer/reports/*.pdf'
+
+  # Count the blobs under a prefix and add up their sizes
+  azst ls -r --summary az://myaccount/mycontainer/logs/
+
+  # Answer from the listing the last `ls -r` or `azst cache refresh` kept
+  azst ls --cached 'az://myaccount/mycontainer/logs/*.gz'
+
+  # Long listing with ETags
+  azst ls -e az://myaccount/mycontainer/
+
+  # Choose the columns of the long listing
+  azst ls --columns size,tier,etag,name az://myaccount/mycontainer/
+
+  # Show blobs added (A), modified (M) or deleted (D) since the previous run
+  azst ls --since-state inbox.json az://myaccount/mycontainer/inbox/
+
+  # Remove every log under a prefix, whatever characters the names contain
+  azst ls -0 'az://myaccount/mycontainer/logs/**/*.log' | azst rm -f -I
+
+  # Storage accounts of one resource group whose names start with prod
+  azst ls -l --resource-group analytics --name-filter 'prod*'
+
+  # Storage accounts in every subscription you can see
+  azst ls --all-subscriptions")]
+    Ls(Box<LsArgs>),
+    /// Mount a prefix as a read-only local filesystem (FUSE)
+    #[cfg(feature = "fuse")]
+    #[command(long_about = "Mount a prefix as a read-only local filesystem (FUSE)
+
+Lists the blobs under the prefix once and shows them as files and directories.
+File contents are fetched on demand with ranged reads and kept in a block cache,
+so tools that only understand files can read parts of large blobs without
+downloading them. Runs in the foreground until Ctrl-C or `fusermount -u`.
+
+Examples:
+  # Browse a container
+  mkdir -p /mnt/data && azst mount az://myaccount/data/ /mnt/data
+
+  # Larger blocks, cache and readahead for sequential reads of big files
+  azst mount --block-size 16M --cache-size 512M --readahead 8 az://myaccount/rasters/2024/ /mnt/rasters")]
+    Mount {
+        /// Prefix to mount (az://account/container/[prefix/])
+        uri: String,
+        /// Existing directory to mount it on
+        mountpoint: PathBuf,
+        /// Size of the ranged reads blobs are fetched in [default: 4M, or
+        /// block_size under [readahead] in the config file]
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        block_size: Option<u64>,
+        /// Memory for cached blocks of each open file [default: 128M, or
+        /// cache_size under [readahead]]
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        cache_size: Option<u64>,
+        /// Blocks fetched ahead of a sequential reader [default: 4, or blocks
+        /// under [readahead]]
+        #[arg(long, value_name = "BLOCKS")]
+        readahead: Option<u64>,
+        /// Let other users read the mount (needs user_allow_other in /etc/fuse.conf)
+        #[arg(long)]
+        allow_other: bool,
     },
     /// Move files to/from Azure storage (like gsutil mv)
     #[command(long_about = "Move files to/from Azure storage (like gsutil mv)
 
 Moves files by copying to destination and deleting from source. Supports local-to-Azure,
-Azure-to-local, and Azure-to-Azure operations.
+Azure-to-local, and Azure-to-Azure operations. Only files that were copied are removed:
+failed transfers, and files skipped because they already exist, stay at the source.
+
+Moves within one storage account happen server-side without transferring data: a single
+atomic rename on accounts with a hierarchical namespace, otherwise a copy and delete per blob.
 
 Examples:
   # Move file to Azure
@@ -214,19 +932,83 @@ Examples:
   # Force move without confirmation
   azst mv -rf /local/file.txt az://myaccount/mycontainer/
 
+  # Rename a directory within an account (instant on hierarchical namespace accounts)
+  azst mv -r az://myaccount/data/staging/ az://myaccount/data/published/
+
   # Move between Azure accounts
-  azst mv -r az://account1/container1/data/ az://account2/container2/")]
-    Mv {
-        /// Source path (local file or az://container/path)
-        source: String,
-        /// Destination path (local file or az://container/path)
-        destination: String,
-        /// Recursive move for directories
-        #[arg(short, long)]
-        recursive: bool,
-        /// Force removal without confirmation
-        #[arg(short, long)]
-        force: bool,
+  azst mv -r az://account1/container1/data/ az://account2/container2/
+
+  # Preview a filtered move: what would be copied and which sources removed
+  azst mv -r --dry-run --include-pattern '*.csv' /exports/ az://myaccount/archive/")]
+    Mv(Box<MvArgs>),
+    /// Show how an az:// URI is interpreted
+    #[command(long_about = "Show how an az:// URI is interpreted
+
+Prints the storage account, container, path and HTTPS URL that azst derives from
+a URI, or explains what is wrong with it. Useful for debugging path issues.
+
+Examples:
+  # Inspect a blob URI
+  azst parse az://myaccount/mycontainer/data/file.csv
+
+  # Find out why a URI is rejected
+  azst parse az://myaccount/My_Container/")]
+    Parse {
+        /// URI to interpret (az://account/container/path)
+        uri: String,
+    },
+    /// Manage time-based retention (immutability) policies
+    #[command(long_about = "Manage time-based retention (immutability) policies
+
+While a retention policy is in effect, blobs can be read but not changed or
+deleted (WORM storage). A blob is retained until a date, which needs
+version-level immutability to be enabled on its container or account; a
+container retains every blob for a number of days after the blob is created.
+Container policies are managed through Azure Resource Manager, so they need a
+role that can manage the storage account.
+
+Unlocked policies can be changed or removed, for testing. Locked policies can
+only be extended, and the account can't be deleted while they protect data.
+
+Examples:
+  # Keep a blob for a year
+  azst retention set --days 365 az://myaccount/records/2024/ledger.csv
+
+  # Keep it until a date, for good
+  azst retention set --until 2031-12-31 --lock az://myaccount/records/2024/ledger.csv
+
+  # Keep every blob in a container for seven years after upload
+  azst retention set --days 2555 az://myaccount/records/
+
+  # Show the policy and legal hold in effect
+  azst retention get az://myaccount/records/
+
+  # Remove an unlocked policy
+  azst retention clear az://myaccount/records/2024/ledger.csv")]
+    Retention {
+        #[command(subcommand)]
+        action: RetentionAction,
+    },
+    /// Re-attempt only the transfers that failed in an earlier job
+    #[command(
+        long_about = "Re-attempt only the transfers that failed in an earlier job
+
+Reads the failures file written by cp or rm with --failures-out and retries each
+failed transfer, then rewrites the file with whatever still fails. Without a file,
+the failed transfers are taken from the most recent AzCopy job log
+(AZCOPY_LOG_LOCATION, or ~/.azcopy).
+
+Examples:
+  # Copy a large tree, then retry the handful of files that failed
+  azst cp -r --failures-out failures.json /data/ az://myaccount/data/
+  azst retry-failed failures.json
+
+  # Retry the failures of the last AzCopy job
+  azst retry-failed"
+    )]
+    RetryFailed {
+        /// Failures file written by --failures-out
+        failures: Option<PathBuf>,
     },
     /// Remove objects from Azure storage (like gsutil rm)
     #[command(long_about = "Remove objects from Azure storage (like gsutil rm)
@@ -234,6 +1016,15 @@ Examples:
 Removes blobs from Azure storage or local files. Use with caution, especially 
 with recursive and force flags.
 
+--older-than, --newer-than, --min-size and --max-size remove only the blobs under
+the prefix whose age and size match, for retention cleanups without a lifecycle
+policy.
+
+Removing more than 1000 blobs (confirm_above under [rm] in the config) asks for the
+container name instead of y/N. --max-deletes (or max_deletes under [rm]) refuses
+removals of more blobs than that before deleting anything, even with -f. Blobs
+removed by rm itself are deleted in waves that shrink when Azure throttles.
+
 Examples:
   # Remove single object
   azst rm az://myaccount/mycontainer/file.txt
@@ -251,26 +1042,75 @@ Examples:
   azst rm -r --exclude-pattern '*.db;*.config' az://myaccount/temp-data/
 
   # Remove only specific file types
-  azst rm -r --include-pattern '*.log;*.tmp' az://myaccount/mycontainer/")]
-    Rm {
-        /// Path to remove (az://container/path)
-        path: String,
-        /// Recursive removal
-        #[arg(short, long)]
-        recursive: bool,
-        /// Force removal without confirmation
-        #[arg(short, long)]
-        force: bool,
-        /// Preview what would be removed without actually removing
-        #[arg(long)]
-        dry_run: bool,
-        /// Include only files matching this pattern (supports wildcards like *.jpg;*.pdf)
-        #[arg(long)]
-        include_pattern: Option<String>,
-        /// Exclude files matching this pattern (supports wildcards like *.log;*.tmp)
+  azst rm -r --include-pattern '*.log;*.tmp' az://myaccount/mycontainer/
+
+  # Remove the blobs a wildcard matches, at any depth with **
+  azst rm -f 'az://myaccount/mycontainer/tmp/**/*.partial'
+
+  # Remove the paths listed by ls -0 (or one per line)
+  azst ls -0 'az://myaccount/mycontainer/tmp/*' | azst rm -f -I
+
+  # Record the blobs that couldn't be removed, with their error codes
+  azst rm -rf --failures-out failures.json az://myaccount/mycontainer/old/
+
+  # Retention cleanup: logs last modified over 30 days ago
+  azst rm -rf --older-than 30d az://myaccount/mycontainer/logs/
+
+  # Empty blobs left by failed uploads, previewed first
+  azst rm -r --max-size 0 --dry-run az://myaccount/mycontainer/uploads/
+
+  # Unattended cleanup that stops if the prefix holds more than expected
+  azst rm -rf --max-deletes 50000 az://myaccount/mycontainer/scratch/")]
+    Rm(Box<RmArgs>),
+    /// Serve the blobs under a prefix over local HTTP
+    #[command(long_about = "Serve the blobs under a prefix over local HTTP
+
+Answers GET and HEAD requests by reading the blob at the request path under the
+prefix, with Range requests for partial reads. A directory path serves its
+index.html, so static sites can be previewed, or with --list an index page of
+its contents. Listens on localhost only unless --bind says otherwise.
+
+Examples:
+  # Preview a static site at http://127.0.0.1:8080/
+  azst serve 'az://myaccount/$web/'
+
+  # Browse a prefix from another machine
+  azst serve --list --bind 0.0.0.0 --port 9000 az://myaccount/data/exports/")]
+    Serve {
+        /// Prefix to serve (az://account/container/[prefix/])
+        uri: String,
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+        /// List directories that have no index.html
         #[arg(long)]
-        exclude_pattern: Option<String>,
+        list: bool,
     },
+    /// Run azst commands interactively, with a working prefix
+    #[command(long_about = "Run azst commands interactively, with a working prefix
+
+Reads commands from a prompt and runs them without the leading 'azst'. The
+credential is resolved once and reused by every command, so each one starts
+without the sign-in lookup. 'cd' sets a working prefix that relative paths are
+taken from, and a bare 'ls' or 'du' lists it; write local files as /abs, ./rel,
+../rel or ~/rel. TAB completes command names and remote paths, and history is
+kept between sessions. Global options given to 'azst shell' apply to every
+command in the session.
+
+Examples:
+  # Browse an account and copy from it
+  azst shell
+  azst az://> cd myaccount/data/2024
+  azst az://myaccount/data/2024/> ls -l
+  azst az://myaccount/data/2024/> cp report.csv ./
+  azst az://myaccount/data/2024/> exit
+
+  # Fail commands whose transfers partly failed, for the whole session
+  azst --strict shell")]
+    Shell,
     /// Sync directories to/from Azure storage (like rsync)
     #[command(long_about = "Sync directories to/from Azure storage (like rsync)
 
@@ -284,164 +1124,1760 @@ Examples:
   # Sync from Azure to local
   azst sync az://myaccount/backup/ /local/restore/
 
+  # Mirror one local directory to another (no Azure involved)
+  azst sync --delete /local/photos/ /mnt/usb/photos/
+
   # Sync with delete (remove extra files in destination)
   azst sync --delete /local/docs/ az://myaccount/documents/
 
+  # Mirror, but stop and list the deletions if over 50 files would go
+  azst sync --mirror --max-delete 50 /local/site/ az://myaccount/www/
+
   # Preview sync operations without executing
   azst sync --dry-run /local/data/ az://myaccount/backup/
 
+  # The dry-run plan as JSON, for scripts
+  azst --format json sync --dry-run /local/data/ az://myaccount/backup/
+
   # Sync only text files, excluding temporary ones
   azst sync --include-pattern '*.txt;*.md' --exclude-pattern '*~;*.tmp' \\
     /documents/ az://myaccount/docs/
 
+  # Sync only some subdirectories of a tree
+  azst sync --include-path 'reports;exports/2024' /data/ az://myaccount/data/
+
   # Limit bandwidth and ensure data integrity
-  azst sync --cap-mbps 50 --put-md5 /backups/ az://myaccount/backup/")]
-    Sync {
-        /// Source path (local directory or az://container/path)
-        source: String,
-        /// Destination path (local directory or az://container/path)
-        destination: String,
-        /// Delete files in destination that don't exist in source
-        #[arg(short, long)]
-        delete: bool,
-        /// Skip confirmation prompt for delete operations
-        #[arg(short, long)]
-        force: bool,
-        /// Preview what would be synced without actually syncing
-        #[arg(long)]
-        dry_run: bool,
-        /// Limit transfer rate in megabits per second
-        #[arg(long)]
-        cap_mbps: Option<f64>,
-        /// Block size in MiB for upload/download (e.g., 8, 16, 32)
-        #[arg(long)]
-        block_size_mb: Option<f64>,
-        /// Create MD5 hash for each file and save as Content-MD5 property
-        #[arg(long)]
-        put_md5: bool,
-        /// Include only files matching this pattern (supports wildcards like *.jpg;*.pdf)
-        #[arg(long)]
-        include_pattern: Option<String>,
-        /// Exclude files matching this pattern (supports wildcards like *.log;*.tmp)
+  azst sync --cap-mbps 50 --put-md5 /backups/ az://myaccount/backup/
+
+  # More requests in flight and a bigger buffer for a fat pipe
+  azst sync --concurrency 256 --buffer-gb 4 /backups/ az://myaccount/backup/
+
+  # Hold off during office hours and let the sync run freely otherwise
+  azst sync --bandwidth-schedule '08:00-19:00=pause' /backups/ az://myaccount/backup/
+
+  # Track state between runs and keep the newer version of conflicting files
+  azst sync --track-state .azst-sync.json --conflict newer /local/docs/ az://myaccount/docs/
+
+  # Two-way sync: propagate changes and deletions in both directions
+  azst sync --bidirectional --track-state .azst-sync.json /local/shared/ az://myaccount/team/shared/
+
+  # Shared drive: leave files edited on both sides alone and list them
+  azst sync --two-way --state state.json --conflict flag /local/shared/ az://myaccount/team/shared/
+
+  # Compare content hashes to catch changes that kept the size and timestamp
+  azst sync --compare checksum /local/data/ az://myaccount/data/
+
+  # Quick pass that only copies files whose size differs
+  azst sync --compare size /local/media/ az://myaccount/media/
+
+  # Keep symbolic links as blobs holding their targets
+  azst sync --preserve-symlinks /opt/app/ az://myaccount/releases/app/")]
+    Sync(Box<SyncArgs>),
+    /// Print blobs created, modified or deleted under a prefix as it happens
+    #[command(
+        long_about = "Print blobs created, modified or deleted under a prefix as it happens
+
+Lists the prefix every --interval and prints one line per change, \"created\",
+\"modified\" or \"deleted\" and the blob's URI (a JSON object per event with
+--format json), until Ctrl-C. Only changes after the watch starts are reported.
+
+With --change-feed, the account's Blob Change Feed is read instead of listing
+the prefix, which costs the same however many blobs it holds. The feed must be
+enabled on the account, and its events arrive in hourly segments, so up to an
+hour late.
+
+--exec runs a shell command for each event, one at a time, with AZST_EVENT and
+AZST_URI in its environment. A failing command is reported and the watch goes on.
+
+Examples:
+  # Print changes under a prefix, checking every 30 seconds
+  azst watch az://myaccount/mycontainer/inbox/
+
+  # Process each new CSV as it lands
+  azst watch --interval 10s --exec 'test $AZST_EVENT = created && ./ingest.sh \"$AZST_URI\"' \\
+    'az://myaccount/mycontainer/inbox/*.csv'
+
+  # Audit deletions in a large container from the change feed
+  azst watch --change-feed --interval 5m az://myaccount/archive/ | grep ^deleted"
+    )]
+    Watch {
+        /// Prefix to watch (az://account/container/[prefix], wildcards allowed)
+        uri: String,
+        /// How often to check (e.g., 30s, 5m)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "30s")]
+        interval: Duration,
+        /// Read the account's Blob Change Feed instead of listing the prefix
         #[arg(long)]
-        exclude_pattern: Option<String>,
+        change_feed: bool,
+        /// Shell command to run for each event, with AZST_EVENT and AZST_URI set
+        #[arg(long, value_name = "CMD")]
+        exec: Option<String>,
     },
 }
 
+/// Arguments of `azst bench`
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Container and optional prefix to write test data under (az://account/container/[prefix])
+    pub uri: String,
+    /// Size of each generated file (e.g., 64K, 256M, 1G)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "250M")]
+    pub size_per_file: u64,
+    /// Number of files to transfer
+    #[arg(long, value_name = "N", default_value = "100", value_parser = clap::value_parser!(u32).range(1..))]
+    pub file_count: u32,
+    /// Measure uploads (the default)
+    #[arg(long, conflicts_with = "download")]
+    pub upload: bool,
+    /// Measure downloads of files uploaded for the purpose
+    #[arg(long)]
+    pub download: bool,
+    /// Leave the test data in the container
+    #[arg(long)]
+    pub keep_data: bool,
+    /// Block size in MiB for the transfers (e.g., 8, 16, 32)
+    #[arg(long)]
+    pub block_size_mb: Option<f64>,
+    /// Requests azcopy keeps in flight (sets AZCOPY_CONCURRENCY_VALUE)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub concurrency: Option<u32>,
+    /// Memory azcopy may use to buffer data, in GB (sets AZCOPY_BUFFER_GB)
+    #[arg(long, value_name = "GB")]
+    pub buffer_gb: Option<f64>,
+    /// Directories azcopy lists at once while scanning (sets AZCOPY_CONCURRENT_SCAN)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub scan_concurrency: Option<u32>,
+}
+
+/// Arguments of `azst cp`
+#[derive(Args)]
+#[command(group = ArgGroup::new("archive").multiple(false))]
+pub struct CpArgs {
+    /// Source path (local file, az://container/path, http(s):// URL, or -
+    /// for a list of URLs on stdin)
+    #[arg(value_parser = parse_location)]
+    pub source: String,
+    /// Destination path (local file or az://container/path)
+    #[arg(value_parser = parse_location)]
+    pub destination: String,
+    /// Recursive copy for directories
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Preview what would be copied without actually copying
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Limit transfer rate in megabits per second
+    #[arg(long)]
+    pub cap_mbps: Option<f64>,
+    /// Vary the rate limit by local time of day, e.g.
+    /// "09:00-18:00=50,18:00-09:00=0" (Mbps; 0 lifts the limit, pause
+    /// stops the transfer). --cap-mbps applies outside the windows
+    #[arg(long, value_name = "SCHEDULE", value_parser = BandwidthSchedule::parse)]
+    pub bandwidth_schedule: Option<BandwidthSchedule>,
+    /// Block size in MiB for upload/download (e.g., 8, 16, 32)
+    #[arg(long)]
+    pub block_size_mb: Option<f64>,
+    /// Requests azcopy keeps in flight (sets AZCOPY_CONCURRENCY_VALUE)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub concurrency: Option<u32>,
+    /// Block size and concurrency defaults for the destination account:
+    /// auto picks premium ones for premium block blob accounts, found
+    /// through the management API. --block-size-mb and --concurrency still win
+    #[arg(long, value_enum, value_name = "MODE", default_value_t)]
+    pub tuning: TuningMode,
+    /// Memory azcopy may use to buffer data, in GB (sets AZCOPY_BUFFER_GB)
+    #[arg(long, value_name = "GB")]
+    pub buffer_gb: Option<f64>,
+    /// Directories azcopy lists at once while scanning (sets AZCOPY_CONCURRENT_SCAN)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub scan_concurrency: Option<u32>,
+    /// Create MD5 hash for each file and save as Content-MD5 property
+    #[arg(long)]
+    pub put_md5: bool,
+    /// Include only files matching this pattern (supports wildcards like *.jpg;*.pdf)
+    #[arg(long)]
+    pub include_pattern: Option<String>,
+    /// Exclude files matching this pattern (supports wildcards like *.log;*.tmp)
+    #[arg(long)]
+    pub exclude_pattern: Option<String>,
+    /// Only transfer these paths, relative to the source (e.g. images;docs/a.txt)
+    #[arg(long, value_name = "PATHS")]
+    pub include_path: Option<String>,
+    /// Skip these paths and everything under them, relative to the source
+    /// (e.g. node_modules;.git)
+    #[arg(long, value_name = "PATHS")]
+    pub exclude_path: Option<String>,
+    /// Only transfer files whose relative path matches one of these regular expressions
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex_list)]
+    pub include_regex: Option<String>,
+    /// Skip files whose relative path matches one of these regular expressions
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex_list)]
+    pub exclude_regex: Option<String>,
+    /// Don't replace files that already exist at the destination
+    #[arg(short, long, conflicts_with = "overwrite")]
+    pub no_clobber: bool,
+    /// What to do when a destination file already exists
+    #[arg(long, value_enum)]
+    pub overwrite: Option<OverwritePolicy>,
+    /// Preserve file properties (POSIX mode and timestamps, or SMB info on Windows)
+    #[arg(short, long)]
+    pub preserve: bool,
+    /// Content-Type to set on uploaded blobs
+    #[arg(long)]
+    pub content_type: Option<String>,
+    /// Cache-Control header to set on uploaded blobs
+    #[arg(long)]
+    pub cache_control: Option<String>,
+    /// Content-Encoding header to set on uploaded blobs (e.g., gzip)
+    #[arg(long)]
+    pub content_encoding: Option<String>,
+    /// Metadata to set on uploaded blobs as key=value (can be repeated;
+    /// values can't contain ';')
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_metadata)]
+    pub metadata: Vec<(String, String)>,
+    /// Access tier for uploaded blobs
+    #[arg(long, value_enum)]
+    pub tier: Option<BlobTier>,
+    /// Kind of blob to create (default: page blobs for .vhd and .vhdx
+    /// files, block blobs otherwise); a single file that isn't a whole
+    /// number of 512-byte pages is zero-padded
+    #[arg(long, value_enum, value_name = "TYPE")]
+    pub blob_type: Option<BlobType>,
+    /// Don't guess the content type of uploaded files from their extension
+    #[arg(long)]
+    pub no_guess_mime_type: bool,
+    /// Verify MD5 hashes after transfer (--check-md5 alone means --check-md5=fail)
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "fail"
+    )]
+    pub check_md5: Option<Md5Check>,
+    /// Write downloads directly to the destination instead of staging them and renaming on completion
+    #[arg(long)]
+    pub no_atomic: bool,
+    /// How to handle blob names that differ only by case when downloading
+    /// (default: abort on case-insensitive file systems)
+    #[arg(long, value_enum)]
+    pub case_conflict: Option<CaseConflictPolicy>,
+    /// Keep empty directories by storing zero-byte "dir/" marker blobs on
+    /// upload and recreating the directories on download
+    #[arg(long)]
+    pub preserve_empty_dirs: bool,
+    /// Upload the files and directories symbolic links under a local
+    /// source point to
+    #[arg(long, conflicts_with_all = ["skip_symlinks", "preserve_symlinks", "tar"])]
+    pub follow_symlinks: bool,
+    /// Leave symbolic links under a local source out, warning how many
+    /// were skipped (the default)
+    #[arg(long, conflicts_with_all = ["preserve_symlinks", "tar"])]
+    pub skip_symlinks: bool,
+    /// Upload each symbolic link under a local source as a blob holding
+    /// its target, marked with is_symlink and link_target metadata
+    #[arg(long)]
+    pub preserve_symlinks: bool,
+    /// Abort before uploading more than this many bytes (e.g., 500M, 10G)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_upload_bytes: Option<u64>,
+    /// Compress files before uploading, storing the algorithm as Content-Encoding
+    #[arg(long, value_enum, conflicts_with = "content_encoding")]
+    pub compress: Option<Compression>,
+    /// Gzip files with these extensions before uploading, storing
+    /// Content-Encoding: gzip (comma-separated, e.g. html,css,js)
+    #[arg(
+        short = 'z',
+        long,
+        value_name = "EXTENSIONS",
+        value_delimiter = ',',
+        conflicts_with_all = ["content_encoding", "compress"]
+    )]
+    pub gzip_local: Vec<String>,
+    /// Gzip every file before uploading (the same as --compress gzip)
+    #[arg(
+        short = 'Z',
+        long,
+        conflicts_with_all = ["content_encoding", "compress", "gzip_local"]
+    )]
+    pub gzip_local_all: bool,
+    /// Decompress downloaded blobs stored with a gzip or zstd Content-Encoding
+    /// (the default, except for wildcard downloads)
+    #[arg(long)]
+    pub decompress: bool,
+    /// Download blobs exactly as stored, even with a gzip or zstd
+    /// Content-Encoding
+    #[arg(long, conflicts_with = "decompress")]
+    pub no_decompress: bool,
+    /// Place a copied directory under a folder named after it at the
+    /// destination; false copies its contents straight in
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    pub as_subdir: bool,
+    /// Copy every file into the destination directory itself, dropping
+    /// the directory structure (names must be unique)
+    #[arg(
+        long,
+        requires = "recursive",
+        conflicts_with_all = [
+            "preserve_empty_dirs",
+            "compress",
+            "gzip_local",
+            "gzip_local_all",
+            "decompress"
+        ]
+    )]
+    pub flatten: bool,
+    /// Allow Azure-to-Azure transfers between accounts in different regions
+    #[arg(long)]
+    pub allow_cross_region: bool,
+    /// Write the transfers that failed to this JSON file
+    #[arg(long, value_name = "FILE")]
+    pub failures_out: Option<PathBuf>,
+    /// Lease to present when overwriting a leased blob (default: the one
+    /// azst acquired on the destination, if any)
+    #[arg(long, value_parser = parse_lease_id)]
+    pub lease_id: Option<LeaseId>,
+    /// Base64-encoded AES-256 key to encrypt uploads and decrypt downloads
+    /// with (customer-provided key)
+    #[arg(long, env = "AZST_CUSTOMER_KEY", hide_env_values = true)]
+    pub customer_key: Option<CustomerKey>,
+    /// Upload a local directory as one tar blob, compressed when the blob
+    /// is named .tar.gz/.tgz or .tar.zst
+    #[arg(long, group = "archive", conflicts_with_all = ARCHIVE_CONFLICTS)]
+    pub tar: bool,
+    /// Extract a tar blob (optionally gzip or zstd) into a local directory
+    /// as it downloads
+    #[arg(long, group = "archive", conflicts_with_all = ARCHIVE_CONFLICTS)]
+    pub untar: bool,
+    /// Upload a directory with its files smaller than SIZE (default 1M)
+    /// grouped into large pack blobs behind an index, cutting per-file
+    /// requests for trees of many tiny files
+    #[arg(
+        long,
+        value_name = "SIZE",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_PACK_THRESHOLD,
+        value_parser = parse_size,
+        requires = "recursive",
+        group = "archive",
+        conflicts_with_all = ARCHIVE_CONFLICTS
+    )]
+    pub pack: Option<u64>,
+    /// Download a packed upload, extracting its packed files next to
+    /// the ones stored as blobs of their own
+    #[arg(
+        long,
+        requires = "recursive",
+        group = "archive",
+        conflicts_with_all = ARCHIVE_CONFLICTS
+    )]
+    pub unpack: bool,
+    /// Record the files copied in this JSON file, and skip the ones it
+    /// already lists when the same copy is run again
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "recursive",
+        conflicts_with_all = [
+            "tar",
+            "untar",
+            "pack",
+            "unpack",
+            "flatten",
+            "compress",
+            "gzip_local",
+            "gzip_local_all",
+            "decompress",
+            "async_copy"
+        ]
+    )]
+    pub manifest: Option<PathBuf>,
+    /// Wait for server-side copies from URLs that run in the background,
+    /// showing their progress
+    #[arg(long, conflicts_with = "async_copy")]
+    pub poll: bool,
+    /// Start server-side copies between Azure locations and return without
+    /// waiting for them (see cp-status)
+    #[arg(long = "async", conflicts_with_all = ARCHIVE_CONFLICTS)]
+    pub async_copy: bool,
+}
+
+/// Arguments of `azst du`
+#[derive(Args)]
+pub struct DuArgs {
+    /// Path to analyze (az://container/path or local path)
+    pub path: Option<String>,
+    /// Display only total size for each argument
+    #[arg(short, long)]
+    pub summarize: bool,
+    /// Show sizes in human readable format (KB, MB, GB)
+    #[arg(short = 'H', long)]
+    pub human_readable: bool,
+    /// Display grand total
+    #[arg(short = 'c', long)]
+    pub total: bool,
+    /// Only report directories up to N levels below the path
+    #[arg(short = 'd', long, value_name = "N", conflicts_with = "summarize")]
+    pub max_depth: Option<usize>,
+    /// Order of the directories: by path, or largest first
+    #[arg(long, value_enum, default_value_t = SortOrder::Name, conflicts_with = "summarize")]
+    pub sort: SortOrder,
+    /// Only report the N largest directories, largest first
+    #[arg(long, value_name = "N", conflicts_with = "summarize")]
+    pub top: Option<usize>,
+    /// Also count the blobs (or files) under each directory
+    #[arg(long)]
+    pub objects: bool,
+    /// Also show the bytes in each access tier (Hot, Cool, Cold, Archive)
+    #[arg(long)]
+    pub tier_breakdown: bool,
+    /// List the first-level prefixes as N concurrent listings
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub partitions: Option<u16>,
+    /// Answer from the listings cached by `ls -r`, `inventory` or `azst cache refresh`
+    #[arg(long, conflicts_with = "partitions")]
+    pub cached: bool,
+    /// Storage account name
+    #[arg(short, long)]
+    pub account: Option<String>,
+}
+
+/// Arguments of `azst ls`
+#[derive(Args)]
+pub struct LsArgs {
+    /// Path to list (az://account/container/ or az://account/container/prefix)
+    pub path: Option<String>,
+    /// Show detailed information
+    #[arg(short, long)]
+    pub long: bool,
+    /// Show file sizes in human readable format
+    #[arg(short = 'H', long)]
+    pub human_readable: bool,
+    /// Recursive listing, in a section per directory
+    #[arg(short, long, short_alias = 'R')]
+    pub recursive: bool,
+    /// With -r, one fully-qualified URI per line without sections, streamed
+    #[arg(long, requires = "recursive")]
+    pub flat: bool,
+    /// List directories themselves rather than their contents
+    #[arg(short, long, conflicts_with_all = ["recursive", "since_state"])]
+    pub directory: bool,
+    /// Print the number of blobs and their total size after the listing
+    /// (the default for long listings at a terminal)
+    #[arg(long, overrides_with = "no_summary", conflicts_with = "since_state")]
+    pub summary: bool,
+    /// Don't print the summary, even for long listings at a terminal
+    #[arg(long, overrides_with = "summary")]
+    pub no_summary: bool,
+    /// Answer from the listing cached by `ls -r`, `inventory` or `azst cache refresh`
+    #[arg(long, conflicts_with_all = ["directory", "since_state"])]
+    pub cached: bool,
+    /// Storage account name
+    #[arg(short, long)]
+    pub account: Option<String>,
+    /// Print a read-only HTTPS URL for each blob, valid for this long (e.g., 30m, 1h, 7d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub signed_urls: Option<Duration>,
+    /// Print only blobs added, modified or deleted since the listing recorded in FILE, then record this one
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["long", "signed_urls", "etag", "columns"])]
+    pub since_state: Option<String>,
+    /// Long listing with each blob's ETag
+    #[arg(short, long, conflicts_with = "columns")]
+    pub etag: bool,
+    /// Long listing with the given columns, e.g. size,tier,etag,name
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "COLUMNS")]
+    pub columns: Option<Vec<Column>>,
+    /// Print bare URIs terminated by NUL instead of newline, for xargs -0 and rm -I
+    #[arg(short = '0', long, conflicts_with_all = ["long", "signed_urls", "since_state", "etag", "columns"])]
+    pub null: bool,
+    /// List the storage accounts of this subscription instead of the current one
+    #[arg(long, value_name = "ID")]
+    pub subscription: Option<String>,
+    /// List the storage accounts of every enabled subscription
+    #[arg(long, conflicts_with = "subscription")]
+    pub all_subscriptions: bool,
+    /// Only list storage accounts in this resource group
+    #[arg(short = 'g', long, value_name = "NAME")]
+    pub resource_group: Option<String>,
+    /// Only list storage accounts whose names match this pattern (e.g. 'prod*'),
+    /// or contain it when it has no wildcards
+    #[arg(long, value_name = "PATTERN")]
+    pub name_filter: Option<String>,
+}
+
+/// Arguments of `azst mv`
+#[derive(Args)]
+pub struct MvArgs {
+    /// Source path (local file or az://container/path)
+    #[arg(value_parser = parse_location)]
+    pub source: String,
+    /// Destination path (local file or az://container/path)
+    #[arg(value_parser = parse_location)]
+    pub destination: String,
+    /// Recursive move for directories
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Force removal without confirmation, as needed when nobody can
+    /// answer it (--non-interactive, no terminal)
+    #[arg(short, long)]
+    pub force: bool,
+    /// Preview what would be moved without copying or removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Limit transfer rate in megabits per second
+    #[arg(long)]
+    pub cap_mbps: Option<f64>,
+    /// Vary the rate limit by local time of day, e.g.
+    /// "09:00-18:00=50,18:00-09:00=0" (Mbps; 0 lifts the limit, pause
+    /// stops the transfer). --cap-mbps applies outside the windows
+    #[arg(long, value_name = "SCHEDULE", value_parser = BandwidthSchedule::parse)]
+    pub bandwidth_schedule: Option<BandwidthSchedule>,
+    /// Block size in MiB for upload/download (e.g., 8, 16, 32)
+    #[arg(long)]
+    pub block_size_mb: Option<f64>,
+    /// Create MD5 hash for each file and save as Content-MD5 property
+    #[arg(long)]
+    pub put_md5: bool,
+    /// Include only files matching this pattern (supports wildcards like *.jpg;*.pdf)
+    #[arg(long)]
+    pub include_pattern: Option<String>,
+    /// Exclude files matching this pattern (supports wildcards like *.log;*.tmp)
+    #[arg(long)]
+    pub exclude_pattern: Option<String>,
+    /// Only move these paths, relative to the source (e.g. images;docs/a.txt)
+    #[arg(long, value_name = "PATHS")]
+    pub include_path: Option<String>,
+    /// Skip these paths and everything under them, relative to the source
+    #[arg(long, value_name = "PATHS")]
+    pub exclude_path: Option<String>,
+    /// Only move files whose relative path matches one of these regular expressions
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex_list)]
+    pub include_regex: Option<String>,
+    /// Skip files whose relative path matches one of these regular expressions
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex_list)]
+    pub exclude_regex: Option<String>,
+    /// Don't replace files that already exist at the destination
+    #[arg(short, long, conflicts_with = "overwrite")]
+    pub no_clobber: bool,
+    /// What to do when a destination file already exists
+    #[arg(long, value_enum)]
+    pub overwrite: Option<OverwritePolicy>,
+    /// Preserve file properties (POSIX mode and timestamps, or SMB info on Windows)
+    #[arg(short, long)]
+    pub preserve: bool,
+    /// Allow Azure-to-Azure transfers between accounts in different regions
+    #[arg(long)]
+    pub allow_cross_region: bool,
+}
+
+/// Arguments of `azst rm`
+#[derive(Args)]
+pub struct RmArgs {
+    /// Path to remove (az://container/path)
+    #[arg(required_unless_present = "stdin")]
+    pub path: Option<String>,
+    /// Recursive removal
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Force removal without confirmation, as needed when nobody can
+    /// answer it (--non-interactive, no terminal)
+    #[arg(short, long)]
+    pub force: bool,
+    /// Preview what would be removed without actually removing
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Include only files matching this pattern (supports wildcards like *.jpg;*.pdf)
+    #[arg(long)]
+    pub include_pattern: Option<String>,
+    /// Exclude files matching this pattern (supports wildcards like *.log;*.tmp)
+    #[arg(long)]
+    pub exclude_pattern: Option<String>,
+    /// Read the paths to remove from stdin, NUL-terminated (ls -0) or one per line
+    #[arg(short = 'I', long, conflicts_with = "path", requires = "force")]
+    pub stdin: bool,
+    /// Write the removals that failed to this JSON file
+    #[arg(long, value_name = "FILE")]
+    pub failures_out: Option<PathBuf>,
+    /// Lease to present when deleting a leased blob (default: the one
+    /// azst acquired on it, if any)
+    #[arg(long, value_parser = parse_lease_id, conflicts_with = "stdin")]
+    pub lease_id: Option<LeaseId>,
+    /// Only remove blobs last modified at least this long ago (e.g. 30d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["stdin", "lease_id"])]
+    pub older_than: Option<Duration>,
+    /// Only remove blobs last modified at most this long ago (e.g. 12h)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["stdin", "lease_id"])]
+    pub newer_than: Option<Duration>,
+    /// Only remove blobs of at least this size (e.g. 1G)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with_all = ["stdin", "lease_id"])]
+    pub min_size: Option<u64>,
+    /// Only remove blobs of at most this size (e.g. 0 for empty blobs)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with_all = ["stdin", "lease_id"])]
+    pub max_size: Option<u64>,
+    /// Refuse to remove more than this many blobs; overrides max_deletes in the config
+    #[arg(long, value_name = "N")]
+    pub max_deletes: Option<u64>,
+}
+
+/// Arguments of `azst sync`
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Source path (local directory or az://container/path)
+    #[arg(value_parser = parse_location)]
+    pub source: String,
+    /// Destination path (local directory or az://container/path)
+    #[arg(value_parser = parse_location)]
+    pub destination: String,
+    /// Delete files in destination that don't exist in source
+    #[arg(short, long)]
+    pub delete: bool,
+    /// Skip confirmation prompt for delete operations, as needed when
+    /// nobody can answer it (--non-interactive, no terminal)
+    #[arg(short, long)]
+    pub force: bool,
+    /// Make the destination match the source, deleting extra files, but
+    /// stop first if more would be deleted than --max-delete allows
+    #[arg(long, conflicts_with = "bidirectional")]
+    pub mirror: bool,
+    /// Most a mirror may delete without --force: a number of files or a
+    /// percentage of the destination (e.g. 100, 25%)
+    #[arg(long, value_name = "LIMIT", default_value = "10%", requires = "mirror")]
+    pub max_delete: DeleteLimit,
+    /// Preview what would be synced without actually syncing
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Limit transfer rate in megabits per second
+    #[arg(long)]
+    pub cap_mbps: Option<f64>,
+    /// Vary the rate limit by local time of day, e.g.
+    /// "09:00-18:00=50,18:00-09:00=0" (Mbps; 0 lifts the limit, pause
+    /// stops the transfer). --cap-mbps applies outside the windows
+    #[arg(long, value_name = "SCHEDULE", value_parser = BandwidthSchedule::parse)]
+    pub bandwidth_schedule: Option<BandwidthSchedule>,
+    /// Block size in MiB for upload/download (e.g., 8, 16, 32)
+    #[arg(long)]
+    pub block_size_mb: Option<f64>,
+    /// Requests azcopy keeps in flight (sets AZCOPY_CONCURRENCY_VALUE)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub concurrency: Option<u32>,
+    /// Block size and concurrency defaults for the destination account:
+    /// auto picks premium ones for premium block blob accounts, found
+    /// through the management API. --block-size-mb and --concurrency still win
+    #[arg(long, value_enum, value_name = "MODE", default_value_t)]
+    pub tuning: TuningMode,
+    /// Memory azcopy may use to buffer data, in GB (sets AZCOPY_BUFFER_GB)
+    #[arg(long, value_name = "GB")]
+    pub buffer_gb: Option<f64>,
+    /// Directories azcopy lists at once while scanning (sets AZCOPY_CONCURRENT_SCAN)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub scan_concurrency: Option<u32>,
+    /// Create MD5 hash for each file and save as Content-MD5 property
+    #[arg(long)]
+    pub put_md5: bool,
+    /// Include only files matching this pattern (supports wildcards like *.jpg;*.pdf)
+    #[arg(long)]
+    pub include_pattern: Option<String>,
+    /// Exclude files matching this pattern (supports wildcards like *.log;*.tmp)
+    #[arg(long)]
+    pub exclude_pattern: Option<String>,
+    /// Only transfer these paths, relative to the source (e.g. images;docs/a.txt)
+    #[arg(long, value_name = "PATHS")]
+    pub include_path: Option<String>,
+    /// Skip these paths and everything under them, relative to the source
+    /// (e.g. node_modules;.git)
+    #[arg(long, value_name = "PATHS")]
+    pub exclude_path: Option<String>,
+    /// Only transfer files whose relative path matches one of these regular expressions
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex_list)]
+    pub include_regex: Option<String>,
+    /// Skip files whose relative path matches one of these regular expressions
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex_list)]
+    pub exclude_regex: Option<String>,
+    /// Record the state of both sides in this file to detect conflicting changes
+    #[arg(long, value_name = "FILE", visible_alias = "state")]
+    pub track_state: Option<String>,
+    /// How to resolve files changed on both sides since the last tracked sync
+    #[arg(long, value_enum, default_value = "rename", requires = "track_state")]
+    pub conflict: ConflictPolicy,
+    /// Propagate changes and deletions in both directions (requires --track-state)
+    #[arg(
+        long,
+        visible_alias = "two-way",
+        requires = "track_state",
+        conflicts_with = "delete"
+    )]
+    pub bidirectional: bool,
+    /// Preserve file properties (POSIX mode and timestamps, or SMB info on Windows)
+    #[arg(short, long)]
+    pub preserve: bool,
+    /// Keep empty directories by storing zero-byte "dir/" marker blobs on
+    /// upload and recreating the directories on download
+    #[arg(long)]
+    pub preserve_empty_dirs: bool,
+    /// Upload the files and directories symbolic links under a local
+    /// source point to
+    #[arg(long, conflicts_with_all = ["skip_symlinks", "preserve_symlinks"])]
+    pub follow_symlinks: bool,
+    /// Leave symbolic links under a local source out, warning how many
+    /// were skipped (the default)
+    #[arg(long, conflicts_with_all = ["preserve_symlinks"])]
+    pub skip_symlinks: bool,
+    /// Upload each symbolic link under a local source as a blob holding
+    /// its target, marked with is_symlink and link_target metadata
+    #[arg(long)]
+    pub preserve_symlinks: bool,
+    /// Abort before uploading more than this many bytes (e.g., 500M, 10G)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_upload_bytes: Option<u64>,
+    /// Allow Azure-to-Azure transfers between accounts in different regions
+    #[arg(long)]
+    pub allow_cross_region: bool,
+    /// How to decide that a file differs: mtime (size and modification
+    /// time), size, or checksum (MD5; local files are hashed in parallel
+    /// and their hashes cached until they change)
+    #[arg(long, value_enum, default_value = "mtime")]
+    pub compare: CompareMode,
+}
+
+impl Commands {
+    /// Name of a command that transfers or removes files in bulk, which
+    /// --quiet sums up in a single line
+    pub fn transfer_name(&self) -> Option<&'static str> {
+        match self {
+            Commands::Cp(_) => Some("cp"),
+            Commands::Mv(_) => Some("mv"),
+            Commands::Rm(_) => Some("rm"),
+            Commands::Sync(_) => Some("sync"),
+            _ => None,
+        }
+    }
+
+    /// Name and locations of a command that changes files or blobs, for the
+    /// audit log; None for read-only commands and dry runs
+    fn audited(&self) -> Option<(&'static str, Vec<String>)> {
+        match self {
+            Commands::Compose { uris, .. } => Some(("compose", uris.clone())),
+            Commands::Cp(args) if !args.dry_run => {
+                Some(("cp", vec![args.source.clone(), args.destination.clone()]))
+            }
+            Commands::CpStatus {
+                uri, abort: true, ..
+            } => Some(("cp-status --abort", vec![uri.clone()])),
+            Commands::Ctl {
+                action: CtlAction::SetPublicAccess { uri, .. },
+            } => Some(("ctl set-public-access", vec![uri.clone()])),
+            Commands::Lease { action } => match action {
+                LeaseAction::Acquire { uri, .. } => Some(("lease acquire", vec![uri.clone()])),
+                LeaseAction::Release { uri, .. } => Some(("lease release", vec![uri.clone()])),
+                LeaseAction::Break { uri, .. } => Some(("lease break", vec![uri.clone()])),
+                LeaseAction::Renew { .. } => None,
+            },
+            Commands::Mv(args) if !args.dry_run => {
+                Some(("mv", vec![args.source.clone(), args.destination.clone()]))
+            }
+            Commands::Legalhold { action } => match action {
+                LegalHoldAction::Set { uri, .. } => Some(("legalhold set", vec![uri.clone()])),
+                LegalHoldAction::Clear { uri, .. } => Some(("legalhold clear", vec![uri.clone()])),
+            },
+            Commands::Lifecycle {
+                action:
+                    LifecycleAction::Set {
+                        uri,
+                        dry_run: false,
+                        ..
+                    },
+            } => Some(("lifecycle set", vec![uri.clone()])),
+            Commands::Retention { action } => match action {
+                RetentionAction::Set { uri, .. } => Some(("retention set", vec![uri.clone()])),
+                RetentionAction::Clear { uri } => Some(("retention clear", vec![uri.clone()])),
+                RetentionAction::Get { .. } => None,
+            },
+            Commands::RetryFailed { .. } => Some(("retry-failed", Vec::new())),
+            Commands::Rm(args) if !args.dry_run => {
+                Some(("rm", args.path.iter().cloned().collect()))
+            }
+            Commands::Sync(args) if !args.dry_run => {
+                Some(("sync", vec![args.source.clone(), args.destination.clone()]))
+            }
+            _ => None,
+        }
+    }
+}
+
 impl Cli {
+    /// Parse the command line, expanding aliases and `az:container/path`
+    /// arguments into full az:// URIs
+    pub fn parse_args() -> Self {
+        // A broken config file is reported by the command that needs it
+        let config = Config::load().unwrap_or_default();
+        Self::parse_from(std::env::args_os().map(|arg| {
+            arg.to_str()
+                .and_then(|text| config.expand_uri(text))
+                .map(OsString::from)
+                .unwrap_or(arg)
+        }))
+    }
+
+    /// Append the finished command to the audit log, if one is configured
+    /// and the command changed anything
+    /// With --strict, fail a command whose azcopy jobs completed with errors
+    pub fn check_strict(&self) -> Result<()> {
+        let failed = audit_log::failed_transfers();
+        if self.strict && failed > 0 {
+            return Err(AzstError::TransferFailed(format!(
+                "{} transfer(s) failed; see the warnings above",
+                failed
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn write_audit_log(&self, result: &Result<()>) {
+        let Some((command, uris)) = self.command.audited() else {
+            return;
+        };
+        let Some(path) = self
+            .audit_log
+            .clone()
+            .or_else(|| Config::load().ok().and_then(|config| config.audit.log))
+        else {
+            return;
+        };
+        if let Err(e) = audit_log::append(&path, command, uris, result) {
+            eprintln!(
+                "{} Failed to write the audit log {}: {:#}",
+                "⚠".yellow(),
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// Run the command until it finishes, --timeout runs out or Ctrl-C
+    /// stops it
+    pub async fn run_cancellable(&self) -> Result<()> {
+        cancel::run(self.run(), self.timeout, !self.stops_on_ctrl_c()).await
+    }
+
+    /// Commands that end cleanly on Ctrl-C by themselves; the shell cancels
+    /// each of its commands instead
+    fn stops_on_ctrl_c(&self) -> bool {
+        match self.command {
+            Commands::Serve { .. } | Commands::Shell | Commands::Watch { .. } => true,
+            #[cfg(feature = "fuse")]
+            Commands::Mount { .. } => true,
+            _ => false,
+        }
+    }
+
     pub async fn run(&self) -> Result<()> {
+        network::configure(NetworkOptions {
+            proxy: self.proxy.clone(),
+            ca_cert: self.ca_cert.clone(),
+            insecure: self.insecure,
+        })?;
+        if let Some(profile) = &self.profile {
+            // Fail early on a profile the config file doesn't have
+            Config::load()?.transfer_for(Some(profile))?;
+            config::set_profile(profile);
+        }
+        let prompter = prompt::create_prompter();
+
         match &self.command {
+            Commands::Account { action } => match action {
+                AccountAction::Set { name } => account::set(name).await,
+                AccountAction::Show => account::show().await,
+            },
+            Commands::Alias { action } => match action {
+                AliasAction::Add { name, uri } => alias::add(name, uri).await,
+                AliasAction::Remove { name } => alias::remove(name).await,
+                AliasAction::List => alias::list().await,
+            },
+            Commands::Audit {
+                source,
+                destination,
+                sample,
+                seed,
+                cap_mbps,
+            } => match destination {
+                Some(destination) => {
+                    audit::execute(source, destination, *sample, *seed, *cap_mbps).await
+                }
+                None => security::execute(source).await,
+            },
+            Commands::Auth { action } => match action {
+                AuthAction::Login {
+                    device_code,
+                    tenant,
+                    subscription,
+                } => {
+                    let flow = if *device_code {
+                        LoginFlow::DeviceCode
+                    } else {
+                        LoginFlow::Browser
+                    };
+                    auth::login(flow, tenant, subscription.clone()).await
+                }
+                AuthAction::Status => auth::status().await,
+                AuthAction::Token { scope } => auth::token(scope).await,
+                AuthAction::Logout => auth::logout().await,
+            },
+            Commands::Azcopy { action } => match action {
+                AzcopyAction::Install { force } => azcopy::install(*force).await,
+                AzcopyAction::Update => azcopy::update().await,
+                AzcopyAction::Which => azcopy::which().await,
+            },
+            Commands::Bench(args) => {
+                let BenchArgs {
+                    uri,
+                    size_per_file,
+                    file_count,
+                    upload: _,
+                    download,
+                    keep_data,
+                    block_size_mb,
+                    concurrency,
+                    buffer_gb,
+                    scan_concurrency,
+                } = &**args;
+                let transfer_flags = TransferConfig {
+                    concurrency: *concurrency,
+                    buffer_gb: *buffer_gb,
+                    scan_concurrency: *scan_concurrency,
+                };
+                let mode = if *download {
+                    BenchMode::Download
+                } else {
+                    BenchMode::Upload
+                };
+                bench::execute(
+                    uri,
+                    *size_per_file,
+                    *file_count,
+                    mode,
+                    *keep_data,
+                    *block_size_mb,
+                    transfer_flags,
+                )
+                .await
+            }
+            Commands::Cache { action } => match action {
+                CacheAction::Refresh { uri } => cache::refresh(uri).await,
+                CacheAction::Clear { uri } => cache::clear(uri.as_deref()),
+            },
             Commands::Cat {
                 urls,
                 header,
                 range,
-            } => cat::execute(urls, *header, range.as_deref()).await,
-            Commands::Cp {
-                source,
-                destination,
-                recursive,
-                dry_run,
-                cap_mbps,
-                block_size_mb,
-                put_md5,
-                include_pattern,
-                exclude_pattern,
+                prefetch,
+                parallel_ranges,
+                customer_key,
+                decompress,
+                raw,
             } => {
-                cp::execute(
+                cat::execute(
+                    urls,
+                    *header,
+                    range.as_deref(),
+                    *prefetch,
+                    *parallel_ranges,
+                    customer_key.clone(),
+                    *decompress,
+                    *raw,
+                )
+                .await
+            }
+            Commands::Compose { uris, content_type } => {
+                let (destination, sources) =
+                    uris.split_last().expect("clap requires at least two URIs");
+                compose::execute(sources, destination, content_type.as_deref()).await
+            }
+            Commands::Complete { partial } => complete::execute(partial).await,
+            Commands::Cp(args) => {
+                let CpArgs {
                     source,
                     destination,
-                    *recursive,
-                    *dry_run,
-                    *cap_mbps,
-                    *block_size_mb,
-                    *put_md5,
-                    include_pattern.as_deref(),
-                    exclude_pattern.as_deref(),
-                )
+                    recursive,
+                    dry_run,
+                    cap_mbps,
+                    bandwidth_schedule,
+                    block_size_mb,
+                    concurrency,
+                    buffer_gb,
+                    scan_concurrency,
+                    put_md5,
+                    include_pattern,
+                    exclude_pattern,
+                    include_path,
+                    exclude_path,
+                    include_regex,
+                    exclude_regex,
+                    no_clobber,
+                    overwrite,
+                    preserve,
+                    content_type,
+                    cache_control,
+                    content_encoding,
+                    metadata,
+                    tier,
+                    no_guess_mime_type,
+                    check_md5,
+                    no_atomic,
+                    case_conflict,
+                    preserve_empty_dirs,
+                    follow_symlinks,
+                    skip_symlinks: _,
+                    preserve_symlinks,
+                    max_upload_bytes,
+                    compress,
+                    gzip_local,
+                    gzip_local_all,
+                    decompress,
+                    no_decompress,
+                    as_subdir,
+                    flatten,
+                    allow_cross_region,
+                    failures_out,
+                    lease_id,
+                    customer_key,
+                    tar,
+                    untar,
+                    pack,
+                    unpack,
+                    manifest,
+                    poll,
+                    async_copy,
+                    blob_type,
+                    tuning,
+                } = &**args;
+                let headers = BlobHeaders {
+                    content_type: content_type.clone(),
+                    cache_control: cache_control.clone(),
+                    content_encoding: content_encoding.clone(),
+                    metadata: metadata.clone(),
+                    tier: *tier,
+                };
+                let transfer_flags = TransferConfig {
+                    concurrency: *concurrency,
+                    buffer_gb: *buffer_gb,
+                    scan_concurrency: *scan_concurrency,
+                };
+                let filters = PathFilters {
+                    include_path: include_path.clone(),
+                    exclude_path: exclude_path.clone(),
+                    include_regex: include_regex.clone(),
+                    exclude_regex: exclude_regex.clone(),
+                };
+                cp::execute(CopyOptions {
+                    source,
+                    destination,
+                    recursive: *recursive,
+                    dry_run: *dry_run,
+                    cap_mbps: *cap_mbps,
+                    bandwidth_schedule: bandwidth_schedule.clone(),
+                    block_size_mb: *block_size_mb,
+                    put_md5: *put_md5,
+                    include_pattern: include_pattern.as_deref(),
+                    exclude_pattern: exclude_pattern.as_deref(),
+                    filters,
+                    overwrite: overwrite_policy(*no_clobber, *overwrite),
+                    preserve: *preserve,
+                    headers,
+                    guess_mime_type: !*no_guess_mime_type,
+                    check_md5: *check_md5,
+                    atomic: !*no_atomic,
+                    case_conflict: *case_conflict,
+                    preserve_empty_dirs: *preserve_empty_dirs,
+                    as_subdir: *as_subdir,
+                    flatten: *flatten,
+                    max_upload_bytes: *max_upload_bytes,
+                    compress: if *gzip_local_all {
+                        Some(Compression::Gzip)
+                    } else {
+                        *compress
+                    },
+                    gzip_extensions: gzip_local.clone(),
+                    decompress: match (*decompress, *no_decompress) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    },
+                    allow_cross_region: *allow_cross_region,
+                    failures_out: failures_out.as_deref(),
+                    lease_id: *lease_id,
+                    customer_key: customer_key.clone(),
+                    tar: *tar,
+                    untar: *untar,
+                    pack: *pack,
+                    unpack: *unpack,
+                    manifest: manifest.as_deref(),
+                    poll: *poll,
+                    async_copy: *async_copy,
+                    blob_type: *blob_type,
+                    symlinks: symlink_policy(*follow_symlinks, *preserve_symlinks),
+                    tuning: *tuning,
+                    transfer_flags,
+                    prompter: &*prompter,
+                })
                 .await
             }
-            Commands::Du {
-                path,
-                summarize,
-                human_readable,
-                total,
-                account,
-            } => {
+            Commands::CpStatus {
+                uri,
+                recursive,
+                abort,
+            } => cp_status::execute(uri, *recursive, *abort).await,
+            Commands::Ctl { action } => match action {
+                CtlAction::SetPublicAccess { uri, level } => {
+                    ctl::set_public_access(uri, *level).await
+                }
+                CtlAction::Show { uri } => ctl::show(uri).await,
+            },
+            Commands::Du(args) => {
+                let DuArgs {
+                    path,
+                    summarize,
+                    human_readable,
+                    total,
+                    max_depth,
+                    sort,
+                    top,
+                    objects,
+                    tier_breakdown,
+                    partitions,
+                    cached,
+                    account,
+                } = &**args;
                 du::execute(
                     path.as_deref(),
                     *summarize,
-                    *human_readable,
                     *total,
+                    Breakdown {
+                        max_depth: *max_depth,
+                        sort: *sort,
+                        top: *top,
+                    },
+                    du::Columns {
+                        human_readable: *human_readable,
+                        objects: *objects,
+                        tiers: *tier_breakdown,
+                    },
+                    match (partitions, cached) {
+                        (Some(partitions), _) => du::Listing::Partitioned(usize::from(*partitions)),
+                        (None, true) => du::Listing::Cached,
+                        (None, false) => du::Listing::Single,
+                    },
                     account.as_deref(),
                 )
                 .await
             }
-            Commands::Ls {
-                path,
-                long,
-                human_readable,
-                recursive,
-                account,
-            } => {
+            Commands::Inventory {
+                uri,
+                out,
+                restart,
+                partitions,
+            } => inventory::execute(uri, out, *restart, partitions.map(usize::from)).await,
+            Commands::Hash {
+                paths,
+                md5,
+                crc64,
+                hex,
+            } => hash::execute(paths, *md5, *crc64, *hex).await,
+            Commands::Lease { action } => match action {
+                LeaseAction::Acquire {
+                    uri,
+                    duration,
+                    lease_id,
+                } => lease::acquire(uri, duration.clone(), *lease_id).await,
+                LeaseAction::Renew { uri, lease_id } => lease::renew(uri, *lease_id).await,
+                LeaseAction::Release { uri, lease_id } => lease::release(uri, *lease_id).await,
+                LeaseAction::Break { uri, break_period } => {
+                    lease::break_lease(uri, *break_period).await
+                }
+            },
+            Commands::Legalhold { action } => match action {
+                LegalHoldAction::Set { uri, tags } => immutability::set_legal_hold(uri, tags).await,
+                LegalHoldAction::Clear { uri, tags } => {
+                    immutability::clear_legal_hold(uri, tags).await
+                }
+            },
+            Commands::Lifecycle { action } => match action {
+                LifecycleAction::Get { uri, file } => lifecycle::get(uri, file.as_deref()).await,
+                LifecycleAction::Set { uri, file, dry_run } => {
+                    lifecycle::set(uri, file, *dry_run).await
+                }
+            },
+            Commands::Ls(args) => {
+                let LsArgs {
+                    path,
+                    long,
+                    human_readable,
+                    recursive,
+                    flat,
+                    directory,
+                    summary,
+                    no_summary,
+                    cached,
+                    account,
+                    signed_urls,
+                    since_state,
+                    etag,
+                    columns,
+                    null,
+                    subscription,
+                    all_subscriptions,
+                    resource_group,
+                    name_filter,
+                } = &**args;
                 ls::execute(
                     path.as_deref(),
                     *long,
                     *human_readable,
                     *recursive,
+                    *directory,
+                    *flat,
+                    match (summary, no_summary) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    },
+                    *cached,
                     account.as_deref(),
+                    *signed_urls,
+                    since_state.as_deref(),
+                    *etag,
+                    columns.as_deref(),
+                    *null,
+                    &AccountFilter {
+                        subscription: subscription.clone(),
+                        all_subscriptions: *all_subscriptions,
+                        resource_group: resource_group.clone(),
+                        name: name_filter.clone(),
+                    },
                 )
                 .await
             }
-            Commands::Mv {
-                source,
-                destination,
-                recursive,
-                force,
-            } => mv::execute(source, destination, *recursive, *force).await,
-            Commands::Rm {
-                path,
-                recursive,
-                force,
-                dry_run,
-                include_pattern,
-                exclude_pattern,
+            #[cfg(feature = "fuse")]
+            Commands::Mount {
+                uri,
+                mountpoint,
+                block_size,
+                cache_size,
+                readahead,
+                allow_other,
             } => {
-                rm::execute(
+                let settings = config::ReadaheadSettings {
+                    block_size: *block_size,
+                    blocks: *readahead,
+                    cache_size: *cache_size,
+                };
+                mount::execute(uri, mountpoint, settings, *allow_other).await
+            }
+            Commands::Mv(args) => {
+                let MvArgs {
+                    source,
+                    destination,
+                    recursive,
+                    force,
+                    dry_run,
+                    cap_mbps,
+                    bandwidth_schedule,
+                    block_size_mb,
+                    put_md5,
+                    include_pattern,
+                    exclude_pattern,
+                    include_path,
+                    exclude_path,
+                    include_regex,
+                    exclude_regex,
+                    no_clobber,
+                    overwrite,
+                    preserve,
+                    allow_cross_region,
+                } = &**args;
+                let filters = PathFilters {
+                    include_path: include_path.clone(),
+                    exclude_path: exclude_path.clone(),
+                    include_regex: include_regex.clone(),
+                    exclude_regex: exclude_regex.clone(),
+                };
+                mv::execute(
+                    CopyOptions {
+                        source,
+                        destination,
+                        recursive: *recursive,
+                        dry_run: *dry_run,
+                        cap_mbps: *cap_mbps,
+                        bandwidth_schedule: bandwidth_schedule.clone(),
+                        block_size_mb: *block_size_mb,
+                        put_md5: *put_md5,
+                        include_pattern: include_pattern.as_deref(),
+                        exclude_pattern: exclude_pattern.as_deref(),
+                        filters,
+                        overwrite: overwrite_policy(*no_clobber, *overwrite),
+                        preserve: *preserve,
+                        allow_cross_region: *allow_cross_region,
+                        prompter: &*prompter,
+                        ..Default::default()
+                    },
+                    *force,
+                )
+                .await
+            }
+            Commands::Parse { uri } => parse::execute(uri).await,
+            Commands::Retention { action } => match action {
+                RetentionAction::Set {
+                    uri,
+                    days,
+                    until,
+                    lock,
+                } => immutability::set_retention(uri, *days, *until, *lock).await,
+                RetentionAction::Get { uri } => immutability::get(uri).await,
+                RetentionAction::Clear { uri } => immutability::clear_retention(uri).await,
+            },
+            Commands::RetryFailed { failures } => retry_failed::execute(failures.as_deref()).await,
+            Commands::Rm(args) => {
+                let RmArgs {
                     path,
+                    recursive,
+                    force,
+                    dry_run,
+                    include_pattern,
+                    exclude_pattern,
+                    stdin,
+                    failures_out,
+                    lease_id,
+                    older_than,
+                    newer_than,
+                    min_size,
+                    max_size,
+                    max_deletes,
+                } = &**args;
+                rm::execute(
+                    path.as_deref(),
+                    *stdin,
                     *recursive,
                     *force,
                     *dry_run,
                     include_pattern.as_deref(),
                     exclude_pattern.as_deref(),
+                    failures_out.as_deref(),
+                    *lease_id,
+                    BlobSelection {
+                        older_than: *older_than,
+                        newer_than: *newer_than,
+                        min_size: *min_size,
+                        max_size: *max_size,
+                    },
+                    *max_deletes,
+                    &*prompter,
                 )
                 .await
             }
-            Commands::Sync {
-                source,
-                destination,
-                delete,
-                force,
-                dry_run,
-                cap_mbps,
-                block_size_mb,
-                put_md5,
-                include_pattern,
-                exclude_pattern,
-            } => {
+            Commands::Serve {
+                uri,
+                port,
+                bind,
+                list,
+            } => serve::execute(uri, bind, *port, *list).await,
+            Commands::Shell => {
+                shell::execute(shell::SessionOptions {
+                    strict: self.strict,
+                    audit_log: self.audit_log.clone(),
+                })
+                .await
+            }
+            Commands::Sync(args) => {
+                let SyncArgs {
+                    source,
+                    destination,
+                    delete,
+                    mirror,
+                    max_delete,
+                    force,
+                    dry_run,
+                    cap_mbps,
+                    bandwidth_schedule,
+                    block_size_mb,
+                    concurrency,
+                    buffer_gb,
+                    scan_concurrency,
+                    put_md5,
+                    include_pattern,
+                    exclude_pattern,
+                    include_path,
+                    exclude_path,
+                    include_regex,
+                    exclude_regex,
+                    track_state,
+                    conflict,
+                    bidirectional,
+                    preserve,
+                    preserve_empty_dirs,
+                    follow_symlinks,
+                    skip_symlinks: _,
+                    preserve_symlinks,
+                    max_upload_bytes,
+                    allow_cross_region,
+                    compare,
+                    tuning,
+                } = &**args;
+                let transfer_flags = TransferConfig {
+                    concurrency: *concurrency,
+                    buffer_gb: *buffer_gb,
+                    scan_concurrency: *scan_concurrency,
+                };
+                let filters = PathFilters {
+                    include_path: include_path.clone(),
+                    exclude_path: exclude_path.clone(),
+                    include_regex: include_regex.clone(),
+                    exclude_regex: exclude_regex.clone(),
+                };
                 sync::execute(
                     source,
                     destination,
                     *delete,
+                    *mirror,
+                    *max_delete,
                     *force,
                     *dry_run,
                     *cap_mbps,
+                    bandwidth_schedule.clone(),
                     *block_size_mb,
                     *put_md5,
                     include_pattern.as_deref(),
                     exclude_pattern.as_deref(),
+                    filters,
+                    track_state.as_deref(),
+                    *conflict,
+                    *bidirectional,
+                    *preserve,
+                    *preserve_empty_dirs,
+                    *max_upload_bytes,
+                    *allow_cross_region,
+                    *compare,
+                    symlink_policy(*follow_symlinks, *preserve_symlinks),
+                    *tuning,
+                    transfer_flags,
+                    &*prompter,
                 )
                 .await
             }
+            Commands::Watch {
+                uri,
+                interval,
+                change_feed,
+                exec,
+            } => watch::execute(uri, *interval, *change_feed, exec.as_deref()).await,
         }
     }
 }
+
+#[derive(Subcommand)]
+pub enum AccountAction {
+    /// Save a storage account as the default
+    Set {
+        /// Storage account name
+        name: String,
+    },
+    /// Show the default storage account
+    Show,
+}
+
+#[derive(Subcommand)]
+pub enum AliasAction {
+    /// Add an alias, or point an existing one elsewhere
+    Add {
+        /// Name to use as a URI scheme, e.g. 'curated' for curated://
+        name: String,
+        /// Location it stands for (az://account/[container/][prefix])
+        uri: String,
+    },
+    /// Remove an alias
+    #[command(visible_alias = "rm")]
+    Remove {
+        /// Alias to remove
+        name: String,
+    },
+    /// List aliases
+    #[command(visible_alias = "ls")]
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum AuthAction {
+    /// Sign in with a Microsoft Entra ID account
+    Login {
+        /// Sign in by entering a code on any device instead of opening a browser
+        #[arg(long)]
+        device_code: bool,
+        /// Tenant ID or domain to sign in to; 'organizations' picks the account's home tenant
+        #[arg(long, default_value = DEFAULT_TENANT)]
+        tenant: String,
+        /// Subscription to list storage accounts in, instead of asking the Azure CLI
+        #[arg(long)]
+        subscription: Option<String>,
+    },
+    /// Show which credential, identity and tenant commands use
+    Status,
+    /// Print an access token for scripting
+    Token {
+        /// OAuth scope to request the token for
+        #[arg(long, default_value = STORAGE_SCOPE)]
+        scope: String,
+    },
+    /// Sign out and remove cached Azure tokens
+    Logout,
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// List again and replace the cached listing
+    Refresh {
+        /// Container, prefix or account (az://account/[container/][prefix])
+        uri: String,
+    },
+    /// Remove cached listings
+    Clear {
+        /// Only those of this account or container (default: all)
+        uri: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CtlAction {
+    /// Set who can read a container without credentials
+    SetPublicAccess {
+        /// Container (az://account/container)
+        uri: String,
+        /// Public access level
+        #[arg(value_enum)]
+        level: PublicAccessLevel,
+    },
+    /// Show a container's settings, or the public access of an account's containers
+    Show {
+        /// Container or account (az://account/[container])
+        uri: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LeaseAction {
+    /// Take a lease and print its ID
+    Acquire {
+        /// Container or blob to lease (az://account/container[/blob])
+        uri: String,
+        /// How long the lease lasts unless renewed: 15 to 60 seconds, or 'infinite'
+        #[arg(long, default_value = "60", value_parser = parse_lease_duration)]
+        duration: LeaseDuration,
+        /// Lease ID (a GUID) to use instead of one picked by Azure
+        #[arg(long, value_parser = parse_lease_id)]
+        lease_id: Option<LeaseId>,
+    },
+    /// Restart a lease's duration
+    Renew {
+        /// Leased container or blob
+        uri: String,
+        /// Lease to renew (default: the one azst acquired on the URI)
+        #[arg(long, value_parser = parse_lease_id)]
+        lease_id: Option<LeaseId>,
+    },
+    /// End a lease right away
+    Release {
+        /// Leased container or blob
+        uri: String,
+        /// Lease to release (default: the one azst acquired on the URI)
+        #[arg(long, value_parser = parse_lease_id)]
+        lease_id: Option<LeaseId>,
+    },
+    /// End a lease without its ID
+    Break {
+        /// Leased container or blob
+        uri: String,
+        /// Seconds the lease may still be held before it breaks (default:
+        /// the rest of a fixed lease, or at once for an infinite one)
+        #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u8).range(0..=60))]
+        break_period: Option<u8>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LegalHoldAction {
+    /// Place a legal hold
+    Set {
+        /// Container or blob to hold (az://account/container[/blob])
+        uri: String,
+        /// Tag naming the hold, e.g. a case number (containers only; can be repeated)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Lift a legal hold
+    Clear {
+        /// Held container or blob
+        uri: String,
+        /// Hold to lift (containers only; default: every hold; can be repeated)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LifecycleAction {
+    /// Print the policy as JSON, or save it to a file
+    Get {
+        /// Storage account (az://account/)
+        uri: String,
+        /// File to write the policy to (.json, .yaml or .yml)
+        file: Option<PathBuf>,
+    },
+    /// Replace the policy with the one in a file
+    Set {
+        /// Storage account (az://account/)
+        uri: String,
+        /// Policy file (.json, .yaml or .yml), or - for stdin
+        file: PathBuf,
+        /// Check the file and show its rules without applying it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RetentionAction {
+    /// Set or extend a retention policy
+    Set {
+        /// Container or blob to protect (az://account/container[/blob])
+        uri: String,
+        /// Days to retain a blob from now, or every blob of a container after
+        /// its creation
+        #[arg(
+            long,
+            required_unless_present = "until",
+            conflicts_with = "until",
+            value_parser = clap::value_parser!(u32).range(1..=i64::from(MAX_RETENTION_DAYS))
+        )]
+        days: Option<u32>,
+        /// Date to retain a blob until (YYYY-MM-DD or RFC 3339)
+        #[arg(long, value_parser = parse_until)]
+        until: Option<DateTime<Utc>>,
+        /// Lock the policy so it can never be shortened or removed
+        #[arg(long)]
+        lock: bool,
+    },
+    /// Show the retention policy and legal hold in effect
+    Get {
+        /// Container or blob (az://account/container[/blob])
+        uri: String,
+    },
+    /// Remove an unlocked retention policy
+    Clear {
+        /// Protected container or blob
+        uri: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AzcopyAction {
+    /// Download the pinned AzCopy release into the bundled location
+    Install {
+        /// Download again even if the pinned version is already installed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Replace the bundled AzCopy if it isn't the pinned release
+    Update,
+    /// Show which AzCopy binary will be used and its version
+    Which,
+}
+
+/// cp options that select or transform individual files, which an archive
+/// transfer (--tar or --untar) or a packed one (--pack or --unpack) can't honor;
+/// those four are one "archive" group, so only one of them can be given
+const ARCHIVE_CONFLICTS: [&str; 18] = [
+    "cap_mbps",
+    "bandwidth_schedule",
+    "include_pattern",
+    "exclude_pattern",
+    "include_path",
+    "exclude_path",
+    "include_regex",
+    "exclude_regex",
+    "check_md5",
+    "case_conflict",
+    "preserve_empty_dirs",
+    "max_upload_bytes",
+    "compress",
+    "gzip_local",
+    "gzip_local_all",
+    "flatten",
+    "failures_out",
+    "lease_id",
+];
+
+/// Resolve the effective overwrite policy from --no-clobber and --overwrite
+fn symlink_policy(follow: bool, preserve: bool) -> SymlinkPolicy {
+    if follow {
+        SymlinkPolicy::Follow
+    } else if preserve {
+        SymlinkPolicy::Preserve
+    } else {
+        SymlinkPolicy::Skip
+    }
+}
+
+fn overwrite_policy(no_clobber: bool, overwrite: Option<OverwritePolicy>) -> OverwritePolicy {
+    if no_clobber {
+        OverwritePolicy::Never
+    } else {
+        overwrite.unwrap_or_default()
+    }
+}
+
+/// Parse a `key=value` metadata pair
+fn parse_metadata(pair: &str) -> Result<(String, String), String> {
+    let (key, value) = pair
+        .split_once('=')
+        .ok_or_else(|| format!("invalid metadata '{}': expected key=value", pair))?;
+
+    let key = key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "invalid metadata key '{}': use letters, digits and underscores",
+            key
+        ));
+    }
+
+    // azcopy takes all pairs in one --metadata, separated by ';'
+    if value.contains(';') {
+        return Err(format!(
+            "invalid metadata value '{}': ';' can't be used in metadata values",
+            value
+        ));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_md5_without_value() {
+        let cli =
+            Cli::try_parse_from(["azst", "cp", "--check-md5", "a.txt", "az://acct/c/"]).unwrap();
+        let Commands::Cp(args) = cli.command else {
+            panic!("not a cp command");
+        };
+        assert_eq!(args.check_md5, Some(Md5Check::Fail));
+        assert_eq!(args.source, "a.txt");
+        assert_eq!(args.destination, "az://acct/c/");
+
+        let cli = Cli::try_parse_from(["azst", "cp", "--check-md5=log", "a.txt", "az://acct/c/"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Cp(args) if args.check_md5 == Some(Md5Check::Log)
+        ));
+    }
+}
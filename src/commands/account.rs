@@ -0,0 +1,45 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+
+use crate::config::Config;
+use crate::utils::is_storage_account_name;
+
+/// Make `account` the default for `az:container/path` URIs and for legacy
+/// URIs without an account
+pub async fn set(account: &str) -> Result<()> {
+    let account = account.trim_start_matches("az://").trim_end_matches('/');
+    if !is_storage_account_name(account) {
+        return Err(anyhow!(
+            "'{}' isn't a storage account name: 3-24 lowercase letters and digits",
+            account
+        ));
+    }
+
+    let path = Config::save_default_account(account)?;
+    println!(
+        "{} Default account: {} (saved to {})",
+        "✓".green(),
+        account.cyan(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Show the default account and where it is configured
+pub async fn show() -> Result<()> {
+    let config = Config::load()?;
+    match config.default_account {
+        Some(account) => {
+            println!("Default account: {}", account);
+            if let Some(path) = Config::path() {
+                println!("Config file:     {}", path.display());
+            }
+            println!(
+                "Shorthand:       az:<container>/<path> means az://{}/<container>/<path>",
+                account
+            );
+        }
+        None => println!("No default account; choose one with 'azst account set <name>'"),
+    }
+    Ok(())
+}
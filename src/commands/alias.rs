@@ -0,0 +1,95 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+
+use crate::config::Config;
+use crate::utils::parse_azure_uri;
+
+/// Schemes an alias may not take over
+const RESERVED: &[&str] = &["az", "http", "https", "file", "gs", "s3"];
+
+/// Alias names are URI schemes: a lowercase letter, then letters, digits,
+/// '-' or '_'
+fn check_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(anyhow!(
+            "Invalid alias '{}': use a lowercase letter, then letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    if RESERVED.contains(&name) {
+        return Err(anyhow!("'{}' can't be an alias: it is a URI scheme", name));
+    }
+    Ok(())
+}
+
+/// Save `name` as a short name for `uri`, so `name://rest` means `uri/rest`
+pub async fn add(name: &str, uri: &str) -> Result<()> {
+    check_name(name)?;
+    let (account, _, _) = parse_azure_uri(uri)?;
+    if account.is_none() {
+        return Err(anyhow!(
+            "Aliases need a storage account: az://<account>/[container/][prefix]"
+        ));
+    }
+
+    let path = Config::save_alias(name, uri)?;
+    println!(
+        "{} {}:// now means {} (saved to {})",
+        "✓".green(),
+        name.cyan(),
+        uri.cyan(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Forget an alias
+pub async fn remove(name: &str) -> Result<()> {
+    if !Config::load()?.aliases.contains_key(name) {
+        return Err(anyhow!("No alias '{}'", name));
+    }
+    Config::remove_alias(name)?;
+    println!("{} Removed {}://", "✓".green(), name);
+    Ok(())
+}
+
+/// Print every alias and what it stands for
+pub async fn list() -> Result<()> {
+    let config = Config::load()?;
+    if config.aliases.is_empty() {
+        println!("No aliases; add one with 'azst alias add <name> az://<account>/<container>/'");
+        return Ok(());
+    }
+    let mut aliases: Vec<_> = config.aliases.iter().collect();
+    aliases.sort();
+    let width = aliases
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        + 3;
+    for (name, uri) in aliases {
+        println!("{:<width$} {}", format!("{}://", name), uri, width = width);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("data").is_ok());
+        assert!(check_name("raw-2024_v2").is_ok());
+        assert!(check_name("Data").is_err());
+        assert!(check_name("2024").is_err());
+        assert!(check_name("my data").is_err());
+        assert!(check_name("az").is_err());
+        assert!(check_name("https").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::collections::BTreeMap;
+
+use crate::azure::{AzureClient, BlobInfo, BlobItem};
+use crate::blob_reader::hash_blob;
+use crate::checksum::{to_hex, HashSelection};
+use crate::utils::{directory_prefix, format_size, parse_azure_uri};
+
+/// One side of an audit: the blobs found under a prefix, keyed by relative name
+struct Listing {
+    client: AzureClient,
+    container: String,
+    prefix: String,
+    blobs: BTreeMap<String, BlobInfo>,
+}
+
+/// Outcome of checking a single sampled object
+#[derive(Debug, PartialEq)]
+enum Finding {
+    Match,
+    Missing,
+    SizeMismatch { source: u64, destination: u64 },
+    Md5Mismatch { source: String, destination: String },
+}
+
+pub async fn execute(
+    source: &str,
+    destination: &str,
+    sample_percent: f64,
+    seed: u64,
+    cap_mbps: Option<f64>,
+) -> Result<()> {
+    if !(sample_percent > 0.0 && sample_percent <= 100.0) {
+        return Err(anyhow!("--sample must be between 0 and 100 percent"));
+    }
+
+    status!(
+        "{} Auditing {} against {} ({}% sample)",
+        "→".green(),
+        source.cyan(),
+        destination.cyan(),
+        sample_percent
+    );
+
+    let src = list_side(source).await?;
+    let dst = list_side(destination).await?;
+
+    let sampled: Vec<String> = src
+        .blobs
+        .keys()
+        .filter(|name| is_sampled(name, seed, sample_percent))
+        .cloned()
+        .collect();
+
+    status!(
+        "{} {} source objects ({}), {} sampled",
+        "ℹ".blue(),
+        src.blobs.len(),
+        format_size(
+            src.blobs
+                .values()
+                .map(|b| b.properties.content_length)
+                .sum()
+        ),
+        sampled.len()
+    );
+
+    let mut failures = 0;
+    for name in &sampled {
+        let finding = check_object(name, &src, &dst, cap_mbps).await?;
+        let detail = match &finding {
+            Finding::Match => continue,
+            Finding::Missing => "missing at destination".to_string(),
+            Finding::SizeMismatch {
+                source,
+                destination,
+            } => format!("size {} != {}", source, destination),
+            Finding::Md5Mismatch {
+                source,
+                destination,
+            } => format!("md5 {} != {}", source, destination),
+        };
+        failures += 1;
+        println!("{} {}: {}", "×".red(), name, detail);
+    }
+
+    print_report(src.blobs.len(), sampled.len(), failures);
+
+    if failures > 0 {
+        return Err(anyhow!(
+            "{} of {} sampled objects differ",
+            failures,
+            sampled.len()
+        ));
+    }
+    Ok(())
+}
+
+async fn list_side(uri: &str) -> Result<Listing> {
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!(
+            "Audit requires a storage account in '{}': az://<account>/<container>/[path]",
+            uri
+        )
+    })?;
+    if container.is_empty() {
+        return Err(anyhow!("Audit requires a container in '{}'", uri));
+    }
+
+    // Treat the prefix as a directory
+    let prefix = directory_prefix(prefix.as_deref());
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+    client.check_prerequisites().await?;
+
+    let mut blobs = BTreeMap::new();
+    let list_prefix = (!prefix.is_empty()).then_some(prefix.as_str());
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    let relative = blob.name.strip_prefix(&prefix).unwrap_or(&blob.name);
+                    if !relative.is_empty() && !relative.ends_with('/') {
+                        blobs.insert(relative.to_string(), blob);
+                    }
+                }
+            }
+            Ok(())
+        })
+        .await?;
+
+    Ok(Listing {
+        client,
+        container,
+        prefix,
+        blobs,
+    })
+}
+
+/// Compare one object's size and MD5 on both sides, hashing content only when
+/// a side has no stored Content-MD5
+async fn check_object(
+    name: &str,
+    src: &Listing,
+    dst: &Listing,
+    cap_mbps: Option<f64>,
+) -> Result<Finding> {
+    let Some(dst_blob) = dst.blobs.get(name) else {
+        return Ok(Finding::Missing);
+    };
+    let src_blob = &src.blobs[name];
+
+    let (src_size, dst_size) = (
+        src_blob.properties.content_length,
+        dst_blob.properties.content_length,
+    );
+    if src_size != dst_size {
+        return Ok(Finding::SizeMismatch {
+            source: src_size,
+            destination: dst_size,
+        });
+    }
+
+    let src_md5 = match src_blob.properties.content_md5.clone() {
+        Some(md5) => md5,
+        None => object_md5(src, name, cap_mbps).await?,
+    };
+    let dst_md5 = match dst_blob.properties.content_md5.clone() {
+        Some(md5) => md5,
+        None => object_md5(dst, name, cap_mbps).await?,
+    };
+
+    if src_md5 == dst_md5 {
+        Ok(Finding::Match)
+    } else {
+        Ok(Finding::Md5Mismatch {
+            source: src_md5,
+            destination: dst_md5,
+        })
+    }
+}
+
+async fn object_md5(side: &Listing, name: &str, cap_mbps: Option<f64>) -> Result<String> {
+    let blob = format!("{}{}", side.prefix, name);
+    let selection = HashSelection {
+        md5: true,
+        crc64: false,
+    };
+    let digests = hash_blob(
+        side.client.clone(),
+        &side.container,
+        &blob,
+        selection,
+        cap_mbps,
+    )
+    .await?;
+    Ok(to_hex(&digests.md5.unwrap_or_default()))
+}
+
+/// Deterministically decide whether an object belongs to the sample
+///
+/// Uses FNV-1a so that the same seed selects the same objects across runs and
+/// releases, making audits reproducible.
+fn is_sampled(name: &str, seed: u64, percent: f64) -> bool {
+    let mut hash: u64 = 0xcbf29ce484222325 ^ seed;
+    for byte in name.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (hash % 1_000_000) as f64 / 10_000.0 < percent
+}
+
+/// Upper bound of the 95% Wilson score interval for a failure proportion
+fn wilson_upper_bound(failures: usize, sampled: usize) -> f64 {
+    if sampled == 0 {
+        return 1.0;
+    }
+    let z = 1.96_f64;
+    let n = sampled as f64;
+    let p = failures as f64 / n;
+    let center = p + z * z / (2.0 * n);
+    let margin = z * ((p * (1.0 - p) + z * z / (4.0 * n)) / n).sqrt();
+    ((center + margin) / (1.0 + z * z / n)).min(1.0)
+}
+
+fn print_report(total: usize, sampled: usize, failures: usize) {
+    println!();
+    println!("{}", "Audit report".bold());
+    println!("  Objects in source:  {}", total);
+    println!("  Objects sampled:    {}", sampled);
+    println!("  Mismatches found:   {}", failures);
+
+    if sampled == 0 {
+        println!(
+            "{} No objects were sampled; increase --sample to get an estimate",
+            "⚠".yellow()
+        );
+        return;
+    }
+
+    let observed = failures as f64 / sampled as f64;
+    let upper = wilson_upper_bound(failures, sampled);
+    println!("  Observed rate:      {:.4}%", observed * 100.0);
+    println!(
+        "  95% upper bound:    {:.4}% (≈ {} of {} objects)",
+        upper * 100.0,
+        (upper * total as f64).ceil() as u64,
+        total
+    );
+
+    if failures == 0 {
+        println!(
+            "{} With 95% confidence, at most {:.4}% of objects differ",
+            "✓".green(),
+            upper * 100.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sampled_is_deterministic() {
+        let names: Vec<String> = (0..10_000).map(|i| format!("part-{:05}.csv", i)).collect();
+        let first: Vec<bool> = names.iter().map(|n| is_sampled(n, 7, 5.0)).collect();
+        let second: Vec<bool> = names.iter().map(|n| is_sampled(n, 7, 5.0)).collect();
+        assert_eq!(first, second);
+
+        // Roughly 5% selected
+        let selected = first.iter().filter(|s| **s).count();
+        assert!((300..700).contains(&selected), "selected {}", selected);
+    }
+
+    #[test]
+    fn test_is_sampled_bounds() {
+        assert!(is_sampled("anything", 0, 100.0));
+        assert!(!is_sampled("anything", 0, 0.0));
+    }
+
+    #[test]
+    fn test_wilson_upper_bound() {
+        // Zero failures in 300 samples: close to the "rule of three" (1%)
+        let upper = wilson_upper_bound(0, 300);
+        assert!(upper > 0.009 && upper < 0.013, "upper {}", upper);
+
+        // Bound always exceeds the observed rate
+        assert!(wilson_upper_bound(5, 100) > 0.05);
+        assert_eq!(wilson_upper_bound(0, 0), 1.0);
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use colored::*;
+
+use crate::azure::{clear_token_cache, AzureClient};
+use crate::login::{self, LoginFlow, StoredLogin, TokenClaims, STORAGE_SCOPE};
+
+/// Sign in with a Microsoft Entra ID account, without the Azure CLI
+///
+/// The sign-in is kept until `azst auth logout` and takes precedence over
+/// every other credential.
+pub async fn login(flow: LoginFlow, tenant: &str, subscription: Option<String>) -> Result<()> {
+    let token = login::login(flow, tenant, subscription).await?;
+
+    // Tokens cached for a previous identity must not outlive the switch
+    clear_token_cache()?;
+
+    let claims = TokenClaims::from_token(token.token.secret()).unwrap_or_default();
+    println!(
+        "{} Signed in as {} (tenant {})",
+        "✓".green(),
+        claims.identity().unwrap_or("unknown identity"),
+        claims.tid.as_deref().unwrap_or(tenant)
+    );
+    Ok(())
+}
+
+/// Show which credential commands use and who it authenticates as
+pub async fn status() -> Result<()> {
+    println!("Credential:   {}", AzureClient::credential_source());
+
+    let token = AzureClient::new().access_token(STORAGE_SCOPE).await?;
+    let claims = TokenClaims::from_token(token.token.secret()).unwrap_or_default();
+    println!("Identity:     {}", claims.identity().unwrap_or("unknown"));
+    println!(
+        "Tenant:       {}",
+        claims.tid.as_deref().unwrap_or("unknown")
+    );
+    if let Some(subscription) = std::env::var("AZURE_SUBSCRIPTION_ID")
+        .ok()
+        .or_else(|| StoredLogin::load().and_then(|login| login.subscription))
+    {
+        println!("Subscription: {}", subscription);
+    }
+    if let Ok(expires_on) = token
+        .expires_on
+        .format(&time::format_description::well_known::Rfc3339)
+    {
+        println!("Expires:      {}", expires_on);
+    }
+    Ok(())
+}
+
+/// Print an access token for `scope`, for use with curl and other tools
+pub async fn token(scope: &str) -> Result<()> {
+    let token = AzureClient::new().access_token(scope).await?;
+    println!("{}", token.token.secret());
+    Ok(())
+}
+
+/// Sign out and forget the Azure tokens azst cached between invocations
+///
+/// The next command acquires fresh tokens through the credential chain, e.g.
+/// for the account selected with `az login` or `az account set`.
+pub async fn logout() -> Result<()> {
+    let signed_out = StoredLogin::remove()?;
+    if signed_out {
+        println!("{} Signed out", "✓".green());
+    }
+    if clear_token_cache()? {
+        println!("{} Removed cached Azure tokens", "✓".green());
+    } else if !signed_out {
+        println!("{} No cached Azure tokens", "ℹ".blue());
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use crate::azure::{
+    determine_azcopy_executable, get_azcopy_version, get_bundled_azcopy_path, AZCOPY_PINNED_VERSION,
+};
+use crate::network;
+
+const AZCOPY_REPO: &str = "Azure/azure-storage-azcopy";
+
+/// A release asset as described by the GitHub releases API
+#[derive(Debug, Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+    /// "sha256:<hex>", published by GitHub for every asset
+    digest: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    assets: Vec<ReleaseAsset>,
+}
+
+/// Download the pinned AzCopy into the bundled location
+///
+/// Does nothing when the bundled copy is already the pinned version, unless
+/// `force` is set.
+pub async fn install(force: bool) -> Result<()> {
+    let path = get_bundled_azcopy_path()?;
+    let installed = bundled_version(&path).await;
+
+    if !force {
+        match installed.as_deref() {
+            Some(AZCOPY_PINNED_VERSION) => {
+                status!(
+                    "{} AzCopy {} is already installed at {}",
+                    "✓".green(),
+                    AZCOPY_PINNED_VERSION,
+                    path.display()
+                );
+                return Ok(());
+            }
+            Some(other) => {
+                return Err(anyhow!(
+                    "AzCopy {} is installed at {}. Run 'azst azcopy update' to replace it with {}",
+                    other,
+                    path.display(),
+                    AZCOPY_PINNED_VERSION
+                ));
+            }
+            None => {}
+        }
+    }
+
+    download_pinned(&path).await?;
+    status!(
+        "{} AzCopy {} installed at {}",
+        "✓".green(),
+        AZCOPY_PINNED_VERSION,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Replace the bundled AzCopy with the pinned version if it differs
+pub async fn update() -> Result<()> {
+    let path = get_bundled_azcopy_path()?;
+    let installed = bundled_version(&path).await;
+
+    if installed.as_deref() == Some(AZCOPY_PINNED_VERSION) {
+        status!(
+            "{} AzCopy is up to date ({})",
+            "✓".green(),
+            AZCOPY_PINNED_VERSION
+        );
+        return Ok(());
+    }
+
+    download_pinned(&path).await?;
+    status!(
+        "{} AzCopy updated from {} to {}",
+        "✓".green(),
+        installed.as_deref().unwrap_or("(none)"),
+        AZCOPY_PINNED_VERSION
+    );
+    Ok(())
+}
+
+/// Report which AzCopy executable azst will run and where it comes from
+pub async fn which() -> Result<()> {
+    let executable = determine_azcopy_executable().await?;
+    let bundled = get_bundled_azcopy_path()?;
+    let version = get_azcopy_version(&executable).await.ok().flatten();
+
+    let source = if Path::new(&executable) == bundled {
+        "bundled"
+    } else {
+        "system PATH"
+    };
+
+    println!("{} {}", "Executable:".bold(), executable);
+    println!("{} {}", "Source:    ".bold(), source);
+    println!(
+        "{} {}",
+        "Version:   ".bold(),
+        version.as_deref().unwrap_or("not found")
+    );
+    println!("{} {}", "Pinned:    ".bold(), AZCOPY_PINNED_VERSION);
+    println!("{} {}", "Bundled:   ".bold(), bundled.display());
+
+    if version.as_deref() != Some(AZCOPY_PINNED_VERSION) {
+        println!(
+            "{} No AzCopy {} found. Run 'azst azcopy install' to download it",
+            "⚠".yellow(),
+            AZCOPY_PINNED_VERSION
+        );
+    }
+    Ok(())
+}
+
+async fn bundled_version(path: &Path) -> Option<String> {
+    if !path.exists() {
+        return None;
+    }
+    get_azcopy_version(&path.to_string_lossy())
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Download, verify and unpack the pinned release into `path`
+async fn download_pinned(path: &Path) -> Result<()> {
+    let asset_name = asset_name(std::env::consts::OS, std::env::consts::ARCH)?;
+    let client = network::client_builder()?
+        .user_agent(concat!("azst/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+
+    let release_url = format!(
+        "https://api.github.com/repos/{}/releases/tags/v{}",
+        AZCOPY_REPO, AZCOPY_PINNED_VERSION
+    );
+    let release: Release = client
+        .get(&release_url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Failed to look up the AzCopy release on GitHub")?
+        .json()
+        .await
+        .context("Unexpected response from the GitHub releases API")?;
+
+    let asset = release
+        .assets
+        .into_iter()
+        .find(|asset| asset.name == asset_name)
+        .ok_or_else(|| anyhow!("AzCopy release has no asset named {}", asset_name))?;
+    let expected = asset
+        .digest
+        .as_deref()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .ok_or_else(|| anyhow!("No SHA-256 checksum published for {}", asset_name))?
+        .to_lowercase();
+
+    status!("{} Downloading {}", "→".green(), asset.browser_download_url);
+    let archive = client
+        .get(&asset.browser_download_url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to download {}", asset_name))?
+        .bytes()
+        .await?;
+
+    let actual = format!("{:x}", Sha256::digest(&archive));
+    if actual != expected {
+        return Err(anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            asset_name,
+            expected,
+            actual
+        ));
+    }
+    status!("{} Checksum verified (sha256:{})", "✓".green(), actual);
+
+    let binary = extract_azcopy(&archive, asset_name.ends_with(".zip"))?;
+    write_executable(path, &binary)
+}
+
+/// Release asset for an OS/architecture, using Rust's names for both
+fn asset_name(os: &str, arch: &str) -> Result<String> {
+    let (os, extension) = match os {
+        "linux" => ("linux", "tar.gz"),
+        "macos" => ("darwin", "zip"),
+        "windows" => ("windows", "zip"),
+        other => return Err(anyhow!("AzCopy is not available for {}", other)),
+    };
+    let arch = match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => return Err(anyhow!("AzCopy is not available for {}", other)),
+    };
+    Ok(format!(
+        "azcopy_{}_{}_{}.{}",
+        os, arch, AZCOPY_PINNED_VERSION, extension
+    ))
+}
+
+/// Pull the azcopy executable out of a release archive
+fn extract_azcopy(archive: &[u8], is_zip: bool) -> Result<Vec<u8>> {
+    let is_binary = |name: &str| {
+        matches!(
+            Path::new(name).file_name().and_then(|n| n.to_str()),
+            Some("azcopy" | "azcopy.exe")
+        )
+    };
+
+    let mut binary = Vec::new();
+    if is_zip {
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i)?;
+            if entry.is_file() && is_binary(entry.name()) {
+                entry.read_to_end(&mut binary)?;
+                return Ok(binary);
+            }
+        }
+    } else {
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().to_string();
+            if entry.header().entry_type().is_file() && is_binary(&name) {
+                entry.read_to_end(&mut binary)?;
+                return Ok(binary);
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "Could not find the azcopy binary in the downloaded archive"
+    ))
+}
+
+/// Write the binary next to its final path and rename it into place, so a
+/// running azst never sees a half-written executable
+fn write_executable(path: &Path, binary: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    std::fs::write(&partial, binary)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&partial, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    std::fs::rename(&partial, path)
+        .with_context(|| format!("Failed to install AzCopy at {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_name() {
+        assert_eq!(
+            asset_name("linux", "x86_64").unwrap(),
+            format!("azcopy_linux_amd64_{}.tar.gz", AZCOPY_PINNED_VERSION)
+        );
+        assert_eq!(
+            asset_name("macos", "aarch64").unwrap(),
+            format!("azcopy_darwin_arm64_{}.zip", AZCOPY_PINNED_VERSION)
+        );
+        assert!(asset_name("freebsd", "x86_64").is_err());
+        assert!(asset_name("linux", "riscv64").is_err());
+    }
+
+    #[test]
+    fn test_extract_azcopy_from_tarball() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [
+            ("azcopy_linux_amd64/NOTICE.txt", &b"notice"[..]),
+            ("azcopy_linux_amd64/azcopy", &b"binary"[..]),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content).unwrap();
+        }
+        let tarball = builder.into_inner().unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut gz, &tarball).unwrap();
+        let archive = gz.finish().unwrap();
+
+        assert_eq!(extract_azcopy(&archive, false).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn test_extract_azcopy_missing_binary() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("README.md", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut zip, b"readme").unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+
+        assert!(extract_azcopy(&archive, true).is_err());
+    }
+
+    #[test]
+    fn test_write_executable() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("azcopy").join("azcopy");
+        write_executable(&path, b"binary").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"binary");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+}
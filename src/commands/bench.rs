@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::azcopy_output::PerformanceAdvice;
+use crate::azure::{convert_az_uri_to_url, AzCopyClient, AzCopyOptions};
+use crate::config::TransferConfig;
+use crate::output::{output_format, OutputFormat};
+use crate::throttle::{default_concurrency, STEP_DOWN_BUSY_PERCENT};
+use crate::utils::{format_size, is_azure_uri, parse_azure_uri};
+
+// ============================================================================
+// Bench - Measure transfer throughput to a container with azcopy's benchmark
+// ============================================================================
+
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+
+/// Files at least this large move faster in bigger blocks than azcopy's 8 MiB
+const LARGE_FILE: u64 = GIB;
+
+/// Files below this size are bound by operations per second, not bandwidth
+const SMALL_FILE: u64 = MIB;
+
+/// What a benchmark measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BenchMode {
+    /// Upload generated files to the container
+    Upload,
+    /// Upload files, then time downloading them
+    Download,
+}
+
+impl BenchMode {
+    fn as_azcopy_value(&self) -> &'static str {
+        match self {
+            BenchMode::Upload => "Upload",
+            BenchMode::Download => "Download",
+        }
+    }
+}
+
+/// Results of a benchmark, as printed with `--format json`
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    mode: BenchMode,
+    files: u64,
+    bytes: u64,
+    seconds: f64,
+    megabits_per_second: f64,
+    iops: f64,
+    server_busy_percent: f64,
+    advice: Vec<PerformanceAdvice>,
+    suggestions: Vec<String>,
+}
+
+/// Size in the form azcopy's `--size-per-file` takes: a number with a K, M
+/// or G suffix
+fn azcopy_size(bytes: u64) -> String {
+    if bytes >= GIB && bytes.is_multiple_of(GIB) {
+        format!("{}G", bytes / GIB)
+    } else if bytes >= MIB && bytes.is_multiple_of(MIB) {
+        format!("{}M", bytes / MIB)
+    } else {
+        format!("{}K", bytes.div_ceil(1024).max(1))
+    }
+}
+
+fn megabits_per_second(bytes: u64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        bytes as f64 * 8.0 / seconds / 1_000_000.0
+    } else {
+        0.0
+    }
+}
+
+/// Tuning to try on real transfers, from the shape of the benchmark and how
+/// the service answered it
+fn suggestions(
+    size_per_file: u64,
+    block_size_mb: Option<f64>,
+    server_busy_percent: f64,
+) -> Vec<String> {
+    let mut suggestions = Vec::new();
+    if server_busy_percent > STEP_DOWN_BUSY_PERCENT {
+        suggestions.push(format!(
+            "The account throttled {:.1}% of requests: lower the concurrency, e.g. --concurrency {}",
+            server_busy_percent,
+            default_concurrency() / 2
+        ));
+    }
+    if size_per_file >= LARGE_FILE && block_size_mb.is_none() {
+        suggestions.push(
+            "Large files: bigger blocks mean fewer requests; compare with --block-size-mb 32"
+                .to_string(),
+        );
+    }
+    if size_per_file < SMALL_FILE {
+        suggestions.push(format!(
+            "Small files are bound by requests per second: compare with a higher concurrency, e.g. --concurrency {}",
+            default_concurrency() * 2
+        ));
+    }
+    suggestions
+}
+
+pub async fn execute(
+    uri: &str,
+    size_per_file: u64,
+    file_count: u32,
+    mode: BenchMode,
+    keep_data: bool,
+    block_size_mb: Option<f64>,
+    transfer_flags: TransferConfig,
+) -> Result<()> {
+    if !is_azure_uri(uri) {
+        return Err(anyhow!(
+            "bench needs a container to write to: az://<account>/<container>/[prefix]"
+        ));
+    }
+    let (_, container, _) = parse_azure_uri(uri)?;
+    if container.is_empty() {
+        return Err(anyhow!(
+            "bench needs a container to write to: az://<account>/<container>/[prefix]"
+        ));
+    }
+    if size_per_file == 0 {
+        return Err(anyhow!("--size-per-file must be more than 0 bytes"));
+    }
+
+    let mut azcopy = AzCopyClient::new();
+    azcopy.check_prerequisites().await?;
+
+    let mut args = vec![
+        format!("--size-per-file={}", azcopy_size(size_per_file)),
+        format!("--file-count={}", file_count),
+        format!("--mode={}", mode.as_azcopy_value()),
+    ];
+    if keep_data {
+        args.push("--delete-test-data=false".to_string());
+    }
+    if let Some(block_size) = block_size_mb {
+        args.push(format!("--block-size-mb={}", block_size));
+    }
+
+    let verb = match mode {
+        BenchMode::Upload => "upload to",
+        BenchMode::Download => "download from",
+    };
+    status!(
+        "{} Benchmarking {} × {} {} {}",
+        "⏱".cyan(),
+        file_count,
+        format_size(size_per_file),
+        verb,
+        uri.cyan()
+    );
+
+    let started = Instant::now();
+    let options = AzCopyOptions::new().with_transfer_flags(transfer_flags);
+    let summary = azcopy
+        .bench(&convert_az_uri_to_url(uri)?, &args, &options)
+        .await?;
+    let elapsed = started.elapsed();
+
+    let report = BenchReport {
+        mode,
+        files: summary.completed_count,
+        bytes: summary.bytes_transferred,
+        seconds: elapsed.as_secs_f64(),
+        megabits_per_second: megabits_per_second(summary.bytes_transferred, elapsed),
+        iops: summary.average_iops,
+        server_busy_percent: summary.server_busy_percent,
+        suggestions: suggestions(size_per_file, block_size_mb, summary.server_busy_percent),
+        advice: summary.performance_advice,
+    };
+
+    if output_format() == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    print_report(&report);
+    Ok(())
+}
+
+fn print_report(report: &BenchReport) {
+    println!(
+        "Throughput:   {:.1} Mbps ({}/s)",
+        report.megabits_per_second,
+        format_size((report.megabits_per_second * 125_000.0) as u64)
+    );
+    println!("IOPS:         {:.0}", report.iops);
+    println!(
+        "Transferred:  {} file(s), {} in {:.1}s",
+        report.files,
+        format_size(report.bytes),
+        report.seconds
+    );
+    println!("Server busy:  {:.1}%", report.server_busy_percent);
+
+    // azcopy's own advice was printed with the job summary
+    if report.advice.is_empty() && report.suggestions.is_empty() {
+        println!("{} No tuning advice", "✓".green());
+        return;
+    }
+    if report.suggestions.is_empty() {
+        return;
+    }
+    println!();
+    println!("Suggestions:");
+    for suggestion in &report.suggestions {
+        println!("  {} {}", "•".yellow(), suggestion);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_azcopy_size() {
+        assert_eq!(azcopy_size(256 * MIB), "256M");
+        assert_eq!(azcopy_size(2 * GIB), "2G");
+        assert_eq!(azcopy_size(1536 * MIB), "1536M");
+        assert_eq!(azcopy_size(1000), "1K");
+        assert_eq!(azcopy_size(1500 * 1024), "1500K");
+    }
+
+    #[test]
+    fn test_megabits_per_second() {
+        assert_eq!(
+            megabits_per_second(125_000_000, Duration::from_secs(10)),
+            100.0
+        );
+        assert_eq!(megabits_per_second(1, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert!(suggestions(256 * MIB, None, 0.0).is_empty());
+
+        let throttled = suggestions(256 * MIB, None, 12.0);
+        assert_eq!(throttled.len(), 1);
+        assert!(throttled[0].contains("--concurrency"));
+
+        assert_eq!(suggestions(4 * GIB, None, 0.0).len(), 1);
+        assert!(suggestions(4 * GIB, Some(32.0), 0.0).is_empty());
+        assert!(suggestions(64 * 1024, None, 0.0)[0].contains("requests per second"));
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+
+use crate::azure::{AzureClient, BlobItem};
+use crate::listing_cache::{self, CachedListing};
+use crate::utils::{format_count, parse_azure_uri};
+
+// ============================================================================
+// Cache - Keep the on-disk listings behind `--cached` up to date
+// ============================================================================
+
+/// List a container (or every container of an account) again and replace
+/// its cached listing
+pub async fn refresh(uri: &str) -> Result<()> {
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!("Caching needs a storage account: az://<account>/[container/][prefix]")
+    })?;
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+    client.check_prerequisites().await?;
+
+    let containers = if container.is_empty() {
+        client
+            .list_containers()
+            .await?
+            .into_iter()
+            .map(|container| container.name)
+            .collect()
+    } else {
+        vec![container]
+    };
+
+    for container in containers {
+        let blobs: Vec<_> = client
+            .list_blobs(&container, prefix.as_deref(), None)
+            .await?
+            .into_iter()
+            .filter_map(|item| match item {
+                BlobItem::Blob(blob) => Some(blob),
+                BlobItem::Prefix(_) => None,
+            })
+            .collect();
+        let count = blobs.len() as u64;
+        CachedListing::new(&account, &container, prefix.as_deref(), blobs).save()?;
+        status!(
+            "{} Cached {} blob(s) of az://{}/{}/{}",
+            "✓".green(),
+            format_count(count),
+            account,
+            container,
+            prefix.as_deref().unwrap_or("")
+        );
+    }
+    Ok(())
+}
+
+/// Forget the cached listings of a container, an account, or everything
+pub fn clear(uri: Option<&str>) -> Result<()> {
+    let (account, container) = match uri {
+        Some(uri) => {
+            let (account, container, _) = parse_azure_uri(uri)?;
+            let account = account.ok_or_else(|| {
+                anyhow!("Name the account whose listings to clear: az://<account>/[container/]")
+            })?;
+            (Some(account), (!container.is_empty()).then_some(container))
+        }
+        None => (None, None),
+    };
+    let removed = listing_cache::clear(account.as_deref(), container.as_deref())?;
+    status!("{} Removed {} cached listing(s)", "✓".green(), removed);
+    Ok(())
+}
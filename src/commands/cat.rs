@@ -1,21 +1,51 @@
 use anyhow::{anyhow, Result};
 use colored::*;
+use futures::StreamExt;
 use std::io::Write;
+use std::path::Path;
 
-use crate::azure::AzureClient;
-use crate::utils::{is_azure_uri, parse_azure_uri};
+use crate::azure::{AzureClient, BlobItem, CustomerKey};
+use crate::blob_reader::{BlobReader, ReadaheadConfig};
+use crate::compression::{Compression, Decoder};
+use crate::error::{storage_error_code, AzstError};
+use crate::utils::{is_azure_uri, parse_azure_uri, uri_base, Glob};
 
 pub struct CatOptions<'a> {
     pub urls: &'a [String],
     pub header: bool,
     pub range: Option<&'a str>,
+    /// Number of upcoming blobs to start downloading while one is written
+    pub prefetch: usize,
+    /// Concurrent ranged GETs per blob, instead of the default readahead
+    pub parallel_ranges: Option<u64>,
+    /// Key the blobs were encrypted with, if they use a customer-provided key
+    pub customer_key: Option<CustomerKey>,
+    /// Also decompress blobs named .gz or .zst that have no Content-Encoding
+    pub decompress: bool,
+    /// Write the stored bytes, even of compressed blobs
+    pub raw: bool,
 }
 
-pub async fn execute(urls: &[String], header: bool, range: Option<&str>) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    urls: &[String],
+    header: bool,
+    range: Option<&str>,
+    prefetch: usize,
+    parallel_ranges: Option<u64>,
+    customer_key: Option<CustomerKey>,
+    decompress: bool,
+    raw: bool,
+) -> Result<()> {
     let options = CatOptions {
         urls,
         header,
         range,
+        prefetch,
+        parallel_ranges,
+        customer_key,
+        decompress,
+        raw,
     };
     execute_with_options(options).await
 }
@@ -25,14 +55,45 @@ async fn execute_with_options(options: CatOptions<'_>) -> Result<()> {
         return Err(anyhow!("No URLs provided"));
     }
 
-    // Process each URL
-    for (idx, url) in options.urls.iter().enumerate() {
+    // Check every URL before any download starts
+    for url in options.urls {
         if !is_azure_uri(url) {
             return Err(anyhow!(
                 "Invalid URL '{}'. Must be an Azure URL (az://container/path)",
                 url
             ));
         }
+    }
+
+    let urls = expand_wildcards(options.urls).await?;
+
+    // Convert range format to Azure's format
+    let range = match options.range {
+        Some(range_str) => parse_range(range_str)?,
+        None => None,
+    };
+
+    let mut config = ReadaheadConfig::configured()?;
+    if let Some(ranges) = options.parallel_ranges {
+        config = config.with_parallel_ranges(ranges);
+    }
+
+    // Open upcoming blobs and start their readahead in the background, while
+    // the current one is written; `buffered` keeps them in output order
+    let mut blobs = futures::stream::iter(&urls)
+        .map(|url| {
+            tokio::spawn(open_blob(
+                url.clone(),
+                range,
+                config,
+                options.customer_key.clone(),
+            ))
+        })
+        .buffered(options.prefetch + 1);
+
+    let mut idx = 0;
+    while let Some(opened) = blobs.next().await {
+        let url = &urls[idx];
 
         // Print header if requested (and if multiple files, or if header flag is set)
         let should_print_header = options.header;
@@ -46,98 +107,212 @@ async fn execute_with_options(options: CatOptions<'_>) -> Result<()> {
             eprintln!("==> {} <==", url.cyan());
         }
 
-        // Download to stdout
-        if options.range.is_some() {
-            download_with_range(url, options.range).await?;
+        let blob = opened.map_err(|e| anyhow!("Prefetch task failed: {}", e))??;
+        // Byte ranges are of the stored bytes, which can't be decoded alone
+        let compression = if options.raw || range.is_some() {
+            None
         } else {
-            download_to_stdout(url).await?;
-        }
+            decompression(
+                blob.reader.content_encoding(),
+                &blob.blob,
+                options.decompress,
+            )
+        };
+        stream_to_stdout(blob, range, config, compression).await?;
+        idx += 1;
     }
 
     Ok(())
 }
 
-async fn download_to_stdout(display_url: &str) -> Result<()> {
+/// The blobs to write: each URL as given, or for one with wildcards, the
+/// blobs it matches in name order
+async fn expand_wildcards(urls: &[String]) -> Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for url in urls {
+        let (account, container, path) = parse_azure_uri(url)?;
+        let Some(glob) = path.as_deref().map(Glob::new).transpose()?.flatten() else {
+            expanded.push(url.clone());
+            continue;
+        };
+        let mut client = match account {
+            Some(account) => AzureClient::new().with_storage_account(&account),
+            None => AzureClient::new(),
+        };
+        let base = uri_base(url, path.as_deref());
+        let before = expanded.len();
+        client
+            .list_blobs_with_callback(
+                &container,
+                Some(glob.prefix()).filter(|prefix| !prefix.is_empty()),
+                None,
+                |items| {
+                    for item in items {
+                        if let BlobItem::Blob(blob) = item {
+                            if glob.selects(&blob.name, false) {
+                                expanded.push(format!("{}/{}", base, blob.name));
+                            }
+                        }
+                    }
+                    Ok(())
+                },
+            )
+            .await?;
+        if expanded.len() == before {
+            return Err(AzstError::NotFound(format!("No blobs match '{}'", url)).into());
+        }
+    }
+    Ok(expanded)
+}
+
+/// A blob opened for reading, with the names needed to explain failures
+struct OpenBlob {
+    reader: BlobReader,
+    container: String,
+    blob: String,
+}
+
+/// Open a blob and start fetching the part of it that will be written
+async fn open_blob(
+    display_url: String,
+    range: Option<(u64, Option<u64>)>,
+    config: ReadaheadConfig,
+    customer_key: Option<CustomerKey>,
+) -> Result<OpenBlob> {
     // Parse account, container and blob from the az:// URL
-    let (account_opt, container, blob_path_opt) = parse_azure_uri(display_url)?;
+    let (account_opt, container, blob_path_opt) = parse_azure_uri(&display_url)?;
 
     let blob =
         blob_path_opt.ok_or_else(|| anyhow!("No blob path specified in URL '{}'", display_url))?;
 
     // Create Azure client
-    let mut azure_client = AzureClient::new();
+    let mut azure_client = AzureClient::new().with_customer_key(customer_key);
     if let Some(account_name) = account_opt {
         azure_client = azure_client.with_storage_account(&account_name);
     }
     azure_client.check_prerequisites().await?;
 
-    // Download blob content
-    let content = azure_client
-        .download_blob(&container, &blob, None)
+    let mut reader = BlobReader::open(azure_client, &container, &blob, config)
         .await
-        .map_err(|e| {
-            // Provide user-friendly error messages
-            let err_str = e.to_string();
-            if err_str.contains("BlobNotFound") || err_str.contains("does not exist") {
-                anyhow!(
-                    "Blob '{}' not found in container '{}'. Please verify the blob path.",
-                    blob,
-                    container
-                )
-            } else if err_str.contains("ContainerNotFound") {
-                anyhow!(
-                    "Container '{}' does not exist. Please verify the container name.",
-                    container
-                )
-            } else {
-                e
-            }
-        })?;
-
-    // Write to stdout
-    std::io::stdout()
-        .write_all(&content)
-        .map_err(|e| anyhow!("Failed to write to stdout: {}", e))?;
+        .map_err(|e| friendly_download_error(e.into(), &container, &blob))?;
+    reader.start_readahead(range.map_or(0, |(start, _)| start));
 
-    Ok(())
+    Ok(OpenBlob {
+        reader,
+        container,
+        blob,
+    })
 }
 
-async fn download_with_range(display_url: &str, range: Option<&str>) -> Result<()> {
-    let range_str = range.ok_or_else(|| anyhow!("Range is required"))?;
+/// The algorithm to decompress a blob with: its Content-Encoding, or with
+/// `by_extension`, a .gz or .zst name
+fn decompression(
+    content_encoding: Option<&str>,
+    blob: &str,
+    by_extension: bool,
+) -> Option<Compression> {
+    content_encoding
+        .and_then(Compression::from_content_encoding)
+        .or_else(|| {
+            by_extension
+                .then(|| Compression::from_extension(Path::new(blob)))
+                .flatten()
+        })
+}
 
-    // Parse account, container and blob from the az:// URL
-    let (account_opt, container, blob_path_opt) = parse_azure_uri(display_url)?;
+/// Stream a blob (or a byte range of it) to stdout block by block,
+/// prefetching upcoming blocks while the current one is written, and
+/// decompressing it on the way if `compression` is set
+async fn stream_to_stdout(
+    opened: OpenBlob,
+    range: Option<(u64, Option<u64>)>,
+    config: ReadaheadConfig,
+    compression: Option<Compression>,
+) -> Result<()> {
+    let OpenBlob {
+        mut reader,
+        container,
+        blob,
+    } = opened;
 
-    let blob =
-        blob_path_opt.ok_or_else(|| anyhow!("No blob path specified in URL '{}'", display_url))?;
+    let (start, end) = match range {
+        Some((start, Some(end))) => (start, end.saturating_add(1).min(reader.size())),
+        Some((start, None)) => (start, reader.size()),
+        None => (0, reader.size()),
+    };
 
-    // Convert range format to Azure's format
-    let azure_range = parse_range(range_str)?;
+    let mut stdout = std::io::stdout();
+    let mut decoder = compression
+        .map(|compression| Decoder::new(compression, std::io::stdout()))
+        .transpose()?;
+    let write_error = |e: std::io::Error| {
+        if decoder_error(&e, compression.is_some()) {
+            anyhow!("Failed to decompress '{}': {}", blob, e)
+        } else {
+            anyhow!("Failed to write to stdout: {}", e)
+        }
+    };
+    let mut offset = start;
+    while offset < end {
+        let len = config.block_size.min(end - offset);
+        let chunk = reader
+            .read_at(offset, len)
+            .await
+            .map_err(|e| friendly_download_error(e.into(), &container, &blob))?;
+        if chunk.is_empty() {
+            break;
+        }
 
-    // Create Azure client
-    let mut azure_client = AzureClient::new();
-    if let Some(account_name) = account_opt {
-        azure_client = azure_client.with_storage_account(&account_name);
+        match &mut decoder {
+            Some(decoder) => decoder.write_all(&chunk),
+            None => stdout.write_all(&chunk),
+        }
+        .map_err(write_error)?;
+        offset += chunk.len() as u64;
     }
-    azure_client.check_prerequisites().await?;
-
-    // Download blob content with range
-    let content = if let Some((start, end)) = azure_range {
-        azure_client
-            .download_blob(&container, &blob, Some((start, end.unwrap_or(u64::MAX))))
-            .await?
-    } else {
-        azure_client.download_blob(&container, &blob, None).await?
-    };
 
-    // Write to stdout
-    std::io::stdout()
-        .write_all(&content)
-        .map_err(|e| anyhow!("Failed to write to stdout: {}", e))?;
+    if let Some(decoder) = decoder {
+        decoder
+            .finish()
+            .map_err(|e| match e.downcast::<std::io::Error>() {
+                Ok(e) => write_error(e),
+                Err(e) => e,
+            })?;
+    }
+    stdout.flush().map_err(write_error)?;
 
     Ok(())
 }
 
+/// Whether a write failed because the data didn't decompress, rather than
+/// because stdout went away
+fn decoder_error(e: &std::io::Error, decompressing: bool) -> bool {
+    decompressing && e.kind() != std::io::ErrorKind::BrokenPipe
+}
+
+/// Provide user-friendly error messages for common download failures
+fn friendly_download_error(e: anyhow::Error, container: &str, blob: &str) -> anyhow::Error {
+    let err_str = format!("{:#}", e);
+    if storage_error_code(&e) == Some("ContainerNotFound") {
+        AzstError::NotFound(format!(
+            "Container '{}' does not exist. Please verify the container name.",
+            container
+        ))
+        .into()
+    } else if err_str.contains("BlobNotFound")
+        || err_str.contains("does not exist")
+        || err_str.contains("404")
+    {
+        AzstError::NotFound(format!(
+            "Blob '{}' not found in container '{}'. Please verify the blob path.",
+            blob, container
+        ))
+        .into()
+    } else {
+        e
+    }
+}
+
 /// Parse range string in gsutil format and convert to (start, end) bytes
 /// Formats: "start-end", "start-", "-numbytes"
 fn parse_range(range: &str) -> Result<Option<(u64, Option<u64>)>> {
@@ -173,3 +348,26 @@ fn parse_range(range: &str) -> Result<Option<(u64, Option<u64>)>> {
 
     Ok(Some((start, end)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::error::ErrorKind;
+    use azure_core::StatusCode;
+
+    #[test]
+    fn test_friendly_download_error_names_missing_container() {
+        let not_found = |error_code: Option<&str>| {
+            anyhow::Error::new(azure_core::error::Error::message(
+                ErrorKind::HttpResponse {
+                    status: StatusCode::NotFound,
+                    error_code: error_code.map(str::to_string),
+                },
+                "404 Not Found",
+            ))
+        };
+        let message = |e| format!("{:#}", friendly_download_error(e, "data", "a.csv"));
+        assert!(message(not_found(Some("ContainerNotFound"))).starts_with("Container 'data'"));
+        assert!(message(not_found(Some("BlobNotFound"))).starts_with("Blob 'a.csv'"));
+    }
+}
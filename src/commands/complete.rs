@@ -0,0 +1,257 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::azure::{AzureClient, BlobItem, StorageAccountInfo};
+
+/// Give up on a listing after this long so a TAB press never hangs the shell
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long a cached listing is reused before Azure is asked again
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The account list changes rarely and takes a management API call to fetch,
+/// so it is kept longer
+const ACCOUNTS_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Where completions of `az://` are cached
+const ACCOUNTS_BASE: &str = "az://";
+
+/// The listing needed to complete a partial URI
+#[derive(Debug, PartialEq)]
+enum Level<'a> {
+    /// `az://acc` - storage account names
+    Accounts,
+    /// `az://acct/con` - containers in an account
+    Containers { account: &'a str },
+    /// `az://acct/cont/dir/fi` - blobs and prefixes directly under `dir`
+    Blobs {
+        account: &'a str,
+        container: &'a str,
+        dir: &'a str,
+    },
+}
+
+/// A cached listing, stored as JSON in the user's cache directory
+#[derive(Serialize, Deserialize)]
+struct CachedListing {
+    created: u64,
+    entries: Vec<String>,
+}
+
+/// Print the completions of a partial az:// URI, one per line
+///
+/// Used by shell completion scripts. Failures and timeouts print nothing, so a
+/// missing login or slow network only means no suggestions.
+pub async fn execute(partial: &str) -> Result<()> {
+    for candidate in completions(partial).await {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
+
+/// The completions of a partial az:// URI, empty when Azure can't be asked
+pub async fn completions(partial: &str) -> Vec<String> {
+    let partial = if partial.is_empty() {
+        ACCOUNTS_BASE
+    } else {
+        partial
+    };
+    let Some((level, base)) = split_partial(partial) else {
+        return Vec::new();
+    };
+
+    let ttl = match level {
+        Level::Accounts => ACCOUNTS_CACHE_TTL,
+        _ => CACHE_TTL,
+    };
+    let entries = match load_cache(base, ttl) {
+        Some(entries) => entries,
+        None => match tokio::time::timeout(COMPLETION_TIMEOUT, list(&level)).await {
+            Ok(Ok(entries)) => {
+                save_cache(base, &entries);
+                entries
+            }
+            _ => return Vec::new(),
+        },
+    };
+    candidates(partial, base, &entries)
+}
+
+/// Work out what to list for `partial`, and the part of it that is fixed
+/// (everything up to the last '/')
+fn split_partial(partial: &str) -> Option<(Level<'_>, &str)> {
+    let rest = partial.strip_prefix("az://")?;
+    let base_len = partial.rfind('/').map(|pos| pos + 1)?;
+    let base = &partial[..base_len];
+
+    let level = match rest.split_once('/') {
+        None => Level::Accounts,
+        Some((account, rest)) => match rest.split_once('/') {
+            None => Level::Containers { account },
+            Some((container, path)) => {
+                let dir = &path[..path.rfind('/').map(|pos| pos + 1).unwrap_or(0)];
+                Level::Blobs {
+                    account,
+                    container,
+                    dir,
+                }
+            }
+        },
+    };
+
+    Some((level, base))
+}
+
+/// Names directly under the level: accounts and containers end in '/',
+/// as do virtual directories
+async fn list(level: &Level<'_>) -> Result<Vec<String>> {
+    match level {
+        Level::Accounts => {
+            let mut client = AzureClient::new();
+            Ok(account_entries(&client.list_storage_accounts().await?))
+        }
+        Level::Containers { account } => {
+            let mut client = AzureClient::new().with_storage_account(account);
+            let containers = client.list_containers().await?;
+            Ok(containers
+                .into_iter()
+                .map(|container| format!("{}/", container.name))
+                .collect())
+        }
+        Level::Blobs {
+            account,
+            container,
+            dir,
+        } => {
+            let mut client = AzureClient::new().with_storage_account(account);
+            let prefix = (!dir.is_empty()).then_some(*dir);
+            let items = client.list_blobs(container, prefix, Some("/")).await?;
+            Ok(items
+                .into_iter()
+                .map(|item| match item {
+                    BlobItem::Blob(blob) => blob.name,
+                    BlobItem::Prefix(name) => name,
+                })
+                .filter_map(|name| name.strip_prefix(dir).map(str::to_string))
+                .filter(|name| !name.is_empty())
+                .collect())
+        }
+    }
+}
+
+fn account_entries(accounts: &[StorageAccountInfo]) -> Vec<String> {
+    accounts
+        .iter()
+        .map(|account| format!("{}/", account.name))
+        .collect()
+}
+
+/// Keep an account list fetched by another command for completing `az://`
+pub fn cache_accounts(accounts: &[StorageAccountInfo]) {
+    save_cache(ACCOUNTS_BASE, &account_entries(accounts));
+}
+
+/// Full URIs for the entries that extend what has been typed
+fn candidates(partial: &str, base: &str, entries: &[String]) -> Vec<String> {
+    let typed = &partial[base.len()..];
+    let mut matches: Vec<String> = entries
+        .iter()
+        .filter(|entry| entry.starts_with(typed))
+        .map(|entry| format!("{}{}", base, entry))
+        .collect();
+    matches.sort();
+    matches
+}
+
+fn cache_path(base: &str) -> Option<PathBuf> {
+    let key = format!("{:x}", md5::compute(base.as_bytes()));
+    dirs::cache_dir().map(|dir| dir.join("azst").join("completions").join(key))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The cached listing for `base`, if it is younger than `ttl`
+fn load_cache(base: &str, ttl: Duration) -> Option<Vec<String>> {
+    let content = std::fs::read_to_string(cache_path(base)?).ok()?;
+    let cached: CachedListing = serde_json::from_str(&content).ok()?;
+    (now().saturating_sub(cached.created) < ttl.as_secs()).then_some(cached.entries)
+}
+
+/// Best effort: a completion that can't write its cache still completes
+fn save_cache(base: &str, entries: &[String]) {
+    let write = || -> Result<()> {
+        let path = cache_path(base).ok_or_else(|| anyhow!("No cache directory"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let cached = CachedListing {
+            created: now(),
+            entries: entries.to_vec(),
+        };
+        std::fs::write(path, serde_json::to_string(&cached)?)?;
+        Ok(())
+    };
+    let _ = write();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_partial_levels() {
+        assert_eq!(split_partial("az://"), Some((Level::Accounts, "az://")));
+        assert_eq!(split_partial("az://my"), Some((Level::Accounts, "az://")));
+        assert_eq!(
+            split_partial("az://acct/da"),
+            Some((Level::Containers { account: "acct" }, "az://acct/"))
+        );
+        assert_eq!(
+            split_partial("az://acct/data/"),
+            Some((
+                Level::Blobs {
+                    account: "acct",
+                    container: "data",
+                    dir: ""
+                },
+                "az://acct/data/"
+            ))
+        );
+        assert_eq!(
+            split_partial("az://acct/data/2024/jan/fi"),
+            Some((
+                Level::Blobs {
+                    account: "acct",
+                    container: "data",
+                    dir: "2024/jan/"
+                },
+                "az://acct/data/2024/jan/"
+            ))
+        );
+        assert_eq!(split_partial("/local/path"), None);
+    }
+
+    #[test]
+    fn test_candidates_filters_and_sorts() {
+        let entries = vec![
+            "logs/".to_string(),
+            "data.csv".to_string(),
+            "data/".to_string(),
+        ];
+        assert_eq!(
+            candidates("az://acct/c/da", "az://acct/c/", &entries),
+            vec!["az://acct/c/data.csv", "az://acct/c/data/"]
+        );
+        assert_eq!(
+            candidates("az://acct/c/", "az://acct/c/", &entries).len(),
+            3
+        );
+    }
+}
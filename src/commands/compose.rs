@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::audit_log;
+use crate::azure::{AzureClient, BlobItem, UrlSigner};
+use crate::utils::{format_size, parse_azure_uri, Glob};
+
+/// How long the signed URLs used as copy sources stay valid
+const SOURCE_URL_VALIDITY: Duration = Duration::from_secs(3600);
+
+/// A resolved source blob
+struct Source {
+    account: String,
+    container: String,
+    blob: String,
+    size: u64,
+    content_type: Option<String>,
+}
+
+pub async fn execute(
+    sources: &[String],
+    destination: &str,
+    content_type: Option<&str>,
+) -> Result<()> {
+    let (dest_account, dest_container, dest_blob) = parse_azure_uri(destination)?;
+    let dest_account = dest_account.ok_or_else(|| {
+        anyhow!("Destination must include a storage account: az://<account>/<container>/<blob>")
+    })?;
+    let dest_blob = dest_blob
+        .filter(|b| !b.ends_with('/'))
+        .ok_or_else(|| anyhow!("Destination '{}' must name a blob", destination))?;
+
+    // Expand wildcards and look up sizes, keeping the order given by the user
+    let mut resolved = Vec::new();
+    for uri in sources {
+        resolved.extend(resolve_sources(uri).await?);
+    }
+    if resolved.is_empty() {
+        return Err(anyhow!("No source blobs matched"));
+    }
+
+    let total: u64 = resolved.iter().map(|s| s.size).sum();
+    status!(
+        "{} Composing {} blob(s) ({}) into {}",
+        "→".green(),
+        resolved.len(),
+        format_size(total),
+        destination.cyan()
+    );
+
+    // Sources are read by the service, so each needs a signed URL; one signer per account
+    let mut signers: HashMap<String, UrlSigner> = HashMap::new();
+    let mut urls = Vec::new();
+    for source in &resolved {
+        if !signers.contains_key(&source.account) {
+            let signer = AzureClient::new()
+                .with_storage_account(&source.account)
+                .url_signer(SOURCE_URL_VALIDITY)
+                .await?;
+            signers.insert(source.account.clone(), signer);
+        }
+        let url = signers[&source.account].sign(&source.container, &source.blob)?;
+        urls.push((url, source.size));
+    }
+
+    let content_type = content_type
+        .map(str::to_string)
+        .or_else(|| resolved[0].content_type.clone());
+
+    let mut client = AzureClient::new().with_storage_account(&dest_account);
+    client
+        .compose_blob(&dest_container, &dest_blob, &urls, content_type.as_deref())
+        .await?;
+
+    audit_log::record(1, total);
+    status!("{} Composed {}", "✓".green(), destination.cyan());
+    Ok(())
+}
+
+/// Resolve a source URI to one blob, or to all matching blobs (sorted by name)
+/// when it contains wildcards
+async fn resolve_sources(uri: &str) -> Result<Vec<Source>> {
+    let (account, container, path) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!(
+            "Source '{}' must include a storage account: az://<account>/<container>/<blob>",
+            uri
+        )
+    })?;
+    let path = path.ok_or_else(|| anyhow!("Source '{}' must name a blob", uri))?;
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+
+    let Some(glob) = Glob::new(&path)? else {
+        let info = client.get_blob_properties(&container, &path).await?;
+        return Ok(vec![Source {
+            account,
+            container,
+            blob: path,
+            size: info.properties.content_length,
+            content_type: info.properties.content_type,
+        }]);
+    };
+
+    let list_prefix = Some(glob.prefix()).filter(|prefix| !prefix.is_empty());
+    let mut matches = Vec::new();
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    if glob.selects(&blob.name, false) {
+                        matches.push(blob);
+                    }
+                }
+            }
+            Ok(())
+        })
+        .await?;
+    matches.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if matches.is_empty() {
+        return Err(anyhow!("No blobs match '{}'", uri));
+    }
+
+    Ok(matches
+        .into_iter()
+        .map(|blob| Source {
+            account: account.clone(),
+            container: container.clone(),
+            blob: blob.name,
+            size: blob.properties.content_length,
+            content_type: blob.properties.content_type,
+        })
+        .collect())
+}
@@ -1,9 +1,57 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use azure_core::request_options::LeaseId;
 use colored::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
-use crate::azure::{convert_az_uri_to_url, AzCopyClient, AzCopyOptions};
-use crate::utils::{get_filename, get_parent_dir, is_azure_uri, is_directory, path_exists};
+use crate::archive::{self, archive_compression};
+use crate::async_copy;
+use crate::audit_log;
+use crate::azcopy_output::{AzCopyOperation, FailedTransfer, FailureReport, JobSummary};
+use crate::azure::{
+    convert_az_uri_to_url, AzCopyClient, AzCopyOptions, AzureClient, BlobHeaders, BlobItem,
+    BlobType, CustomerKey, Md5Check, OverwritePolicy, PathFilters, PAGE_SIZE,
+};
+use crate::bandwidth::BandwidthSchedule;
+use crate::cancel;
+use crate::commands::lease::stored_lease;
+use crate::compression::{
+    decompress_in_place, has_extension, CompressedCopy, Compression, COMPRESSION_METADATA_KEY,
+};
+use crate::config::{Config, TransferConfig};
+use crate::content_type::{configured_content_type, detect_content_type};
+use crate::dir_markers;
+use crate::dry_run::{DryRunPlan, PlannedTransfer};
+use crate::error::AzstError;
+use crate::manifest::{batches, CopyManifest};
+use crate::pack::{self, PACK_DIR};
+use crate::prompt::{self, Prompter, TtyPrompter};
+use crate::quota::{self, Transfer};
+use crate::region;
+use crate::symlinks::SymlinkPolicy;
+use crate::sync_state::{join_location, snapshot, snapshot_with, Snapshot};
+use crate::tuning::{self, TuningMode};
+use crate::url_copy::{copy_from_urls, is_http_url};
+use crate::utils::{
+    contains_wildcard, directory_prefix, file_md5, format_size, get_filename, get_parent_dir,
+    is_azure_uri, is_directory, matches_any_pattern, parse_azure_uri, path_exists, uri_base, Glob,
+};
+
+/// How to copy without being asked about each existing file
+const OVERWRITE_BYPASS: &str = "choose another --overwrite policy or pass --no-clobber";
+
+/// What to do when blob names differ only by case and the download target
+/// can't tell them apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CaseConflictPolicy {
+    /// Refuse to download and list the colliding names
+    Abort,
+    /// Download colliding blobs under suffixed names (e.g. Data~2.csv)
+    Rename,
+    /// Download anyway; later files overwrite earlier ones
+    Ignore,
+}
 
 pub struct CopyOptions<'a> {
     pub source: &'a str,
@@ -11,60 +59,794 @@ pub struct CopyOptions<'a> {
     pub recursive: bool,
     pub dry_run: bool,
     pub cap_mbps: Option<f64>,
+    pub bandwidth_schedule: Option<BandwidthSchedule>,
     pub block_size_mb: Option<f64>,
     pub put_md5: bool,
     pub include_pattern: Option<&'a str>,
     pub exclude_pattern: Option<&'a str>,
+    pub filters: PathFilters,
+    pub overwrite: OverwritePolicy,
+    pub preserve: bool,
+    pub headers: BlobHeaders,
+    pub guess_mime_type: bool,
+    pub check_md5: Option<Md5Check>,
+    pub atomic: bool,
+    pub case_conflict: Option<CaseConflictPolicy>,
+    pub preserve_empty_dirs: bool,
+    pub as_subdir: bool,
+    pub flatten: bool,
+    pub max_upload_bytes: Option<u64>,
+    pub compress: Option<Compression>,
+    /// Gzip only the files with these extensions (`-z`)
+    pub gzip_extensions: Vec<String>,
+    /// Decompress downloads by their Content-Encoding; None does so where
+    /// possible, without failing where it isn't
+    pub decompress: Option<bool>,
+    pub allow_cross_region: bool,
+    pub failures_out: Option<&'a Path>,
+    /// Lease to present when overwriting a leased blob
+    pub lease_id: Option<LeaseId>,
+    /// Key to encrypt uploads and decrypt downloads with (CPK)
+    pub customer_key: Option<CustomerKey>,
+    /// Upload a local directory as a single tar blob
+    pub tar: bool,
+    /// Extract a tar blob into a local directory
+    pub untar: bool,
+    /// Upload a local directory with files smaller than this many bytes packed
+    pub pack: Option<u64>,
+    /// Extract the packed files of a packed upload as it downloads
+    pub unpack: bool,
+    /// Record copied files here and skip the ones it lists on reruns
+    pub manifest: Option<&'a Path>,
+    /// Wait for server-side copies from URLs that run in the background
+    pub poll: bool,
+    /// Start server-side copies between Azure locations and leave them running
+    pub async_copy: bool,
+    /// Kind of blob uploads and copies between Azure locations create
+    pub blob_type: Option<BlobType>,
+    /// What uploads do with symbolic links under a local source
+    pub symlinks: SymlinkPolicy,
+    /// How transfers are tuned for the account they write to
+    pub tuning: TuningMode,
+    /// `--concurrency`, `--buffer-gb` and `--scan-concurrency`
+    pub transfer_flags: TransferConfig,
+    /// Asks before replacing local files under `--overwrite prompt`
+    pub prompter: &'a dyn Prompter,
 }
 
-#[allow(clippy::too_many_arguments)]
-pub async fn execute(
-    source: &str,
-    destination: &str,
-    recursive: bool,
-    dry_run: bool,
-    cap_mbps: Option<f64>,
-    block_size_mb: Option<f64>,
-    put_md5: bool,
-    include_pattern: Option<&str>,
-    exclude_pattern: Option<&str>,
-) -> Result<()> {
-    let options = CopyOptions {
-        source,
-        destination,
-        recursive,
-        dry_run,
-        cap_mbps,
-        block_size_mb,
-        put_md5,
-        include_pattern,
-        exclude_pattern,
-    };
-    execute_with_options(options).await
+impl Default for CopyOptions<'_> {
+    /// The defaults of `azst cp`
+    fn default() -> Self {
+        Self {
+            source: "",
+            destination: "",
+            recursive: false,
+            dry_run: false,
+            cap_mbps: None,
+            bandwidth_schedule: None,
+            block_size_mb: None,
+            put_md5: false,
+            include_pattern: None,
+            exclude_pattern: None,
+            filters: PathFilters::default(),
+            overwrite: OverwritePolicy::default(),
+            preserve: false,
+            headers: BlobHeaders::default(),
+            guess_mime_type: true,
+            check_md5: None,
+            atomic: true,
+            case_conflict: None,
+            preserve_empty_dirs: false,
+            as_subdir: false,
+            flatten: false,
+            max_upload_bytes: None,
+            compress: None,
+            gzip_extensions: Vec::new(),
+            decompress: None,
+            allow_cross_region: false,
+            failures_out: None,
+            lease_id: None,
+            customer_key: None,
+            tar: false,
+            untar: false,
+            pack: None,
+            unpack: false,
+            manifest: None,
+            poll: false,
+            async_copy: false,
+            blob_type: None,
+            symlinks: SymlinkPolicy::Skip,
+            tuning: TuningMode::Auto,
+            transfer_flags: TransferConfig::default(),
+            prompter: &TtyPrompter,
+        }
+    }
 }
 
-async fn execute_with_options(options: CopyOptions<'_>) -> Result<()> {
+pub async fn execute(options: CopyOptions<'_>) -> Result<()> {
+    copy(options).await?;
+    Ok(())
+}
+
+/// Copy with the given options, returning the transfers that failed or that
+/// azcopy skipped, so callers such as `mv` know which sources weren't copied
+pub async fn copy(mut options: CopyOptions<'_>) -> Result<Vec<FailedTransfer>, AzstError> {
     let source = options.source;
     let destination = options.destination;
     let source_is_azure = is_azure_uri(source);
     let dest_is_azure = is_azure_uri(destination);
 
+    if !dest_is_azure && !options.headers.is_empty() {
+        return Err(anyhow!(
+            "Content headers, metadata and tier can only be set when the destination is Azure"
+        )
+        .into());
+    }
+
+    // azcopy would ask on the stdout its JSON progress is read from, so only
+    // local copies, which azst makes itself, can ask before overwriting
+    if options.overwrite == OverwritePolicy::Prompt && (source_is_azure || dest_is_azure) {
+        return Err(anyhow!(
+            "--overwrite prompt only applies to local copies; use --no-clobber or --overwrite if-newer with Azure"
+        ).into());
+    }
+
+    // Web sources are copied by the service, without passing through here
+    if source == "-" || is_http_url(source) {
+        if !dest_is_azure {
+            return Err(anyhow!("URLs can only be copied into Azure").into());
+        }
+        if options.customer_key.is_some() || !options.headers.is_empty() {
+            return Err(anyhow!(
+                "Copies from URLs can't set content headers, metadata, tier or --customer-key"
+            )
+            .into());
+        }
+        if options.blob_type.is_some() {
+            return Err(anyhow!("Copies from URLs make block blobs; drop --blob-type").into());
+        }
+        let failures = copy_from_urls(source, destination, options.poll, options.dry_run).await?;
+        if let Some(path) = options.failures_out {
+            save_failures(path, AzCopyOperation::Copy, failures.clone())?;
+        }
+        return Ok(failures);
+    }
+
+    if options.async_copy {
+        if !source_is_azure || !dest_is_azure {
+            return Err(anyhow!("--async only applies to copies between Azure locations").into());
+        }
+        if options.customer_key.is_some() || !options.headers.is_empty() {
+            return Err(anyhow!(
+                "--async copies can't set content headers, metadata, tier or --customer-key"
+            )
+            .into());
+        }
+        if options.blob_type.is_some() {
+            return Err(
+                anyhow!("--async copies keep the source's blob type; drop --blob-type").into(),
+            );
+        }
+        let failures = async_copy::start_copies(
+            source,
+            destination,
+            options.recursive,
+            options.as_subdir,
+            options.dry_run,
+        )
+        .await?;
+        if let Some(path) = options.failures_out {
+            save_failures(path, AzCopyOperation::Copy, failures.clone())?;
+        }
+        return Ok(failures);
+    }
+
+    if options.symlinks != SymlinkPolicy::Skip && source_is_azure {
+        return Err(
+            anyhow!("--follow-symlinks and --preserve-symlinks apply to local sources").into(),
+        );
+    }
+    if options.blob_type.is_some() && !dest_is_azure {
+        return Err(anyhow!(
+            "--blob-type only applies to uploads and copies between Azure locations"
+        )
+        .into());
+    }
+    if matches!(options.blob_type, Some(BlobType::Page | BlobType::Append)) {
+        if options.headers.tier.is_some() {
+            return Err(anyhow!("--tier only applies to block blobs").into());
+        }
+        if options.tar
+            || options.pack.is_some()
+            || options.compress.is_some()
+            || !options.gzip_extensions.is_empty()
+        {
+            return Err(anyhow!(
+                "--tar, --pack and compressed uploads make block blobs; drop --blob-type"
+            )
+            .into());
+        }
+    }
+    if options.compress.is_some() && (source_is_azure || !dest_is_azure) {
+        return Err(anyhow!("--compress only applies to uploads").into());
+    }
+    if !options.gzip_extensions.is_empty() && (source_is_azure || !dest_is_azure) {
+        return Err(anyhow!("--gzip-local only applies to uploads").into());
+    }
+    // These uploads send archives or staged copies, whose mode and times
+    // aren't the source files'
+    if options.preserve
+        && (options.tar
+            || options.pack.is_some()
+            || options.compress.is_some()
+            || !options.gzip_extensions.is_empty())
+    {
+        return Err(anyhow!(
+            "--preserve can't be combined with --tar, --pack, --compress or --gzip-local"
+        )
+        .into());
+    }
+    if options.decompress == Some(true) && (!source_is_azure || dest_is_azure) {
+        return Err(anyhow!("--decompress only applies to downloads").into());
+    }
+    if options.customer_key.is_some() && !source_is_azure && !dest_is_azure {
+        return Err(anyhow!("--customer-key only applies to copies to or from Azure").into());
+    }
+    if options.decompress == Some(true) && contains_wildcard(source) {
+        return Err(anyhow!("--decompress can't be combined with wildcards").into());
+    }
+    if options.overwrite == OverwritePolicy::Prompt && !options.dry_run {
+        prompt::require_interactive(OVERWRITE_BYPASS)?;
+    }
+    if options.flatten && source_is_azure == dest_is_azure {
+        return Err(anyhow!("--flatten only applies to uploads and downloads").into());
+    }
+    if options.flatten && contains_wildcard(source) {
+        return Err(anyhow!("--flatten can't be combined with wildcards").into());
+    }
+    if options.flatten && source_is_azure && options.overwrite != OverwritePolicy::Always {
+        return Err(anyhow!(
+            "Flattened downloads always replace existing files; drop --no-clobber and --overwrite"
+        )
+        .into());
+    }
+
+    // A manifest lists files by their path under the source directory
+    if let Some(manifest) = options.manifest {
+        if !options.recursive || contains_wildcard(source) {
+            return Err(anyhow!(
+                "--manifest needs a recursive copy of a directory or prefix, without wildcards"
+            )
+            .into());
+        }
+        // Refuse another copy's manifest before anything is transferred
+        CopyManifest::load(manifest, source, destination)?;
+    }
+
+    if options.tar || options.untar {
+        copy_archive(&options).await?;
+        return Ok(Vec::new());
+    }
+    if let Some(threshold) = options.pack {
+        copy_packed(&options, threshold).await?;
+        return Ok(Vec::new());
+    }
+    if options.unpack && (!source_is_azure || dest_is_azure) {
+        return Err(anyhow!("--unpack only applies to downloads").into());
+    }
+    if options.unpack && contains_wildcard(source) {
+        return Err(anyhow!("--unpack can't be combined with wildcards").into());
+    }
+
+    // A single file is either compressed or not
+    if !options.gzip_extensions.is_empty() && !is_directory(source) {
+        if has_extension(Path::new(source), &options.gzip_extensions) {
+            options.compress = Some(Compression::Gzip);
+        }
+        options.gzip_extensions.clear();
+    }
+    // Downloads are restored to what was uploaded unless asked otherwise;
+    // wildcard and flattened downloads can't be matched back to their blobs,
+    // nor can files a manifest skips be told apart from ones just downloaded
+    let decompress = options.decompress.unwrap_or(
+        source_is_azure
+            && !dest_is_azure
+            && !contains_wildcard(source)
+            && !options.flatten
+            && options.manifest.is_none(),
+    );
+    options.decompress = Some(decompress);
+
+    if let Some((target, lease_id)) = upload_lease(&options)? {
+        if options.preserve {
+            return Err(anyhow!("--preserve doesn't apply to uploads onto a leased blob").into());
+        }
+        upload_leased_file(&options, &target, lease_id).await?;
+        return Ok(Vec::new());
+    }
+    if options.blob_type == Some(BlobType::Page) && !source_is_azure {
+        if let Some(target) = padded_page_target(&options)? {
+            if options.preserve {
+                return Err(anyhow!(
+                    "--preserve doesn't apply to page blobs padded to whole pages"
+                )
+                .into());
+            }
+            upload_padded_page_blob(&options, &target).await?;
+            return Ok(Vec::new());
+        }
+        check_page_alignment(&options).await?;
+    }
+
     match (source_is_azure, dest_is_azure) {
         (false, true) | (true, false) | (true, true) => {
             // Any Azure operation - use AzCopy for performance
             let mut azcopy = AzCopyClient::new();
             azcopy.check_prerequisites().await?;
-            copy_with_azcopy(&mut azcopy, options).await
+            Ok(copy_with_azcopy(&mut azcopy, options).await?)
+        }
+        (false, false) if options.dry_run => {
+            let planned = plan_local_copy(source, destination, options.recursive).await?;
+            DryRunPlan::new(planned).print();
+            Ok(Vec::new())
         }
         (false, false) => {
             // Local to Local - use regular file copy
-            copy_local_files(source, destination, options.recursive).await
+            copy_local_files(
+                source,
+                destination,
+                options.recursive,
+                options.overwrite,
+                options.preserve,
+                options.prompter,
+            )
+            .await?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// The blob and lease to upload under, when a single file is uploaded onto a
+/// blob leased with --lease-id or with `azst lease acquire`
+fn upload_lease(options: &CopyOptions) -> Result<Option<(String, LeaseId)>> {
+    let (source, destination) = (options.source, options.destination);
+    let single_upload = !is_azure_uri(source)
+        && is_azure_uri(destination)
+        && !options.recursive
+        && !contains_wildcard(source)
+        && !is_directory(source);
+    if !single_upload {
+        if options.lease_id.is_some() {
+            return Err(anyhow!(
+                "--lease-id only applies to uploading a single file"
+            ));
+        }
+        return Ok(None);
+    }
+
+    let target = if destination.ends_with('/') {
+        format!("{}{}", destination, get_filename(source))
+    } else {
+        destination.to_string()
+    };
+    Ok(options
+        .lease_id
+        .or_else(|| stored_lease(&target))
+        .map(|lease_id| (target, lease_id)))
+}
+
+/// Upload one file onto a leased blob through the SDK, as azcopy has no way
+/// to present a lease
+async fn upload_leased_file(
+    options: &CopyOptions<'_>,
+    target: &str,
+    lease_id: LeaseId,
+) -> Result<()> {
+    let source = options.source;
+    let headers = &options.headers;
+    if headers.cache_control.is_some()
+        || headers.content_encoding.is_some()
+        || !headers.metadata.is_empty()
+        || headers.tier.is_some()
+        || options.compress.is_some()
+        || options.put_md5
+        || matches!(options.blob_type, Some(BlobType::Page | BlobType::Append))
+    {
+        return Err(anyhow!(
+            "Only --content-type can be set when writing to a leased blob"
+        ));
+    }
+    if options.overwrite != OverwritePolicy::Always {
+        return Err(anyhow!(
+            "A leased blob always exists; drop --no-clobber and --overwrite to replace it"
+        ));
+    }
+
+    let size = fs::metadata(source)
+        .await
+        .with_context(|| format!("Source path '{}' does not exist", source))?
+        .len();
+    if let Some(max) = options.max_upload_bytes.filter(|max| size > *max) {
+        return Err(anyhow!(
+            "Uploading {} would exceed --max-upload-bytes ({})",
+            format_size(size),
+            format_size(max)
+        ));
+    }
+    if options.dry_run {
+        DryRunPlan::new(vec![PlannedTransfer::transfer(source, target, Some(size))]).print();
+        return Ok(());
+    }
+
+    let content_type = match &headers.content_type {
+        Some(content_type) => Some(content_type.clone()),
+        None if options.guess_mime_type => {
+            detect_content_type(source, &Config::load()?.content_types)
         }
+        None => None,
+    };
+    let (account, container, blob) = parse_azure_uri(target)?;
+    let blob = blob.ok_or_else(|| anyhow!("Destination '{}' must name a blob", target))?;
+    let mut client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    }
+    .with_customer_key(options.customer_key.clone());
+
+    status!(
+        "{} Uploading {} to leased blob {}",
+        "→".green(),
+        source,
+        target.cyan()
+    );
+    let uploaded = client
+        .upload_file(
+            &container,
+            &blob,
+            Path::new(source),
+            content_type.as_deref(),
+            Some(lease_id),
+        )
+        .await?;
+    audit_log::record(1, uploaded);
+    status!(
+        "{} Uploaded {} ({})",
+        "✓".green(),
+        target.cyan(),
+        format_size(uploaded)
+    );
+    Ok(())
+}
+
+/// The blob a single file uploaded as a page blob goes to, when the file
+/// isn't a whole number of pages and azst pads it, as azcopy won't
+fn padded_page_target(options: &CopyOptions) -> Result<Option<String>> {
+    let source = options.source;
+    if contains_wildcard(source) || is_directory(source) {
+        return Ok(None);
+    }
+    let size = std::fs::metadata(source)
+        .with_context(|| format!("Source path '{}' does not exist", source))?
+        .len();
+    if size % PAGE_SIZE == 0 {
+        return Ok(None);
+    }
+    // A VHD ends with a footer that must stay at the very end
+    if has_extension(Path::new(source), &["vhd".to_string(), "vhdx".to_string()]) {
+        return Err(anyhow!(
+            "'{}' is {} bytes, not a whole number of {}-byte pages; padding would move its \
+             footer, so resize the disk image before uploading it",
+            source,
+            size,
+            PAGE_SIZE
+        ));
+    }
+
+    let headers = &options.headers;
+    if headers.cache_control.is_some() || options.put_md5 || options.customer_key.is_some() {
+        return Err(anyhow!(
+            "'{}' is padded to a whole number of pages by azst, which can't set \
+             --cache-control, --put-md5 or --customer-key",
+            source
+        ));
+    }
+    if options.overwrite != OverwritePolicy::Always {
+        return Err(anyhow!(
+            "Padded page blobs always replace existing blobs; drop --no-clobber and --overwrite"
+        ));
+    }
+
+    let destination = options.destination;
+    Ok(Some(if destination.ends_with('/') {
+        format!("{}{}", destination, get_filename(source))
+    } else {
+        destination.to_string()
+    }))
+}
+
+/// Upload one file as a page blob through the SDK, zero-padded to the next
+/// page boundary
+async fn upload_padded_page_blob(options: &CopyOptions<'_>, target: &str) -> Result<()> {
+    let source = options.source;
+    let size = fs::metadata(source).await?.len();
+    if let Some(max) = options.max_upload_bytes.filter(|max| size > *max) {
+        return Err(anyhow!(
+            "Uploading {} would exceed --max-upload-bytes ({})",
+            format_size(size),
+            format_size(max)
+        ));
+    }
+    if options.dry_run {
+        DryRunPlan::new(vec![PlannedTransfer::transfer(source, target, Some(size))]).print();
+        return Ok(());
+    }
+
+    let mut headers = options.headers.clone();
+    if headers.content_type.is_none() && options.guess_mime_type {
+        headers.content_type = detect_content_type(source, &Config::load()?.content_types);
+    }
+    let (account, container, blob) = parse_azure_uri(target)?;
+    let blob = blob.ok_or_else(|| anyhow!("Destination '{}' must name a blob", target))?;
+    let mut client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    };
+
+    status!(
+        "{} Uploading {} as a page blob to {}, zero-padded from {} to {} bytes",
+        "→".green(),
+        source,
+        target.cyan(),
+        size,
+        size.next_multiple_of(PAGE_SIZE)
+    );
+    let uploaded = client
+        .upload_page_blob(&container, &blob, Path::new(source), &headers)
+        .await?;
+    audit_log::record(1, uploaded);
+    status!(
+        "{} Uploaded {} ({})",
+        "✓".green(),
+        target.cyan(),
+        format_size(uploaded)
+    );
+    Ok(())
+}
+
+/// Fail before azcopy starts when files of a directory uploaded as page
+/// blobs aren't whole numbers of pages, listing them
+async fn check_page_alignment(options: &CopyOptions<'_>) -> Result<()> {
+    if !is_directory(options.source) {
+        return Ok(());
+    }
+    let unaligned: Vec<String> = snapshot_with(options.source, options.symlinks)
+        .await?
+        .into_iter()
+        .filter(|(name, state)| {
+            let file_name = name.rsplit('/').next().unwrap_or(name);
+            state.size % PAGE_SIZE != 0
+                && options
+                    .include_pattern
+                    .is_none_or(|patterns| matches_any_pattern(file_name, patterns))
+                && !options
+                    .exclude_pattern
+                    .is_some_and(|patterns| matches_any_pattern(file_name, patterns))
+                && options.filters.matches(name)
+        })
+        .map(|(name, _)| name)
+        .collect();
+    if unaligned.is_empty() {
+        return Ok(());
+    }
+    for name in &unaligned {
+        eprintln!("{} {}", "×".red(), name);
+    }
+    Err(anyhow!(
+        "{} file(s) aren't a whole number of {}-byte pages; upload them one at a time to \
+         have them zero-padded, or exclude them",
+        unaligned.len(),
+        PAGE_SIZE
+    ))
+}
+
+/// Stream a local directory into a single tar blob (--tar), or a tar blob
+/// into a local directory (--untar), without staging the archive on disk
+async fn copy_archive(options: &CopyOptions<'_>) -> Result<()> {
+    let (source, destination) = (options.source, options.destination);
+    let (remote, flag) = if options.tar {
+        (destination, "--tar")
+    } else {
+        (source, "--untar")
+    };
+    let local = if options.tar { source } else { destination };
+
+    if is_azure_uri(local) || !is_azure_uri(remote) {
+        return Err(anyhow!(if options.tar {
+            "--tar uploads a local directory to a blob"
+        } else {
+            "--untar downloads a blob into a local directory"
+        }));
+    }
+    if contains_wildcard(source) {
+        return Err(anyhow!("{} can't be combined with wildcards", flag));
+    }
+    if options.tar && !is_directory(source) {
+        return Err(anyhow!("--tar needs a local directory, not '{}'", source));
+    }
+    if options.overwrite != OverwritePolicy::Always {
+        return Err(anyhow!(
+            "{} always replaces existing files; drop --no-clobber and --overwrite",
+            flag
+        ));
+    }
+    let headers = &options.headers;
+    if headers.cache_control.is_some()
+        || headers.content_encoding.is_some()
+        || !headers.metadata.is_empty()
+        || headers.tier.is_some()
+    {
+        return Err(anyhow!("Only --content-type can be set on a --tar blob"));
+    }
+    if options.tar && options.customer_key.is_some() {
+        return Err(anyhow!("--tar can't encrypt with a customer-provided key"));
+    }
+
+    let (account, container, blob) = parse_azure_uri(remote)?;
+    let blob = blob
+        .filter(|blob| !blob.ends_with('/'))
+        .ok_or_else(|| anyhow!("{} needs a blob name, not '{}'", flag, remote))?;
+    if options.dry_run {
+        DryRunPlan::new(vec![PlannedTransfer::transfer(source, destination, None)]).print();
+        return Ok(());
+    }
+
+    let client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    }
+    .with_customer_key(options.customer_key.clone());
+
+    if options.tar {
+        let content_type = match &headers.content_type {
+            Some(content_type) => Some(content_type.clone()),
+            None => detect_content_type(&blob, &Config::load()?.content_types)
+                .or_else(|| Some("application/x-tar".to_string())),
+        };
+        status!(
+            "{} Archiving {} into {}",
+            "→".green(),
+            source,
+            destination.cyan()
+        );
+        let summary = archive::upload_tar(
+            client,
+            &container,
+            &blob,
+            Path::new(source),
+            archive_compression(&blob),
+            content_type.as_deref(),
+        )
+        .await?;
+        audit_log::record(1, summary.archive_bytes);
+        status!(
+            "{} Archived {} files ({}) into {} ({})",
+            "✓".green(),
+            summary.files,
+            format_size(summary.bytes),
+            destination.cyan(),
+            format_size(summary.archive_bytes)
+        );
+    } else {
+        status!(
+            "{} Extracting {} into {}",
+            "→".green(),
+            source.cyan(),
+            destination
+        );
+        let summary =
+            archive::download_untar(client, &container, &blob, Path::new(destination)).await?;
+        audit_log::record(summary.files, summary.bytes);
+        status!(
+            "{} Extracted {} files ({}) from {} ({})",
+            "✓".green(),
+            summary.files,
+            format_size(summary.bytes),
+            source.cyan(),
+            format_size(summary.archive_bytes)
+        );
+    }
+    Ok(())
+}
+
+/// Upload a local directory with its files smaller than `threshold` grouped
+/// into pack blobs behind an index (--pack)
+async fn copy_packed(options: &CopyOptions<'_>, threshold: u64) -> Result<()> {
+    let (source, destination) = (options.source, options.destination);
+    if is_azure_uri(source) || !is_azure_uri(destination) {
+        return Err(anyhow!("--pack uploads a local directory to Azure"));
+    }
+    if contains_wildcard(source) {
+        return Err(anyhow!("--pack can't be combined with wildcards"));
+    }
+    if !is_directory(source) {
+        return Err(anyhow!("--pack needs a local directory, not '{}'", source));
+    }
+    if options.overwrite != OverwritePolicy::Always {
+        return Err(anyhow!(
+            "--pack always replaces existing blobs; drop --no-clobber and --overwrite"
+        ));
+    }
+    if !options.headers.is_empty() {
+        return Err(anyhow!(
+            "Content headers, metadata and tier can't be set on a packed upload"
+        ));
+    }
+    if options.customer_key.is_some() {
+        return Err(anyhow!("--pack can't encrypt with a customer-provided key"));
+    }
+
+    // Packs live wherever azcopy would have placed the directory
+    let remote_root = match nested_dir_name(source, options.as_subdir) {
+        Some(dir_name) => format!("{}/{}/", destination.trim_end_matches('/'), dir_name),
+        None => format!("{}/", destination.trim_end_matches('/')),
+    };
+    let plan = pack::plan_upload(Path::new(source), threshold, options.symlinks).await?;
+
+    if options.dry_run {
+        let pack_root = format!("{}{}/", remote_root, PACK_DIR);
+        let packs = plan
+            .index
+            .packs
+            .iter()
+            .zip(plan.pack_sizes())
+            .map(|(name, size)| {
+                PlannedTransfer::transfer(source, &format!("{}{}", pack_root, name), Some(size))
+            });
+        let loose = plan.loose.iter().map(|(relative, size)| {
+            PlannedTransfer::transfer(
+                &Path::new(source).join(relative).to_string_lossy(),
+                &format!("{}{}", remote_root, relative),
+                Some(*size),
+            )
+        });
+        let links = plan.links.iter().map(|relative| {
+            PlannedTransfer::transfer(
+                &Path::new(source).join(relative).to_string_lossy(),
+                &format!("{}{}", remote_root, relative),
+                None,
+            )
+        });
+        DryRunPlan::new(packs.chain(loose).chain(links).collect()).print();
+        return Ok(());
     }
+
+    status!(
+        "{} Uploading {} to {} ({} files packed into {} blobs, {} loose)",
+        "→".green(),
+        source,
+        remote_root.cyan(),
+        plan.index.files.len(),
+        plan.index.packs.len(),
+        plan.loose.len()
+    );
+    let summary = pack::upload(Path::new(source), &remote_root, &plan).await?;
+    audit_log::record((summary.packs + summary.loose_files) as u64, summary.bytes);
+    status!(
+        "{} Uploaded {} files ({}) as {} packs and {} blobs",
+        "✓".green(),
+        summary.packed_files + summary.loose_files,
+        format_size(summary.bytes),
+        summary.packs,
+        summary.loose_files
+    );
+    Ok(())
 }
 
 /// Copy using AzCopy for high performance
-async fn copy_with_azcopy(azcopy: &mut AzCopyClient, options: CopyOptions<'_>) -> Result<()> {
+async fn copy_with_azcopy(
+    azcopy: &mut AzCopyClient,
+    mut options: CopyOptions<'_>,
+) -> Result<Vec<FailedTransfer>> {
     let source = options.source;
     let destination = options.destination;
     let recursive = options.recursive;
@@ -85,8 +867,38 @@ async fn copy_with_azcopy(azcopy: &mut AzCopyClient, options: CopyOptions<'_>) -
         source.to_string()
     };
 
+    // A destination ending with a slash is a directory to copy into, and is
+    // created if needed so a single blob lands inside it rather than at it
+    if is_azure_uri(source)
+        && !is_azure_uri(destination)
+        && destination.ends_with('/')
+        && !options.dry_run
+    {
+        std::fs::create_dir_all(destination)
+            .with_context(|| format!("Failed to create {}", destination))?;
+    }
+
+    // Files that keep only their names must not overwrite each other
+    let flattened = if options.flatten && (is_azure_uri(source) || is_directory(source)) {
+        Some(flattened_files(&options).await?)
+    } else {
+        None
+    };
+
+    // Downloads are written to a staging location and moved into place once
+    // complete, so nobody watching the destination sees truncated files.
+    // Other overwrite policies need azcopy to see the real destination.
+    // Flattened downloads are always staged, and flattened as they're committed.
+    let staging = (!options.dry_run
+        && is_azure_uri(source)
+        && !is_azure_uri(destination)
+        && (options.flatten || (options.atomic && options.overwrite == OverwritePolicy::Always)))
+        .then(|| Staging::plan(source, destination, recursive, options.flatten));
+
     let dest_url = if is_azure_uri(destination) {
         convert_az_uri_to_url(destination)?
+    } else if let Some(staging) = &staging {
+        staging.azcopy_destination()?
     } else {
         destination.to_string()
     };
@@ -99,25 +911,96 @@ async fn copy_with_azcopy(azcopy: &mut AzCopyClient, options: CopyOptions<'_>) -
         _ => "Copying",
     };
 
+    let is_upload = !is_azure_uri(source) && is_azure_uri(destination);
+    let is_download = is_azure_uri(source) && !is_azure_uri(destination);
+
+    // Verifying an upload compares against the Content-MD5 stored by --put-md5
+    let verify_upload =
+        is_upload && matches!(options.check_md5, Some(Md5Check::Log | Md5Check::Fail));
+    if verify_upload {
+        options.put_md5 = true;
+    }
+
+    // Premium block blob accounts get their own block size and concurrency
+    // unless the user chose them
+    let tuned = if is_azure_uri(destination) && !options.dry_run {
+        tuning::tune(destination, options.tuning).await
+    } else {
+        None
+    };
+    if let (None, Some(tuned)) = (options.block_size_mb, tuned) {
+        options.block_size_mb = Some(tuned.block_size_mb);
+    }
+
     let mut flags_display = Vec::new();
     if recursive {
         flags_display.push("recursive");
     }
+    if tuned.is_some() {
+        flags_display.push("premium-tuned");
+    }
     if options.dry_run {
         flags_display.push("dry-run");
     }
     if options.cap_mbps.is_some() {
         flags_display.push("rate-limited");
     }
+    if options.bandwidth_schedule.is_some() {
+        flags_display.push("bandwidth-schedule");
+    }
     if options.block_size_mb.is_some() {
         flags_display.push("custom-block-size");
     }
     if options.put_md5 {
         flags_display.push("md5-hashing");
     }
-    if options.include_pattern.is_some() {
+    if options.include_pattern.is_some() || !options.filters.is_empty() {
         flags_display.push("filtered");
     }
+    match options.overwrite {
+        OverwritePolicy::Always => {}
+        OverwritePolicy::Never => flags_display.push("no-clobber"),
+        OverwritePolicy::IfNewer => flags_display.push("overwrite-if-newer"),
+        OverwritePolicy::Prompt => flags_display.push("overwrite-prompt"),
+    }
+    if options.preserve {
+        flags_display.push("preserve");
+    }
+    if !options.headers.is_empty() {
+        flags_display.push("custom-headers");
+    }
+    if !options.guess_mime_type {
+        flags_display.push("no-mime-guess");
+    }
+    if matches!(options.check_md5, Some(Md5Check::Log | Md5Check::Fail)) {
+        flags_display.push("md5-check");
+    }
+    if is_download && !options.atomic {
+        flags_display.push("non-atomic");
+    }
+    if options.preserve_empty_dirs {
+        flags_display.push("empty-dirs");
+    }
+    match options.blob_type {
+        Some(BlobType::Block) => flags_display.push("block-blobs"),
+        Some(BlobType::Page) => flags_display.push("page-blobs"),
+        Some(BlobType::Append) => flags_display.push("append-blobs"),
+        None => {}
+    }
+    if let Some(compression) = options.compress {
+        flags_display.push(compression.content_encoding());
+    }
+    if !options.gzip_extensions.is_empty() {
+        flags_display.push("gzip-local");
+    }
+    if options.decompress == Some(true) {
+        flags_display.push("decompress");
+    }
+    if options.flatten {
+        flags_display.push("flatten");
+    } else if !options.as_subdir {
+        flags_display.push("no-subdir");
+    }
 
     let flags_str = if !flags_display.is_empty() {
         format!(" ({})", flags_display.join(", "))
@@ -125,7 +1008,7 @@ async fn copy_with_azcopy(azcopy: &mut AzCopyClient, options: CopyOptions<'_>) -
         String::new()
     };
 
-    println!(
+    status!(
         "{} {} {} to {}{}",
         "→".green(),
         operation_type,
@@ -134,13 +1017,126 @@ async fn copy_with_azcopy(azcopy: &mut AzCopyClient, options: CopyOptions<'_>) -
         flags_str.dimmed()
     );
 
+    // Single-file uploads get a content type from our table and the user's
+    // config; directory and wildcard uploads use azcopy's guesses, with the
+    // configured types applied once the upload finishes
+    let guess_content_type =
+        is_upload && options.guess_mime_type && options.headers.content_type.is_none();
+    let content_types = if guess_content_type {
+        Config::load()?.content_types
+    } else {
+        HashMap::new()
+    };
+    let many_files = is_directory(source) || contains_wildcard(source);
+    if guess_content_type && !many_files {
+        options.headers.content_type = detect_content_type(source, &content_types);
+    }
+
+    // Compressed uploads send compressed copies under the original names,
+    // marked with Content-Encoding so downloads can restore them
+    let compressed = match options.compress {
+        Some(compression) if !options.dry_run => {
+            status!(
+                "{} Compressing with {}",
+                "ℹ".blue(),
+                compression.content_encoding()
+            );
+            Some(CompressedCopy::create(Path::new(source), compression)?)
+        }
+        // Only some files are compressed: their blobs are marked once uploaded
+        None if !options.gzip_extensions.is_empty() && !options.dry_run => {
+            status!(
+                "{} Compressing .{} files with gzip",
+                "ℹ".blue(),
+                options.gzip_extensions.join(", .")
+            );
+            Some(CompressedCopy::create_selected(
+                Path::new(source),
+                Compression::Gzip,
+                Some(&options.gzip_extensions),
+            )?)
+        }
+        _ => None,
+    };
+    if let Some(compression) = options.compress {
+        let encoding = compression.content_encoding().to_string();
+        options.headers.content_encoding = Some(encoding.clone());
+        options
+            .headers
+            .metadata
+            .push((COMPRESSION_METADATA_KEY.to_string(), encoding));
+    }
+    let source_url = match &compressed {
+        Some(copy) => copy.root.to_string_lossy().to_string(),
+        None => source_url,
+    };
+
+    // Flattened uploads send links to every file, gathered in one directory
+    // whose contents are uploaded without it
+    let flat = match &flattened {
+        Some(files) if is_upload => Some(FlatCopy::create(Path::new(source), files)?),
+        _ => None,
+    };
+    // azcopy only takes wildcards in a source's last segment; the blobs of
+    // wildcards spanning directories are matched here and copied from the
+    // prefix before them, keeping their paths below it
+    let spanning = match spanning_glob_matches(source, recursive).await? {
+        Some((prefix_uri, matched)) => {
+            let matched: Vec<String> = matched
+                .into_iter()
+                .filter(|relative| options.filters.matches(relative))
+                .collect();
+            Some((convert_az_uri_to_url(&prefix_uri)?, matched))
+        }
+        None => None,
+    };
+    let source_url = match (&flat, &spanning) {
+        (Some(copy), _) => copy.root.to_string_lossy().to_string(),
+        (None, Some((prefix_url, _))) => prefix_url.clone(),
+        (None, None) => source_url,
+    };
+    let as_subdir = options.as_subdir && flat.is_none() && spanning.is_none();
+
+    // A dry run reports the cost without refusing
+    region::check_cross_region(
+        source,
+        destination,
+        Transfer::Copy,
+        options.allow_cross_region || options.dry_run,
+    )
+    .await?;
+    quota::check_upload(
+        source,
+        destination,
+        Transfer::Copy,
+        options.max_upload_bytes,
+        options.symlinks,
+    )
+    .await?;
+
     // Build options
     let mut azcopy_options = AzCopyOptions::new()
         .with_recursive(recursive)
         .with_dry_run(options.dry_run)
         .with_cap_mbps(options.cap_mbps)
+        .with_bandwidth_schedule(options.bandwidth_schedule.clone())
         .with_block_size_mb(options.block_size_mb)
-        .with_put_md5(options.put_md5);
+        .with_put_md5(options.put_md5)
+        .with_overwrite(Some(options.overwrite))
+        .with_preserve_properties(options.preserve)
+        // Restoring mtimes only applies when the destination is the local file system
+        .with_preserve_last_modified_time(options.preserve && is_download)
+        .with_headers(options.headers.clone())
+        .with_no_guess_mime_type(!options.guess_mime_type)
+        // azcopy checks hashes itself on download; uploads are verified below
+        .with_check_md5(options.check_md5.filter(|_| is_download))
+        .with_path_filters(&options.filters)
+        .with_no_as_subdir(!as_subdir)
+        .with_customer_key(options.customer_key.clone())
+        .with_blob_type(options.blob_type)
+        .with_symlinks(options.symlinks)
+        .with_tuned_concurrency(tuned.map(|tuned| tuned.concurrency))
+        .with_transfer_flags(options.transfer_flags.clone());
 
     if let Some(pattern) = options.include_pattern {
         azcopy_options = azcopy_options.with_include_pattern(Some(pattern.to_string()));
@@ -149,6 +1145,28 @@ async fn copy_with_azcopy(azcopy: &mut AzCopyClient, options: CopyOptions<'_>) -
         azcopy_options = azcopy_options.with_exclude_pattern(Some(pattern.to_string()));
     }
 
+    // Blobs renamed to avoid case collisions are left out of the bulk copy
+    // and downloaded one by one afterwards. Flattened downloads were already
+    // checked for colliding names.
+    let renames = if is_download
+        && recursive
+        && !contains_wildcard(source)
+        && !options.dry_run
+        && !options.flatten
+    {
+        resolve_case_collisions(source, destination, options.case_conflict).await?
+    } else {
+        Vec::new()
+    };
+    if !renames.is_empty() {
+        let excluded: Vec<&str> = renames.iter().map(|(blob, _)| blob.as_str()).collect();
+        azcopy_options = azcopy_options.with_extra_exclude_paths(&excluded);
+    }
+    // Packs are extracted by azst rather than downloaded as they are
+    if options.unpack {
+        azcopy_options = azcopy_options.with_extra_exclude_paths(&[PACK_DIR]);
+    }
+
     // Show the actual AzCopy command for debugging
     let mut cmd_parts = vec![format!("azcopy copy '{}' '{}'", source_url, dest_url)];
     if recursive {
@@ -172,34 +1190,1268 @@ async fn copy_with_azcopy(azcopy: &mut AzCopyClient, options: CopyOptions<'_>) -
     if let Some(pattern) = options.exclude_pattern {
         cmd_parts.push(format!("--exclude-pattern='{}'", pattern));
     }
+    cmd_parts.push(format!(
+        "--overwrite={}",
+        options.overwrite.as_azcopy_value()
+    ));
+    if options.preserve {
+        cmd_parts.push(AzCopyOptions::preserve_properties_flag().to_string());
+    }
+    if azcopy_options.preserve_last_modified_time {
+        cmd_parts.push("--preserve-last-modified-time".to_string());
+    }
+    for arg in options.headers.azcopy_args() {
+        cmd_parts.push(format!("'{}'", arg));
+    }
+    if !options.guess_mime_type {
+        cmd_parts.push("--no-guess-mime-type".to_string());
+    }
+    if let Some(check_md5) = &azcopy_options.check_md5 {
+        cmd_parts.push(format!("--check-md5={}", check_md5.as_azcopy_value()));
+    }
+    if let Some(paths) = &azcopy_options.include_path {
+        cmd_parts.push(format!("--include-path='{}'", paths));
+    }
+    if let Some(paths) = &azcopy_options.exclude_path {
+        cmd_parts.push(format!("--exclude-path='{}'", paths));
+    }
+    if let Some(regex) = &azcopy_options.include_regex {
+        cmd_parts.push(format!("--include-regex='{}'", regex));
+    }
+    if let Some(regex) = &azcopy_options.exclude_regex {
+        cmd_parts.push(format!("--exclude-regex='{}'", regex));
+    }
+    if azcopy_options.no_as_subdir {
+        cmd_parts.push("--as-subdir=false".to_string());
+    }
+    if azcopy_options.customer_key.is_some() {
+        cmd_parts.push("--cpk-by-value".to_string());
+    }
     cmd_parts.push("--output-type json".to_string());
 
-    println!("{} {}", "⚙".dimmed(), cmd_parts.join(" ").dimmed());
+    tracing::debug!("{}", cmd_parts.join(" "));
 
     // Use AzCopy for the operation
-    azcopy
-        .copy_with_options(&source_url, &dest_url, &azcopy_options)
-        .await?;
+    let result = async {
+        let summary = match options.manifest {
+            Some(manifest) => {
+                copy_with_manifest(
+                    azcopy,
+                    manifest,
+                    &options,
+                    &source_url,
+                    &dest_url,
+                    &azcopy_options,
+                    staging.as_ref(),
+                )
+                .await?
+            }
+            None => match &spanning {
+                Some((_, matched)) => {
+                    copy_matches(
+                        azcopy,
+                        matched,
+                        &source_url,
+                        &dest_url,
+                        &azcopy_options.clone().with_recursive(true),
+                    )
+                    .await?
+                }
+                None => {
+                    azcopy
+                        .copy_with_options(&source_url, &dest_url, &azcopy_options)
+                        .await?
+                }
+            },
+        };
+        let mut failures = summary.failed_transfers;
+        if !renames.is_empty() {
+            failures.extend(
+                download_renamed(
+                    azcopy,
+                    source,
+                    &dest_url,
+                    as_subdir,
+                    &renames,
+                    options.customer_key.as_ref(),
+                )
+                .await?,
+            );
+        }
+        Ok::<_, anyhow::Error>((failures, summary.skipped_transfers, summary.planned))
+    }
+    .await;
 
-    println!("{} Operation completed successfully", "✓".green());
-    Ok(())
-}
+    if let Some(staging) = &staging {
+        match &result {
+            Ok(_) => staging.commit()?,
+            Err(_) => staging.discard(),
+        }
+    }
+    let (mut failures, mut skipped, mut planned) = result?;
 
-// Local file operations
-async fn copy_local_files(source: &str, destination: &str, recursive: bool) -> Result<()> {
-    if is_directory(source) {
+    // Report flattened uploads against the files the user named
+    if let Some(flat) = &flat {
+        for transfer in failures.iter_mut().chain(skipped.iter_mut()) {
+            transfer.source = flat.original(&transfer.source);
+        }
+        for transfer in &mut planned {
+            transfer.source = flat.original(&transfer.source);
+        }
+    }
+
+    if options.dry_run {
+        DryRunPlan::new(planned).print();
+    }
+
+    if let Some(path) = options.failures_out {
+        // Failed downloads should be retried into the destination, not the
+        // staging location that no longer exists
+        if let Some(staging) = &staging {
+            for failure in &mut failures {
+                failure.destination = staging.final_destination(&failure.destination);
+            }
+        }
+        save_failures(path, AzCopyOperation::Copy, failures.clone())?;
+    }
+
+    if verify_upload && !options.dry_run {
+        let policy = options.check_md5.unwrap_or(Md5Check::Fail);
+        // Stored hashes are of what was uploaded, i.e. the compressed copies;
+        // flattened uploads are checked from the links that were uploaded
+        let uploaded = compressed
+            .as_ref()
+            .map(|copy| &copy.root)
+            .or(flat.as_ref().map(|copy| &copy.root))
+            .map(|root| root.to_string_lossy().to_string());
+        verify_upload_md5(
+            uploaded.as_deref().unwrap_or(source),
+            destination,
+            recursive,
+            as_subdir,
+            policy,
+            options.symlinks,
+        )
+        .await?;
+    }
+
+    if options.decompress == Some(true) && !options.dry_run {
+        decompress_downloads(
+            source,
+            destination,
+            recursive,
+            as_subdir,
+            &renames,
+            options.customer_key.as_ref(),
+        )
+        .await?;
+    }
+
+    // Changing the type reads the blob's properties back, which the SDK can't
+    // do for blobs encrypted with a customer-provided key
+    if guess_content_type
+        && many_files
+        && !content_types.is_empty()
+        && !options.dry_run
+        && options.customer_key.is_none()
+    {
+        let uploaded = flat
+            .as_ref()
+            .map(|copy| copy.root.to_string_lossy().to_string());
+        apply_configured_content_types(
+            uploaded.as_deref().unwrap_or(source),
+            destination,
+            recursive,
+            as_subdir,
+            &content_types,
+            options.symlinks,
+        )
+        .await?;
+    }
+
+    if let Some(copy) = compressed.as_ref().filter(|_| options.compress.is_none()) {
+        mark_compressed_uploads(
+            &copy.root.to_string_lossy(),
+            destination,
+            recursive,
+            as_subdir,
+            &options.gzip_extensions,
+            options.symlinks,
+        )
+        .await?;
+    }
+
+    if options.preserve_empty_dirs && recursive && !options.dry_run {
+        copy_empty_dirs(source, destination, as_subdir).await?;
+    }
+
+    if options.unpack && !options.dry_run {
+        let local_root = local_download_root(source, destination, as_subdir);
+        let summary = pack::unpack(source, &local_root).await?;
+        audit_log::record(summary.packed_files as u64, summary.bytes);
+        status!(
+            "{} Unpacked {} files ({}) from {} packs",
+            "ℹ".blue(),
+            summary.packed_files,
+            format_size(summary.bytes),
+            summary.packs
+        );
+    }
+
+    status!("{} Operation completed successfully", "✓".green());
+    failures.extend(skipped);
+    Ok(failures)
+}
+
+/// Copy the files `manifest` doesn't list as copied yet, a batch at a time,
+/// adding each batch to it as soon as azcopy finishes the batch
+///
+/// Staged downloads are moved into place after every batch, so whatever the
+/// manifest lists is at the destination even if a later batch never runs.
+async fn copy_with_manifest(
+    azcopy: &mut AzCopyClient,
+    path: &Path,
+    options: &CopyOptions<'_>,
+    source_url: &str,
+    dest_url: &str,
+    azcopy_options: &AzCopyOptions,
+    staging: Option<&Staging>,
+) -> Result<JobSummary> {
+    let mut manifest = CopyManifest::load(path, options.source, options.destination)?;
+    let files: Snapshot = snapshot_with(options.source, options.symlinks)
+        .await?
+        .into_iter()
+        .filter(|(relative, _)| {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            options.filters.matches(relative)
+                && options
+                    .include_pattern
+                    .is_none_or(|patterns| matches_any_pattern(name, patterns))
+                && !options
+                    .exclude_pattern
+                    .is_some_and(|patterns| matches_any_pattern(name, patterns))
+        })
+        .collect();
+    let pending = manifest.pending(&files);
+    if pending.len() < files.len() {
+        status!(
+            "{} {} of {} files were already copied according to {}",
+            "ℹ".blue(),
+            files.len() - pending.len(),
+            files.len(),
+            path.display()
+        );
+    }
+
+    let mut summary = JobSummary::default();
+    for batch in batches(&pending) {
+        if cancel::is_cancelled() {
+            break;
+        }
+        // Committing the previous batch removed the staging directory
+        let dest_url = match staging {
+            Some(staging) => staging.azcopy_destination()?,
+            None => dest_url.to_string(),
+        };
+        let batch_options = azcopy_options
+            .clone()
+            .with_include_path(Some(batch.join(";")));
+        let result = azcopy
+            .copy_with_options(source_url, &dest_url, &batch_options)
+            .await?;
+        if let Some(staging) = staging {
+            staging.commit()?;
+        }
+        if !options.dry_run {
+            manifest.record_batch(&batch, &files, &result.failed_transfers);
+            manifest.save(path)?;
+        }
+        summary.failed_transfers.extend(result.failed_transfers);
+        summary.skipped_transfers.extend(result.skipped_transfers);
+        summary.planned.extend(result.planned);
+    }
+    Ok(summary)
+}
+
+/// For an Azure source with wildcards spanning directories, which azcopy
+/// can't match, the prefix before them and the paths below it of the blobs
+/// they select
+async fn spanning_glob_matches(
+    source: &str,
+    recursive: bool,
+) -> Result<Option<(String, Vec<String>)>> {
+    if !is_azure_uri(source) {
+        return Ok(None);
+    }
+    let (account, container, path) = parse_azure_uri(source)?;
+    let Some(glob) = path
+        .as_deref()
+        .map(Glob::new)
+        .transpose()?
+        .flatten()
+        .filter(Glob::spans_directories)
+    else {
+        return Ok(None);
+    };
+
+    let mut client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    };
+    let mut matched = Vec::new();
+    client
+        .list_blobs_with_callback(
+            &container,
+            Some(glob.prefix()).filter(|prefix| !prefix.is_empty()),
+            None,
+            |items| {
+                for item in items {
+                    if let BlobItem::Blob(blob) = item {
+                        if glob.selects(&blob.name, recursive) {
+                            matched.push(blob.name[glob.prefix().len()..].to_string());
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )
+        .await?;
+    if matched.is_empty() {
+        return Err(anyhow!("No blobs match '{}'", source));
+    }
+    let prefix_uri = format!("{}/{}", uri_base(source, path.as_deref()), glob.prefix());
+    Ok(Some((prefix_uri, matched)))
+}
+
+/// Copy the blobs at `paths` below the prefix at `source_url`, a batch of
+/// --include-path at a time
+async fn copy_matches(
+    azcopy: &mut AzCopyClient,
+    paths: &[String],
+    source_url: &str,
+    dest_url: &str,
+    azcopy_options: &AzCopyOptions,
+) -> Result<JobSummary> {
+    let mut summary = JobSummary::default();
+    for batch in batches(paths) {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let batch_options = azcopy_options
+            .clone()
+            .with_include_path(Some(batch.join(";")));
+        let result = azcopy
+            .copy_with_options(source_url, dest_url, &batch_options)
+            .await?;
+        summary.failed_transfers.extend(result.failed_transfers);
+        summary.skipped_transfers.extend(result.skipped_transfers);
+        summary.planned.extend(result.planned);
+    }
+    Ok(summary)
+}
+
+/// The directory a recursive copy creates at the destination: azcopy names
+/// it after the source's last path segment, whether or not the source ends
+/// with a slash. Wildcard sources and `--as-subdir=false` copy the contents
+/// straight into the destination.
+fn nested_dir_name(source: &str, as_subdir: bool) -> Option<String> {
+    (as_subdir && !contains_wildcard(source)).then(|| {
+        let root = source.trim_end_matches(['/', std::path::MAIN_SEPARATOR]);
+        root.rsplit(['/', std::path::MAIN_SEPARATOR])
+            .next()
+            .unwrap_or(root)
+            .to_string()
+    })
+}
+
+/// Local directory the files of a recursive download end up in
+fn local_download_root(source: &str, destination: &str, as_subdir: bool) -> PathBuf {
+    match nested_dir_name(source, as_subdir) {
+        Some(dir_name) => Path::new(destination).join(dir_name),
+        None => PathBuf::from(destination),
+    }
+}
+
+/// Carry empty directories across a recursive upload or download
+///
+/// The markers and restored directories live wherever the copied directory
+/// was placed.
+async fn copy_empty_dirs(source: &str, destination: &str, as_subdir: bool) -> Result<()> {
+    let (verb, count) = match (is_azure_uri(source), is_azure_uri(destination)) {
+        (false, true) => {
+            let destination = destination.trim_end_matches('/');
+            let remote_root = match nested_dir_name(source, as_subdir) {
+                Some(dir_name) => format!("{}/{}", destination, dir_name),
+                None => destination.to_string(),
+            };
+            let count = dir_markers::upload_markers(Path::new(source), &remote_root).await?;
+            ("Stored markers for", count)
+        }
+        (true, false) if !contains_wildcard(source) => {
+            let local_root = local_download_root(source, destination, as_subdir);
+            let count = dir_markers::restore_empty_dirs(source, &local_root).await?;
+            ("Recreated", count)
+        }
+        _ => return Ok(()),
+    };
+
+    if count > 0 {
+        status!("{} {} {} empty director(ies)", "ℹ".blue(), verb, count);
+    }
+    Ok(())
+}
+
+/// Decompress downloaded files whose blobs have a Content-Encoding azst can decode
+///
+/// `renames` maps blobs downloaded under another name to avoid case
+/// collisions, as (blob path relative to the source, local relative path).
+async fn decompress_downloads(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    as_subdir: bool,
+    renames: &[(String, String)],
+    customer_key: Option<&CustomerKey>,
+) -> Result<()> {
+    let (account, container, path) = parse_azure_uri(source)?;
+    let mut client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    }
+    .with_customer_key(customer_key.cloned());
+
+    // (local path, algorithm) for every compressed download
+    let mut targets = Vec::new();
+    if recursive {
+        let prefix = directory_prefix(path.as_deref());
+        let local_root = local_download_root(source, destination, as_subdir);
+        let renamed: HashMap<&str, &str> = renames
+            .iter()
+            .map(|(blob, local)| (blob.as_str(), local.as_str()))
+            .collect();
+
+        let list_prefix = (!prefix.is_empty()).then_some(prefix.as_str());
+        client
+            .list_blobs_with_callback(&container, list_prefix, None, |items| {
+                for item in items {
+                    let BlobItem::Blob(blob) = item else {
+                        continue;
+                    };
+                    let Some(compression) = blob
+                        .properties
+                        .content_encoding
+                        .as_deref()
+                        .and_then(Compression::from_content_encoding)
+                    else {
+                        continue;
+                    };
+                    let relative = blob.name.strip_prefix(&prefix).unwrap_or(&blob.name);
+                    let local = renamed.get(relative).copied().unwrap_or(relative);
+                    targets.push((local_root.join(local), compression));
+                }
+                Ok(())
+            })
+            .await?;
+    } else {
+        let blob_path = path.ok_or_else(|| anyhow!("No blob path in '{}'", source))?;
+        let blob = client.get_blob_properties(&container, &blob_path).await?;
+        if let Some(compression) = blob
+            .properties
+            .content_encoding
+            .as_deref()
+            .and_then(Compression::from_content_encoding)
+        {
+            let target = if is_directory(destination) || destination.ends_with('/') {
+                Path::new(destination).join(get_filename(source))
+            } else {
+                PathBuf::from(destination)
+            };
+            targets.push((target, compression));
+        }
+    }
+
+    let mut decompressed = 0;
+    for (path, compression) in targets {
+        // Filtered out by include/exclude patterns
+        if !path.is_file() {
+            continue;
+        }
+        decompress_in_place(&path, compression)?;
+        decompressed += 1;
+    }
+
+    if decompressed > 0 {
+        status!("{} Decompressed {} file(s)", "ℹ".blue(), decompressed);
+    }
+    Ok(())
+}
+
+/// Find blobs under `source` whose names collide when case is ignored and
+/// decide how to download them
+///
+/// Returns (blob path relative to the source, local path relative to the
+/// downloaded directory) for every blob that must be renamed.
+async fn resolve_case_collisions(
+    source: &str,
+    destination: &str,
+    policy: Option<CaseConflictPolicy>,
+) -> Result<Vec<(String, String)>> {
+    // Without an explicit policy, only case-insensitive targets need checking
+    let policy = match policy {
+        Some(policy) => policy,
+        None if is_case_insensitive(Path::new(destination)) => CaseConflictPolicy::Abort,
+        None => return Ok(Vec::new()),
+    };
+    if policy == CaseConflictPolicy::Ignore {
+        return Ok(Vec::new());
+    }
+
+    let names: Vec<String> = snapshot(source).await?.into_keys().collect();
+    let collisions = find_case_collisions(&names);
+    if collisions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    match policy {
+        CaseConflictPolicy::Abort => {
+            for group in &collisions {
+                println!("{} {}", "×".red(), group.join(", "));
+            }
+            Err(anyhow!(
+                "{} group(s) of blob names differ only by case and would overwrite each other. \
+                 Use --case-conflict rename to download them under suffixed names, \
+                 or --case-conflict ignore to download anyway",
+                collisions.len()
+            ))
+        }
+        CaseConflictPolicy::Rename => {
+            let renames = plan_case_renames(&names, &collisions);
+            for (from, to) in &renames {
+                status!("{} Renaming {} to {}", "ℹ".blue(), from, to.cyan());
+            }
+            Ok(renames)
+        }
+        CaseConflictPolicy::Ignore => Ok(Vec::new()),
+    }
+}
+
+/// Group names that are equal when compared case-insensitively
+fn find_case_collisions(names: &[String]) -> Vec<Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for name in names {
+        groups
+            .entry(name.to_lowercase())
+            .or_default()
+            .push(name.clone());
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect()
+}
+
+/// Pick unique names for every colliding name but the first of each group
+fn plan_case_renames(names: &[String], collisions: &[Vec<String>]) -> Vec<(String, String)> {
+    let mut taken: HashSet<String> = names.iter().map(|n| n.to_lowercase()).collect();
+    let mut renames = Vec::new();
+
+    for group in collisions {
+        for name in group.iter().skip(1) {
+            let path = Path::new(name);
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| format!(".{}", e))
+                .unwrap_or_default();
+            let parent = name.rsplit_once('/').map(|(dir, _)| format!("{}/", dir));
+
+            let mut n = 2;
+            let renamed = loop {
+                let candidate = format!(
+                    "{}{}~{}{}",
+                    parent.as_deref().unwrap_or(""),
+                    stem,
+                    n,
+                    extension
+                );
+                if taken.insert(candidate.to_lowercase()) {
+                    break candidate;
+                }
+                n += 1;
+            };
+            renames.push((name.clone(), renamed));
+        }
+    }
+
+    renames
+}
+
+/// Check whether the file system holding `path` ignores case in file names
+fn is_case_insensitive(path: &Path) -> bool {
+    let dir = path
+        .ancestors()
+        .find(|p| p.is_dir())
+        .unwrap_or_else(|| Path::new("."));
+    let probe = dir.join(format!(".azst-CASE-probe-{}", std::process::id()));
+    if std::fs::write(&probe, b"").is_err() {
+        return false;
+    }
+
+    let lowercase = dir.join(format!(".azst-case-probe-{}", std::process::id()));
+    let insensitive = lowercase.exists();
+    let _ = std::fs::remove_file(&probe);
+    insensitive
+}
+
+/// Download blobs that were excluded from the bulk copy to their renamed paths
+async fn download_renamed(
+    azcopy: &mut AzCopyClient,
+    source: &str,
+    local_root: &str,
+    as_subdir: bool,
+    renames: &[(String, String)],
+    customer_key: Option<&CustomerKey>,
+) -> Result<Vec<FailedTransfer>> {
+    let source_root = source.trim_end_matches('/');
+    let target_dir = local_download_root(source, local_root, as_subdir);
+
+    let options = AzCopyOptions::new().with_customer_key(customer_key.cloned());
+    let mut failures = Vec::new();
+    for (blob, renamed) in renames {
+        let blob_url = convert_az_uri_to_url(&format!("{}/{}", source_root, blob))?;
+        let target = target_dir.join(renamed);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let summary = azcopy
+            .copy_with_options(&blob_url, &target.to_string_lossy(), &options)
+            .await?;
+        failures.extend(summary.failed_transfers);
+    }
+
+    Ok(failures)
+}
+
+/// Write `--failures-out`, even when nothing failed so a stale file from an
+/// earlier run isn't mistaken for this one's
+pub fn save_failures(
+    path: &Path,
+    operation: AzCopyOperation,
+    failures: Vec<FailedTransfer>,
+) -> Result<()> {
+    let count = failures.len();
+    FailureReport {
+        operation,
+        failures,
+    }
+    .save(path)?;
+    if count > 0 {
+        status!(
+            "{} {} failed transfer(s) written to {}",
+            "ℹ".blue(),
+            count,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Relative paths of the files a flattened copy transfers
+///
+/// Files from different directories that share a name would overwrite each
+/// other once flattened, so any such names are an error.
+async fn flattened_files(options: &CopyOptions<'_>) -> Result<Vec<String>> {
+    let names: Vec<String> = snapshot_with(options.source, options.symlinks)
+        .await?
+        .into_keys()
+        .filter(|name| {
+            let file_name = name.rsplit('/').next().unwrap_or(name);
+            options
+                .include_pattern
+                .is_none_or(|patterns| matches_any_pattern(file_name, patterns))
+                && !options
+                    .exclude_pattern
+                    .is_some_and(|patterns| matches_any_pattern(file_name, patterns))
+                && options.filters.matches(name)
+        })
+        .collect();
+
+    let collisions = find_name_collisions(&names);
+    if !collisions.is_empty() {
+        for group in &collisions {
+            println!("{} {}", "×".red(), group.join(", "));
+        }
+        return Err(anyhow!(
+            "{} file name(s) appear in more than one directory and would overwrite each \
+             other when flattened",
+            collisions.len()
+        ));
+    }
+    Ok(names)
+}
+
+/// Group relative paths whose file names are the same
+fn find_name_collisions(names: &[String]) -> Vec<Vec<String>> {
+    let mut groups: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for name in names {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        groups.entry(file_name).or_default().push(name.clone());
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect()
+}
+
+/// The files of a flattened upload side by side in a temporary directory,
+/// hard-linked where possible so nothing is copied
+struct FlatCopy {
+    root: PathBuf,
+    /// Original path of every file in `root`
+    originals: HashMap<PathBuf, PathBuf>,
+}
+
+impl FlatCopy {
+    fn create(source: &Path, files: &[String]) -> Result<Self> {
+        let root = std::env::temp_dir().join(format!("azst-flatten-{}", std::process::id()));
+        let mut copy = Self {
+            root,
+            originals: HashMap::new(),
+        };
+        std::fs::create_dir_all(&copy.root)?;
+
+        for relative in files {
+            let original = source.join(relative);
+            let link = copy.root.join(original.file_name().unwrap_or_default());
+            if std::fs::hard_link(&original, &link).is_err() {
+                std::fs::copy(&original, &link)
+                    .with_context(|| format!("Failed to stage {}", original.display()))?;
+            }
+            copy.originals.insert(link, original);
+        }
+        Ok(copy)
+    }
+
+    /// The file a path azcopy reported stands for
+    fn original(&self, path: &str) -> String {
+        self.originals
+            .get(Path::new(path))
+            .map(|original| original.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string())
+    }
+}
+
+impl Drop for FlatCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Temporary location for a download that is moved into place on success
+enum Staging {
+    /// A single blob, written to `<target>.partial` and renamed over the target
+    File { partial: PathBuf, target: PathBuf },
+    /// A recursive download, written into a hidden directory inside the
+    /// destination whose files are renamed into place one by one, directly
+    /// into the destination when `flat`
+    Directory {
+        staging: PathBuf,
+        target: PathBuf,
+        flat: bool,
+    },
+}
+
+impl Staging {
+    fn plan(source: &str, destination: &str, recursive: bool, flat: bool) -> Self {
+        if recursive || source.ends_with('/') || contains_wildcard(source) {
+            let target = PathBuf::from(destination);
+            let staging = target.join(format!(".azst-partial-{}", std::process::id()));
+            Staging::Directory {
+                staging,
+                target,
+                flat,
+            }
+        } else {
+            let target = if is_directory(destination) || destination.ends_with('/') {
+                Path::new(destination).join(get_filename(source))
+            } else {
+                PathBuf::from(destination)
+            };
+            let mut partial = target.clone().into_os_string();
+            partial.push(".partial");
+            Staging::File {
+                partial: PathBuf::from(partial),
+                target,
+            }
+        }
+    }
+
+    /// Path handed to azcopy instead of the real destination
+    fn azcopy_destination(&self) -> Result<String> {
+        let path = match self {
+            Staging::File { partial, .. } => partial,
+            Staging::Directory { staging, .. } => {
+                std::fs::create_dir_all(staging)?;
+                staging
+            }
+        };
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    /// Where a path azcopy wrote to ends up once the download is committed
+    fn final_destination(&self, path: &str) -> String {
+        let path = Path::new(path);
+        let final_path = match self {
+            Staging::File { partial, target } if path == partial => target.clone(),
+            Staging::Directory {
+                staging,
+                target,
+                flat,
+            } => match path.strip_prefix(staging) {
+                Ok(relative) => Self::committed_path(target, relative, *flat),
+                Err(_) => path.to_path_buf(),
+            },
+            _ => path.to_path_buf(),
+        };
+        final_path.to_string_lossy().to_string()
+    }
+
+    /// Move the completed download into place
+    fn commit(&self) -> Result<()> {
+        match self {
+            Staging::File { partial, target } => {
+                std::fs::rename(partial, target)
+                    .with_context(|| format!("Failed to move {} into place", partial.display()))?;
+            }
+            Staging::Directory {
+                staging,
+                target,
+                flat,
+            } => {
+                // Batched copies commit as they go, leaving nothing at the end
+                if !staging.exists() {
+                    return Ok(());
+                }
+                let mut stack = vec![staging.clone()];
+                while let Some(dir) = stack.pop() {
+                    for entry in std::fs::read_dir(&dir)? {
+                        let path = entry?.path();
+                        if path.is_dir() {
+                            stack.push(path);
+                            continue;
+                        }
+                        let relative = path.strip_prefix(staging).unwrap_or(&path);
+                        let final_path = Self::committed_path(target, relative, *flat);
+                        if let Some(parent) = final_path.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+                        std::fs::rename(&path, &final_path).with_context(|| {
+                            format!("Failed to move {} into place", final_path.display())
+                        })?;
+                    }
+                }
+                std::fs::remove_dir_all(staging)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Where a file staged at `relative` is moved to
+    fn committed_path(target: &Path, relative: &Path, flat: bool) -> PathBuf {
+        match relative.file_name() {
+            Some(name) if flat => target.join(name),
+            _ => target.join(relative),
+        }
+    }
+
+    /// Remove whatever a failed download left behind
+    fn discard(&self) {
+        let _ = match self {
+            Staging::File { partial, .. } => std::fs::remove_file(partial),
+            Staging::Directory { staging, .. } => std::fs::remove_dir_all(staging),
+        };
+    }
+}
+
+/// Where an upload's files ended up: the destination container and the
+/// blob each local file was written to
+struct UploadedFiles {
+    client: AzureClient,
+    container: String,
+    /// Directory prefix (with trailing slash, or empty) covering every blob
+    dir_prefix: String,
+    /// (local path, blob name) pairs
+    pairs: Vec<(PathBuf, String)>,
+}
+
+/// Map the files of an upload to their blobs
+///
+/// Directories land under `<destination>/<dirname>/`, like azcopy places
+/// them, or straight under the destination without `as_subdir`.
+async fn uploaded_files(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    as_subdir: bool,
+    symlinks: SymlinkPolicy,
+) -> Result<UploadedFiles> {
+    let (account, container, prefix) = parse_azure_uri(destination)?;
+    let client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    };
+
+    let prefix = prefix.unwrap_or_default();
+    let dir_prefix = if prefix.is_empty() || prefix.ends_with('/') {
+        prefix.clone()
+    } else {
+        format!("{}/", prefix)
+    };
+
+    let mut pairs = Vec::new();
+    if contains_wildcard(source) {
+        // azcopy puts what a wildcard matches straight under the destination,
+        // and the files of matched directories below them
+        let matches = glob::glob(source)
+            .map_err(|e| anyhow!("Invalid wildcard pattern '{}': {}", source, e))?;
+        for matched in matches.filter_map(|path| path.ok()) {
+            let name = get_filename(&matched.to_string_lossy());
+            if matched.is_dir() {
+                if !recursive {
+                    continue;
+                }
+                for relative in snapshot_with(&matched.to_string_lossy(), symlinks)
+                    .await?
+                    .into_keys()
+                {
+                    let local = matched.join(&relative);
+                    pairs.push((local, format!("{}{}/{}", dir_prefix, name, relative)));
+                }
+            } else {
+                pairs.push((matched, format!("{}{}", dir_prefix, name)));
+            }
+        }
+    } else if is_directory(source) {
+        let root = match nested_dir_name(source, as_subdir) {
+            Some(dir_name) => format!("{}{}/", dir_prefix, dir_name),
+            None => dir_prefix.clone(),
+        };
+        for relative in snapshot_with(source, symlinks).await?.into_keys() {
+            let local = Path::new(source).join(&relative);
+            pairs.push((local, format!("{}{}", root, relative)));
+        }
+    } else {
+        let blob = if prefix.is_empty() || prefix.ends_with('/') {
+            format!("{}{}", dir_prefix, get_filename(source))
+        } else {
+            prefix
+        };
+        pairs.push((PathBuf::from(source), blob));
+    }
+
+    Ok(UploadedFiles {
+        client,
+        container,
+        dir_prefix,
+        pairs,
+    })
+}
+
+/// Set the content types from the user's config on blobs uploaded from a
+/// directory or wildcard, which azcopy typed with its own guesses
+async fn apply_configured_content_types(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    as_subdir: bool,
+    overrides: &HashMap<String, String>,
+    symlinks: SymlinkPolicy,
+) -> Result<()> {
+    let UploadedFiles {
+        mut client,
+        container,
+        dir_prefix,
+        pairs,
+    } = uploaded_files(source, destination, recursive, as_subdir, symlinks).await?;
+
+    // Files filtered out by include/exclude patterns were never uploaded
+    let list_prefix = (!dir_prefix.is_empty()).then_some(dir_prefix.as_str());
+    let mut uploaded = HashSet::new();
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    uploaded.insert(blob.name);
+                }
+            }
+            Ok(())
+        })
+        .await?;
+
+    let mut updated = 0;
+    for (local, blob) in pairs {
+        let Some(content_type) = configured_content_type(&local.to_string_lossy(), overrides)
+        else {
+            continue;
+        };
+        if !uploaded.contains(&blob) {
+            continue;
+        }
+        client
+            .set_blob_content_type(&container, &blob, &content_type)
+            .await?;
+        updated += 1;
+    }
+
+    if updated > 0 {
+        status!(
+            "{} Applied configured content types to {} blob(s)",
+            "ℹ".blue(),
+            updated
+        );
+    }
+    Ok(())
+}
+
+/// Set Content-Encoding: gzip on the blobs uploaded from files with one of
+/// `extensions`, which `--gzip-local` compressed
+async fn mark_compressed_uploads(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    as_subdir: bool,
+    extensions: &[String],
+    symlinks: SymlinkPolicy,
+) -> Result<()> {
+    let UploadedFiles {
+        mut client,
+        container,
+        dir_prefix,
+        pairs,
+    } = uploaded_files(source, destination, recursive, as_subdir, symlinks).await?;
+
+    // Files filtered out by include/exclude patterns were never uploaded
+    let list_prefix = (!dir_prefix.is_empty()).then_some(dir_prefix.as_str());
+    let mut uploaded = HashSet::new();
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    uploaded.insert(blob.name);
+                }
+            }
+            Ok(())
+        })
+        .await?;
+
+    let encoding = Compression::Gzip.content_encoding();
+    for (local, blob) in pairs {
+        if has_extension(&local, extensions) && uploaded.contains(&blob) {
+            client
+                .mark_blob_compressed(&container, &blob, encoding, COMPRESSION_METADATA_KEY)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Compare local files against the Content-MD5 of the blobs they were uploaded to
+///
+/// Files without a matching blob are skipped, since include/exclude patterns
+/// may have filtered them out.
+async fn verify_upload_md5(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    as_subdir: bool,
+    policy: Md5Check,
+    symlinks: SymlinkPolicy,
+) -> Result<()> {
+    let UploadedFiles {
+        mut client,
+        container,
+        dir_prefix,
+        pairs,
+    } = uploaded_files(source, destination, recursive, as_subdir, symlinks).await?;
+
+    // Fetch the stored hashes with a single listing under the common prefix
+    let list_prefix = (!dir_prefix.is_empty()).then_some(dir_prefix.as_str());
+    let mut remote = std::collections::HashMap::new();
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    remote.insert(blob.name, blob.properties.content_md5);
+                }
+            }
+            Ok(())
+        })
+        .await?;
+
+    status!(
+        "{} Verifying MD5 of {} uploaded file(s)",
+        "→".green(),
+        pairs.len()
+    );
+
+    let mut failures = 0;
+    for (local, blob) in pairs {
+        let Some(stored) = remote.get(&blob) else {
+            continue;
+        };
+
+        let problem = match stored {
+            None => Some("blob has no Content-MD5".to_string()),
+            Some(stored) => {
+                let computed = file_md5(&local)?;
+                (&computed != stored).then(|| format!("local {} != stored {}", computed, stored))
+            }
+        };
+
+        if let Some(problem) = problem {
+            failures += 1;
+            println!(
+                "{} MD5 mismatch for {}: {}",
+                "⚠".yellow(),
+                local.display(),
+                problem
+            );
+        }
+    }
+
+    if failures == 0 {
+        status!("{} MD5 verified", "✓".green());
+    } else if policy == Md5Check::Fail {
+        return Err(anyhow!("{} file(s) failed MD5 verification", failures));
+    }
+
+    Ok(())
+}
+
+// Local file operations
+async fn copy_local_files(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+    overwrite: OverwritePolicy,
+    preserve: bool,
+    prompter: &dyn Prompter,
+) -> Result<()> {
+    if is_directory(source) {
         if !recursive {
             return Err(anyhow!(
                 "Source is a directory. Use -r flag for recursive copy"
             ));
         }
-        copy_directory(source, destination).await
+        copy_directory(source, destination, overwrite, preserve, prompter).await
     } else {
-        copy_file(source, destination).await
+        copy_file(source, destination, overwrite, preserve, prompter).await
+    }
+}
+
+/// The files a local copy would write, for --dry-run
+async fn plan_local_copy(
+    source: &str,
+    destination: &str,
+    recursive: bool,
+) -> Result<Vec<PlannedTransfer>> {
+    if !is_directory(source) {
+        let size = fs::metadata(source)
+            .await
+            .with_context(|| format!("Source path '{}' does not exist", source))?
+            .len();
+        let dest_path = if is_directory(destination) {
+            join_location(destination, &get_filename(source))
+        } else {
+            destination.to_string()
+        };
+        return Ok(vec![PlannedTransfer::transfer(
+            source,
+            &dest_path,
+            Some(size),
+        )]);
+    }
+    if !recursive {
+        return Err(anyhow!(
+            "Source is a directory. Use -r flag for recursive copy"
+        ));
+    }
+
+    Ok(snapshot(source)
+        .await?
+        .into_iter()
+        .map(|(relative, state)| {
+            PlannedTransfer::transfer(
+                &join_location(source, &relative),
+                &join_location(destination, &relative),
+                Some(state.size),
+            )
+        })
+        .collect())
+}
+
+/// Copy access and modification times from one local file to another
+/// (permissions are already copied by `fs::copy`)
+fn preserve_timestamps(source: &str, dest_path: &str) -> Result<()> {
+    let metadata = std::fs::metadata(source)?;
+    let mut times = std::fs::FileTimes::new().set_modified(metadata.modified()?);
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+
+    std::fs::File::options()
+        .write(true)
+        .open(dest_path)?
+        .set_times(times)?;
+    Ok(())
+}
+
+/// Decide whether an existing local destination may be replaced
+async fn should_overwrite(
+    source: &str,
+    dest_path: &str,
+    overwrite: OverwritePolicy,
+    prompter: &dyn Prompter,
+) -> Result<bool> {
+    if !path_exists(dest_path) {
+        return Ok(true);
+    }
+
+    match overwrite {
+        OverwritePolicy::Always => Ok(true),
+        OverwritePolicy::Never => Ok(false),
+        OverwritePolicy::IfNewer => {
+            let source_modified = fs::metadata(source).await?.modified()?;
+            let dest_modified = fs::metadata(dest_path).await?.modified()?;
+            Ok(source_modified > dest_modified)
+        }
+        OverwritePolicy::Prompt => {
+            prompter
+                .confirm(
+                    &format!("Overwrite '{}'?", dest_path.cyan()),
+                    OVERWRITE_BYPASS,
+                )
+                .await
+        }
     }
 }
 
-async fn copy_file(source: &str, destination: &str) -> Result<()> {
+async fn copy_file(
+    source: &str,
+    destination: &str,
+    overwrite: OverwritePolicy,
+    preserve: bool,
+    prompter: &dyn Prompter,
+) -> Result<()> {
     let dest_path = if is_directory(destination) {
         format!(
             "{}/{}",
@@ -215,21 +2467,33 @@ async fn copy_file(source: &str, destination: &str) -> Result<()> {
         fs::create_dir_all(parent).await?;
     }
 
-    println!(
+    if !should_overwrite(source, &dest_path, overwrite, prompter).await? {
+        status!("{} Skipping existing {}", "=".dimmed(), dest_path.cyan());
+        return Ok(());
+    }
+
+    status!(
         "{} Copying {} to {}",
         "→".green(),
         source.cyan(),
         dest_path.cyan()
     );
 
-    fs::copy(source, &dest_path).await?;
-    println!("{} Copy completed", "✓".green());
+    let bytes = fs::copy(source, &dest_path).await?;
+    audit_log::record(1, bytes);
+    if preserve {
+        preserve_timestamps(source, &dest_path)?;
+    }
+    status!("{} Copy completed", "✓".green());
     Ok(())
 }
 
 fn copy_directory<'a>(
     source: &'a str,
     destination: &'a str,
+    overwrite: OverwritePolicy,
+    preserve: bool,
+    prompter: &'a dyn Prompter,
 ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
     Box::pin(async move {
         let mut entries = fs::read_dir(source).await?;
@@ -247,20 +2511,354 @@ fn copy_directory<'a>(
 
             if entry_path.is_dir() {
                 // Recursively copy subdirectory
-                copy_directory(entry_str, &dest_path).await?;
+                copy_directory(entry_str, &dest_path, overwrite, preserve, prompter).await?;
+            } else if !should_overwrite(entry_str, &dest_path, overwrite, prompter).await? {
+                status!("{} Skipping existing {}", "=".dimmed(), dest_path.cyan());
             } else {
                 // Copy file
-                println!(
+                status!(
                     "{} Copying {} to {}",
                     "→".green(),
                     entry_str.cyan(),
                     dest_path.cyan()
                 );
 
-                fs::copy(entry_str, &dest_path).await?;
+                let bytes = fs::copy(entry_str, &dest_path).await?;
+                audit_log::record(1, bytes);
+                if preserve {
+                    preserve_timestamps(entry_str, &dest_path)?;
+                }
             }
         }
 
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::ScriptedPrompter;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_find_case_collisions() {
+        let names: Vec<String> = [
+            "data.csv",
+            "Data.csv",
+            "other.csv",
+            "logs/A.txt",
+            "Logs/a.txt",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let collisions = find_case_collisions(&names);
+        assert_eq!(
+            collisions,
+            vec![
+                vec!["Data.csv".to_string(), "data.csv".to_string()],
+                vec!["Logs/a.txt".to_string(), "logs/A.txt".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_case_renames_avoids_existing_names() {
+        let names: Vec<String> = ["Data.csv", "data.csv", "data~2.csv"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let collisions = find_case_collisions(&names);
+
+        let renames = plan_case_renames(&names, &collisions);
+        assert_eq!(
+            renames,
+            vec![("data.csv".to_string(), "data~3.csv".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_plan_case_renames_keeps_directory() {
+        let names: Vec<String> = ["dir/README", "dir/readme"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let collisions = find_case_collisions(&names);
+
+        let renames = plan_case_renames(&names, &collisions);
+        assert_eq!(
+            renames,
+            vec![("dir/readme".to_string(), "dir/readme~2".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_staging_single_file_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().to_str().unwrap();
+
+        let staging = Staging::plan("az://acct/data/report.csv", dest, false, false);
+        let partial = staging.azcopy_destination().unwrap();
+        assert!(partial.ends_with("report.csv.partial"));
+
+        std::fs::write(&partial, "a,b").unwrap();
+        staging.commit().unwrap();
+        assert!(!Path::new(&partial).exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("report.csv")).unwrap(),
+            "a,b"
+        );
+    }
+
+    #[test]
+    fn test_staging_final_destination() {
+        let staging = Staging::plan("az://acct/data/logs/", "/restore", true, false);
+        let staged = format!("/restore/.azst-partial-{}/logs/a.log", std::process::id());
+        assert_eq!(staging.final_destination(&staged), "/restore/logs/a.log");
+        assert_eq!(
+            staging.final_destination("/elsewhere/a.log"),
+            "/elsewhere/a.log"
+        );
+
+        let staging = Staging::plan(
+            "az://acct/data/report.csv",
+            "/restore/report.csv",
+            false,
+            false,
+        );
+        assert_eq!(
+            staging.final_destination("/restore/report.csv.partial"),
+            "/restore/report.csv"
+        );
+    }
+
+    #[test]
+    fn test_staging_directory_commit_and_discard() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().to_str().unwrap();
+
+        let staging = Staging::plan("az://acct/data/logs/", dest, true, false);
+        let staged = PathBuf::from(staging.azcopy_destination().unwrap());
+        std::fs::create_dir_all(staged.join("logs/2024")).unwrap();
+        std::fs::write(staged.join("logs/2024/app.log"), "ok").unwrap();
+
+        staging.commit().unwrap();
+        assert!(!staged.exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("logs/2024/app.log")).unwrap(),
+            "ok"
+        );
+
+        let staging = Staging::plan("az://acct/data/logs/", dest, true, false);
+        let staged = PathBuf::from(staging.azcopy_destination().unwrap());
+        std::fs::write(staged.join("half.log"), "trunc").unwrap();
+        staging.discard();
+        assert!(!staged.exists());
+        assert!(!dir.path().join("half.log").exists());
+    }
+
+    #[test]
+    fn test_staging_flat_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().to_str().unwrap();
+
+        let staging = Staging::plan("az://acct/data/reports/", dest, true, true);
+        let staged = PathBuf::from(staging.azcopy_destination().unwrap());
+        std::fs::create_dir_all(staged.join("reports/2024/q1")).unwrap();
+        std::fs::write(staged.join("reports/2024/q1/sales.csv"), "ok").unwrap();
+
+        let staged_file = staged.join("reports/2024/q1/sales.csv");
+        assert_eq!(
+            staging.final_destination(&staged_file.to_string_lossy()),
+            dir.path().join("sales.csv").to_string_lossy()
+        );
+
+        staging.commit().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("sales.csv")).unwrap(),
+            "ok"
+        );
+        assert!(!dir.path().join("reports").exists());
+    }
+
+    #[test]
+    fn test_find_name_collisions() {
+        let names: Vec<String> = ["a/data.csv", "b/c/data.csv", "a/other.csv", "readme"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            find_name_collisions(&names),
+            vec![vec!["a/data.csv".to_string(), "b/c/data.csv".to_string()]]
+        );
+        assert!(find_name_collisions(&names[2..]).is_empty());
+    }
+
+    #[test]
+    fn test_flat_copy_links_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("x/y")).unwrap();
+        std::fs::write(dir.path().join("x/y/a.txt"), "a").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+
+        let files = vec!["x/y/a.txt".to_string(), "b.txt".to_string()];
+        let copy = FlatCopy::create(dir.path(), &files).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(copy.root.join("a.txt")).unwrap(),
+            "a"
+        );
+        assert_eq!(
+            copy.original(&copy.root.join("a.txt").to_string_lossy()),
+            dir.path().join("x/y/a.txt").to_string_lossy()
+        );
+        assert_eq!(copy.original("/elsewhere/c.txt"), "/elsewhere/c.txt");
+
+        let root = copy.root.clone();
+        drop(copy);
+        assert!(!root.exists());
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_files_of_wildcard() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("site/css")).unwrap();
+        std::fs::write(dir.path().join("site/index.html"), "").unwrap();
+        std::fs::write(dir.path().join("site/css/main.css"), "").unwrap();
+        let source = format!("{}/site/*", dir.path().display());
+
+        let blobs = |recursive| {
+            let source = source.clone();
+            async move {
+                let mut blobs: Vec<String> = uploaded_files(
+                    &source,
+                    "az://acct/web/www/",
+                    recursive,
+                    true,
+                    SymlinkPolicy::Skip,
+                )
+                .await
+                .unwrap()
+                .pairs
+                .into_iter()
+                .map(|(_, blob)| blob)
+                .collect();
+                blobs.sort();
+                blobs
+            }
+        };
+        assert_eq!(
+            blobs(true).await,
+            vec!["www/css/main.css".to_string(), "www/index.html".to_string()]
+        );
+        assert_eq!(blobs(false).await, vec!["www/index.html".to_string()]);
+    }
+
+    #[test]
+    fn test_nested_dir_name() {
+        assert_eq!(nested_dir_name("/local/dir/", true).as_deref(), Some("dir"));
+        assert_eq!(
+            nested_dir_name("az://acct/data/logs", true).as_deref(),
+            Some("logs")
+        );
+        assert_eq!(
+            nested_dir_name("az://acct/data/", true).as_deref(),
+            Some("data")
+        );
+        assert_eq!(nested_dir_name("/local/dir/", false), None);
+        assert_eq!(nested_dir_name("/local/dir/*", true), None);
+    }
+
+    #[test]
+    fn test_preserve_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src.txt");
+        let dest = dir.path().join("dest.txt");
+        std::fs::write(&source, "data").unwrap();
+        std::fs::write(&dest, "data").unwrap();
+
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        preserve_timestamps(source.to_str().unwrap(), dest.to_str().unwrap()).unwrap();
+        assert_eq!(std::fs::metadata(&dest).unwrap().modified().unwrap(), old);
+    }
+
+    #[tokio::test]
+    async fn test_should_overwrite_missing_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src.txt");
+        std::fs::write(&source, "data").unwrap();
+        let dest = dir.path().join("missing.txt");
+
+        for policy in [OverwritePolicy::Never, OverwritePolicy::IfNewer] {
+            assert!(should_overwrite(
+                source.to_str().unwrap(),
+                dest.to_str().unwrap(),
+                policy,
+                &ScriptedPrompter::default()
+            )
+            .await
+            .unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_should_overwrite_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src.txt");
+        let dest = dir.path().join("dest.txt");
+        std::fs::write(&source, "new").unwrap();
+        std::fs::write(&dest, "old").unwrap();
+
+        // Make the destination older than the source
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&dest)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let (src, dst) = (source.to_str().unwrap(), dest.to_str().unwrap());
+        let prompter = ScriptedPrompter::new(["n", "y"]);
+        assert!(
+            should_overwrite(src, dst, OverwritePolicy::Always, &prompter)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !should_overwrite(src, dst, OverwritePolicy::Never, &prompter)
+                .await
+                .unwrap()
+        );
+        assert!(
+            should_overwrite(src, dst, OverwritePolicy::IfNewer, &prompter)
+                .await
+                .unwrap()
+        );
+        // Reversed: the source is now older than the destination
+        assert!(
+            !should_overwrite(dst, src, OverwritePolicy::IfNewer, &prompter)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !should_overwrite(src, dst, OverwritePolicy::Prompt, &prompter)
+                .await
+                .unwrap()
+        );
+        assert!(
+            should_overwrite(src, dst, OverwritePolicy::Prompt, &prompter)
+                .await
+                .unwrap()
+        );
+        assert_eq!(prompter.asked().len(), 2);
+    }
+}
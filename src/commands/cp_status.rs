@@ -0,0 +1,162 @@
+use anyhow::Result;
+use azure_storage_blobs::blob::CopyStatus;
+use colored::*;
+use serde::Serialize;
+
+use crate::azure::{AzureClient, CopyState};
+use crate::error::AzstError;
+use crate::output::{output_format, OutputFormat};
+use crate::utils::{format_size, parse_azure_uri, uri_base};
+
+/// One copy as `cp-status --format json` reports it
+#[derive(Debug, Serialize)]
+struct CopyReport {
+    uri: String,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    copy_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_copied: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+/// Report the server-side copies into a blob, or into every blob under a
+/// prefix when `recursive`, aborting the pending ones when `abort`
+///
+/// Fails when any of the copies failed, so scripts can check on copies left
+/// running by `cp --async`.
+pub async fn execute(uri: &str, recursive: bool, abort: bool) -> Result<()> {
+    let (account, container, path) = parse_azure_uri(uri)?;
+    let mut client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    };
+    let base = uri_base(uri, path.as_deref());
+
+    let mut copies = if recursive {
+        client.copy_states(&container, path.as_deref()).await?
+    } else {
+        let blob = path.ok_or_else(|| {
+            AzstError::InvalidUri(format!("'{}' must name a blob, or use -r", uri))
+        })?;
+        match client.copy_state(&container, &blob).await? {
+            Some(state) => vec![(blob, state)],
+            None => Vec::new(),
+        }
+    };
+    if copies.is_empty() {
+        return Err(AzstError::NotFound(format!("No copies into {} found", uri)).into());
+    }
+
+    if abort {
+        let mut aborted = 0;
+        for (blob, state) in copies
+            .iter_mut()
+            .filter(|(_, state)| state.status == CopyStatus::Pending)
+        {
+            if let Some(id) = &state.id {
+                client.abort_copy(&container, blob, id).await?;
+                state.status = CopyStatus::Aborted;
+                aborted += 1;
+            }
+        }
+        status!("{} Aborted {} pending copy(ies)", "✓".green(), aborted);
+    }
+
+    let failed = copies
+        .iter()
+        .filter(|(_, state)| state.status == CopyStatus::Failed)
+        .count();
+    if output_format() == OutputFormat::Json {
+        let reports: Vec<CopyReport> = copies
+            .into_iter()
+            .map(|(blob, state)| CopyReport {
+                uri: format!("{}/{}", base, blob),
+                status: state.status.to_string(),
+                copy_id: state.id,
+                bytes_copied: state.progress.map(|(copied, _)| copied),
+                bytes_total: state.progress.map(|(_, total)| total),
+                description: state.description,
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        let mut counts = [0; 4];
+        for (blob, state) in &copies {
+            counts[match state.status {
+                CopyStatus::Pending => 0,
+                CopyStatus::Success => 1,
+                CopyStatus::Aborted => 2,
+                CopyStatus::Failed => 3,
+            }] += 1;
+            println!("{}  {}/{}", describe(state), base, blob);
+        }
+        status!(
+            "{} pending, {} succeeded, {} aborted, {} failed",
+            counts[0],
+            counts[1],
+            counts[2],
+            counts[3]
+        );
+    }
+
+    if failed > 0 {
+        return Err(AzstError::TransferFailed(format!("{} copy(ies) failed", failed)).into());
+    }
+    Ok(())
+}
+
+/// Status and progress columns of a copy
+fn describe(state: &CopyState) -> String {
+    let status = format!("{:<8}", state.status.to_string());
+    let status = match state.status {
+        CopyStatus::Pending => status.yellow(),
+        CopyStatus::Success => status.green(),
+        CopyStatus::Aborted => status.normal(),
+        CopyStatus::Failed => status.red(),
+    };
+    let progress = match state.progress {
+        Some((copied, total)) if state.status == CopyStatus::Pending && total > 0 => format!(
+            "{:>3}% {} of {}",
+            copied * 100 / total,
+            format_size(copied),
+            format_size(total)
+        ),
+        Some((_, total)) => format_size(total),
+        None => String::new(),
+    };
+    let description = state
+        .description
+        .as_deref()
+        .filter(|_| state.status == CopyStatus::Failed)
+        .map(|description| format!(" ({})", description))
+        .unwrap_or_default();
+    format!("{} {:<28}{}", status, progress, description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_copy_progress() {
+        colored::control::set_override(false);
+        let pending = CopyState {
+            id: Some("id".to_string()),
+            status: CopyStatus::Pending,
+            progress: Some((512, 2048)),
+            description: None,
+        };
+        assert!(describe(&pending).starts_with("pending   25% 512 B of 2.0 KB"));
+
+        let failed = CopyState {
+            status: CopyStatus::Failed,
+            description: Some("500 InternalError".to_string()),
+            ..pending
+        };
+        assert!(describe(&failed).ends_with("(500 InternalError)"));
+    }
+}
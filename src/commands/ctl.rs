@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Result};
+use azure_storage_blobs::prelude::PublicAccess;
+use colored::*;
+
+use crate::azure::{AzureClient, ContainerSettings};
+use crate::output::{output_format, OutputFormat};
+use crate::utils::parse_azure_uri;
+
+// ============================================================================
+// Container Control - Public access and properties of containers
+// ============================================================================
+
+/// Who can read a container's blobs without credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PublicAccessLevel {
+    /// Only authorized requests
+    Off,
+    /// Anyone can read blobs, but not list them
+    Blob,
+    /// Anyone can read and list blobs
+    Container,
+}
+
+impl PublicAccessLevel {
+    fn to_sdk(self) -> PublicAccess {
+        match self {
+            PublicAccessLevel::Off => PublicAccess::None,
+            PublicAccessLevel::Blob => PublicAccess::Blob,
+            PublicAccessLevel::Container => PublicAccess::Container,
+        }
+    }
+}
+
+pub async fn set_public_access(uri: &str, level: PublicAccessLevel) -> Result<()> {
+    let (account, container) = parse_target(uri)?;
+    let container = container.ok_or_else(|| {
+        anyhow!(
+            "'{}' must name a container: az://<account>/<container>",
+            uri
+        )
+    })?;
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+    client
+        .set_container_public_access(&container, level.to_sdk())
+        .await?;
+
+    match level {
+        PublicAccessLevel::Off => status!(
+            "{} Disabled anonymous access to {}",
+            "✓".green(),
+            uri.cyan()
+        ),
+        PublicAccessLevel::Blob => status!(
+            "{} Anyone can now read blobs in {}",
+            "⚠".yellow(),
+            uri.cyan()
+        ),
+        PublicAccessLevel::Container => status!(
+            "{} Anyone can now list and read blobs in {}",
+            "⚠".yellow(),
+            uri.cyan()
+        ),
+    }
+    Ok(())
+}
+
+/// Show one container's settings, or the public access of every container
+/// in an account
+pub async fn show(uri: &str) -> Result<()> {
+    let (account, container) = parse_target(uri)?;
+    let mut client = AzureClient::new().with_storage_account(&account);
+
+    let Some(container) = container else {
+        let mut all = Vec::new();
+        for container in client.list_containers().await? {
+            all.push(client.container_settings(&container.name).await?);
+        }
+        if output_format() == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&all)?);
+        } else {
+            for settings in &all {
+                let level = format!("{:<9}", settings.public_access);
+                let level = if settings.public_access == "off" {
+                    level.normal()
+                } else {
+                    level.yellow()
+                };
+                println!("{} az://{}/{}/", level, account, settings.name);
+            }
+        }
+        return Ok(());
+    };
+
+    let settings = client.container_settings(&container).await?;
+    if output_format() == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&settings)?);
+    } else {
+        print_settings(&account, &settings);
+    }
+    Ok(())
+}
+
+fn print_settings(account: &str, settings: &ContainerSettings) {
+    println!("Container:      az://{}/{}/", account, settings.name);
+    println!("Public access:  {}", settings.public_access);
+    println!("Last modified:  {}", settings.last_modified);
+    println!("Lease:          {}", settings.lease_state);
+
+    let mut protections = Vec::new();
+    if settings.has_immutability_policy {
+        protections.push("retention policy");
+    }
+    if settings.has_legal_hold {
+        protections.push("legal hold");
+    }
+    if protections.is_empty() {
+        protections.push("none");
+    }
+    println!("Immutability:   {}", protections.join(", "));
+
+    if settings.metadata.is_empty() {
+        println!("Metadata:       none");
+    }
+    for (i, (key, value)) in settings.metadata.iter().enumerate() {
+        let label = if i == 0 { "Metadata:" } else { "" };
+        println!("{:<16}{}={}", label, key, value);
+    }
+
+    if settings.access_policies.is_empty() {
+        println!("Policies:       none");
+    }
+    for (i, policy) in settings.access_policies.iter().enumerate() {
+        let label = if i == 0 { "Policies:" } else { "" };
+        println!(
+            "{:<16}{} ({}, {} to {})",
+            label, policy.id, policy.permission, policy.start, policy.expiry
+        );
+    }
+}
+
+/// The account and, if named, the container of a `ctl` URI
+fn parse_target(uri: &str) -> Result<(String, Option<String>)> {
+    let (account, container, path) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!("URIs must include a storage account: az://<account>/[<container>]")
+    })?;
+    if path.is_some_and(|path| !path.is_empty()) {
+        return Err(anyhow!(
+            "'{}' names a path; access settings apply to whole containers",
+            uri
+        ));
+    }
+    Ok((account, Some(container).filter(|c| !c.is_empty())))
+}
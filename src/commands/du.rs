@@ -1,28 +1,194 @@
 use anyhow::{anyhow, Result};
+use futures::StreamExt;
 use std::collections::HashMap;
 
-use crate::azure::{AzureClient, BlobItem};
-use crate::output::create_writer;
-use crate::utils::{format_size, is_azure_uri, parse_azure_uri};
+use crate::azure::{AzureClient, BlobInfo, BlobItem};
+use crate::commands::ls::{friendly_listing_error, matching_containers};
+use crate::config;
+use crate::listing_cache::CachedListing;
+use crate::output::{create_writer, OutputWriter};
+use crate::utils::{format_size, is_azure_uri, parse_azure_uri, split_container_pattern, Glob};
+
+/// Access tiers --tier-breakdown gives a column each; blobs in any other
+/// tier (premium, or none reported) are added up under "other"
+const TIERS: [&str; 4] = ["hot", "cool", "cold", "archive"];
+
+/// Order of the per-directory sizes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortOrder {
+    /// By path
+    #[default]
+    Name,
+    /// Largest first
+    Size,
+}
+
+/// Which per-directory sizes are reported, and in what order
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Breakdown {
+    /// Deepest directory level reported, counted from the listed path
+    pub max_depth: Option<usize>,
+    pub sort: SortOrder,
+    /// Only the N largest directories, largest first
+    pub top: Option<usize>,
+}
+
+impl Breakdown {
+    /// Pick and order `(path, usage)` entries; `depth` says how far below the
+    /// listed path each one is
+    fn select<'a>(
+        &self,
+        entries: impl IntoIterator<Item = (&'a String, &'a Usage)>,
+        depth: impl Fn(&str) -> usize,
+    ) -> Vec<(&'a String, &'a Usage)> {
+        let mut selected: Vec<(&String, &Usage)> = entries
+            .into_iter()
+            .filter(|(path, _)| self.max_depth.is_none_or(|max| depth(path) <= max))
+            .collect();
+        if self.sort == SortOrder::Size || self.top.is_some() {
+            selected.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(b.0)));
+        } else {
+            selected.sort_by(|a, b| a.0.cmp(b.0));
+        }
+        if let Some(top) = self.top {
+            selected.truncate(top);
+        }
+        selected
+    }
+}
+
+/// Where the blobs are listed from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Listing {
+    /// One listing of the whole path
+    #[default]
+    Single,
+    /// Concurrent listings of its first-level prefixes, N at a time
+    Partitioned(usize),
+    /// The listings kept on disk by `ls -r`, `inventory` or `azst cache refresh`
+    Cached,
+}
+
+/// What is stored under a directory, added up blob by blob as the listing
+/// streams in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Usage {
+    bytes: u64,
+    objects: u64,
+    /// Bytes in each of `TIERS`, then in any other tier
+    tier_bytes: [u64; TIERS.len() + 1],
+}
+
+impl Usage {
+    fn add(&mut self, bytes: u64, tier: Option<&str>) {
+        self.bytes += bytes;
+        self.objects += 1;
+        let index = tier
+            .and_then(|tier| TIERS.iter().position(|t| t.eq_ignore_ascii_case(tier)))
+            .unwrap_or(TIERS.len());
+        self.tier_bytes[index] += bytes;
+    }
+
+    fn merge(&mut self, other: &Usage) {
+        self.bytes += other.bytes;
+        self.objects += other.objects;
+        for (bytes, other) in self.tier_bytes.iter_mut().zip(other.tier_bytes) {
+            *bytes += other;
+        }
+    }
+}
+
+/// What is printed for each directory besides its size and path
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Columns {
+    pub human_readable: bool,
+    /// Number of blobs or files
+    pub objects: bool,
+    /// Bytes per access tier
+    pub tiers: bool,
+}
+
+impl Columns {
+    fn size(&self, bytes: u64) -> String {
+        if self.human_readable {
+            format_size(bytes)
+        } else {
+            bytes.to_string()
+        }
+    }
+
+    /// The extra columns of a line, as (name, value)
+    fn details(&self, usage: &Usage) -> Vec<(&'static str, String)> {
+        let mut details = Vec::new();
+        if self.objects {
+            details.push(("objects", usage.objects.to_string()));
+        }
+        if self.tiers {
+            for (tier, bytes) in TIERS.iter().chain(&["other"]).zip(usage.tier_bytes) {
+                details.push((*tier, self.size(bytes)));
+            }
+        }
+        details
+    }
+
+    /// Name the columns when there are more than size and path
+    fn write_header(&self, writer: &dyn OutputWriter) {
+        let details = self.details(&Usage::default());
+        if !details.is_empty() {
+            let names: Vec<&str> = details.iter().map(|(name, _)| *name).collect();
+            writer.write_header(&format!("size\t{}\tpath", names.join("\t")));
+        }
+    }
+
+    fn write(&self, writer: &dyn OutputWriter, usage: &Usage, path: &str) {
+        writer.write_disk_usage(&self.size(usage.bytes), &self.details(usage), path);
+    }
+
+    fn write_total(&self, writer: &dyn OutputWriter, usage: &Usage, path: &str) {
+        writer.write_disk_usage_total(&self.size(usage.bytes), &self.details(usage), path);
+    }
+}
 
 /// Execute the disk usage command
 pub async fn execute(
     path: Option<&str>,
     summarize: bool,
-    human_readable: bool,
     total: bool,
+    breakdown: Breakdown,
+    columns: Columns,
+    listing: Listing,
     account: Option<&str>,
 ) -> Result<()> {
     match path {
         Some(p) if is_azure_uri(p) => {
             let mut azure_client = AzureClient::new();
-            if let Some(account_name) = account {
-                azure_client = azure_client.with_storage_account(account_name);
+            // Legacy az://container/path URIs: --account, else the default account
+            if let Some(account_name) = account.map(str::to_string).or_else(config::default_account)
+            {
+                azure_client = azure_client.with_storage_account(&account_name);
+            }
+            if !matches!(listing, Listing::Cached) {
+                azure_client.check_prerequisites().await?;
             }
-            azure_client.check_prerequisites().await?;
-            calculate_azure_usage(p, summarize, human_readable, total, &mut azure_client).await
+            calculate_azure_usage(
+                p,
+                summarize,
+                total,
+                breakdown,
+                columns,
+                listing,
+                &mut azure_client,
+            )
+            .await
+            .map_err(|e| friendly_listing_error(e, p, &azure_client))
         }
-        Some(p) => calculate_local_usage(p, summarize, human_readable, total).await,
+        Some(_) if columns.tiers => Err(anyhow!(
+            "--tier-breakdown can only be used with az:// paths"
+        )),
+        Some(_) if !matches!(listing, Listing::Single) => Err(anyhow!(
+            "--partitions and --cached can only be used with az:// paths"
+        )),
+        Some(p) => calculate_local_usage(p, summarize, total, breakdown, columns).await,
         None => Err(anyhow!("Path is required for du command")),
     }
 }
@@ -30,10 +196,47 @@ pub async fn execute(
 async fn calculate_azure_usage(
     path: &str,
     summarize: bool,
-    human_readable: bool,
     total: bool,
+    breakdown: Breakdown,
+    columns: Columns,
+    listing: Listing,
     azure_client: &mut AzureClient,
 ) -> Result<()> {
+    // `az://acct/logs-*/` totals each matching container like `du az://acct/`;
+    // with a path after the pattern, each container's copy of it is listed
+    if let Some((account_name, pattern, rest)) = split_container_pattern(path) {
+        let mut client = AzureClient::new().with_storage_account(account_name);
+        let containers =
+            matching_containers(&mut client, pattern, matches!(listing, Listing::Cached)).await?;
+        if rest.is_empty() {
+            return containers_usage(
+                containers,
+                summarize,
+                total,
+                breakdown,
+                columns,
+                listing,
+                &mut client,
+            )
+            .await;
+        }
+        for container in containers {
+            let location = format!("az://{}/{}/{}", account_name, container, rest);
+            Box::pin(calculate_azure_usage(
+                &location,
+                summarize,
+                total,
+                breakdown,
+                columns,
+                listing,
+                &mut client,
+            ))
+            .await
+            .map_err(|e| friendly_listing_error(e, &location, &client))?;
+        }
+        return Ok(());
+    }
+
     let (account, container, prefix) = parse_azure_uri(path)?;
 
     // Create azure client with account if specified in URI
@@ -51,66 +254,76 @@ async fn calculate_azure_usage(
 
     // Special case: If we have an account but no container, calculate usage for all containers
     if account.is_some() && container.is_empty() {
-        return calculate_all_containers_usage(summarize, human_readable, total, &mut client).await;
+        return calculate_all_containers_usage(
+            summarize,
+            total,
+            breakdown,
+            columns,
+            listing,
+            &mut client,
+        )
+        .await;
     }
 
-    // List all blobs recursively (no delimiter)
-    let blobs = client
-        .list_blobs(&container, prefix.as_deref(), None)
-        .await?;
+    // A wildcard adds up the blobs it matches and everything inside the
+    // directories it matches, listed from the prefix before it
+    let glob = prefix.as_deref().map(Glob::new).transpose()?.flatten();
+    let list_prefix = match &glob {
+        Some(glob) => Some(glob.prefix()).filter(|prefix| !prefix.is_empty()),
+        None => prefix.as_deref(),
+    };
+
+    // List all blobs recursively (no delimiter), adding each one up as its
+    // page arrives rather than holding the whole listing
+    let mut total_usage = Usage::default();
+    let mut dir_usage: HashMap<String, Usage> = HashMap::new();
+    for_each_blob(&mut client, &container, list_prefix, listing, |blob| {
+        if glob
+            .as_ref()
+            .is_some_and(|glob| !glob.selects(&blob.name, true))
+        {
+            return;
+        }
+        let size = blob.properties.content_length;
+        let tier = blob.properties.access_tier.as_deref();
+        total_usage.add(size, tier);
+        if !summarize {
+            // Get the relative path (strip base prefix if present)
+            let relative_path = match list_prefix {
+                Some(prefix) => blob.name.strip_prefix(prefix).unwrap_or(&blob.name),
+                None => &blob.name,
+            };
+            add_to_directories(&mut dir_usage, relative_path, size, tier);
+        }
+    })
+    .await?;
+
+    let writer = create_writer();
+    columns.write_header(writer.as_ref());
+    let display_path = format!(
+        "az://{}/{}{}",
+        actual_account,
+        container,
+        prefix.as_deref().unwrap_or("")
+    );
 
     if summarize {
         // Calculate total size only
-        let total_size = calculate_total_size(&blobs);
-        let size_str = if human_readable {
-            format_size(total_size)
-        } else {
-            total_size.to_string()
-        };
-
-        let display_path = format!(
-            "az://{}/{}{}",
-            actual_account,
-            container,
-            prefix.as_deref().unwrap_or("")
-        );
-        println!("{}\t{}", size_str, display_path);
+        columns.write(writer.as_ref(), &total_usage, &display_path);
     } else {
-        // Calculate size for each directory level
-        let dir_sizes = calculate_directory_sizes(&blobs, prefix.as_deref());
-
-        // Sort by path for consistent output
-        let mut sorted_dirs: Vec<_> = dir_sizes.iter().collect();
-        sorted_dirs.sort_by(|a, b| a.0.cmp(b.0));
-
-        let writer = create_writer();
-
-        for (dir_path, size) in sorted_dirs {
-            let size_str = if human_readable {
-                format_size(*size)
-            } else {
-                size.to_string()
-            };
-
-            let display_path = format!("az://{}/{}/{}", actual_account, container, dir_path);
-            writer.write_disk_usage(&size_str, &display_path);
+        // "a/b/" is two levels below the listed prefix
+        let sorted_dirs = breakdown.select(&dir_usage, |dir_path| {
+            dir_path.trim_end_matches('/').split('/').count()
+        });
+
+        for (dir_path, usage) in sorted_dirs {
+            let dir_display_path = format!("az://{}/{}/{}", actual_account, container, dir_path);
+            columns.write(writer.as_ref(), usage, &dir_display_path);
         }
 
         // Print total if requested
         if total {
-            let total_size = calculate_total_size(&blobs);
-            let size_str = if human_readable {
-                format_size(total_size)
-            } else {
-                total_size.to_string()
-            };
-            let display_path = format!(
-                "az://{}/{}{}",
-                actual_account,
-                container,
-                prefix.as_deref().unwrap_or("")
-            );
-            writer.write_disk_usage_total(&size_str, &display_path);
+            columns.write_total(writer.as_ref(), &total_usage, &display_path);
         }
     }
 
@@ -119,105 +332,151 @@ async fn calculate_azure_usage(
 
 async fn calculate_all_containers_usage(
     summarize: bool,
-    human_readable: bool,
     total: bool,
+    breakdown: Breakdown,
+    columns: Columns,
+    listing: Listing,
     client: &mut AzureClient,
 ) -> Result<()> {
-    let containers = client.list_containers().await?;
+    let containers = match listing {
+        // Every container with a cached listing
+        Listing::Cached => CachedListing::containers(
+            client
+                .get_storage_account()
+                .ok_or_else(|| anyhow!("Storage account not configured"))?,
+        )?,
+        _ => client
+            .list_containers()
+            .await?
+            .into_iter()
+            .map(|container| container.name)
+            .collect(),
+    };
 
     if containers.is_empty() {
         println!("No containers found");
         return Ok(());
     }
+    containers_usage(
+        containers, summarize, total, breakdown, columns, listing, client,
+    )
+    .await
+}
 
+/// A row per container and the account total
+async fn containers_usage(
+    containers: Vec<String>,
+    summarize: bool,
+    total: bool,
+    breakdown: Breakdown,
+    columns: Columns,
+    listing: Listing,
+    client: &mut AzureClient,
+) -> Result<()> {
     let actual_account = client
         .get_storage_account()
         .ok_or_else(|| anyhow!("Storage account not configured"))?
         .to_string();
 
     let writer = create_writer();
-    let mut grand_total: u64 = 0;
+    let mut grand_total = Usage::default();
+    let mut container_usage: HashMap<String, Usage> = HashMap::new();
 
     for container in containers {
-        let blobs = client.list_blobs(&container.name, None, None).await?;
-        let container_size = calculate_total_size(&blobs);
-        grand_total += container_size;
+        let mut usage = Usage::default();
+        for_each_blob(client, &container, None, listing, |blob| {
+            usage.add(
+                blob.properties.content_length,
+                blob.properties.access_tier.as_deref(),
+            );
+        })
+        .await?;
+        grand_total.merge(&usage);
+        container_usage.insert(container, usage);
+    }
 
-        if !summarize {
-            let size_str = if human_readable {
-                format_size(container_size)
-            } else {
-                container_size.to_string()
-            };
-            let display_path = format!("az://{}/{}/", actual_account, container.name);
-            writer.write_disk_usage(&size_str, &display_path);
+    columns.write_header(writer.as_ref());
+    if !summarize {
+        // Containers are the first level below the account
+        for (name, usage) in breakdown.select(&container_usage, |_| 1) {
+            let display_path = format!("az://{}/{}/", actual_account, name);
+            columns.write(writer.as_ref(), usage, &display_path);
         }
     }
 
     if summarize || total {
-        let size_str = if human_readable {
-            format_size(grand_total)
-        } else {
-            grand_total.to_string()
-        };
         let display_path = format!("az://{}/", actual_account);
         if summarize {
-            writer.write_disk_usage(&size_str, &display_path);
+            columns.write(writer.as_ref(), &grand_total, &display_path);
         } else {
-            writer.write_disk_usage_total(&size_str, &display_path);
+            columns.write_total(writer.as_ref(), &grand_total, &display_path);
         }
     }
 
     Ok(())
 }
 
-fn calculate_total_size(blobs: &[BlobItem]) -> u64 {
-    blobs
-        .iter()
-        .map(|item| match item {
-            BlobItem::Blob(blob) => blob.properties.content_length,
-            BlobItem::Prefix(_) => 0, // Prefixes don't have size
-        })
-        .sum()
-}
-
-fn calculate_directory_sizes(
-    blobs: &[BlobItem],
-    base_prefix: Option<&str>,
-) -> HashMap<String, u64> {
-    let mut dir_sizes: HashMap<String, u64> = HashMap::new();
-
-    for item in blobs {
-        if let BlobItem::Blob(blob) = item {
-            let size = blob.properties.content_length;
-
-            // Get the relative path (strip base prefix if present)
-            let relative_path = if let Some(prefix) = base_prefix {
-                blob.name.strip_prefix(prefix).unwrap_or(&blob.name)
-            } else {
-                &blob.name
-            };
-
-            // Split the path into segments and accumulate sizes for each directory level
-            let segments: Vec<&str> = relative_path.split('/').collect();
-
-            // Add size to each directory level
-            // For path "a/b/c/file.txt", add to "a/", "a/b/", "a/b/c/"
-            for i in 1..segments.len() {
-                let dir_path = segments[..i].join("/") + "/";
-                *dir_sizes.entry(dir_path).or_insert(0) += size;
+/// Call `add` for every blob under a prefix as the listing streams in
+async fn for_each_blob(
+    client: &mut AzureClient,
+    container: &str,
+    prefix: Option<&str>,
+    listing: Listing,
+    mut add: impl FnMut(&BlobInfo),
+) -> Result<()> {
+    match listing {
+        Listing::Single => Ok(client
+            .list_blobs_with_callback(container, prefix, None, |items| {
+                for item in items {
+                    if let BlobItem::Blob(blob) = item {
+                        add(&blob);
+                    }
+                }
+                Ok(())
+            })
+            .await?),
+        Listing::Partitioned(partitions) => {
+            let mut pages = client
+                .partitioned_blob_pages(container, prefix, partitions, false)
+                .await?;
+            while let Some(page) = pages.next().await {
+                page?.iter().for_each(&mut add);
             }
+            Ok(())
+        }
+        Listing::Cached => {
+            let account = client
+                .get_storage_account()
+                .ok_or_else(|| anyhow!("Storage account not configured"))?;
+            let cached = CachedListing::load(account, container, prefix)?;
+            cached.warn_if_stale();
+            cached.blobs_under(prefix).iter().for_each(add);
+            Ok(())
         }
     }
+}
 
-    dir_sizes
+/// Add a blob to every directory above it: for "a/b/c/file.txt", to "a/",
+/// "a/b/" and "a/b/c/"
+fn add_to_directories(
+    dir_usage: &mut HashMap<String, Usage>,
+    relative_path: &str,
+    size: u64,
+    tier: Option<&str>,
+) {
+    let segments: Vec<&str> = relative_path.split('/').collect();
+    for i in 1..segments.len() {
+        let dir_path = segments[..i].join("/") + "/";
+        dir_usage.entry(dir_path).or_default().add(size, tier);
+    }
 }
 
 async fn calculate_local_usage(
     path: &str,
     summarize: bool,
-    human_readable: bool,
     total: bool,
+    breakdown: Breakdown,
+    columns: Columns,
 ) -> Result<()> {
     use std::path::Path;
     use tokio::fs;
@@ -228,16 +487,15 @@ async fn calculate_local_usage(
         return Err(anyhow!("Path '{}' does not exist", path));
     }
 
+    let writer = create_writer();
+
     if path_obj.is_file() {
         // Single file - just show its size
         let metadata = fs::metadata(path).await?;
-        let size = metadata.len();
-        let size_str = if human_readable {
-            format_size(size)
-        } else {
-            size.to_string()
-        };
-        println!("{}\t{}", size_str, path);
+        let mut usage = Usage::default();
+        usage.add(metadata.len(), None);
+        columns.write_header(writer.as_ref());
+        columns.write(writer.as_ref(), &usage, path);
         return Ok(());
     }
 
@@ -246,43 +504,30 @@ async fn calculate_local_usage(
     }
 
     // Calculate directory sizes
-    let dir_sizes = calculate_local_directory_sizes(path, summarize).await?;
-
-    let writer = create_writer();
+    let dir_usage = calculate_local_directory_sizes(path, summarize).await?;
 
+    columns.write_header(writer.as_ref());
     if summarize {
         // Just show the total for the main directory
-        if let Some(total_size) = dir_sizes.get(path) {
-            let size_str = if human_readable {
-                format_size(*total_size)
-            } else {
-                total_size.to_string()
-            };
-            writer.write_disk_usage(&size_str, path);
+        if let Some(usage) = dir_usage.get(path) {
+            columns.write(writer.as_ref(), usage, path);
         }
     } else {
-        // Show all subdirectories
-        let mut sorted_dirs: Vec<_> = dir_sizes.iter().collect();
-        sorted_dirs.sort_by(|a, b| a.0.cmp(b.0));
-
-        for (dir_path, size) in sorted_dirs {
-            let size_str = if human_readable {
-                format_size(*size)
-            } else {
-                size.to_string()
-            };
-            writer.write_disk_usage(&size_str, dir_path);
+        // Show subdirectories, the listed directory itself at depth 0
+        let sorted_dirs = breakdown.select(&dir_usage, |dir_path| {
+            Path::new(dir_path)
+                .strip_prefix(path)
+                .map_or(0, |relative| relative.components().count())
+        });
+
+        for (dir_path, usage) in sorted_dirs {
+            columns.write(writer.as_ref(), usage, dir_path);
         }
 
         // Print total if requested
         if total {
-            if let Some(total_size) = dir_sizes.get(path) {
-                let size_str = if human_readable {
-                    format_size(*total_size)
-                } else {
-                    total_size.to_string()
-                };
-                writer.write_disk_usage_total(&size_str, path);
+            if let Some(usage) = dir_usage.get(path) {
+                columns.write_total(writer.as_ref(), usage, path);
             }
         }
     }
@@ -293,21 +538,21 @@ async fn calculate_local_usage(
 async fn calculate_local_directory_sizes(
     root_path: &str,
     summarize_only: bool,
-) -> Result<HashMap<String, u64>> {
+) -> Result<HashMap<String, Usage>> {
     use std::path::Path;
     use tokio::fs;
 
-    let mut dir_sizes: HashMap<String, u64> = HashMap::new();
+    let mut dir_usage: HashMap<String, Usage> = HashMap::new();
 
     // Recursive function to traverse directory tree
     fn traverse_dir<'a>(
         dir_path: &'a Path,
         _root: &'a Path,
-        dir_sizes: &'a mut HashMap<String, u64>,
+        dir_usage: &'a mut HashMap<String, Usage>,
         summarize_only: bool,
-    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<u64>> + Send + 'a>> {
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Usage>> + Send + 'a>> {
         Box::pin(async move {
-            let mut total_size: u64 = 0;
+            let mut total_usage = Usage::default();
             let mut entries = fs::read_dir(dir_path).await?;
 
             while let Some(entry) = entries.next_entry().await? {
@@ -315,37 +560,108 @@ async fn calculate_local_directory_sizes(
                 let metadata = entry.metadata().await?;
 
                 if metadata.is_file() {
-                    total_size += metadata.len();
+                    total_usage.add(metadata.len(), None);
                 } else if metadata.is_dir() {
                     // Recursively calculate subdirectory size
-                    let subdir_size =
-                        traverse_dir(&entry_path, _root, dir_sizes, summarize_only).await?;
-                    total_size += subdir_size;
+                    let subdir_usage =
+                        traverse_dir(&entry_path, _root, dir_usage, summarize_only).await?;
+                    total_usage.merge(&subdir_usage);
 
                     // Store this subdirectory's size unless we're only summarizing the root
                     if !summarize_only {
                         if let Some(path_str) = entry_path.to_str() {
-                            dir_sizes.insert(path_str.to_string(), subdir_size);
+                            dir_usage.insert(path_str.to_string(), subdir_usage);
                         }
                     }
                 }
             }
 
-            Ok(total_size)
+            Ok(total_usage)
         })
     }
 
     let root = Path::new(root_path);
-    let total_size = traverse_dir(root, root, &mut dir_sizes, summarize_only).await?;
+    let total_usage = traverse_dir(root, root, &mut dir_usage, summarize_only).await?;
 
     // Always store the root directory's total size
-    dir_sizes.insert(root_path.to_string(), total_size);
+    dir_usage.insert(root_path.to_string(), total_usage);
 
-    Ok(dir_sizes)
+    Ok(dir_usage)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakdown_select() {
+        let sizes: HashMap<String, Usage> = [("a/", 30), ("a/b/", 20), ("c/", 50), ("d/", 5)]
+            .into_iter()
+            .map(|(path, size)| {
+                let mut usage = Usage::default();
+                usage.add(size, None);
+                (path.to_string(), usage)
+            })
+            .collect();
+        let depth = |path: &str| path.trim_end_matches('/').split('/').count();
+        let paths = |breakdown: Breakdown| -> Vec<String> {
+            breakdown
+                .select(&sizes, depth)
+                .into_iter()
+                .map(|(path, _)| path.clone())
+                .collect()
+        };
+
+        assert_eq!(paths(Breakdown::default()), ["a/", "a/b/", "c/", "d/"]);
+        assert_eq!(
+            paths(Breakdown {
+                max_depth: Some(1),
+                sort: SortOrder::Size,
+                top: None,
+            }),
+            ["c/", "a/", "d/"]
+        );
+        assert_eq!(
+            paths(Breakdown {
+                top: Some(2),
+                ..Breakdown::default()
+            }),
+            ["c/", "a/"]
+        );
+    }
+
+    #[test]
+    fn test_add_to_directories_counts_objects_and_tiers() {
+        let mut dir_usage = HashMap::new();
+        add_to_directories(&mut dir_usage, "a/b/one.bin", 10, Some("Hot"));
+        add_to_directories(&mut dir_usage, "a/two.bin", 5, Some("Archive"));
+        add_to_directories(&mut dir_usage, "a/b/three.bin", 1, Some("P10"));
+        add_to_directories(&mut dir_usage, "top.bin", 100, None);
+
+        let a = &dir_usage["a/"];
+        assert_eq!((a.bytes, a.objects), (16, 3));
+        assert_eq!(a.tier_bytes, [10, 0, 0, 5, 1]);
+        assert_eq!(dir_usage["a/b/"].objects, 2);
+        assert_eq!(dir_usage.len(), 2);
+
+        let columns = Columns {
+            human_readable: false,
+            objects: true,
+            tiers: true,
+        };
+        assert_eq!(
+            columns.details(a),
+            [
+                ("objects", "3".to_string()),
+                ("hot", "10".to_string()),
+                ("cool", "0".to_string()),
+                ("cold", "0".to_string()),
+                ("archive", "5".to_string()),
+                ("other", "1".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_du_container_docs() {
         // Test case: azst du az://account/container/
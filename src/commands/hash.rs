@@ -0,0 +1,159 @@
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use colored::*;
+use std::io::Read;
+
+use crate::azure::AzureClient;
+use crate::blob_reader::hash_blob;
+use crate::checksum::{to_hex, Digests, HashSelection, Hasher};
+use crate::utils::{is_azure_uri, is_directory, parse_azure_uri};
+
+pub async fn execute(paths: &[String], md5: bool, crc64: bool, hex: bool) -> Result<()> {
+    // Like gsutil, no selection means every supported hash
+    let selection = if md5 || crc64 {
+        HashSelection { md5, crc64 }
+    } else {
+        HashSelection {
+            md5: true,
+            crc64: true,
+        }
+    };
+
+    for path in paths {
+        let digests = if is_azure_uri(path) {
+            hash_remote(path, selection).await?
+        } else {
+            hash_local(path, selection)?
+        };
+        print_digests(path, &digests, hex);
+    }
+
+    Ok(())
+}
+
+fn hash_local(path: &str, selection: HashSelection) -> Result<Digests> {
+    if is_directory(path) {
+        return Err(anyhow!("'{}' is a directory", path));
+    }
+
+    let mut file =
+        std::fs::File::open(path).map_err(|e| anyhow!("Failed to open '{}': {}", path, e))?;
+    let mut hasher = Hasher::new(selection);
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Hash a blob, using its stored Content-MD5 when available and streaming the
+/// content only for hashes Azure doesn't store
+async fn hash_remote(uri: &str, selection: HashSelection) -> Result<Digests> {
+    let (account, container, blob) = parse_azure_uri(uri)?;
+    let blob = blob
+        .filter(|b| !b.ends_with('/'))
+        .ok_or_else(|| anyhow!("'{}' is not a blob", uri))?;
+
+    let mut client = AzureClient::new();
+    if let Some(account) = account {
+        client = client.with_storage_account(&account);
+    }
+    client.check_prerequisites().await?;
+
+    let stored_md5 = if selection.md5 {
+        client
+            .get_blob_properties(&container, &blob)
+            .await?
+            .properties
+            .content_md5
+    } else {
+        None
+    };
+
+    let remaining = HashSelection {
+        md5: selection.md5 && stored_md5.is_none(),
+        crc64: selection.crc64,
+    };
+    let mut digests = if remaining.md5 || remaining.crc64 {
+        hash_blob(client, &container, &blob, remaining, None).await?
+    } else {
+        Digests::default()
+    };
+
+    if let Some(stored) = stored_md5 {
+        digests.md5 = Some(from_hex(&stored)?);
+    }
+
+    Ok(digests)
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid hex digest '{}'", hex))
+        })
+        .collect()
+}
+
+fn print_digests(path: &str, digests: &Digests, hex: bool) {
+    let encode = |bytes: &[u8]| {
+        if hex {
+            to_hex(bytes)
+        } else {
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        }
+    };
+
+    println!(
+        "Hashes [{}] for {}:",
+        if hex { "hex" } else { "base64" },
+        path.cyan()
+    );
+    if let Some(crc64) = &digests.crc64 {
+        println!("\tHash (crc64):\t{}", encode(crc64));
+    }
+    if let Some(md5) = &digests.md5 {
+        println!("\tHash (md5):\t{}", encode(md5));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_local_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.txt");
+        std::fs::write(&path, "hello world").unwrap();
+
+        let digests = hash_local(
+            path.to_str().unwrap(),
+            HashSelection {
+                md5: true,
+                crc64: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_hex(&digests.md5.unwrap()),
+            "5eb63bbbe01eeed093cb22bb8f5acdc3"
+        );
+        assert_eq!(digests.crc64.unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(from_hex("00ab10").unwrap(), vec![0x00, 0xab, 0x10]);
+        assert!(from_hex("zz").is_err());
+    }
+}
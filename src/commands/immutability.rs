@@ -0,0 +1,271 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::*;
+
+use crate::azure::AzureClient;
+use crate::utils::parse_azure_uri;
+
+// ============================================================================
+// Immutability - Retention policies and legal holds (WORM storage)
+// ============================================================================
+
+/// Longest retention Azure accepts for a container, in days
+pub const MAX_RETENTION_DAYS: u32 = 146_000;
+
+/// Parse a retention end: an RFC 3339 time or a date (midnight UTC)
+pub fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        })
+        .map_err(|_| {
+            format!(
+                "invalid date '{}': expected YYYY-MM-DD or an RFC 3339 time",
+                value
+            )
+        })
+}
+
+/// Settings apply to a whole container or to the current version of a blob
+enum Target {
+    Container(String),
+    Blob(String, String),
+}
+
+fn target(uri: &str) -> Result<(AzureClient, Target)> {
+    let (account, container, blob) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!("URIs must include a storage account: az://<account>/<container>[/<blob>]")
+    })?;
+    if container.is_empty() {
+        return Err(anyhow!("'{}' must name a container or blob", uri));
+    }
+    let target = match blob.filter(|b| !b.is_empty()) {
+        Some(blob) if blob.ends_with('/') => {
+            return Err(anyhow!(
+                "'{}' is a directory; retention and legal holds apply to containers and blobs",
+                uri
+            ))
+        }
+        Some(blob) => Target::Blob(container, blob),
+        None => Target::Container(container),
+    };
+    Ok((AzureClient::new().with_storage_account(&account), target))
+}
+
+/// Set a time-based retention policy: until a date for a blob, or a number
+/// of days after creation for every blob in a container
+pub async fn set_retention(
+    uri: &str,
+    days: Option<u32>,
+    until: Option<DateTime<Utc>>,
+    lock: bool,
+) -> Result<()> {
+    let (mut client, target) = target(uri)?;
+    match target {
+        Target::Blob(container, blob) => {
+            let until = match (until, days) {
+                (Some(until), _) => until,
+                (None, Some(days)) => Utc::now() + chrono::Duration::days(days.into()),
+                (None, None) => return Err(anyhow!("Pass --days or --until")),
+            };
+            if until <= Utc::now() {
+                return Err(anyhow!("The retention must end in the future"));
+            }
+            client
+                .set_blob_immutability_policy(&container, &blob, until, lock)
+                .await?;
+            status!(
+                "{} {} is retained until {}",
+                "✓".green(),
+                uri.cyan(),
+                until.to_rfc3339()
+            );
+        }
+        Target::Container(container) => {
+            if until.is_some() {
+                return Err(anyhow!(
+                    "Containers retain blobs for a number of days; use --days"
+                ));
+            }
+            let days = days.ok_or_else(|| anyhow!("Pass --days"))?;
+            client
+                .set_container_retention(&container, days.into(), lock)
+                .await?;
+            status!(
+                "{} Blobs in {} are retained for {} day(s) after creation",
+                "✓".green(),
+                uri.cyan(),
+                days
+            );
+        }
+    }
+    if lock {
+        status!(
+            "{} The policy is locked: it can be extended but never shortened or removed",
+            "⚠".yellow()
+        );
+    }
+    Ok(())
+}
+
+/// Print the retention policy and legal hold of a container or blob
+pub async fn get(uri: &str) -> Result<()> {
+    let (mut client, target) = target(uri)?;
+    match target {
+        Target::Blob(container, blob) => {
+            let settings = client.blob_immutability(&container, &blob).await?;
+            match (&settings.until, &settings.mode) {
+                (Some(until), mode) => println!(
+                    "Retention:  until {} ({})",
+                    until,
+                    mode.as_deref().unwrap_or("unlocked").to_lowercase()
+                ),
+                (None, _) => println!("Retention:  none"),
+            }
+            println!(
+                "Legal hold: {}",
+                if settings.legal_hold { "on" } else { "off" }
+            );
+        }
+        Target::Container(container) => {
+            let settings = client.container_immutability(&container).await?;
+            match settings.retention_days {
+                Some(days) => println!(
+                    "Retention:  {} day(s) after creation ({})",
+                    days,
+                    if settings.locked {
+                        "locked"
+                    } else {
+                        "unlocked"
+                    }
+                ),
+                None => println!("Retention:  none"),
+            }
+            if settings.legal_hold_tags.is_empty() {
+                println!("Legal hold: off");
+            } else {
+                println!("Legal hold: on ({})", settings.legal_hold_tags.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove an unlocked retention policy
+pub async fn clear_retention(uri: &str) -> Result<()> {
+    let (mut client, target) = target(uri)?;
+    let removed = match target {
+        Target::Blob(container, blob) => {
+            client
+                .delete_blob_immutability_policy(&container, &blob)
+                .await?;
+            true
+        }
+        Target::Container(container) => client.delete_container_retention(&container).await?,
+    };
+    if removed {
+        status!(
+            "{} Removed the retention policy of {}",
+            "✓".green(),
+            uri.cyan()
+        );
+    } else {
+        status!("{} {} has no retention policy", "ℹ".blue(), uri.cyan());
+    }
+    Ok(())
+}
+
+/// Place a legal hold; containers hold one per tag, e.g. a case number
+pub async fn set_legal_hold(uri: &str, tags: &[String]) -> Result<()> {
+    let (mut client, target) = target(uri)?;
+    match target {
+        Target::Blob(container, blob) => {
+            if !tags.is_empty() {
+                return Err(anyhow!("Legal holds on blobs have no tags"));
+            }
+            client.set_blob_legal_hold(&container, &blob, true).await?;
+            status!("{} Placed a legal hold on {}", "✓".green(), uri.cyan());
+        }
+        Target::Container(container) => {
+            if tags.is_empty() {
+                return Err(anyhow!("Legal holds on containers need at least one --tag"));
+            }
+            let held = client
+                .set_container_legal_hold(&container, tags, true)
+                .await?;
+            status!(
+                "{} {} is under legal hold ({})",
+                "✓".green(),
+                uri.cyan(),
+                held.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Lift a legal hold; on a container, the given tags or else all of them
+pub async fn clear_legal_hold(uri: &str, tags: &[String]) -> Result<()> {
+    let (mut client, target) = target(uri)?;
+    match target {
+        Target::Blob(container, blob) => {
+            if !tags.is_empty() {
+                return Err(anyhow!("Legal holds on blobs have no tags"));
+            }
+            client.set_blob_legal_hold(&container, &blob, false).await?;
+            status!("{} Lifted the legal hold on {}", "✓".green(), uri.cyan());
+        }
+        Target::Container(container) => {
+            let tags = if tags.is_empty() {
+                client
+                    .container_immutability(&container)
+                    .await?
+                    .legal_hold_tags
+            } else {
+                tags.to_vec()
+            };
+            if tags.is_empty() {
+                status!("{} {} has no legal hold", "ℹ".blue(), uri.cyan());
+                return Ok(());
+            }
+            let held = client
+                .set_container_legal_hold(&container, &tags, false)
+                .await?;
+            if held.is_empty() {
+                status!("{} Lifted the legal hold on {}", "✓".green(), uri.cyan());
+            } else {
+                status!(
+                    "{} {} is still held by {}",
+                    "ℹ".blue(),
+                    uri.cyan(),
+                    held.join(", ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_until() {
+        assert_eq!(
+            parse_until("2030-01-31").unwrap().to_rfc3339(),
+            "2030-01-31T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_until("2030-01-31T12:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2030-01-31T10:00:00+00:00"
+        );
+        assert!(parse_until("31/01/2030").is_err());
+        assert!(parse_until("").is_err());
+    }
+}
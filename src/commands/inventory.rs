@@ -0,0 +1,628 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use futures::StreamExt;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::azure::{AzureClient, BlobInfo};
+use crate::listing_cache::CachedListing;
+use crate::output::join_fields;
+use crate::utils::parse_azure_uri;
+
+// ============================================================================
+// Inventory - The full listing of a container or account, written to a file
+// ============================================================================
+
+/// Fields of each record, in column order
+const COLUMNS: [&str; 8] = [
+    "container",
+    "name",
+    "size",
+    "modified",
+    "tier",
+    "etag",
+    "content_md5",
+    "tags",
+];
+
+/// Characters escaped in the `key=value&...` form of tags
+const TAG_ENCODE_SET: &AsciiSet = &CONTROLS.add(b'%').add(b'&').add(b'=');
+
+/// File format of an inventory, chosen by the extension of `--out`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InventoryFormat {
+    Csv,
+    Tsv,
+    /// One JSON object per line
+    Jsonl,
+    Parquet,
+}
+
+impl InventoryFormat {
+    fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("csv") => Ok(InventoryFormat::Csv),
+            Some("tsv") => Ok(InventoryFormat::Tsv),
+            Some("jsonl") | Some("ndjson") => Ok(InventoryFormat::Jsonl),
+            Some("parquet") => Ok(InventoryFormat::Parquet),
+            _ => Err(anyhow!(
+                "Can't tell the format of '{}': name it .csv, .tsv, .jsonl or .parquet",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// One blob of the inventory
+#[derive(Debug, Serialize)]
+struct Record {
+    container: String,
+    name: String,
+    size: u64,
+    /// Last modified time, RFC 3339 in UTC
+    modified: String,
+    /// The same as a Unix timestamp, for Parquet's timestamp column
+    #[serde(skip)]
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    modified_unix: i64,
+    tier: Option<String>,
+    etag: Option<String>,
+    /// Content-MD5 in hex, when the blob has one
+    content_md5: Option<String>,
+    tags: BTreeMap<String, String>,
+}
+
+impl Record {
+    fn new(container: &str, blob: BlobInfo) -> Self {
+        let properties = blob.properties;
+        Record {
+            container: container.to_string(),
+            name: blob.name,
+            size: properties.content_length,
+            modified: chrono::DateTime::from_timestamp(properties.last_modified_unix, 0)
+                .map(|time| time.to_rfc3339())
+                .unwrap_or(properties.last_modified),
+            modified_unix: properties.last_modified_unix,
+            tier: properties.access_tier,
+            etag: properties.etag,
+            content_md5: properties.content_md5,
+            tags: blob.tags.unwrap_or_default(),
+        }
+    }
+
+    /// Tags as `key=value&key2=value2`, the form `x-ms-tags` takes
+    fn tags_query(&self) -> String {
+        self.tags
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(key, TAG_ENCODE_SET),
+                    utf8_percent_encode(value, TAG_ENCODE_SET)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Values in `COLUMNS` order, for CSV and TSV
+    fn fields(&self) -> [String; 8] {
+        let or_empty = |value: &Option<String>| value.clone().unwrap_or_default();
+        [
+            self.container.clone(),
+            self.name.clone(),
+            self.size.to_string(),
+            self.modified.clone(),
+            or_empty(&self.tier),
+            or_empty(&self.etag),
+            or_empty(&self.content_md5),
+            self.tags_query(),
+        ]
+    }
+}
+
+/// Where an interrupted export stopped, kept in `<out>.resume` until it
+/// completes
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Checkpoint {
+    /// The az:// location exported, so exporting another one starts over
+    location: String,
+    /// Container to continue with; empty once the last one is done
+    container: String,
+    /// Listing marker to continue the container from
+    marker: Option<String>,
+    /// Length of the output after the last page written
+    bytes: u64,
+    blobs: u64,
+}
+
+impl Checkpoint {
+    fn path(out: &Path) -> PathBuf {
+        let mut path = out.as_os_str().to_owned();
+        path.push(".resume");
+        PathBuf::from(path)
+    }
+
+    /// The checkpoint of an interrupted export of `location` to `out`, if any
+    fn load(out: &Path, location: &str) -> Result<Option<Self>> {
+        let path = Self::path(out);
+        if !path.exists() || !out.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&contents)
+            .with_context(|| format!("{} is not an inventory checkpoint", path.display()))?;
+        Ok((checkpoint.location == location).then_some(checkpoint))
+    }
+
+    fn remove(out: &Path) -> Result<()> {
+        let path = Self::path(out);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, out: &Path) -> Result<()> {
+        // Written aside and renamed, so a crash never leaves half a checkpoint
+        let path = Self::path(out);
+        let partial = path.with_extension("resume.partial");
+        std::fs::write(&partial, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        std::fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// CSV, TSV or JSON Lines output, flushed after every page
+struct TextSink {
+    format: InventoryFormat,
+    file: BufWriter<File>,
+}
+
+impl TextSink {
+    /// Start `path`, or with `resume_at`, cut it back to the end of the last
+    /// page a checkpoint recorded and append from there
+    fn open(path: &Path, format: InventoryFormat, resume_at: Option<u64>) -> Result<Self> {
+        let file = match resume_at {
+            Some(length) => {
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                file.set_len(length)?;
+                file.seek(SeekFrom::End(0))?;
+                file
+            }
+            None => File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?,
+        };
+        let mut sink = TextSink {
+            format,
+            file: BufWriter::new(file),
+        };
+        if resume_at.is_none() {
+            if let Some(delimiter) = sink.delimiter() {
+                writeln!(sink.file, "{}", join_fields(&COLUMNS, delimiter))?;
+            }
+        }
+        Ok(sink)
+    }
+
+    fn delimiter(&self) -> Option<char> {
+        match self.format {
+            InventoryFormat::Csv => Some(','),
+            InventoryFormat::Tsv => Some('\t'),
+            _ => None,
+        }
+    }
+
+    /// Write a page of records and return the length of the output
+    fn write(&mut self, records: &[Record]) -> Result<u64> {
+        for record in records {
+            match self.delimiter() {
+                Some(delimiter) => {
+                    let fields = record.fields();
+                    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+                    writeln!(self.file, "{}", join_fields(&fields, delimiter))?;
+                }
+                None => writeln!(self.file, "{}", serde_json::to_string(record)?)?,
+            }
+        }
+        self.file.flush()?;
+        Ok(self.file.get_mut().stream_position()?)
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use anyhow::{Context, Result};
+    use parquet::column::writer::ColumnWriter;
+    use parquet::data_type::ByteArray;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::Record;
+
+    const SCHEMA: &str = "message inventory {
+        REQUIRED BYTE_ARRAY container (UTF8);
+        REQUIRED BYTE_ARRAY name (UTF8);
+        REQUIRED INT64 size;
+        REQUIRED INT64 modified (TIMESTAMP(MILLIS, true));
+        OPTIONAL BYTE_ARRAY tier (UTF8);
+        OPTIONAL BYTE_ARRAY etag (UTF8);
+        OPTIONAL BYTE_ARRAY content_md5 (UTF8);
+        REQUIRED BYTE_ARRAY tags (UTF8);
+    }";
+
+    /// Parquet output, one row group per page of the listing
+    pub struct ParquetSink {
+        writer: SerializedFileWriter<File>,
+    }
+
+    impl ParquetSink {
+        pub fn create(path: &Path) -> Result<Self> {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let schema = Arc::new(parse_message_type(SCHEMA)?);
+            let properties = Arc::new(WriterProperties::builder().build());
+            Ok(ParquetSink {
+                writer: SerializedFileWriter::new(file, schema, properties)?,
+            })
+        }
+
+        pub fn write(&mut self, records: &[Record]) -> Result<()> {
+            let mut row_group = self.writer.next_row_group()?;
+            let mut index = 0;
+            while let Some(mut column) = row_group.next_column()? {
+                match (index, column.untyped()) {
+                    (2, ColumnWriter::Int64ColumnWriter(writer)) => {
+                        let sizes: Vec<i64> = records.iter().map(|r| r.size as i64).collect();
+                        writer.write_batch(&sizes, None, None)?;
+                    }
+                    (3, ColumnWriter::Int64ColumnWriter(writer)) => {
+                        let times: Vec<i64> =
+                            records.iter().map(|r| r.modified_unix * 1000).collect();
+                        writer.write_batch(&times, None, None)?;
+                    }
+                    (index, ColumnWriter::ByteArrayColumnWriter(writer)) => {
+                        let values: Vec<Option<String>> = records
+                            .iter()
+                            .map(|record| match index {
+                                0 => Some(record.container.clone()),
+                                1 => Some(record.name.clone()),
+                                4 => record.tier.clone(),
+                                5 => record.etag.clone(),
+                                6 => record.content_md5.clone(),
+                                _ => Some(record.tags_query()),
+                            })
+                            .collect();
+                        let present: Vec<ByteArray> = values
+                            .iter()
+                            .flatten()
+                            .map(|value| ByteArray::from(value.as_str()))
+                            .collect();
+                        // Only the optional columns take definition levels
+                        let levels: Vec<i16> = values.iter().map(|v| v.is_some() as i16).collect();
+                        let levels = (4..=6).contains(&index).then_some(&levels[..]);
+                        writer.write_batch(&present, levels, None)?;
+                    }
+                    _ => unreachable!("column {} doesn't match the schema", index),
+                }
+                column.close()?;
+                index += 1;
+            }
+            row_group.close()?;
+            Ok(())
+        }
+
+        pub fn finish(self) -> Result<()> {
+            self.writer.close()?;
+            Ok(())
+        }
+    }
+}
+
+enum Sink {
+    Text(TextSink),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet_sink::ParquetSink),
+}
+
+/// Export every blob under `uri` (a container, a prefix, or a whole account)
+/// to `out`
+///
+/// CSV, TSV and JSON Lines exports record how far they got after each page,
+/// so running the same command again after an interruption continues where it
+/// stopped. `restart` ignores that and starts over.
+///
+/// With `partitions`, each container is listed as that many concurrent
+/// listings of its first-level prefixes. Records then come in no particular
+/// order, so there is no single marker to resume from and no checkpoint.
+pub async fn execute(
+    uri: &str,
+    out: &Path,
+    restart: bool,
+    partitions: Option<usize>,
+) -> Result<()> {
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!("Inventory needs a storage account: az://<account>/[container/][prefix]")
+    })?;
+    let format = InventoryFormat::from_path(out)?;
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+    client.check_prerequisites().await?;
+
+    let mut containers = if container.is_empty() {
+        let mut names: Vec<String> = client
+            .list_containers()
+            .await?
+            .into_iter()
+            .map(|container| container.name)
+            .collect();
+        names.sort();
+        names
+    } else {
+        vec![container]
+    };
+
+    let checkpoint = match format {
+        _ if restart || partitions.is_some() => None,
+        InventoryFormat::Parquet => None,
+        _ => Checkpoint::load(out, uri)?,
+    };
+    let mut blobs = checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.blobs);
+    let mut marker = None;
+    if let Some(checkpoint) = &checkpoint {
+        let start = if checkpoint.container.is_empty() {
+            containers.len()
+        } else {
+            containers
+                .iter()
+                .position(|name| *name == checkpoint.container)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Container '{}' of the interrupted export is gone; run again with --restart",
+                        checkpoint.container
+                    )
+                })?
+        };
+        containers.drain(..start);
+        marker = checkpoint.marker.clone();
+        status!(
+            "{} Resuming after {} blob(s) already in {}",
+            "→".green(),
+            blobs,
+            out.display()
+        );
+    }
+
+    let mut sink = match format {
+        #[cfg(feature = "parquet")]
+        InventoryFormat::Parquet => Sink::Parquet(parquet_sink::ParquetSink::create(out)?),
+        #[cfg(not(feature = "parquet"))]
+        InventoryFormat::Parquet => {
+            return Err(anyhow!(
+                "Parquet output needs azst built with --features parquet; use .csv or .jsonl"
+            ))
+        }
+        // An export starting over must not leave an older checkpoint behind
+        _ if checkpoint.is_none() => {
+            Checkpoint::remove(out)?;
+            Sink::Text(TextSink::open(out, format, None)?)
+        }
+        _ => Sink::Text(TextSink::open(
+            out,
+            format,
+            checkpoint.as_ref().map(|checkpoint| checkpoint.bytes),
+        )?),
+    };
+
+    for (index, container) in containers.iter().enumerate() {
+        // Complete listings of a container are kept for --cached as well
+        let mut listed = marker.is_none().then(Vec::new);
+        let mut pages = match partitions {
+            Some(partitions) => client
+                .partitioned_blob_pages(container, prefix.as_deref(), partitions, true)
+                .await?
+                .map(|page| page.map(|blobs| (blobs, None)))
+                .boxed(),
+            None => {
+                client
+                    .inventory_pages(container, prefix.as_deref(), marker.take())
+                    .await?
+            }
+        };
+        while let Some(page) = pages.next().await {
+            let (page_blobs, next_marker) = page?;
+            if let Some(listed) = &mut listed {
+                listed.extend(page_blobs.iter().cloned());
+            }
+            let records: Vec<Record> = page_blobs
+                .into_iter()
+                .map(|blob| Record::new(container, blob))
+                .collect();
+            blobs += records.len() as u64;
+            match &mut sink {
+                Sink::Text(text) => {
+                    let bytes = text.write(&records)?;
+                    if partitions.is_some() {
+                        continue;
+                    }
+                    // After a container's last page, continue with the next one
+                    let (container, marker) = match next_marker {
+                        Some(marker) => (container.clone(), Some(marker)),
+                        None => (containers.get(index + 1).cloned().unwrap_or_default(), None),
+                    };
+                    Checkpoint {
+                        location: uri.to_string(),
+                        container,
+                        marker,
+                        bytes,
+                        blobs,
+                    }
+                    .save(out)?;
+                }
+                #[cfg(feature = "parquet")]
+                Sink::Parquet(parquet) => parquet.write(&records)?,
+            }
+        }
+        if let Some(listed) = listed {
+            CachedListing::new(&account, container, prefix.as_deref(), listed).save_quietly();
+        }
+    }
+
+    match sink {
+        Sink::Text(_) => Checkpoint::remove(out)?,
+        #[cfg(feature = "parquet")]
+        Sink::Parquet(parquet) => parquet.finish()?,
+    }
+
+    status!(
+        "{} Wrote {} blob(s) to {}",
+        "✓".green(),
+        blobs,
+        out.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::azure::BlobProperties;
+
+    fn record() -> Record {
+        Record::new(
+            "data",
+            BlobInfo {
+                name: "a,b.csv".to_string(),
+                properties: BlobProperties {
+                    content_length: 42,
+                    last_modified: "Mon, 01 Jan 2024 00:00:00 GMT".to_string(),
+                    content_type: None,
+                    last_modified_unix: 1704067200,
+                    content_md5: Some("00ff".to_string()),
+                    content_encoding: None,
+                    etag: Some("0x1".to_string()),
+                    access_tier: Some("Cool".to_string()),
+                    blob_type: None,
+                    lease_state: None,
+                },
+                version_count: None,
+                tags: Some(BTreeMap::from([
+                    ("owner".to_string(), "data team".to_string()),
+                    ("q".to_string(), "a&b=c".to_string()),
+                ])),
+            },
+        )
+    }
+
+    #[test]
+    fn test_inventory_format_from_extension() {
+        let format = |name: &str| InventoryFormat::from_path(Path::new(name));
+        assert_eq!(format("out.CSV").unwrap(), InventoryFormat::Csv);
+        assert_eq!(format("out.ndjson").unwrap(), InventoryFormat::Jsonl);
+        assert_eq!(format("out.parquet").unwrap(), InventoryFormat::Parquet);
+        assert!(format("out.txt").is_err());
+        assert!(format("out").is_err());
+    }
+
+    #[test]
+    fn test_record_fields() {
+        let record = record();
+        assert_eq!(
+            record.fields(),
+            [
+                "data",
+                "a,b.csv",
+                "42",
+                "2024-01-01T00:00:00+00:00",
+                "Cool",
+                "0x1",
+                "00ff",
+                "owner=data team&q=a%26b%3Dc",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_sink_resumes_at_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("inventory.csv");
+
+        let mut sink = TextSink::open(&out, InventoryFormat::Csv, None).unwrap();
+        let checkpoint = sink.write(&[record()]).unwrap();
+        // A page written after the last checkpoint, then an interruption
+        sink.write(&[record()]).unwrap();
+        drop(sink);
+
+        let mut sink = TextSink::open(&out, InventoryFormat::Csv, Some(checkpoint)).unwrap();
+        sink.write(&[record()]).unwrap();
+        let contents = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        assert!(contents.starts_with("container,name,size,"));
+        assert!(contents.contains("data,\"a,b.csv\",42,"));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_sink_writes_row_group_per_page() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("inventory.parquet");
+        let mut sink = parquet_sink::ParquetSink::create(&out).unwrap();
+        sink.write(&[record(), record()]).unwrap();
+        sink.write(&[record()]).unwrap();
+        sink.finish().unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&out).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.num_row_groups(), 2);
+        assert_eq!(metadata.file_metadata().num_rows(), 3);
+        let row = reader.get_row_iter(None).unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            row.to_string(),
+            "{container: \"data\", name: \"a,b.csv\", size: 42, modified: 2024-01-01 00:00:00.000 +00:00, \
+             tier: \"Cool\", etag: \"0x1\", content_md5: \"00ff\", tags: \"owner=data team&q=a%26b%3Dc\"}"
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_only_resumes_same_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("inventory.jsonl");
+        std::fs::write(&out, "").unwrap();
+        Checkpoint {
+            location: "az://acct/data/".to_string(),
+            container: "data".to_string(),
+            marker: Some("m1".to_string()),
+            bytes: 0,
+            blobs: 5000,
+        }
+        .save(&out)
+        .unwrap();
+
+        let checkpoint = Checkpoint::load(&out, "az://acct/data/").unwrap().unwrap();
+        assert_eq!(checkpoint.marker.as_deref(), Some("m1"));
+        assert!(Checkpoint::load(&out, "az://acct/other/")
+            .unwrap()
+            .is_none());
+    }
+}
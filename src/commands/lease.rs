@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Result};
+use azure_core::request_options::{LeaseDuration, LeaseId};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::azure::AzureClient;
+use crate::utils::{parse_azure_uri, write_private_file};
+
+// ============================================================================
+// Leases - Exclusive write locks on containers and blobs
+// ============================================================================
+
+/// Lease IDs acquired by azst, keyed by the az:// URI they lock
+///
+/// Later `lease renew`/`release` calls and writes through cp and rm use them
+/// when no --lease-id is given, so scripts don't have to carry the ID around.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredLeases {
+    leases: BTreeMap<String, String>,
+}
+
+impl StoredLeases {
+    fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("azst").join("leases.json"))
+    }
+
+    /// Missing or unreadable files count as empty: the service holds the leases
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow!("No cache directory"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_private_file(&path, &serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// `az://acct/container/` and `az://acct/container` lock the same thing
+fn lease_key(uri: &str) -> String {
+    uri.trim_end_matches('/').to_string()
+}
+
+/// The lease azst last acquired on `uri`, if it still remembers one
+pub fn stored_lease(uri: &str) -> Option<LeaseId> {
+    StoredLeases::load()
+        .leases
+        .get(&lease_key(uri))
+        .and_then(|id| LeaseId::from_str(id).ok())
+}
+
+/// Forget the lease held on `uri`, once released, broken or spent on a delete
+pub fn forget_lease(uri: &str) -> Result<()> {
+    let mut stored = StoredLeases::load();
+    if stored.leases.remove(&lease_key(uri)).is_some() {
+        stored.save()?;
+    }
+    Ok(())
+}
+
+fn remember_lease(uri: &str, lease_id: LeaseId) -> Result<()> {
+    let mut stored = StoredLeases::load();
+    stored.leases.insert(lease_key(uri), lease_id.to_string());
+    stored.save()
+}
+
+/// Parse a lease duration: 15 to 60 seconds, or `infinite`
+pub fn parse_lease_duration(value: &str) -> Result<LeaseDuration, String> {
+    if value.eq_ignore_ascii_case("infinite") {
+        return Ok(LeaseDuration::Infinite);
+    }
+    let seconds = value.trim_end_matches('s').parse::<u8>().ok();
+    match seconds {
+        Some(seconds) if (15..=60).contains(&seconds) => Ok(LeaseDuration::Seconds(seconds)),
+        _ => Err(format!(
+            "invalid lease duration '{}': expected 15 to 60 seconds, or 'infinite'",
+            value
+        )),
+    }
+}
+
+/// Parse a lease ID, which Azure requires to be a GUID
+pub fn parse_lease_id(value: &str) -> Result<LeaseId, String> {
+    LeaseId::from_str(value).map_err(|_| format!("invalid lease ID '{}': expected a GUID", value))
+}
+
+/// The client, container and optional blob a lease URI names
+fn lease_target(uri: &str) -> Result<(AzureClient, String, Option<String>)> {
+    let (account, container, blob) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!("Lease URIs must include a storage account: az://<account>/<container>[/<blob>]")
+    })?;
+    if container.is_empty() {
+        return Err(anyhow!("'{}' must name a container or blob to lease", uri));
+    }
+    if blob.as_deref().is_some_and(|b| b.ends_with('/')) {
+        return Err(anyhow!(
+            "'{}' is a directory; only containers and blobs can be leased",
+            uri
+        ));
+    }
+    let blob = blob.filter(|b| !b.is_empty());
+    Ok((
+        AzureClient::new().with_storage_account(&account),
+        container,
+        blob,
+    ))
+}
+
+/// The explicit lease ID, or else the one stored for `uri`
+fn lease_id_for(uri: &str, lease_id: Option<LeaseId>) -> Result<LeaseId> {
+    lease_id
+        .or_else(|| stored_lease(uri))
+        .ok_or_else(|| anyhow!("No lease on {} is known to azst; pass --lease-id", uri))
+}
+
+/// Acquire a lease and print its ID on stdout for scripts to capture
+pub async fn acquire(
+    uri: &str,
+    duration: LeaseDuration,
+    proposed_id: Option<LeaseId>,
+) -> Result<()> {
+    let (mut client, container, blob) = lease_target(uri)?;
+    let lease_id = client
+        .acquire_lease(&container, blob.as_deref(), duration.clone(), proposed_id)
+        .await?;
+    remember_lease(uri, lease_id)?;
+
+    let duration = match duration {
+        LeaseDuration::Infinite => "until released".to_string(),
+        LeaseDuration::Seconds(seconds) => format!("for {}s", seconds),
+    };
+    status!("{} Leased {} {}", "✓".green(), uri.cyan(), duration);
+    println!("{}", lease_id);
+    Ok(())
+}
+
+pub async fn renew(uri: &str, lease_id: Option<LeaseId>) -> Result<()> {
+    let lease_id = lease_id_for(uri, lease_id)?;
+    let (mut client, container, blob) = lease_target(uri)?;
+    client
+        .renew_lease(&container, blob.as_deref(), lease_id)
+        .await?;
+    status!(
+        "{} Renewed lease {} on {}",
+        "✓".green(),
+        lease_id,
+        uri.cyan()
+    );
+    Ok(())
+}
+
+pub async fn release(uri: &str, lease_id: Option<LeaseId>) -> Result<()> {
+    let lease_id = lease_id_for(uri, lease_id)?;
+    let (mut client, container, blob) = lease_target(uri)?;
+    client
+        .release_lease(&container, blob.as_deref(), lease_id)
+        .await?;
+    forget_lease(uri)?;
+    status!(
+        "{} Released lease {} on {}",
+        "✓".green(),
+        lease_id,
+        uri.cyan()
+    );
+    Ok(())
+}
+
+/// Break whatever lease is held, e.g. one left behind by a crashed script
+pub async fn break_lease(uri: &str, break_period: Option<u8>) -> Result<()> {
+    let (mut client, container, blob) = lease_target(uri)?;
+    let remaining = client
+        .break_lease(
+            &container,
+            blob.as_deref(),
+            break_period.map(|seconds| Duration::from_secs(seconds.into())),
+        )
+        .await?;
+    forget_lease(uri)?;
+    if remaining == 0 {
+        status!("{} Broke the lease on {}", "✓".green(), uri.cyan());
+    } else {
+        status!(
+            "{} The lease on {} breaks in {}s",
+            "ℹ".blue(),
+            uri.cyan(),
+            remaining
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lease_duration() {
+        assert!(matches!(
+            parse_lease_duration("infinite"),
+            Ok(LeaseDuration::Infinite)
+        ));
+        assert!(matches!(
+            parse_lease_duration("30"),
+            Ok(LeaseDuration::Seconds(30))
+        ));
+        assert!(matches!(
+            parse_lease_duration("60s"),
+            Ok(LeaseDuration::Seconds(60))
+        ));
+        for invalid in ["5", "61", "-1", "forever", ""] {
+            assert!(parse_lease_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_lease_id() {
+        let id = parse_lease_id("0f8fad5b-d9cb-469f-a165-70867728950e").unwrap();
+        assert_eq!(id.to_string(), "0f8fad5b-d9cb-469f-a165-70867728950e");
+        assert!(parse_lease_id("not-a-guid").is_err());
+    }
+
+    #[test]
+    fn test_lease_key() {
+        assert_eq!(lease_key("az://acct/data/"), lease_key("az://acct/data"));
+        assert_eq!(lease_key("az://acct/data/a.txt"), "az://acct/data/a.txt");
+    }
+}
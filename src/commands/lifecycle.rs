@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Context, Result};
+use azure_mgmt_storage::models::ManagementPolicySchema;
+use colored::*;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::Path;
+
+use crate::azure::AzureClient;
+use crate::utils::parse_azure_uri;
+
+// ============================================================================
+// Lifecycle - The account's rules for tiering and deleting blobs by age
+// ============================================================================
+
+/// A policy file: the rules themselves, as `az storage account
+/// management-policy create --policy` takes them, or the output of
+/// `management-policy show` with the rules under `policy`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PolicyFile {
+    Rules(ManagementPolicySchema),
+    Show { policy: ManagementPolicySchema },
+}
+
+/// Files ending in .yaml or .yml are YAML; everything else is JSON
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml")
+        })
+}
+
+fn parse_policy(content: &str, yaml: bool) -> Result<ManagementPolicySchema> {
+    let file: PolicyFile = if yaml {
+        serde_yaml::from_str(content)?
+    } else {
+        serde_json::from_str(content)?
+    };
+    let policy = match file {
+        PolicyFile::Rules(policy) | PolicyFile::Show { policy } => policy,
+    };
+
+    if policy.rules.is_empty() {
+        return Err(anyhow!("The policy has no rules"));
+    }
+    let mut names = HashSet::new();
+    for rule in &policy.rules {
+        if !names.insert(rule.name.as_str()) {
+            return Err(anyhow!("More than one rule is named '{}'", rule.name));
+        }
+    }
+    Ok(policy)
+}
+
+fn format_policy(policy: &ManagementPolicySchema, yaml: bool) -> Result<String> {
+    Ok(if yaml {
+        serde_yaml::to_string(policy)?
+    } else {
+        serde_json::to_string_pretty(policy)? + "\n"
+    })
+}
+
+/// The client for the account an `az://account/` URI names
+fn account_client(uri: &str) -> Result<(AzureClient, String)> {
+    let (account, container, _) = parse_azure_uri(uri)?;
+    let account =
+        account.ok_or_else(|| anyhow!("Lifecycle policies belong to accounts: az://<account>/"))?;
+    if !container.is_empty() {
+        return Err(anyhow!(
+            "Lifecycle policies apply to a whole account; use az://{}/ and filter \
+             rules by prefix instead",
+            account
+        ));
+    }
+    Ok((AzureClient::new().with_storage_account(&account), account))
+}
+
+/// Write the account's policy to `file`, or to stdout as JSON
+pub async fn get(uri: &str, file: Option<&Path>) -> Result<()> {
+    let (mut client, account) = account_client(uri)?;
+    let Some(policy) = client.lifecycle_policy().await? else {
+        status!("{} {} has no lifecycle policy", "ℹ".blue(), account.cyan());
+        return Ok(());
+    };
+
+    match file {
+        Some(path) => {
+            std::fs::write(path, format_policy(&policy, is_yaml(path))?)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            status!(
+                "{} Wrote {} rule(s) to {}",
+                "✓".green(),
+                policy.rules.len(),
+                path.display()
+            );
+        }
+        None => print!("{}", format_policy(&policy, false)?),
+    }
+    Ok(())
+}
+
+/// Replace the account's policy with the one in `file`
+///
+/// "-" reads stdin, as YAML, which takes JSON as well.
+pub async fn set(uri: &str, file: &Path, dry_run: bool) -> Result<()> {
+    let (mut client, account) = account_client(uri)?;
+    let stdin = file == Path::new("-");
+    let content = if stdin {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?
+    };
+    let policy = parse_policy(&content, stdin || is_yaml(file))
+        .with_context(|| format!("Invalid lifecycle policy in {}", file.display()))?;
+
+    let names: Vec<&str> = policy.rules.iter().map(|rule| rule.name.as_str()).collect();
+    if dry_run {
+        println!(
+            "Would set {} rule(s) on {}: {}",
+            names.len(),
+            account,
+            names.join(", ")
+        );
+        return Ok(());
+    }
+
+    let rules = names.join(", ");
+    client.set_lifecycle_policy(policy).await?;
+    status!(
+        "{} Set the lifecycle policy of {} ({})",
+        "✓".green(),
+        account.cyan(),
+        rules
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES_JSON: &str = r#"{
+        "rules": [{
+            "enabled": true,
+            "name": "age-out-logs",
+            "type": "Lifecycle",
+            "definition": {
+                "actions": {
+                    "baseBlob": {
+                        "tierToCool": {"daysAfterModificationGreaterThan": 30},
+                        "delete": {"daysAfterModificationGreaterThan": 365}
+                    }
+                },
+                "filters": {"blobTypes": ["blockBlob"], "prefixMatch": ["logs/"]}
+            }
+        }]
+    }"#;
+
+    #[test]
+    fn test_parse_policy_formats() {
+        let policy = parse_policy(RULES_JSON, false).unwrap();
+        assert_eq!(policy.rules[0].name, "age-out-logs");
+
+        // What `az storage account management-policy show` prints
+        let shown = format!(
+            r#"{{"name": "DefaultManagementPolicy", "policy": {}}}"#,
+            RULES_JSON
+        );
+        assert_eq!(parse_policy(&shown, false).unwrap(), policy);
+
+        // YAML round trip
+        let yaml = format_policy(&policy, true).unwrap();
+        assert!(yaml.contains("prefixMatch:"));
+        assert_eq!(parse_policy(&yaml, true).unwrap(), policy);
+    }
+
+    #[test]
+    fn test_parse_policy_rejects_invalid_rules() {
+        assert!(parse_policy(r#"{"rules": []}"#, false).is_err());
+
+        let policy = parse_policy(RULES_JSON, false).unwrap();
+        let mut rules = policy.rules.clone();
+        rules.extend(policy.rules);
+        let duplicated = serde_json::to_string(&ManagementPolicySchema::new(rules)).unwrap();
+        assert!(parse_policy(&duplicated, false)
+            .unwrap_err()
+            .to_string()
+            .contains("age-out-logs"));
+    }
+
+    #[test]
+    fn test_is_yaml() {
+        assert!(is_yaml(Path::new("policy.yaml")));
+        assert!(is_yaml(Path::new("policy.YML")));
+        assert!(!is_yaml(Path::new("policy.json")));
+        assert!(!is_yaml(Path::new("-")));
+    }
+}
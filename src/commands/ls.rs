@@ -1,56 +1,259 @@
-use anyhow::{anyhow, Result};
-
-use crate::azure::{AzureClient, BlobItem};
-use crate::output::create_writer;
+use anyhow::{anyhow, Context, Result};
+use azure_core::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::azure::{AccountFilter, AzureClient, BlobInfo, BlobItem, UrlSigner};
+use crate::commands::complete;
+use crate::config;
+use crate::error::{host_not_found, http_status, storage_error_code, AzstError};
+use crate::listing_cache::CachedListing;
+use crate::output::{
+    create_writer, is_deterministic, is_interactive, is_machine_readable, output_format,
+    set_null_terminated, Column, OutputFormat, OutputWriter,
+};
 use crate::utils::{
-    contains_recursive_wildcard, format_size, is_azure_uri, matches_pattern, parse_azure_uri,
-    split_wildcard_path,
+    contains_wildcard, format_size, is_azure_uri, is_container_name, matches_pattern,
+    parse_azure_uri, split_container_pattern, Glob, GlobMatch,
 };
 
-use std::io::IsTerminal;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
 
-/// Calculate the depth of a pattern (number of path segments)
-/// Treats ** as matching any depth
-fn pattern_depth(pattern: &str) -> Option<usize> {
-    if pattern.contains("**") {
-        None // Infinite depth
-    } else {
-        // Count the number of path segments
-        Some(pattern.split('/').filter(|s| !s.is_empty()).count())
-    }
+/// Blob name -> ETag for every blob under a listed location
+pub type Etags = BTreeMap<String, String>;
+
+/// Listing recorded by `--since-state`, compared against on the next run
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ListingState {
+    location: String,
+    etags: Etags,
 }
 
+/// How a blob differs from the recorded listing
+#[derive(Debug, PartialEq)]
+pub enum Change<'a> {
+    Added(&'a str),
+    Modified(&'a str),
+    Deleted(&'a str),
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     path: Option<&str>,
     long: bool,
     human_readable: bool,
     recursive: bool,
+    directory: bool,
+    flat: bool,
+    summary: Option<bool>,
+    cached: bool,
     account: Option<&str>,
+    signed_urls: Option<Duration>,
+    since_state: Option<&str>,
+    etag: bool,
+    columns: Option<&[Column]>,
+    null: bool,
+    accounts: &AccountFilter,
 ) -> Result<()> {
+    if path.is_some() && !accounts.is_empty() {
+        return Err(anyhow!(
+            "--subscription, --all-subscriptions, --resource-group and --name-filter only apply when listing storage accounts (ls without a path)"
+        ));
+    }
+    // `azst ls mycontainer/` lists a container of the default account, unless
+    // a local path of that name exists
+    let in_default_account = path
+        .filter(|p| {
+            !is_azure_uri(p)
+                && !Path::new(p).exists()
+                && p.split('/').next().is_some_and(is_container_name)
+        })
+        .and_then(|p| config::default_account().map(|account| format!("az://{}/{}", account, p)));
+    let path = in_default_account.as_deref().or(path);
+
+    if signed_urls.is_some() && !path.is_some_and(is_azure_uri) {
+        return Err(anyhow!(
+            "--signed-urls can only be used when listing az:// paths"
+        ));
+    }
+    if signed_urls.is_some() && output_format() != OutputFormat::Text {
+        return Err(anyhow!(
+            "--signed-urls can't be combined with --format csv, tsv or json"
+        ));
+    }
+    if null && output_format() != OutputFormat::Text {
+        return Err(anyhow!(
+            "-0 can't be combined with --format csv, tsv or json"
+        ));
+    }
+    set_null_terminated(null);
+    if cached && !path.is_some_and(is_azure_uri) {
+        return Err(anyhow!(
+            "--cached can only be used when listing az:// paths"
+        ));
+    }
+    if since_state.is_some() && !path.is_some_and(is_azure_uri) {
+        return Err(anyhow!(
+            "--since-state can only be used when listing az:// paths"
+        ));
+    }
+
+    // -e and --columns choose the columns of a long listing
+    let columns = columns.or(etag.then_some(Column::LONG_WITH_ETAG));
+    let long = long || columns.is_some();
+
     match path {
         Some(p) if is_azure_uri(p) => {
             let mut azure_client = AzureClient::new();
-            if let Some(account_name) = account {
-                azure_client = azure_client.with_storage_account(account_name);
+            // Legacy az://container/path URIs: --account, else the default account
+            if let Some(account_name) = account.map(str::to_string).or_else(config::default_account)
+            {
+                azure_client = azure_client.with_storage_account(&account_name);
             }
-            azure_client.check_prerequisites().await?;
-            list_azure_objects(p, long, human_readable, recursive, &mut azure_client).await
+            if !cached {
+                azure_client.check_prerequisites().await?;
+            }
+            let container_pattern = split_container_pattern(p);
+            if let Some(state_path) = since_state {
+                if container_pattern.is_some() {
+                    return Err(anyhow!(
+                        "--since-state records a single container; name one instead of a pattern"
+                    ));
+                }
+                return list_changes(p, state_path, &mut azure_client).await;
+            }
+            // `az://acct/logs-*/` lists each matching container in turn;
+            // with -d, the matching containers themselves
+            let locations = match container_pattern {
+                Some((account_name, pattern, "")) if directory => {
+                    let mut client = AzureClient::new().with_storage_account(account_name);
+                    return list_containers(long, Some(pattern), &mut client).await;
+                }
+                Some((account_name, pattern, rest)) => {
+                    let mut client = AzureClient::new().with_storage_account(account_name);
+                    matching_containers(&mut client, pattern, cached)
+                        .await?
+                        .into_iter()
+                        .map(|container| format!("az://{}/{}/{}", account_name, container, rest))
+                        .collect()
+                }
+                None => vec![p.to_string()],
+            };
+            let totals = Cell::new((0, 0));
+            for location in &locations {
+                list_azure_objects(
+                    location,
+                    long,
+                    human_readable,
+                    recursive,
+                    directory,
+                    flat,
+                    &totals,
+                    cached,
+                    signed_urls,
+                    columns,
+                    &mut azure_client,
+                )
+                .await
+                .map_err(|e| friendly_listing_error(e, location, &azure_client))?;
+            }
+            // Container listings have nothing to total
+            let lists_blobs = container_pattern.is_some()
+                || parse_azure_uri(p).is_ok_and(|(_, container, _)| !container.is_empty());
+            if lists_blobs && summary.unwrap_or(long && is_interactive()) {
+                let (objects, bytes) = totals.get();
+                create_writer().write_summary(objects, bytes);
+            }
+            Ok(())
         }
-        Some(p) => list_local_path(p, long, human_readable, recursive).await,
+        Some(p) => list_local_path(p, long, human_readable, recursive, directory).await,
         None => {
             // List all storage accounts - requires Azure
             let mut azure_client = AzureClient::new();
             azure_client.check_prerequisites().await?;
-            list_storage_accounts(long, &mut azure_client).await
+            list_storage_accounts(long, accounts, &mut azure_client).await
         }
     }
 }
 
-async fn list_storage_accounts(long: bool, azure_client: &mut AzureClient) -> Result<()> {
-    let accounts = azure_client.list_storage_accounts().await?;
+/// Concise guidance for the listing failures users hit most, instead of the
+/// SDK's error dump: a missing container, no data role on the account, or a
+/// firewall in the way. Other errors are returned as they are.
+pub fn friendly_listing_error(
+    e: anyhow::Error,
+    location: &str,
+    client: &AzureClient,
+) -> anyhow::Error {
+    let Ok((account, container, _)) = parse_azure_uri(location) else {
+        return e;
+    };
+    let Some(account) = account.or_else(|| client.get_storage_account().map(str::to_string)) else {
+        return e;
+    };
+    let target = if container.is_empty() {
+        format!("list the containers of az://{}/", account)
+    } else {
+        format!("list az://{}/{}/", account, container)
+    };
+
+    let friendly = match (storage_error_code(&e), http_status(&e)) {
+        (Some("ContainerNotFound"), _) => AzstError::NotFound(format!(
+            "Container '{}' does not exist in storage account '{}'. Run 'azst ls az://{}/' to see its containers.",
+            container, account, account
+        )),
+        (Some("AuthorizationPermissionMismatch"), _) => AzstError::PermissionDenied(format!(
+            "Not allowed to {}: you have no data role on the account. Listing needs \
+             'Storage Blob Data Reader' (or Contributor) on the storage account or container; \
+             Owner and Contributor alone don't grant access to blobs. An admin can assign it with:\n  \
+             az role assignment create --role \"Storage Blob Data Reader\" --assignee <user or principal> --scope <storage account resource ID>",
+            target
+        )),
+        (Some("AuthorizationFailure"), _) => AzstError::PermissionDenied(format!(
+            "Not allowed to {}: the account rejected the request, usually because its firewall \
+             doesn't allow your network. Check the account's networking settings.",
+            target
+        )),
+        (_, Some(StatusCode::Forbidden)) => AzstError::PermissionDenied(format!(
+            "Not allowed to {}. Check that you have a 'Storage Blob Data' role on the account or container.",
+            target
+        )),
+        _ if host_not_found(&e) => AzstError::NotFound(format!(
+            "Storage account '{}' not found. Run 'azst ls' to see the accounts you can use.",
+            account
+        )),
+        _ => return e,
+    };
+    tracing::debug!("{:#}", e);
+    friendly.into()
+}
+
+/// Say that a listing is empty, on stderr when stdout is parsed by a program
+fn report_empty(message: &str) {
+    if is_machine_readable() {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+async fn list_storage_accounts(
+    long: bool,
+    filter: &AccountFilter,
+    azure_client: &mut AzureClient,
+) -> Result<()> {
+    let mut accounts = azure_client.list_storage_accounts_matching(filter).await?;
+    if filter.is_empty() {
+        // The same list `azst complete` offers for az://
+        complete::cache_accounts(&accounts);
+    }
+    if is_deterministic() {
+        accounts.sort_by(|a, b| a.name.cmp(&b.name));
+    }
 
     if accounts.is_empty() {
-        println!("No storage accounts found");
+        report_empty("No storage accounts found");
         return Ok(());
     }
 
@@ -69,11 +272,64 @@ async fn list_storage_accounts(long: bool, azure_client: &mut AzureClient) -> Re
     Ok(())
 }
 
-async fn list_containers(long: bool, azure_client: &mut AzureClient) -> Result<()> {
-    let containers = azure_client.list_containers().await?;
+/// Names of the containers matching a wildcard pattern, live or from the
+/// cached listings; an error when there are none
+pub async fn matching_containers(
+    client: &mut AzureClient,
+    pattern: &str,
+    cached: bool,
+) -> Result<Vec<String>> {
+    let account = client
+        .get_storage_account()
+        .ok_or_else(|| anyhow!("Storage account not configured"))?
+        .to_string();
+    let names: Vec<String> = if cached {
+        let mut names: Vec<String> = CachedListing::containers(&account)?
+            .into_iter()
+            .filter(|name| matches_pattern(name, pattern))
+            .collect();
+        names.sort();
+        names
+    } else {
+        client
+            .list_containers_matching(pattern)
+            .await
+            .map_err(|e| friendly_listing_error(e.into(), &format!("az://{}/", account), client))?
+            .into_iter()
+            .map(|container| container.name)
+            .collect()
+    };
+    if names.is_empty() {
+        return Err(anyhow!(
+            "No containers in az://{}/ match '{}'",
+            account,
+            pattern
+        ));
+    }
+    Ok(names)
+}
+
+async fn list_containers(
+    long: bool,
+    pattern: Option<&str>,
+    azure_client: &mut AzureClient,
+) -> Result<()> {
+    let containers = match pattern {
+        Some(pattern) => azure_client.list_containers_matching(pattern).await,
+        None => azure_client.list_containers().await,
+    };
+    let location = format!(
+        "az://{}/",
+        azure_client.get_storage_account().unwrap_or_default()
+    );
+    let mut containers =
+        containers.map_err(|e| friendly_listing_error(e.into(), &location, azure_client))?;
+    if is_deterministic() {
+        containers.sort_by(|a, b| a.name.cmp(&b.name));
+    }
 
     if containers.is_empty() {
-        println!("No containers found");
+        report_empty("No containers found");
         return Ok(());
     }
 
@@ -97,6 +353,105 @@ async fn list_containers(long: bool, azure_client: &mut AzureClient) -> Result<(
     Ok(())
 }
 
+/// How blob entries are written: short or long, or with selected columns
+struct BlobFormat<'a> {
+    long: bool,
+    human_readable: bool,
+    /// Columns chosen with `-e` or `--columns`, instead of the fixed `-l` ones
+    columns: Option<&'a [Column]>,
+    signer: Option<&'a UrlSigner>,
+    /// Versions per blob name, when the Versions column is shown
+    version_counts: Option<HashMap<String, usize>>,
+    /// Blobs written so far and their total size, for the summary footer
+    totals: &'a Cell<(u64, u64)>,
+}
+
+impl BlobFormat<'_> {
+    fn write_table_header(&self, writer: &dyn OutputWriter) {
+        if let Some(columns) = self.columns {
+            let headers: Vec<(&str, usize)> = columns
+                .iter()
+                .map(|column| (column.header(), column.width()))
+                .collect();
+            writer.write_table_header(&headers);
+            writer.write_separator(80);
+        } else if self.long {
+            writer.write_table_header(&[("Size", 10), ("Type", 15), ("Modified", 20), ("Name", 0)]);
+            writer.write_separator(80);
+        }
+    }
+
+    fn write_item(
+        &self,
+        writer: &dyn OutputWriter,
+        item: BlobItem,
+        account: &str,
+        container: &str,
+    ) -> Result<()> {
+        match item {
+            BlobItem::Blob(mut blob) => {
+                let (objects, bytes) = self.totals.get();
+                self.totals
+                    .set((objects + 1, bytes + blob.properties.content_length));
+                let blob_uri = format!("az://{}/{}/{}", account, container, blob.name);
+                if let Some(columns) = self.columns {
+                    if let Some(counts) = &self.version_counts {
+                        blob.version_count = counts.get(&blob.name).copied();
+                    }
+                    let cells: Vec<String> = columns
+                        .iter()
+                        .map(|column| blob_cell(*column, &blob, &blob_uri, self.human_readable))
+                        .collect();
+                    writer.write_blob_row(columns, &cells);
+                } else {
+                    writer.write_blob(
+                        &blob_uri,
+                        &blob_cell(Column::Size, &blob, &blob_uri, self.human_readable),
+                        &blob_cell(Column::Type, &blob, &blob_uri, self.human_readable),
+                        &blob.properties.last_modified,
+                        self.long,
+                    );
+                }
+                if let Some(signer) = self.signer {
+                    writer.write_signed_url(&signer.sign(container, &blob.name)?);
+                }
+            }
+            BlobItem::Prefix(prefix) => {
+                // Display directory/prefix with trailing slash
+                let prefix_uri = format!("az://{}/{}/{}", account, container, prefix);
+                match self.columns {
+                    Some(columns) => writer.write_prefix_row(columns, &prefix_uri),
+                    None => writer.write_prefix(&prefix_uri, self.long),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Value of one column for a blob
+fn blob_cell(column: Column, blob: &BlobInfo, uri: &str, human_readable: bool) -> String {
+    let properties = &blob.properties;
+    let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    match column {
+        Column::Size if human_readable => format_size(properties.content_length),
+        Column::Size => properties.content_length.to_string(),
+        Column::Type => properties
+            .content_type
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string()),
+        Column::Modified => properties.last_modified.clone(),
+        Column::Etag => or_dash(&properties.etag),
+        Column::Tier => or_dash(&properties.access_tier),
+        Column::BlobType => or_dash(&properties.blob_type),
+        Column::Lease => or_dash(&properties.lease_state),
+        Column::Versions => blob
+            .version_count
+            .map_or_else(|| "-".to_string(), |count| count.to_string()),
+        Column::Name => uri.to_string(),
+    }
+}
+
 /// Stream blob results directly without buffering - for non-wildcard listings
 async fn list_blobs_streaming(
     client: &mut AzureClient,
@@ -104,76 +459,68 @@ async fn list_blobs_streaming(
     actual_account: &str,
     prefix: Option<&str>,
     delimiter: Option<&str>,
-    long: bool,
-    human_readable: bool,
+    cache: Option<&CachedListing>,
+    format: &BlobFormat<'_>,
 ) -> Result<()> {
     let writer = create_writer();
-    let is_tty = std::io::stdout().is_terminal();
+    let is_tty = is_interactive();
     if is_tty {
         writer.write_header(&format!(
             "Contents of az://{}/{}:",
             actual_account, container
         ));
-        if long {
-            writer.write_table_header(&[("Size", 10), ("Type", 15), ("Modified", 20), ("Name", 0)]);
-            writer.write_separator(80);
-        }
+        format.write_table_header(writer.as_ref());
     }
 
     let mut item_count = 0;
+    // Recursive listings are kept for --cached
+    let mut listed = Vec::new();
+    let mut write_page = |items: Vec<BlobItem>| {
+        for item in items {
+            item_count += 1;
+            if let (None, None, BlobItem::Blob(blob)) = (cache, delimiter, &item) {
+                listed.push(BlobItem::Blob(blob.clone()));
+            }
+            format.write_item(writer.as_ref(), item, actual_account, container)?;
+        }
+        Ok(())
+    };
 
-    // Use the callback-based API to process items as they arrive
-    client
-        .list_blobs_with_callback(container, prefix, delimiter, |items| {
-            for item in items {
-                item_count += 1;
-                match item {
-                    BlobItem::Blob(blob) => {
-                        let size_str = if human_readable {
-                            format_size(blob.properties.content_length)
-                        } else {
-                            blob.properties.content_length.to_string()
-                        };
-
-                        let content_type = blob
-                            .properties
-                            .content_type
-                            .unwrap_or_else(|| "unknown".to_string());
-
-                        let blob_uri =
-                            format!("az://{}/{}/{}", actual_account, container, blob.name);
-
-                        writer.write_blob(
-                            &blob_uri,
-                            &size_str,
-                            &content_type,
-                            &blob.properties.last_modified,
-                            long,
-                        );
-                    }
-                    BlobItem::Prefix(prefix) => {
-                        let prefix_uri =
-                            format!("az://{}/{}/{}", actual_account, container, prefix);
-                        writer.write_prefix(&prefix_uri, long);
-                    }
-                }
+    match cache {
+        Some(cache) => write_page(cache.list(prefix, delimiter))?,
+        None => {
+            // Use the callback-based API to process items as they arrive
+            client
+                .list_blobs_with_callback(container, prefix, delimiter, &mut write_page)
+                .await?;
+            if delimiter.is_none() {
+                cache_listing(actual_account, container, prefix, &listed);
             }
-            Ok(())
-        })
-        .await?;
+        }
+    }
 
     if item_count == 0 {
-        println!("No objects found in az://{}/{}/", actual_account, container);
+        report_empty(&format!(
+            "No objects found in az://{}/{}/",
+            actual_account, container
+        ));
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn list_azure_objects(
     path: &str,
     long: bool,
     human_readable: bool,
     recursive: bool,
+    directory: bool,
+    flat: bool,
+    totals: &Cell<(u64, u64)>,
+    cached: bool,
+    signed_urls: Option<Duration>,
+    columns: Option<&[Column]>,
     azure_client: &mut AzureClient,
 ) -> Result<()> {
     let (account, container, prefix) = parse_azure_uri(path)?;
@@ -188,44 +535,32 @@ async fn list_azure_objects(
     // Special case: If we have an account but no container (az://account or az://account/),
     // list all containers in that account
     if account.is_some() && container.is_empty() {
-        return list_containers(long, &mut client).await;
+        if cached {
+            return Err(anyhow!(
+                "--cached lists blobs: az://<account>/<container>/[prefix]"
+            ));
+        }
+        return list_containers(long, None, &mut client).await;
     }
 
-    //Check if the prefix contains wildcards
-    let (list_prefix, pattern, force_recursive) = if let Some(prefix_str) = &prefix {
-        if let Some((before_wildcard, mut wildcard_pattern)) = split_wildcard_path(prefix_str) {
-            // Has wildcard: list with prefix before wildcard, then filter with pattern
-            // If pattern contains **, force recursive listing (no delimiter)
-            // Also force recursive if the wildcard pattern contains / (multi-segment pattern)
-            let is_recursive =
-                contains_recursive_wildcard(&wildcard_pattern) || wildcard_pattern.contains('/');
-
-            // If pattern ends with /, append * to match contents of that directory
-            if wildcard_pattern.ends_with('/') {
-                wildcard_pattern.push('*');
-            }
-
-            (
-                if before_wildcard.is_empty() {
-                    None
-                } else {
-                    Some(before_wildcard)
-                },
-                Some(wildcard_pattern),
-                is_recursive,
-            )
-        } else {
-            // No wildcard: use prefix as-is
-            (prefix.clone(), None, false)
+    // A path with wildcards lists the prefix before them and keeps the
+    // names matching the rest; one ending with a slash lists the contents
+    // of the directories it matches, unless -d asked for the directories
+    let glob = match prefix.as_deref() {
+        Some(path) if path.ends_with('/') && !directory && contains_wildcard(path) => {
+            Glob::new(&format!("{}*", path))?
         }
-    } else {
-        // No prefix at all
-        (None, None, false)
+        Some(path) => Glob::new(path)?,
+        None => None,
+    };
+    let list_prefix = match &glob {
+        Some(glob) => Some(glob.prefix().to_string()).filter(|prefix| !prefix.is_empty()),
+        None => prefix.clone(),
     };
 
     // Use delimiter for non-recursive listing (hierarchical, like gsutil default behavior)
-    // Omit delimiter for recursive listing or when using ** wildcard
-    let delimiter = if recursive || force_recursive {
+    // Omit delimiter for recursive listing or patterns spanning directories
+    let delimiter = if recursive || glob.as_ref().is_some_and(Glob::spans_directories) {
         None
     } else {
         Some("/")
@@ -237,180 +572,402 @@ async fn list_azure_objects(
         .ok_or_else(|| anyhow!("Storage account not configured"))?
         .to_string();
 
+    // --cached answers from the listing `ls -r` or `azst cache refresh` kept
+    let cache = if cached {
+        let listing = CachedListing::load(&actual_account, &container, list_prefix.as_deref())?;
+        listing.warn_if_stale();
+        Some(listing)
+    } else {
+        None
+    };
+
+    // One user delegation key signs every URL in the listing
+    let signer = match signed_urls {
+        Some(valid_for) => Some(client.url_signer(valid_for).await?),
+        None => None,
+    };
+
+    // Versions come from a second listing, so only count them when shown
+    let version_counts = if columns.is_some_and(|columns| columns.contains(&Column::Versions)) {
+        Some(
+            client
+                .count_blob_versions(&container, list_prefix.as_deref())
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    let format = BlobFormat {
+        long,
+        human_readable,
+        columns,
+        signer: signer.as_ref(),
+        version_counts,
+        totals,
+    };
+
+    // -d lists the entry the path names rather than its contents
+    if directory && glob.is_none() {
+        return list_entry_itself(
+            &mut client,
+            &container,
+            &actual_account,
+            list_prefix.as_deref(),
+            &format,
+        )
+        .await;
+    }
+
+    // A path without a trailing slash that names a blob lists just that
+    // blob, as gsutil does for object URLs, rather than every name it
+    // prefixes; otherwise it's listed as a prefix
+    if let (None, None, Some(name)) = (
+        &glob,
+        &cache,
+        list_prefix.as_deref().filter(|name| !name.ends_with('/')),
+    ) {
+        match client.get_blob_properties(&container, name).await {
+            Ok(blob) => {
+                let writer = create_writer();
+                format.write_table_header(writer.as_ref());
+                return format.write_item(
+                    writer.as_ref(),
+                    BlobItem::Blob(blob),
+                    &actual_account,
+                    &container,
+                );
+            }
+            // The listing reports missing containers and denied access
+            Err(e) => tracing::debug!("Listing {} as a prefix: {:#}", name, e),
+        }
+    }
+
+    // Sections per directory need the whole listing; --flat, CSV/JSON and -0
+    // stream one URI per blob instead
+    let grouped = recursive && !flat && !is_machine_readable();
+
     // If there's no pattern, we can stream results directly without buffering
-    if pattern.is_none() {
+    if glob.is_none() && !grouped {
         return list_blobs_streaming(
             &mut client,
             &container,
             &actual_account,
             list_prefix.as_deref(),
             delimiter,
-            long,
-            human_readable,
+            cache.as_ref(),
+            &format,
         )
         .await;
     }
 
     // For patterns, we need to collect and filter all results
-    let blobs = client
-        .list_blobs(&container, list_prefix.as_deref(), delimiter)
-        .await?;
-
-    // Filter blobs if we have a pattern
-    let filtered_blobs: Vec<BlobItem> = if let Some(ref pattern_str) = pattern {
-        // Calculate the expected depth based on the pattern
-        let expected_depth = pattern_depth(pattern_str);
-
-        // If we have a specific depth (not **) and we're NOT in explicit recursive mode,
-        // we need to extract directory prefixes at that depth (hierarchical view)
-        if let Some(depth) = expected_depth {
-            if force_recursive && !recursive {
-                // Extract unique prefixes at the target depth (non-recursive mode with multi-segment pattern)
-                let mut unique_prefixes = std::collections::HashSet::new();
-
-                for item in &blobs {
-                    let name = match item {
-                        BlobItem::Blob(blob) => &blob.name,
-                        BlobItem::Prefix(prefix) => prefix,
-                    };
-
-                    let match_part = if let Some(ref prefix) = list_prefix {
-                        name.strip_prefix(prefix).unwrap_or(name)
-                    } else {
-                        name
-                    };
-
-                    // Extract prefix at target depth
-                    let segments: Vec<&str> = match_part.split('/').collect();
-                    if segments.len() >= depth {
-                        let prefix_at_depth = segments[..depth].join("/") + "/";
-
-                        // Check if this prefix matches the pattern
-                        if matches_pattern(&prefix_at_depth, pattern_str) {
-                            unique_prefixes.insert(prefix_at_depth);
-                        }
+    let blobs = match &cache {
+        Some(cache) => cache.list(list_prefix.as_deref(), delimiter),
+        None => {
+            let blobs = client
+                .list_blobs(&container, list_prefix.as_deref(), delimiter)
+                .await?;
+            if delimiter.is_none() {
+                cache_listing(&actual_account, &container, list_prefix.as_deref(), &blobs);
+            }
+            blobs
+        }
+    };
+
+    // Without -r, names inside a matching directory stand for it
+    let filtered_blobs: Vec<BlobItem> = match &glob {
+        Some(glob) => {
+            let mut entries = BTreeMap::new();
+            for item in blobs {
+                let name = match &item {
+                    BlobItem::Blob(blob) => blob.name.clone(),
+                    BlobItem::Prefix(prefix) => prefix.clone(),
+                };
+                match glob.matches(&name) {
+                    Some(GlobMatch::Under(dir)) if !recursive => {
+                        entries
+                            .entry(dir.to_string())
+                            .or_insert_with(|| BlobItem::Prefix(dir.to_string()));
+                    }
+                    Some(_) => {
+                        entries.insert(name, item);
                     }
+                    None => {}
                 }
-
-                // Convert prefixes to BlobItem::Prefix
-                unique_prefixes
-                    .into_iter()
-                    .map(|prefix| {
-                        let full_name = if let Some(ref list_pfx) = list_prefix {
-                            format!("{}{}", list_pfx, prefix)
-                        } else {
-                            prefix
-                        };
-                        BlobItem::Prefix(full_name)
-                    })
-                    .collect()
-            } else {
-                // Regular filtering for non-recursive with delimiter
-                blobs
-                    .into_iter()
-                    .filter(|item| {
-                        let name = match item {
-                            BlobItem::Blob(blob) => &blob.name,
-                            BlobItem::Prefix(prefix) => prefix,
-                        };
-
-                        let match_part = if let Some(ref prefix) = list_prefix {
-                            name.strip_prefix(prefix).unwrap_or(name)
-                        } else {
-                            name
-                        };
-
-                        matches_pattern(match_part, pattern_str)
-                    })
-                    .collect()
             }
-        } else {
-            // ** pattern - show all matches at any depth
-            blobs
-                .into_iter()
-                .filter(|item| {
-                    let name = match item {
-                        BlobItem::Blob(blob) => &blob.name,
-                        BlobItem::Prefix(prefix) => prefix,
-                    };
-
-                    let match_part = if let Some(ref prefix) = list_prefix {
-                        name.strip_prefix(prefix).unwrap_or(name)
-                    } else {
-                        name
-                    };
-
-                    matches_pattern(match_part, pattern_str)
-                })
-                .collect()
+            entries.into_values().collect()
         }
-    } else {
-        blobs
+        None => blobs,
     };
 
     if filtered_blobs.is_empty() {
-        if pattern.is_some() {
-            println!(
+        if glob.is_some() {
+            report_empty(&format!(
                 "No objects matching pattern in az://{}/{}/",
                 actual_account, container
-            );
+            ));
         } else {
-            println!("No objects found in az://{}/{}/", actual_account, container);
+            report_empty(&format!(
+                "No objects found in az://{}/{}/",
+                actual_account, container
+            ));
         }
         return Ok(());
     }
 
     let writer = create_writer();
+    if grouped {
+        format.write_table_header(writer.as_ref());
+        return write_sections(
+            writer.as_ref(),
+            &format,
+            filtered_blobs,
+            &actual_account,
+            &container,
+        );
+    }
     writer.write_header(&format!(
         "Contents of az://{}/{}:",
         actual_account, container
     ));
 
-    if long {
-        writer.write_table_header(&[("Size", 10), ("Type", 15), ("Modified", 20), ("Name", 0)]);
-        writer.write_separator(80);
-    }
+    format.write_table_header(writer.as_ref());
 
     for item in filtered_blobs {
-        match item {
-            BlobItem::Blob(blob) => {
-                let size_str = if human_readable {
-                    format_size(blob.properties.content_length)
-                } else {
-                    blob.properties.content_length.to_string()
-                };
+        format.write_item(writer.as_ref(), item, &actual_account, &container)?;
+    }
 
-                let content_type = blob
-                    .properties
-                    .content_type
-                    .unwrap_or_else(|| "unknown".to_string());
+    Ok(())
+}
 
-                let blob_uri = format!("az://{}/{}/{}", actual_account, container, blob.name);
+/// Keep the blobs of a recursive listing for `--cached`
+fn cache_listing(account: &str, container: &str, prefix: Option<&str>, items: &[BlobItem]) {
+    let blobs = items
+        .iter()
+        .filter_map(|item| match item {
+            BlobItem::Blob(blob) => Some(blob.clone()),
+            BlobItem::Prefix(_) => None,
+        })
+        .collect();
+    CachedListing::new(account, container, prefix, blobs).save_quietly();
+}
 
-                writer.write_blob(
-                    &blob_uri,
-                    &size_str,
-                    &content_type,
-                    &blob.properties.last_modified,
-                    long,
-                );
-            }
-            BlobItem::Prefix(prefix) => {
-                // Display directory/prefix with trailing slash
-                let prefix_uri = format!("az://{}/{}/{}", actual_account, container, prefix);
-                writer.write_prefix(&prefix_uri, long);
-            }
-        }
+/// `ls -d`: the blob or directory `prefix` names, or the container itself
+async fn list_entry_itself(
+    client: &mut AzureClient,
+    container: &str,
+    actual_account: &str,
+    prefix: Option<&str>,
+    format: &BlobFormat<'_>,
+) -> Result<()> {
+    let writer = create_writer();
+    let Some(name) = prefix.map(|prefix| prefix.trim_end_matches('/')) else {
+        writer.write_prefix(
+            &format!("az://{}/{}/", actual_account, container),
+            format.long,
+        );
+        return Ok(());
+    };
+
+    // A delimited listing of the name finds both the blob and the directory
+    let directory = format!("{}/", name);
+    let entries: Vec<BlobItem> = client
+        .list_blobs(container, Some(name), Some("/"))
+        .await?
+        .into_iter()
+        .filter(|item| match item {
+            BlobItem::Blob(blob) => blob.name == name,
+            BlobItem::Prefix(prefix) => *prefix == directory,
+        })
+        .collect();
+
+    if entries.is_empty() {
+        report_empty(&format!(
+            "No objects found at az://{}/{}/{}",
+            actual_account, container, name
+        ));
+        return Ok(());
+    }
+
+    format.write_table_header(writer.as_ref());
+    for item in entries {
+        format.write_item(writer.as_ref(), item, actual_account, container)?;
+    }
+    Ok(())
+}
+
+/// Directory of a blob or prefix name, with its trailing slash ("" at the top)
+fn parent_directory(name: &str) -> &str {
+    match name.trim_end_matches('/').rfind('/') {
+        Some(slash) => &name[..=slash],
+        None => "",
+    }
+}
+
+/// Write a recursive listing the way `gsutil ls -r` does: one section per
+/// directory, headed by its URI, with the blobs directly inside it
+fn write_sections(
+    writer: &dyn OutputWriter,
+    format: &BlobFormat<'_>,
+    items: Vec<BlobItem>,
+    account: &str,
+    container: &str,
+) -> Result<()> {
+    let mut sections: BTreeMap<String, Vec<BlobItem>> = BTreeMap::new();
+    for item in items {
+        let name = match &item {
+            BlobItem::Blob(blob) => &blob.name,
+            BlobItem::Prefix(prefix) => prefix,
+        };
+        sections
+            .entry(parent_directory(name).to_string())
+            .or_default()
+            .push(item);
     }
 
+    for (index, (directory, items)) in sections.into_iter().enumerate() {
+        if index > 0 {
+            writer.write_header("");
+        }
+        writer.write_header(&format!("az://{}/{}/{}:", account, container, directory));
+        for item in items {
+            format.write_item(writer, item, account, container)?;
+        }
+    }
     Ok(())
 }
 
+/// Print blobs added, modified or deleted under `path` since the listing
+/// recorded in `state_path`, then record the current listing there
+///
+/// Compares ETags, so any write is detected even when the size and
+/// modification time are unchanged. Without a recorded listing every blob is
+/// reported as added.
+async fn list_changes(path: &str, state_path: &str, azure_client: &mut AzureClient) -> Result<()> {
+    let (account, container, prefix) = parse_azure_uri(path)?;
+    if container.is_empty() {
+        return Err(anyhow!(
+            "--since-state needs a container: az://<account>/<container>/[prefix]"
+        ));
+    }
+
+    let mut client = match &account {
+        Some(account_name) => AzureClient::new().with_storage_account(account_name),
+        None => azure_client.clone(),
+    };
+    let actual_account = client
+        .get_storage_account()
+        .ok_or_else(|| anyhow!("Storage account not configured"))?
+        .to_string();
+
+    let previous = load_listing_state(state_path)?;
+    if let Some(previous) = &previous {
+        if previous.location != path {
+            return Err(anyhow!(
+                "State file '{}' was recorded for {}, not {}",
+                state_path,
+                previous.location,
+                path
+            ));
+        }
+    }
+
+    let current = list_etags(&mut client, &container, prefix.as_deref()).await?;
+
+    let empty = Etags::new();
+    let changes = diff_listing(
+        previous.as_ref().map_or(&empty, |state| &state.etags),
+        &current,
+    );
+    for change in &changes {
+        let (status, name) = match change {
+            Change::Added(name) => ("A", name),
+            Change::Modified(name) => ("M", name),
+            Change::Deleted(name) => ("D", name),
+        };
+        println!("{}\taz://{}/{}/{}", status, actual_account, container, name);
+    }
+    if changes.is_empty() && is_interactive() {
+        println!("No changes since the last listing");
+    }
+
+    let state = ListingState {
+        location: path.to_string(),
+        etags: current,
+    };
+    std::fs::write(state_path, serde_json::to_string_pretty(&state)?)
+        .with_context(|| format!("Failed to write listing state file '{}'", state_path))
+}
+
+/// ETags of every blob under `prefix`, which may end in a wildcard pattern
+pub async fn list_etags(
+    client: &mut AzureClient,
+    container: &str,
+    prefix: Option<&str>,
+) -> Result<Etags> {
+    // Changes anywhere under the path count, so always list recursively
+    let glob = prefix.map(Glob::new).transpose()?.flatten();
+    let list_prefix = match &glob {
+        Some(glob) => Some(glob.prefix()).filter(|prefix| !prefix.is_empty()),
+        None => prefix,
+    };
+    Ok(client
+        .list_blobs(container, list_prefix, None)
+        .await?
+        .into_iter()
+        .filter_map(|item| match item {
+            BlobItem::Blob(blob) => Some(blob),
+            BlobItem::Prefix(_) => None,
+        })
+        .filter(|blob| {
+            glob.as_ref()
+                .is_none_or(|glob| glob.selects(&blob.name, true))
+        })
+        .map(|blob| (blob.name, blob.properties.etag.unwrap_or_default()))
+        .collect())
+}
+
+/// The recorded listing, or None on the first run
+fn load_listing_state(path: &str) -> Result<Option<ListingState>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read listing state file '{}'", path))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .with_context(|| format!("Listing state file '{}' is not valid JSON", path))
+}
+
+/// Changes between two listings, in name order
+pub fn diff_listing<'a>(previous: &'a Etags, current: &'a Etags) -> Vec<Change<'a>> {
+    let mut changes: Vec<Change> = current
+        .iter()
+        .filter_map(|(name, etag)| match previous.get(name) {
+            None => Some(Change::Added(name)),
+            Some(old) if old != etag => Some(Change::Modified(name)),
+            Some(_) => None,
+        })
+        .chain(
+            previous
+                .keys()
+                .filter(|name| !current.contains_key(*name))
+                .map(|name| Change::Deleted(name)),
+        )
+        .collect();
+    changes.sort_by_key(|change| match change {
+        Change::Added(name) | Change::Modified(name) | Change::Deleted(name) => *name,
+    });
+    changes
+}
+
 async fn list_local_path(
     path: &str,
     long: bool,
     human_readable: bool,
     recursive: bool,
+    directory: bool,
 ) -> Result<()> {
     use std::path::Path;
 
@@ -420,7 +977,18 @@ async fn list_local_path(
         return Err(anyhow!("Path '{}' does not exist", path));
     }
 
-    if path_obj.is_file() {
+    if directory && path_obj.is_dir() {
+        // -d: the directory itself, not its contents
+        let name = format!("{}/", path.trim_end_matches('/'));
+        let size = path_obj.metadata()?.len();
+        let size_str = match (long, human_readable) {
+            (false, _) => String::new(),
+            (true, true) => format_size(size),
+            (true, false) => size.to_string(),
+        };
+        create_writer().write_local_file(&name, &size_str, "dir", long);
+        Ok(())
+    } else if path_obj.is_file() {
         // List single file
         list_single_file(path, long, human_readable).await
     } else if path_obj.is_dir() {
@@ -459,8 +1027,6 @@ async fn list_directory(
     human_readable: bool,
     recursive: bool,
 ) -> Result<()> {
-    use tokio::fs;
-
     let writer = create_writer();
 
     if long {
@@ -471,9 +1037,7 @@ async fn list_directory(
     if recursive {
         list_directory_recursive(dir_path, "", long, human_readable).await
     } else {
-        let mut entries = fs::read_dir(dir_path).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
+        for entry in read_dir_entries(dir_path).await? {
             let entry_path = entry.path();
             let entry_name = entry.file_name();
             let name_str = entry_name.to_str().unwrap_or("?");
@@ -516,12 +1080,9 @@ fn list_directory_recursive<'a>(
     human_readable: bool,
 ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
     Box::pin(async move {
-        use tokio::fs;
-
         let writer = create_writer();
-        let mut entries = fs::read_dir(dir_path).await?;
 
-        while let Some(entry) = entries.next_entry().await? {
+        for entry in read_dir_entries(dir_path).await? {
             let entry_path = entry.path();
             let entry_name = entry.file_name();
             let name_str = entry_name.to_str().unwrap_or("?");
@@ -568,8 +1129,146 @@ fn list_directory_recursive<'a>(
     })
 }
 
+/// Entries of a local directory, sorted by name under --deterministic
+async fn read_dir_entries(dir_path: &str) -> Result<Vec<tokio::fs::DirEntry>> {
+    let mut entries = tokio::fs::read_dir(dir_path).await?;
+    let mut result = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        result.push(entry);
+    }
+    if is_deterministic() {
+        result.sort_by_key(|entry| entry.file_name());
+    }
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn storage_error(status: StatusCode, code: &str) -> anyhow::Error {
+        anyhow::Error::new(azure_core::error::Error::message(
+            azure_core::error::ErrorKind::HttpResponse {
+                status,
+                error_code: Some(code.to_string()),
+            },
+            "request failed",
+        ))
+        .context("Failed to fetch blob page")
+    }
+
+    #[test]
+    fn test_friendly_listing_error() {
+        let client = AzureClient::new();
+
+        let e = friendly_listing_error(
+            storage_error(StatusCode::NotFound, "ContainerNotFound"),
+            "az://acct/logs/2024/",
+            &client,
+        );
+        assert_eq!(crate::error::exit_code(&e), crate::error::EXIT_NOT_FOUND);
+        assert!(e
+            .to_string()
+            .starts_with("Container 'logs' does not exist in storage account 'acct'"));
+
+        let e = friendly_listing_error(
+            storage_error(StatusCode::Forbidden, "AuthorizationPermissionMismatch"),
+            "az://acct/logs/",
+            &client,
+        );
+        assert_eq!(
+            crate::error::exit_code(&e),
+            crate::error::EXIT_PERMISSION_DENIED
+        );
+        assert!(e.to_string().contains("list az://acct/logs/"));
+        assert!(e.to_string().contains("Storage Blob Data Reader"));
+
+        let e = friendly_listing_error(
+            storage_error(StatusCode::Forbidden, "AuthorizationFailure"),
+            "az://acct/",
+            &client,
+        );
+        assert!(e.to_string().contains("list the containers of az://acct/"));
+        assert!(e.to_string().contains("firewall"));
+
+        let e = friendly_listing_error(
+            storage_error(StatusCode::InternalServerError, "InternalError"),
+            "az://acct/logs/",
+            &client,
+        );
+        assert_eq!(
+            format!("{:#}", e),
+            "Failed to fetch blob page: request failed"
+        );
+    }
+
+    fn etags(entries: &[(&str, &str)]) -> Etags {
+        entries
+            .iter()
+            .map(|(name, etag)| (name.to_string(), etag.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_blob_cell() {
+        let blob: BlobInfo = serde_json::from_str(
+            r#"{
+                "name": "data/file.csv",
+                "properties": {
+                    "contentLength": 2048,
+                    "lastModified": "2024-01-01T00:00:00Z",
+                    "contentType": "text/csv",
+                    "etag": "0x8DC1",
+                    "accessTier": "Cool",
+                    "blobType": "BlockBlob"
+                }
+            }"#,
+        )
+        .unwrap();
+        let uri = "az://acct/cont/data/file.csv";
+
+        assert_eq!(blob_cell(Column::Size, &blob, uri, false), "2048");
+        assert_eq!(blob_cell(Column::Size, &blob, uri, true), "2.0 KB");
+        assert_eq!(blob_cell(Column::Etag, &blob, uri, false), "0x8DC1");
+        assert_eq!(blob_cell(Column::Tier, &blob, uri, false), "Cool");
+        assert_eq!(blob_cell(Column::BlobType, &blob, uri, false), "BlockBlob");
+        assert_eq!(blob_cell(Column::Lease, &blob, uri, false), "-");
+        assert_eq!(blob_cell(Column::Versions, &blob, uri, false), "-");
+        assert_eq!(blob_cell(Column::Name, &blob, uri, false), uri);
+    }
+
+    #[test]
+    fn test_parent_directory() {
+        assert_eq!(parent_directory("a.txt"), "");
+        assert_eq!(parent_directory("logs/a.txt"), "logs/");
+        assert_eq!(parent_directory("logs/2024/a.txt"), "logs/2024/");
+        assert_eq!(parent_directory("logs/2024/"), "logs/");
+    }
+
+    #[test]
+    fn test_diff_listing() {
+        let previous = etags(&[("a.csv", "0x1"), ("b.csv", "0x2"), ("c.csv", "0x3")]);
+        let current = etags(&[("a.csv", "0x1"), ("b.csv", "0x9"), ("d.csv", "0x4")]);
+        assert_eq!(
+            diff_listing(&previous, &current),
+            vec![
+                Change::Modified("b.csv"),
+                Change::Deleted("c.csv"),
+                Change::Added("d.csv"),
+            ]
+        );
+        assert!(diff_listing(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_load_listing_state_missing_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("state.json");
+        assert!(load_listing_state(path.to_str().unwrap())
+            .unwrap()
+            .is_none());
+    }
+
     #[test]
     fn test_list_containers_docs() {
         // Test case: azst ls
@@ -588,6 +1287,13 @@ mod tests {
         // Expected: List blobs starting with prefix
     }
 
+    #[test]
+    fn test_list_single_blob_docs() {
+        // Test case: azst ls az://account/container/dir/file.txt
+        // Expected: Only that blob (not file.txt.bak), found with a HEAD
+        // request; a name no blob has is listed as a prefix
+    }
+
     #[test]
     fn test_list_long_format_docs() {
         // Test case: azst ls -l az://account/container/
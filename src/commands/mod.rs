@@ -1,7 +1,31 @@
+pub mod account;
+pub mod alias;
+pub mod audit;
+pub mod auth;
+pub mod azcopy;
+pub mod bench;
+pub mod cache;
 pub mod cat;
+pub mod complete;
+pub mod compose;
 pub mod cp;
+pub mod cp_status;
+pub mod ctl;
 pub mod du;
+pub mod hash;
+pub mod immutability;
+pub mod inventory;
+pub mod lease;
+pub mod lifecycle;
 pub mod ls;
+#[cfg(feature = "fuse")]
+pub mod mount;
 pub mod mv;
+pub mod parse;
+pub mod retry_failed;
 pub mod rm;
+pub mod security;
+pub mod serve;
+pub mod shell;
 pub mod sync;
+pub mod watch;
@@ -0,0 +1,482 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, Request,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Handle;
+
+use crate::azure::{AzureClient, BlobItem};
+use crate::blob_reader::{BlobReader, ReadaheadConfig};
+use crate::cancel;
+use crate::config::{Config, ReadaheadSettings};
+use crate::utils::{directory_prefix, format_size, parse_azure_uri};
+
+// ============================================================================
+// Mount - A prefix exposed as a read-only FUSE filesystem
+// ============================================================================
+
+/// How long the kernel may cache names and attributes; the listing is taken
+/// once at mount time, so they never change while mounted
+const TTL: Duration = Duration::from_secs(60);
+
+const ROOT: u64 = fuser::FUSE_ROOT_ID;
+
+/// A file or directory of the mounted listing
+#[derive(Debug)]
+struct Node {
+    parent: u64,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+enum NodeKind {
+    /// Children by name
+    Dir(BTreeMap<String, u64>),
+    File {
+        blob: String,
+        size: u64,
+        /// Last modified time as a Unix timestamp (seconds)
+        modified: i64,
+    },
+}
+
+/// The blobs under a prefix as a directory tree, where inode `n` is
+/// `nodes[n - 1]` and the root is inode 1
+#[derive(Debug)]
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    /// Build the tree from (blob name, size, modified) entries under `prefix`
+    ///
+    /// Directory marker blobs ("dir/") become empty directories. A blob whose
+    /// name is taken by a directory, or whose path runs through a file, is
+    /// left out.
+    fn build(prefix: &str, blobs: impl IntoIterator<Item = (String, u64, i64)>) -> Self {
+        let mut tree = Tree {
+            nodes: vec![Node {
+                parent: ROOT,
+                kind: NodeKind::Dir(BTreeMap::new()),
+            }],
+        };
+
+        'blobs: for (blob, size, modified) in blobs {
+            let Some(relative) = blob.strip_prefix(prefix) else {
+                continue;
+            };
+            let mut parts: Vec<&str> = relative.split('/').collect();
+            let name = parts.pop().unwrap_or_default();
+
+            let mut dir = ROOT;
+            for part in parts.into_iter().filter(|part| !part.is_empty()) {
+                dir = match tree.child(dir, part) {
+                    Some(child) if tree.is_dir(child) => child,
+                    Some(_) => {
+                        tracing::warn!(
+                            "Not mounting {}: a file has the name of its directory",
+                            blob
+                        );
+                        continue 'blobs;
+                    }
+                    None => tree.insert(dir, part, NodeKind::Dir(BTreeMap::new())),
+                };
+            }
+            if name.is_empty() {
+                continue;
+            }
+            if tree.child(dir, name).is_some() {
+                tracing::warn!("Not mounting {}: its name is taken by a directory", blob);
+                continue;
+            }
+            let name = name.to_string();
+            tree.insert(
+                dir,
+                &name,
+                NodeKind::File {
+                    blob,
+                    size,
+                    modified,
+                },
+            );
+        }
+
+        tree
+    }
+
+    fn insert(&mut self, parent: u64, name: &str, kind: NodeKind) -> u64 {
+        self.nodes.push(Node { parent, kind });
+        let ino = self.nodes.len() as u64;
+        if let NodeKind::Dir(children) = &mut self.nodes[parent as usize - 1].kind {
+            children.insert(name.to_string(), ino);
+        }
+        ino
+    }
+
+    fn get(&self, ino: u64) -> Option<&Node> {
+        ino.checked_sub(1)
+            .and_then(|index| self.nodes.get(index as usize))
+    }
+
+    fn child(&self, parent: u64, name: &str) -> Option<u64> {
+        match &self.get(parent)?.kind {
+            NodeKind::Dir(children) => children.get(name).copied(),
+            NodeKind::File { .. } => None,
+        }
+    }
+
+    fn is_dir(&self, ino: u64) -> bool {
+        matches!(self.get(ino).map(|node| &node.kind), Some(NodeKind::Dir(_)))
+    }
+
+    /// Number of files and their total size
+    fn totals(&self) -> (usize, u64) {
+        self.nodes
+            .iter()
+            .filter_map(|node| match node.kind {
+                NodeKind::File { size, .. } => Some(size),
+                NodeKind::Dir(_) => None,
+            })
+            .fold((0, 0), |(files, bytes), size| (files + 1, bytes + size))
+    }
+}
+
+/// The FUSE filesystem: a fixed tree, with file contents read on demand
+struct BlobFs {
+    tree: Tree,
+    client: AzureClient,
+    container: String,
+    config: ReadaheadConfig,
+    /// Runtime the FUSE thread runs blob reads on
+    runtime: Handle,
+    /// Open files by handle, each with its own block cache
+    readers: HashMap<u64, BlobReader>,
+    next_fh: u64,
+    uid: u32,
+    gid: u32,
+    mounted_at: SystemTime,
+}
+
+impl BlobFs {
+    fn attr(&self, ino: u64, node: &Node) -> FileAttr {
+        let (kind, perm, size, mtime) = match &node.kind {
+            NodeKind::Dir(_) => (FileType::Directory, 0o555, 0, self.mounted_at),
+            NodeKind::File { size, modified, .. } => {
+                let mtime = u64::try_from(*modified)
+                    .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+                    .unwrap_or(self.mounted_at);
+                (FileType::RegularFile, 0o444, *size, mtime)
+            }
+        };
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: self.config.block_size.min(u32::MAX as u64) as u32,
+            flags: 0,
+        }
+    }
+}
+
+impl Filesystem for BlobFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let found = name
+            .to_str()
+            .and_then(|name| self.tree.child(parent, name))
+            .and_then(|ino| Some((ino, self.tree.get(ino)?)));
+        match found {
+            Some((ino, node)) => reply.entry(&TTL, &self.attr(ino, node), 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.tree.get(ino) {
+            Some(node) => reply.attr(&TTL, &self.attr(ino, node)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            return reply.error(libc::EROFS);
+        }
+        let blob = match self.tree.get(ino).map(|node| &node.kind) {
+            Some(NodeKind::File { blob, .. }) => blob.clone(),
+            Some(NodeKind::Dir(_)) => return reply.error(libc::EISDIR),
+            None => return reply.error(libc::ENOENT),
+        };
+
+        let opened = self.runtime.block_on(BlobReader::open(
+            self.client.clone(),
+            &self.container,
+            &blob,
+            self.config,
+        ));
+        match opened {
+            Ok(reader) => {
+                let fh = self.next_fh;
+                self.next_fh += 1;
+                self.readers.insert(fh, reader);
+                reply.opened(fh, 0);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to open {}: {:#}", blob, e);
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let (Some(reader), Ok(offset)) = (self.readers.get_mut(&fh), u64::try_from(offset)) else {
+            return reply.error(libc::EBADF);
+        };
+        match self.runtime.block_on(reader.read_at(offset, size as u64)) {
+            Ok(data) => reply.data(&data),
+            Err(e) => {
+                tracing::warn!("Failed to read at offset {}: {:#}", offset, e);
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.readers.remove(&fh);
+        reply.ok();
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(node) = self.tree.get(ino) else {
+            return reply.error(libc::ENOENT);
+        };
+        let NodeKind::Dir(children) = &node.kind else {
+            return reply.error(libc::ENOTDIR);
+        };
+
+        let entries = [(ino, ".".to_string()), (node.parent, "..".to_string())]
+            .into_iter()
+            .chain(children.iter().map(|(name, child)| (*child, name.clone())));
+        for (index, (child, name)) in entries.enumerate().skip(offset.max(0) as usize) {
+            let kind = if self.tree.is_dir(child) {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            };
+            // The offset given back is where the next readdir call resumes
+            if reply.add(child, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Mount the blobs under `uri` read-only on `mountpoint`, until Ctrl-C or
+/// an external unmount
+pub async fn execute(
+    uri: &str,
+    mountpoint: &Path,
+    readahead: ReadaheadSettings,
+    allow_other: bool,
+) -> Result<()> {
+    if readahead.block_size == Some(0) {
+        return Err(anyhow!("--block-size must be greater than zero"));
+    }
+    let config = ReadaheadConfig::from_settings(readahead.or(Config::load()?.readahead));
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    let prefix = directory_prefix(prefix.as_deref());
+    let metadata = std::fs::metadata(mountpoint)
+        .with_context(|| format!("Mount point {} does not exist", mountpoint.display()))?;
+    if !metadata.is_dir() {
+        return Err(anyhow!(
+            "Mount point {} is not a directory",
+            mountpoint.display()
+        ));
+    }
+
+    let mut client = AzureClient::new();
+    if let Some(account) = account {
+        client = client.with_storage_account(&account);
+    }
+    client.check_prerequisites().await?;
+
+    status!("{} Listing {}", "→".green(), uri.cyan());
+    let mut blobs = Vec::new();
+    let list_prefix = (!prefix.is_empty()).then_some(prefix.as_str());
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    let properties = &blob.properties;
+                    blobs.push((
+                        blob.name.clone(),
+                        properties.content_length,
+                        properties.last_modified_unix,
+                    ));
+                }
+            }
+            Ok(())
+        })
+        .await?;
+    let tree = Tree::build(&prefix, blobs);
+    let (files, bytes) = tree.totals();
+
+    let filesystem = BlobFs {
+        tree,
+        client,
+        container,
+        config,
+        runtime: Handle::current(),
+        readers: HashMap::new(),
+        next_fh: 1,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mounted_at: SystemTime::now(),
+    };
+    let mut options = vec![
+        MountOption::RO,
+        MountOption::FSName(uri.to_string()),
+        MountOption::Subtype("azst".to_string()),
+        MountOption::DefaultPermissions,
+    ];
+    if allow_other {
+        options.push(MountOption::AllowOther);
+    }
+    let session = fuser::spawn_mount2(filesystem, mountpoint, &options)
+        .with_context(|| format!("Failed to mount {} on {}", uri, mountpoint.display()))?;
+
+    status!(
+        "{} Mounted {} ({} files, {}) read-only on {}",
+        "✓".green(),
+        uri.cyan(),
+        files,
+        format_size(bytes),
+        mountpoint.display()
+    );
+    status!(
+        "{} Press Ctrl-C or run `fusermount -u {}` to unmount",
+        "ℹ".blue(),
+        mountpoint.display()
+    );
+
+    let cancel = cancel::token();
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {
+                if session.guard.is_finished() {
+                    break;
+                }
+            }
+        }
+    }
+    // Dropping the session unmounts the filesystem if it still is mounted
+    drop(session);
+
+    status!("{} Unmounted {}", "✓".green(), mountpoint.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(prefix: &str, names: &[&str]) -> Tree {
+        Tree::build(
+            prefix,
+            names
+                .iter()
+                .map(|name| (name.to_string(), 10, 1_700_000_000)),
+        )
+    }
+
+    fn path(tree: &Tree, path: &str) -> Option<u64> {
+        path.split('/')
+            .try_fold(ROOT, |dir, name| tree.child(dir, name))
+    }
+
+    #[test]
+    fn test_tree_nests_blobs_under_prefix() {
+        let tree = tree(
+            "data/",
+            &["data/a.csv", "data/2024/01/b.csv", "data/2024/c.csv"],
+        );
+
+        assert!(tree.is_dir(path(&tree, "2024").unwrap()));
+        assert!(tree.is_dir(path(&tree, "2024/01").unwrap()));
+        let file = path(&tree, "2024/01/b.csv").unwrap();
+        assert!(matches!(
+            &tree.get(file).unwrap().kind,
+            NodeKind::File { blob, .. } if blob == "data/2024/01/b.csv"
+        ));
+        assert_eq!(
+            tree.get(file).unwrap().parent,
+            path(&tree, "2024/01").unwrap()
+        );
+        assert_eq!(tree.totals(), (3, 30));
+    }
+
+    #[test]
+    fn test_tree_markers_become_empty_dirs() {
+        let tree = tree("", &["empty/", "logs/app.log"]);
+
+        let empty = path(&tree, "empty").unwrap();
+        assert!(
+            matches!(&tree.get(empty).unwrap().kind, NodeKind::Dir(children) if children.is_empty())
+        );
+        assert_eq!(tree.totals(), (1, 10));
+    }
+
+    #[test]
+    fn test_tree_skips_names_taken_by_files() {
+        let tree = tree("", &["a", "a/b", "c/", "c"]);
+
+        assert!(!tree.is_dir(path(&tree, "a").unwrap()));
+        assert_eq!(path(&tree, "a/b"), None);
+        assert!(tree.is_dir(path(&tree, "c").unwrap()));
+        assert_eq!(tree.totals(), (1, 10));
+    }
+}
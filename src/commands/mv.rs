@@ -1,13 +1,52 @@
 use anyhow::{anyhow, Result};
 use colored::*;
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::path::Path;
 
-use crate::commands::{cp, rm};
-use crate::utils::is_azure_uri;
+use crate::audit_log;
+use crate::azure::{AzCopyClient, AzureClient, OverwritePolicy};
+use crate::commands::cp::{self, CopyOptions};
+use crate::commands::sync::delete_batch;
+use crate::config::TransferConfig;
+use crate::error::AzstError;
+use crate::prompt::Prompter;
+use crate::sync_state::{join_location, snapshot};
+use crate::url_copy::is_http_url;
+use crate::utils::{
+    contains_wildcard, is_azure_uri, is_directory, matches_any_pattern, matches_pattern,
+    parse_azure_uri,
+};
 
-pub async fn execute(source: &str, destination: &str, recursive: bool, force: bool) -> Result<()> {
+/// Blobs copied and deleted at once by a server-side rename
+const RENAME_CONCURRENCY: usize = 16;
+
+/// Copy `options.source` to its destination, then delete what was copied
+///
+/// Directories move into the destination and content is moved as it is
+/// stored, so `as_subdir` and `decompress` are set here.
+pub async fn execute(options: CopyOptions<'_>, force: bool) -> Result<()> {
+    let CopyOptions {
+        source,
+        destination,
+        recursive,
+        dry_run,
+        include_pattern,
+        exclude_pattern,
+        overwrite,
+        prompter,
+        ..
+    } = options;
+    let filters = options.filters.clone();
     let source_is_azure = is_azure_uri(source);
     let dest_is_azure = is_azure_uri(destination);
 
+    if is_http_url(source) {
+        return Err(anyhow!(
+            "Web URLs can't be moved; use 'cp' to copy them into Azure"
+        ));
+    }
+
     // Validate that at least one side is Azure
     if !source_is_azure && !dest_is_azure {
         return Err(anyhow!(
@@ -15,7 +54,7 @@ pub async fn execute(source: &str, destination: &str, recursive: bool, force: bo
         ));
     }
 
-    println!(
+    status!(
         "{} {} {} to {}",
         "⇄".green(),
         "Moving".bold(),
@@ -23,25 +62,394 @@ pub async fn execute(source: &str, destination: &str, recursive: bool, force: bo
         destination.cyan()
     );
 
+    // Within one account, blobs can be renamed without moving any data
+    if let Some(rename) = server_side_rename(source, destination)? {
+        let unfiltered =
+            include_pattern.is_none() && exclude_pattern.is_none() && filters.is_empty();
+        if unfiltered && overwrite == OverwritePolicy::Always {
+            return rename.run(recursive, force, dry_run, prompter).await;
+        }
+    }
+
+    // List the sources first: files that appear while copying weren't
+    // copied and must stay
+    let (root, files) = source_files(source, recursive).await?;
+
     // Step 1: Copy the source to destination
-    println!("{} Step 1: Copying files...", "→".dimmed());
-    cp::execute(
-        source,
-        destination,
-        recursive,
-        false,
-        None,
-        None,
-        false,
-        None,
-        None,
-    )
+    status!("{} Step 1: Copying files...", "→".dimmed());
+    let incomplete = cp::copy(CopyOptions {
+        as_subdir: true,
+        decompress: Some(false),
+        ..options
+    })
     .await?;
 
-    // Step 2: Remove the source
-    println!("{} Step 2: Removing source files...", "×".dimmed());
-    rm::execute(source, recursive, force, false, None, None).await?;
+    // Step 2: Remove the sources that were copied, leaving failed and
+    // skipped transfers in place
+    status!("{} Step 2: Removing source files...", "×".dimmed());
+    let not_copied: HashSet<String> = incomplete
+        .iter()
+        .map(|transfer| location_key(&transfer.source))
+        .collect();
+    let moved: Vec<String> = files
+        .into_iter()
+        .filter(|relative| {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            include_pattern.is_none_or(|patterns| matches_any_pattern(name, patterns))
+                && !exclude_pattern.is_some_and(|patterns| matches_any_pattern(name, patterns))
+                && filters.matches(relative)
+                && !not_copied.contains(&location_key(&join_location(&root, relative)))
+        })
+        .collect();
 
-    println!("{} Move operation completed successfully", "✓".green());
+    if dry_run {
+        for relative in &moved {
+            status!(
+                "  {} {} (would remove)",
+                "×".red(),
+                join_location(&root, relative)
+            );
+        }
+        return Ok(());
+    }
+
+    if !moved.is_empty() && (force || confirm_removal(prompter, source, moved.len()).await?) {
+        let mut azcopy = AzCopyClient::new();
+        if source_is_azure {
+            azcopy.check_prerequisites().await?;
+        }
+        delete_batch(&mut azcopy, &root, &moved, &TransferConfig::default()).await?;
+        if !source_is_azure && recursive && is_directory(source) {
+            remove_empty_dirs(Path::new(source));
+        }
+    }
+
+    if !incomplete.is_empty() {
+        return Err(anyhow!(
+            "{} file(s) were not copied and remain at the source",
+            incomplete.len()
+        ));
+    }
+
+    status!("{} Move operation completed successfully", "✓".green());
     Ok(())
 }
+
+/// A move between two paths of the same storage account
+struct ServerSideRename {
+    account: String,
+    container: String,
+    /// Source blob or directory, without a trailing slash
+    path: String,
+    to_container: String,
+    /// Destination prefix: the new name, or where the source's name goes
+    to_path: Option<String>,
+    /// Whether the destination names a directory to move the source into
+    into_directory: bool,
+}
+
+/// Plan a server-side rename when source and destination are in the same
+/// account and the source is a single path rather than a wildcard
+fn server_side_rename(source: &str, destination: &str) -> Result<Option<ServerSideRename>> {
+    if !is_azure_uri(source) || !is_azure_uri(destination) || contains_wildcard(source) {
+        return Ok(None);
+    }
+    let (account, container, path) = parse_azure_uri(source)?;
+    let (to_account, to_container, to_path) = parse_azure_uri(destination)?;
+    let (Some(account), Some(path)) = (account, path) else {
+        return Ok(None);
+    };
+    let path = path.trim_end_matches('/').to_string();
+    if to_account.as_deref() != Some(account.as_str()) || to_container.is_empty() || path.is_empty()
+    {
+        return Ok(None);
+    }
+
+    let to_path = to_path
+        .map(|p| p.trim_end_matches('/').to_string())
+        .filter(|p| !p.is_empty());
+    Ok(Some(ServerSideRename {
+        account,
+        container,
+        path,
+        to_container,
+        into_directory: to_path.is_none() || destination.ends_with('/'),
+        to_path,
+    }))
+}
+
+impl ServerSideRename {
+    /// The new name of `path` when it moves; directories, like with azcopy,
+    /// always move into the destination
+    fn target(&self, is_directory: bool) -> String {
+        let name = self.path.rsplit('/').next().unwrap_or(&self.path);
+        match &self.to_path {
+            Some(to_path) if is_directory || self.into_directory => format!("{}/{}", to_path, name),
+            Some(to_path) => to_path.clone(),
+            None => name.to_string(),
+        }
+    }
+
+    fn uri(&self, container: &str, path: &str) -> String {
+        format!("az://{}/{}/{}", self.account, container, path)
+    }
+
+    /// Rename atomically through the DFS endpoint on accounts with a
+    /// hierarchical namespace, otherwise copy and delete blob by blob
+    async fn run(
+        &self,
+        recursive: bool,
+        force: bool,
+        dry_run: bool,
+        prompter: &dyn Prompter,
+    ) -> Result<()> {
+        let mut client = AzureClient::new().with_storage_account(&self.account);
+        client.check_prerequisites().await?;
+
+        let source_root = self.uri(&self.container, &self.path);
+        let blobs: Vec<String> = if recursive {
+            snapshot(&source_root).await?.into_keys().collect()
+        } else {
+            Vec::new()
+        };
+        let is_directory = !blobs.is_empty();
+        let target = self.target(is_directory);
+        let target_uri = self.uri(&self.to_container, &target);
+
+        status!(
+            "{} Renaming server-side within {}",
+            "ℹ".blue(),
+            self.account.cyan()
+        );
+        if dry_run {
+            status!(
+                "  {} {} → {} (would rename)",
+                "→".green(),
+                source_root,
+                target_uri
+            );
+            return Ok(());
+        }
+        if !force
+            && !confirm(
+                prompter,
+                &format!("rename {} to {}?", source_root.yellow(), target_uri),
+            )
+            .await?
+        {
+            return Ok(());
+        }
+
+        // Without -r only a blob moves, never a whole directory
+        if recursive
+            && self.container == self.to_container
+            && client.is_hns_enabled(&self.container).await?
+        {
+            match client
+                .rename_path(&self.container, &self.path, &target)
+                .await
+            {
+                Ok(()) => {
+                    audit_log::record(blobs.len().max(1) as u64, 0);
+                    status!("{} Move operation completed successfully", "✓".green());
+                    return Ok(());
+                }
+                // e.g. the target directory exists; merge blob by blob instead
+                Err(e) => tracing::debug!("DFS rename failed, copying instead: {:#}", e),
+            }
+        }
+
+        let renames: Vec<(String, String)> = if is_directory {
+            blobs
+                .iter()
+                .map(|blob| {
+                    (
+                        format!("{}/{}", self.path, blob),
+                        format!("{}/{}", target, blob),
+                    )
+                })
+                .collect()
+        } else {
+            vec![(self.path.clone(), target)]
+        };
+
+        let total = renames.len();
+        let results: Vec<Result<()>> = futures::stream::iter(renames)
+            .map(|(from, to)| {
+                let mut client = client.clone();
+                async move {
+                    client
+                        .copy_blob(&self.container, &from, &self.to_container, &to)
+                        .await?;
+                    client.delete_blob(&self.container, &from, None).await?;
+                    Ok(())
+                }
+            })
+            .buffer_unordered(RENAME_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut failed = 0;
+        for error in results.into_iter().filter_map(Result::err) {
+            eprintln!("  {} {:#}", "×".red(), error);
+            failed += 1;
+        }
+        audit_log::record((total - failed) as u64, 0);
+        if failed > 0 {
+            return Err(AzstError::TransferFailed(format!(
+                "{} of {} blobs were not moved and remain at the source",
+                failed, total
+            ))
+            .into());
+        }
+
+        status!("{} Moved {} blob(s) server-side", "✓".green(), total);
+        Ok(())
+    }
+}
+
+/// The files a move copies from `source`, as a root location and paths
+/// relative to it
+async fn source_files(source: &str, recursive: bool) -> Result<(String, Vec<String>)> {
+    let (parent, name) = source
+        .trim_end_matches('/')
+        .rsplit_once('/')
+        .unwrap_or((".", source));
+
+    // A wildcard selects entries of its parent; directories only with -r
+    if contains_wildcard(name) {
+        let files = snapshot(parent)
+            .await?
+            .into_keys()
+            .filter(|relative| {
+                let (first, rest) = relative.split_once('/').unwrap_or((relative, ""));
+                matches_pattern(first, name) && (recursive || rest.is_empty())
+            })
+            .collect();
+        return Ok((parent.to_string(), files));
+    }
+
+    if recursive && (is_azure_uri(source) || is_directory(source)) {
+        let files: Vec<String> = snapshot(source).await?.into_keys().collect();
+        // A blob given with -r is a single file rather than a prefix
+        if !files.is_empty() || !is_azure_uri(source) {
+            return Ok((source.to_string(), files));
+        }
+    }
+
+    Ok((parent.to_string(), vec![name.to_string()]))
+}
+
+/// Compare transfer sources regardless of percent-encoding or relative paths
+fn location_key(location: &str) -> String {
+    if is_azure_uri(location) {
+        percent_encoding::percent_decode_str(location.trim_end_matches('/'))
+            .decode_utf8_lossy()
+            .into_owned()
+    } else {
+        std::fs::canonicalize(location)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| location.to_string())
+    }
+}
+
+async fn confirm_removal(prompter: &dyn Prompter, source: &str, count: usize) -> Result<bool> {
+    confirm(
+        prompter,
+        &format!("remove {} copied file(s) from {}?", count, source.yellow()),
+    )
+    .await
+}
+
+async fn confirm(prompter: &dyn Prompter, question: &str) -> Result<bool> {
+    if !prompter
+        .confirm(question, "pass --force to move without asking")
+        .await?
+    {
+        println!("Aborted");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Remove the directories a local move emptied, including the source itself
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    // Fails, as intended, for directories that still hold files
+    let _ = std::fs::remove_dir(dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_key() {
+        assert_eq!(
+            location_key("az://acct/data/a%20b.txt"),
+            location_key("az://acct/data/a b.txt")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "a").unwrap();
+        let dotted = dir.path().join(".").join("a.txt");
+        assert_eq!(
+            location_key(&file.to_string_lossy()),
+            location_key(&dotted.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn test_server_side_rename_targets() {
+        let rename = |source, destination| server_side_rename(source, destination).unwrap();
+
+        let file = rename("az://acct/data/a.txt", "az://acct/data/b.txt").unwrap();
+        assert_eq!(file.target(false), "b.txt");
+        let into = rename("az://acct/data/a.txt", "az://acct/archive/2024/").unwrap();
+        assert_eq!(into.to_container, "archive");
+        assert_eq!(into.target(false), "2024/a.txt");
+        let dir = rename("az://acct/data/raw/", "az://acct/data/old").unwrap();
+        assert_eq!(dir.path, "raw");
+        assert_eq!(dir.target(true), "old/raw");
+        let root = rename("az://acct/data/raw", "az://acct/other").unwrap();
+        assert_eq!(root.target(true), "raw");
+
+        assert!(rename("az://acct/data/a.txt", "az://other/data/").is_none());
+        assert!(rename("az://acct/data/*.txt", "az://acct/data/old/").is_none());
+        assert!(rename("/local/a.txt", "az://acct/data/").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_source_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("data");
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        std::fs::write(root.join("a.csv"), "a").unwrap();
+        std::fs::write(root.join("b.txt"), "b").unwrap();
+        std::fs::write(root.join("nested/c.csv"), "c").unwrap();
+        let root = root.to_string_lossy().to_string();
+
+        let (base, files) = source_files(&root, true).await.unwrap();
+        assert_eq!(base, root);
+        assert_eq!(files, vec!["a.csv", "b.txt", "nested/c.csv"]);
+
+        let (base, files) = source_files(&format!("{}/*.csv", root), false)
+            .await
+            .unwrap();
+        assert_eq!(base, root);
+        assert_eq!(files, vec!["a.csv"]);
+
+        let (base, files) = source_files(&format!("{}/b.txt", root), false)
+            .await
+            .unwrap();
+        assert_eq!(base, root);
+        assert_eq!(files, vec!["b.txt"]);
+    }
+}
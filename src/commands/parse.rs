@@ -0,0 +1,43 @@
+use anyhow::Result;
+use colored::*;
+
+use crate::azure::convert_az_uri_to_url;
+use crate::utils::{contains_wildcard, parse_azure_uri};
+
+/// Show how azst interprets an az:// URI, without contacting Azure
+pub async fn execute(uri: &str) -> Result<()> {
+    let (account, container, path) = parse_azure_uri(uri)?;
+
+    let kind = match (&account, container.is_empty(), &path) {
+        (Some(_), true, _) => "storage account (lists containers)",
+        (_, false, None) => "container",
+        (_, false, Some(p)) if contains_wildcard(p) => "wildcard pattern",
+        (_, false, Some(p)) if p.ends_with('/') => "prefix (directory)",
+        (_, false, Some(_)) => "blob or prefix",
+        (None, true, _) => "unknown",
+    };
+
+    println!("{} {}", "URI:      ".bold(), uri.cyan());
+    match &account {
+        Some(account) => println!("{} {}", "Account:  ".bold(), account),
+        None => println!(
+            "{} {}",
+            "Account:  ".bold(),
+            "(not in URI; legacy az://container/path form, uses --account or the default account)"
+                .dimmed()
+        ),
+    }
+    if !container.is_empty() {
+        println!("{} {}", "Container:".bold(), container);
+    }
+    if let Some(path) = &path {
+        println!("{} {}", "Path:     ".bold(), path);
+    }
+    println!("{} {}", "Kind:     ".bold(), kind);
+
+    if account.is_some() && !container.is_empty() {
+        println!("{} {}", "HTTPS URL:".bold(), convert_az_uri_to_url(uri)?);
+    }
+
+    Ok(())
+}
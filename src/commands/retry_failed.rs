@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use std::path::{Path, PathBuf};
+
+use crate::audit_log;
+use crate::azcopy_output::{AzCopyOperation, FailedTransfer, FailureReport};
+use crate::azure::{convert_url_to_az_uri, AzCopyClient, AzCopyOptions};
+use crate::commands::sync::to_azcopy_location;
+
+/// Retry the transfers recorded in a `--failures-out` file, or those found in
+/// the most recent AzCopy job log when no file is given
+///
+/// A failures file is rewritten with the transfers that still fail, so the
+/// command can simply be run again.
+pub async fn execute(failures_file: Option<&Path>) -> Result<()> {
+    let report = match failures_file {
+        Some(path) => FailureReport::load(path)?,
+        None => {
+            let log = latest_job_log()?;
+            status!("{} Reading failures from {}", "ℹ".blue(), log.display());
+            let contents = std::fs::read_to_string(&log)
+                .with_context(|| format!("Failed to read {}", log.display()))?;
+            FailureReport {
+                operation: AzCopyOperation::Copy,
+                failures: parse_job_log(&contents),
+            }
+        }
+    };
+
+    if report.failures.is_empty() {
+        println!("{} No failed transfers to retry", "✓".green());
+        return Ok(());
+    }
+
+    let mut azcopy = AzCopyClient::new();
+    azcopy.check_prerequisites().await?;
+
+    let attempted = report.failures.len();
+    let mut remaining = Vec::new();
+    for failure in report.failures {
+        status!("{} Retrying {}", "→".dimmed(), failure.source.cyan());
+        audit_log::add_uri(&failure.source);
+        if !failure.destination.is_empty() {
+            audit_log::add_uri(&failure.destination);
+        }
+        let result = match report.operation {
+            AzCopyOperation::Copy | AzCopyOperation::Sync => {
+                azcopy
+                    .copy_with_options(
+                        &to_azcopy_location(&failure.source)?,
+                        &to_azcopy_location(&failure.destination)?,
+                        &AzCopyOptions::new(),
+                    )
+                    .await
+            }
+            AzCopyOperation::Remove => {
+                azcopy
+                    .remove_with_options(
+                        &to_azcopy_location(&failure.source)?,
+                        &AzCopyOptions::new(),
+                    )
+                    .await
+            }
+        };
+        match result {
+            Ok(summary) if summary.failed_count == 0 => {}
+            // Keep the fresh error code when AzCopy reported one
+            Ok(summary) => remaining.push(
+                summary
+                    .failed_transfers
+                    .into_iter()
+                    .next()
+                    .unwrap_or(failure),
+            ),
+            Err(e) => {
+                eprintln!("{} {}: {:#}", "⚠".yellow(), failure.source, e);
+                remaining.push(failure);
+            }
+        }
+    }
+
+    let still_failing = remaining.len();
+    if let Some(path) = failures_file {
+        FailureReport {
+            operation: report.operation,
+            failures: remaining,
+        }
+        .save(path)?;
+    }
+
+    if still_failing > 0 {
+        return Err(anyhow!(
+            "{} of {} transfers still failing",
+            still_failing,
+            attempted
+        ));
+    }
+    println!("{} Retried {} failed transfer(s)", "✓".green(), attempted);
+    Ok(())
+}
+
+/// Directory AzCopy writes its job logs to
+fn azcopy_log_dir() -> Option<PathBuf> {
+    std::env::var_os("AZCOPY_LOG_LOCATION")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".azcopy")))
+}
+
+/// The most recently written `<job id>.log`, skipping the separate
+/// `<job id>-scanning.log` files
+fn latest_job_log() -> Result<PathBuf> {
+    let dir =
+        azcopy_log_dir().ok_or_else(|| anyhow!("Could not determine the AzCopy log directory"))?;
+    let entries = std::fs::read_dir(&dir)
+        .with_context(|| format!("No AzCopy job logs found in {}", dir.display()))?;
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "log")
+                && !path
+                    .file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy().ends_with("-scanning"))
+        })
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+        .ok_or_else(|| anyhow!("No AzCopy job logs found in {}", dir.display()))
+}
+
+/// Failed uploads, downloads and copies recorded in an AzCopy job log
+///
+/// Each appears as an `ERR:` line naming the source and status, followed by
+/// a line with the destination:
+///
+/// ```text
+/// 2024/05/01 10:00:00 ERR: [P#0-T#3] UPLOADFAILED: /data/a.txt : 403 : This request is not authorized
+///    Dst: https://acct.blob.core.windows.net/data/a.txt
+/// ```
+fn parse_job_log(log: &str) -> Vec<FailedTransfer> {
+    let mut failures = Vec::new();
+    let mut lines = log.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let Some((_, rest)) = line.split_once("ERR: ") else {
+            continue;
+        };
+        let Some((_, rest)) = ["UPLOADFAILED: ", "DOWNLOADFAILED: ", "COPYFAILED: "]
+            .iter()
+            .find_map(|marker| rest.split_once(marker))
+        else {
+            continue;
+        };
+        let mut fields = rest.split(" : ");
+        let source = fields.next().unwrap_or_default().trim();
+        let error_code = fields
+            .next()
+            .and_then(|code| code.trim().parse().ok())
+            .unwrap_or(0);
+        let Some(destination) = lines
+            .peek()
+            .and_then(|next| next.trim().strip_prefix("Dst: "))
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        lines.next();
+
+        let location =
+            |value: &str| convert_url_to_az_uri(value).unwrap_or_else(|| value.to_string());
+        failures.push(FailedTransfer {
+            source: location(source),
+            destination: location(&destination),
+            error_code,
+        });
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_job_log() {
+        let log = "\
+2024/05/01 10:00:00 INFO: [P#0-T#0] Starting transfer: Source \"/data/ok.txt\"
+2024/05/01 10:00:01 ERR: [P#0-T#3] UPLOADFAILED: /data/a b.txt : 403 : This request is not authorized : When Staging block.
+   Dst: https://acct.blob.core.windows.net/data/a%20b.txt
+2024/05/01 10:00:02 ERR: [P#0-T#4] DOWNLOADFAILED: https://acct.blob.core.windows.net/data/c.txt?sv=REDACTED : 000 : timeout
+   Dst: /restore/c.txt
+2024/05/01 10:00:03 ERR: unrelated error without a transfer
+";
+        assert_eq!(
+            parse_job_log(log),
+            vec![
+                FailedTransfer {
+                    source: "/data/a b.txt".to_string(),
+                    destination: "az://acct/data/a%20b.txt".to_string(),
+                    error_code: 403,
+                },
+                FailedTransfer {
+                    source: "az://acct/data/c.txt".to_string(),
+                    destination: "/restore/c.txt".to_string(),
+                    error_code: 0,
+                },
+            ]
+        );
+    }
+}
@@ -1,36 +1,260 @@
 use anyhow::{anyhow, Result};
+use azure_core::request_options::LeaseId;
+use azure_core::StatusCode;
 use colored::*;
-use std::io::{self, Write};
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::audit_log;
+use crate::azcopy_output::{AzCopyOperation, FailedTransfer, JobSummary};
+use crate::azure::{
+    convert_az_uri_to_url, AzCopyClient, AzCopyOptions, AzureClient, BlobInfo, BlobItem,
+};
+use crate::cancel;
+use crate::commands::cp::save_failures;
+use crate::commands::lease::{forget_lease, stored_lease};
+use crate::config::{Config, RmConfig};
+use crate::dry_run::{DryRunPlan, PlannedTransfer};
+use crate::error::http_status;
+use crate::prompt::{self, Prompter};
+use crate::sync_state::{join_location, snapshot};
+use crate::utils::{
+    contains_wildcard, format_size, is_azure_uri, matches_any_pattern, parse_azure_uri, Glob,
+};
+
+/// Blobs deleted at once when `rm` removes a selection itself
+const DELETE_CONCURRENCY: usize = 32;
+
+/// A blob the service keeps throttling is given up on after this many waves
+const BUSY_RETRIES: u32 = 5;
+
+/// Pause after a wave of deletions the service throttled
+const BUSY_PAUSE: Duration = Duration::from_secs(2);
+
+/// How to remove without being asked first
+const FORCE_BYPASS: &str = "pass --force to remove without asking";
+
+/// Removals of more blobs than this ask for the container name, unless the
+/// config sets `[rm] confirm_above`
+pub const DEFAULT_CONFIRM_ABOVE: u64 = 1000;
+
+/// How many blobs one `rm` may delete without more than a y/N, and at all
+#[derive(Debug, Clone, Copy)]
+pub struct DeleteLimits {
+    /// Refuse removals of more blobs than this
+    pub max_deletes: Option<u64>,
+    /// Past this many blobs, confirming means typing the container name
+    pub confirm_above: u64,
+}
+
+impl DeleteLimits {
+    /// `--max-deletes` wins over the config's `max_deletes`
+    pub fn new(max_deletes: Option<u64>, config: &RmConfig) -> Self {
+        Self {
+            max_deletes: max_deletes.or(config.max_deletes),
+            confirm_above: config.confirm_above.unwrap_or(DEFAULT_CONFIRM_ABOVE),
+        }
+    }
+
+    /// Stop a removal of `count` blobs past the cap before anything is
+    /// deleted; a dry run only warns
+    fn check(&self, path: &str, count: usize, dry_run: bool) -> Result<()> {
+        let Some(max) = self.max_deletes.filter(|max| count as u64 > *max) else {
+            return Ok(());
+        };
+        let message = format!(
+            "Removing {} would delete {} blobs, more than --max-deletes {}",
+            path, count, max
+        );
+        if dry_run {
+            eprintln!("{} {}", "⚠".yellow(), message);
+            return Ok(());
+        }
+        Err(anyhow!("{}; nothing was removed", message))
+    }
+
+    /// Whether removing `count` blobs needs the container name typed
+    fn needs_name(&self, count: Option<usize>) -> bool {
+        count.is_some_and(|count| count as u64 > self.confirm_above)
+    }
+}
+
+/// `--older-than`, `--newer-than`, `--min-size` and `--max-size`: blobs to
+/// remove by age and size, for retention cleanups
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlobSelection {
+    /// Last modified at least this long ago
+    pub older_than: Option<Duration>,
+    /// Last modified at most this long ago
+    pub newer_than: Option<Duration>,
+    /// At least this many bytes
+    pub min_size: Option<u64>,
+    /// At most this many bytes
+    pub max_size: Option<u64>,
+}
+
+impl BlobSelection {
+    pub fn is_empty(&self) -> bool {
+        self.older_than.is_none()
+            && self.newer_than.is_none()
+            && self.min_size.is_none()
+            && self.max_size.is_none()
+    }
 
-use crate::azure::{convert_az_uri_to_url, AzCopyClient, AzCopyOptions};
-use crate::utils::{is_azure_uri, parse_azure_uri};
+    /// Reject ranges that can't select anything
+    fn check(&self) -> Result<()> {
+        if let (Some(older), Some(newer)) = (self.older_than, self.newer_than) {
+            if older >= newer {
+                return Err(anyhow!(
+                    "--older-than must be shorter than --newer-than: blobs last modified between the two are removed"
+                ));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) {
+            if min > max {
+                return Err(anyhow!("--min-size can't be larger than --max-size"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a blob falls in the selection at `now` (Unix seconds)
+    fn matches(&self, blob: &BlobInfo, now: i64) -> bool {
+        let age = now - blob.properties.last_modified_unix;
+        let size = blob.properties.content_length;
+        self.older_than
+            .is_none_or(|older| age >= older.as_secs() as i64)
+            && self
+                .newer_than
+                .is_none_or(|newer| age <= newer.as_secs() as i64)
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+    }
+}
 
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
-    path: &str,
+    path: Option<&str>,
+    from_stdin: bool,
     recursive: bool,
     force: bool,
     dry_run: bool,
     include_pattern: Option<&str>,
     exclude_pattern: Option<&str>,
+    failures_out: Option<&Path>,
+    lease_id: Option<LeaseId>,
+    selection: BlobSelection,
+    max_deletes: Option<u64>,
+    prompter: &dyn Prompter,
 ) -> Result<()> {
-    if is_azure_uri(path) {
-        let mut azcopy = AzCopyClient::new();
-        azcopy.check_prerequisites().await?;
-        remove_azure_object(
-            &mut azcopy,
-            path,
-            recursive,
-            force,
-            dry_run,
-            include_pattern,
-            exclude_pattern,
-        )
-        .await
+    selection.check()?;
+    let limits = DeleteLimits::new(max_deletes, &Config::load()?.rm);
+    let paths = if from_stdin {
+        let mut input = Vec::new();
+        io::stdin().read_to_end(&mut input)?;
+        let paths = parse_path_list(&input)?;
+        for path in &paths {
+            audit_log::add_uri(path);
+        }
+        paths
     } else {
-        remove_local_path(path, recursive, force).await
+        path.map(str::to_string).into_iter().collect()
+    };
+
+    if lease_id.is_some() && paths.iter().any(|path| !is_azure_uri(path)) {
+        return Err(anyhow!("--lease-id only applies to blobs"));
+    }
+    if lease_id.is_some() && paths.iter().any(|path| contains_wildcard(path)) {
+        return Err(anyhow!("--lease-id only applies to removing a single blob"));
     }
+    if !selection.is_empty() && paths.iter().any(|path| !is_azure_uri(path)) {
+        return Err(anyhow!(
+            "--older-than, --newer-than, --min-size and --max-size only apply to blobs"
+        ));
+    }
+
+    let mut azcopy = None;
+    let mut failures = Vec::new();
+    let mut planned = Vec::new();
+    for path in &paths {
+        if is_azure_uri(path) {
+            let azcopy = match &mut azcopy {
+                Some(azcopy) => azcopy,
+                None => {
+                    let mut client = AzCopyClient::new();
+                    client.check_prerequisites().await?;
+                    azcopy.insert(client)
+                }
+            };
+            // Wildcards are matched by azst, the same way every command
+            // matches them, rather than by azcopy
+            let summary = if selection.is_empty() && !contains_wildcard(path) {
+                remove_azure_object(
+                    azcopy,
+                    path,
+                    recursive,
+                    force,
+                    dry_run,
+                    include_pattern,
+                    exclude_pattern,
+                    lease_id,
+                    &limits,
+                    prompter,
+                )
+                .await?
+            } else {
+                remove_selected_blobs(
+                    path,
+                    recursive,
+                    force,
+                    dry_run,
+                    include_pattern,
+                    exclude_pattern,
+                    &selection,
+                    &limits,
+                    prompter,
+                )
+                .await?
+            };
+            failures.extend(summary.failed_transfers);
+            planned.extend(summary.planned);
+        } else if dry_run {
+            planned.extend(plan_local_removal(path, recursive).await?);
+        } else {
+            remove_local_path(path, recursive, force, prompter).await?;
+        }
+    }
+
+    if dry_run {
+        DryRunPlan::new(planned).print();
+    }
+
+    if let Some(path) = failures_out {
+        save_failures(path, AzCopyOperation::Remove, failures)?;
+    }
+    Ok(())
+}
+
+/// Split the paths piped to `rm -I`: NUL-terminated as written by `ls -0`,
+/// otherwise one per line
+fn parse_path_list(input: &[u8]) -> Result<Vec<String>> {
+    let input = std::str::from_utf8(input).map_err(|_| anyhow!("Paths on stdin must be UTF-8"))?;
+    let paths = if input.contains('\0') {
+        input.split('\0').collect::<Vec<_>>()
+    } else {
+        input.lines().collect()
+    };
+    Ok(paths
+        .into_iter()
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn remove_azure_object(
     azcopy: &mut AzCopyClient,
     path: &str,
@@ -39,8 +263,11 @@ async fn remove_azure_object(
     dry_run: bool,
     include_pattern: Option<&str>,
     exclude_pattern: Option<&str>,
-) -> Result<()> {
-    let (_account, container, blob_path) = parse_azure_uri(path)?;
+    lease_id: Option<LeaseId>,
+    limits: &DeleteLimits,
+    prompter: &dyn Prompter,
+) -> Result<JobSummary> {
+    let (account, container, blob_path) = parse_azure_uri(path)?;
 
     // Validate that we have a container specified
     if container.is_empty() {
@@ -55,28 +282,70 @@ async fn remove_azure_object(
         return Err(anyhow!("Cannot remove entire container with rm"));
     }
 
-    // Auto-enable recursive if path contains wildcards
-    let has_wildcard = path.contains('*') || path.contains('?');
-    let recursive = recursive || has_wildcard;
+    if recursive && lease_id.is_some() {
+        return Err(anyhow!("--lease-id only applies to removing a single blob"));
+    }
+    // azcopy can't present a lease, so leased blobs are deleted directly
+    let lease_id = lease_id
+        .or_else(|| stored_lease(path))
+        .filter(|_| !recursive);
+
+    // Count what a recursive removal would delete, for the cap and to know
+    // how to confirm; a dry run is checked against azcopy's plan instead
+    let count = match &account {
+        Some(account) if recursive && !dry_run && (!force || limits.max_deletes.is_some()) => {
+            let mut client = AzureClient::new().with_storage_account(account);
+            let blobs = list_removal(
+                &mut client,
+                &container,
+                blob_path.as_deref().unwrap_or_default(),
+                recursive,
+                include_pattern,
+                exclude_pattern,
+                &BlobSelection::default(),
+            )
+            .await?;
+            limits.check(path, blobs.len(), dry_run)?;
+            Some(blobs.len())
+        }
+        None if recursive && limits.max_deletes.is_some() => {
+            return Err(anyhow!(
+                "--max-deletes needs a storage account: az://<account>/<container>/[path]"
+            ));
+        }
+        _ => None,
+    };
 
-    // Prompt for confirmation unless force flag is set
-    if !force {
-        let action = if recursive {
-            "recursively remove"
-        } else {
-            "remove"
+    // Prompt for confirmation unless force flag is set or nothing is removed
+    if !force && !dry_run {
+        let question = match (recursive, count) {
+            (true, Some(count)) => {
+                format!("recursively remove {} ({} blobs)", path.yellow(), count)
+            }
+            (true, None) => format!("recursively remove {}", path.yellow()),
+            (false, _) => format!("remove {}", path.yellow()),
         };
-        print!("{} {}? (y/N): ", action, path.yellow());
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim().to_lowercase();
-
-        if input != "y" && input != "yes" {
+        if !confirm(prompter, &question, &container, limits.needs_name(count)).await? {
             println!("Aborted");
-            return Ok(());
+            return Ok(JobSummary::default());
+        }
+    }
+
+    if let (Some(lease_id), Some(blob)) = (lease_id, &blob_path) {
+        let mut summary = JobSummary::default();
+        if dry_run {
+            summary.planned.push(PlannedTransfer::delete(path, None));
+            return Ok(summary);
         }
+        let mut client = match account {
+            Some(account) => AzureClient::new().with_storage_account(&account),
+            None => AzureClient::new(),
+        };
+        client.delete_blob(&container, blob, Some(lease_id)).await?;
+        forget_lease(path)?;
+        audit_log::record(1, 0);
+        status!("{} Removed leased blob {}", "✓".green(), path.cyan());
+        return Ok(summary);
     }
 
     // Convert az:// URI to HTTPS URL for AzCopy
@@ -99,7 +368,7 @@ async fn remove_azure_object(
         String::new()
     };
 
-    println!(
+    status!(
         "{} Removing {}{}",
         "×".red(),
         path.cyan(),
@@ -134,16 +403,332 @@ async fn remove_azure_object(
     }
     cmd_parts.push("--output-type json".to_string());
 
-    println!("{} {}", "⚙".dimmed(), cmd_parts.join(" ").dimmed());
-    println!(); // Blank line before AzCopy output
+    tracing::debug!("{}", cmd_parts.join(" "));
+    status!(); // Blank line before AzCopy output
 
     // Use AzCopy for removal
-    azcopy.remove_with_options(&target_url, &options).await?;
+    let summary = azcopy.remove_with_options(&target_url, &options).await?;
+    if dry_run {
+        limits.check(path, summary.planned.len(), dry_run)?;
+    }
+    Ok(summary)
+}
 
-    Ok(())
+/// Remove the blobs under a prefix that match `selection`, or the ones a
+/// wildcard matches, listing and deleting them directly rather than through
+/// azcopy, which can't filter by age or size
+#[allow(clippy::too_many_arguments)]
+async fn remove_selected_blobs(
+    path: &str,
+    recursive: bool,
+    force: bool,
+    dry_run: bool,
+    include_pattern: Option<&str>,
+    exclude_pattern: Option<&str>,
+    selection: &BlobSelection,
+    limits: &DeleteLimits,
+    prompter: &dyn Prompter,
+) -> Result<JobSummary> {
+    let (account, container, blob_path) = parse_azure_uri(path)?;
+    if container.is_empty() {
+        return Err(anyhow!(
+            "Invalid URI '{}'. You must specify both storage account and container: az://<account>/<container>/[path]",
+            path
+        ));
+    }
+    let blob_path = blob_path.unwrap_or_default();
+    if !recursive && !contains_wildcard(&blob_path) {
+        return Err(anyhow!(
+            "--older-than, --newer-than, --min-size and --max-size select blobs under a prefix: add -r"
+        ));
+    }
+
+    let mut client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    };
+    let account = client
+        .get_storage_account()
+        .ok_or_else(|| anyhow!("Storage account not configured"))?
+        .to_string();
+    let selected = list_removal(
+        &mut client,
+        &container,
+        &blob_path,
+        recursive,
+        include_pattern,
+        exclude_pattern,
+        selection,
+    )
+    .await?;
+
+    let uri = |blob: &BlobInfo| format!("az://{}/{}/{}", account, container, blob.name);
+    let mut summary = JobSummary::default();
+    if selected.is_empty() {
+        if selection.is_empty() {
+            status!("{} No blobs match {}", "ℹ".blue(), path.cyan());
+        } else {
+            status!(
+                "{} No blobs under {} match the filters",
+                "ℹ".blue(),
+                path.cyan()
+            );
+        }
+        return Ok(summary);
+    }
+    let bytes: u64 = selected
+        .iter()
+        .map(|blob| blob.properties.content_length)
+        .sum();
+    limits.check(path, selected.len(), dry_run)?;
+
+    if dry_run {
+        summary.planned = selected
+            .iter()
+            .map(|blob| PlannedTransfer::delete(&uri(blob), Some(blob.properties.content_length)))
+            .collect();
+        return Ok(summary);
+    }
+
+    if !force {
+        let question = format!(
+            "remove {} blob(s) ({}) under {}",
+            selected.len(),
+            format_size(bytes),
+            path.yellow()
+        );
+        if !confirm(
+            prompter,
+            &question,
+            &container,
+            limits.needs_name(Some(selected.len())),
+        )
+        .await?
+        {
+            println!("Aborted");
+            return Ok(summary);
+        }
+    }
+
+    status!(
+        "{} Removing {} blob(s) ({}) under {}",
+        "×".red(),
+        selected.len(),
+        format_size(bytes),
+        path.cyan()
+    );
+    let (completed, failures) = delete_in_waves(&client, &container, &selected).await;
+    summary.completed_count = completed;
+    for (blob, e) in failures {
+        // Under --quiet the command's summary line reports the failures instead
+        if !crate::logging::is_quiet() {
+            eprintln!("{} {:#}", "⚠".yellow(), e);
+        }
+        summary.failed_count += 1;
+        summary.failed_transfers.push(FailedTransfer {
+            source: uri(blob),
+            destination: String::new(),
+            error_code: http_status(&e).map_or(0, |status| u16::from(status) as i32),
+        });
+    }
+    audit_log::record_job(&summary);
+    status!(
+        "{} Removed {} blob(s){}",
+        "✓".green(),
+        summary.completed_count,
+        if summary.failed_count > 0 {
+            format!(", {} failed", summary.failed_count)
+        } else {
+            String::new()
+        }
+    );
+    Ok(summary)
+}
+
+/// The blobs `rm -r` removes under `blob_path`: everything below a
+/// directory, or the names matching a wildcard (and with `recursive`, what's
+/// inside matching directories), narrowed by the include and exclude
+/// patterns and `selection`
+async fn list_removal(
+    client: &mut AzureClient,
+    container: &str,
+    blob_path: &str,
+    recursive: bool,
+    include_pattern: Option<&str>,
+    exclude_pattern: Option<&str>,
+    selection: &BlobSelection,
+) -> Result<Vec<BlobInfo>> {
+    // A wildcard is matched against the names under the part before it;
+    // otherwise the path is a directory, like with azcopy
+    let glob = Glob::new(blob_path)?;
+    let prefix = match &glob {
+        Some(glob) => glob.prefix().to_string(),
+        None if blob_path.is_empty() || blob_path.ends_with('/') => blob_path.to_string(),
+        None => format!("{}/", blob_path),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let mut selected = Vec::new();
+    client
+        .list_blobs_with_callback(
+            container,
+            (!prefix.is_empty()).then_some(prefix.as_str()),
+            None,
+            |items| {
+                for item in items {
+                    let BlobItem::Blob(blob) = item else {
+                        continue;
+                    };
+                    let name = blob.name.rsplit('/').next().unwrap_or(&blob.name);
+                    let wanted = glob
+                        .as_ref()
+                        .is_none_or(|glob| glob.selects(&blob.name, recursive))
+                        && include_pattern
+                            .is_none_or(|patterns| matches_any_pattern(name, patterns))
+                        && !exclude_pattern
+                            .is_some_and(|patterns| matches_any_pattern(name, patterns))
+                        && selection.matches(&blob, now);
+                    if wanted {
+                        selected.push(blob);
+                    }
+                }
+                Ok(())
+            },
+        )
+        .await?;
+    Ok(selected)
+}
+
+/// Delete `blobs` a wave at a time, returning how many were deleted and the
+/// ones that failed; a cancelled command stops after the current wave
+///
+/// A wave the service throttles (429 or 503) halves the next one and waits
+/// before its throttled blobs are tried again; a clean wave grows the next
+/// by a quarter, back up to `DELETE_CONCURRENCY`.
+async fn delete_in_waves<'a>(
+    client: &AzureClient,
+    container: &str,
+    blobs: &'a [BlobInfo],
+) -> (u64, Vec<(&'a BlobInfo, anyhow::Error)>) {
+    let mut pending: VecDeque<(&BlobInfo, u32)> = blobs.iter().map(|blob| (blob, 0)).collect();
+    let mut completed = 0;
+    let mut failures = Vec::new();
+    let mut width = DELETE_CONCURRENCY;
+    while !pending.is_empty() && !cancel::is_cancelled() {
+        let wave: Vec<_> = pending.drain(..width.min(pending.len())).collect();
+        let deletions = wave.into_iter().map(|(blob, attempts)| {
+            let mut client = client.clone();
+            async move {
+                let result = client
+                    .delete_blob(container, &blob.name, None)
+                    .await
+                    .map_err(anyhow::Error::from);
+                (blob, attempts, result)
+            }
+        });
+        let results: Vec<_> = futures::stream::iter(deletions)
+            .buffer_unordered(width)
+            .collect()
+            .await;
+
+        let mut throttled = 0;
+        for (blob, attempts, result) in results {
+            match result {
+                Ok(()) => completed += 1,
+                Err(e) if is_busy(&e) && attempts < BUSY_RETRIES => {
+                    throttled += 1;
+                    pending.push_back((blob, attempts + 1));
+                }
+                Err(e) => failures.push((blob, e)),
+            }
+        }
+        width = next_wave_width(width, throttled > 0);
+        if throttled > 0 {
+            tracing::debug!(
+                "{} deletion(s) throttled; retrying {} at a time",
+                throttled,
+                width
+            );
+            tokio::time::sleep(BUSY_PAUSE).await;
+        }
+    }
+    (completed, failures)
+}
+
+/// Whether a request failed because the service is shedding load
+fn is_busy(error: &anyhow::Error) -> bool {
+    matches!(
+        http_status(error),
+        Some(StatusCode::TooManyRequests | StatusCode::ServiceUnavailable)
+    )
 }
 
-async fn remove_local_path(path: &str, recursive: bool, force: bool) -> Result<()> {
+fn next_wave_width(width: usize, throttled: bool) -> usize {
+    if throttled {
+        (width / 2).max(1)
+    } else {
+        (width + width.div_ceil(4)).min(DELETE_CONCURRENCY)
+    }
+}
+
+/// Ask before removing; when `needs_name` is set, only typing the container
+/// name confirms
+async fn confirm(
+    prompter: &dyn Prompter,
+    question: &str,
+    container: &str,
+    needs_name: bool,
+) -> Result<bool> {
+    let question = if needs_name {
+        format!(
+            "{}? This can't be undone; type the container name ({}) to confirm: ",
+            question,
+            container.bold()
+        )
+    } else {
+        format!("{}? (y/N): ", question)
+    };
+    let answer = prompter.ask(&question, FORCE_BYPASS).await?;
+    Ok(accepts(&answer, container, needs_name))
+}
+
+fn accepts(answer: &str, container: &str, needs_name: bool) -> bool {
+    if needs_name {
+        answer.trim() == container
+    } else {
+        prompt::is_yes(answer)
+    }
+}
+
+/// The files removing a local path would delete, for --dry-run
+async fn plan_local_removal(path: &str, recursive: bool) -> Result<Vec<PlannedTransfer>> {
+    let path_obj = Path::new(path);
+
+    if path_obj.is_file() {
+        let size = path_obj.metadata()?.len();
+        return Ok(vec![PlannedTransfer::delete(path, Some(size))]);
+    }
+    if !path_obj.is_dir() {
+        return Err(anyhow!("Path '{}' does not exist", path));
+    }
+    if !recursive {
+        return Err(anyhow!("Cannot remove directory without -r flag"));
+    }
+    Ok(snapshot(path)
+        .await?
+        .into_iter()
+        .map(|(relative, state)| {
+            PlannedTransfer::delete(&join_location(path, &relative), Some(state.size))
+        })
+        .collect())
+}
+
+async fn remove_local_path(
+    path: &str,
+    recursive: bool,
+    force: bool,
+    prompter: &dyn Prompter,
+) -> Result<()> {
     use std::path::Path;
 
     let path_obj = Path::new(path);
@@ -153,70 +738,141 @@ async fn remove_local_path(path: &str, recursive: bool, force: bool) -> Result<(
     }
 
     if path_obj.is_file() {
-        remove_local_file(path, force).await
+        remove_local_file(path, force, prompter).await
     } else if path_obj.is_dir() {
         if !recursive {
             return Err(anyhow!("Cannot remove directory without -r flag"));
         }
-        remove_local_directory(path, force).await
+        remove_local_directory(path, force, prompter).await
     } else {
         Err(anyhow!("Path '{}' is neither file nor directory", path))
     }
 }
 
-async fn remove_local_file(path: &str, force: bool) -> Result<()> {
+async fn remove_local_file(path: &str, force: bool, prompter: &dyn Prompter) -> Result<()> {
     use tokio::fs;
 
-    if !force {
-        print!("Remove file '{}'? (y/N): ", path.cyan());
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim().to_lowercase();
-
-        if input != "y" && input != "yes" {
-            println!("Aborted");
-            return Ok(());
-        }
+    if !force
+        && !prompter
+            .confirm(&format!("Remove file '{}'?", path.cyan()), FORCE_BYPASS)
+            .await?
+    {
+        println!("Aborted");
+        return Ok(());
     }
 
-    println!("{} Removing {}", "×".red(), path.cyan());
+    status!("{} Removing {}", "×".red(), path.cyan());
     fs::remove_file(path).await?;
-    println!("{} Removed", "✓".green());
+    audit_log::record(1, 0);
+    status!("{} Removed", "✓".green());
 
     Ok(())
 }
 
-async fn remove_local_directory(path: &str, force: bool) -> Result<()> {
+async fn remove_local_directory(path: &str, force: bool, prompter: &dyn Prompter) -> Result<()> {
     use tokio::fs;
 
-    if !force {
-        print!(
-            "Remove directory '{}' and all its contents? (y/N): ",
-            path.cyan()
-        );
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim().to_lowercase();
-
-        if input != "y" && input != "yes" {
-            println!("Aborted");
-            return Ok(());
-        }
+    if !force
+        && !prompter
+            .confirm(
+                &format!("Remove directory '{}' and all its contents?", path.cyan()),
+                FORCE_BYPASS,
+            )
+            .await?
+    {
+        println!("Aborted");
+        return Ok(());
     }
 
-    println!("{} Removing directory {}", "×".red(), path.cyan());
+    status!("{} Removing directory {}", "×".red(), path.cyan());
+    let files = snapshot(path).await.map(|files| files.len()).unwrap_or(0);
     fs::remove_dir_all(path).await?;
-    println!("{} Removed", "✓".green());
+    audit_log::record(files as u64, 0);
+    status!("{} Removed", "✓".green());
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::prompt::ScriptedPrompter;
+
+    #[test]
+    fn test_parse_path_list() {
+        assert_eq!(
+            parse_path_list(b"az://a/c/one two\0az://a/c/line\nbreak\0").unwrap(),
+            vec!["az://a/c/one two", "az://a/c/line\nbreak"]
+        );
+        assert_eq!(
+            parse_path_list(b"az://a/c/x\r\naz://a/c/y\n\n").unwrap(),
+            vec!["az://a/c/x", "az://a/c/y"]
+        );
+        assert!(parse_path_list(b"").unwrap().is_empty());
+    }
+
+    fn blob(size: u64, last_modified_unix: i64) -> BlobInfo {
+        BlobInfo {
+            name: "logs/app.log".to_string(),
+            properties: crate::azure::BlobProperties {
+                content_length: size,
+                last_modified: String::new(),
+                content_type: None,
+                last_modified_unix,
+                content_md5: None,
+                content_encoding: None,
+                etag: None,
+                access_tier: None,
+                blob_type: None,
+                lease_state: None,
+            },
+            version_count: None,
+            tags: None,
+        }
+    }
+
+    #[test]
+    fn test_blob_selection_matches() {
+        const DAY: i64 = 24 * 3600;
+        let now = 100 * DAY;
+        let retention = BlobSelection {
+            older_than: Some(Duration::from_secs(30 * DAY as u64)),
+            ..Default::default()
+        };
+        assert!(retention.matches(&blob(10, now - 31 * DAY), now));
+        assert!(!retention.matches(&blob(10, now - 29 * DAY), now));
+
+        let window = BlobSelection {
+            older_than: Some(Duration::from_secs(7 * DAY as u64)),
+            newer_than: Some(Duration::from_secs(30 * DAY as u64)),
+            min_size: Some(1),
+            max_size: Some(1024),
+        };
+        assert!(window.matches(&blob(512, now - 10 * DAY), now));
+        assert!(!window.matches(&blob(512, now - 40 * DAY), now));
+        assert!(!window.matches(&blob(0, now - 10 * DAY), now));
+        assert!(!window.matches(&blob(2048, now - 10 * DAY), now));
+    }
+
+    #[test]
+    fn test_blob_selection_check() {
+        assert!(BlobSelection::default().is_empty());
+        assert!(BlobSelection {
+            older_than: Some(Duration::from_secs(60)),
+            newer_than: Some(Duration::from_secs(30)),
+            ..Default::default()
+        }
+        .check()
+        .is_err());
+        assert!(BlobSelection {
+            min_size: Some(10),
+            max_size: Some(1),
+            ..Default::default()
+        }
+        .check()
+        .is_err());
+    }
+
     #[test]
     fn test_remove_single_blob_docs() {
         // Test case: azst rm az://account/container/file.txt
@@ -258,4 +914,58 @@ mod tests {
         // Test case: azst rm az://account/container/prefix/ (without -r)
         // Expected: Error - requires -r flag
     }
+
+    #[test]
+    fn test_delete_limits() {
+        let config = RmConfig {
+            confirm_above: None,
+            max_deletes: Some(100),
+        };
+        let limits = DeleteLimits::new(None, &config);
+        assert_eq!(limits.confirm_above, DEFAULT_CONFIRM_ABOVE);
+        assert!(limits.check("az://a/c/x/", 100, false).is_ok());
+        assert!(limits.check("az://a/c/x/", 101, false).is_err());
+        assert!(limits.check("az://a/c/x/", 101, true).is_ok());
+        assert!(DeleteLimits::new(Some(200), &config)
+            .check("az://a/c/x/", 101, false)
+            .is_ok());
+
+        assert!(!limits.needs_name(None));
+        assert!(!limits.needs_name(Some(1000)));
+        assert!(limits.needs_name(Some(1001)));
+    }
+
+    #[test]
+    fn test_accepts() {
+        assert!(accepts("Y\n", "logs", false));
+        assert!(accepts("yes\n", "logs", false));
+        assert!(!accepts("\n", "logs", false));
+        assert!(accepts("logs\n", "logs", true));
+        assert!(!accepts("y\n", "logs", true));
+        assert!(!accepts("Logs\n", "logs", true));
+    }
+
+    #[tokio::test]
+    async fn test_remove_local_file_asks_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "data").unwrap();
+        let path = file.to_str().unwrap();
+
+        let prompter = ScriptedPrompter::new(["n", "y"]);
+        remove_local_file(path, false, &prompter).await.unwrap();
+        assert!(file.exists());
+        remove_local_file(path, false, &prompter).await.unwrap();
+        assert!(!file.exists());
+        assert_eq!(prompter.asked().len(), 2);
+    }
+
+    #[test]
+    fn test_next_wave_width() {
+        assert_eq!(next_wave_width(32, true), 16);
+        assert_eq!(next_wave_width(1, true), 1);
+        assert_eq!(next_wave_width(1, false), 2);
+        assert_eq!(next_wave_width(16, false), 20);
+        assert_eq!(next_wave_width(30, false), DELETE_CONCURRENCY);
+    }
 }
@@ -0,0 +1,342 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+use serde::Serialize;
+
+use crate::azure::{AccountSecurity, AzureClient, ContainerSettings};
+use crate::output::{output_format, OutputFormat};
+use crate::utils::parse_azure_uri;
+
+// ============================================================================
+// Security Audit - Settings that expose an account's data or make it hard to
+// recover
+// ============================================================================
+
+/// How urgently a finding needs attention, least urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    fn label(self) -> ColoredString {
+        let label = format!("{:<8}", format!("{:?}", self).to_lowercase());
+        match self {
+            Severity::High => label.red().bold(),
+            Severity::Medium => label.yellow(),
+            Severity::Low => label.normal(),
+            Severity::Info => label.dimmed(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// The az:// URI of the account or container
+    pub resource: String,
+    pub finding: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, resource: String, finding: impl Into<String>) -> Self {
+        Self {
+            severity,
+            resource,
+            finding: finding.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    account: &'a str,
+    findings: &'a [Finding],
+}
+
+/// Findings for the account's authorization and data protection settings
+fn account_findings(account: &str, security: &AccountSecurity) -> Vec<Finding> {
+    let resource = format!("az://{}/", account);
+    let mut findings = Vec::new();
+    if security.allow_shared_key_access {
+        findings.push(Finding::new(
+            Severity::Medium,
+            resource.clone(),
+            "Shared key authorization is allowed: anyone with an account key or a \
+             key-signed SAS has full access",
+        ));
+    }
+    if security.blob_soft_delete_days.is_none() {
+        findings.push(Finding::new(
+            Severity::Medium,
+            resource.clone(),
+            "Blob soft delete is off: deleted and overwritten blobs can't be recovered",
+        ));
+    }
+    if security.container_soft_delete_days.is_none() {
+        findings.push(Finding::new(
+            Severity::Low,
+            resource.clone(),
+            "Container soft delete is off: deleted containers can't be recovered",
+        ));
+    }
+    if !security.versioning {
+        findings.push(Finding::new(
+            Severity::Low,
+            resource,
+            "Blob versioning is off: previous versions of blobs aren't kept",
+        ));
+    }
+    findings
+}
+
+/// Findings for containers open to anonymous reads
+///
+/// Azure has no per-blob ACLs: blobs are public-read when their container's
+/// public access level is "blob" or "container". The account setting
+/// overrides every container's, so those are only reported as latent.
+fn container_findings(
+    account: &str,
+    containers: &[ContainerSettings],
+    public_access_allowed: bool,
+) -> Vec<Finding> {
+    containers
+        .iter()
+        .filter(|settings| settings.public_access != "off")
+        .map(|settings| {
+            let resource = format!("az://{}/{}/", account, settings.name);
+            if !public_access_allowed {
+                return Finding::new(
+                    Severity::Info,
+                    resource,
+                    format!(
+                        "Public access level is '{}', but the account blocks anonymous access",
+                        settings.public_access
+                    ),
+                );
+            }
+            if settings.public_access == "container" {
+                Finding::new(
+                    Severity::High,
+                    resource,
+                    "Anyone can list and read its blobs without credentials",
+                )
+            } else {
+                Finding::new(
+                    Severity::High,
+                    resource,
+                    "Anyone can read its blobs without credentials, given their names",
+                )
+            }
+        })
+        .collect()
+}
+
+/// Most severe first, then by resource
+fn sort_findings(findings: &mut [Finding]) {
+    findings.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.resource.cmp(&b.resource))
+    });
+}
+
+fn print_table(findings: &[Finding]) {
+    let width = findings
+        .iter()
+        .map(|finding| finding.resource.len())
+        .max()
+        .unwrap_or_default()
+        .max("RESOURCE".len());
+    println!(
+        "{}",
+        format!("{:<8}  {:<width$}  FINDING", "SEVERITY", "RESOURCE").bold()
+    );
+    for finding in findings {
+        println!(
+            "{}  {:<width$}  {}",
+            finding.severity.label(),
+            finding.resource,
+            finding.finding
+        );
+    }
+}
+
+/// Report the account's settings that expose data or prevent recovering it
+pub async fn execute(uri: &str) -> Result<()> {
+    let (account, container, _) = parse_azure_uri(uri)?;
+    let account = account
+        .ok_or_else(|| anyhow!("A security audit needs a storage account: az://<account>/"))?;
+    if !container.is_empty() {
+        return Err(anyhow!(
+            "A security audit covers a whole account; use az://{}/, or pass a \
+             destination to spot-check a migration",
+            account
+        ));
+    }
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+    status!("{} Auditing {}...", "→".blue(), account.cyan());
+
+    // Account settings come from the resource provider, which needs a role
+    // on the account; a data-only role still gets the container findings
+    let mut findings = Vec::new();
+    let public_access_allowed = match client.account_security().await {
+        Ok(security) => {
+            findings.extend(account_findings(&account, &security));
+            security.allow_blob_public_access
+        }
+        Err(e) => {
+            findings.push(Finding::new(
+                Severity::Info,
+                format!("az://{}/", account),
+                format!("Account settings weren't checked: {:#}", e),
+            ));
+            true
+        }
+    };
+
+    let mut containers = Vec::new();
+    for container in client.list_containers().await? {
+        containers.push(client.container_settings(&container.name).await?);
+    }
+    findings.extend(container_findings(
+        &account,
+        &containers,
+        public_access_allowed,
+    ));
+    sort_findings(&mut findings);
+
+    if output_format() == OutputFormat::Json {
+        let report = Report {
+            account: &account,
+            findings: &findings,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if findings.is_empty() {
+        println!("{} No findings for {}", "✓".green(), account);
+        return Ok(());
+    }
+    print_table(&findings);
+    let count = |severity| {
+        findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    };
+    status!(
+        "\n{} finding(s): {} high, {} medium, {} low ({} container(s) checked)",
+        findings.len(),
+        count(Severity::High),
+        count(Severity::Medium),
+        count(Severity::Low),
+        containers.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(name: &str, public_access: &str) -> ContainerSettings {
+        ContainerSettings {
+            name: name.to_string(),
+            public_access: public_access.to_string(),
+            last_modified: String::new(),
+            lease_state: String::new(),
+            has_immutability_policy: false,
+            has_legal_hold: false,
+            metadata: Default::default(),
+            access_policies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_account_findings() {
+        let hardened = AccountSecurity {
+            allow_shared_key_access: false,
+            allow_blob_public_access: false,
+            blob_soft_delete_days: Some(7),
+            container_soft_delete_days: Some(7),
+            versioning: true,
+        };
+        assert!(account_findings("acct", &hardened).is_empty());
+
+        let unprotected = AccountSecurity {
+            allow_shared_key_access: false,
+            allow_blob_public_access: false,
+            blob_soft_delete_days: None,
+            container_soft_delete_days: None,
+            versioning: false,
+        };
+        let severities: Vec<Severity> = account_findings("acct", &unprotected)
+            .iter()
+            .map(|f| f.severity)
+            .collect();
+        assert_eq!(severities, [Severity::Medium, Severity::Low, Severity::Low]);
+
+        let shared_key = AccountSecurity {
+            allow_shared_key_access: true,
+            ..hardened
+        };
+        let findings = account_findings("acct", &shared_key);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].resource, "az://acct/");
+        assert!(findings[0].finding.contains("Shared key"));
+    }
+
+    #[test]
+    fn test_container_findings() {
+        let containers = [
+            container("private", "off"),
+            container("site", "blob"),
+            container("dump", "container"),
+        ];
+        let findings = container_findings("acct", &containers, true);
+        let resources: Vec<&str> = findings.iter().map(|f| f.resource.as_str()).collect();
+        assert_eq!(resources, ["az://acct/site/", "az://acct/dump/"]);
+        assert!(findings.iter().all(|f| f.severity == Severity::High));
+
+        // Blocked at the account: still worth knowing before anyone unblocks it
+        let findings = container_findings("acct", &containers, false);
+        assert_eq!(findings.len(), 2);
+        assert!(findings.iter().all(|f| f.severity == Severity::Info));
+    }
+
+    #[test]
+    fn test_sort_findings() {
+        let mut findings = vec![
+            Finding::new(Severity::Low, "az://acct/".into(), "versioning"),
+            Finding::new(Severity::High, "az://acct/b/".into(), "public"),
+            Finding::new(Severity::Info, "az://acct/".into(), "note"),
+            Finding::new(Severity::High, "az://acct/a/".into(), "public"),
+            Finding::new(Severity::Medium, "az://acct/".into(), "shared key"),
+        ];
+        sort_findings(&mut findings);
+        let order: Vec<(Severity, &str)> = findings
+            .iter()
+            .map(|f| (f.severity, f.resource.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (Severity::High, "az://acct/a/"),
+                (Severity::High, "az://acct/b/"),
+                (Severity::Medium, "az://acct/"),
+                (Severity::Low, "az://acct/"),
+                (Severity::Info, "az://acct/"),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&findings[0]).unwrap()["severity"],
+            "high"
+        );
+    }
+}
@@ -0,0 +1,509 @@
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use colored::*;
+use futures::StreamExt;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Empty, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{self, HeaderValue};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use crate::azure::{AzureClient, BlobInfo, BlobItem, PATH_ENCODE_SET};
+use crate::blob_reader::ReadaheadConfig;
+use crate::cancel;
+use crate::error::{AzstError, EXIT_NOT_FOUND};
+use crate::utils::{directory_prefix, format_size, parse_azure_uri};
+
+// ============================================================================
+// Serve - A local HTTP gateway to the blobs under a prefix
+// ============================================================================
+
+/// Blob served for a directory, as on static website hosting
+const INDEX_DOCUMENT: &str = "index.html";
+
+type Body = UnsyncBoxBody<Bytes, anyhow::Error>;
+
+/// The part of a blob a request asked for
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Whole,
+    /// Inclusive byte offsets
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Interpret a Range header against a blob of `size` bytes
+///
+/// Only single `bytes=` ranges are honored; anything else is served whole,
+/// as RFC 9110 allows.
+fn parse_range(header: Option<&str>, size: u64) -> ByteRange {
+    let Some(spec) = header.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Whole;
+    };
+    let Some((start, end)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return ByteRange::Whole;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=-N: the last N bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 || size == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            (size.saturating_sub(suffix), size - 1)
+        }
+        (Ok(start), Err(_)) if end.is_empty() => (start, size.saturating_sub(1)),
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(size.saturating_sub(1))),
+        _ => return ByteRange::Whole,
+    };
+    if range.0 >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(range.0, range.1)
+}
+
+/// The blob path a request path refers to, relative to the served prefix
+///
+/// None for paths that aren't valid UTF-8 once decoded or that climb out
+/// with `..`.
+fn request_blob_path(path: &str) -> Option<String> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+    let relative = decoded.trim_start_matches('/');
+    if relative
+        .split('/')
+        .any(|segment| segment == ".." || segment == ".")
+    {
+        return None;
+    }
+    Some(relative.to_string())
+}
+
+/// Escape text for an HTML page
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// HTML listing of a directory: its subdirectories, then its blobs
+///
+/// Names are relative to the directory, `dir` is its path from the served root.
+fn render_index(dir: &str, prefixes: &[String], blobs: &[(String, u64)]) -> String {
+    let title = escape_html(&format!("/{}", dir));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n\
+         <body>\n<h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    if !dir.is_empty() {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for name in prefixes {
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            utf8_percent_encode(name, PATH_ENCODE_SET),
+            escape_html(name)
+        ));
+    }
+    for (name, size) in blobs {
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}</a> ({})</li>\n",
+            utf8_percent_encode(name, PATH_ENCODE_SET),
+            escape_html(name),
+            format_size(*size)
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    html
+}
+
+/// Whether a storage error means the blob isn't there
+fn is_not_found(e: &AzstError) -> bool {
+    e.exit_code() == EXIT_NOT_FOUND
+}
+
+fn full(data: impl Into<Bytes>) -> Body {
+    Full::new(data.into())
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+fn empty() -> Body {
+    Empty::new().map_err(|never| match never {}).boxed_unsync()
+}
+
+fn plain(status: StatusCode, message: &str) -> Response<Body> {
+    let mut response = Response::new(full(format!("{}\n", message)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+/// Answers requests from the blobs under one prefix
+struct Gateway {
+    client: AzureClient,
+    container: String,
+    /// Blob name prefix, empty or ending with `/`
+    prefix: String,
+    /// List directories without an index.html
+    list: bool,
+    /// Size of the ranged GETs bodies are fetched in, and how many are kept
+    /// in flight for one response
+    readahead: ReadaheadConfig,
+}
+
+impl Gateway {
+    async fn handle(&self, request: Request<Incoming>) -> Response<Body> {
+        let head = request.method() == Method::HEAD;
+        let response = if request.method() != Method::GET && !head {
+            let mut response = plain(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Only GET and HEAD are served",
+            );
+            response
+                .headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+            response
+        } else {
+            match request_blob_path(request.uri().path()) {
+                Some(path) => {
+                    let range = request
+                        .headers()
+                        .get(header::RANGE)
+                        .and_then(|value| value.to_str().ok());
+                    self.respond(&path, range).await
+                }
+                None => plain(StatusCode::BAD_REQUEST, "Invalid path"),
+            }
+        };
+
+        status!(
+            "{} {} {}",
+            request.method(),
+            request.uri().path(),
+            match response.status() {
+                status if status.is_success() => status.as_u16().to_string().green(),
+                status if status.is_redirection() => status.as_u16().to_string().cyan(),
+                status => status.as_u16().to_string().red(),
+            }
+        );
+        if head {
+            let (parts, _) = response.into_parts();
+            return Response::from_parts(parts, empty());
+        }
+        response
+    }
+
+    async fn respond(&self, path: &str, range: Option<&str>) -> Response<Body> {
+        if path.is_empty() || path.ends_with('/') {
+            return self.directory(path).await;
+        }
+
+        let mut client = self.client.clone();
+        let blob = format!("{}{}", self.prefix, path);
+        match client.get_blob_properties(&self.container, &blob).await {
+            Ok(info) => self.blob(info, range),
+            Err(e) if is_not_found(&e) => {
+                // A directory requested without its slash
+                let dir = format!("{}/", path);
+                match self.children(&dir).await {
+                    Ok((prefixes, blobs)) if !prefixes.is_empty() || !blobs.is_empty() => {
+                        let location = format!("/{}", utf8_percent_encode(&dir, PATH_ENCODE_SET));
+                        let mut response = plain(StatusCode::MOVED_PERMANENTLY, "Moved");
+                        if let Ok(location) = HeaderValue::from_str(&location) {
+                            response.headers_mut().insert(header::LOCATION, location);
+                        }
+                        response
+                    }
+                    _ => plain(StatusCode::NOT_FOUND, "Not found"),
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {:#}", blob, e);
+                plain(StatusCode::BAD_GATEWAY, "Storage request failed")
+            }
+        }
+    }
+
+    /// Serve a directory's index.html, or list it
+    async fn directory(&self, dir: &str) -> Response<Body> {
+        let mut client = self.client.clone();
+        let index = format!("{}{}{}", self.prefix, dir, INDEX_DOCUMENT);
+        match client.get_blob_properties(&self.container, &index).await {
+            Ok(info) => return self.blob(info, None),
+            Err(e) if !is_not_found(&e) => {
+                tracing::warn!("Failed to read {}: {:#}", index, e);
+                return plain(StatusCode::BAD_GATEWAY, "Storage request failed");
+            }
+            Err(_) => {}
+        }
+        if !self.list {
+            return plain(StatusCode::NOT_FOUND, "Not found");
+        }
+
+        match self.children(dir).await {
+            Ok((prefixes, blobs))
+                if dir.is_empty() || !prefixes.is_empty() || !blobs.is_empty() =>
+            {
+                let mut response = Response::new(full(render_index(dir, &prefixes, &blobs)));
+                response.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("text/html; charset=utf-8"),
+                );
+                response
+            }
+            Ok(_) => plain(StatusCode::NOT_FOUND, "Not found"),
+            Err(e) => {
+                tracing::warn!("Failed to list {}{}: {:#}", self.prefix, dir, e);
+                plain(StatusCode::BAD_GATEWAY, "Storage request failed")
+            }
+        }
+    }
+
+    /// Subdirectories and blobs directly under `dir`, by relative name
+    async fn children(&self, dir: &str) -> Result<(Vec<String>, Vec<(String, u64)>)> {
+        let mut client = self.client.clone();
+        let full_prefix = format!("{}{}", self.prefix, dir);
+        let list_prefix = (!full_prefix.is_empty()).then_some(full_prefix.as_str());
+        let items = client
+            .list_blobs(&self.container, list_prefix, Some("/"))
+            .await?;
+
+        let mut prefixes = Vec::new();
+        let mut blobs = Vec::new();
+        for item in items {
+            match item {
+                BlobItem::Prefix(name) => {
+                    if let Some(name) = name.strip_prefix(&full_prefix) {
+                        prefixes.push(name.to_string());
+                    }
+                }
+                BlobItem::Blob(blob) => {
+                    // Directory markers stand for the directory itself
+                    match blob.name.strip_prefix(&full_prefix) {
+                        Some(name) if !name.is_empty() => {
+                            blobs.push((name.to_string(), blob.properties.content_length))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok((prefixes, blobs))
+    }
+
+    /// Respond with a blob, or the part of it a Range header asked for
+    fn blob(&self, info: BlobInfo, range: Option<&str>) -> Response<Body> {
+        let properties = &info.properties;
+        let size = properties.content_length;
+        let (status, start, end) = match parse_range(range, size) {
+            ByteRange::Whole => (StatusCode::OK, 0, size.saturating_sub(1)),
+            ByteRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end),
+            ByteRange::Unsatisfiable => {
+                let mut response =
+                    plain(StatusCode::RANGE_NOT_SATISFIABLE, "Range not satisfiable");
+                if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", size)) {
+                    response.headers_mut().insert(header::CONTENT_RANGE, value);
+                }
+                return response;
+            }
+        };
+        let length = if size == 0 { 0 } else { end - start + 1 };
+
+        let mut response = Response::new(self.body(&info.name, start, length));
+        *response.status_mut() = status;
+        let headers = response.headers_mut();
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+        if status == StatusCode::PARTIAL_CONTENT {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, size)) {
+                headers.insert(header::CONTENT_RANGE, value);
+            }
+        }
+        let optional = [
+            (header::CONTENT_TYPE, properties.content_type.as_deref()),
+            // Browsers decode blobs uploaded with -z/--compress themselves
+            (
+                header::CONTENT_ENCODING,
+                properties.content_encoding.as_deref(),
+            ),
+            (header::ETAG, properties.etag.as_deref()),
+            (
+                header::LAST_MODIFIED,
+                Some(properties.last_modified.as_str()),
+            ),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value.and_then(|value| HeaderValue::from_str(value).ok()) {
+                headers.insert(name, value);
+            }
+        }
+        response
+    }
+
+    /// Stream `length` bytes of a blob from `start`, in ranged GETs fetched
+    /// a few at a time
+    fn body(&self, blob: &str, start: u64, length: u64) -> Body {
+        let chunk_size = self.readahead.block_size;
+        let chunks: Vec<(u64, u64)> = (start..start + length)
+            .step_by(chunk_size as usize)
+            .map(|from| (from, (from + chunk_size).min(start + length) - 1))
+            .collect();
+        let (client, container, blob) = (
+            self.client.clone(),
+            self.container.clone(),
+            blob.to_string(),
+        );
+
+        let frames = futures::stream::iter(chunks)
+            .map(move |range| {
+                let (mut client, container, blob) =
+                    (client.clone(), container.clone(), blob.clone());
+                async move {
+                    let data = client.download_blob(&container, &blob, Some(range)).await?;
+                    Ok(Frame::data(Bytes::from(data)))
+                }
+            })
+            .buffered(self.readahead.readahead_blocks as usize);
+        StreamBody::new(frames).boxed_unsync()
+    }
+}
+
+/// Serve GET and HEAD requests for the blobs under `uri` on `bind:port`,
+/// until Ctrl-C
+pub async fn execute(uri: &str, bind: &str, port: u16, list: bool) -> Result<()> {
+    let ip: IpAddr = bind
+        .parse()
+        .map_err(|_| anyhow!("Invalid address to listen on: {}", bind))?;
+    let address = SocketAddr::new(ip, port);
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    let prefix = directory_prefix(prefix.as_deref());
+
+    let mut client = AzureClient::new();
+    if let Some(account) = account {
+        client = client.with_storage_account(&account);
+    }
+    client.check_prerequisites().await?;
+
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to listen on {}", address))?;
+    let gateway = Arc::new(Gateway {
+        client,
+        container,
+        prefix,
+        list,
+        readahead: ReadaheadConfig::configured()?,
+    });
+
+    status!(
+        "{} Serving {} on http://{}/ (Ctrl-C to stop)",
+        "✓".green(),
+        uri.cyan(),
+        listener.local_addr()?
+    );
+
+    let cancel = cancel::token();
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = tokio::signal::ctrl_c() => break,
+            _ = cancel.cancelled() => break,
+        };
+        let gateway = gateway.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let gateway = gateway.clone();
+                async move { Ok::<_, Infallible>(gateway.handle(request).await) }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Connection closed with an error: {}", e);
+            }
+        });
+    }
+
+    status!("{} Stopped serving {}", "✓".green(), uri.cyan());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None, 100), ByteRange::Whole);
+        assert_eq!(
+            parse_range(Some("bytes=0-9"), 100),
+            ByteRange::Partial(0, 9)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=90-"), 100),
+            ByteRange::Partial(90, 99)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-10"), 100),
+            ByteRange::Partial(90, 99)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=50-500"), 100),
+            ByteRange::Partial(50, 99)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-500"), 100),
+            ByteRange::Partial(0, 99)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=100-"), 100),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=0-"), 0), ByteRange::Unsatisfiable);
+        // Multiple, inverted or foreign ranges are served whole
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), ByteRange::Whole);
+        assert_eq!(parse_range(Some("bytes=9-0"), 100), ByteRange::Whole);
+        assert_eq!(parse_range(Some("items=0-1"), 100), ByteRange::Whole);
+    }
+
+    #[test]
+    fn test_request_blob_path() {
+        assert_eq!(request_blob_path("/"), Some(String::new()));
+        assert_eq!(
+            request_blob_path("/site/my%20page.html"),
+            Some("site/my page.html".to_string())
+        );
+        assert_eq!(request_blob_path("/a/../secret"), None);
+        assert_eq!(request_blob_path("/a/%2e%2e/secret"), None);
+        assert_eq!(request_blob_path("/%ff"), None);
+    }
+
+    #[test]
+    fn test_render_index_escapes_and_links() {
+        let html = render_index(
+            "docs/",
+            &["img/".to_string()],
+            &[("a <b>.txt".to_string(), 2048)],
+        );
+
+        assert!(html.contains("<title>Index of /docs/</title>"));
+        assert!(html.contains("<a href=\"../\">../</a>"));
+        assert!(html.contains("<a href=\"img/\">img/</a>"));
+        assert!(html.contains("<a href=\"a%20%3Cb%3E.txt\">a &lt;b&gt;.txt</a>"));
+    }
+}
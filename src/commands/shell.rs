@@ -0,0 +1,524 @@
+use anyhow::{anyhow, Result};
+use clap::{CommandFactory, Parser};
+use colored::*;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+use crate::cli::Cli;
+use crate::commands::complete;
+use crate::config::Config;
+use crate::{audit_log, cancel, output, symlinks};
+
+// ============================================================================
+// Shell - Run azst commands interactively with a working prefix
+// ============================================================================
+
+/// The working prefix a session starts in: nothing to resolve against
+const ROOT: &str = "az://";
+
+/// Positional arguments that name a location and so are taken relative to
+/// the working prefix
+const LOCATION_ARGS: &[&str] = &["source", "destination", "path", "uri", "urls"];
+
+/// Commands handled by the shell itself
+const BUILTINS: &[&str] = &["cd", "pwd", "lcd", "help", "exit", "quit"];
+
+/// Settings from `azst shell`'s own command line that every command in the
+/// session inherits
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    pub strict: bool,
+    pub audit_log: Option<PathBuf>,
+}
+
+/// Read and run commands until `exit` or Ctrl-D
+///
+/// Commands run in this process, so the credential resolved by the first one
+/// is reused by the rest instead of being looked up again for every command.
+pub async fn execute(options: SessionOptions) -> Result<()> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper {
+        cwd: ROOT.to_string(),
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // No history yet on first use
+        let _ = editor.load_history(path);
+    }
+
+    // Commands piped in as a script get only their own output
+    if std::io::stdin().is_terminal() {
+        status!(
+            "{} azst shell: 'cd' into a prefix, 'help' for builtins, 'exit' or Ctrl-D to leave",
+            "›".cyan()
+        );
+    }
+
+    let mut cwd = ROOT.to_string();
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.cwd = cwd.clone();
+        }
+        let prompt = format!("azst {}> ", cwd);
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            // Ctrl-C drops the line being typed, like a regular shell
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line.as_str());
+
+        match run_line(&line, &mut cwd, &options).await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => eprintln!("Error: {:#}", e),
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Err(e) = editor.save_history(path) {
+            tracing::debug!("Failed to save shell history: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("azst").join("shell_history"))
+}
+
+/// Run one line, returning false once the session should end
+async fn run_line(line: &str, cwd: &mut String, options: &SessionOptions) -> Result<bool> {
+    let mut words = shlex::split(line).ok_or_else(|| anyhow!("Unbalanced quotes"))?;
+    if words.first().map(String::as_str) == Some("azst") {
+        words.remove(0);
+    }
+    let Some(first) = words.first() else {
+        return Ok(true);
+    };
+
+    match first.as_str() {
+        "exit" | "quit" => return Ok(false),
+        "help" => {
+            print_help();
+            return Ok(true);
+        }
+        "pwd" => {
+            println!("{}", cwd);
+            return Ok(true);
+        }
+        "cd" => {
+            let config = Config::load().unwrap_or_default();
+            let target = words
+                .get(1)
+                .map(|target| config.expand_uri(target).unwrap_or(target.clone()));
+            *cwd = change_dir(cwd, target.as_deref())?;
+            return Ok(true);
+        }
+        "lcd" => {
+            let dir = match words.get(1) {
+                Some(dir) => expand_home(dir),
+                None => dirs::home_dir().ok_or_else(|| anyhow!("No home directory"))?,
+            };
+            std::env::set_current_dir(&dir)
+                .map_err(|e| anyhow!("lcd: {}: {}", dir.display(), e))?;
+            return Ok(true);
+        }
+        "shell" => return Err(anyhow!("Already in an azst shell")),
+        _ => {}
+    }
+
+    // Aliases added during the session apply to the next command
+    let config = Config::load().unwrap_or_default();
+    let words: Vec<String> = words
+        .into_iter()
+        .map(|word| config.expand_uri(&word).unwrap_or(word))
+        .collect();
+    let words = resolve_args(&words, cwd);
+
+    let mut cli = match Cli::try_parse_from(
+        std::iter::once(OsString::from("azst")).chain(words.into_iter().map(OsString::from)),
+    ) {
+        Ok(cli) => cli,
+        Err(e) => {
+            // --help and usage errors print themselves
+            let _ = e.print();
+            return Ok(true);
+        }
+    };
+    cli.strict |= options.strict;
+    if cli.audit_log.is_none() {
+        cli.audit_log = options.audit_log.clone();
+    }
+
+    audit_log::reset();
+    output::reset_header();
+    symlinks::reset_warnings();
+    cancel::reset();
+    // Boxed, as `run` is what started the shell
+    let result = Box::pin(cli.run_cancellable())
+        .await
+        .and_then(|()| cli.check_strict());
+    cli.write_audit_log(&result);
+    result.map(|()| true)
+}
+
+fn print_help() {
+    println!("Run any azst command without the 'azst', e.g. 'ls -l' or 'cp file.csv ./'.");
+    println!();
+    println!("Builtins:");
+    println!("  cd [PREFIX]   Change the working prefix ('cd' alone returns to az://)");
+    println!("  pwd           Print the working prefix");
+    println!("  lcd [DIR]     Change the local directory");
+    println!("  help          Show this help");
+    println!("  exit, quit    Leave the shell (or press Ctrl-D)");
+    println!();
+    println!("Relative paths are taken from the working prefix, and '.' is the prefix");
+    println!("itself. Write local files as /abs, ./rel, ../rel or ~/rel.");
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None if path == "~" => dirs::home_dir().unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+/// The working prefix after `cd target`
+///
+/// Prefixes are not checked against Azure: like blob "directories", any
+/// prefix may be entered, and listing it shows whether anything is there.
+fn change_dir(cwd: &str, target: Option<&str>) -> Result<String> {
+    let target = match target {
+        None | Some("/") => return Ok(ROOT.to_string()),
+        Some(target) => target,
+    };
+    if target.contains("://") {
+        if !target.starts_with(ROOT) {
+            return Err(anyhow!("cd: only az:// prefixes can be entered"));
+        }
+        return Ok(join(ROOT, &target[ROOT.len()..]));
+    }
+    // '/account/container' is taken from the top, like an absolute path
+    match target.strip_prefix('/') {
+        Some(rest) => Ok(join(ROOT, rest)),
+        None => Ok(join(cwd, target)),
+    }
+}
+
+/// Append a relative path to a prefix, with `.` and `..` segments applied,
+/// giving a prefix that ends in '/'
+fn join(base: &str, relative: &str) -> String {
+    let mut segments: Vec<&str> = base[ROOT.len()..]
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut joined = ROOT.to_string();
+    for segment in segments {
+        joined.push_str(segment);
+        joined.push('/');
+    }
+    joined
+}
+
+/// Whether a word names a local file rather than a path under the prefix
+fn is_local(word: &str) -> bool {
+    word == "-"
+        || word == ".."
+        || word == "~"
+        || ["/", "./", "../", "~/"]
+            .iter()
+            .any(|prefix| word.starts_with(prefix))
+}
+
+/// Resolve one location argument against the working prefix
+fn resolve(cwd: &str, word: &str) -> String {
+    if word.starts_with("~/") || word == "~" {
+        return expand_home(word).to_string_lossy().into_owned();
+    }
+    if cwd == ROOT || word.contains("://") || is_local(word) {
+        return word.to_string();
+    }
+    if word == "." {
+        return cwd.to_string();
+    }
+    let joined = join(cwd, word);
+    if word.ends_with('/') {
+        joined
+    } else {
+        // A blob, or a prefix the command reads as one without the '/'
+        joined.trim_end_matches('/').to_string()
+    }
+}
+
+/// Resolve the location arguments of a command line against the working
+/// prefix; a bare `ls` or `du` lists the prefix itself
+fn resolve_args(words: &[String], cwd: &str) -> Vec<String> {
+    let mut command = Cli::command();
+    command.build();
+
+    let mut resolved = Vec::with_capacity(words.len() + 1);
+    let mut current = &command;
+    let mut path: Vec<&str> = Vec::new();
+    let mut position = 0;
+    let mut positionals_seen = 0;
+    let mut only_positionals = false;
+    let mut words_iter = words.iter();
+
+    while let Some(word) = words_iter.next() {
+        if !only_positionals && word == "--" {
+            only_positionals = true;
+            resolved.push(word.clone());
+            continue;
+        }
+        if !only_positionals && word.starts_with("--") {
+            resolved.push(word.clone());
+            let takes_value = !word.contains('=')
+                && current
+                    .get_arguments()
+                    .find(|arg| arg.get_long() == Some(&word[2..]))
+                    .is_some_and(|arg| arg.get_action().takes_values());
+            if takes_value {
+                if let Some(value) = words_iter.next() {
+                    resolved.push(value.clone());
+                }
+            }
+            continue;
+        }
+        if !only_positionals && word.starts_with('-') && word.len() > 1 {
+            resolved.push(word.clone());
+            // In a cluster like -rf or -ofile, a short option that takes a
+            // value ends it; with nothing after, the value is the next word
+            let mut needs_value = false;
+            for (i, short) in word.char_indices().skip(1) {
+                let takes_value = current
+                    .get_arguments()
+                    .find(|arg| arg.get_short() == Some(short))
+                    .is_some_and(|arg| arg.get_action().takes_values());
+                if takes_value {
+                    needs_value = i + short.len_utf8() == word.len();
+                    break;
+                }
+            }
+            if needs_value {
+                if let Some(value) = words_iter.next() {
+                    resolved.push(value.clone());
+                }
+            }
+            continue;
+        }
+
+        if current.has_subcommands() {
+            if let Some(subcommand) = current.find_subcommand(word) {
+                current = subcommand;
+                path.push(subcommand.get_name());
+                position = 0;
+                resolved.push(word.clone());
+                continue;
+            }
+        }
+
+        let positionals: Vec<_> = current.get_positionals().collect();
+        let arg = positionals.get(position).copied();
+        let is_location = arg.is_some_and(|arg| LOCATION_ARGS.contains(&arg.get_id().as_str()));
+        let repeats = arg.is_some_and(|arg| {
+            arg.get_num_args()
+                .is_some_and(|range| range.max_values() > 1)
+        });
+        if !repeats {
+            position += 1;
+        }
+        positionals_seen += 1;
+        resolved.push(if is_location {
+            resolve(cwd, word)
+        } else {
+            word.clone()
+        });
+    }
+
+    if cwd != ROOT && positionals_seen == 0 && matches!(path.as_slice(), ["ls"] | ["du"]) {
+        resolved.push(cwd.to_string());
+    }
+    resolved
+}
+
+/// Tab completion of command names and remote paths
+struct ShellHelper {
+    cwd: String,
+}
+
+impl ShellHelper {
+    fn complete_word(&self, word: &str, first: bool) -> Vec<String> {
+        if first {
+            let mut names: Vec<String> = Cli::command()
+                .get_subcommands()
+                .map(|command| command.get_name().to_string())
+                .chain(BUILTINS.iter().map(|name| name.to_string()))
+                .filter(|name| name.starts_with(word))
+                .collect();
+            names.sort();
+            return names.into_iter().map(|name| name + " ").collect();
+        }
+
+        let (partial, strip) = if word.starts_with(ROOT) {
+            (word.to_string(), "")
+        } else if self.cwd != ROOT && !word.contains("://") && !is_local(word) {
+            (format!("{}{}", self.cwd, word), self.cwd.as_str())
+        } else {
+            return Vec::new();
+        };
+        let handle = tokio::runtime::Handle::current();
+        let candidates =
+            tokio::task::block_in_place(|| handle.block_on(complete::completions(&partial)));
+        candidates
+            .into_iter()
+            .map(|candidate| candidate[strip.len()..].to_string())
+            .collect()
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let first = before[..start].trim().is_empty() || before[..start].trim() == "azst";
+        Ok((start, self.complete_word(&before[start..], first)))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        shlex::split(line).unwrap()
+    }
+
+    #[test]
+    fn test_change_dir() {
+        assert_eq!(change_dir(ROOT, Some("acct")).unwrap(), "az://acct/");
+        assert_eq!(
+            change_dir("az://acct/", Some("data/2024")).unwrap(),
+            "az://acct/data/2024/"
+        );
+        assert_eq!(
+            change_dir("az://acct/data/2024/", Some("..")).unwrap(),
+            "az://acct/data/"
+        );
+        assert_eq!(
+            change_dir("az://acct/data/", Some("../../..")).unwrap(),
+            ROOT
+        );
+        assert_eq!(
+            change_dir("az://acct/data/", Some("az://other/logs")).unwrap(),
+            "az://other/logs/"
+        );
+        assert_eq!(
+            change_dir("az://acct/data/", Some("/other/logs/")).unwrap(),
+            "az://other/logs/"
+        );
+        assert_eq!(change_dir("az://acct/data/", None).unwrap(), ROOT);
+        assert!(change_dir(ROOT, Some("https://example.com/")).is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let cwd = "az://acct/data/";
+        assert_eq!(resolve(cwd, "2024/"), "az://acct/data/2024/");
+        assert_eq!(resolve(cwd, "file.csv"), "az://acct/data/file.csv");
+        assert_eq!(resolve(cwd, "."), cwd);
+        assert_eq!(resolve(cwd, "../logs/"), "../logs/");
+        assert_eq!(resolve(cwd, "./file.csv"), "./file.csv");
+        assert_eq!(resolve(cwd, "/tmp/file.csv"), "/tmp/file.csv");
+        assert_eq!(resolve(cwd, "-"), "-");
+        assert_eq!(resolve(cwd, "az://other/x"), "az://other/x");
+        // Nothing to resolve against at the top
+        assert_eq!(resolve(ROOT, "file.csv"), "file.csv");
+    }
+
+    #[test]
+    fn test_resolve_args_only_touches_locations() {
+        let cwd = "az://acct/data/";
+        assert_eq!(
+            resolve_args(&words("cp -r 2024/ ./local/"), cwd),
+            words("cp -r az://acct/data/2024/ ./local/")
+        );
+        assert_eq!(
+            resolve_args(&words("cp --exclude-pattern tmp file.csv ./"), cwd),
+            words("cp --exclude-pattern tmp az://acct/data/file.csv ./")
+        );
+        assert_eq!(
+            resolve_args(&words("rm --exclude-pattern=*.db -- -weird"), cwd),
+            words("rm --exclude-pattern=*.db -- az://acct/data/-weird")
+        );
+        assert_eq!(
+            resolve_args(&words("alias add mine ."), cwd),
+            words("alias add mine az://acct/data/")
+        );
+    }
+
+    #[test]
+    fn test_resolve_args_lists_the_prefix() {
+        let cwd = "az://acct/data/";
+        assert_eq!(
+            resolve_args(&words("ls -l"), cwd),
+            words("ls -l az://acct/data/")
+        );
+        assert_eq!(resolve_args(&words("du"), cwd), words("du az://acct/data/"));
+        assert_eq!(resolve_args(&words("ls"), ROOT), words("ls"));
+        assert_eq!(resolve_args(&words("pwd"), cwd), words("pwd"));
+    }
+
+    #[test]
+    fn test_is_local() {
+        assert!(is_local("/tmp"));
+        assert!(is_local("./x"));
+        assert!(is_local("~/x"));
+        assert!(!is_local("x"));
+        assert!(!is_local("."));
+    }
+}
@@ -1,21 +1,65 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use colored::*;
-use std::io::{self, Write};
+use std::path::Path;
 
-use crate::azure::{convert_az_uri_to_url, AzCopyClient, AzCopyOptions};
-use crate::utils::{is_azure_uri, parse_azure_uri};
+use crate::audit_log;
+use crate::azure::{convert_az_uri_to_url, AzCopyClient, AzCopyOptions, PathFilters};
+use crate::bandwidth::BandwidthSchedule;
+use crate::config::TransferConfig;
+use crate::dir_markers;
+use crate::dry_run::{DryRunPlan, PlannedTransfer};
+use crate::manifest::batches;
+use crate::prompt::Prompter;
+use crate::quota::{self, Transfer};
+use crate::region;
+use crate::symlinks::SymlinkPolicy;
+use crate::sync_state::{
+    conflict_name, detect_conflicts, hash_unrecorded, join_location, plan_bidirectional,
+    plan_one_way, snapshot_for, CompareMode, Conflict, ConflictPolicy, DeleteLimit, Snapshot,
+    SyncAction, SyncState,
+};
+use crate::tuning::{self, TuningMode};
+use crate::utils::{is_azure_uri, matches_any_pattern, parse_azure_uri};
+
+/// How to sync conflicts without being asked about each
+const CONFLICT_BYPASS: &str = "choose a --conflict policy other than ask";
 
 pub struct SyncOptions<'a> {
     pub source: &'a str,
     pub destination: &'a str,
     pub delete_destination: bool,
+    /// Delete like `delete_destination`, but only after listing the
+    /// deletions and checking them against `max_delete`
+    pub mirror: bool,
+    pub max_delete: DeleteLimit,
     pub force: bool,
     pub dry_run: bool,
     pub cap_mbps: Option<f64>,
+    pub bandwidth_schedule: Option<BandwidthSchedule>,
     pub block_size_mb: Option<f64>,
     pub put_md5: bool,
     pub include_pattern: Option<&'a str>,
     pub exclude_pattern: Option<&'a str>,
+    pub filters: PathFilters,
+    pub track_state: Option<&'a str>,
+    pub conflict: ConflictPolicy,
+    pub bidirectional: bool,
+    pub preserve: bool,
+    pub preserve_empty_dirs: bool,
+    pub max_upload_bytes: Option<u64>,
+    pub allow_cross_region: bool,
+    pub compare: CompareMode,
+    /// What to do with symbolic links under a local side
+    pub symlinks: SymlinkPolicy,
+    /// How transfers are tuned for the account they write to
+    pub tuning: TuningMode,
+    /// Concurrency `tuning` chose for the destination account, filled in
+    /// once it's looked up
+    pub tuned_concurrency: Option<u32>,
+    /// `--concurrency`, `--buffer-gb` and `--scan-concurrency`
+    pub transfer_flags: TransferConfig,
+    /// Asks before deleting and, under `--conflict ask`, about each conflict
+    pub prompter: &'a dyn Prompter,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -23,48 +67,85 @@ pub async fn execute(
     source: &str,
     destination: &str,
     delete_destination: bool,
+    mirror: bool,
+    max_delete: DeleteLimit,
     force: bool,
     dry_run: bool,
     cap_mbps: Option<f64>,
+    bandwidth_schedule: Option<BandwidthSchedule>,
     block_size_mb: Option<f64>,
     put_md5: bool,
     include_pattern: Option<&str>,
     exclude_pattern: Option<&str>,
+    filters: PathFilters,
+    track_state: Option<&str>,
+    conflict: ConflictPolicy,
+    bidirectional: bool,
+    preserve: bool,
+    preserve_empty_dirs: bool,
+    max_upload_bytes: Option<u64>,
+    allow_cross_region: bool,
+    compare: CompareMode,
+    symlinks: SymlinkPolicy,
+    tuning: TuningMode,
+    transfer_flags: TransferConfig,
+    prompter: &dyn Prompter,
 ) -> Result<()> {
     let options = SyncOptions {
         source,
         destination,
-        delete_destination,
+        delete_destination: delete_destination || mirror,
+        mirror,
+        max_delete,
         force,
         dry_run,
         cap_mbps,
+        bandwidth_schedule,
         block_size_mb,
         put_md5,
         include_pattern,
         exclude_pattern,
+        filters,
+        track_state,
+        conflict,
+        bidirectional,
+        preserve,
+        preserve_empty_dirs,
+        max_upload_bytes,
+        allow_cross_region,
+        compare,
+        symlinks,
+        tuning,
+        tuned_concurrency: None,
+        transfer_flags,
+        prompter,
     };
     execute_with_options(options).await
 }
 
 async fn execute_with_options(options: SyncOptions<'_>) -> Result<()> {
-    let source = options.source;
-    let destination = options.destination;
-    let source_is_azure = is_azure_uri(source);
-    let dest_is_azure = is_azure_uri(destination);
+    let mut azcopy = AzCopyClient::new();
 
-    // Sync only works with at least one Azure location
-    if !source_is_azure && !dest_is_azure {
-        return Err(anyhow!(
-            "Sync requires at least one Azure location (az://...)"
-        ));
+    // Local-to-local syncs copy files directly and don't need AzCopy
+    if is_local_sync(&options) {
+        if !Path::new(options.source).is_dir() {
+            return Err(anyhow!(
+                "Source directory '{}' does not exist",
+                options.source
+            ));
+        }
+    } else {
+        azcopy.check_prerequisites().await?;
     }
-
-    let mut azcopy = AzCopyClient::new();
-    azcopy.check_prerequisites().await?;
     sync_with_azcopy(&mut azcopy, options).await
 }
 
-async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -> Result<()> {
+/// Whether both sides of the sync are local directories
+fn is_local_sync(options: &SyncOptions<'_>) -> bool {
+    !is_azure_uri(options.source) && !is_azure_uri(options.destination)
+}
+
+async fn sync_with_azcopy(azcopy: &mut AzCopyClient, mut options: SyncOptions<'_>) -> Result<()> {
     let source = options.source;
     let destination = options.destination;
     let delete_destination = options.delete_destination;
@@ -91,27 +172,75 @@ async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -
         }
     }
 
-    // Warn about delete-destination if not forced
-    if delete_destination && !force {
+    if options.symlinks != SymlinkPolicy::Skip && is_azure_uri(source) {
+        return Err(anyhow!(
+            "--follow-symlinks and --preserve-symlinks apply to local sources"
+        ));
+    }
+
+    // A dry run reports the cost without refusing
+    region::check_cross_region(
+        source,
+        destination,
+        Transfer::Sync,
+        options.allow_cross_region || options.dry_run,
+    )
+    .await?;
+    quota::check_upload(
+        source,
+        destination,
+        Transfer::Sync,
+        options.max_upload_bytes,
+        options.symlinks,
+    )
+    .await?;
+
+    // Premium block blob accounts get their own block size and concurrency
+    // unless the user chose them
+    let tuned = if is_azure_uri(destination) && !options.dry_run {
+        tuning::tune(destination, options.tuning).await
+    } else {
+        None
+    };
+    if let (None, Some(tuned)) = (options.block_size_mb, tuned) {
+        options.block_size_mb = Some(tuned.block_size_mb);
+    }
+    options.tuned_concurrency = tuned.map(|tuned| tuned.concurrency);
+
+    // Two-way sync plans its own copies and deletions from the tracked state
+    if options.bidirectional {
+        let state_path = options
+            .track_state
+            .ok_or_else(|| anyhow!("Bidirectional sync requires --track-state"))?;
+        return sync_bidirectional(azcopy, &options, state_path).await;
+    }
+
+    // Warn about delete-destination if not forced; a mirror checks its
+    // deletions against --max-delete instead
+    if delete_destination && !force && !options.mirror {
         println!(
             "{} {}",
             "⚠".yellow(),
             "Sync with --delete will remove files in destination that don't exist in source!"
                 .yellow()
         );
-        print!("Continue? (y/N): ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim().to_lowercase();
-
-        if input != "y" && input != "yes" {
+        if !options
+            .prompter
+            .confirm("Continue?", "pass --force to delete without asking")
+            .await?
+        {
             println!("Aborted");
             return Ok(());
         }
     }
 
+    // Resolve files changed on both sides since the last tracked sync
+    let resolved = match options.track_state {
+        Some(state_path) => resolve_conflicts(azcopy, &options, state_path).await?,
+        None => Resolved::default(),
+    };
+    let kept_destination = resolved.kept_destination;
+
     // Convert az:// URIs to HTTPS URLs for AzCopy
     let source_url = if is_azure_uri(source) {
         convert_az_uri_to_url(source)?
@@ -130,11 +259,16 @@ async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -
         (false, true) => "Syncing local to Azure",
         (true, false) => "Syncing Azure to local",
         (true, true) => "Syncing Azure to Azure",
-        _ => "Syncing",
+        (false, false) => "Syncing local to local",
     };
 
     let mut flags_display = Vec::new();
-    if delete_destination {
+    if tuned.is_some() {
+        flags_display.push("premium-tuned");
+    }
+    if options.mirror {
+        flags_display.push("mirror");
+    } else if delete_destination {
         flags_display.push("delete");
     }
     if options.dry_run {
@@ -143,15 +277,29 @@ async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -
     if options.cap_mbps.is_some() {
         flags_display.push("rate-limited");
     }
+    if options.bandwidth_schedule.is_some() {
+        flags_display.push("bandwidth-schedule");
+    }
     if options.block_size_mb.is_some() {
         flags_display.push("custom-block-size");
     }
     if options.put_md5 {
         flags_display.push("md5-hashing");
     }
-    if options.include_pattern.is_some() {
+    if options.include_pattern.is_some() || !options.filters.is_empty() {
         flags_display.push("filtered");
     }
+    if options.track_state.is_some() {
+        flags_display.push("tracked");
+    }
+    if options.preserve {
+        flags_display.push("preserve");
+    }
+    match options.compare {
+        CompareMode::Mtime => {}
+        CompareMode::Size => flags_display.push("size-only"),
+        CompareMode::Checksum => flags_display.push("checksum"),
+    }
 
     let flags_str = if !flags_display.is_empty() {
         format!(" ({})", flags_display.join(", "))
@@ -159,7 +307,7 @@ async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -
         String::new()
     };
 
-    println!(
+    status!(
         "{} {} {} → {}{}",
         "⇄".green(),
         operation_type,
@@ -168,12 +316,35 @@ async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -
         flags_str.yellow()
     );
 
+    // azcopy can't compare sizes alone, sync two local directories, limit a
+    // sync to some paths or say beforehand what it will delete, so those
+    // syncs are planned here. So are checksum syncs of a local tree: azcopy
+    // hashes every file again on each run, while the planner reuses the hashes
+    // of unchanged files
+    if options.compare == CompareMode::Size
+        || (options.compare == CompareMode::Checksum
+            && !(is_azure_uri(source) && is_azure_uri(destination)))
+        || is_local_sync(&options)
+        || options.filters.include_path.is_some()
+        || options.mirror
+    {
+        sync_planned(azcopy, &options, &kept_destination).await?;
+        return finish_sync(&options, &resolved.flagged).await;
+    }
+
     // Build options
     let mut azcopy_options = AzCopyOptions::new()
         .with_dry_run(options.dry_run)
         .with_cap_mbps(options.cap_mbps)
+        .with_bandwidth_schedule(options.bandwidth_schedule.clone())
         .with_block_size_mb(options.block_size_mb)
-        .with_put_md5(options.put_md5);
+        .with_put_md5(options.put_md5)
+        .with_preserve_properties(options.preserve)
+        .with_compare_md5(options.compare == CompareMode::Checksum)
+        .with_path_filters(&options.filters)
+        .with_symlinks(options.symlinks)
+        .with_tuned_concurrency(options.tuned_concurrency)
+        .with_transfer_flags(options.transfer_flags.clone());
 
     if let Some(pattern) = options.include_pattern {
         azcopy_options = azcopy_options.with_include_pattern(Some(pattern.to_string()));
@@ -181,6 +352,8 @@ async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -
     if let Some(pattern) = options.exclude_pattern {
         azcopy_options = azcopy_options.with_exclude_pattern(Some(pattern.to_string()));
     }
+    let kept: Vec<&str> = kept_destination.iter().map(String::as_str).collect();
+    azcopy_options = azcopy_options.with_extra_exclude_paths(&kept);
 
     // Show the actual AzCopy command for debugging
     let mut cmd_parts = vec![format!("azcopy sync '{}' '{}'", source_url, dest_url)];
@@ -199,22 +372,852 @@ async fn sync_with_azcopy(azcopy: &mut AzCopyClient, options: SyncOptions<'_>) -
     if options.put_md5 {
         cmd_parts.push("--put-md5".to_string());
     }
+    if options.preserve {
+        cmd_parts.push(AzCopyOptions::preserve_properties_flag().to_string());
+    }
+    if azcopy_options.compare_md5 {
+        cmd_parts.push("--compare-hash=MD5".to_string());
+    }
     if let Some(pattern) = options.include_pattern {
         cmd_parts.push(format!("--include-pattern='{}'", pattern));
     }
     if let Some(pattern) = options.exclude_pattern {
         cmd_parts.push(format!("--exclude-pattern='{}'", pattern));
     }
+    if let Some(path) = &azcopy_options.exclude_path {
+        cmd_parts.push(format!("--exclude-path='{}'", path));
+    }
+    if let Some(regex) = &azcopy_options.include_regex {
+        cmd_parts.push(format!("--include-regex='{}'", regex));
+    }
+    if let Some(regex) = &azcopy_options.exclude_regex {
+        cmd_parts.push(format!("--exclude-regex='{}'", regex));
+    }
+    cmd_parts.push("--output-type json".to_string());
 
-    println!("{} {}", "⚙".dimmed(), cmd_parts.join(" ").dimmed());
-    println!(); // Blank line before AzCopy output
+    tracing::debug!("{}", cmd_parts.join(" "));
+    status!(); // Blank line before AzCopy output
 
     // Use AzCopy for the sync operation
-    azcopy
+    let summary = azcopy
         .sync_with_options(&source_url, &dest_url, delete_destination, &azcopy_options)
         .await?;
+    if options.dry_run {
+        DryRunPlan::new(summary.planned).print();
+    }
+
+    status!(); // Blank line after AzCopy output
+
+    finish_sync(&options, &resolved.flagged).await
+}
+
+/// Carry over empty directories and record the tracked state after a
+/// one-way sync
+async fn finish_sync(options: &SyncOptions<'_>, flagged: &[String]) -> Result<()> {
+    let source = options.source;
+    let destination = options.destination;
+
+    if options.preserve_empty_dirs && !options.dry_run {
+        sync_empty_dirs(source, destination).await?;
+    }
+
+    // Record the new state of both sides for the next run
+    if let (Some(state_path), false) = (options.track_state, options.dry_run) {
+        save_state(options, state_path, flagged).await?;
+    }
+
+    warn_flagged(flagged);
+    status!("{} Sync completed successfully", "✓".green());
+    Ok(())
+}
 
-    println!(); // Blank line after AzCopy output
-    println!("{} Sync completed successfully", "✓".green());
+/// Record the state of both sides after a tracked sync; files left in
+/// conflict keep their previous entries, so the next run flags them again
+/// until they are resolved by hand
+async fn save_state(options: &SyncOptions<'_>, state_path: &str, flagged: &[String]) -> Result<()> {
+    let previous = SyncState::load(state_path)?;
+    let mut state = SyncState {
+        source: options.source.to_string(),
+        destination: options.destination.to_string(),
+        source_entries: snapshot_for(options.source, options.compare, options.symlinks).await?,
+        destination_entries: snapshot_for(options.destination, options.compare, options.symlinks)
+            .await?,
+    };
+    state.keep_previous(&previous, flagged);
+    state.save(state_path)?;
+    status!("{} Sync state saved to {}", "ℹ".blue(), state_path.dimmed());
     Ok(())
 }
+
+/// List the conflicts `--conflict flag` left alone
+fn warn_flagged(flagged: &[String]) {
+    if flagged.is_empty() {
+        return;
+    }
+    eprintln!(
+        "{} {} file(s) changed on both sides were left as they are; keep the version you want on both sides and sync again:",
+        "⚠".yellow(),
+        flagged.len()
+    );
+    for path in flagged {
+        eprintln!("  {} {}", "!".yellow(), path);
+    }
+}
+
+/// One-way sync planned from snapshots of both sides: copy files that are
+/// missing or differ at the destination, and with --delete remove files the
+/// source doesn't have
+async fn sync_planned(
+    azcopy: &mut AzCopyClient,
+    options: &SyncOptions<'_>,
+    kept_destination: &[String],
+) -> Result<()> {
+    let source_entries = snapshot_for(options.source, options.compare, options.symlinks).await?;
+    let destination_entries =
+        snapshot_for(options.destination, options.compare, options.symlinks).await?;
+    let actions = plan_one_way(
+        &source_entries,
+        &destination_entries,
+        options.compare,
+        options.delete_destination,
+    );
+
+    let mut copies = Vec::new();
+    let mut deletions = Vec::new();
+    for action in actions {
+        match action {
+            SyncAction::CopyToDestination(path)
+                if matches_filters(options, &path) && !kept_destination.contains(&path) =>
+            {
+                copies.push(path);
+            }
+            SyncAction::DeleteFromDestination(path) if matches_filters(options, &path) => {
+                deletions.push(path);
+            }
+            _ => {}
+        }
+    }
+
+    if options.mirror {
+        check_mirror_deletions(options, &deletions, &destination_entries)?;
+    }
+
+    if options.dry_run {
+        let mut planned = planned_copies(
+            options.source,
+            options.destination,
+            &copies,
+            &source_entries,
+        );
+        planned.extend(planned_deletions(
+            options.destination,
+            &deletions,
+            &destination_entries,
+        ));
+        DryRunPlan::new(planned).print();
+        return Ok(());
+    }
+    for path in &copies {
+        status!("  {} {}", "→".green(), path);
+    }
+    for path in &deletions {
+        status!("  {} {} (destination)", "×".red(), path);
+    }
+    if copies.is_empty() && deletions.is_empty() {
+        status!("{} Destination is already in sync", "✓".green());
+    }
+    copy_batch(
+        azcopy,
+        options.source,
+        options.destination,
+        &copies,
+        options,
+    )
+    .await?;
+    delete_batch(
+        azcopy,
+        options.destination,
+        &deletions,
+        &options.transfer_flags,
+    )
+    .await
+}
+
+/// Stop a mirror that would delete more of the destination than --max-delete
+/// allows, unless forced, listing what it would have deleted
+///
+/// An empty or mistyped source makes a mirror delete everything, so a large
+/// share of deletions is taken as a mistake. Dry runs only warn.
+fn check_mirror_deletions(
+    options: &SyncOptions<'_>,
+    deletions: &[String],
+    destination_entries: &Snapshot,
+) -> Result<()> {
+    let total = destination_entries
+        .keys()
+        .filter(|path| matches_filters(options, path))
+        .count();
+    if options.force || !options.max_delete.exceeded_by(deletions.len(), total) {
+        return Ok(());
+    }
+    let message = format!(
+        "Mirroring would delete {} of the {} file(s) at the destination, more than --max-delete {}",
+        deletions.len(),
+        total,
+        options.max_delete
+    );
+    if options.dry_run {
+        eprintln!("{} {}; a real run would stop here", "⚠".yellow(), message);
+        return Ok(());
+    }
+    eprintln!("{} {}:", "⚠".yellow(), message);
+    for path in deletions {
+        eprintln!(
+            "  {} {}",
+            "×".red(),
+            join_location(options.destination, path)
+        );
+    }
+    Err(anyhow!(
+        "Mirror aborted: check the source, or raise --max-delete or pass --force to delete them"
+    ))
+}
+
+/// Copies of `paths` from one sync root to the other, for a dry run's plan
+fn planned_copies(
+    from_root: &str,
+    to_root: &str,
+    paths: &[String],
+    from_entries: &Snapshot,
+) -> Vec<PlannedTransfer> {
+    paths
+        .iter()
+        .map(|path| {
+            PlannedTransfer::transfer(
+                &join_location(from_root, path),
+                &join_location(to_root, path),
+                from_entries.get(path).map(|entry| entry.size),
+            )
+        })
+        .collect()
+}
+
+/// Deletions of `paths` under a sync root, for a dry run's plan
+fn planned_deletions(root: &str, paths: &[String], entries: &Snapshot) -> Vec<PlannedTransfer> {
+    paths
+        .iter()
+        .map(|path| {
+            PlannedTransfer::delete(
+                &join_location(root, path),
+                entries.get(path).map(|entry| entry.size),
+            )
+        })
+        .collect()
+}
+
+/// Whether a relative path passes --include-pattern and --exclude-pattern,
+/// which like azcopy's match file names against `;`-separated wildcards, and
+/// the path and regex filters
+fn matches_filters(options: &SyncOptions<'_>, path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let matches_any = |patterns: &str| matches_any_pattern(name, patterns);
+    options.include_pattern.is_none_or(matches_any)
+        && !options.exclude_pattern.is_some_and(matches_any)
+        && options.filters.matches(path)
+}
+
+/// Which version of a conflicting file to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    KeepSource,
+    KeepDestination,
+    RenameDestination,
+    /// Touch neither side and report the conflict
+    Flag,
+}
+
+/// Conflicts resolved before a one-way tracked sync
+#[derive(Default)]
+struct Resolved {
+    /// Paths to exclude from the sync: where the destination wins or the
+    /// conflict was flagged
+    kept_destination: Vec<String>,
+    /// Paths left in conflict
+    flagged: Vec<String>,
+}
+
+/// Detect and resolve conflicts against the tracked state
+///
+/// Files where the source wins are copied explicitly (azcopy sync would skip
+/// them if the destination is newer), renamed destination files are moved
+/// aside, and files where the destination wins or that are flagged are
+/// excluded from the sync.
+async fn resolve_conflicts(
+    azcopy: &mut AzCopyClient,
+    options: &SyncOptions<'_>,
+    state_path: &str,
+) -> Result<Resolved> {
+    let source = options.source;
+    let destination = options.destination;
+
+    let state = SyncState::load(state_path)?;
+    if !state.source_entries.is_empty() && !state.matches(source, destination) {
+        return Err(anyhow!(
+            "Sync state file '{}' was recorded for '{}' → '{}'. Use a different --track-state file for this pair.",
+            state_path,
+            state.source,
+            state.destination
+        ));
+    }
+
+    let conflicts = detect_conflicts(
+        &state,
+        &snapshot_for(source, options.compare, options.symlinks).await?,
+        &snapshot_for(destination, options.compare, options.symlinks).await?,
+        options.compare,
+    );
+    if conflicts.is_empty() {
+        return Ok(Resolved::default());
+    }
+
+    status!(
+        "{} {} file(s) changed on both sides since the last sync",
+        "⚠".yellow(),
+        conflicts.len()
+    );
+
+    let mut resolved = Resolved::default();
+
+    for conflict in &conflicts {
+        let resolution = choose_resolution(options, conflict).await?;
+        let source_path = join_location(source, &conflict.path);
+        let dest_path = join_location(destination, &conflict.path);
+
+        match resolution {
+            Resolution::KeepDestination => {
+                status!("  {} {} (keeping destination)", "=".dimmed(), conflict.path);
+                resolved.kept_destination.push(conflict.path.clone());
+            }
+            Resolution::Flag => {
+                status!(
+                    "  {} {} (conflict, left as is)",
+                    "!".yellow(),
+                    conflict.path
+                );
+                resolved.kept_destination.push(conflict.path.clone());
+                resolved.flagged.push(conflict.path.clone());
+            }
+            Resolution::KeepSource => {
+                let action = if options.dry_run {
+                    "would keep source"
+                } else {
+                    "keeping source"
+                };
+                status!("  {} {} ({})", "→".green(), conflict.path, action);
+                if !options.dry_run {
+                    copy_single(azcopy, &source_path, &dest_path).await?;
+                }
+            }
+            Resolution::RenameDestination => {
+                let renamed = conflict_name(
+                    &conflict.path,
+                    time::OffsetDateTime::now_utc().unix_timestamp(),
+                );
+                let kept = if options.dry_run {
+                    "would be kept"
+                } else {
+                    "kept"
+                };
+                status!(
+                    "  {} {} (destination copy {} as {})",
+                    "↻".yellow(),
+                    conflict.path,
+                    kept,
+                    renamed.cyan()
+                );
+                if !options.dry_run {
+                    let renamed_path = join_location(destination, &renamed);
+                    move_single(azcopy, &dest_path, &renamed_path).await?;
+                }
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Pick the version to keep for a conflict according to the configured policy
+async fn choose_resolution(options: &SyncOptions<'_>, conflict: &Conflict) -> Result<Resolution> {
+    // "local" means the source for Azure-to-Azure syncs
+    let source_is_local = !is_azure_uri(options.source) || is_azure_uri(options.destination);
+
+    let resolution = match options.conflict {
+        ConflictPolicy::Newer => {
+            if conflict.source.modified >= conflict.destination.modified {
+                Resolution::KeepSource
+            } else {
+                Resolution::KeepDestination
+            }
+        }
+        ConflictPolicy::Local if source_is_local => Resolution::KeepSource,
+        ConflictPolicy::Local => Resolution::KeepDestination,
+        ConflictPolicy::Remote if source_is_local => Resolution::KeepDestination,
+        ConflictPolicy::Remote => Resolution::KeepSource,
+        ConflictPolicy::Ask => prompt_resolution(options.prompter, conflict).await?,
+        ConflictPolicy::Rename => Resolution::RenameDestination,
+        ConflictPolicy::Flag => Resolution::Flag,
+    };
+
+    Ok(resolution)
+}
+
+/// Propagate changes in both directions based on the tracked state
+async fn sync_bidirectional(
+    azcopy: &mut AzCopyClient,
+    options: &SyncOptions<'_>,
+    state_path: &str,
+) -> Result<()> {
+    let source = options.source;
+    let destination = options.destination;
+
+    let state = SyncState::load(state_path)?;
+    if !state.source_entries.is_empty() && !state.matches(source, destination) {
+        return Err(anyhow!(
+            "Sync state file '{}' was recorded for '{}' ⇄ '{}'. Use a different --track-state file for this pair.",
+            state_path,
+            state.source,
+            state.destination
+        ));
+    }
+
+    let dry_run_str = if options.dry_run { " (dry-run)" } else { "" };
+    status!(
+        "{} Syncing {} ⇄ {}{}",
+        "⇄".green(),
+        source.cyan(),
+        destination.cyan(),
+        dry_run_str.yellow()
+    );
+
+    let mut source_entries = snapshot_for(source, options.compare, options.symlinks).await?;
+    let mut destination_entries =
+        snapshot_for(destination, options.compare, options.symlinks).await?;
+    hash_unrecorded(
+        &state,
+        source,
+        &mut source_entries,
+        destination,
+        &mut destination_entries,
+    )
+    .await?;
+    let actions = plan_bidirectional(
+        &state,
+        &source_entries,
+        &destination_entries,
+        options.compare,
+    );
+    if actions.is_empty() {
+        status!("{} Both sides are already in sync", "✓".green());
+    }
+
+    let mut to_destination = Vec::new();
+    let mut to_source = Vec::new();
+    let mut delete_destination = Vec::new();
+    let mut delete_source = Vec::new();
+    let mut flagged = Vec::new();
+
+    for action in actions {
+        match action {
+            // A dry run lists these in its plan instead
+            SyncAction::CopyToDestination(path) => {
+                if !options.dry_run {
+                    status!("  {} {}", "→".green(), path);
+                }
+                to_destination.push(path);
+            }
+            SyncAction::CopyToSource(path) => {
+                if !options.dry_run {
+                    status!("  {} {}", "←".green(), path);
+                }
+                to_source.push(path);
+            }
+            SyncAction::DeleteFromDestination(path) => {
+                if !options.dry_run {
+                    status!("  {} {} (destination)", "×".red(), path);
+                }
+                delete_destination.push(path);
+            }
+            SyncAction::DeleteFromSource(path) => {
+                if !options.dry_run {
+                    status!("  {} {} (source)", "×".red(), path);
+                }
+                delete_source.push(path);
+            }
+            SyncAction::Conflict(conflict) => match choose_resolution(options, &conflict).await? {
+                Resolution::KeepSource => {
+                    let action = if options.dry_run {
+                        "would keep source"
+                    } else {
+                        "keeping source"
+                    };
+                    status!(
+                        "  {} {} (conflict, {})",
+                        "→".yellow(),
+                        conflict.path,
+                        action
+                    );
+                    to_destination.push(conflict.path);
+                }
+                Resolution::KeepDestination => {
+                    let action = if options.dry_run {
+                        "would keep destination"
+                    } else {
+                        "keeping destination"
+                    };
+                    status!(
+                        "  {} {} (conflict, {})",
+                        "←".yellow(),
+                        conflict.path,
+                        action
+                    );
+                    to_source.push(conflict.path);
+                }
+                Resolution::RenameDestination => {
+                    // Keep both versions on both sides
+                    let renamed = conflict_name(
+                        &conflict.path,
+                        time::OffsetDateTime::now_utc().unix_timestamp(),
+                    );
+                    let kept = if options.dry_run {
+                        "would be kept"
+                    } else {
+                        "kept"
+                    };
+                    status!(
+                        "  {} {} (conflict, destination copy {} as {})",
+                        "↻".yellow(),
+                        conflict.path,
+                        kept,
+                        renamed.cyan()
+                    );
+                    if !options.dry_run {
+                        move_single(
+                            azcopy,
+                            &join_location(destination, &conflict.path),
+                            &join_location(destination, &renamed),
+                        )
+                        .await?;
+                    }
+                    to_destination.push(conflict.path);
+                    to_source.push(renamed);
+                }
+                Resolution::Flag => {
+                    status!(
+                        "  {} {} (conflict, left as is)",
+                        "!".yellow(),
+                        conflict.path
+                    );
+                    flagged.push(conflict.path);
+                }
+            },
+        }
+    }
+
+    if options.dry_run {
+        let mut planned = planned_copies(source, destination, &to_destination, &source_entries);
+        planned.extend(planned_copies(
+            destination,
+            source,
+            &to_source,
+            &destination_entries,
+        ));
+        planned.extend(planned_deletions(
+            destination,
+            &delete_destination,
+            &destination_entries,
+        ));
+        planned.extend(planned_deletions(source, &delete_source, &source_entries));
+        DryRunPlan::new(planned).print();
+        warn_flagged(&flagged);
+        return Ok(());
+    }
+
+    copy_batch(azcopy, source, destination, &to_destination, options).await?;
+    copy_batch(azcopy, destination, source, &to_source, options).await?;
+    delete_batch(
+        azcopy,
+        destination,
+        &delete_destination,
+        &options.transfer_flags,
+    )
+    .await?;
+    delete_batch(azcopy, source, &delete_source, &options.transfer_flags).await?;
+
+    if options.preserve_empty_dirs {
+        sync_empty_dirs(source, destination).await?;
+        sync_empty_dirs(destination, source).await?;
+    }
+
+    save_state(options, state_path, &flagged).await?;
+
+    warn_flagged(&flagged);
+    status!("{} Sync completed successfully", "✓".green());
+    Ok(())
+}
+
+/// Mirror empty directories from one sync root to the other via marker blobs
+async fn sync_empty_dirs(from_root: &str, to_root: &str) -> Result<()> {
+    let (verb, count) = match (is_azure_uri(from_root), is_azure_uri(to_root)) {
+        (false, true) => (
+            "Stored markers for",
+            dir_markers::upload_markers(Path::new(from_root), to_root).await?,
+        ),
+        (true, false) => (
+            "Recreated",
+            dir_markers::restore_empty_dirs(from_root, Path::new(to_root)).await?,
+        ),
+        _ => return Ok(()),
+    };
+
+    if count > 0 {
+        status!("{} {} {} empty director(ies)", "ℹ".blue(), verb, count);
+    }
+    Ok(())
+}
+
+/// Copy the given relative paths from one sync root to another in a single transfer
+async fn copy_batch(
+    azcopy: &mut AzCopyClient,
+    from_root: &str,
+    to_root: &str,
+    paths: &[String],
+    options: &SyncOptions<'_>,
+) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    if !is_azure_uri(from_root) && !is_azure_uri(to_root) {
+        for path in paths {
+            let target = join_location(to_root, path);
+            if let Some(parent) = Path::new(&target).parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            copy_local_file(&join_location(from_root, path), &target)?;
+        }
+        return Ok(());
+    }
+
+    // Copy the contents of the root (not the root itself), limited to the given paths
+    let from = to_azcopy_location(&format!("{}/*", from_root.trim_end_matches('/')))?;
+    let is_download = is_azure_uri(from_root) && !is_azure_uri(to_root);
+    let is_upload = !is_azure_uri(from_root) && is_azure_uri(to_root);
+    // Checksum syncs compare against the blobs' Content-MD5, so uploads store it
+    let put_md5 = options.put_md5 || options.compare == CompareMode::Checksum;
+    let azcopy_options = AzCopyOptions::new()
+        .with_recursive(true)
+        .with_cap_mbps(options.cap_mbps)
+        .with_bandwidth_schedule(options.bandwidth_schedule.clone())
+        .with_preserve_properties(options.preserve)
+        .with_preserve_last_modified_time(options.preserve && is_download)
+        .with_put_md5(put_md5 && is_upload)
+        .with_tuned_concurrency(options.tuned_concurrency)
+        .with_transfer_flags(options.transfer_flags.clone());
+    let azcopy_options = if is_upload {
+        azcopy_options.with_symlinks(options.symlinks)
+    } else {
+        azcopy_options
+    };
+
+    let to = to_azcopy_location(to_root)?;
+    let (batches, singles) = include_path_batches(paths);
+    for batch in batches {
+        let batch_options = azcopy_options
+            .clone()
+            .with_include_path(Some(batch.join(";")));
+        azcopy.copy_with_options(&from, &to, &batch_options).await?;
+    }
+    for path in singles {
+        azcopy
+            .copy_with_options(
+                &to_azcopy_location(&join_location(from_root, path))?,
+                &to_azcopy_location(&join_location(to_root, path))?,
+                &azcopy_options.clone().with_recursive(false),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Delete the given relative paths under a sync root, tuning azcopy with
+/// `transfer_flags`
+pub async fn delete_batch(
+    azcopy: &mut AzCopyClient,
+    root: &str,
+    paths: &[String],
+    transfer_flags: &TransferConfig,
+) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    if !is_azure_uri(root) {
+        for path in paths {
+            std::fs::remove_file(join_location(root, path))?;
+        }
+        audit_log::record(paths.len() as u64, 0);
+        return Ok(());
+    }
+
+    let url = convert_az_uri_to_url(root)?;
+    let (batches, singles) = include_path_batches(paths);
+    for batch in batches {
+        let options = AzCopyOptions::new()
+            .with_recursive(true)
+            .with_include_path(Some(batch.join(";")))
+            .with_transfer_flags(transfer_flags.clone());
+        azcopy.remove_with_options(&url, &options).await?;
+    }
+    for path in singles {
+        azcopy
+            .remove_with_options(
+                &convert_az_uri_to_url(&join_location(root, path))?,
+                &AzCopyOptions::new(),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// `paths` in `--include-path` batches that fit on a command line, and the
+/// paths azcopy would split at their ';', which are transferred one by one
+fn include_path_batches(paths: &[String]) -> (Vec<Vec<String>>, Vec<&String>) {
+    let (singles, listable): (Vec<&String>, Vec<&String>) =
+        paths.iter().partition(|path| path.contains(';'));
+    let listable: Vec<String> = listable.into_iter().cloned().collect();
+    (batches(&listable), singles)
+}
+
+/// Ask the user how to resolve a single conflict
+async fn prompt_resolution(prompter: &dyn Prompter, conflict: &Conflict) -> Result<Resolution> {
+    prompter.check(CONFLICT_BYPASS)?;
+    println!(
+        "{} {} changed on both sides (source: {} bytes @ {}, destination: {} bytes @ {})",
+        "?".yellow(),
+        conflict.path.cyan(),
+        conflict.source.size,
+        conflict.source.modified,
+        conflict.destination.size,
+        conflict.destination.modified
+    );
+
+    loop {
+        let input = prompter
+            .ask(
+                "Keep [s]ource, [d]estination, [r]ename destination, or [l]eave both? ",
+                CONFLICT_BYPASS,
+            )
+            .await?;
+
+        match input.trim().to_lowercase().as_str() {
+            "s" | "source" => return Ok(Resolution::KeepSource),
+            "d" | "destination" => return Ok(Resolution::KeepDestination),
+            "r" | "rename" => return Ok(Resolution::RenameDestination),
+            "l" | "leave" => return Ok(Resolution::Flag),
+            _ => println!("Please answer 's', 'd', 'r', or 'l'"),
+        }
+    }
+}
+
+/// Convert a location to something AzCopy understands
+pub fn to_azcopy_location(location: &str) -> Result<String> {
+    if is_azure_uri(location) {
+        Ok(convert_az_uri_to_url(location)?)
+    } else {
+        Ok(location.to_string())
+    }
+}
+
+/// Copy a single file or blob, overwriting the destination
+async fn copy_single(azcopy: &mut AzCopyClient, source: &str, destination: &str) -> Result<()> {
+    if !is_azure_uri(source) && !is_azure_uri(destination) {
+        return copy_local_file(source, destination);
+    }
+
+    azcopy
+        .copy_with_options(
+            &to_azcopy_location(source)?,
+            &to_azcopy_location(destination)?,
+            &AzCopyOptions::new(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Copy a local file, keeping its modification time so the next mtime
+/// comparison sees both sides as equal
+fn copy_local_file(source: &str, destination: &str) -> Result<()> {
+    let bytes = std::fs::copy(source, destination)
+        .with_context(|| format!("Failed to copy '{}' to '{}'", source, destination))?;
+    audit_log::record(1, bytes);
+    let modified = std::fs::metadata(source)?.modified()?;
+    std::fs::File::options()
+        .write(true)
+        .open(destination)?
+        .set_modified(modified)?;
+    Ok(())
+}
+
+/// Move a single file or blob to a new name on the same side
+async fn move_single(azcopy: &mut AzCopyClient, from: &str, to: &str) -> Result<()> {
+    if !is_azure_uri(from) {
+        std::fs::rename(from, to)?;
+        audit_log::record(1, 0);
+        return Ok(());
+    }
+
+    copy_single(azcopy, from, to).await?;
+    azcopy
+        .remove_with_options(&convert_az_uri_to_url(from)?, &AzCopyOptions::new())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::ScriptedPrompter;
+    use crate::sync_state::EntryState;
+
+    #[tokio::test]
+    async fn test_prompt_resolution_asks_until_answered() {
+        let state = |size| EntryState {
+            size,
+            modified: 0,
+            md5: None,
+        };
+        let conflict = Conflict {
+            path: "notes.txt".to_string(),
+            source: state(1),
+            destination: state(2),
+        };
+        let prompter = ScriptedPrompter::new(["maybe", "R"]);
+        assert_eq!(
+            prompt_resolution(&prompter, &conflict).await.unwrap(),
+            Resolution::RenameDestination
+        );
+        assert_eq!(prompter.asked().len(), 2);
+        assert!(prompt_resolution(&prompter, &conflict).await.is_err());
+    }
+
+    #[test]
+    fn test_include_path_batches() {
+        let mut paths: Vec<String> = (0..2500).map(|i| format!("dir/file{}.csv", i)).collect();
+        paths.push("notes;draft.txt".to_string());
+        let (batches, singles) = include_path_batches(&paths);
+        assert!(batches.len() > 1);
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), 2500);
+        assert!(batches.iter().flatten().all(|path| !path.contains(';')));
+        assert_eq!(singles, vec!["notes;draft.txt"]);
+    }
+}
@@ -0,0 +1,511 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, DurationRound, NaiveDateTime, TimeDelta, Utc};
+use colored::*;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+
+use super::ls::{diff_listing, list_etags, Change, Etags};
+use crate::azure::{AzureClient, BlobItem};
+use crate::cancel;
+use crate::output::{output_format, OutputFormat};
+use crate::utils::{parse_azure_uri, Glob};
+
+// ============================================================================
+// Watch - Created, modified and deleted blobs as they happen
+// ============================================================================
+
+/// Container the service writes the Blob Change Feed to
+const CHANGE_FEED_CONTAINER: &str = "$blobchangefeed";
+
+/// What happened to a blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl EventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Created => "created",
+            EventKind::Modified => "modified",
+            EventKind::Deleted => "deleted",
+        }
+    }
+
+    /// The kind of a change feed event, by its `eventType`
+    fn from_event_type(event_type: &str) -> Option<Self> {
+        match event_type {
+            "BlobCreated" => Some(EventKind::Created),
+            "BlobDeleted" => Some(EventKind::Deleted),
+            "BlobPropertiesUpdated" | "BlobSnapshotCreated" | "BlobTierChanged" => {
+                Some(EventKind::Modified)
+            }
+            // Control events and asynchronous operations still in progress
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    kind: EventKind,
+    /// Name of the blob within its container
+    name: String,
+    time: DateTime<Utc>,
+}
+
+/// The blobs a watch covers: those under a prefix, optionally matching a
+/// wildcard pattern below it
+struct Watched {
+    account: String,
+    container: String,
+    prefix: Option<String>,
+}
+
+impl Watched {
+    fn contains(&self, name: &str) -> bool {
+        let Some(prefix) = self.prefix.as_deref() else {
+            return true;
+        };
+        // Changes inside a matching directory count, as when polling
+        match Glob::new(prefix) {
+            Ok(Some(glob)) => glob.selects(name, true),
+            Ok(None) => name.starts_with(prefix),
+            Err(_) => false,
+        }
+    }
+
+    fn uri(&self, name: &str) -> String {
+        format!("az://{}/{}/{}", self.account, self.container, name)
+    }
+}
+
+/// Watch by listing the prefix again every interval and comparing ETags
+struct Poller {
+    etags: Etags,
+}
+
+impl Poller {
+    async fn start(client: &mut AzureClient, watched: &Watched) -> Result<Self> {
+        let etags = list_etags(client, &watched.container, watched.prefix.as_deref()).await?;
+        Ok(Poller { etags })
+    }
+
+    async fn poll(&mut self, client: &mut AzureClient, watched: &Watched) -> Result<Vec<Event>> {
+        let current = list_etags(client, &watched.container, watched.prefix.as_deref()).await?;
+        let time = Utc::now();
+        let events = diff_listing(&self.etags, &current)
+            .into_iter()
+            .map(|change| {
+                let (kind, name) = match change {
+                    Change::Added(name) => (EventKind::Created, name),
+                    Change::Modified(name) => (EventKind::Modified, name),
+                    Change::Deleted(name) => (EventKind::Deleted, name),
+                };
+                Event {
+                    kind,
+                    name: name.to_string(),
+                    time,
+                }
+            })
+            .collect();
+        self.etags = current;
+        Ok(events)
+    }
+}
+
+/// `meta/segments.json` of the change feed
+#[derive(Debug, Deserialize)]
+struct FeedMeta {
+    /// Begin time of the last segment readers may consume
+    #[serde(rename = "lastConsumable")]
+    last_consumable: DateTime<Utc>,
+}
+
+/// The manifest of an hour's segment of the change feed
+#[derive(Debug, Deserialize)]
+struct SegmentManifest {
+    /// One prefix per shard, each holding Avro chunk files
+    #[serde(rename = "chunkFilePaths")]
+    chunk_file_paths: Vec<String>,
+}
+
+/// The fields of a change feed record that a watch reports
+#[derive(Debug, Deserialize)]
+struct FeedRecord {
+    /// `/blobServices/default/containers/<container>/blobs/<name>`
+    subject: String,
+    #[serde(rename = "eventType")]
+    event_type: String,
+    #[serde(rename = "eventTime")]
+    event_time: String,
+}
+
+/// Watch by reading the account's Blob Change Feed
+///
+/// The service publishes the feed in hourly segments, and only segments up to
+/// `lastConsumable` are complete, so events arrive up to an hour late. The
+/// watched container itself is never listed, though: watching a prefix of a
+/// huge container costs the same as watching an empty one.
+struct ChangeFeed {
+    /// Events before this are not reported
+    since: DateTime<Utc>,
+    /// Begin time of the next segment to read
+    next_segment: DateTime<Utc>,
+}
+
+impl ChangeFeed {
+    async fn start(client: &mut AzureClient) -> Result<Self> {
+        let enabled = client
+            .list_containers()
+            .await?
+            .iter()
+            .any(|container| container.name == CHANGE_FEED_CONTAINER);
+        if !enabled {
+            return Err(anyhow!(
+                "The change feed isn't enabled on this storage account; watch without --change-feed to poll instead"
+            ));
+        }
+        let since = Utc::now();
+        Ok(ChangeFeed {
+            since,
+            // The segment holding events from `since` began on the hour
+            next_segment: since.duration_trunc(TimeDelta::hours(1))?,
+        })
+    }
+
+    async fn poll(&mut self, client: &mut AzureClient, watched: &Watched) -> Result<Vec<Event>> {
+        let meta: FeedMeta = serde_json::from_slice(
+            &client
+                .download_blob(CHANGE_FEED_CONTAINER, "meta/segments.json", None)
+                .await?,
+        )
+        .context("The change feed's meta/segments.json is not valid")?;
+        if meta.last_consumable < self.next_segment {
+            return Ok(Vec::new());
+        }
+
+        let mut segments = Vec::new();
+        for year in self.next_segment.year()..=meta.last_consumable.year() {
+            let prefix = format!("idx/segments/{}/", year);
+            for item in client
+                .list_blobs(CHANGE_FEED_CONTAINER, Some(&prefix), None)
+                .await?
+            {
+                if let BlobItem::Blob(blob) = item {
+                    if let Some(begin) = segment_time(&blob.name) {
+                        if begin >= self.next_segment && begin <= meta.last_consumable {
+                            segments.push((begin, blob.name));
+                        }
+                    }
+                }
+            }
+        }
+        segments.sort();
+
+        let mut events = Vec::new();
+        for (_, manifest) in &segments {
+            let manifest: SegmentManifest = serde_json::from_slice(
+                &client
+                    .download_blob(CHANGE_FEED_CONTAINER, manifest, None)
+                    .await?,
+            )
+            .with_context(|| format!("Change feed segment {} is not valid", manifest))?;
+            for shard in &manifest.chunk_file_paths {
+                let shard = shard
+                    .strip_prefix(CHANGE_FEED_CONTAINER)
+                    .unwrap_or(shard)
+                    .trim_start_matches('/');
+                let mut chunks: Vec<String> = client
+                    .list_blobs(CHANGE_FEED_CONTAINER, Some(shard), None)
+                    .await?
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        BlobItem::Blob(blob) => Some(blob.name),
+                        BlobItem::Prefix(_) => None,
+                    })
+                    .collect();
+                chunks.sort();
+                for chunk in chunks {
+                    let content = client
+                        .download_blob(CHANGE_FEED_CONTAINER, &chunk, None)
+                        .await?;
+                    events.extend(
+                        feed_events(&content, watched)
+                            .with_context(|| format!("Failed to read change feed chunk {}", chunk))?
+                            .into_iter()
+                            .filter(|event| event.time >= self.since),
+                    );
+                }
+            }
+        }
+        if let Some((begin, _)) = segments.last() {
+            self.next_segment = *begin + TimeDelta::seconds(1);
+        }
+        events.sort_by_key(|event| event.time);
+        Ok(events)
+    }
+}
+
+/// Begin time of a segment from its manifest's name,
+/// `idx/segments/YYYY/MM/DD/HHMM/meta.json`
+fn segment_time(name: &str) -> Option<DateTime<Utc>> {
+    let time = name
+        .strip_prefix("idx/segments/")?
+        .strip_suffix("/meta.json")?;
+    NaiveDateTime::parse_from_str(time, "%Y/%m/%d/%H%M")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Events for watched blobs in an Avro chunk of the change feed
+fn feed_events(content: &[u8], watched: &Watched) -> Result<Vec<Event>> {
+    let subject_prefix = format!(
+        "/blobServices/default/containers/{}/blobs/",
+        watched.container
+    );
+    let mut events = Vec::new();
+    for value in apache_avro::Reader::new(content)? {
+        let record: FeedRecord = apache_avro::from_value(&value?)?;
+        let Some(name) = record.subject.strip_prefix(&subject_prefix) else {
+            continue;
+        };
+        let Some(kind) = EventKind::from_event_type(&record.event_type) else {
+            continue;
+        };
+        if !watched.contains(name) {
+            continue;
+        }
+        let time = DateTime::parse_from_rfc3339(&record.event_time)
+            .with_context(|| format!("Invalid event time '{}'", record.event_time))?;
+        events.push(Event {
+            kind,
+            name: name.to_string(),
+            time: time.with_timezone(&Utc),
+        });
+    }
+    Ok(events)
+}
+
+enum Source {
+    Poller(Poller),
+    ChangeFeed(ChangeFeed),
+}
+
+/// Print an event, then run `exec` for it with AZST_EVENT and AZST_URI set
+async fn report(event: &Event, watched: &Watched, exec: Option<&str>) -> Result<()> {
+    let uri = watched.uri(&event.name);
+    match output_format() {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "event": event.kind.as_str(),
+                "uri": uri,
+                "time": event.time.to_rfc3339(),
+            })
+        ),
+        _ => println!("{}\t{}", event.kind.as_str(), uri),
+    }
+
+    if let Some(exec) = exec {
+        let mut command = if cfg!(windows) {
+            let mut command = AsyncCommand::new("cmd");
+            command.arg("/C");
+            command
+        } else {
+            let mut command = AsyncCommand::new("sh");
+            command.arg("-c");
+            command
+        };
+        let status = command
+            .arg(exec)
+            .env("AZST_EVENT", event.kind.as_str())
+            .env("AZST_URI", &uri)
+            .status()
+            .await
+            .with_context(|| format!("Failed to run '{}'", exec))?;
+        // One failed handler doesn't stop the watch
+        if !status.success() {
+            eprintln!("{} '{}' failed for {}: {}", "⚠".yellow(), exec, uri, status);
+        }
+    }
+    Ok(())
+}
+
+/// Report blobs created, modified or deleted under `uri` until Ctrl-C,
+/// checking every `interval`
+///
+/// Polling lists the prefix each time; `change_feed` reads the account's
+/// Blob Change Feed instead. `exec` is run through the shell for each event.
+pub async fn execute(
+    uri: &str,
+    interval: Duration,
+    change_feed: bool,
+    exec: Option<&str>,
+) -> Result<()> {
+    if interval.is_zero() {
+        return Err(anyhow!("--interval must be at least a second"));
+    }
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    if container.is_empty() {
+        return Err(anyhow!(
+            "watch needs a container: az://<account>/<container>/[prefix]"
+        ));
+    }
+    let mut client = AzureClient::new();
+    if let Some(account) = &account {
+        client = client.with_storage_account(account);
+    }
+    client.check_prerequisites().await?;
+    let watched = Watched {
+        account: client
+            .get_storage_account()
+            .ok_or_else(|| anyhow!("Storage account not configured"))?
+            .to_string(),
+        container,
+        prefix,
+    };
+
+    let mut source = if change_feed {
+        Source::ChangeFeed(ChangeFeed::start(&mut client).await?)
+    } else {
+        Source::Poller(Poller::start(&mut client, &watched).await?)
+    };
+    // Events go to stdout, so status lines go to stderr
+    eprintln!(
+        "{} Watching {} every {}s (Ctrl-C to stop)",
+        "→".green(),
+        uri.cyan(),
+        interval.as_secs()
+    );
+
+    let cancel = cancel::token();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+            _ = cancel.cancelled() => break,
+        }
+        let events = match &mut source {
+            Source::Poller(poller) => poller.poll(&mut client, &watched).await,
+            Source::ChangeFeed(feed) => feed.poll(&mut client, &watched).await,
+        };
+        match events {
+            Ok(events) => {
+                for event in &events {
+                    report(event, &watched, exec).await?;
+                }
+            }
+            // A failed check is tried again on the next one
+            Err(e) => eprintln!("{} Failed to check for changes: {:#}", "⚠".yellow(), e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apache_avro::types::Record;
+    use apache_avro::{Schema, Writer};
+
+    fn watched(prefix: Option<&str>) -> Watched {
+        Watched {
+            account: "acct".to_string(),
+            container: "data".to_string(),
+            prefix: prefix.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_watched_prefix_and_pattern() {
+        assert!(watched(None).contains("anything"));
+        assert!(watched(Some("inbox/")).contains("inbox/a.csv"));
+        assert!(!watched(Some("inbox/")).contains("outbox/a.csv"));
+        let csv = watched(Some("inbox/*.csv"));
+        assert!(csv.contains("inbox/a.csv"));
+        assert!(!csv.contains("inbox/a.json"));
+        assert!(!csv.contains("inbox/old/a.csv"));
+        assert!(watched(Some("inbox/**/*.csv")).contains("inbox/old/a.csv"));
+        assert_eq!(csv.uri("inbox/a.csv"), "az://acct/data/inbox/a.csv");
+    }
+
+    #[test]
+    fn test_segment_time_from_manifest_name() {
+        assert_eq!(
+            segment_time("idx/segments/2024/03/05/1400/meta.json"),
+            Some(
+                NaiveDateTime::parse_from_str("2024-03-05 14:00", "%Y-%m-%d %H:%M")
+                    .unwrap()
+                    .and_utc()
+            )
+        );
+        assert_eq!(
+            segment_time("idx/segments/1601/01/01/0000/other.json"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_feed_events_from_avro_chunk() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "BlobChangeEvent", "fields": [
+                {"name": "schemaVersion", "type": "int"},
+                {"name": "topic", "type": "string"},
+                {"name": "subject", "type": "string"},
+                {"name": "eventType", "type": "string"},
+                {"name": "eventTime", "type": "string"}
+            ]}"#,
+        )
+        .unwrap();
+        let mut writer = Writer::new(&schema, Vec::new());
+        for (subject, event_type) in [
+            (
+                "/blobServices/default/containers/data/blobs/inbox/a.csv",
+                "BlobCreated",
+            ),
+            (
+                "/blobServices/default/containers/data/blobs/inbox/b.csv",
+                "BlobDeleted",
+            ),
+            (
+                "/blobServices/default/containers/data/blobs/other/c.csv",
+                "BlobCreated",
+            ),
+            (
+                "/blobServices/default/containers/logs/blobs/inbox/d.csv",
+                "BlobCreated",
+            ),
+            ("", "RestorePointMarkerCreated"),
+        ] {
+            let mut record = Record::new(&schema).unwrap();
+            record.put("schemaVersion", 3);
+            record.put("topic", "/subscriptions/x/storageAccounts/acct");
+            record.put("subject", subject);
+            record.put("eventType", event_type);
+            record.put("eventTime", "2024-03-05T14:12:01.1234567Z");
+            writer.append(record).unwrap();
+        }
+        let content = writer.into_inner().unwrap();
+
+        let events = feed_events(&content, &watched(Some("inbox/"))).unwrap();
+        let summary: Vec<(EventKind, &str)> = events
+            .iter()
+            .map(|event| (event.kind, event.name.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (EventKind::Created, "inbox/a.csv"),
+                (EventKind::Deleted, "inbox/b.csv")
+            ]
+        );
+        assert_eq!(
+            events[0].time.to_rfc3339(),
+            "2024-03-05T14:12:01.123456700+00:00"
+        );
+    }
+}
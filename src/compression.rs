@@ -0,0 +1,379 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// ============================================================================
+// Transparent Compression - Files compressed on upload, restored on download
+// ============================================================================
+
+/// Metadata key recording which algorithm azst compressed a blob with
+pub const COMPRESSION_METADATA_KEY: &str = "azst_compression";
+
+/// Compression level for zstd; favours speed, since uploads are usually
+/// network-bound
+const ZSTD_LEVEL: i32 = 3;
+
+/// Algorithm used to compress files on upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    /// Zstandard: fast, with a better ratio than gzip
+    Zstd,
+    /// gzip: decoded natively by browsers and most HTTP clients
+    Gzip,
+}
+
+impl Compression {
+    /// Value stored in the blob's Content-Encoding header
+    pub fn content_encoding(&self) -> &'static str {
+        match self {
+            Compression::Zstd => "zstd",
+            Compression::Gzip => "gzip",
+        }
+    }
+
+    /// The algorithm behind a Content-Encoding header, if azst can decode it
+    pub fn from_content_encoding(encoding: &str) -> Option<Self> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "zstd" => Some(Compression::Zstd),
+            "gzip" | "x-gzip" => Some(Compression::Gzip),
+            _ => None,
+        }
+    }
+
+    /// The algorithm a file name's extension suggests (.gz or .zst)
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "gz" | "gzip" => Some(Compression::Gzip),
+            _ => None,
+        }
+    }
+
+    fn compress(&self, source: &Path, destination: &Path) -> Result<()> {
+        let mut input = BufReader::new(File::open(source)?);
+        let output = BufWriter::new(File::create(destination)?);
+        match self {
+            Compression::Zstd => zstd::stream::copy_encode(&mut input, output, ZSTD_LEVEL)?,
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(output, flate2::Compression::default());
+                std::io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decompress(&self, source: &Path, destination: &Path) -> Result<()> {
+        let input = BufReader::new(File::open(source)?);
+        let mut output = BufWriter::new(File::create(destination)?);
+        match self {
+            Compression::Zstd => zstd::stream::copy_decode(input, &mut output)?,
+            Compression::Gzip => {
+                std::io::copy(&mut flate2::read::GzDecoder::new(input), &mut output)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A writer that decompresses what is written to it into `W`, for
+/// streaming compressed blobs as they download
+pub enum Decoder<W: Write> {
+    Zstd(zstd::stream::write::Decoder<'static, W>),
+    Gzip(flate2::write::GzDecoder<W>),
+}
+
+impl<W: Write> Decoder<W> {
+    pub fn new(compression: Compression, inner: W) -> Result<Self> {
+        Ok(match compression {
+            Compression::Zstd => Decoder::Zstd(zstd::stream::write::Decoder::new(inner)?),
+            Compression::Gzip => Decoder::Gzip(flate2::write::GzDecoder::new(inner)),
+        })
+    }
+
+    /// Flush the remaining output, failing if the stream was cut short
+    pub fn finish(self) -> Result<W> {
+        Ok(match self {
+            Decoder::Zstd(mut decoder) => {
+                decoder.flush()?;
+                decoder.into_inner()
+            }
+            Decoder::Gzip(decoder) => decoder.finish()?,
+        })
+    }
+}
+
+impl<W: Write> Write for Decoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Decoder::Zstd(decoder) => decoder.write(buf),
+            Decoder::Gzip(decoder) => decoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Decoder::Zstd(decoder) => decoder.flush(),
+            Decoder::Gzip(decoder) => decoder.flush(),
+        }
+    }
+}
+
+/// A writer that compresses what is written to it into `W`, for streaming
+/// archives as they are built
+pub enum Encoder<W: Write> {
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Gzip(flate2::write::GzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(compression: Compression, inner: W) -> Result<Self> {
+        Ok(match compression {
+            Compression::Zstd => {
+                Encoder::Zstd(zstd::stream::write::Encoder::new(inner, ZSTD_LEVEL)?)
+            }
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                inner,
+                flate2::Compression::default(),
+            )),
+        })
+    }
+
+    /// Write the end of the compressed stream
+    pub fn finish(self) -> Result<W> {
+        Ok(match self {
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+        })
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::Zstd(encoder) => encoder.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Compressed copies of upload sources in a temporary directory, removed
+/// when dropped
+pub struct CompressedCopy {
+    dir: PathBuf,
+    /// Compressed counterpart of the source, with the same file name so
+    /// azcopy names the uploaded blobs the same way
+    pub root: PathBuf,
+}
+
+/// Whether a file's extension is one of `extensions`, given without dots
+/// and matched in any case
+pub fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extensions.iter().any(|wanted| {
+                wanted
+                    .trim_start_matches('.')
+                    .eq_ignore_ascii_case(extension)
+            })
+        })
+}
+
+impl CompressedCopy {
+    /// Compress a file, or every file under a directory, keeping names and layout
+    pub fn create(source: &Path, compression: Compression) -> Result<Self> {
+        Self::create_selected(source, compression, None)
+    }
+
+    /// Like `create`, but only compress files with one of `extensions`
+    /// (see `has_extension`) and stage the others unchanged
+    pub fn create_selected(
+        source: &Path,
+        compression: Compression,
+        extensions: Option<&[String]>,
+    ) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("azst-compress-{}", std::process::id()));
+        let name = source
+            .file_name()
+            .map(|name| name.to_os_string())
+            .unwrap_or_else(|| "upload".into());
+        let root = dir.join(name);
+        let copy = Self { dir, root };
+
+        if source.is_dir() {
+            let mut stack = vec![source.to_path_buf()];
+            while let Some(current) = stack.pop() {
+                let target_dir = copy.root.join(current.strip_prefix(source)?);
+                std::fs::create_dir_all(&target_dir)?;
+                for entry in std::fs::read_dir(&current)? {
+                    let path = entry?.path();
+                    let target = target_dir.join(path.file_name().unwrap_or_default());
+                    if path.is_dir() {
+                        stack.push(path);
+                    } else if extensions.is_none_or(|extensions| has_extension(&path, extensions)) {
+                        compression
+                            .compress(&path, &target)
+                            .with_context(|| format!("Failed to compress {}", path.display()))?;
+                    } else if std::fs::hard_link(&path, &target).is_err() {
+                        std::fs::copy(&path, &target)
+                            .with_context(|| format!("Failed to stage {}", path.display()))?;
+                    }
+                }
+            }
+        } else {
+            std::fs::create_dir_all(&copy.dir)?;
+            compression
+                .compress(source, &copy.root)
+                .with_context(|| format!("Failed to compress {}", source.display()))?;
+        }
+
+        Ok(copy)
+    }
+}
+
+impl Drop for CompressedCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Replace a downloaded file with its decompressed content
+pub fn decompress_in_place(path: &Path, compression: Compression) -> Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".azst-decompress");
+    let partial = PathBuf::from(partial);
+
+    if let Err(e) = compression.decompress(path, &partial) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e).with_context(|| format!("Failed to decompress {}", path.display()));
+    }
+    std::fs::rename(&partial, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_encoding_round_trip() {
+        for compression in [Compression::Zstd, Compression::Gzip] {
+            assert_eq!(
+                Compression::from_content_encoding(compression.content_encoding()),
+                Some(compression)
+            );
+        }
+        assert_eq!(
+            Compression::from_content_encoding("GZIP"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::from_content_encoding("br"), None);
+    }
+
+    #[test]
+    fn test_compress_and_decompress_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("dataset");
+        std::fs::create_dir_all(source.join("nested")).unwrap();
+        let content = "a,b,c\n".repeat(1000);
+        std::fs::write(source.join("table.csv"), &content).unwrap();
+        std::fs::write(source.join("nested/more.csv"), &content).unwrap();
+
+        for compression in [Compression::Zstd, Compression::Gzip] {
+            let copy = CompressedCopy::create(&source, compression).unwrap();
+            assert_eq!(copy.root.file_name().unwrap(), "dataset");
+
+            let compressed = copy.root.join("nested/more.csv");
+            assert!(std::fs::metadata(&compressed).unwrap().len() < content.len() as u64);
+
+            decompress_in_place(&compressed, compression).unwrap();
+            assert_eq!(std::fs::read_to_string(&compressed).unwrap(), content);
+
+            let dir = copy.dir.clone();
+            drop(copy);
+            assert!(!dir.exists());
+        }
+    }
+
+    #[test]
+    fn test_compress_selected_extensions() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("site");
+        std::fs::create_dir_all(&source).unwrap();
+        let content = "<p>hello</p>\n".repeat(1000);
+        std::fs::write(source.join("index.HTML"), &content).unwrap();
+        std::fs::write(source.join("logo.png"), &content).unwrap();
+
+        let extensions = vec!["html".to_string(), ".css".to_string()];
+        let copy =
+            CompressedCopy::create_selected(&source, Compression::Gzip, Some(&extensions)).unwrap();
+        assert!(
+            std::fs::metadata(copy.root.join("index.HTML"))
+                .unwrap()
+                .len()
+                < content.len() as u64
+        );
+        assert_eq!(
+            std::fs::read_to_string(copy.root.join("logo.png")).unwrap(),
+            content
+        );
+
+        assert!(has_extension(Path::new("a/style.css"), &extensions));
+        assert!(!has_extension(Path::new("a/html"), &extensions));
+    }
+
+    #[test]
+    fn test_streaming_decoder() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("events.json");
+        let content = "{\"event\": \"click\"}\n".repeat(1000);
+        std::fs::write(&source, &content).unwrap();
+
+        for compression in [Compression::Zstd, Compression::Gzip] {
+            let compressed = temp.path().join("events.json.compressed");
+            compression.compress(&source, &compressed).unwrap();
+            let compressed = std::fs::read(&compressed).unwrap();
+
+            // Written in small pieces, as blocks arrive
+            let mut decoder = Decoder::new(compression, Vec::new()).unwrap();
+            for piece in compressed.chunks(100) {
+                decoder.write_all(piece).unwrap();
+            }
+            assert_eq!(
+                String::from_utf8(decoder.finish().unwrap()).unwrap(),
+                content
+            );
+        }
+
+        assert_eq!(
+            Compression::from_extension(Path::new("logs/app.json.GZ")),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("dump.zst")),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_extension(Path::new("data.tar")), None);
+    }
+
+    #[test]
+    fn test_decompress_invalid_data_keeps_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("plain.txt");
+        std::fs::write(&path, "not compressed").unwrap();
+
+        assert!(decompress_in_place(&path, Compression::Zstd).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not compressed");
+        assert!(!temp.path().join("plain.txt.azst-decompress").exists());
+    }
+}
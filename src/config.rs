@@ -0,0 +1,641 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::utils::{expand_account_shorthand, parse_size};
+
+// ============================================================================
+// User Configuration - Loaded from <config dir>/azst/config.toml
+// ============================================================================
+
+/// Settings read from the user's azst config file
+///
+/// ```toml
+/// default_account = "myaccount"
+///
+/// [content_types]
+/// wasm = "application/wasm"
+/// "cog.tif" = "image/tiff; application=geotiff; profile=cloud-optimized"
+///
+/// [quotas]
+/// "az://myaccount" = "5T"
+/// "az://myaccount/scratch" = "500G"
+///
+/// [retry]
+/// max_retries = 5
+/// initial_delay_ms = 500
+///
+/// [audit]
+/// log = "~/.local/state/azst/audit.jsonl"
+///
+/// [rm]
+/// confirm_above = 1000
+/// max_deletes = 100000
+///
+/// [transfer]
+/// concurrency = 64
+///
+/// [readahead]
+/// block_size = "8M"
+/// blocks = 8
+///
+/// [profiles.fast.transfer]
+/// concurrency = 512
+/// buffer_gb = 4
+///
+/// [aliases]
+/// curated = "az://prodaccount/datasets/curated/"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Account for `az:container/path` and legacy `az://container/path`
+    /// URIs, as chosen with `azst account set`
+    pub default_account: Option<String>,
+    /// Content types by file extension, overriding the built-in table
+    pub content_types: HashMap<String, String>,
+    /// Maximum bytes stored under an account, container or prefix
+    #[serde(deserialize_with = "deserialize_quotas")]
+    pub quotas: HashMap<String, u64>,
+    /// Backoff for Azure SDK requests; unset fields keep the SDK defaults
+    pub retry: RetryConfig,
+    pub audit: AuditConfig,
+    pub rm: RmConfig,
+    /// azcopy tuning for every transfer
+    pub transfer: TransferConfig,
+    /// Block cache of the reads behind cat, mount, serve and --untar
+    pub readahead: ReadaheadSettings,
+    /// Named sets of settings, picked with --profile or AZST_PROFILE
+    pub profiles: HashMap<String, Profile>,
+    /// Short names for locations: `data = "az://acct/datasets/"` makes
+    /// `data://2024/` mean `az://acct/datasets/2024/`
+    pub aliases: HashMap<String, String>,
+}
+
+/// A `[profiles.<name>]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Tuning that replaces the `[transfer]` values it sets
+    pub transfer: TransferConfig,
+}
+
+/// The `[transfer]` section: azcopy tuning, as also set by --concurrency,
+/// --buffer-gb and --scan-concurrency
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TransferConfig {
+    /// Requests azcopy keeps in flight
+    pub concurrency: Option<u32>,
+    /// Memory azcopy may use to buffer data, in GB
+    pub buffer_gb: Option<f64>,
+    /// Directories azcopy lists at once while scanning
+    pub scan_concurrency: Option<u32>,
+}
+
+impl TransferConfig {
+    /// Values set here, falling back to those of `other`
+    pub fn or(self, other: TransferConfig) -> TransferConfig {
+        TransferConfig {
+            concurrency: self.concurrency.or(other.concurrency),
+            buffer_gb: self.buffer_gb.or(other.buffer_gb),
+            scan_concurrency: self.scan_concurrency.or(other.scan_concurrency),
+        }
+    }
+
+    /// The azcopy environment variables these settings stand for
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = Vec::new();
+        if let Some(concurrency) = self.concurrency {
+            vars.push(("AZCOPY_CONCURRENCY_VALUE", concurrency.to_string()));
+        }
+        if let Some(buffer_gb) = self.buffer_gb {
+            vars.push(("AZCOPY_BUFFER_GB", buffer_gb.to_string()));
+        }
+        if let Some(scan_concurrency) = self.scan_concurrency {
+            vars.push(("AZCOPY_CONCURRENT_SCAN", scan_concurrency.to_string()));
+        }
+        vars
+    }
+}
+
+/// The `[readahead]` section: how blobs read in blocks are fetched and
+/// cached, as also set by mount's --block-size, --cache-size and --readahead
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ReadaheadSettings {
+    /// Size of the ranged reads blobs are fetched in (default: 4M)
+    #[serde(deserialize_with = "deserialize_size")]
+    pub block_size: Option<u64>,
+    /// Blocks fetched ahead of a sequential reader (default: 4)
+    pub blocks: Option<u64>,
+    /// Memory for cached blocks of each open blob (default: 128M)
+    #[serde(deserialize_with = "deserialize_size")]
+    pub cache_size: Option<u64>,
+}
+
+impl ReadaheadSettings {
+    /// Values set here, falling back to those of `other`
+    pub fn or(self, other: ReadaheadSettings) -> ReadaheadSettings {
+        ReadaheadSettings {
+            block_size: self.block_size.or(other.block_size),
+            blocks: self.blocks.or(other.blocks),
+            cache_size: self.cache_size.or(other.cache_size),
+        }
+    }
+}
+
+/// Profile picked with --profile or AZST_PROFILE
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Use the settings of a `[profiles.<name>]` section for the rest of the run
+pub fn set_profile(name: &str) {
+    let _ = PROFILE.set(name.to_string());
+}
+
+pub fn profile() -> Option<&'static str> {
+    PROFILE.get().map(String::as_str)
+}
+
+/// The account `azst account set` chose, if any
+pub fn default_account() -> Option<String> {
+    Config::load()
+        .ok()
+        .and_then(|config| config.default_account)
+}
+
+/// The `[audit]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// JSONL file every command that changes files or blobs is appended to
+    pub log: Option<PathBuf>,
+}
+
+/// The `[rm]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RmConfig {
+    /// Removals of more blobs than this ask for the container name rather
+    /// than y/N (default: 1000)
+    pub confirm_above: Option<u64>,
+    /// Refuse removals of more blobs than this; --max-deletes overrides it
+    pub max_deletes: Option<u64>,
+}
+
+/// The `[retry]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Retries after the first attempt (SDK default: 8)
+    pub max_retries: Option<u32>,
+    /// Delay before the first retry, doubled for each one after (default: 200)
+    pub initial_delay_ms: Option<u64>,
+    /// Longest delay between two attempts (default: 30)
+    pub max_delay_secs: Option<u64>,
+    /// Give up once this long has passed since the first attempt (default: 60)
+    pub max_elapsed_secs: Option<u64>,
+}
+
+/// A size written either as a byte count or a string like "500G"
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeValue {
+    Bytes(u64),
+    Text(String),
+}
+
+fn deserialize_quotas<'de, D>(deserializer: D) -> Result<HashMap<String, u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = HashMap::<String, SizeValue>::deserialize(deserializer)?;
+    raw.into_iter()
+        .map(|(location, size)| {
+            let bytes = match size {
+                SizeValue::Bytes(bytes) => bytes,
+                SizeValue::Text(text) => parse_size(&text).map_err(serde::de::Error::custom)?,
+            };
+            Ok((location.trim_end_matches('/').to_string(), bytes))
+        })
+        .collect()
+}
+
+fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<SizeValue>::deserialize(deserializer)? {
+        Some(SizeValue::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(SizeValue::Text(text)) => parse_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+impl Config {
+    /// Location of the config file, if the platform has a config directory
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("azst").join("config.toml"))
+    }
+
+    /// Load the config file, falling back to defaults when it doesn't exist
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => {
+                let contents = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read config file {}", path.display()))?;
+                Self::parse(&contents)
+                    .with_context(|| format!("Invalid config file {}", path.display()))
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    fn parse(contents: &str) -> Result<Self> {
+        let mut config: Config = toml::from_str(contents)?;
+        config.content_types = std::mem::take(&mut config.content_types)
+            .into_iter()
+            .map(|(ext, mime)| (ext.trim_start_matches('.').to_lowercase(), mime))
+            .collect();
+        Ok(config)
+    }
+
+    /// Transfer tuning of a profile over the `[transfer]` section
+    pub fn transfer_for(&self, profile: Option<&str>) -> Result<TransferConfig> {
+        let Some(name) = profile else {
+            return Ok(self.transfer.clone());
+        };
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            names.sort();
+            anyhow!(
+                "No profile '{}' in the config file (profiles: {})",
+                name,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            )
+        })?;
+        Ok(profile.transfer.clone().or(self.transfer.clone()))
+    }
+
+    /// Make `account` the default
+    pub fn save_default_account(account: &str) -> Result<PathBuf> {
+        Self::edit(|contents| with_setting(contents, None, "default_account", Some(account)))
+    }
+
+    /// Add or replace the alias `name` for `uri`
+    pub fn save_alias(name: &str, uri: &str) -> Result<PathBuf> {
+        Self::edit(|contents| with_setting(contents, Some("aliases"), name, Some(uri)))
+    }
+
+    /// Forget the alias `name`
+    pub fn remove_alias(name: &str) -> Result<PathBuf> {
+        Self::edit(|contents| with_setting(contents, Some("aliases"), name, None))
+    }
+
+    /// Rewrite the config file in place, so its comments and other settings
+    /// survive
+    fn edit(change: impl FnOnce(&str) -> String) -> Result<PathBuf> {
+        let path = Self::path().ok_or_else(|| anyhow!("No config directory on this platform"))?;
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read config file {}", path.display()))
+            }
+        };
+        let contents = change(&contents);
+        // Refuse to leave behind a file the next command can't load
+        Self::parse(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write config file {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// `arg` with an alias (`data://2024/`) or the default account shorthand
+    /// (`az:container/path`) expanded into a full az:// URI
+    pub fn expand_uri(&self, arg: &str) -> Option<String> {
+        if let Some((scheme, rest)) = arg.split_once("://") {
+            let target = self.aliases.get(scheme)?;
+            return Some(format!(
+                "{}/{}",
+                target.trim_end_matches('/'),
+                rest.trim_start_matches('/')
+            ));
+        }
+        expand_account_shorthand(arg, self.default_account.as_deref()?)
+    }
+
+    /// The most specific quota covering `destination`, as (location, bytes)
+    pub fn quota_for(&self, destination: &str) -> Option<(&str, u64)> {
+        let destination = destination.trim_end_matches('/');
+        self.quotas
+            .iter()
+            .filter(|(location, _)| {
+                destination == location.as_str()
+                    || destination
+                        .strip_prefix(location.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|(location, _)| location.len())
+            .map(|(location, bytes)| (location.as_str(), *bytes))
+    }
+}
+
+/// `contents` with `key` of `section` (top-level when `None`) set to
+/// `value`, or removed when `value` is `None`
+///
+/// An existing key is replaced where it is. A new top-level key becomes the
+/// first line, a new key of a section its last; a missing section is added
+/// at the end.
+fn with_setting(contents: &str, section: Option<&str>, key: &str, value: Option<&str>) -> String {
+    let is_header = |line: &String| line.trim_start().starts_with('[');
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+
+    let start = match section {
+        None => Some(0),
+        Some(name) => lines
+            .iter()
+            .position(|line| line.trim() == format!("[{}]", name))
+            .map(|header| header + 1),
+    };
+    let Some(start) = start else {
+        if let (Some(name), Some(value)) = (section, value) {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{}]", name));
+            lines.push(setting_line(key, value));
+        }
+        return finish_lines(lines);
+    };
+    let end = lines[start..]
+        .iter()
+        .position(is_header)
+        .map_or(lines.len(), |offset| start + offset);
+
+    let existing = (start..end).find(|&index| {
+        lines[index]
+            .split_once('=')
+            .is_some_and(|(name, _)| name.trim().trim_matches('"') == key)
+    });
+    match (existing, value) {
+        (Some(index), Some(value)) => lines[index] = setting_line(key, value),
+        (Some(index), None) => {
+            lines.remove(index);
+        }
+        (None, Some(value)) => {
+            let at = match section {
+                None => 0,
+                // After the section's last setting, before blank lines
+                Some(_) => (start..end)
+                    .rev()
+                    .find(|&index| !lines[index].trim().is_empty())
+                    .map_or(start, |index| index + 1),
+            };
+            lines.insert(at, setting_line(key, value));
+        }
+        (None, None) => {}
+    }
+    finish_lines(lines)
+}
+
+fn setting_line(key: &str, value: &str) -> String {
+    format!("{} = {}", key, toml::Value::String(value.to_string()))
+}
+
+fn finish_lines(lines: Vec<String>) -> String {
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty_config() {
+        let config = Config::parse("").unwrap();
+        assert!(config.content_types.is_empty());
+    }
+
+    #[test]
+    fn test_parse_content_types_normalizes_extensions() {
+        let config = Config::parse(
+            r#"
+            [content_types]
+            ".WASM" = "application/wasm"
+            md = "text/markdown"
+            ".COG.tif" = "image/tiff; profile=cloud-optimized"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.content_types["wasm"], "application/wasm");
+        assert_eq!(config.content_types["md"], "text/markdown");
+        assert_eq!(
+            config.content_types["cog.tif"],
+            "image/tiff; profile=cloud-optimized"
+        );
+    }
+
+    #[test]
+    fn test_parse_quotas() {
+        let config = Config::parse(
+            r#"
+            [quotas]
+            "az://acct" = "1K"
+            "az://acct/scratch/" = 100
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.quotas["az://acct"], 1024);
+        assert_eq!(config.quotas["az://acct/scratch"], 100);
+        assert!(Config::parse("[quotas]\n\"az://acct\" = \"lots\"").is_err());
+    }
+
+    #[test]
+    fn test_quota_for_picks_most_specific_location() {
+        let config = Config::parse(
+            r#"
+            [quotas]
+            "az://acct" = 1000
+            "az://acct/scratch" = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.quota_for("az://acct/scratch/run1/"),
+            Some(("az://acct/scratch", 10))
+        );
+        assert_eq!(
+            config.quota_for("az://acct/data"),
+            Some(("az://acct", 1000))
+        );
+        assert_eq!(
+            config.quota_for("az://acct/scratchpad"),
+            Some(("az://acct", 1000))
+        );
+        assert_eq!(config.quota_for("az://other/data"), None);
+    }
+
+    #[test]
+    fn test_parse_retry() {
+        let config = Config::parse(
+            r#"
+            [retry]
+            max_retries = 3
+            max_delay_secs = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.retry.max_retries, Some(3));
+        assert_eq!(config.retry.max_delay_secs, Some(10));
+        assert_eq!(config.retry.initial_delay_ms, None);
+    }
+
+    #[test]
+    fn test_parse_readahead() {
+        let config = Config::parse(
+            r#"
+            [readahead]
+            block_size = "8M"
+            cache_size = 268435456
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.readahead.block_size, Some(8 * 1024 * 1024));
+        assert_eq!(config.readahead.cache_size, Some(256 * 1024 * 1024));
+        assert_eq!(config.readahead.blocks, None);
+        assert!(Config::parse("[readahead]\nblock_size = \"lots\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_rm() {
+        let config = Config::parse(
+            r#"
+            [rm]
+            max_deletes = 5000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.rm.max_deletes, Some(5000));
+        assert_eq!(config.rm.confirm_above, None);
+    }
+
+    #[test]
+    fn test_transfer_profiles() {
+        let config = Config::parse(
+            r#"
+            [transfer]
+            concurrency = 64
+            scan_concurrency = 4
+
+            [profiles.fast.transfer]
+            concurrency = 512
+            buffer_gb = 2.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.transfer_for(None).unwrap().concurrency, Some(64));
+
+        let fast = config.transfer_for(Some("fast")).unwrap();
+        assert_eq!(
+            fast,
+            TransferConfig {
+                concurrency: Some(512),
+                buffer_gb: Some(2.5),
+                scan_concurrency: Some(4),
+            }
+        );
+        assert_eq!(fast.env_vars()[1], ("AZCOPY_BUFFER_GB", "2.5".to_string()));
+
+        let error = config.transfer_for(Some("slow")).unwrap_err();
+        assert!(error.to_string().contains("profiles: fast"));
+    }
+
+    #[test]
+    fn test_with_setting_top_level() {
+        let set = |contents, value| with_setting(contents, None, "default_account", Some(value));
+        assert_eq!(set("", "myaccount"), "default_account = \"myaccount\"\n");
+
+        let contents = "# azst settings\ndefault_account = \"old\"\n\n[retry]\nmax_retries = 3\n";
+        assert_eq!(
+            set(contents, "new"),
+            "# azst settings\ndefault_account = \"new\"\n\n[retry]\nmax_retries = 3\n"
+        );
+
+        // A key of the same name inside a section isn't the top-level one
+        let updated = set("[profiles.x]\ndefault_account = \"other\"\n", "new");
+        assert!(updated.starts_with("default_account = \"new\"\n[profiles.x]"));
+        assert_eq!(
+            Config::parse(&set("[retry]\nmax_retries = 3\n", "acct"))
+                .unwrap()
+                .default_account
+                .as_deref(),
+            Some("acct")
+        );
+    }
+
+    #[test]
+    fn test_with_setting_aliases() {
+        let add = |contents, name, uri| with_setting(contents, Some("aliases"), name, Some(uri));
+
+        let contents = add("[retry]\nmax_retries = 3\n", "data", "az://acct/datasets/");
+        assert_eq!(
+            contents,
+            "[retry]\nmax_retries = 3\n\n[aliases]\ndata = \"az://acct/datasets/\"\n"
+        );
+        let contents = add(&contents, "logs", "az://acct/logs/");
+        let contents = add(&contents, "data", "az://other/datasets/");
+        let config = Config::parse(&contents).unwrap();
+        assert_eq!(config.aliases["data"], "az://other/datasets/");
+        assert_eq!(config.aliases["logs"], "az://acct/logs/");
+        assert_eq!(config.retry.max_retries, Some(3));
+
+        let contents = with_setting(&contents, Some("aliases"), "data", None);
+        let config = Config::parse(&contents).unwrap();
+        assert!(!config.aliases.contains_key("data"));
+        assert_eq!(config.aliases.len(), 1);
+    }
+
+    #[test]
+    fn test_expand_uri() {
+        let config = Config::parse(
+            "default_account = \"myaccount\"\n[aliases]\ncurated = \"az://prod/datasets/curated/\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.expand_uri("curated://2024/").as_deref(),
+            Some("az://prod/datasets/curated/2024/")
+        );
+        assert_eq!(
+            config.expand_uri("curated://").as_deref(),
+            Some("az://prod/datasets/curated/")
+        );
+        assert_eq!(
+            config.expand_uri("az:data/x.csv").as_deref(),
+            Some("az://myaccount/data/x.csv")
+        );
+        assert_eq!(config.expand_uri("az://acct/data/"), None);
+        assert_eq!(config.expand_uri("other://x"), None);
+        assert_eq!(config.expand_uri("local/path"), None);
+    }
+
+    #[test]
+    fn test_parse_invalid_config() {
+        assert!(Config::parse("content_types = 3").is_err());
+    }
+}
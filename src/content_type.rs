@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+// ============================================================================
+// Content Type Detection - MIME types guessed from file extensions
+// ============================================================================
+
+/// Built-in extension to MIME type table, covering common web and data formats
+const BUILTIN_MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("tsv", "text/tab-separated-values"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("ogg", "audio/ogg"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tgz", "application/gzip"),
+    ("zst", "application/zstd"),
+    ("tar", "application/x-tar"),
+    ("wasm", "application/wasm"),
+    ("parquet", "application/vnd.apache.parquet"),
+    ("geojson", "application/geo+json"),
+];
+
+/// Guess the content type of a file from its extension
+///
+/// User overrides (keyed by lowercase extension) take precedence over the
+/// built-in table. Returns `None` for unknown or missing extensions.
+pub fn detect_content_type(path: &str, overrides: &HashMap<String, String>) -> Option<String> {
+    configured_content_type(path, overrides).or_else(|| {
+        extensions(path).into_iter().find_map(|extension| {
+            BUILTIN_MIME_TYPES
+                .iter()
+                .find(|(ext, _)| *ext == extension)
+                .map(|(_, mime)| mime.to_string())
+        })
+    })
+}
+
+/// The content type the user configured for a file's extension, if any
+///
+/// Multi-part extensions such as "cog.tif" match before their last part.
+pub fn configured_content_type(path: &str, overrides: &HashMap<String, String>) -> Option<String> {
+    extensions(path)
+        .into_iter()
+        .find_map(|extension| overrides.get(&extension).cloned())
+}
+
+/// Lowercase extensions of a file name, longest first
+/// ("scene.cog.tif" -> ["cog.tif", "tif"])
+fn extensions(path: &str) -> Vec<String> {
+    let Some(name) = Path::new(path).file_name().and_then(|name| name.to_str()) else {
+        return Vec::new();
+    };
+    // A leading dot marks a hidden file, not an extension
+    let name = name.to_lowercase();
+    let stem_end = name.trim_start_matches('.').len();
+    let name = &name[name.len() - stem_end..];
+
+    name.match_indices('.')
+        .map(|(pos, _)| name[pos + 1..].to_string())
+        .filter(|extension| !extension.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_builtin_types() {
+        let none = HashMap::new();
+        assert_eq!(
+            detect_content_type("site/index.html", &none).as_deref(),
+            Some("text/html")
+        );
+        assert_eq!(
+            detect_content_type("photo.JPG", &none).as_deref(),
+            Some("image/jpeg")
+        );
+        assert_eq!(detect_content_type("README", &none), None);
+        assert_eq!(detect_content_type("data.unknownext", &none), None);
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let mut overrides = HashMap::new();
+        overrides.insert("js".to_string(), "application/javascript".to_string());
+        overrides.insert("dat".to_string(), "application/x-custom".to_string());
+
+        assert_eq!(
+            detect_content_type("app.js", &overrides).as_deref(),
+            Some("application/javascript")
+        );
+        assert_eq!(
+            detect_content_type("blob.dat", &overrides).as_deref(),
+            Some("application/x-custom")
+        );
+    }
+
+    #[test]
+    fn test_multi_part_extensions() {
+        let mut overrides = HashMap::new();
+        overrides.insert(
+            "cog.tif".to_string(),
+            "image/tiff; profile=cloud-optimized".to_string(),
+        );
+
+        assert_eq!(
+            detect_content_type("scenes/a.COG.tif", &overrides).as_deref(),
+            Some("image/tiff; profile=cloud-optimized")
+        );
+        assert_eq!(
+            detect_content_type("scenes/a.tif", &overrides).as_deref(),
+            Some("image/tiff")
+        );
+        assert_eq!(
+            detect_content_type("archive.tar.gz", &overrides).as_deref(),
+            Some("application/gzip")
+        );
+        assert_eq!(configured_content_type("a.tif", &overrides), None);
+        assert_eq!(detect_content_type(".bashrc", &overrides), None);
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+
+use crate::azure::{AzureClient, BlobItem};
+use crate::utils::{directory_prefix, parse_azure_uri};
+
+// ============================================================================
+// Directory Markers - Zero-byte "dir/" blobs standing in for empty directories
+// ============================================================================
+
+/// Create a marker blob for every empty directory under `local_root`
+///
+/// Blob storage has no directories, so an empty folder would otherwise vanish
+/// on upload. Each marker is a zero-byte blob named `<prefix>/<relative>/`
+/// under `remote_root`. Returns the number of markers written.
+pub async fn upload_markers(local_root: &Path, remote_root: &str) -> Result<usize> {
+    let empty_dirs = find_empty_dirs(local_root)?;
+    if empty_dirs.is_empty() {
+        return Ok(0);
+    }
+
+    let (mut client, container, prefix) = remote_location(remote_root, "Directory markers")?;
+    for relative in &empty_dirs {
+        client
+            .put_empty_blob(&container, &format!("{}{}/", prefix, relative))
+            .await?;
+    }
+
+    Ok(empty_dirs.len())
+}
+
+/// Recreate the directories recorded by marker blobs under `remote_root`
+/// inside `local_root`, returning the number of directories created
+pub async fn restore_empty_dirs(remote_root: &str, local_root: &Path) -> Result<usize> {
+    let (mut client, container, prefix) = remote_location(remote_root, "Directory markers")?;
+
+    let mut markers = Vec::new();
+    let list_prefix = (!prefix.is_empty()).then_some(prefix.as_str());
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    if let Some(relative) = marker_directory(&blob.name, &prefix) {
+                        if blob.properties.content_length == 0 {
+                            markers.push(relative.to_string());
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+        .await?;
+
+    let mut created = 0;
+    for relative in markers {
+        let dir = local_root.join(&relative);
+        if !dir.is_dir() {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+            created += 1;
+        }
+    }
+
+    Ok(created)
+}
+
+/// Client, container and directory prefix (with trailing slash, or empty)
+/// of a location that `feature` needs a storage account in
+pub(crate) fn remote_location(uri: &str, feature: &str) -> Result<(AzureClient, String, String)> {
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!(
+            "{} require a storage account in '{}': az://<account>/<container>/[path]",
+            feature,
+            uri
+        )
+    })?;
+
+    let prefix = directory_prefix(prefix.as_deref());
+
+    Ok((
+        AzureClient::new().with_storage_account(&account),
+        container,
+        prefix,
+    ))
+}
+
+/// Relative directory recorded by a marker blob name, if it is one
+fn marker_directory<'a>(name: &'a str, prefix: &str) -> Option<&'a str> {
+    let relative = name.strip_prefix(prefix)?.strip_suffix('/')?;
+    (!relative.is_empty()).then_some(relative)
+}
+
+/// Relative paths ('/'-separated) of directories under `root` with no entries
+fn find_empty_dirs(root: &Path) -> Result<Vec<String>> {
+    let mut empty = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let mut has_entries = false;
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            has_entries = true;
+            if path.is_dir() {
+                stack.push(path);
+            }
+        }
+
+        if !has_entries && dir != root {
+            let relative = dir
+                .strip_prefix(root)
+                .unwrap_or(&dir)
+                .to_string_lossy()
+                .replace('\\', "/");
+            empty.push(relative);
+        }
+    }
+
+    empty.sort();
+    Ok(empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_empty_dirs() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::create_dir_all(root.join("c")).unwrap();
+        std::fs::create_dir_all(root.join("d/e/f")).unwrap();
+        std::fs::write(root.join("c/file.txt"), b"x").unwrap();
+
+        assert_eq!(find_empty_dirs(root).unwrap(), vec!["a/b", "d/e/f"]);
+    }
+
+    #[test]
+    fn test_find_empty_dirs_ignores_empty_root() {
+        let temp = tempfile::tempdir().unwrap();
+        assert!(find_empty_dirs(temp.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_marker_directory() {
+        assert_eq!(marker_directory("data/logs/", "data/"), Some("logs"));
+        assert_eq!(marker_directory("data/a/b/", "data/"), Some("a/b"));
+        assert_eq!(marker_directory("logs/", ""), Some("logs"));
+        assert_eq!(marker_directory("data/file.txt", "data/"), None);
+        assert_eq!(marker_directory("data/", "data/"), None);
+        assert_eq!(marker_directory("other/x/", "data/"), None);
+    }
+}
@@ -0,0 +1,258 @@
+use colored::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::azure::convert_url_to_az_uri;
+use crate::output::{is_deterministic, output_format, OutputFormat};
+use crate::utils::{format_size, is_azure_uri};
+
+// ============================================================================
+// Dry Runs - What a cp, sync or rm would do, without doing it
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlannedAction {
+    Upload,
+    Download,
+    Copy,
+    Delete,
+}
+
+/// One file a dry run would transfer or delete
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedTransfer {
+    pub action: PlannedAction,
+    /// The file read, or the one deleted
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl PlannedTransfer {
+    /// A transfer from `source` to `destination`, its action picked by which
+    /// side is in Azure
+    pub fn transfer(source: &str, destination: &str, size: Option<u64>) -> Self {
+        let action = match (is_azure_uri(source), is_azure_uri(destination)) {
+            (false, true) => PlannedAction::Upload,
+            (true, false) => PlannedAction::Download,
+            _ => PlannedAction::Copy,
+        };
+        Self {
+            action,
+            source: source.to_string(),
+            destination: Some(destination.to_string()),
+            size,
+        }
+    }
+
+    pub fn delete(target: &str, size: Option<u64>) -> Self {
+        Self {
+            action: PlannedAction::Delete,
+            source: target.to_string(),
+            destination: None,
+            size,
+        }
+    }
+}
+
+/// A `Dryrun` message of azcopy's JSON output
+///
+/// ```text
+/// {"Source":"/data/a.txt","Destination":"https://acct.blob.core.windows.net/data/a.txt",
+///  "SourceSize":1024,"FromTo":"LocalBlob", ...}
+/// ```
+///
+/// Deletions have no destination and a `FromTo` ending in "Trash".
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DryRunMessage {
+    source: String,
+    #[serde(default)]
+    destination: String,
+    #[serde(default)]
+    source_size: Option<i64>,
+    #[serde(default)]
+    from_to: Option<Value>,
+}
+
+/// The transfer described by the content of a `Dryrun` message, with blob
+/// URLs turned back into az:// URIs
+pub fn parse_dry_run_message(content: &str) -> Option<PlannedTransfer> {
+    let message: DryRunMessage = serde_json::from_str(content).ok()?;
+    let location = |value: &str| convert_url_to_az_uri(value).unwrap_or_else(|| value.to_string());
+    let size = message
+        .source_size
+        .and_then(|size| u64::try_from(size).ok());
+    let deletes = message.destination.is_empty()
+        || message
+            .from_to
+            .as_ref()
+            .and_then(Value::as_str)
+            .is_some_and(|from_to| from_to.ends_with("Trash"));
+
+    Some(if deletes {
+        PlannedTransfer::delete(&location(&message.source), size)
+    } else {
+        PlannedTransfer::transfer(
+            &location(&message.source),
+            &location(&message.destination),
+            size,
+        )
+    })
+}
+
+/// Counts of a dry run, as written under `--format json`
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct PlanSummary {
+    pub uploads: usize,
+    pub downloads: usize,
+    pub copies: usize,
+    pub deletions: usize,
+    /// Bytes the transfers would move, deletions excluded
+    pub bytes: u64,
+}
+
+/// Everything a dry run would do
+#[derive(Debug, Default, Serialize)]
+pub struct DryRunPlan {
+    pub summary: PlanSummary,
+    pub transfers: Vec<PlannedTransfer>,
+}
+
+impl DryRunPlan {
+    pub fn new(mut transfers: Vec<PlannedTransfer>) -> Self {
+        // azcopy plans in parallel, so its order changes from run to run
+        if is_deterministic() {
+            transfers.sort_by(|a, b| (a.action, &a.source).cmp(&(b.action, &b.source)));
+        }
+
+        let mut summary = PlanSummary::default();
+        for transfer in &transfers {
+            match transfer.action {
+                PlannedAction::Upload => summary.uploads += 1,
+                PlannedAction::Download => summary.downloads += 1,
+                PlannedAction::Copy => summary.copies += 1,
+                PlannedAction::Delete => summary.deletions += 1,
+            }
+            if transfer.action != PlannedAction::Delete {
+                summary.bytes += transfer.size.unwrap_or(0);
+            }
+        }
+        Self { summary, transfers }
+    }
+
+    /// Print the plan to stdout: one `+`/`-` line per file and a summary, or
+    /// a single JSON document with `--format json`
+    pub fn print(&self) {
+        if output_format() == OutputFormat::Json {
+            match serde_json::to_string_pretty(self) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("{} Failed to write the plan: {}", "⚠".yellow(), e),
+            }
+            return;
+        }
+
+        for transfer in &self.transfers {
+            match &transfer.destination {
+                Some(destination) => {
+                    let size = transfer
+                        .size
+                        .map(|size| format!(" ({})", format_size(size)))
+                        .unwrap_or_default();
+                    println!("{} {}{}", "+".green(), destination, size.dimmed());
+                }
+                None => println!("{} {}", "-".red(), transfer.source),
+            }
+        }
+        println!("{}", self.describe().bold());
+    }
+
+    /// e.g. "Would upload 3 file(s) totaling 1.2 GB and delete 1 file(s)"
+    fn describe(&self) -> String {
+        let summary = &self.summary;
+        let mut parts: Vec<String> = [
+            ("upload", summary.uploads),
+            ("download", summary.downloads),
+            ("copy", summary.copies),
+        ]
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(verb, count)| format!("{} {} file(s)", verb, count))
+        .collect();
+        if !parts.is_empty() {
+            let last = parts.len() - 1;
+            parts[last] += &format!(" totaling {}", format_size(summary.bytes));
+        }
+        if summary.deletions > 0 {
+            parts.push(format!("delete {} file(s)", summary.deletions));
+        }
+
+        match parts.len() {
+            0 => "Dry run: nothing to do".to_string(),
+            1 => format!("Would {}", parts[0]),
+            _ => {
+                let last = parts.pop().unwrap_or_default();
+                format!("Would {} and {}", parts.join(", "), last)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dry_run_message() {
+        let upload = r#"{"EntityType":"File","BlobType":"","FromTo":"LocalBlob",
+            "Source":"/data/a.txt","Destination":"https://acct.blob.core.windows.net/data/a.txt",
+            "SourceSize":1024}"#;
+        assert_eq!(
+            parse_dry_run_message(upload),
+            Some(PlannedTransfer {
+                action: PlannedAction::Upload,
+                source: "/data/a.txt".to_string(),
+                destination: Some("az://acct/data/a.txt".to_string()),
+                size: Some(1024),
+            })
+        );
+
+        let delete = r#"{"FromTo":"BlobTrash","Source":"https://acct.blob.core.windows.net/data/old.txt","Destination":"","SourceSize":null}"#;
+        assert_eq!(
+            parse_dry_run_message(delete),
+            Some(PlannedTransfer::delete("az://acct/data/old.txt", None))
+        );
+
+        assert_eq!(parse_dry_run_message("DRYRUN: copy a to b"), None);
+    }
+
+    #[test]
+    fn test_plan_summary() {
+        let plan = DryRunPlan::new(vec![
+            PlannedTransfer::transfer("/data/a.txt", "az://acct/data/a.txt", Some(1024)),
+            PlannedTransfer::transfer("/data/b.txt", "az://acct/data/b.txt", Some(2048)),
+            PlannedTransfer::delete("az://acct/data/old.txt", Some(4096)),
+        ]);
+        assert_eq!(
+            plan.summary,
+            PlanSummary {
+                uploads: 2,
+                downloads: 0,
+                copies: 0,
+                deletions: 1,
+                bytes: 3072,
+            }
+        );
+        assert_eq!(
+            plan.describe(),
+            "Would upload 2 file(s) totaling 3.0 KB and delete 1 file(s)"
+        );
+        assert_eq!(
+            DryRunPlan::new(Vec::new()).describe(),
+            "Dry run: nothing to do"
+        );
+    }
+}
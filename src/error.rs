@@ -0,0 +1,309 @@
+use azure_core::error::ErrorKind as AzureErrorKind;
+use azure_core::StatusCode;
+use std::error::Error as StdError;
+use std::fmt;
+
+// ============================================================================
+// Errors - Failure categories with their own process exit codes
+// ============================================================================
+
+/// Anything not covered below
+pub const EXIT_ERROR: i32 = 1;
+/// Bad arguments, including invalid az:// URIs (also what clap exits with),
+/// or a confirmation nobody is at a terminal to give
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_AUTH: i32 = 4;
+/// An azcopy job failed, or with `--strict`, some of its files did
+pub const EXIT_TRANSFER_FAILED: i32 = 5;
+pub const EXIT_PERMISSION_DENIED: i32 = 6;
+pub const EXIT_THROTTLED: i32 = 7;
+pub const EXIT_AZCOPY_MISSING: i32 = 8;
+/// --timeout ran out, like timeout(1)
+pub const EXIT_TIMED_OUT: i32 = 124;
+/// Stopped with Ctrl-C, like a shell reports SIGINT
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// The error of the library API, and failures scripts can tell apart by exit
+/// code rather than by message
+///
+/// The categories are raised where they are known. Everything else, and
+/// categories that picked up context on the way out, arrive as `Other` with
+/// their chain of causes; [`AzstError::exit_code`] still finds the category.
+/// Inside the crate errors travel as `anyhow::Error` and convert with `?`.
+#[derive(Debug)]
+pub enum AzstError {
+    /// No usable credential, or the service rejected it (401)
+    Auth(String),
+    /// The account, container or blob doesn't exist (404)
+    NotFound(String),
+    /// Signed in, but not allowed to do this (403)
+    PermissionDenied(String),
+    /// The service still answered 429/503 once retries ran out
+    Throttled(String),
+    /// An azcopy job failed without transferring what it was asked to
+    TransferFailed(String),
+    /// Not a valid az:// URI
+    InvalidUri(String),
+    /// A prompt needed an answer, but the run is non-interactive
+    NeedsConfirmation(String),
+    /// AzCopy isn't installed or doesn't run
+    AzCopyMissing(String),
+    /// The command ran longer than --timeout
+    TimedOut(String),
+    /// Ctrl-C stopped the command
+    Interrupted(String),
+    /// Any other failure, e.g. a storage request the service refused, whose
+    /// `source()` chain holds the causes
+    Other(Box<dyn StdError + Send + Sync>),
+}
+
+impl AzstError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AzstError::Other(error) => chain_exit_code(error.as_ref()),
+            AzstError::Auth(_) => EXIT_AUTH,
+            AzstError::NotFound(_) => EXIT_NOT_FOUND,
+            AzstError::PermissionDenied(_) => EXIT_PERMISSION_DENIED,
+            AzstError::Throttled(_) => EXIT_THROTTLED,
+            AzstError::TransferFailed(_) => EXIT_TRANSFER_FAILED,
+            AzstError::InvalidUri(_) | AzstError::NeedsConfirmation(_) => EXIT_USAGE,
+            AzstError::AzCopyMissing(_) => EXIT_AZCOPY_MISSING,
+            AzstError::TimedOut(_) => EXIT_TIMED_OUT,
+            AzstError::Interrupted(_) => EXIT_INTERRUPTED,
+        }
+    }
+}
+
+impl fmt::Display for AzstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AzstError::Auth(message)
+            | AzstError::NotFound(message)
+            | AzstError::PermissionDenied(message)
+            | AzstError::Throttled(message)
+            | AzstError::TransferFailed(message)
+            | AzstError::InvalidUri(message)
+            | AzstError::NeedsConfirmation(message)
+            | AzstError::AzCopyMissing(message)
+            | AzstError::TimedOut(message)
+            | AzstError::Interrupted(message) => f.write_str(message),
+            AzstError::Other(error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+impl StdError for AzstError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            AzstError::Other(error) => error.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for AzstError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<AzstError>() {
+            Ok(error) => error,
+            Err(error) => AzstError::Other(error.into()),
+        }
+    }
+}
+
+/// Errors of the libraries the API calls into, passed on as `Other`
+macro_rules! other_from {
+    ($($error:ty),* $(,)?) => {
+        $(
+            impl From<$error> for AzstError {
+                fn from(error: $error) -> Self {
+                    AzstError::Other(Box::new(error))
+                }
+            }
+        )*
+    };
+}
+
+other_from!(
+    azure_core::error::Error,
+    std::io::Error,
+    serde_json::Error,
+    reqwest::Error,
+);
+
+/// Each error in a chain, with the error an `AzstError::Other` holds in
+/// place of the wrapper, so the types behind it can be downcast to
+fn causes<'a>(
+    error: &'a (dyn StdError + 'static),
+) -> impl Iterator<Item = &'a (dyn StdError + 'static)> {
+    std::iter::successors(Some(error), |&cause| cause.source()).map(|cause| {
+        match cause.downcast_ref::<AzstError>() {
+            Some(AzstError::Other(inner)) => inner.as_ref() as &(dyn StdError + 'static),
+            _ => cause,
+        }
+    })
+}
+
+/// Exit code for an error: that of the first `AzstError` in its chain, else
+/// one picked from a failed storage request's status, else `EXIT_ERROR`
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    chain_exit_code(error.as_ref())
+}
+
+fn chain_exit_code(error: &(dyn StdError + 'static)) -> i32 {
+    for cause in causes(error) {
+        if let Some(error) = cause.downcast_ref::<AzstError>() {
+            return error.exit_code();
+        }
+        if let Some(code) = cause
+            .downcast_ref::<azure_core::error::Error>()
+            .and_then(|error| azure_exit_code(error.kind()))
+        {
+            return code;
+        }
+    }
+    EXIT_ERROR
+}
+
+/// HTTP status of the failed storage request behind an error, if any
+pub fn http_status(error: &anyhow::Error) -> Option<StatusCode> {
+    causes(error.as_ref()).find_map(|cause| {
+        match cause.downcast_ref::<azure_core::error::Error>()?.kind() {
+            AzureErrorKind::HttpResponse { status, .. } => Some(*status),
+            _ => None,
+        }
+    })
+}
+
+/// Error code the storage service gave a failed request (e.g.
+/// `ContainerNotFound`), if any
+pub fn storage_error_code(error: &anyhow::Error) -> Option<&str> {
+    causes(error.as_ref()).find_map(|cause| {
+        match cause.downcast_ref::<azure_core::error::Error>()?.kind() {
+            AzureErrorKind::HttpResponse { error_code, .. } => error_code.as_deref(),
+            _ => None,
+        }
+    })
+}
+
+/// Whether a request failed because the host it was sent to has no address,
+/// e.g. a storage account that doesn't exist
+///
+/// Connection failures don't say why in a type, so the host of the failed
+/// request is looked up again.
+pub fn host_not_found(error: &anyhow::Error) -> bool {
+    use std::net::ToSocketAddrs;
+
+    causes(error.as_ref())
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .find(|error| error.is_connect())
+        .and_then(|error| error.url()?.host_str().map(str::to_string))
+        .is_some_and(|host| (host.as_str(), 443).to_socket_addrs().is_err())
+}
+
+fn azure_exit_code(kind: &AzureErrorKind) -> Option<i32> {
+    match kind {
+        AzureErrorKind::Credential => Some(EXIT_AUTH),
+        AzureErrorKind::HttpResponse { status, .. } => match status {
+            StatusCode::Unauthorized => Some(EXIT_AUTH),
+            StatusCode::Forbidden => Some(EXIT_PERMISSION_DENIED),
+            StatusCode::NotFound => Some(EXIT_NOT_FOUND),
+            StatusCode::TooManyRequests | StatusCode::ServiceUnavailable => Some(EXIT_THROTTLED),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code_finds_typed_error_under_context() {
+        let error = Err::<(), _>(AzstError::NotFound("Blob 'a' not found".to_string()))
+            .context("Failed to download")
+            .unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_NOT_FOUND);
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to download: Blob 'a' not found"
+        );
+    }
+
+    #[test]
+    fn test_exit_code_from_storage_status() {
+        let response = |status| {
+            anyhow::Error::new(azure_core::error::Error::message(
+                AzureErrorKind::HttpResponse {
+                    status,
+                    error_code: None,
+                },
+                "request failed",
+            ))
+        };
+        assert_eq!(
+            exit_code(&response(StatusCode::Forbidden)),
+            EXIT_PERMISSION_DENIED
+        );
+        assert_eq!(exit_code(&response(StatusCode::NotFound)), EXIT_NOT_FOUND);
+        assert_eq!(
+            exit_code(&response(StatusCode::ServiceUnavailable)),
+            EXIT_THROTTLED
+        );
+        assert_eq!(exit_code(&response(StatusCode::BadRequest)), EXIT_ERROR);
+        assert_eq!(
+            http_status(&response(StatusCode::TooManyRequests).context("Failed to delete")),
+            Some(StatusCode::TooManyRequests)
+        );
+        assert_eq!(http_status(&anyhow::anyhow!("no request")), None);
+
+        let missing = anyhow::Error::new(azure_core::error::Error::message(
+            AzureErrorKind::HttpResponse {
+                status: StatusCode::NotFound,
+                error_code: Some("ContainerNotFound".to_string()),
+            },
+            "request failed",
+        ))
+        .context("Failed to fetch blob page");
+        assert_eq!(storage_error_code(&missing), Some("ContainerNotFound"));
+        assert_eq!(storage_error_code(&response(StatusCode::NotFound)), None);
+    }
+
+    #[test]
+    fn test_other_keeps_its_causes() {
+        let storage = azure_core::error::Error::message(
+            AzureErrorKind::HttpResponse {
+                status: StatusCode::NotFound,
+                error_code: Some("BlobNotFound".to_string()),
+            },
+            "request failed",
+        );
+        let error = AzstError::from(anyhow::Error::new(storage).context("Failed to download"));
+        assert!(matches!(error, AzstError::Other(_)));
+        assert_eq!(error.exit_code(), EXIT_NOT_FOUND);
+
+        // Back inside the crate, the storage error is still found
+        let error = anyhow::Error::from(error).context("Failed to copy");
+        assert_eq!(exit_code(&error), EXIT_NOT_FOUND);
+        assert_eq!(storage_error_code(&error), Some("BlobNotFound"));
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to copy: Failed to download: request failed"
+        );
+
+        // A category raised without context stays that category
+        let error = AzstError::from(anyhow::Error::from(AzstError::InvalidUri("bad".into())));
+        assert!(matches!(error, AzstError::InvalidUri(_)));
+    }
+
+    #[test]
+    fn test_exit_code_defaults_to_generic_error() {
+        assert_eq!(exit_code(&anyhow::anyhow!("something broke")), EXIT_ERROR);
+        assert_eq!(
+            exit_code(&AzstError::InvalidUri("bad".to_string()).into()),
+            EXIT_USAGE
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::utils::file_md5;
+
+// ============================================================================
+// Hash Cache - Parallel MD5 of local trees, remembered between runs
+// ============================================================================
+
+/// What a cached hash was computed from; a file whose size or modification
+/// time differs is hashed again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    /// Modification time as a Unix timestamp (seconds)
+    modified: i64,
+    /// Sub-second part of the modification time, so an edit within the same
+    /// second that keeps the size still counts as a change
+    nanos: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedHash {
+    #[serde(flatten)]
+    stamp: FileStamp,
+    md5: String,
+}
+
+/// The hashes of one local tree, stored as JSON in the user's cache
+/// directory and keyed by path relative to the tree
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TreeHashes {
+    files: HashMap<String, CachedHash>,
+}
+
+/// Hash this many files at once: hashing is bound by the disk and the CPU,
+/// not the network
+fn hash_concurrency() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn stamp(path: &Path) -> Result<FileStamp> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    Ok(FileStamp {
+        size: metadata.len(),
+        modified: modified.as_secs() as i64,
+        nanos: modified.subsec_nanos(),
+    })
+}
+
+/// Where the hashes of the tree at `root` are kept
+fn cache_path(root: &Path) -> Option<PathBuf> {
+    let root = std::fs::canonicalize(root).ok()?;
+    let key = format!("{:x}", md5::compute(root.to_string_lossy().as_bytes()));
+    dirs::cache_dir().map(|dir| dir.join("azst").join("hashes").join(key))
+}
+
+fn load(path: &Path) -> TreeHashes {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Best effort: a sync that can't write its cache only hashes again next time
+fn save(path: &Path, hashes: &TreeHashes) {
+    let write = || -> Result<()> {
+        let parent = path
+            .parent()
+            .ok_or_else(|| anyhow!("No parent directory"))?;
+        std::fs::create_dir_all(parent)?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string(hashes)?)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    };
+    if let Err(e) = write() {
+        tracing::debug!("Failed to save hash cache {}: {}", path.display(), e);
+    }
+}
+
+/// MD5 (lowercase hex) of each file under `root`, by relative path
+///
+/// Files are hashed on blocking threads, as many at once as there are CPUs.
+/// A file whose size and modification time match the previous run reuses
+/// that run's hash without being read.
+pub async fn md5_tree(root: &Path, relatives: Vec<String>) -> Result<HashMap<String, String>> {
+    md5_tree_cached(cache_path(root).as_deref(), root, relatives).await
+}
+
+async fn md5_tree_cached(
+    cache: Option<&Path>,
+    root: &Path,
+    relatives: Vec<String>,
+) -> Result<HashMap<String, String>> {
+    let previous = cache.map(load).unwrap_or_default().files;
+
+    let jobs = relatives.into_iter().map(|relative| {
+        let path = root.join(&relative);
+        let cached = previous.get(&relative).cloned();
+        async move {
+            tokio::task::spawn_blocking(move || -> Result<(String, CachedHash, bool)> {
+                let stamp = stamp(&path)?;
+                if let Some(cached) = cached.filter(|cached| cached.stamp == stamp) {
+                    return Ok((relative, cached, true));
+                }
+                let md5 = file_md5(&path)
+                    .map_err(|e| anyhow!("Failed to hash {}: {}", path.display(), e))?;
+                Ok((relative, CachedHash { stamp, md5 }, false))
+            })
+            .await?
+        }
+    });
+    let results: Vec<Result<(String, CachedHash, bool)>> = futures::stream::iter(jobs)
+        .buffer_unordered(hash_concurrency())
+        .collect()
+        .await;
+
+    // Only the files seen now are kept, so deleted files drop out
+    let mut current = TreeHashes::default();
+    let mut reused = 0;
+    for result in results {
+        let (relative, hash, was_cached) = result?;
+        if was_cached {
+            reused += 1;
+        }
+        current.files.insert(relative, hash);
+    }
+    tracing::debug!(
+        "Hashed {} file(s) under {}, {} unchanged since the last run",
+        current.files.len() - reused,
+        root.display(),
+        reused
+    );
+
+    if let Some(cache) = cache {
+        save(cache, &current);
+    }
+    Ok(current
+        .files
+        .into_iter()
+        .map(|(relative, hash)| (relative, hash.md5))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, relative: &str, contents: &str) {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_md5_tree_hashes_every_file() {
+        let tree = TempDir::new().unwrap();
+        write(tree.path(), "a.txt", "hello");
+        write(tree.path(), "dir/b.txt", "world");
+
+        let hashes = md5_tree_cached(
+            None,
+            tree.path(),
+            vec!["a.txt".to_string(), "dir/b.txt".to_string()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(hashes["a.txt"], "5d41402abc4b2a76b9719d911017c592");
+        assert_eq!(hashes["dir/b.txt"], "7d793037a0760186574b0282f2f435e7");
+    }
+
+    #[tokio::test]
+    async fn test_md5_tree_reuses_unchanged_hashes() {
+        let tree = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let cache = cache_dir.path().join("hashes");
+        write(tree.path(), "a.txt", "hello");
+        write(tree.path(), "gone.txt", "bye");
+        let files = || vec!["a.txt".to_string()];
+
+        md5_tree_cached(
+            Some(&cache),
+            tree.path(),
+            vec!["a.txt".to_string(), "gone.txt".to_string()],
+        )
+        .await
+        .unwrap();
+
+        // A stored hash is trusted while the size and time still match
+        let mut stored = load(&cache);
+        assert!(stored.files.contains_key("gone.txt"));
+        stored.files.get_mut("a.txt").unwrap().md5 = "cached".to_string();
+        save(&cache, &stored);
+        let hashes = md5_tree_cached(Some(&cache), tree.path(), files())
+            .await
+            .unwrap();
+        assert_eq!(hashes["a.txt"], "cached");
+        assert!(!load(&cache).files.contains_key("gone.txt"));
+
+        // An edit that changes the size is hashed again
+        write(tree.path(), "a.txt", "hello, world");
+        let hashes = md5_tree_cached(Some(&cache), tree.path(), files())
+            .await
+            .unwrap();
+        assert_eq!(hashes["a.txt"], "e4d7f1b4ed2e42d15898f4b27b019da4");
+    }
+}
@@ -0,0 +1,79 @@
+//! Azure Blob Storage operations behind the `azst` command line tool
+//!
+//! The items re-exported at the crate root are the supported API: URI
+//! parsing, the SDK-backed [`AzureClient`] (listings, properties, ranged
+//! reads, small uploads), the azcopy-backed transfer engine ([`AzCopyClient`]
+//! and [`copy`]) and block-cached [`BlobReader`]s. The modules themselves are
+//! public so the binary can be built on top of them, but may change between
+//! releases.
+//!
+//! Fallible calls return an [`AzstError`], which keeps the underlying SDK or
+//! I/O error as its source and maps to the CLI's exit codes.
+//!
+//! ```no_run
+//! use azst::{parse_azure_uri, AzureClient, BlobItem};
+//! use futures::StreamExt;
+//!
+//! # async fn example() -> Result<(), azst::AzstError> {
+//! let (account, container, prefix) = parse_azure_uri("az://myaccount/data/2024/")?;
+//! let mut client = AzureClient::new().with_storage_account(&account.unwrap());
+//!
+//! let mut pages = client.blob_pages(&container, prefix.as_deref(), None).await?;
+//! while let Some(page) = pages.next().await {
+//!     for item in page? {
+//!         if let BlobItem::Blob(blob) = item {
+//!             println!("{} {}", blob.properties.content_length, blob.name);
+//!         }
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+// Declared first so the status! macro is visible in every other module
+#[macro_use]
+pub mod logging;
+
+pub mod archive;
+pub mod async_copy;
+pub mod audit_log;
+pub mod azcopy_output;
+pub mod azure;
+pub mod bandwidth;
+pub mod blob_reader;
+pub mod cancel;
+pub mod checksum;
+#[doc(hidden)]
+pub mod cli;
+pub mod commands;
+pub mod compression;
+pub mod config;
+pub mod content_type;
+pub mod dir_markers;
+pub mod dry_run;
+pub mod error;
+pub mod hash_cache;
+pub mod listing_cache;
+pub mod login;
+pub mod manifest;
+pub mod network;
+pub mod output;
+pub mod pack;
+pub mod prompt;
+pub mod quota;
+pub mod region;
+pub mod symlinks;
+pub mod sync_state;
+pub mod throttle;
+pub mod tuning;
+pub mod url_copy;
+pub mod utils;
+
+pub use azure::{
+    convert_az_uri_to_url, convert_url_to_az_uri, AzCopyClient, AzCopyOptions, AzureClient,
+    BlobHeaders, BlobInfo, BlobItem, BlobProperties, OverwritePolicy, PathFilters,
+};
+pub use blob_reader::{BlobReader, ReadaheadConfig};
+pub use commands::cp::{copy, CopyOptions};
+pub use error::AzstError;
+pub use utils::{is_azure_uri, parse_azure_uri};
@@ -0,0 +1,353 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::azure::{BlobInfo, BlobItem};
+
+// ============================================================================
+// Listing Cache - Recursive listings kept on disk for `--cached` queries
+// ============================================================================
+
+/// Cached listings older than this are answered with a warning
+pub const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Every blob under a container prefix, as one recursive listing found it
+///
+/// `ls -r`, `inventory` and `azst cache refresh` write one file per
+/// container; `ls --cached` and `du --cached` answer from it without
+/// touching the network.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedListing {
+    pub account: String,
+    pub container: String,
+    /// Prefix the listing was taken under; empty for the whole container
+    pub prefix: String,
+    /// When the listing was taken, as a Unix timestamp (seconds)
+    pub created: u64,
+    /// Sorted by name
+    pub blobs: Vec<BlobInfo>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Directory holding one subdirectory of listings per account
+fn cache_root() -> Result<PathBuf> {
+    dirs::cache_dir()
+        .map(|dir| dir.join("azst").join("listings"))
+        .ok_or_else(|| anyhow!("No cache directory"))
+}
+
+fn listing_path(root: &Path, account: &str, container: &str) -> PathBuf {
+    // Account and container names are lowercase letters, digits and hyphens
+    root.join(account).join(format!("{}.json", container))
+}
+
+impl CachedListing {
+    pub fn new(
+        account: &str,
+        container: &str,
+        prefix: Option<&str>,
+        mut blobs: Vec<BlobInfo>,
+    ) -> Self {
+        blobs.sort_by(|a, b| a.name.cmp(&b.name));
+        CachedListing {
+            account: account.to_string(),
+            container: container.to_string(),
+            prefix: prefix.unwrap_or_default().to_string(),
+            created: now(),
+            blobs,
+        }
+    }
+
+    /// The cached listing of a container, if one covers `prefix`
+    pub fn load(account: &str, container: &str, prefix: Option<&str>) -> Result<Self> {
+        Self::load_from(&cache_root()?, account, container, prefix)
+    }
+
+    fn load_from(
+        root: &Path,
+        account: &str,
+        container: &str,
+        prefix: Option<&str>,
+    ) -> Result<Self> {
+        let location = format!("az://{}/{}/{}", account, container, prefix.unwrap_or(""));
+        let missing = || {
+            anyhow!(
+                "No cached listing of {}; run 'azst cache refresh {}' first",
+                location,
+                location
+            )
+        };
+        let path = listing_path(root, account, container);
+        if !path.exists() {
+            return Err(missing());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let listing: CachedListing = serde_json::from_str(&content)
+            .with_context(|| format!("{} is not a cached listing", path.display()))?;
+        if !prefix.unwrap_or("").starts_with(&listing.prefix) {
+            return Err(missing());
+        }
+        Ok(listing)
+    }
+
+    /// Containers of an account with a cached listing, by name
+    pub fn containers(account: &str) -> Result<Vec<String>> {
+        let dir = cache_root()?.join(account);
+        let mut names = Vec::new();
+        if dir.is_dir() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+                {
+                    if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_in(&cache_root()?)
+    }
+
+    fn save_in(&self, root: &Path) -> Result<()> {
+        let path = listing_path(root, &self.account, &self.container);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Written aside and renamed, so a crash never leaves half a listing
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        std::fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Best effort, for commands that cache a listing on the side: failing
+    /// to write it doesn't fail the listing, and a listing of a prefix
+    /// doesn't replace a broader one of the same container
+    pub fn save_quietly(&self) {
+        #[derive(Deserialize)]
+        struct Covered {
+            prefix: String,
+        }
+
+        let write = || -> Result<()> {
+            let root = cache_root()?;
+            let path = listing_path(&root, &self.account, &self.container);
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                let covered: Covered = serde_json::from_str(&content)?;
+                if self.prefix != covered.prefix && self.prefix.starts_with(&covered.prefix) {
+                    return Ok(());
+                }
+            }
+            self.save_in(&root)
+        };
+        if let Err(e) = write() {
+            tracing::debug!("Failed to cache the listing: {:#}", e);
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.created))
+    }
+
+    /// Warn on stderr when the listing is older than `STALE_AFTER`
+    pub fn warn_if_stale(&self) {
+        let age = self.age();
+        if age >= STALE_AFTER {
+            let location = format!("az://{}/{}/{}", self.account, self.container, self.prefix);
+            let hours = age.as_secs() / 3600;
+            let ago = if hours >= 48 {
+                format!("{} days", hours / 24)
+            } else {
+                format!("{} hours", hours)
+            };
+            eprintln!(
+                "{} The listing of {} was cached {} ago; run 'azst cache refresh {}' to update it",
+                "⚠".yellow(),
+                location,
+                ago,
+                location
+            );
+        }
+    }
+
+    /// Blobs whose names start with `prefix`
+    pub fn blobs_under(&self, prefix: Option<&str>) -> &[BlobInfo] {
+        let prefix = prefix.unwrap_or("");
+        let start = self
+            .blobs
+            .partition_point(|blob| blob.name.as_str() < prefix);
+        let len = self.blobs[start..].partition_point(|blob| blob.name.starts_with(prefix));
+        &self.blobs[start..start + len]
+    }
+
+    /// What a listing of `prefix` would return: with a delimiter, the blobs
+    /// directly under it and a `Prefix` for each "subdirectory", in name order
+    pub fn list(&self, prefix: Option<&str>, delimiter: Option<&str>) -> Vec<BlobItem> {
+        let blobs = self.blobs_under(prefix);
+        let Some(delimiter) = delimiter else {
+            return blobs.iter().cloned().map(BlobItem::Blob).collect();
+        };
+        let start = prefix.unwrap_or("").len();
+        let mut items = Vec::new();
+        let mut last_prefix: Option<&str> = None;
+        for blob in blobs {
+            match blob.name[start..].find(delimiter) {
+                Some(end) => {
+                    let directory = &blob.name[..start + end + delimiter.len()];
+                    if last_prefix != Some(directory) {
+                        items.push(BlobItem::Prefix(directory.to_string()));
+                        last_prefix = Some(directory);
+                    }
+                }
+                None => items.push(BlobItem::Blob(blob.clone())),
+            }
+        }
+        items
+    }
+}
+
+/// Remove the cached listings of a container, of every container of an
+/// account, or all of them; returns how many were removed
+pub fn clear(account: Option<&str>, container: Option<&str>) -> Result<usize> {
+    clear_in(&cache_root()?, account, container)
+}
+
+fn clear_in(root: &Path, account: Option<&str>, container: Option<&str>) -> Result<usize> {
+    let mut removed = 0;
+    let accounts: Vec<PathBuf> = match account {
+        Some(account) => vec![root.join(account)],
+        None if root.is_dir() => std::fs::read_dir(root)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?,
+        None => Vec::new(),
+    };
+    for dir in accounts.iter().filter(|dir| dir.is_dir()) {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_stem().and_then(|name| name.to_str());
+            if container.is_none_or(|container| name == Some(container)) {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::azure::BlobProperties;
+
+    fn blob(name: &str) -> BlobInfo {
+        BlobInfo {
+            name: name.to_string(),
+            properties: BlobProperties {
+                content_length: 10,
+                last_modified: "Mon, 01 Jan 2024 00:00:00 GMT".to_string(),
+                content_type: None,
+                last_modified_unix: 1_704_067_200,
+                content_md5: None,
+                content_encoding: None,
+                etag: None,
+                access_tier: Some("Hot".to_string()),
+                blob_type: None,
+                lease_state: None,
+            },
+            version_count: None,
+            tags: None,
+        }
+    }
+
+    fn listing() -> CachedListing {
+        let names = [
+            "logs/b.txt",
+            "a.txt",
+            "logs/2024/c.txt",
+            "logs/a.txt",
+            "logsx",
+        ];
+        CachedListing::new("acct", "data", None, names.into_iter().map(blob).collect())
+    }
+
+    fn names(items: &[BlobItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match item {
+                BlobItem::Blob(blob) => blob.name.clone(),
+                BlobItem::Prefix(prefix) => format!("{} (dir)", prefix),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_list_recursive_under_prefix() {
+        let listing = listing();
+        assert_eq!(
+            names(&listing.list(Some("logs/"), None)),
+            vec!["logs/2024/c.txt", "logs/a.txt", "logs/b.txt"]
+        );
+        assert_eq!(listing.list(None, None).len(), 5);
+        assert!(listing.list(Some("nothing/"), None).is_empty());
+    }
+
+    #[test]
+    fn test_list_with_delimiter_collapses_directories() {
+        let listing = listing();
+        assert_eq!(
+            names(&listing.list(None, Some("/"))),
+            vec!["a.txt", "logs/ (dir)", "logsx"]
+        );
+        assert_eq!(
+            names(&listing.list(Some("logs/"), Some("/"))),
+            vec!["logs/2024/ (dir)", "logs/a.txt", "logs/b.txt"]
+        );
+    }
+
+    #[test]
+    fn test_load_needs_a_listing_covering_the_prefix() {
+        let root = tempfile::tempdir().unwrap();
+        let partial = CachedListing::new("acct", "data", Some("logs/"), vec![blob("logs/a.txt")]);
+        partial.save_in(root.path()).unwrap();
+
+        let loaded = CachedListing::load_from(root.path(), "acct", "data", Some("logs/2024/"));
+        assert_eq!(loaded.unwrap().blobs.len(), 1);
+        let error = CachedListing::load_from(root.path(), "acct", "data", None).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("azst cache refresh az://acct/data/"));
+        assert!(CachedListing::load_from(root.path(), "acct", "other", None).is_err());
+    }
+
+    #[test]
+    fn test_clear_by_container_and_account() {
+        let root = tempfile::tempdir().unwrap();
+        for (account, container) in [("a", "one"), ("a", "two"), ("b", "one")] {
+            CachedListing::new(account, container, None, Vec::new())
+                .save_in(root.path())
+                .unwrap();
+        }
+        assert_eq!(clear_in(root.path(), Some("a"), Some("one")).unwrap(), 1);
+        assert_eq!(clear_in(root.path(), Some("a"), None).unwrap(), 1);
+        assert_eq!(clear_in(root.path(), None, None).unwrap(), 1);
+        assert_eq!(clear_in(root.path(), None, None).unwrap(), 0);
+    }
+}
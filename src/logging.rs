@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing_subscriber::EnvFilter;
+
+// ============================================================================
+// Logging - Diagnostics through tracing, status lines silenced by --quiet
+// ============================================================================
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Print a decorative status line (✓, →, ℹ, ...) unless --quiet was given
+///
+/// Requested output (listings, file contents, hashes) and warnings keep
+/// using `println!`, so scripts still get them under --quiet.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::logging::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Set up the tracing subscriber for the global -v/-q flags
+///
+/// Diagnostics go to stderr. RUST_LOG, when set, overrides the filter implied
+/// by the flags.
+pub fn init(verbose: u8, quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(default_filter(verbose, quiet)));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(crate::output::colors_enabled())
+        .with_target(verbose >= 2)
+        .init();
+}
+
+/// Keep status lines and progress bars out of stdout, e.g. while it carries
+/// a JSON document
+pub fn suppress_status() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+/// Whether status lines and progress bars should be suppressed
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// -v shows azcopy invocations, HTTP requests and retries; -vv adds
+/// everything the HTTP stack logs
+fn default_filter(verbose: u8, quiet: bool) -> &'static str {
+    if quiet {
+        return "error";
+    }
+    match verbose {
+        0 => "warn",
+        1 => "warn,azst=debug,azure_core=debug",
+        _ => "debug,azst=trace,azure_core=trace",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_filter() {
+        assert_eq!(default_filter(0, false), "warn");
+        assert_eq!(default_filter(3, true), "error");
+        assert!(default_filter(1, false).contains("azst=debug"));
+        assert!(default_filter(5, false).contains("azst=trace"));
+    }
+}
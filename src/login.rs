@@ -0,0 +1,517 @@
+use anyhow::{anyhow, Context, Result};
+use azure_core::auth::{AccessToken, TokenCredential};
+use azure_core::error::{Error as AzureError, ErrorKind};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+use crate::network;
+use crate::utils::write_private_file;
+
+// ============================================================================
+// Interactive Login - Device code and browser sign-in without the Azure CLI
+// ============================================================================
+
+/// Public client ID of the Azure CLI, which Microsoft Entra ID accepts from
+/// any native tool signing users in to Azure
+const CLIENT_ID: &str = "04b07795-8ddb-461a-bbee-02f9e1bf7b46";
+
+/// Tenant signed in to when none is given: any work or school account
+pub const DEFAULT_TENANT: &str = "organizations";
+
+/// Scope for blob data requests
+pub const STORAGE_SCOPE: &str = "https://storage.azure.com/.default";
+
+/// Requested at sign-in. The refresh token that comes back is exchanged for
+/// storage and management tokens as commands need them.
+const LOGIN_SCOPES: &str = "https://management.azure.com/.default offline_access openid profile";
+
+/// How long to wait for the browser to redirect back
+const BROWSER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How `azst auth login` signs the user in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoginFlow {
+    /// Open a browser on this machine
+    Browser,
+    /// Enter a code on any device, e.g. from an SSH session or container
+    DeviceCode,
+}
+
+impl fmt::Display for LoginFlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoginFlow::Browser => write!(f, "browser"),
+            LoginFlow::DeviceCode => write!(f, "device code"),
+        }
+    }
+}
+
+/// A sign-in kept between invocations, in a file only the current user can read
+#[derive(Serialize, Deserialize)]
+pub struct StoredLogin {
+    pub tenant: String,
+    pub flow: LoginFlow,
+    /// Subscription to list storage accounts in, instead of asking `az`
+    #[serde(default)]
+    pub subscription: Option<String>,
+    refresh_token: String,
+}
+
+impl StoredLogin {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("azst").join("login.json"))
+    }
+
+    /// The current sign-in, if `azst auth login` was run
+    pub fn load() -> Option<Self> {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow!("No config directory"))?;
+        write_private_file(&path, &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Forget the sign-in, returning whether there was one
+    pub fn remove() -> Result<bool> {
+        match Self::path() {
+            Some(path) if path.exists() => {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Successful response from the token endpoint
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+    refresh_token: Option<String>,
+}
+
+/// Error response from the token endpoint
+#[derive(Debug, Deserialize)]
+struct TokenError {
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.error_description.lines().next() {
+            Some(description) => write!(f, "{}", description),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+/// Response to a device code request
+#[derive(Debug, Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    /// Instructions for the user, with the URL and code to enter
+    message: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+fn endpoint(tenant: &str, name: &str) -> String {
+    format!(
+        "https://login.microsoftonline.com/{}/oauth2/v2.0/{}",
+        tenant, name
+    )
+}
+
+/// POST to the token endpoint, separating errors Entra ID reports from
+/// failures to reach it
+async fn request_token(
+    tenant: &str,
+    form: &[(&str, &str)],
+) -> Result<std::result::Result<TokenResponse, TokenError>> {
+    let response = network::http_client()?
+        .post(endpoint(tenant, "token"))
+        .form(form)
+        .send()
+        .await
+        .context("Failed to reach Microsoft Entra ID")?;
+
+    if response.status().is_success() {
+        Ok(Ok(response
+            .json()
+            .await
+            .context("Failed to parse token response")?))
+    } else {
+        Ok(Err(response
+            .json()
+            .await
+            .context("Failed to parse token error response")?))
+    }
+}
+
+/// Sign in interactively and keep the resulting refresh token
+pub async fn login(
+    flow: LoginFlow,
+    tenant: &str,
+    subscription: Option<String>,
+) -> Result<AccessToken> {
+    let response = match flow {
+        LoginFlow::Browser => browser_login(tenant).await?,
+        LoginFlow::DeviceCode => device_code_login(tenant).await?,
+    };
+    let refresh_token = response
+        .refresh_token
+        .clone()
+        .ok_or_else(|| anyhow!("Microsoft Entra ID did not return a refresh token"))?;
+
+    StoredLogin {
+        tenant: tenant.to_string(),
+        flow,
+        subscription,
+        refresh_token,
+    }
+    .save()
+    .context("Failed to save sign-in")?;
+
+    Ok(to_access_token(response))
+}
+
+async fn device_code_login(tenant: &str) -> Result<TokenResponse> {
+    let code: DeviceCode = network::http_client()?
+        .post(endpoint(tenant, "devicecode"))
+        .form(&[("client_id", CLIENT_ID), ("scope", LOGIN_SCOPES)])
+        .send()
+        .await
+        .context("Failed to reach Microsoft Entra ID")?
+        .error_for_status()
+        .context("Failed to start device code sign-in")?
+        .json()
+        .await
+        .context("Failed to parse device code response")?;
+
+    // Not a status line: without it there is no way to sign in
+    println!("{} {}", "→".cyan(), code.message);
+
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = Duration::from_secs(code.interval.max(1));
+    loop {
+        tokio::time::sleep(interval).await;
+        let form = [
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("client_id", CLIENT_ID),
+            ("device_code", code.device_code.as_str()),
+        ];
+        match request_token(tenant, &form).await? {
+            Ok(response) => return Ok(response),
+            Err(e) if e.error == "authorization_pending" => {}
+            Err(e) if e.error == "slow_down" => interval += Duration::from_secs(5),
+            Err(e) => return Err(anyhow!("Sign-in failed: {}", e)),
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow!(
+                "The sign-in code expired. Run 'azst auth login' again"
+            ));
+        }
+    }
+}
+
+async fn browser_login(tenant: &str) -> Result<TokenResponse> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .context("Failed to listen for the sign-in redirect")?;
+    let redirect_uri = format!("http://localhost:{}", listener.local_addr()?.port());
+
+    // PKCE: only whoever holds the verifier can redeem the code
+    let verifier = random_string()?;
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    let state = random_string()?;
+
+    let url = reqwest::Url::parse_with_params(
+        &endpoint(tenant, "authorize"),
+        &[
+            ("client_id", CLIENT_ID),
+            ("response_type", "code"),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("scope", LOGIN_SCOPES),
+            ("state", state.as_str()),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            ("prompt", "select_account"),
+        ],
+    )?;
+
+    println!(
+        "{} Opening a browser to sign in. If it doesn't open, visit:\n  {}",
+        "→".cyan(),
+        url
+    );
+    if let Err(e) = open_browser(url.as_str()) {
+        tracing::debug!("Failed to open a browser: {}", e);
+    }
+
+    let code = tokio::time::timeout(BROWSER_TIMEOUT, receive_code(&listener, &state))
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for the browser sign-in"))??;
+
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("client_id", CLIENT_ID),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("code_verifier", verifier.as_str()),
+        ("scope", LOGIN_SCOPES),
+    ];
+    request_token(tenant, &form)
+        .await?
+        .map_err(|e| anyhow!("Sign-in failed: {}", e))
+}
+
+/// Wait for the browser to come back with an authorization code
+///
+/// Requests without one (e.g. for /favicon.ico) are answered and ignored.
+async fn receive_code(listener: &TcpListener, state: &str) -> Result<String> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request_line = String::new();
+        BufReader::new(&mut stream)
+            .read_line(&mut request_line)
+            .await?;
+
+        let outcome = request_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|target| reqwest::Url::parse(&format!("http://localhost{}", target)).ok())
+            .and_then(|url| parse_redirect(&url, state));
+
+        let body = match &outcome {
+            Some(Ok(_)) => "Signed in to azst. You can close this window.",
+            Some(Err(_)) => "Sign-in failed. See the terminal for details.",
+            None => "",
+        };
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/plain; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+
+        if let Some(outcome) = outcome {
+            return outcome;
+        }
+    }
+}
+
+/// The authorization code in a redirect, an error if sign-in failed, or None
+/// for requests that aren't the redirect
+fn parse_redirect(url: &reqwest::Url, state: &str) -> Option<Result<String>> {
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    if let Some(error) = param("error") {
+        let description = param("error_description").unwrap_or(error);
+        return Some(Err(anyhow!("Sign-in failed: {}", description)));
+    }
+    let code = param("code")?;
+    if param("state").as_deref() != Some(state) {
+        return Some(Err(anyhow!(
+            "Sign-in failed: the redirect did not come from this login attempt"
+        )));
+    }
+    Some(Ok(code))
+}
+
+fn open_browser(url: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut cmd = std::process::Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = std::process::Command::new("rundll32");
+        cmd.arg("url.dll,FileProtocolHandler");
+        cmd
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut cmd = std::process::Command::new("xdg-open");
+
+    cmd.arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+/// 32 random bytes, URL-safe, for the PKCE verifier and CSRF state
+fn random_string() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    openssl::rand::rand_bytes(&mut bytes)?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn to_access_token(response: TokenResponse) -> AccessToken {
+    let expires_on = time::OffsetDateTime::now_utc() + time::Duration::seconds(response.expires_in);
+    AccessToken::new(response.access_token, expires_on)
+}
+
+/// Credential backed by the refresh token `azst auth login` stored
+///
+/// Each refresh token is exchanged for an access token to whichever resource
+/// is asked for; Entra ID may rotate it along the way, in which case the new
+/// one replaces it on disk.
+pub struct LoginCredential {
+    login: tokio::sync::Mutex<StoredLogin>,
+}
+
+impl LoginCredential {
+    pub fn new(login: StoredLogin) -> Self {
+        Self {
+            login: tokio::sync::Mutex::new(login),
+        }
+    }
+}
+
+impl fmt::Debug for LoginCredential {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LoginCredential").finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenCredential for LoginCredential {
+    async fn get_token(&self, scopes: &[&str]) -> Result<AccessToken, AzureError> {
+        let mut login = self.login.lock().await;
+        let scope = format!("{} offline_access", scopes.join(" "));
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("client_id", CLIENT_ID),
+            ("refresh_token", login.refresh_token.as_str()),
+            ("scope", scope.as_str()),
+        ];
+
+        let response = match request_token(&login.tenant, &form).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                return Err(AzureError::new(
+                    ErrorKind::Credential,
+                    format!("{}. Run 'azst auth login' to sign in again", e),
+                ))
+            }
+            Err(e) => return Err(AzureError::new(ErrorKind::Credential, format!("{:#}", e))),
+        };
+
+        if let Some(rotated) = &response.refresh_token {
+            if *rotated != login.refresh_token {
+                login.refresh_token = rotated.clone();
+                if let Err(e) = login.save() {
+                    tracing::debug!("Failed to save rotated refresh token: {:#}", e);
+                }
+            }
+        }
+        Ok(to_access_token(response))
+    }
+
+    async fn clear_cache(&self) -> Result<(), AzureError> {
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Token Claims - Who an access token was issued to
+// ============================================================================
+
+/// The claims of an access token `azst auth status` reports
+#[derive(Debug, Default, Deserialize)]
+pub struct TokenClaims {
+    /// Tenant ID
+    #[serde(default)]
+    pub tid: Option<String>,
+    #[serde(default)]
+    upn: Option<String>,
+    #[serde(default)]
+    unique_name: Option<String>,
+    #[serde(default)]
+    preferred_username: Option<String>,
+    /// Application ID, for service principals and managed identities
+    #[serde(default)]
+    appid: Option<String>,
+    /// Object ID of the user or service principal
+    #[serde(default)]
+    oid: Option<String>,
+}
+
+impl TokenClaims {
+    /// Decode the payload of a JWT access token, without verifying it
+    pub fn from_token(token: &str) -> Result<Self> {
+        let payload = token
+            .split('.')
+            .nth(1)
+            .ok_or_else(|| anyhow!("Access token is not a JWT"))?;
+        let json = URL_SAFE_NO_PAD
+            .decode(payload.trim_end_matches('='))
+            .context("Access token payload is not valid base64")?;
+        serde_json::from_slice(&json).context("Failed to parse access token claims")
+    }
+
+    /// User name for users, application ID for service principals
+    pub fn identity(&self) -> Option<&str> {
+        self.upn
+            .as_deref()
+            .or(self.preferred_username.as_deref())
+            .or(self.unique_name.as_deref())
+            .or(self.appid.as_deref())
+            .or(self.oid.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redirect() {
+        let url = reqwest::Url::parse("http://localhost/?code=abc&state=xyz").unwrap();
+        assert_eq!(parse_redirect(&url, "xyz").unwrap().unwrap(), "abc");
+        assert!(parse_redirect(&url, "other").unwrap().is_err());
+
+        let url = reqwest::Url::parse(
+            "http://localhost/?error=access_denied&error_description=User+cancelled",
+        )
+        .unwrap();
+        let error = parse_redirect(&url, "xyz").unwrap().unwrap_err();
+        assert!(error.to_string().contains("User cancelled"));
+
+        let url = reqwest::Url::parse("http://localhost/favicon.ico").unwrap();
+        assert!(parse_redirect(&url, "xyz").is_none());
+    }
+
+    #[test]
+    fn test_token_claims() {
+        let payload = URL_SAFE_NO_PAD
+            .encode(r#"{"tid":"tenant-id","upn":"ana@contoso.com","appid":"app-id"}"#);
+        let claims = TokenClaims::from_token(&format!("header.{}.signature", payload)).unwrap();
+        assert_eq!(claims.tid.as_deref(), Some("tenant-id"));
+        assert_eq!(claims.identity(), Some("ana@contoso.com"));
+
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"tid":"tenant-id","appid":"app-id"}"#);
+        let claims = TokenClaims::from_token(&format!("header.{}.signature", payload)).unwrap();
+        assert_eq!(claims.identity(), Some("app-id"));
+
+        assert!(TokenClaims::from_token("opaque").is_err());
+    }
+}
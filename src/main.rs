@@ -1,15 +1,8 @@
 use anyhow::Result;
-use clap::Parser;
 use std::io::{self, ErrorKind};
 
-mod azcopy_output;
-mod azure;
-mod cli;
-mod commands;
-mod output;
-mod utils;
-
-use cli::Cli;
+use azst::cli::Cli;
+use azst::{audit_log, azure, error, logging, output, prompt};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -31,10 +24,37 @@ async fn main() -> Result<()> {
         default_panic(panic_info);
     }));
 
-    let cli = Cli::parse();
+    let cli = Cli::parse_args();
+    output::set_deterministic(cli.deterministic);
+    output::set_color(cli.color, cli.quiet);
+    prompt::set_non_interactive(cli.non_interactive);
+    logging::init(cli.verbose, cli.quiet);
+    output::set_format(cli.format);
+    output::set_progress_mode(cli.progress);
+    if cli.format == output::OutputFormat::Json {
+        logging::suppress_status();
+    }
+    if let Some(retries) = cli.retries {
+        azure::set_max_retries(retries);
+    }
 
-    match cli.run().await {
-        Ok(_) => {}
+    let result = cli
+        .run_cancellable()
+        .await
+        .and_then(|()| cli.check_strict());
+    cli.write_audit_log(&result);
+    // Quiet transfers end with at most one line, for cron mails and log scrapers
+    let summary = cli
+        .quiet
+        .then(|| cli.command.transfer_name())
+        .flatten()
+        .and_then(|command| audit_log::summary_line(command, &result));
+    match result {
+        Ok(_) => {
+            if let Some(summary) = summary {
+                eprintln!("{}", summary);
+            }
+        }
         Err(e) => {
             // Check if the error is a broken pipe error
             if let Some(io_err) = e.downcast_ref::<io::Error>() {
@@ -43,8 +63,11 @@ async fn main() -> Result<()> {
                     std::process::exit(0);
                 }
             }
-            eprintln!("Error: {:#}", e);
-            std::process::exit(1);
+            match summary {
+                Some(summary) => eprintln!("{}", summary),
+                None => eprintln!("Error: {:#}", e),
+            }
+            std::process::exit(error::exit_code(&e));
         }
     }
 
@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::azcopy_output::FailedTransfer;
+use crate::sync_state::Snapshot;
+
+// ============================================================================
+// Copy Manifest - Files a `cp --manifest` run has copied, for reruns to skip
+// ============================================================================
+
+/// Files handed to azcopy at once; the manifest is written after each batch,
+/// so an interrupted run redoes at most one
+const BATCH_FILES: usize = 1000;
+
+/// Longest `--include-path` list passed to azcopy, well under the limit on
+/// the length of a single command-line argument
+const BATCH_ARG_BYTES: usize = 64 * 1024;
+
+/// What one copy has transferred so far, by path relative to its source
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CopyManifest {
+    pub source: String,
+    pub destination: String,
+    /// Each file as it was when copied; a file that changed since is copied again
+    pub completed: Snapshot,
+}
+
+impl CopyManifest {
+    /// Load the manifest of the copy from `source` to `destination`, or
+    /// start one if the file doesn't exist yet
+    pub fn load(path: &Path, source: &str, destination: &str) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self {
+                source: source.to_string(),
+                destination: destination.to_string(),
+                completed: Snapshot::new(),
+            });
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest '{}'", path.display()))?;
+        let manifest: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Manifest '{}' is not valid JSON", path.display()))?;
+        if manifest.source != source || manifest.destination != destination {
+            return Err(anyhow!(
+                "Manifest '{}' records a copy from {} to {}; use another file for this one",
+                path.display(),
+                manifest.source,
+                manifest.destination
+            ));
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest through a temporary file, so a run killed while
+    /// saving leaves the previous version intact
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write manifest '{}'", path.display()))?;
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("Failed to write manifest '{}'", path.display()))
+    }
+
+    /// The files of `source` not copied yet, or changed since they were
+    pub fn pending(&self, source: &Snapshot) -> Vec<String> {
+        source
+            .iter()
+            .filter(|(relative, state)| {
+                self.completed.get(*relative).is_none_or(|copied| {
+                    copied.size != state.size || copied.modified != state.modified
+                })
+            })
+            .map(|(relative, _)| relative.clone())
+            .collect()
+    }
+
+    /// Record the files of a finished batch, except those that failed
+    pub fn record_batch(&mut self, batch: &[String], source: &Snapshot, failed: &[FailedTransfer]) {
+        for relative in batch {
+            if failed
+                .iter()
+                .any(|transfer| is_transfer_of(transfer, relative))
+            {
+                continue;
+            }
+            if let Some(state) = source.get(relative) {
+                self.completed.insert(relative.clone(), state.clone());
+            }
+        }
+    }
+}
+
+/// Whether azcopy's record of a transfer is the one of the file at `relative`
+fn is_transfer_of(transfer: &FailedTransfer, relative: &str) -> bool {
+    let source = percent_encoding::percent_decode_str(&transfer.source).decode_utf8_lossy();
+    let source = source
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .replace('\\', "/");
+    source == relative || source.ends_with(&format!("/{}", relative))
+}
+
+/// Split the files to copy into batches for azcopy's `--include-path`
+pub fn batches(paths: &[String]) -> Vec<Vec<String>> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<String> = Vec::new();
+    let mut bytes = 0;
+    for path in paths {
+        if !batch.is_empty()
+            && (batch.len() == BATCH_FILES || bytes + path.len() + 1 > BATCH_ARG_BYTES)
+        {
+            batches.push(std::mem::take(&mut batch));
+            bytes = 0;
+        }
+        bytes += path.len() + 1;
+        batch.push(path.clone());
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_state::EntryState;
+    use tempfile::TempDir;
+
+    fn entry(size: u64, modified: i64) -> EntryState {
+        EntryState {
+            size,
+            modified,
+            md5: None,
+        }
+    }
+
+    #[test]
+    fn test_manifest_skips_copied_files_until_they_change() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("job.json");
+        let mut source = Snapshot::new();
+        source.insert("a.txt".to_string(), entry(1, 10));
+        source.insert("dir/b.txt".to_string(), entry(2, 20));
+        source.insert("dir/c d.txt".to_string(), entry(3, 30));
+
+        let mut manifest = CopyManifest::load(&path, "/data", "az://acct/c/").unwrap();
+        assert_eq!(manifest.pending(&source).len(), 3);
+        let failed = vec![FailedTransfer {
+            source: "https://acct.blob.core.windows.net/c/data/dir/c%20d.txt".to_string(),
+            destination: String::new(),
+            error_code: 503,
+        }];
+        let batch = manifest.pending(&source);
+        manifest.record_batch(&batch, &source, &failed);
+        manifest.save(&path).unwrap();
+
+        let manifest = CopyManifest::load(&path, "/data", "az://acct/c/").unwrap();
+        assert_eq!(manifest.pending(&source), vec!["dir/c d.txt"]);
+        source.insert("a.txt".to_string(), entry(1, 11));
+        assert_eq!(manifest.pending(&source), vec!["a.txt", "dir/c d.txt"]);
+
+        let error = CopyManifest::load(&path, "/other", "az://acct/c/").unwrap_err();
+        assert!(error.to_string().contains("records a copy from /data"));
+    }
+
+    #[test]
+    fn test_batches() {
+        let paths: Vec<String> = (0..2500).map(|i| format!("f{}", i)).collect();
+        let sizes: Vec<usize> = batches(&paths).iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![1000, 1000, 500]);
+
+        let long: Vec<String> = (0..3).map(|_| "x".repeat(40 * 1024)).collect();
+        assert_eq!(batches(&long).len(), 3);
+        assert!(batches(&[]).is_empty());
+    }
+}
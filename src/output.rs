@@ -1,5 +1,269 @@
 use colored::*;
+use indicatif::{MultiProgress, ProgressBar, ProgressFinish, ProgressStyle};
+use std::borrow::Cow;
 use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use crate::utils::{format_count, format_size};
+
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// Whether output is colored, decided once by `set_color`
+static COLORS: AtomicBool = AtomicBool::new(false);
+
+/// `--format` from the command line
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// `--progress` from the command line
+static PROGRESS: OnceLock<ProgressMode> = OnceLock::new();
+
+/// The bars of the files being transferred, under --progress per-file
+static FILE_BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Set once the header row of CSV/TSV output has been printed, so listings
+/// spread over several writers (one per directory) get a single header
+static HEADER_WRITTEN: AtomicBool = AtomicBool::new(false);
+
+/// Set by `ls -0`: every entry is a bare URI terminated by NUL
+static NULL_TERMINATED: AtomicBool = AtomicBool::new(false);
+
+/// Output format for listings (ls, du) and dry-run plans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns, colored on a terminal
+    Text,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+    /// One JSON object per entry; a single document for dry-run plans
+    Json,
+}
+
+/// When to color output, chosen with --color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color on a terminal, unless NO_COLOR or CLICOLOR=0 is set
+    #[default]
+    Auto,
+    /// Color even when piped, as with CLICOLOR_FORCE
+    Always,
+    /// Never color
+    Never,
+}
+
+/// How transfers show their progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ProgressMode {
+    /// One bar for the whole transfer
+    #[default]
+    Aggregate,
+    /// A bar for each file being transferred, for the transfers azst makes
+    /// itself rather than through azcopy
+    PerFile,
+}
+
+/// Use `format` for every listing written by this process
+pub fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// The format chosen with --format, text by default
+pub fn output_format() -> OutputFormat {
+    FORMAT.get().copied().unwrap_or(OutputFormat::Text)
+}
+
+pub fn set_progress_mode(mode: ProgressMode) {
+    let _ = PROGRESS.set(mode);
+}
+
+/// The progress display chosen with --progress, aggregate by default
+pub fn progress_mode() -> ProgressMode {
+    PROGRESS.get().copied().unwrap_or_default()
+}
+
+/// A bar for one file being transferred, of `size` bytes if known
+///
+/// Shown with the bars of the other active files under --progress per-file
+/// and hidden otherwise, as well as under --quiet and --deterministic. The
+/// bar disappears once dropped, so only files still in flight are listed.
+pub fn file_progress_bar(name: &str, size: Option<u64>) -> ProgressBar {
+    if progress_mode() != ProgressMode::PerFile || crate::logging::is_quiet() || is_deterministic()
+    {
+        return ProgressBar::hidden();
+    }
+    let (progress_bar, template) = match size {
+        Some(size) => (
+            ProgressBar::new(size),
+            "[{bar:30.cyan/blue}] {percent:>3}% {bytes:>10}/{total_bytes:<10} {msg}",
+        ),
+        None => (
+            ProgressBar::no_length(),
+            "{spinner:.green} {bytes:>10} {msg}",
+        ),
+    };
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .expect("Invalid progress bar template")
+            .progress_chars("#>-"),
+    );
+    progress_bar.set_message(name.to_string());
+    FILE_BARS
+        .get_or_init(MultiProgress::new)
+        .add(progress_bar.with_finish(ProgressFinish::AndClear))
+}
+
+/// Let the next listing print its own header row, as each command of an
+/// interactive shell writes a separate table
+pub fn reset_header() {
+    HEADER_WRITTEN.store(false, Ordering::Relaxed);
+}
+
+/// Terminate listed entries with NUL instead of newline, for `xargs -0`
+pub fn set_null_terminated(enabled: bool) {
+    NULL_TERMINATED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether listings are parsed by another program rather than read by a
+/// person, so notes like "No objects found" must stay out of stdout
+pub fn is_machine_readable() -> bool {
+    NULL_TERMINATED.load(Ordering::Relaxed) || output_format() != OutputFormat::Text
+}
+
+/// Switch to output that is identical across runs, for --deterministic
+///
+/// Listings are sorted, colors and progress bars are off, and details that
+/// change from one run to the next (azcopy job IDs, log paths) are left out.
+pub fn set_deterministic(deterministic: bool) {
+    DETERMINISTIC.store(deterministic, Ordering::Relaxed);
+}
+
+/// Decide once whether this process colors its output, for every status
+/// line, warning, listing, progress bar and log line alike
+///
+/// --deterministic output is never colored, and --quiet output only with
+/// `--color always`, as it ends up in cron mails and log files. Call after
+/// `set_deterministic`.
+pub fn set_color(choice: ColorChoice, quiet: bool) {
+    let enabled = !is_deterministic()
+        && match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                !quiet
+                    && auto_colors(
+                        std::env::var("NO_COLOR").ok().as_deref(),
+                        std::env::var("CLICOLOR").ok().as_deref(),
+                        std::env::var("CLICOLOR_FORCE").ok().as_deref(),
+                        io::stdout().is_terminal(),
+                    )
+            }
+        };
+    COLORS.store(enabled, Ordering::Relaxed);
+    colored::control::set_override(enabled);
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
+
+/// Whether output is colored
+pub fn colors_enabled() -> bool {
+    COLORS.load(Ordering::Relaxed)
+}
+
+/// `--color auto` from the environment: a non-empty NO_COLOR turns colors
+/// off and CLICOLOR_FORCE on, otherwise they follow CLICOLOR and whether
+/// stdout is a terminal (https://no-color.org, https://bixense.com/clicolors)
+fn auto_colors(
+    no_color: Option<&str>,
+    clicolor: Option<&str>,
+    clicolor_force: Option<&str>,
+    terminal: bool,
+) -> bool {
+    let set = |value: Option<&str>| value.is_some_and(|value| !value.is_empty() && value != "0");
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    if set(clicolor_force) {
+        return true;
+    }
+    clicolor != Some("0") && terminal
+}
+
+/// Whether --deterministic was given
+pub fn is_deterministic() -> bool {
+    DETERMINISTIC.load(Ordering::Relaxed)
+}
+
+/// Whether output is styled for a person at a terminal rather than a pipe
+/// or golden file
+pub fn is_interactive() -> bool {
+    !is_deterministic() && io::stdout().is_terminal()
+}
+
+/// A column of the long blob listing, selected with `ls --columns`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Column {
+    /// Size in bytes
+    Size,
+    /// Content type
+    Type,
+    /// Last modified time
+    Modified,
+    /// Entity tag, which changes whenever the blob is written
+    Etag,
+    /// Access tier (Hot, Cool, Cold, Archive)
+    Tier,
+    /// Block, page or append blob
+    BlobType,
+    /// Lease state (available, leased, ...)
+    Lease,
+    /// Number of versions, including the current one
+    Versions,
+    /// az:// URI
+    Name,
+}
+
+impl Column {
+    /// Columns of `ls -e`: the long listing plus the ETag
+    pub const LONG_WITH_ETAG: &'static [Column] = &[
+        Column::Size,
+        Column::Type,
+        Column::Modified,
+        Column::Etag,
+        Column::Name,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Size => "Size",
+            Column::Type => "Type",
+            Column::Modified => "Modified",
+            Column::Etag => "ETag",
+            Column::Tier => "Tier",
+            Column::BlobType => "Blob Type",
+            Column::Lease => "Lease",
+            Column::Versions => "Versions",
+            Column::Name => "Name",
+        }
+    }
+
+    /// Padding for the column; the name comes last and isn't padded
+    pub fn width(&self) -> usize {
+        match self {
+            Column::Size => 10,
+            Column::Type => 15,
+            Column::Modified => 20,
+            Column::Etag => 20,
+            Column::Tier => 8,
+            Column::BlobType => 11,
+            Column::Lease => 10,
+            Column::Versions => 8,
+            Column::Name => 0,
+        }
+    }
+}
 
 /// Trait for output formatting strategies
 /// Allows different output formats (TTY with colors, plain text, JSON, etc.)
@@ -22,17 +286,29 @@ pub trait OutputWriter: Send {
     /// Write a blob entry
     fn write_blob(&self, uri: &str, size: &str, content_type: &str, modified: &str, long: bool);
 
+    /// Write a signed URL for the blob entry written just before
+    fn write_signed_url(&self, url: &str);
+
     /// Write a prefix/directory entry
     fn write_prefix(&self, uri: &str, long: bool);
 
+    /// Write a blob entry as the selected columns, one cell per column
+    fn write_blob_row(&self, columns: &[Column], cells: &[String]);
+
+    /// Write a prefix/directory entry in a listing with the selected columns
+    fn write_prefix_row(&self, columns: &[Column], uri: &str);
+
     /// Write a local file entry
     fn write_local_file(&self, name: &str, size: &str, file_type: &str, long: bool);
 
     /// Write a disk usage entry
-    fn write_disk_usage(&self, size: &str, path: &str);
+    fn write_disk_usage(&self, size: &str, details: &[(&str, String)], path: &str);
 
     /// Write a disk usage total entry
-    fn write_disk_usage_total(&self, size: &str, path: &str);
+    fn write_disk_usage_total(&self, size: &str, details: &[(&str, String)], path: &str);
+
+    /// Write the object count and total bytes after a blob listing
+    fn write_summary(&self, objects: u64, bytes: u64);
 }
 
 /// TTY writer with colors and formatting for human reading
@@ -92,6 +368,10 @@ impl OutputWriter for TtyWriter {
         }
     }
 
+    fn write_signed_url(&self, url: &str) {
+        println!("  {} {}", "↳".dimmed(), url);
+    }
+
     fn write_prefix(&self, uri: &str, long: bool) {
         if long {
             println!(
@@ -106,6 +386,38 @@ impl OutputWriter for TtyWriter {
         }
     }
 
+    fn write_blob_row(&self, columns: &[Column], cells: &[String]) {
+        let formatted: Vec<String> = columns
+            .iter()
+            .zip(cells)
+            .map(|(column, cell)| {
+                let styled = match column {
+                    Column::Size => cell.green(),
+                    Column::Type => cell.yellow(),
+                    Column::Name => cell.cyan(),
+                    _ => cell.dimmed(),
+                };
+                format!("{:<width$}", styled, width = column.width())
+            })
+            .collect();
+        println!("{}", formatted.join(" "));
+    }
+
+    fn write_prefix_row(&self, columns: &[Column], uri: &str) {
+        let formatted: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let styled = match column {
+                    Column::Type => "DIR".blue(),
+                    Column::Name => uri.blue().bold(),
+                    _ => "-".dimmed(),
+                };
+                format!("{:<width$}", styled, width = column.width())
+            })
+            .collect();
+        println!("{}", formatted.join(" "));
+    }
+
     fn write_local_file(&self, name: &str, size: &str, file_type: &str, long: bool) {
         if long {
             let display_name = if file_type == "dir" {
@@ -129,17 +441,22 @@ impl OutputWriter for TtyWriter {
         }
     }
 
-    fn write_disk_usage(&self, size: &str, path: &str) {
-        println!("{}\t{}", size.green(), path.cyan());
+    fn write_disk_usage(&self, size: &str, details: &[(&str, String)], path: &str) {
+        println!("{}\t{}{}", size.green(), detail_cells(details), path.cyan());
     }
 
-    fn write_disk_usage_total(&self, size: &str, path: &str) {
+    fn write_disk_usage_total(&self, size: &str, details: &[(&str, String)], path: &str) {
         println!(
-            "{}\t{}",
+            "{}\t{}{}",
             size.green().bold(),
+            detail_cells(details),
             format!("total: {}", path).bold()
         );
     }
+
+    fn write_summary(&self, objects: u64, bytes: u64) {
+        println!("{}", summary_line(objects, bytes).bold());
+    }
 }
 
 /// Plain text writer for piping/scripting (no colors)
@@ -188,6 +505,10 @@ impl OutputWriter for PlainWriter {
         }
     }
 
+    fn write_signed_url(&self, url: &str) {
+        println!("{}", url);
+    }
+
     fn write_prefix(&self, uri: &str, long: bool) {
         if long {
             println!("{:<10} {:<15} {:<20} {}", "-", "DIR", "-", uri);
@@ -196,6 +517,30 @@ impl OutputWriter for PlainWriter {
         }
     }
 
+    fn write_blob_row(&self, columns: &[Column], cells: &[String]) {
+        let formatted: Vec<String> = columns
+            .iter()
+            .zip(cells)
+            .map(|(column, cell)| format!("{:<width$}", cell, width = column.width()))
+            .collect();
+        println!("{}", formatted.join(" "));
+    }
+
+    fn write_prefix_row(&self, columns: &[Column], uri: &str) {
+        let formatted: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                let cell = match column {
+                    Column::Type => "DIR",
+                    Column::Name => uri,
+                    _ => "-",
+                };
+                format!("{:<width$}", cell, width = column.width())
+            })
+            .collect();
+        println!("{}", formatted.join(" "));
+    }
+
     fn write_local_file(&self, name: &str, size: &str, file_type: &str, long: bool) {
         if long {
             println!("{:<10} {:<10} {}", size, file_type, name);
@@ -204,18 +549,311 @@ impl OutputWriter for PlainWriter {
         }
     }
 
-    fn write_disk_usage(&self, size: &str, path: &str) {
-        println!("{}\t{}", size, path);
+    fn write_disk_usage(&self, size: &str, details: &[(&str, String)], path: &str) {
+        println!("{}\t{}{}", size, detail_cells(details), path);
+    }
+
+    fn write_disk_usage_total(&self, size: &str, details: &[(&str, String)], path: &str) {
+        println!("{}\t{}total: {}", size, detail_cells(details), path);
+    }
+
+    fn write_summary(&self, objects: u64, bytes: u64) {
+        println!("{}", summary_line(objects, bytes));
+    }
+}
+
+/// NUL-terminated writer for `ls -0`
+///
+/// Only URIs and paths are written, each followed by a NUL byte, so names
+/// containing spaces or newlines survive `xargs -0` and `rm -I`.
+pub struct NullWriter;
+
+impl NullWriter {
+    fn write_entry(&self, entry: &str) {
+        print!("{}\0", entry);
+    }
+}
+
+impl OutputWriter for NullWriter {
+    fn write_header(&self, _text: &str) {}
+
+    fn write_table_header(&self, _columns: &[(&str, usize)]) {}
+
+    fn write_separator(&self, _length: usize) {}
+
+    fn write_storage_account(
+        &self,
+        name: &str,
+        _location: &str,
+        _resource_group: &str,
+        _long: bool,
+    ) {
+        self.write_entry(&format!("az://{}/", name));
+    }
+
+    fn write_container(&self, account: &str, name: &str, _modified: &str, _long: bool) {
+        self.write_entry(&format!("az://{}/{}/", account, name));
+    }
+
+    fn write_blob(
+        &self,
+        uri: &str,
+        _size: &str,
+        _content_type: &str,
+        _modified: &str,
+        _long: bool,
+    ) {
+        self.write_entry(uri);
+    }
+
+    fn write_blob_row(&self, columns: &[Column], cells: &[String]) {
+        if let Some(uri) = columns
+            .iter()
+            .zip(cells)
+            .find_map(|(column, cell)| (*column == Column::Name).then_some(cell))
+        {
+            self.write_entry(uri);
+        }
+    }
+
+    fn write_signed_url(&self, url: &str) {
+        self.write_entry(url);
+    }
+
+    fn write_prefix(&self, uri: &str, _long: bool) {
+        self.write_entry(uri);
+    }
+
+    fn write_prefix_row(&self, _columns: &[Column], uri: &str) {
+        self.write_entry(uri);
+    }
+
+    fn write_local_file(&self, name: &str, _size: &str, _file_type: &str, _long: bool) {
+        self.write_entry(name);
+    }
+
+    fn write_disk_usage(&self, _size: &str, _details: &[(&str, String)], path: &str) {
+        self.write_entry(path);
+    }
+
+    fn write_disk_usage_total(&self, _size: &str, _details: &[(&str, String)], _path: &str) {}
+
+    fn write_summary(&self, _objects: u64, _bytes: u64) {}
+}
+
+/// Record writer for spreadsheets, DataFrames and `jq`
+///
+/// Each entry is one record: a comma- or tab-separated row, or a JSON object
+/// on a line of its own. The CSV/TSV header row is taken from the first
+/// entry written, so a listing must not mix entry types with different
+/// fields. Fields containing the delimiter, quotes or line breaks are quoted
+/// as in RFC 4180.
+pub struct RecordWriter {
+    delimiter: Option<char>,
+}
+
+impl RecordWriter {
+    fn write_record(&self, header: &[&str], fields: &[&str]) {
+        if self.delimiter.is_none() {
+            let record: serde_json::Map<String, serde_json::Value> = header
+                .iter()
+                .zip(fields)
+                .map(|(key, field)| (key.to_string(), field.to_string().into()))
+                .collect();
+            println!("{}", serde_json::Value::Object(record));
+            return;
+        }
+        if !HEADER_WRITTEN.swap(true, Ordering::Relaxed) {
+            println!("{}", self.join(header));
+        }
+        println!("{}", self.join(fields));
+    }
+
+    fn join(&self, fields: &[&str]) -> String {
+        join_fields(fields, self.delimiter.unwrap_or(','))
+    }
+
+    /// A du line: size, the extra columns asked for, then path
+    fn write_disk_usage_record(&self, size: &str, details: &[(&str, String)], path: &str) {
+        let mut header = vec!["size"];
+        let mut fields = vec![size];
+        for (name, value) in details {
+            header.push(name);
+            fields.push(value);
+        }
+        header.push("path");
+        fields.push(path);
+        self.write_record(&header, &fields);
     }
 
-    fn write_disk_usage_total(&self, size: &str, path: &str) {
-        println!("{}\ttotal: {}", size, path);
+    /// Header name of a selectable column, e.g. "blob_type"
+    fn column_key(column: &Column) -> String {
+        column.header().to_lowercase().replace(' ', "_")
     }
 }
 
-/// Factory function to create the appropriate writer based on output destination
+impl OutputWriter for RecordWriter {
+    fn write_header(&self, _text: &str) {
+        // Titles would break the table
+    }
+
+    fn write_table_header(&self, _columns: &[(&str, usize)]) {
+        // The header row comes from the first record
+    }
+
+    fn write_separator(&self, _length: usize) {}
+
+    fn write_storage_account(&self, name: &str, location: &str, resource_group: &str, long: bool) {
+        let uri = format!("az://{}/", name);
+        if long {
+            self.write_record(
+                &["uri", "location", "resource_group"],
+                &[&uri, location, resource_group],
+            );
+        } else {
+            self.write_record(&["uri"], &[&uri]);
+        }
+    }
+
+    fn write_container(&self, account: &str, name: &str, modified: &str, long: bool) {
+        let uri = format!("az://{}/{}/", account, name);
+        if long {
+            self.write_record(&["uri", "modified"], &[&uri, modified]);
+        } else {
+            self.write_record(&["uri"], &[&uri]);
+        }
+    }
+
+    fn write_blob(&self, uri: &str, size: &str, content_type: &str, modified: &str, long: bool) {
+        if long {
+            self.write_record(
+                &["size", "type", "modified", "uri"],
+                &[size, content_type, modified, uri],
+            );
+        } else {
+            self.write_record(&["uri"], &[uri]);
+        }
+    }
+
+    fn write_blob_row(&self, columns: &[Column], cells: &[String]) {
+        let keys: Vec<String> = columns.iter().map(Self::column_key).collect();
+        let header: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let fields: Vec<&str> = cells.iter().map(String::as_str).collect();
+        self.write_record(&header, &fields);
+    }
+
+    fn write_signed_url(&self, url: &str) {
+        // Only reached if a caller forgot to reject --signed-urls; keep the
+        // URL rather than drop it
+        self.write_record(&["signed_url"], &[url]);
+    }
+
+    fn write_prefix(&self, uri: &str, long: bool) {
+        if long {
+            self.write_record(&["size", "type", "modified", "uri"], &["", "DIR", "", uri]);
+        } else {
+            self.write_record(&["uri"], &[uri]);
+        }
+    }
+
+    fn write_prefix_row(&self, columns: &[Column], uri: &str) {
+        let keys: Vec<String> = columns.iter().map(Self::column_key).collect();
+        let header: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let fields: Vec<&str> = columns
+            .iter()
+            .map(|column| match column {
+                Column::Type => "DIR",
+                Column::Name => uri,
+                _ => "",
+            })
+            .collect();
+        self.write_record(&header, &fields);
+    }
+
+    fn write_local_file(&self, name: &str, size: &str, file_type: &str, long: bool) {
+        if long {
+            self.write_record(&["size", "type", "name"], &[size, file_type, name]);
+        } else {
+            self.write_record(&["name"], &[name]);
+        }
+    }
+
+    fn write_disk_usage(&self, size: &str, details: &[(&str, String)], path: &str) {
+        self.write_disk_usage_record(size, details, path);
+    }
+
+    fn write_disk_usage_total(&self, size: &str, details: &[(&str, String)], path: &str) {
+        self.write_disk_usage_record(size, details, &format!("total: {}", path));
+    }
+
+    fn write_summary(&self, objects: u64, bytes: u64) {
+        // A row of different fields would break a CSV/TSV table
+        if self.delimiter.is_none() {
+            let summary = serde_json::json!({
+                "summary": { "objects": objects, "bytes": bytes }
+            });
+            println!("{}", summary);
+        }
+    }
+}
+
+/// One CSV or TSV row, quoting fields that contain the delimiter, quotes or
+/// line breaks as in RFC 4180
+pub fn join_fields(fields: &[&str], delimiter: char) -> String {
+    fields
+        .iter()
+        .map(|field| quote_field(field, delimiter))
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string())
+}
+
+fn quote_field(field: &str, delimiter: char) -> Cow<'_, str> {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Values of du's extra columns, each followed by a tab
+fn detail_cells(details: &[(&str, String)]) -> String {
+    details
+        .iter()
+        .map(|(_, value)| format!("{}\t", value))
+        .collect()
+}
+
+/// "TOTAL: 1,532 objects, 19,756,849,152 bytes (18.4 GB)"
+fn summary_line(objects: u64, bytes: u64) -> String {
+    format!(
+        "TOTAL: {} objects, {} bytes ({})",
+        format_count(objects),
+        format_count(bytes),
+        format_size(bytes)
+    )
+}
+
+/// Factory function to create the appropriate writer based on --format and
+/// the output destination
 pub fn create_writer() -> Box<dyn OutputWriter> {
-    if io::stdout().is_terminal() {
+    if NULL_TERMINATED.load(Ordering::Relaxed) {
+        return Box::new(NullWriter);
+    }
+    match output_format() {
+        OutputFormat::Csv => {
+            return Box::new(RecordWriter {
+                delimiter: Some(','),
+            })
+        }
+        OutputFormat::Tsv => {
+            return Box::new(RecordWriter {
+                delimiter: Some('\t'),
+            })
+        }
+        OutputFormat::Json => return Box::new(RecordWriter { delimiter: None }),
+        OutputFormat::Text => {}
+    }
+    if is_interactive() {
         Box::new(TtyWriter)
     } else {
         Box::new(PlainWriter)
@@ -226,6 +864,23 @@ pub fn create_writer() -> Box<dyn OutputWriter> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_file_progress_bar_hidden_by_default() {
+        assert_eq!(progress_mode(), ProgressMode::Aggregate);
+        assert!(file_progress_bar("a.bin", Some(1024)).is_hidden());
+    }
+
+    #[test]
+    fn test_auto_colors() {
+        assert!(auto_colors(None, None, None, true));
+        assert!(!auto_colors(None, None, None, false));
+        assert!(!auto_colors(Some("1"), None, Some("1"), true));
+        assert!(auto_colors(Some(""), None, None, true));
+        assert!(!auto_colors(None, Some("0"), None, true));
+        assert!(auto_colors(None, Some("0"), Some("1"), false));
+        assert!(!auto_colors(None, None, Some("0"), false));
+    }
+
     #[test]
     fn test_tty_writer_creates_colored_output() {
         // This is more of a smoke test - actual output verification would need
@@ -235,6 +890,39 @@ mod tests {
         // If this doesn't panic, it works
     }
 
+    #[test]
+    fn test_summary_line() {
+        assert_eq!(
+            summary_line(1532, 19756849152),
+            "TOTAL: 1,532 objects, 19,756,849,152 bytes (18.4 GB)"
+        );
+    }
+
+    #[test]
+    fn test_record_writer_quotes_fields() {
+        let csv = RecordWriter {
+            delimiter: Some(','),
+        };
+        assert_eq!(
+            csv.join(&["az://a/c/plain.txt", "a,b.txt", "say \"hi\"", "two\nlines"]),
+            "az://a/c/plain.txt,\"a,b.txt\",\"say \"\"hi\"\"\",\"two\nlines\""
+        );
+
+        let tsv = RecordWriter {
+            delimiter: Some('\t'),
+        };
+        assert_eq!(
+            tsv.join(&["a,b.txt", "tab\there"]),
+            "a,b.txt\t\"tab\there\""
+        );
+    }
+
+    #[test]
+    fn test_column_keys() {
+        assert_eq!(RecordWriter::column_key(&Column::BlobType), "blob_type");
+        assert_eq!(RecordWriter::column_key(&Column::Etag), "etag");
+    }
+
     #[test]
     fn test_plain_writer_creates_plain_output() {
         let writer = PlainWriter;
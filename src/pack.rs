@@ -0,0 +1,445 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::Config;
+use crate::content_type::detect_content_type;
+use crate::dir_markers::remote_location;
+use crate::output;
+use crate::symlinks::SymlinkPolicy;
+use crate::sync_state::{snapshot_with, Snapshot};
+
+// ============================================================================
+// Packed Uploads - Small files grouped into large blobs behind an index
+// ============================================================================
+
+/// Directory, under the uploaded prefix, holding the packs and their index
+pub const PACK_DIR: &str = ".azst-pack";
+
+/// Name of the index blob inside `PACK_DIR`
+const INDEX_NAME: &str = "index.json";
+
+/// Version of the index format written by this release
+const INDEX_VERSION: u32 = 1;
+
+/// Files smaller than this are packed when --pack is given without a size
+pub const DEFAULT_PACK_THRESHOLD: &str = "1M";
+
+/// Packs are closed once adding a file would take them past this size
+const PACK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Packs or loose files uploaded or downloaded at once
+const PACK_CONCURRENCY: usize = 8;
+
+/// Where every packed file lives; stored as `PACK_DIR/index.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackIndex {
+    pub version: u32,
+    /// Pack blob names, relative to `PACK_DIR`
+    pub packs: Vec<String>,
+    pub files: Vec<PackedFile>,
+}
+
+/// A file stored as a byte range of a pack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackedFile {
+    /// Path relative to the uploaded directory, with `/` separators
+    pub path: String,
+    /// Index into `PackIndex::packs`
+    pub pack: usize,
+    pub offset: u64,
+    pub size: u64,
+    /// Last modified time as a Unix timestamp (seconds)
+    #[serde(default)]
+    pub modified: i64,
+}
+
+/// How a directory is split between packs and blobs of its own
+#[derive(Debug, PartialEq)]
+pub struct PackPlan {
+    pub index: PackIndex,
+    /// Files at or above the threshold, uploaded as they are: (path, size)
+    pub loose: Vec<(String, u64)>,
+    /// Symbolic links kept under --preserve-symlinks, each uploaded as a
+    /// blob holding its target
+    pub links: Vec<String>,
+}
+
+impl PackPlan {
+    /// Size of each pack, in index order
+    pub fn pack_sizes(&self) -> Vec<u64> {
+        let mut sizes = vec![0; self.index.packs.len()];
+        for file in &self.index.files {
+            sizes[file.pack] = sizes[file.pack].max(file.offset + file.size);
+        }
+        sizes
+    }
+}
+
+/// What a packed upload or an unpack moved
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PackSummary {
+    pub packs: usize,
+    pub packed_files: usize,
+    pub loose_files: usize,
+    pub bytes: u64,
+}
+
+/// Put files smaller than `threshold` into packs of about `pack_size` bytes,
+/// in path order, and leave the others loose
+pub fn plan_packs(files: &Snapshot, threshold: u64, pack_size: u64) -> PackPlan {
+    let mut index = PackIndex {
+        version: INDEX_VERSION,
+        packs: Vec::new(),
+        files: Vec::new(),
+    };
+    let mut loose = Vec::new();
+    let mut offset = 0;
+
+    for (path, entry) in files {
+        if entry.size >= threshold {
+            loose.push((path.clone(), entry.size));
+            continue;
+        }
+        if index.packs.is_empty() || (offset > 0 && offset + entry.size > pack_size) {
+            index
+                .packs
+                .push(format!("pack-{:05}.bin", index.packs.len()));
+            offset = 0;
+        }
+        index.files.push(PackedFile {
+            path: path.clone(),
+            pack: index.packs.len() - 1,
+            offset,
+            size: entry.size,
+            modified: entry.modified,
+        });
+        offset += entry.size;
+    }
+
+    PackPlan {
+        index,
+        loose,
+        links: Vec::new(),
+    }
+}
+
+/// Plan a packed upload of a local directory, treating its symbolic links
+/// as `symlinks` says
+pub async fn plan_upload(
+    source: &Path,
+    threshold: u64,
+    symlinks: SymlinkPolicy,
+) -> Result<PackPlan> {
+    let mut files = snapshot_with(&source.to_string_lossy(), symlinks).await?;
+    let links: Vec<String> = if symlinks == SymlinkPolicy::Preserve {
+        files
+            .keys()
+            .filter(|relative| source.join(relative).is_symlink())
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for link in &links {
+        files.remove(link);
+    }
+    Ok(PackPlan {
+        links,
+        ..plan_packs(&files, threshold, PACK_SIZE)
+    })
+}
+
+/// Upload a directory with its small files packed, under `remote_root`
+/// (az://account/container/prefix)
+///
+/// Packs and loose files go up first and the index last, so an interrupted
+/// upload leaves nothing `unpack` would read.
+pub async fn upload(source: &Path, remote_root: &str, plan: &PackPlan) -> Result<PackSummary> {
+    let (client, container, prefix) = remote_location(remote_root, "Packed copies")?;
+    let pack_prefix = format!("{}{}/", prefix, PACK_DIR);
+    let content_types = Config::load()?.content_types;
+
+    let packs = (0..plan.index.packs.len()).map(|pack| {
+        let mut client = client.clone();
+        let (container, pack_prefix) = (&container, &pack_prefix);
+        async move {
+            let name = &plan.index.packs[pack];
+            let mut data = Vec::new();
+            for file in plan.index.files.iter().filter(|file| file.pack == pack) {
+                let path = source.join(&file.path);
+                let content = tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                if content.len() as u64 != file.size || data.len() as u64 != file.offset {
+                    return Err(anyhow!("{} changed while it was packed", path.display()));
+                }
+                data.extend_from_slice(&content);
+            }
+            let size = data.len() as u64;
+            let progress_bar = output::file_progress_bar(name, Some(size));
+            client
+                .put_blob(
+                    container,
+                    &format!("{}{}", pack_prefix, name),
+                    data,
+                    Some("application/octet-stream"),
+                )
+                .await?;
+            progress_bar.inc(size);
+            Ok::<u64, anyhow::Error>(size)
+        }
+    });
+    let loose = plan.loose.iter().map(|(relative, _)| {
+        let mut client = client.clone();
+        let (container, prefix, content_types) = (&container, &prefix, &content_types);
+        async move {
+            let content_type = detect_content_type(relative, content_types);
+            let size = client
+                .upload_file(
+                    container,
+                    &format!("{}{}", prefix, relative),
+                    &source.join(relative),
+                    content_type.as_deref(),
+                    None,
+                )
+                .await?;
+            Ok(size)
+        }
+    });
+
+    let links = plan.links.iter().map(|relative| {
+        let mut client = client.clone();
+        let (container, prefix) = (&container, &prefix);
+        async move {
+            let path = source.join(relative);
+            let target = std::fs::read_link(&path)
+                .with_context(|| format!("Failed to read link {}", path.display()))?;
+            client
+                .put_symlink_blob(container, &format!("{}{}", prefix, relative), &target)
+                .await?;
+            Ok(target.as_os_str().len() as u64)
+        }
+    });
+
+    let pack_results: Vec<Result<u64>> = futures::stream::iter(packs)
+        .buffer_unordered(PACK_CONCURRENCY)
+        .collect()
+        .await;
+    let loose_results: Vec<Result<u64>> = futures::stream::iter(loose)
+        .buffer_unordered(PACK_CONCURRENCY)
+        .collect()
+        .await;
+
+    let link_results: Vec<Result<u64>> = futures::stream::iter(links)
+        .buffer_unordered(PACK_CONCURRENCY)
+        .collect()
+        .await;
+
+    let total = pack_results.len() + loose_results.len() + link_results.len();
+    let mut summary = PackSummary {
+        packs: plan.index.packs.len(),
+        packed_files: plan.index.files.len(),
+        loose_files: plan.loose.len() + plan.links.len(),
+        bytes: 0,
+    };
+    let mut failed = 0;
+    for result in pack_results
+        .into_iter()
+        .chain(loose_results)
+        .chain(link_results)
+    {
+        match result {
+            Ok(bytes) => summary.bytes += bytes,
+            Err(e) => {
+                eprintln!("  {} {:#}", "×".red(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} uploads failed; the pack index was not written",
+            failed,
+            total
+        ));
+    }
+
+    let index = serde_json::to_vec_pretty(&plan.index)?;
+    let mut client = client;
+    client
+        .put_blob(
+            &container,
+            &format!("{}{}", pack_prefix, INDEX_NAME),
+            index,
+            Some("application/json"),
+        )
+        .await?;
+    Ok(summary)
+}
+
+/// Extract the packed files of an upload under `remote_root` into `local_root`
+///
+/// Loose files are ordinary blobs and are downloaded by the copy itself.
+pub async fn unpack(remote_root: &str, local_root: &Path) -> Result<PackSummary> {
+    let (mut client, container, prefix) = remote_location(remote_root, "Packed copies")?;
+    let pack_prefix = format!("{}{}/", prefix, PACK_DIR);
+
+    let index = client
+        .download_blob(&container, &format!("{}{}", pack_prefix, INDEX_NAME), None)
+        .await
+        .with_context(|| format!("No pack index under {}", remote_root))?;
+    let index: PackIndex = serde_json::from_slice(&index).context("Invalid pack index")?;
+    if index.version != INDEX_VERSION {
+        return Err(anyhow!(
+            "Pack index version {} is not supported; upgrade azst",
+            index.version
+        ));
+    }
+    for file in &index.files {
+        if file.pack >= index.packs.len() || local_path(local_root, &file.path).is_none() {
+            return Err(anyhow!("Invalid pack index entry '{}'", file.path));
+        }
+    }
+
+    let packs = index.packs.iter().enumerate().map(|(pack, name)| {
+        let mut client = client.clone();
+        let (container, pack_prefix, index) = (&container, &pack_prefix, &index);
+        async move {
+            let data = client
+                .download_blob(container, &format!("{}{}", pack_prefix, name), None)
+                .await?;
+            let mut bytes = 0;
+            for file in index.files.iter().filter(|file| file.pack == pack) {
+                let content = usize::try_from(file.offset + file.size)
+                    .ok()
+                    .and_then(|end| data.get(file.offset as usize..end))
+                    .ok_or_else(|| anyhow!("Pack {} is shorter than its index says", name))?;
+                let path = local_path(local_root, &file.path).expect("checked above");
+                write_file(&path, content, file.modified)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                bytes += file.size;
+            }
+            Ok::<u64, anyhow::Error>(bytes)
+        }
+    });
+    let results: Vec<Result<u64>> = futures::stream::iter(packs)
+        .buffer_unordered(PACK_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut summary = PackSummary {
+        packs: index.packs.len(),
+        packed_files: index.files.len(),
+        ..PackSummary::default()
+    };
+    for result in results {
+        summary.bytes += result?;
+    }
+    Ok(summary)
+}
+
+/// Where a packed file goes under `root`, unless its path would leave it
+fn local_path(root: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    let normal = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    (normal && relative.components().next().is_some()).then(|| root.join(relative))
+}
+
+fn write_file(path: &Path, content: &[u8], modified: i64) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    if let Ok(seconds) = u64::try_from(modified) {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds);
+        std::fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_times(std::fs::FileTimes::new().set_modified(time))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_state::EntryState;
+
+    fn files(sizes: &[(&str, u64)]) -> Snapshot {
+        sizes
+            .iter()
+            .map(|(path, size)| {
+                let entry = EntryState {
+                    size: *size,
+                    modified: 1_700_000_000,
+                    md5: None,
+                };
+                (path.to_string(), entry)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_packs_splits_by_threshold_and_pack_size() {
+        let plan = plan_packs(
+            &files(&[("a", 40), ("b", 50), ("big.bin", 500), ("c", 30), ("d", 60)]),
+            100,
+            100,
+        );
+
+        assert_eq!(plan.loose, vec![("big.bin".to_string(), 500)]);
+        assert_eq!(plan.index.packs, vec!["pack-00000.bin", "pack-00001.bin"]);
+        let placed: Vec<(&str, usize, u64)> = plan
+            .index
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file.pack, file.offset))
+            .collect();
+        assert_eq!(
+            placed,
+            vec![("a", 0, 0), ("b", 0, 40), ("c", 1, 0), ("d", 1, 30)]
+        );
+        assert_eq!(plan.pack_sizes(), vec![90, 90]);
+    }
+
+    #[test]
+    fn test_plan_packs_everything_loose() {
+        let plan = plan_packs(&files(&[("a", 10)]), 0, 100);
+        assert!(plan.index.packs.is_empty());
+        assert_eq!(plan.loose.len(), 1);
+    }
+
+    #[test]
+    fn test_local_path_stays_inside_root() {
+        let root = Path::new("/restore");
+        assert_eq!(
+            local_path(root, "a/b.txt"),
+            Some(PathBuf::from("/restore/a/b.txt"))
+        );
+        assert_eq!(local_path(root, "../etc/passwd"), None);
+        assert_eq!(local_path(root, "/etc/passwd"), None);
+        assert_eq!(local_path(root, ""), None);
+    }
+
+    #[test]
+    fn test_write_file_sets_modified_time() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("nested/file.txt");
+        write_file(&path, b"packed", 1_600_000_000).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"packed");
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(
+            modified
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            1_600_000_000
+        );
+    }
+}
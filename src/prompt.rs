@@ -0,0 +1,189 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::cancel;
+use crate::error::AzstError;
+
+// ============================================================================
+// Prompts - Questions asked on the terminal, refused when nobody can answer
+// ============================================================================
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Refuse every prompt, for --non-interactive
+pub fn set_non_interactive(enabled: bool) {
+    NON_INTERACTIVE.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether someone can answer a prompt: --non-interactive wasn't given and
+/// stdin is a terminal, not a pipe, a file or a container without a TTY
+pub fn can_prompt() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed) && io::stdin().is_terminal()
+}
+
+/// Fail unless someone can answer a prompt; `bypass` says how to go
+/// without it, e.g. "pass --force to remove without asking"
+pub fn require_interactive(bypass: &str) -> Result<()> {
+    if can_prompt() {
+        return Ok(());
+    }
+    let reason = if NON_INTERACTIVE.load(Ordering::Relaxed) {
+        "--non-interactive was given"
+    } else {
+        "stdin is not a terminal"
+    };
+    Err(
+        AzstError::NeedsConfirmation(format!("Confirmation needed, but {}; {}", reason, bypass))
+            .into(),
+    )
+}
+
+/// Questions commands ask before doing something they can't undo
+///
+/// Commands are handed a prompter rather than reading stdin themselves, so
+/// tests can answer for the user.
+#[async_trait::async_trait]
+pub trait Prompter: Send + Sync {
+    /// Fail unless this prompter can get an answer; `bypass` says how to go
+    /// without one
+    fn check(&self, bypass: &str) -> Result<()>;
+
+    /// Print `question` and read the line typed in answer
+    async fn ask(&self, question: &str, bypass: &str) -> Result<String>;
+
+    /// Ask a yes/no question, no being the default
+    async fn confirm(&self, question: &str, bypass: &str) -> Result<bool> {
+        let answer = self.ask(&format!("{} (y/N): ", question), bypass).await?;
+        Ok(is_yes(&answer))
+    }
+}
+
+/// Asks on the terminal, reading the answer without blocking the runtime so
+/// Ctrl-C and --timeout still stop a command waiting for one
+pub struct TtyPrompter;
+
+#[async_trait::async_trait]
+impl Prompter for TtyPrompter {
+    fn check(&self, bypass: &str) -> Result<()> {
+        require_interactive(bypass)
+    }
+
+    async fn ask(&self, question: &str, bypass: &str) -> Result<String> {
+        self.check(bypass)?;
+        print!("{}", question);
+        io::stdout().flush()?;
+        // A terminal hands over one line per read, so nothing typed ahead
+        // is lost with the reader
+        let mut stdin = BufReader::new(tokio::io::stdin());
+        let mut input = String::new();
+        let token = cancel::token();
+        tokio::select! {
+            read = stdin.read_line(&mut input) => {
+                if read? == 0 {
+                    // Ctrl-D answers no
+                    println!();
+                }
+            }
+            _ = token.cancelled() => {
+                println!();
+                return Err(cancel::error().into());
+            }
+        }
+        Ok(input)
+    }
+}
+
+/// Answers with a fixed script and records the questions, for tests
+#[derive(Default)]
+pub struct ScriptedPrompter {
+    answers: Mutex<VecDeque<String>>,
+    asked: Mutex<Vec<String>>,
+}
+
+impl ScriptedPrompter {
+    pub fn new<'a>(answers: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            answers: Mutex::new(answers.into_iter().map(str::to_string).collect()),
+            asked: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The questions asked so far, in order
+    pub fn asked(&self) -> Vec<String> {
+        self.asked.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl Prompter for ScriptedPrompter {
+    fn check(&self, _bypass: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn ask(&self, question: &str, bypass: &str) -> Result<String> {
+        self.asked
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(question.to_string());
+        self.answers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+            .ok_or_else(|| {
+                AzstError::NeedsConfirmation(format!(
+                    "No answer scripted for '{}'; {}",
+                    question.trim_end(),
+                    bypass
+                ))
+                .into()
+            })
+    }
+}
+
+/// The prompter commands get when run from the command line or the shell
+pub fn create_prompter() -> Box<dyn Prompter> {
+    Box::new(TtyPrompter)
+}
+
+pub fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim();
+    answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_non_interactive_refuses_prompts() {
+        set_non_interactive(true);
+        let error = TtyPrompter
+            .confirm("remove it?", "pass --force to remove without asking")
+            .await
+            .unwrap_err();
+        set_non_interactive(false);
+        assert_eq!(
+            error.to_string(),
+            "Confirmation needed, but --non-interactive was given; pass --force to remove without asking"
+        );
+        assert_eq!(crate::error::exit_code(&error), crate::error::EXIT_USAGE);
+        assert!(is_yes(" Yes\n"));
+        assert!(!is_yes("\n"));
+    }
+
+    #[tokio::test]
+    async fn test_scripted_prompter_answers_in_order() {
+        let prompter = ScriptedPrompter::new(["y", "no"]);
+        assert!(prompter.confirm("first?", "").await.unwrap());
+        assert!(!prompter.confirm("second?", "").await.unwrap());
+        assert!(prompter.confirm("third?", "pass --force").await.is_err());
+        assert_eq!(
+            prompter.asked(),
+            vec!["first? (y/N): ", "second? (y/N): ", "third? (y/N): "]
+        );
+    }
+}
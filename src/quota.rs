@@ -0,0 +1,249 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+
+use crate::azure::AzureClient;
+use crate::config::Config;
+use crate::symlinks::SymlinkPolicy;
+use crate::sync_state::{snapshot, snapshot_with, Snapshot};
+use crate::utils::{format_size, is_azure_uri, parse_azure_uri, Glob};
+
+// ============================================================================
+// Upload Quotas - Refuse transfers that would blow through a storage budget
+// ============================================================================
+
+/// How the bytes a transfer will upload are estimated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    /// Everything under the source is copied
+    Copy,
+    /// Only files missing or different at the destination are copied
+    Sync,
+}
+
+/// Abort before uploading to `destination` if the transfer would exceed
+/// `max_upload_bytes` or the quota configured for the destination
+///
+/// Nothing is listed unless a limit applies, so transfers without a budget
+/// don't pay for the extra round trips. Links under a local source count as
+/// `symlinks` says they'll be uploaded.
+pub async fn check_upload(
+    source: &str,
+    destination: &str,
+    transfer: Transfer,
+    max_upload_bytes: Option<u64>,
+    symlinks: SymlinkPolicy,
+) -> Result<()> {
+    if !is_azure_uri(destination) {
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    let quota = config.quota_for(destination);
+    if max_upload_bytes.is_none() && quota.is_none() {
+        return Ok(());
+    }
+
+    let upload = transfer_bytes(source, destination, transfer, symlinks).await?;
+
+    if let Some(max) = max_upload_bytes {
+        if upload > max {
+            return Err(anyhow!(
+                "Upload of {} exceeds --max-upload-bytes ({}); nothing was transferred",
+                format_size(upload),
+                format_size(max)
+            ));
+        }
+    }
+
+    if let Some((location, limit)) = quota {
+        let stored = stored_bytes(location).await?;
+        if stored + upload > limit {
+            return Err(anyhow!(
+                "Upload of {} would bring {} to {}, over its quota of {}; nothing was transferred",
+                format_size(upload),
+                location,
+                format_size(stored + upload),
+                format_size(limit)
+            ));
+        }
+        status!(
+            "{} Quota for {}: {} of {} used after this upload",
+            "ℹ".blue(),
+            location,
+            format_size(stored + upload),
+            format_size(limit)
+        );
+    }
+
+    Ok(())
+}
+
+/// Bytes a transfer from `source` to `destination` is expected to move
+pub async fn transfer_bytes(
+    source: &str,
+    destination: &str,
+    transfer: Transfer,
+    symlinks: SymlinkPolicy,
+) -> Result<u64> {
+    match transfer {
+        Transfer::Copy => source_bytes(source, symlinks).await,
+        Transfer::Sync => Ok(pending_bytes(
+            &snapshot_with(source, symlinks).await?,
+            &snapshot(destination).await?,
+        )),
+    }
+}
+
+/// Total size of everything a copy from `source` would transfer
+async fn source_bytes(source: &str, symlinks: SymlinkPolicy) -> Result<u64> {
+    if let Some(glob) = Glob::new(source)? {
+        let entries = snapshot_with(glob.prefix(), symlinks).await?;
+        return Ok(entries
+            .iter()
+            .filter(|(path, _)| glob.selects(&format!("{}{}", glob.prefix(), path), false))
+            .map(|(_, entry)| entry.size)
+            .sum());
+    }
+
+    if !is_azure_uri(source) {
+        let metadata = std::fs::metadata(source)?;
+        if metadata.is_file() {
+            return Ok(metadata.len());
+        }
+        return Ok(total(&snapshot_with(source, symlinks).await?));
+    }
+
+    // An Azure source is either a single blob or a prefix
+    let entries = snapshot(source).await?;
+    if !entries.is_empty() {
+        return Ok(total(&entries));
+    }
+    let (account, container, path) = parse_azure_uri(source)?;
+    match (account, path) {
+        (Some(account), Some(path)) => {
+            let mut client = AzureClient::new().with_storage_account(&account);
+            Ok(client
+                .get_blob_properties(&container, &path)
+                .await
+                .map(|blob| blob.properties.content_length)
+                .unwrap_or(0))
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Bytes currently stored under a quota location (account, container or prefix)
+async fn stored_bytes(location: &str) -> Result<u64> {
+    let (account, container, _) = parse_azure_uri(location)?;
+    let account = account.ok_or_else(|| {
+        anyhow!(
+            "Quota location '{}' must include a storage account: az://<account>/[container]/[path]",
+            location
+        )
+    })?;
+
+    if !container.is_empty() {
+        return Ok(total(&snapshot(location).await?));
+    }
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+    let mut stored = 0;
+    for container in client.list_containers().await? {
+        stored += total(&snapshot(&format!("az://{}/{}", account, container.name)).await?);
+    }
+    Ok(stored)
+}
+
+/// Bytes of source entries a sync would copy: new files and changed files
+fn pending_bytes(source: &Snapshot, destination: &Snapshot) -> u64 {
+    source
+        .iter()
+        .filter(|(path, entry)| {
+            destination
+                .get(*path)
+                .is_none_or(|existing| existing.size != entry.size)
+        })
+        .map(|(_, entry)| entry.size)
+        .sum()
+}
+
+fn total(entries: &Snapshot) -> u64 {
+    entries.values().map(|entry| entry.size).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_state::EntryState;
+
+    fn snapshot_of(entries: &[(&str, u64)]) -> Snapshot {
+        entries
+            .iter()
+            .map(|(path, size)| {
+                (
+                    path.to_string(),
+                    EntryState {
+                        size: *size,
+                        modified: 0,
+                        md5: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pending_bytes_counts_new_and_changed_files() {
+        let source = snapshot_of(&[("a.txt", 10), ("b.txt", 20), ("c.txt", 30)]);
+        let destination = snapshot_of(&[("a.txt", 10), ("b.txt", 5)]);
+        assert_eq!(pending_bytes(&source, &destination), 50);
+        assert_eq!(pending_bytes(&source, &source), 0);
+    }
+
+    #[tokio::test]
+    async fn test_source_bytes_local() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        std::fs::write(root.join("a.csv"), vec![0u8; 100]).unwrap();
+        std::fs::write(root.join("b.log"), vec![0u8; 50]).unwrap();
+        std::fs::write(root.join("sub/c.csv"), vec![0u8; 25]).unwrap();
+
+        let root_str = root.to_str().unwrap();
+        assert_eq!(
+            source_bytes(root_str, SymlinkPolicy::Skip).await.unwrap(),
+            175
+        );
+        assert_eq!(
+            source_bytes(&format!("{}/a.csv", root_str), SymlinkPolicy::Skip)
+                .await
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            source_bytes(&format!("{}/*.csv", root_str), SymlinkPolicy::Skip)
+                .await
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            source_bytes(&format!("{}/**/*.csv", root_str), SymlinkPolicy::Skip)
+                .await
+                .unwrap(),
+            125
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_upload_ignores_local_destinations() {
+        check_upload(
+            "/nonexistent",
+            "/tmp/out",
+            Transfer::Copy,
+            Some(0),
+            SymlinkPolicy::Skip,
+        )
+        .await
+        .unwrap();
+    }
+}
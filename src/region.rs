@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::collections::HashMap;
+
+use crate::azure::AzureClient;
+use crate::quota::{transfer_bytes, Transfer};
+use crate::symlinks::SymlinkPolicy;
+use crate::utils::{format_size, is_azure_uri, parse_azure_uri};
+
+// ============================================================================
+// Region Planning - Catch Azure-to-Azure transfers that leave their region
+// ============================================================================
+
+/// Approximate price of moving a GB between regions on the same continent (USD)
+const SAME_CONTINENT_USD_PER_GB: f64 = 0.02;
+
+/// Approximate price of moving a GB between continents (USD)
+const INTERCONTINENTAL_USD_PER_GB: f64 = 0.05;
+
+/// Refuse an Azure-to-Azure transfer between accounts in different regions
+/// unless `allow` is set, quoting the bytes involved and a rough egress cost
+///
+/// Regions come from the management API. When an account isn't visible in the
+/// current subscription the check is skipped rather than blocking the copy.
+pub async fn check_cross_region(
+    source: &str,
+    destination: &str,
+    transfer: Transfer,
+    allow: bool,
+) -> Result<()> {
+    if !is_azure_uri(source) || !is_azure_uri(destination) {
+        return Ok(());
+    }
+    let (Some(source_account), _, _) = parse_azure_uri(source)? else {
+        return Ok(());
+    };
+    let (Some(destination_account), _, _) = parse_azure_uri(destination)? else {
+        return Ok(());
+    };
+    if source_account == destination_account {
+        return Ok(());
+    }
+
+    let regions = match account_regions().await {
+        Ok(regions) => regions,
+        Err(e) => {
+            tracing::debug!("Skipping region check: {:#}", e);
+            return Ok(());
+        }
+    };
+    let (Some(from), Some(to)) = (
+        regions.get(&source_account),
+        regions.get(&destination_account),
+    ) else {
+        tracing::debug!("Skipping region check: account region unknown");
+        return Ok(());
+    };
+    if from == to {
+        return Ok(());
+    }
+
+    // Both sides are in Azure, so there are no local links to count
+    let bytes = transfer_bytes(source, destination, transfer, SymlinkPolicy::Skip).await?;
+    let summary = format!(
+        "{} ({}) to {} ({}): {}, roughly ${:.2} in egress charges",
+        source_account,
+        from,
+        destination_account,
+        to,
+        format_size(bytes),
+        egress_cost(from, to, bytes)
+    );
+
+    if !allow {
+        return Err(anyhow!(
+            "Transfer crosses regions, from {}. Re-run with --allow-cross-region to proceed; nothing was transferred",
+            summary
+        ));
+    }
+    println!("{} Cross-region transfer from {}", "⚠".yellow(), summary);
+    Ok(())
+}
+
+/// Storage account name -> normalized region, for the current subscription
+async fn account_regions() -> Result<HashMap<String, String>> {
+    let mut client = AzureClient::new();
+    Ok(client
+        .list_storage_accounts()
+        .await?
+        .into_iter()
+        .map(|account| (account.name, normalize_region(&account.location)))
+        .collect())
+}
+
+/// "West Europe" and "westeurope" name the same region
+fn normalize_region(region: &str) -> String {
+    region
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Estimated cost in USD of moving `bytes` from one region to another
+fn egress_cost(from: &str, to: &str, bytes: u64) -> f64 {
+    let per_gb = match (continent(from), continent(to)) {
+        (Some(a), Some(b)) if a == b => SAME_CONTINENT_USD_PER_GB,
+        _ => INTERCONTINENTAL_USD_PER_GB,
+    };
+    bytes as f64 / (1024.0 * 1024.0 * 1024.0) * per_gb
+}
+
+/// Continent of an Azure region, as far as the pricing zones go
+fn continent(region: &str) -> Option<&'static str> {
+    const PREFIXES: &[(&str, &str)] = &[
+        ("eastus", "north-america"),
+        ("westus", "north-america"),
+        ("centralus", "north-america"),
+        ("northcentralus", "north-america"),
+        ("southcentralus", "north-america"),
+        ("westcentralus", "north-america"),
+        ("canada", "north-america"),
+        ("mexico", "north-america"),
+        ("brazil", "south-america"),
+        ("chile", "south-america"),
+        ("northeurope", "europe"),
+        ("westeurope", "europe"),
+        ("uk", "europe"),
+        ("france", "europe"),
+        ("germany", "europe"),
+        ("switzerland", "europe"),
+        ("norway", "europe"),
+        ("sweden", "europe"),
+        ("poland", "europe"),
+        ("italy", "europe"),
+        ("spain", "europe"),
+        ("austria", "europe"),
+        ("eastasia", "asia"),
+        ("southeastasia", "asia"),
+        ("japan", "asia"),
+        ("korea", "asia"),
+        ("centralindia", "asia"),
+        ("southindia", "asia"),
+        ("westindia", "asia"),
+        ("jioindia", "asia"),
+        ("indonesia", "asia"),
+        ("malaysia", "asia"),
+        ("australia", "oceania"),
+        ("newzealand", "oceania"),
+        ("southafrica", "africa"),
+        ("uae", "middle-east"),
+        ("qatar", "middle-east"),
+        ("israel", "middle-east"),
+    ];
+    PREFIXES
+        .iter()
+        .find(|(prefix, _)| region.starts_with(prefix))
+        .map(|(_, continent)| *continent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_region() {
+        assert_eq!(normalize_region("West Europe"), "westeurope");
+        assert_eq!(normalize_region("eastus2"), "eastus2");
+    }
+
+    #[test]
+    fn test_egress_cost() {
+        let gb = 1024 * 1024 * 1024;
+        assert!((egress_cost("eastus", "westus2", 100 * gb) - 2.0).abs() < 1e-9);
+        assert!((egress_cost("eastus", "westeurope", 100 * gb) - 5.0).abs() < 1e-9);
+        // Unknown regions are priced as the worst case
+        assert!((egress_cost("marsnorth", "marssouth", 100 * gb) - 5.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_check_cross_region_ignores_local_paths() {
+        check_cross_region("/tmp/data", "az://acct/c/", Transfer::Copy, false)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use colored::*;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+
+// ============================================================================
+// Symlinks - What uploads do with symbolic links under a local source
+// ============================================================================
+
+/// Metadata azcopy's --preserve-symlinks marks a link's blob with
+pub const IS_SYMLINK_KEY: &str = "is_symlink";
+
+/// Metadata recording the target of a link's blob, percent-encoded as
+/// metadata values are ASCII
+pub const LINK_TARGET_KEY: &str = "link_target";
+
+/// How uploads treat symbolic links found while walking a local directory
+///
+/// Links named on the command line are always followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Leave links out, warning how many were skipped
+    #[default]
+    Skip,
+    /// Upload the files and directories links point to
+    Follow,
+    /// Upload each link as a blob holding its target
+    Preserve,
+}
+
+impl SymlinkPolicy {
+    /// The azcopy flag for this policy; azcopy skips links by default
+    pub fn azcopy_flag(&self) -> Option<&'static str> {
+        match self {
+            SymlinkPolicy::Skip => None,
+            SymlinkPolicy::Follow => Some("--follow-symlinks"),
+            SymlinkPolicy::Preserve => Some("--preserve-symlinks"),
+        }
+    }
+}
+
+/// Roots whose skipped links were already reported, as a command may walk
+/// its source more than once
+static WARNED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Forget the reported roots, for the next command of an interactive shell
+pub fn reset_warnings() {
+    *WARNED.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Warn that `count` links under `root` were left out under `policy`, once
+/// per root
+pub fn warn_skipped(root: &str, count: u64, policy: SymlinkPolicy) {
+    if count == 0 {
+        return;
+    }
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if !warned
+        .get_or_insert_with(HashSet::new)
+        .insert(root.to_string())
+    {
+        return;
+    }
+    let hint = match policy {
+        SymlinkPolicy::Skip => "use --follow-symlinks or --preserve-symlinks to upload them",
+        _ => "their targets don't exist",
+    };
+    eprintln!(
+        "{} Skipped {} symbolic link(s) under {}; {}",
+        "⚠".yellow(),
+        count,
+        root,
+        hint
+    );
+}
+
+/// Metadata of the blob standing for a link to `target`
+pub fn link_metadata(target: &Path) -> Vec<(&'static str, String)> {
+    let target = target.to_string_lossy();
+    vec![
+        (IS_SYMLINK_KEY, "true".to_string()),
+        (
+            LINK_TARGET_KEY,
+            percent_encoding::utf8_percent_encode(&target, percent_encoding::NON_ALPHANUMERIC)
+                .to_string(),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_metadata_encodes_target() {
+        let metadata = link_metadata(Path::new("../shared/café data.csv"));
+        assert_eq!(metadata[0], (IS_SYMLINK_KEY, "true".to_string()));
+        assert_eq!(
+            metadata[1],
+            (
+                LINK_TARGET_KEY,
+                "%2E%2E%2Fshared%2Fcaf%C3%A9%20data%2Ecsv".to_string()
+            )
+        );
+        assert_eq!(SymlinkPolicy::default().azcopy_flag(), None);
+    }
+}
@@ -0,0 +1,964 @@
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::azure::{AzureClient, BlobItem};
+use crate::hash_cache;
+use crate::symlinks::{self, SymlinkPolicy};
+use crate::utils::{directory_prefix, is_azure_uri, parse_azure_uri};
+
+// ============================================================================
+// Sync State - Snapshots of both sides recorded after each tracked sync
+// ============================================================================
+
+/// Size and modification time of a single file or blob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryState {
+    pub size: u64,
+    /// Last modified time as a Unix timestamp (seconds)
+    pub modified: i64,
+    /// Hex MD5 of the content: a blob's stored Content-MD5, and for local
+    /// files only what `--compare checksum` or a first two-way sync hashes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
+}
+
+/// How a sync decides that a file differs from its counterpart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CompareMode {
+    /// Size and modification time
+    #[default]
+    Mtime,
+    /// Size only: faster, but misses edits that keep the size
+    Size,
+    /// MD5 of the content: catches changes with the same size and time
+    Checksum,
+}
+
+impl EntryState {
+    /// Whether two entries hold the same content as far as `compare` can tell
+    pub fn same_as(&self, other: &EntryState, compare: CompareMode) -> bool {
+        let same_time = self.size == other.size && self.modified == other.modified;
+        match compare {
+            CompareMode::Mtime => same_time,
+            CompareMode::Size => self.size == other.size,
+            CompareMode::Checksum => match (&self.md5, &other.md5) {
+                (Some(a), Some(b)) => self.size == other.size && a == b,
+                // Blobs uploaded without --put-md5 have no hash to compare
+                _ => same_time,
+            },
+        }
+    }
+
+    /// Whether two entries are known to hold different content: their sizes
+    /// differ, or both have an MD5 and those differ
+    pub fn known_to_differ(&self, other: &EntryState) -> bool {
+        self.size != other.size || matches!((&self.md5, &other.md5), (Some(a), Some(b)) if a != b)
+    }
+}
+
+/// `same_as` for entries that may be missing on either side
+fn same_entry(a: Option<&EntryState>, b: Option<&EntryState>, compare: CompareMode) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.same_as(b, compare),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Relative path -> entry state for one side of a sync
+pub type Snapshot = BTreeMap<String, EntryState>;
+
+/// State recorded after a successful sync, used to detect changes on the next run
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    pub source: String,
+    pub destination: String,
+    pub source_entries: Snapshot,
+    pub destination_entries: Snapshot,
+}
+
+impl SyncState {
+    /// Load the state file, returning an empty state if it doesn't exist yet
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read sync state file '{}'", path))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Sync state file '{}' is not valid JSON", path))
+    }
+
+    /// Write the state file as pretty-printed JSON
+    pub fn save(&self, path: &str) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write sync state file '{}'", path))
+    }
+
+    /// Whether this state was recorded for the same pair of locations
+    pub fn matches(&self, source: &str, destination: &str) -> bool {
+        self.source == source && self.destination == destination
+    }
+
+    /// Whether either side of this state has an entry for `path`
+    pub fn records(&self, path: &str) -> bool {
+        self.source_entries.contains_key(path) || self.destination_entries.contains_key(path)
+    }
+
+    /// Put back the entries `previous` recorded for `paths`, so that files
+    /// left in conflict still differ from the state on both sides
+    pub fn keep_previous(&mut self, previous: &SyncState, paths: &[String]) {
+        for path in paths {
+            for (entries, previous_entries) in [
+                (&mut self.source_entries, &previous.source_entries),
+                (&mut self.destination_entries, &previous.destination_entries),
+            ] {
+                match previous_entries.get(path) {
+                    Some(entry) => entries.insert(path.clone(), entry.clone()),
+                    None => entries.remove(path),
+                };
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Conflict Detection
+// ============================================================================
+
+/// How to resolve files that changed on both sides since the last recorded state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep whichever side was modified most recently
+    Newer,
+    /// Keep the local side (the source for Azure-to-Azure syncs)
+    Local,
+    /// Keep the remote side (the destination for Azure-to-Azure syncs)
+    Remote,
+    /// Ask interactively for each conflicting file
+    Ask,
+    /// Keep both: rename the destination copy before overwriting it
+    Rename,
+    /// Leave both versions as they are and report the file, until it is
+    /// resolved by hand
+    Flag,
+}
+
+/// A file that changed on both sides since the last recorded state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: String,
+    pub source: EntryState,
+    pub destination: EntryState,
+}
+
+/// Whether an entry was added or modified relative to the previous snapshot
+fn changed_since(
+    previous: &Snapshot,
+    path: &str,
+    current: &EntryState,
+    compare: CompareMode,
+) -> bool {
+    !same_entry(previous.get(path), Some(current), compare)
+}
+
+/// Find files that changed on both sides since `state` was recorded
+///
+/// Files that are the same on both sides according to `compare` are not
+/// reported, since overwriting them loses nothing. Neither are files the
+/// state has no record of, as on a first run: nothing tells which side
+/// changed, so the sync handles them as usual and the state is seeded after.
+pub fn detect_conflicts(
+    state: &SyncState,
+    source: &Snapshot,
+    destination: &Snapshot,
+    compare: CompareMode,
+) -> Vec<Conflict> {
+    source
+        .iter()
+        .filter_map(|(path, src)| {
+            let dst = destination.get(path)?;
+            if src.same_as(dst, compare) || !state.records(path) {
+                return None;
+            }
+            if changed_since(&state.source_entries, path, src, compare)
+                && changed_since(&state.destination_entries, path, dst, compare)
+            {
+                Some(Conflict {
+                    path: path.clone(),
+                    source: src.clone(),
+                    destination: dst.clone(),
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// A single step of a bidirectional sync plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncAction {
+    CopyToDestination(String),
+    CopyToSource(String),
+    DeleteFromDestination(String),
+    DeleteFromSource(String),
+    Conflict(Conflict),
+}
+
+/// Plan a bidirectional sync from the changes on each side since `state`
+///
+/// Additions and modifications are propagated to the other side, deletions
+/// are propagated only when the other side is unchanged (a modification wins
+/// over a deletion), and files changed on both sides are reported as conflicts.
+/// Files on both sides that the state has no record of, as on a first run,
+/// are compared by size and MD5 only, since an upload never keeps the local
+/// mtime: they are a conflict when those tell them apart, and are otherwise
+/// left alone for the saved state to record.
+pub fn plan_bidirectional(
+    state: &SyncState,
+    source: &Snapshot,
+    destination: &Snapshot,
+    compare: CompareMode,
+) -> Vec<SyncAction> {
+    let paths: std::collections::BTreeSet<&String> = source
+        .keys()
+        .chain(destination.keys())
+        .chain(state.source_entries.keys())
+        .chain(state.destination_entries.keys())
+        .collect();
+
+    let mut actions = Vec::new();
+    for path in paths {
+        let src = source.get(path);
+        let dst = destination.get(path);
+        let prev_src = state.source_entries.get(path);
+        let prev_dst = state.destination_entries.get(path);
+
+        let src_changed = !same_entry(src, prev_src, compare);
+        let dst_changed = !same_entry(dst, prev_dst, compare);
+
+        let action = match (src, dst, src_changed, dst_changed) {
+            (_, _, false, false) => None,
+            (Some(_), _, true, false) => Some(SyncAction::CopyToDestination(path.clone())),
+            (None, Some(_), true, false) => Some(SyncAction::DeleteFromDestination(path.clone())),
+            (_, Some(_), false, true) => Some(SyncAction::CopyToSource(path.clone())),
+            (Some(_), None, false, true) => Some(SyncAction::DeleteFromSource(path.clone())),
+            (Some(s), Some(d), true, true) => {
+                if s.same_as(d, compare) || (!state.records(path) && !s.known_to_differ(d)) {
+                    None
+                } else {
+                    Some(SyncAction::Conflict(Conflict {
+                        path: path.clone(),
+                        source: s.clone(),
+                        destination: d.clone(),
+                    }))
+                }
+            }
+            (Some(_), None, true, true) => Some(SyncAction::CopyToDestination(path.clone())),
+            (None, Some(_), true, true) => Some(SyncAction::CopyToSource(path.clone())),
+            (None, None, _, _) => None,
+        };
+
+        actions.extend(action);
+    }
+
+    actions
+}
+
+/// Plan a one-way sync: copy what is missing or different at the
+/// destination and, with `delete`, remove what the source doesn't have
+pub fn plan_one_way(
+    source: &Snapshot,
+    destination: &Snapshot,
+    compare: CompareMode,
+    delete: bool,
+) -> Vec<SyncAction> {
+    let copies = source
+        .iter()
+        .filter(|(path, src)| !same_entry(destination.get(*path), Some(src), compare))
+        .map(|(path, _)| SyncAction::CopyToDestination(path.clone()));
+    let deletions = destination
+        .keys()
+        .filter(|path| delete && !source.contains_key(*path))
+        .map(|path| SyncAction::DeleteFromDestination(path.clone()));
+    copies.chain(deletions).collect()
+}
+
+/// Most of the destination a mirror may delete before it stops to ask for
+/// `--force`: a number of files, or a percentage of those it holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeleteLimit {
+    Count(usize),
+    Percent(f64),
+}
+
+impl Default for DeleteLimit {
+    fn default() -> Self {
+        DeleteLimit::Percent(10.0)
+    }
+}
+
+impl std::str::FromStr for DeleteLimit {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        match value.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(percent) if (0.0..=100.0).contains(&percent) => {
+                    Ok(DeleteLimit::Percent(percent))
+                }
+                _ => Err(format!("'{}' is not a percentage from 0% to 100%", value)),
+            },
+            None => value
+                .parse()
+                .map(DeleteLimit::Count)
+                .map_err(|_| format!("'{}' is not a number of files or a percentage", value)),
+        }
+    }
+}
+
+impl std::fmt::Display for DeleteLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeleteLimit::Count(count) => write!(f, "{} file(s)", count),
+            DeleteLimit::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl DeleteLimit {
+    /// Whether deleting `deletions` of the `total` destination files goes
+    /// over the limit
+    pub fn exceeded_by(&self, deletions: usize, total: usize) -> bool {
+        match *self {
+            DeleteLimit::Count(count) => deletions > count,
+            DeleteLimit::Percent(percent) => {
+                deletions > 0 && deletions as f64 * 100.0 > percent * total as f64
+            }
+        }
+    }
+}
+
+/// Build the name used to keep a conflicting copy: `data.csv` -> `data.conflict-<ts>.csv`
+pub fn conflict_name(path: &str, timestamp: i64) -> String {
+    let (dir, file) = match path.rfind('/') {
+        Some(pos) => (&path[..=pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+
+    match file.rfind('.') {
+        Some(dot) if dot > 0 => format!(
+            "{}{}.conflict-{}{}",
+            dir,
+            &file[..dot],
+            timestamp,
+            &file[dot..]
+        ),
+        _ => format!("{}{}.conflict-{}", dir, file, timestamp),
+    }
+}
+
+// ============================================================================
+// Snapshots
+// ============================================================================
+
+/// Join a sync root (local directory or az:// URI) with a relative path
+pub fn join_location(root: &str, relative: &str) -> String {
+    if is_azure_uri(root) {
+        format!("{}/{}", root.trim_end_matches('/'), relative)
+    } else {
+        Path::new(root).join(relative).to_string_lossy().to_string()
+    }
+}
+
+/// Take a snapshot of a local directory or Azure prefix
+pub async fn snapshot(location: &str) -> Result<Snapshot> {
+    snapshot_with(location, SymlinkPolicy::Skip).await
+}
+
+/// Take a snapshot, treating local symbolic links as `symlinks` says
+pub async fn snapshot_with(location: &str, symlinks: SymlinkPolicy) -> Result<Snapshot> {
+    snapshot_for(location, CompareMode::Mtime, symlinks).await
+}
+
+/// Take a snapshot with what `compare` needs; checksums hash every local file
+pub async fn snapshot_for(
+    location: &str,
+    compare: CompareMode,
+    symlinks: SymlinkPolicy,
+) -> Result<Snapshot> {
+    if is_azure_uri(location) {
+        return snapshot_azure(location).await;
+    }
+    let mut entries = snapshot_local(location, symlinks)?;
+    if compare == CompareMode::Checksum {
+        let relatives: Vec<String> = entries.keys().cloned().collect();
+        hash_local(location, &mut entries, &relatives).await?;
+    }
+    Ok(entries)
+}
+
+/// Hash the local files that the state has no record of and that match
+/// their counterpart in size, so `plan_bidirectional` can tell them apart by
+/// content on a first run
+///
+/// Files whose counterpart is a blob without a Content-MD5 are skipped, as
+/// there would be nothing to compare the hash with.
+pub async fn hash_unrecorded(
+    state: &SyncState,
+    source_root: &str,
+    source: &mut Snapshot,
+    destination_root: &str,
+    destination: &mut Snapshot,
+) -> Result<()> {
+    let comparable = |root: &str, entry: &EntryState| entry.md5.is_some() || !is_azure_uri(root);
+    let paths: Vec<String> = source
+        .iter()
+        .filter_map(|(path, src)| {
+            let dst = destination.get(path)?;
+            let candidate = !state.records(path)
+                && src.size == dst.size
+                && (src.md5.is_none() || dst.md5.is_none())
+                && comparable(source_root, src)
+                && comparable(destination_root, dst);
+            candidate.then(|| path.clone())
+        })
+        .collect();
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    hash_local(source_root, source, &paths).await?;
+    hash_local(destination_root, destination, &paths).await
+}
+
+/// Fill in the MD5 of the given local entries that don't have one yet
+async fn hash_local(root: &str, entries: &mut Snapshot, paths: &[String]) -> Result<()> {
+    if is_azure_uri(root) {
+        return Ok(());
+    }
+    let relatives = paths
+        .iter()
+        .filter(|path| entries.get(*path).is_some_and(|entry| entry.md5.is_none()))
+        .cloned()
+        .collect();
+    for (relative, md5) in hash_cache::md5_tree(Path::new(root), relatives).await? {
+        if let Some(entry) = entries.get_mut(&relative) {
+            entry.md5 = Some(md5);
+        }
+    }
+    Ok(())
+}
+
+/// Walk a local directory, treating symbolic links as `policy` says:
+/// skipped, followed (each directory once, so loops end), or listed as
+/// entries of their own, the size of their target's path
+fn snapshot_local(root: &str, policy: SymlinkPolicy) -> Result<Snapshot> {
+    let mut entries = Snapshot::new();
+    let root_path = Path::new(root);
+    if !root_path.exists() {
+        return Ok(entries);
+    }
+
+    let mut visited = HashSet::from([std::fs::canonicalize(root_path)?]);
+    let mut skipped = 0;
+    let mut stack = vec![root_path.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let mut metadata = entry.metadata()?;
+            let path = entry.path();
+            let mut size = metadata.len();
+
+            if metadata.is_symlink() {
+                match policy {
+                    SymlinkPolicy::Skip => {
+                        skipped += 1;
+                        continue;
+                    }
+                    SymlinkPolicy::Follow => match std::fs::metadata(&path) {
+                        Ok(target) => {
+                            size = target.len();
+                            metadata = target;
+                        }
+                        // Dangling links have nothing to follow
+                        Err(_) => {
+                            skipped += 1;
+                            continue;
+                        }
+                    },
+                    SymlinkPolicy::Preserve => {
+                        size = std::fs::read_link(&path)?.as_os_str().len() as u64;
+                    }
+                }
+            }
+
+            if metadata.is_dir() {
+                if policy != SymlinkPolicy::Follow || visited.insert(std::fs::canonicalize(&path)?)
+                {
+                    stack.push(path);
+                }
+            } else if metadata.is_file() || metadata.is_symlink() {
+                let relative = path
+                    .strip_prefix(root_path)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or(0);
+                entries.insert(
+                    relative,
+                    EntryState {
+                        size,
+                        modified,
+                        md5: None,
+                    },
+                );
+            }
+        }
+    }
+    symlinks::warn_skipped(root, skipped, policy);
+
+    Ok(entries)
+}
+
+async fn snapshot_azure(uri: &str) -> Result<Snapshot> {
+    let (account, container, prefix) = parse_azure_uri(uri)?;
+    let account = account.ok_or_else(|| {
+        anyhow!(
+            "Tracked sync requires a storage account in '{}': az://<account>/<container>/[path]",
+            uri
+        )
+    })?;
+
+    // Treat the prefix as a directory, like azcopy sync does
+    let prefix = directory_prefix(prefix.as_deref());
+
+    let mut client = AzureClient::new().with_storage_account(&account);
+    let mut entries = Snapshot::new();
+
+    let list_prefix = (!prefix.is_empty()).then_some(prefix.as_str());
+    client
+        .list_blobs_with_callback(&container, list_prefix, None, |items| {
+            for item in items {
+                if let BlobItem::Blob(blob) = item {
+                    let relative = blob.name.strip_prefix(&prefix).unwrap_or(&blob.name);
+                    if relative.is_empty() || relative.ends_with('/') {
+                        continue;
+                    }
+                    entries.insert(
+                        relative.to_string(),
+                        EntryState {
+                            size: blob.properties.content_length,
+                            modified: blob.properties.last_modified_unix,
+                            md5: blob.properties.content_md5.clone(),
+                        },
+                    );
+                }
+            }
+            Ok(())
+        })
+        .await?;
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64, modified: i64) -> EntryState {
+        EntryState {
+            size,
+            modified,
+            md5: None,
+        }
+    }
+
+    fn hashed(size: u64, modified: i64, md5: &str) -> EntryState {
+        EntryState {
+            md5: Some(md5.to_string()),
+            ..entry(size, modified)
+        }
+    }
+
+    fn snapshot_of(entries: &[(&str, EntryState)]) -> Snapshot {
+        entries
+            .iter()
+            .map(|(path, e)| (path.to_string(), e.clone()))
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_snapshot_local_symlink_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), "abc").unwrap();
+        std::fs::write(dir.path().join("outside.txt"), "12345").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("outside.txt"), root.join("link.txt")).unwrap();
+        // A loop back to the root is walked once
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+        let root = root.to_string_lossy().to_string();
+
+        let keys = |snapshot: &Snapshot| snapshot.keys().cloned().collect::<Vec<_>>();
+        let skipped = snapshot_local(&root, SymlinkPolicy::Skip).unwrap();
+        assert_eq!(keys(&skipped), vec!["sub/a.txt"]);
+
+        let followed = snapshot_local(&root, SymlinkPolicy::Follow).unwrap();
+        assert_eq!(keys(&followed), vec!["link.txt", "sub/a.txt"]);
+        assert_eq!(followed["link.txt"].size, 5);
+
+        let preserved = snapshot_local(&root, SymlinkPolicy::Preserve).unwrap();
+        assert_eq!(keys(&preserved), vec!["link.txt", "sub/a.txt", "sub/loop"]);
+        assert_eq!(
+            preserved["link.txt"].size,
+            dir.path().join("outside.txt").as_os_str().len() as u64
+        );
+    }
+
+    #[test]
+    fn test_detect_conflicts_both_changed() {
+        let state = SyncState {
+            source_entries: snapshot_of(&[("a.txt", entry(10, 100))]),
+            destination_entries: snapshot_of(&[("a.txt", entry(10, 105))]),
+            ..Default::default()
+        };
+        let source = snapshot_of(&[("a.txt", entry(12, 200))]);
+        let destination = snapshot_of(&[("a.txt", entry(11, 210))]);
+
+        let conflicts = detect_conflicts(&state, &source, &destination, CompareMode::Mtime);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "a.txt");
+    }
+
+    #[test]
+    fn test_detect_conflicts_one_side_changed() {
+        let state = SyncState {
+            source_entries: snapshot_of(&[("a.txt", entry(10, 100))]),
+            destination_entries: snapshot_of(&[("a.txt", entry(10, 105))]),
+            ..Default::default()
+        };
+        // Only the source changed - a normal update, not a conflict
+        let source = snapshot_of(&[("a.txt", entry(12, 200))]);
+        let destination = snapshot_of(&[("a.txt", entry(10, 105))]);
+
+        assert!(detect_conflicts(&state, &source, &destination, CompareMode::Mtime).is_empty());
+    }
+
+    #[test]
+    fn test_detect_conflicts_first_run_against_existing_tree() {
+        // Uploads don't keep the local mtime, so an identical tree differs
+        // in every time; without a state file none of it is a conflict
+        let state = SyncState::default();
+        let source = snapshot_of(&[("a.txt", entry(1, 100)), ("b/c.txt", entry(2, 100))]);
+        let destination = snapshot_of(&[("a.txt", entry(1, 500)), ("b/c.txt", entry(2, 500))]);
+
+        assert!(detect_conflicts(&state, &source, &destination, CompareMode::Mtime).is_empty());
+    }
+
+    #[test]
+    fn test_detect_conflicts_identical_entries() {
+        let state = SyncState::default();
+        let source = snapshot_of(&[("same.txt", entry(5, 100))]);
+        let destination = snapshot_of(&[("same.txt", entry(5, 100))]);
+
+        assert!(detect_conflicts(&state, &source, &destination, CompareMode::Mtime).is_empty());
+    }
+
+    #[test]
+    fn test_plan_bidirectional_propagates_changes() {
+        let state = SyncState {
+            source_entries: snapshot_of(&[
+                ("unchanged.txt", entry(1, 1)),
+                ("edited-src.txt", entry(1, 1)),
+                ("edited-dst.txt", entry(1, 1)),
+                ("deleted-src.txt", entry(1, 1)),
+                ("deleted-dst.txt", entry(1, 1)),
+            ]),
+            destination_entries: snapshot_of(&[
+                ("unchanged.txt", entry(1, 2)),
+                ("edited-src.txt", entry(1, 2)),
+                ("edited-dst.txt", entry(1, 2)),
+                ("deleted-src.txt", entry(1, 2)),
+                ("deleted-dst.txt", entry(1, 2)),
+            ]),
+            ..Default::default()
+        };
+        let source = snapshot_of(&[
+            ("unchanged.txt", entry(1, 1)),
+            ("edited-src.txt", entry(2, 5)),
+            ("edited-dst.txt", entry(1, 1)),
+            ("deleted-dst.txt", entry(1, 1)),
+            ("new-src.txt", entry(3, 5)),
+        ]);
+        let destination = snapshot_of(&[
+            ("unchanged.txt", entry(1, 2)),
+            ("edited-src.txt", entry(1, 2)),
+            ("edited-dst.txt", entry(4, 6)),
+            ("deleted-src.txt", entry(1, 2)),
+            ("new-dst.txt", entry(3, 5)),
+        ]);
+
+        let actions = plan_bidirectional(&state, &source, &destination, CompareMode::Mtime);
+        assert_eq!(
+            actions,
+            vec![
+                SyncAction::DeleteFromSource("deleted-dst.txt".to_string()),
+                SyncAction::DeleteFromDestination("deleted-src.txt".to_string()),
+                SyncAction::CopyToSource("edited-dst.txt".to_string()),
+                SyncAction::CopyToDestination("edited-src.txt".to_string()),
+                SyncAction::CopyToSource("new-dst.txt".to_string()),
+                SyncAction::CopyToDestination("new-src.txt".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_bidirectional_modification_wins_over_deletion() {
+        let state = SyncState {
+            source_entries: snapshot_of(&[("a.txt", entry(1, 1))]),
+            destination_entries: snapshot_of(&[("a.txt", entry(1, 2))]),
+            ..Default::default()
+        };
+        // Deleted on the source, modified on the destination
+        let source = Snapshot::new();
+        let destination = snapshot_of(&[("a.txt", entry(5, 9))]);
+
+        assert_eq!(
+            plan_bidirectional(&state, &source, &destination, CompareMode::Mtime),
+            vec![SyncAction::CopyToSource("a.txt".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_plan_bidirectional_conflicts() {
+        let state = SyncState {
+            source_entries: snapshot_of(&[("a.txt", entry(1, 1))]),
+            destination_entries: snapshot_of(&[("a.txt", entry(1, 2))]),
+            ..Default::default()
+        };
+
+        // a.txt changed on both sides; b.txt is new on both sides with the
+        // same size, but different hashes show it isn't the same content
+        let source = snapshot_of(&[("a.txt", entry(2, 3)), ("b.txt", hashed(7, 3, "aaa"))]);
+        let destination = snapshot_of(&[("a.txt", entry(3, 4)), ("b.txt", hashed(7, 9, "bbb"))]);
+        let actions = plan_bidirectional(&state, &source, &destination, CompareMode::Mtime);
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(&actions[0], SyncAction::Conflict(c) if c.path == "a.txt"));
+        assert!(matches!(&actions[1], SyncAction::Conflict(c) if c.path == "b.txt"));
+    }
+
+    #[test]
+    fn test_plan_bidirectional_first_run_against_matching_tree() {
+        // Uploads don't keep the local mtime, so only size and MD5 count
+        let state = SyncState::default();
+        let source = snapshot_of(&[
+            ("hashed.txt", hashed(7, 3, "aaa")),
+            ("unhashed.txt", entry(5, 3)),
+            ("resized.txt", entry(5, 3)),
+        ]);
+        let destination = snapshot_of(&[
+            ("hashed.txt", hashed(7, 9, "aaa")),
+            ("unhashed.txt", entry(5, 9)),
+            ("resized.txt", entry(6, 9)),
+        ]);
+
+        let actions = plan_bidirectional(&state, &source, &destination, CompareMode::Mtime);
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(&actions[0], SyncAction::Conflict(c) if c.path == "resized.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_hash_unrecorded_local_trees() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        for (root, edited) in [(&a, "one"), (&b, "two")] {
+            std::fs::create_dir_all(root).unwrap();
+            std::fs::write(root.join("same.txt"), "abc").unwrap();
+            std::fs::write(root.join("edited.txt"), edited).unwrap();
+        }
+        // As after an upload, the copies don't share their times
+        for name in ["same.txt", "edited.txt"] {
+            let file = std::fs::File::options()
+                .write(true)
+                .open(b.join(name))
+                .unwrap();
+            file.set_modified(UNIX_EPOCH).unwrap();
+        }
+        let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+        let mut source = snapshot_local(&a, SymlinkPolicy::Skip).unwrap();
+        let mut destination = snapshot_local(&b, SymlinkPolicy::Skip).unwrap();
+        let state = SyncState::default();
+
+        hash_unrecorded(&state, &a, &mut source, &b, &mut destination)
+            .await
+            .unwrap();
+        assert!(source.values().all(|entry| entry.md5.is_some()));
+        let actions = plan_bidirectional(&state, &source, &destination, CompareMode::Mtime);
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(&actions[0], SyncAction::Conflict(c) if c.path == "edited.txt"));
+    }
+
+    #[test]
+    fn test_plan_bidirectional_first_run_compares_checksums() {
+        let state = SyncState::default();
+        let source = snapshot_of(&[
+            ("same.txt", hashed(7, 3, "aaa")),
+            ("edited.txt", hashed(7, 3, "bbb")),
+        ]);
+        let destination = snapshot_of(&[
+            ("same.txt", hashed(7, 9, "aaa")),
+            ("edited.txt", hashed(7, 9, "ccc")),
+        ]);
+
+        let actions = plan_bidirectional(&state, &source, &destination, CompareMode::Checksum);
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(&actions[0], SyncAction::Conflict(c) if c.path == "edited.txt"));
+    }
+
+    #[test]
+    fn test_flagged_conflicts_stay_conflicts() {
+        let previous = SyncState {
+            source_entries: snapshot_of(&[("a.txt", entry(1, 1))]),
+            destination_entries: snapshot_of(&[("a.txt", entry(1, 1))]),
+            ..Default::default()
+        };
+        let source = snapshot_of(&[("a.txt", entry(2, 3)), ("new.txt", entry(4, 3))]);
+        let destination = snapshot_of(&[("a.txt", entry(3, 4)), ("new.txt", entry(5, 4))]);
+
+        let mut state = SyncState {
+            source_entries: source.clone(),
+            destination_entries: destination.clone(),
+            ..Default::default()
+        };
+        state.keep_previous(&previous, &["a.txt".to_string(), "new.txt".to_string()]);
+        assert_eq!(state.source_entries, previous.source_entries);
+
+        let actions = plan_bidirectional(&state, &source, &destination, CompareMode::Mtime);
+        assert_eq!(actions.len(), 2);
+        assert!(actions
+            .iter()
+            .all(|action| matches!(action, SyncAction::Conflict(_))));
+    }
+
+    #[test]
+    fn test_delete_limit() {
+        let percent: DeleteLimit = "10%".parse().unwrap();
+        assert_eq!(percent, DeleteLimit::Percent(10.0));
+        assert!(!percent.exceeded_by(10, 100));
+        assert!(percent.exceeded_by(11, 100));
+        // Deleting everything because the source came up empty
+        assert!(percent.exceeded_by(3, 3));
+        assert!(!percent.exceeded_by(0, 0));
+
+        let count: DeleteLimit = "50".parse().unwrap();
+        assert!(!count.exceeded_by(50, 60));
+        assert!(count.exceeded_by(51, 10_000));
+
+        assert!("150%".parse::<DeleteLimit>().is_err());
+        assert!("many".parse::<DeleteLimit>().is_err());
+    }
+
+    #[test]
+    fn test_conflict_name() {
+        assert_eq!(conflict_name("data.csv", 42), "data.conflict-42.csv");
+        assert_eq!(
+            conflict_name("dir/sub/report.tar.gz", 7),
+            "dir/sub/report.tar.conflict-7.gz"
+        );
+        assert_eq!(conflict_name("Makefile", 1), "Makefile.conflict-1");
+        assert_eq!(conflict_name("dir/.env", 1), "dir/.env.conflict-1");
+    }
+
+    #[test]
+    fn test_join_location() {
+        assert_eq!(
+            join_location("az://acct/cont/prefix/", "a/b.txt"),
+            "az://acct/cont/prefix/a/b.txt"
+        );
+        assert_eq!(
+            join_location("az://acct/cont", "b.txt"),
+            "az://acct/cont/b.txt"
+        );
+    }
+
+    #[test]
+    fn test_same_as_compare_modes() {
+        let a = hashed(10, 100, "aa");
+        // Same size and time, different content
+        let corrupted = hashed(10, 100, "bb");
+        let touched = hashed(10, 200, "aa");
+
+        assert!(a.same_as(&corrupted, CompareMode::Mtime));
+        assert!(!a.same_as(&corrupted, CompareMode::Checksum));
+        assert!(!a.same_as(&touched, CompareMode::Mtime));
+        assert!(a.same_as(&touched, CompareMode::Size));
+        assert!(a.same_as(&touched, CompareMode::Checksum));
+        // Without a hash on one side, checksum mode falls back to size and time
+        assert!(!a.same_as(&entry(10, 200), CompareMode::Checksum));
+    }
+
+    #[test]
+    fn test_plan_one_way_by_size() {
+        let source = snapshot_of(&[
+            ("same.txt", entry(10, 100)),
+            ("grown.txt", entry(20, 100)),
+            ("new.txt", entry(5, 100)),
+        ]);
+        let destination = snapshot_of(&[
+            ("same.txt", entry(10, 999)),
+            ("grown.txt", entry(10, 100)),
+            ("extra.txt", entry(1, 100)),
+        ]);
+
+        assert_eq!(
+            plan_one_way(&source, &destination, CompareMode::Size, true),
+            vec![
+                SyncAction::CopyToDestination("grown.txt".to_string()),
+                SyncAction::CopyToDestination("new.txt".to_string()),
+                SyncAction::DeleteFromDestination("extra.txt".to_string()),
+            ]
+        );
+        assert_eq!(
+            plan_one_way(&source, &destination, CompareMode::Size, false).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_sync_state_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let path_str = path.to_str().unwrap();
+
+        // Missing file loads as empty state
+        let empty = SyncState::load(path_str).unwrap();
+        assert!(empty.source_entries.is_empty());
+
+        let state = SyncState {
+            source: "/local".to_string(),
+            destination: "az://acct/cont/".to_string(),
+            source_entries: snapshot_of(&[("a.txt", entry(1, 2))]),
+            destination_entries: Snapshot::new(),
+        };
+        state.save(path_str).unwrap();
+
+        let loaded = SyncState::load(path_str).unwrap();
+        assert!(loaded.matches("/local", "az://acct/cont/"));
+        assert_eq!(loaded.source_entries.get("a.txt"), Some(&entry(1, 2)));
+    }
+}
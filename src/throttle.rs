@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::process::Command as AsyncCommand;
+
+// ============================================================================
+// Throttling Feedback - Concurrency learned from server-busy responses
+// ============================================================================
+
+/// Environment variable azcopy reads its concurrency from
+const CONCURRENCY_VAR: &str = "AZCOPY_CONCURRENCY_VALUE";
+
+/// Share of requests answered with 503/429 above which the next job to the
+/// same account runs with half the concurrency
+pub const STEP_DOWN_BUSY_PERCENT: f64 = 5.0;
+
+/// Below this share a learned limit is raised again, a quarter at a time
+const RECOVER_BUSY_PERCENT: f64 = 0.5;
+
+/// Never step down below this many concurrent requests
+const MIN_CONCURRENCY: u32 = 8;
+
+/// Concurrency limits learned per storage account
+///
+/// Azure throttles at the account level, so one container being hammered
+/// slows every transfer to its account.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LearnedLimits {
+    accounts: BTreeMap<String, u32>,
+}
+
+impl LearnedLimits {
+    fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("azst").join("concurrency.json"))
+    }
+
+    /// Missing or unreadable caches count as empty: they only hold hints
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow!("No cache directory"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Storage account addressed by an azcopy source or destination URL
+pub fn account_from_url(url: &str) -> Option<&str> {
+    url.strip_prefix("https://")?
+        .split_once(".blob.core.windows.net")
+        .map(|(account, _)| account)
+}
+
+/// Concurrency azcopy picks by itself: 16 per CPU, between 32 and 300
+pub fn default_concurrency() -> u32 {
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+    (16 * cpus).clamp(32, 300)
+}
+
+/// Start an azcopy job against `account` at its learned concurrency, or
+/// `tuned`, the one tuning chose for its kind of account
+///
+/// Returns the concurrency the job runs with, or None when the user set
+/// one themselves (AZCOPY_CONCURRENCY_VALUE, --concurrency or the config
+/// file, already applied to `cmd`), in which case nothing is learned.
+pub fn apply_learned_concurrency(
+    cmd: &mut AsyncCommand,
+    account: &str,
+    tuned: Option<u32>,
+) -> Option<u32> {
+    let configured = cmd
+        .as_std()
+        .get_envs()
+        .any(|(var, value)| var == CONCURRENCY_VAR && value.is_some());
+    if configured || std::env::var_os(CONCURRENCY_VAR).is_some() {
+        return None;
+    }
+
+    match LearnedLimits::load().accounts.get(account) {
+        Some(&limit) => {
+            status!(
+                "{} Using concurrency {} for {} (learned from throttling)",
+                "ℹ".blue(),
+                limit,
+                account
+            );
+            cmd.env(CONCURRENCY_VAR, limit.to_string());
+            Some(limit)
+        }
+        None => match tuned {
+            Some(tuned) => {
+                cmd.env(CONCURRENCY_VAR, tuned.to_string());
+                Some(tuned)
+            }
+            None => Some(default_concurrency()),
+        },
+    }
+}
+
+/// Remember how a job against `account` fared, for the next one to start from
+pub fn record_server_busy(account: &str, concurrency: u32, server_busy_percent: f64) {
+    let mut limits = LearnedLimits::load();
+    let learned = limits.accounts.get(account).copied();
+    let next = next_limit(concurrency, learned.is_some(), server_busy_percent);
+    if next == learned {
+        return;
+    }
+
+    match next {
+        Some(limit) => {
+            if limit < concurrency {
+                status!(
+                    "{} {} was busy for {:.1}% of requests; future transfers will use concurrency {}",
+                    "ℹ".blue(),
+                    account,
+                    server_busy_percent,
+                    limit
+                );
+            }
+            limits.accounts.insert(account.to_string(), limit);
+        }
+        None => {
+            limits.accounts.remove(account);
+        }
+    }
+
+    if let Err(e) = limits.save() {
+        tracing::debug!("Failed to save learned concurrency limits: {:#}", e);
+    }
+}
+
+/// The limit for the next job, given the concurrency this one ran with and the
+/// share of its requests the service turned away
+///
+/// None means azcopy's own default is fine.
+fn next_limit(concurrency: u32, learned: bool, server_busy_percent: f64) -> Option<u32> {
+    if server_busy_percent > STEP_DOWN_BUSY_PERCENT {
+        return Some((concurrency / 2).max(MIN_CONCURRENCY));
+    }
+    if !learned {
+        return None;
+    }
+    if server_busy_percent < RECOVER_BUSY_PERCENT {
+        let raised = concurrency + (concurrency / 4).max(1);
+        return (raised < default_concurrency()).then_some(raised);
+    }
+    Some(concurrency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_from_url() {
+        assert_eq!(
+            account_from_url("https://myaccount.blob.core.windows.net/data/file.csv"),
+            Some("myaccount")
+        );
+        assert_eq!(account_from_url("/local/data"), None);
+    }
+
+    #[test]
+    fn test_next_limit_steps_down_when_busy() {
+        assert_eq!(next_limit(128, false, 12.0), Some(64));
+        assert_eq!(next_limit(10, true, 50.0), Some(MIN_CONCURRENCY));
+    }
+
+    #[test]
+    fn test_next_limit_recovers_when_quiet() {
+        assert_eq!(next_limit(128, false, 0.0), None);
+        assert_eq!(next_limit(16, true, 0.0), Some(20));
+        assert_eq!(next_limit(16, true, 2.0), Some(16));
+        assert_eq!(next_limit(default_concurrency(), true, 0.0), None);
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::azure::AzureClient;
+use crate::throttle::default_concurrency;
+use crate::utils::parse_azure_uri;
+
+// ============================================================================
+// Account Tuning - Transfer defaults for the kind of account written to
+// ============================================================================
+
+/// Block size for premium block blob accounts, in MB: their low latency
+/// keeps more, smaller requests busy where standard accounts want fewer,
+/// larger ones
+const PREMIUM_BLOCK_SIZE_MB: f64 = 4.0;
+
+/// Most requests kept in flight against a premium block blob account
+const PREMIUM_MAX_CONCURRENCY: u32 = 600;
+
+/// How transfers are tuned for the account they write to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum TuningMode {
+    /// Look up the account's kind and SKU through the management API
+    #[default]
+    Auto,
+    /// azcopy's own defaults
+    Standard,
+    /// Premium block blob defaults, without looking the account up
+    Premium,
+}
+
+/// Defaults chosen for a transfer; --block-size-mb, --concurrency and
+/// concurrency learned from throttling still win
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    pub block_size_mb: f64,
+    pub concurrency: u32,
+}
+
+impl Tuning {
+    fn premium() -> Self {
+        Self {
+            block_size_mb: PREMIUM_BLOCK_SIZE_MB,
+            concurrency: (default_concurrency() * 2).min(PREMIUM_MAX_CONCURRENCY),
+        }
+    }
+}
+
+/// Pick the defaults for a transfer writing to `destination` under `mode`,
+/// or None to keep azcopy's
+///
+/// Under --tuning auto the account is looked up once and remembered; an
+/// account the management API doesn't show (another subscription, SAS-only
+/// access) keeps azcopy's defaults.
+pub async fn tune(destination: &str, mode: TuningMode) -> Option<Tuning> {
+    let tuning = match mode {
+        TuningMode::Standard => None,
+        TuningMode::Premium => Some(Tuning::premium()),
+        TuningMode::Auto => {
+            let (Some(account), _, _) = parse_azure_uri(destination).ok()? else {
+                return None;
+            };
+            // Boxed, as listing accounts is a large future for every
+            // transfer to carry
+            match Box::pin(is_premium_account(&account)).await {
+                Ok(true) => Some(Tuning::premium()),
+                Ok(false) => None,
+                Err(e) => {
+                    tracing::debug!("Keeping azcopy's defaults for {}: {:#}", account, e);
+                    None
+                }
+            }
+        }
+    };
+    match &tuning {
+        Some(tuning) => tracing::debug!(
+            "Tuned for a premium block blob account: {} MB blocks, concurrency {}",
+            tuning.block_size_mb,
+            tuning.concurrency
+        ),
+        None => tracing::debug!("Using azcopy's default block size and concurrency"),
+    }
+    tuning
+}
+
+/// Whether an account with this kind and SKU stores block blobs on premium
+/// (SSD) storage; premium StorageV2 accounts only hold page blobs
+fn is_premium_block_blob(kind: Option<&str>, sku: Option<&str>) -> bool {
+    kind == Some("BlockBlobStorage") && sku.is_none_or(|sku| sku.starts_with("Premium"))
+}
+
+/// Kinds of the accounts seen so far, as they never change
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KnownAccounts {
+    /// Account name -> whether it's a premium block blob account
+    accounts: BTreeMap<String, bool>,
+}
+
+impl KnownAccounts {
+    fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("azst").join("account_kinds.json"))
+    }
+
+    /// Missing or unreadable caches count as empty: they only save lookups
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow!("No cache directory"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+async fn is_premium_account(account: &str) -> Result<bool> {
+    let mut known = KnownAccounts::load();
+    if let Some(premium) = known.accounts.get(account) {
+        return Ok(*premium);
+    }
+
+    let listed = AzureClient::new().list_storage_accounts().await?;
+    for info in &listed {
+        known.accounts.insert(
+            info.name.clone(),
+            is_premium_block_blob(info.kind.as_deref(), info.sku.as_deref()),
+        );
+    }
+    if let Err(e) = known.save() {
+        tracing::debug!("Failed to save account kinds: {:#}", e);
+    }
+    known
+        .accounts
+        .get(account)
+        .copied()
+        .ok_or_else(|| anyhow!("account not found in the current subscription"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_premium_block_blob() {
+        assert!(is_premium_block_blob(
+            Some("BlockBlobStorage"),
+            Some("Premium_LRS")
+        ));
+        assert!(is_premium_block_blob(Some("BlockBlobStorage"), None));
+        assert!(!is_premium_block_blob(
+            Some("StorageV2"),
+            Some("Premium_LRS")
+        ));
+        assert!(!is_premium_block_blob(
+            Some("StorageV2"),
+            Some("Standard_GRS")
+        ));
+        assert!(!is_premium_block_blob(None, None));
+    }
+
+    #[tokio::test]
+    async fn test_tune_follows_mode() {
+        assert_eq!(tune("az://acct/c/", TuningMode::Standard).await, None);
+
+        let tuning = tune("/local/dir", TuningMode::Premium).await.unwrap();
+        assert_eq!(tuning.block_size_mb, PREMIUM_BLOCK_SIZE_MB);
+        assert!(tuning.concurrency <= PREMIUM_MAX_CONCURRENCY);
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::{anyhow, Result};
+use azure_core::Url;
+use azure_storage_blobs::blob::CopyStatus;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::Read;
+use std::time::Duration;
+
+use crate::audit_log;
+use crate::azcopy_output::FailedTransfer;
+use crate::azure::{AzureClient, CopyState};
+use crate::cancel;
+use crate::dry_run::{DryRunPlan, PlannedAction, PlannedTransfer};
+use crate::error::http_status;
+use crate::network;
+use crate::output::is_deterministic;
+use crate::utils::{format_size, parse_azure_uri, uri_base};
+
+// ============================================================================
+// URL Copies - Ingesting http(s) sources into blobs, server-side
+// ============================================================================
+
+/// How often `--poll` checks on an asynchronous copy
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Whether a cp source is a web URL rather than a local path or az:// URI
+pub fn is_http_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// The URLs of a list, one per line; blank lines and # comments are skipped
+pub fn parse_url_list(input: &str) -> Result<Vec<Url>> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if !is_http_url(line) {
+                return Err(anyhow!("'{}' is not an http(s) URL", line));
+            }
+            Url::parse(line).map_err(|e| anyhow!("Invalid URL '{}': {}", line, e))
+        })
+        .collect()
+}
+
+/// Blob a URL is copied to: `blob_path` itself, or the URL's file name under
+/// it when copying `into` a prefix
+pub fn target_blob(url: &Url, blob_path: Option<&str>, into: bool) -> Result<String> {
+    let prefix = blob_path.unwrap_or_default();
+    if !into && !prefix.is_empty() && !prefix.ends_with('/') {
+        return Ok(prefix.to_string());
+    }
+    let name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| {
+            anyhow!(
+                "Can't name a blob after {}; give the destination blob's full name",
+                url
+            )
+        })?;
+    let name = percent_encoding::percent_decode_str(name).decode_utf8_lossy();
+    if prefix.is_empty() || prefix.ends_with('/') {
+        Ok(format!("{}{}", prefix, name))
+    } else {
+        Ok(format!("{}/{}", prefix, name))
+    }
+}
+
+/// Copy `source`, a URL or "-" for a list of URLs on stdin, into the Azure
+/// `destination` without downloading it
+///
+/// Sources the service can copy in one request are done when this returns;
+/// larger ones keep copying in the background unless `poll` waits for them.
+/// Returns the URLs that couldn't be copied.
+pub async fn copy_from_urls(
+    source: &str,
+    destination: &str,
+    poll: bool,
+    dry_run: bool,
+) -> Result<Vec<FailedTransfer>> {
+    let (account, container, blob_path) = parse_azure_uri(destination)?;
+    let urls = if source == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        parse_url_list(&input)?
+    } else {
+        parse_url_list(source)?
+    };
+    // A list is always copied into a prefix, even with a single URL on it
+    let into = source == "-" || urls.len() > 1;
+    let base = uri_base(destination, blob_path.as_deref());
+
+    let http = network::http_client()?;
+    let mut targets = Vec::new();
+    for url in urls {
+        let blob = target_blob(&url, blob_path.as_deref(), into)?;
+        let size = source_size(&http, &url).await;
+        targets.push((url, blob, size));
+    }
+
+    if dry_run {
+        let planned = targets
+            .iter()
+            .map(|(url, blob, size)| PlannedTransfer {
+                action: PlannedAction::Copy,
+                source: url.to_string(),
+                destination: Some(format!("{}/{}", base, blob)),
+                size: *size,
+            })
+            .collect();
+        DryRunPlan::new(planned).print();
+        return Ok(Vec::new());
+    }
+
+    let mut client = match account {
+        Some(account) => AzureClient::new().with_storage_account(&account),
+        None => AzureClient::new(),
+    };
+    let mut failures = Vec::new();
+    for (url, blob, size) in targets {
+        if cancel::is_cancelled() {
+            break;
+        }
+        let uri = format!("{}/{}", base, blob);
+        audit_log::add_uri(&uri);
+        let mut state = client
+            .copy_from_url(&url, &container, &blob, size)
+            .await
+            .map_err(anyhow::Error::from);
+        if poll {
+            if let Ok(CopyState {
+                status: CopyStatus::Pending,
+                ..
+            }) = state
+            {
+                state = wait_for_copy(&mut client, &container, &blob, &uri).await;
+            }
+        }
+        match state {
+            Ok(state) if state.status == CopyStatus::Success => {
+                let bytes = state.progress.map(|(_, total)| total).or(size);
+                audit_log::record(1, bytes.unwrap_or(0));
+                status!(
+                    "{} Copied {} to {}{}",
+                    "✓".green(),
+                    url,
+                    uri,
+                    bytes
+                        .map(|bytes| format!(" ({})", format_size(bytes)))
+                        .unwrap_or_default()
+                );
+            }
+            Ok(state) if state.status == CopyStatus::Pending => {
+                status!(
+                    "{} Copying {} to {} in the background (copy id {}); use --poll to wait for it",
+                    "ℹ".blue(),
+                    url,
+                    uri,
+                    state.id.as_deref().unwrap_or("unknown")
+                );
+            }
+            Ok(state) => {
+                eprintln!(
+                    "{} Copy of {} to {} {}{}",
+                    "⚠".yellow(),
+                    url,
+                    uri,
+                    state.status,
+                    state
+                        .description
+                        .map(|description| format!(": {}", description))
+                        .unwrap_or_default()
+                );
+                failures.push(failure(&url, &uri, 0));
+            }
+            Err(e) => {
+                eprintln!("{} {:#}", "⚠".yellow(), e);
+                let code = http_status(&e).map_or(0, |status| u16::from(status) as i32);
+                failures.push(failure(&url, &uri, code));
+            }
+        }
+    }
+    audit_log::record_failed(failures.len() as u64);
+    Ok(failures)
+}
+
+fn failure(url: &Url, uri: &str, error_code: i32) -> FailedTransfer {
+    FailedTransfer {
+        source: url.to_string(),
+        destination: uri.to_string(),
+        error_code,
+    }
+}
+
+/// Size of the object at `url`, if it answers a HEAD request with one
+async fn source_size(http: &reqwest::Client, url: &Url) -> Option<u64> {
+    let response = http.head(url.as_str()).send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Check on an asynchronous copy until it ends or the command is cancelled,
+/// which leaves the copy running in the background
+async fn wait_for_copy(
+    client: &mut AzureClient,
+    container: &str,
+    blob: &str,
+    uri: &str,
+) -> Result<CopyState> {
+    let progress_bar = new_progress_bar(uri);
+    let token = cancel::token();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = token.cancelled() => {
+                progress_bar.abandon();
+                return Err(cancel::error().into());
+            }
+        }
+        let state = client
+            .copy_state(container, blob)
+            .await?
+            .ok_or_else(|| anyhow!("{} no longer records a copy", uri))?;
+        if let Some((copied, total)) = state.progress {
+            progress_bar.set_length(total);
+            progress_bar.set_position(copied);
+        }
+        if state.status != CopyStatus::Pending {
+            progress_bar.finish_and_clear();
+            return Ok(state);
+        }
+    }
+}
+
+/// Byte progress of an asynchronous copy, hidden under --quiet and
+/// --deterministic
+fn new_progress_bar(uri: &str) -> ProgressBar {
+    if crate::logging::is_quiet() || is_deterministic() {
+        return ProgressBar::hidden();
+    }
+    let progress_bar = ProgressBar::new(0);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}")
+            .expect("Invalid progress bar template")
+            .progress_chars("#>-"),
+    );
+    progress_bar.set_message(uri.to_string());
+    progress_bar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url_list() {
+        let urls = parse_url_list(
+            "https://example.com/a.csv\n\n# assets\n  http://example.com/img/b%20c.png  \n",
+        )
+        .unwrap();
+        assert_eq!(urls.len(), 2);
+        assert!(parse_url_list("/local/file\n").is_err());
+    }
+
+    #[test]
+    fn test_target_blob() {
+        let url = Url::parse("https://example.com/img/b%20c.png?v=2").unwrap();
+        assert_eq!(
+            target_blob(&url, Some("web/logo.png"), false).unwrap(),
+            "web/logo.png"
+        );
+        assert_eq!(
+            target_blob(&url, Some("web/"), false).unwrap(),
+            "web/b c.png"
+        );
+        assert_eq!(target_blob(&url, Some("web"), true).unwrap(), "web/b c.png");
+        assert_eq!(target_blob(&url, None, false).unwrap(), "b c.png");
+
+        let root = Url::parse("https://example.com/").unwrap();
+        assert!(target_blob(&root, None, false).is_err());
+    }
+}
@@ -1,6 +1,9 @@
 use anyhow::{anyhow, Result};
+use std::borrow::Cow;
 use std::path::Path;
 
+use crate::error::AzstError;
+
 /// Parse an Azure storage URI (az://storage_account/container/path) into components
 /// Returns (storage_account, container, blob_path)
 ///
@@ -10,17 +13,27 @@ use std::path::Path;
 /// - az://account/container -> (Some(account), container, None)
 /// - az://container/path (legacy) -> (None, container, Some(path))
 /// - az://container/ (legacy) -> (None, container, None)
-pub fn parse_azure_uri(uri: &str) -> Result<(Option<String>, String, Option<String>)> {
+pub fn parse_azure_uri(uri: &str) -> Result<(Option<String>, String, Option<String>), AzstError> {
     if !uri.starts_with("az://") {
-        return Err(anyhow!("Invalid Azure URI. Must start with 'az://'"));
+        return Err(scheme_error(uri));
     }
 
     let path_part = &uri[5..]; // Remove "az://" prefix
     let parts: Vec<&str> = path_part.splitn(3, '/').collect();
 
     if parts.is_empty() || parts[0].is_empty() {
-        return Err(anyhow!(
-            "Invalid Azure URI. Storage account or container name is required"
+        let suggestion = path_part
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .filter(|s| !s.is_empty())
+            .map(|_| format!("az://{}", path_part.trim_start_matches('/')));
+        return Err(uri_error(
+            uri,
+            5,
+            1,
+            "storage account or container name is required",
+            suggestion,
         ));
     }
 
@@ -36,9 +49,47 @@ pub fn parse_azure_uri(uri: &str) -> Result<(Option<String>, String, Option<Stri
         Ok((storage_account, container, None))
     } else if parts.len() >= 2 && is_storage_account_name(parts[0]) {
         // New format: az://account/container/path
+        let container_start = 5 + parts[0].len() + 1;
+        let has_path = parts.len() > 2 && !parts[2].is_empty();
+        if parts[1].is_empty() && has_path {
+            return Err(uri_error(
+                uri,
+                container_start,
+                1,
+                "container name is missing",
+                Some(format!("az://{}/<container>/{}", parts[0], parts[2])),
+            ));
+        }
+        if contains_wildcard(parts[1]) {
+            return Err(uri_error(
+                uri,
+                container_start,
+                parts[1].len(),
+                "wildcards in the container name only work with ls and du",
+                None,
+            ));
+        }
+        if !parts[1].is_empty() && !is_container_name(parts[1]) {
+            let suggestion = suggest_container_name(parts[1]).map(|name| {
+                let rest = if parts.len() > 2 {
+                    format!("/{}", parts[2])
+                } else {
+                    String::new()
+                };
+                format!("az://{}/{}{}", parts[0], name, rest)
+            });
+            return Err(uri_error(
+                uri,
+                container_start,
+                parts[1].len(),
+                "container names must be 3-63 lowercase letters, digits or single hyphens, starting and ending with a letter or digit",
+                suggestion,
+            ));
+        }
+
         let storage_account = Some(parts[0].to_string());
         let container = parts[1].to_string();
-        let blob_path = if parts.len() > 2 && !parts[2].is_empty() {
+        let blob_path = if has_path {
             Some(parts[2].to_string())
         } else {
             None
@@ -63,9 +114,114 @@ pub fn parse_azure_uri(uri: &str) -> Result<(Option<String>, String, Option<Stri
     }
 }
 
+/// Expand the `az:container/path` shorthand into a full URI in `account`
+///
+/// Unlike the legacy `az://container/path` form, which is told apart from
+/// `az://account/container` by guessing from the names, the shorthand always
+/// names a container of the default account.
+pub fn expand_account_shorthand(arg: &str, account: &str) -> Option<String> {
+    if is_azure_uri(arg) {
+        return None;
+    }
+    let rest = arg.strip_prefix("az:")?;
+    Some(format!("az://{}/{}", account, rest.trim_start_matches('/')))
+}
+
+/// Build an error for a URI that doesn't use the az:// scheme, suggesting the
+/// equivalent az:// form when the intent is recognizable
+fn scheme_error(uri: &str) -> AzstError {
+    // https://account.blob.core.windows.net/container/path
+    let suggestion = if let Some(rest) = uri
+        .strip_prefix("https://")
+        .or_else(|| uri.strip_prefix("http://"))
+    {
+        rest.split_once(".blob.core.windows.net")
+            .map(|(account, path)| format!("az://{}{}", account, path))
+    } else if let Some((scheme, rest)) = uri.split_once("://") {
+        // Another cloud's scheme (gs://, s3://) or a typo of az://
+        Some(format!("az://{}", rest)).filter(|_| !scheme.is_empty())
+    } else {
+        // Typos like az:/account or az:account
+        uri.strip_prefix("az:")
+            .map(|rest| format!("az://{}", rest.trim_start_matches('/')))
+    };
+
+    let scheme_len = uri.find("://").map(|pos| pos + 3).unwrap_or(0);
+    let reason = if uri.starts_with("az:") {
+        "Azure URIs must start with 'az://' ('az:container/path' needs a default account: azst account set <name>)"
+    } else if scheme_len > 0 && !uri.starts_with("http") {
+        "Azure URIs must start with 'az://' or an alias from 'azst alias add'"
+    } else {
+        "Azure URIs must start with 'az://'"
+    };
+    uri_error(uri, 0, scheme_len.max(1), reason, suggestion)
+}
+
+/// Format a URI error that points at the offending segment and optionally
+/// suggests a corrected URI
+fn uri_error(
+    uri: &str,
+    start: usize,
+    len: usize,
+    reason: &str,
+    suggestion: Option<String>,
+) -> AzstError {
+    let mut message = format!(
+        "Invalid Azure URI '{}'\n  {}\n  {}{} {}",
+        uri,
+        uri,
+        " ".repeat(start),
+        "^".repeat(len.max(1)),
+        reason
+    );
+    if let Some(suggestion) = suggestion {
+        message.push_str(&format!("\n  Did you mean: {}", suggestion));
+    }
+    AzstError::InvalidUri(message)
+}
+
+/// Check if a string is a valid container name
+/// Container names: 3-63 chars, lowercase letters, numbers and single hyphens,
+/// starting and ending with a letter or number ($root, $web and $logs are special)
+pub fn is_container_name(s: &str) -> bool {
+    if matches!(s, "$root" | "$web" | "$logs") {
+        return true;
+    }
+
+    (3..=63).contains(&s.len())
+        && s.chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !s.starts_with('-')
+        && !s.ends_with('-')
+        && !s.contains("--")
+}
+
+/// Suggest a valid container name close to an invalid one
+fn suggest_container_name(s: &str) -> Option<String> {
+    let mut name = String::new();
+    for c in s.to_lowercase().chars() {
+        let c = if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            c
+        } else {
+            '-'
+        };
+        if !(c == '-' && (name.is_empty() || name.ends_with('-'))) {
+            name.push(c);
+        }
+    }
+    let name = name
+        .trim_end_matches('-')
+        .chars()
+        .take(63)
+        .collect::<String>();
+    let name = name.trim_end_matches('-').to_string();
+
+    Some(name).filter(|name| is_container_name(name))
+}
+
 /// Check if a string looks like a storage account name
 /// Storage account names: 3-24 chars, lowercase letters and numbers only
-fn is_storage_account_name(s: &str) -> bool {
+pub fn is_storage_account_name(s: &str) -> bool {
     let len = s.len();
     (3..=24).contains(&len)
         && s.chars()
@@ -77,6 +233,40 @@ pub fn is_azure_uri(path: &str) -> bool {
     path.starts_with("az://")
 }
 
+/// A location given on the command line, with a Windows path written with
+/// forward slashes like every other location azst handles
+///
+/// On Windows `C:\data\` becomes `C:/data/` and `\\server\share\x` becomes
+/// `//server/share/x`, so trailing slashes keep their meaning and blob names
+/// built from the path get `/` separators. The `\\?\` long-path prefix is
+/// dropped, as the standard library and azcopy add it when a path needs it.
+/// Elsewhere a backslash is an ordinary file name character.
+pub fn parse_location(value: &str) -> Result<String> {
+    if cfg!(windows) && !is_azure_uri(value) && !value.contains("://") {
+        Ok(windows_path_to_slashes(value))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+fn windows_path_to_slashes(path: &str) -> String {
+    let path = match path.strip_prefix(r"\\?\UNC\") {
+        Some(share) => format!(r"\\{}", share),
+        None => path.strip_prefix(r"\\?\").unwrap_or(path).to_string(),
+    };
+    path.replace('\\', "/")
+}
+
+/// A local path in the platform's own form, with backslashes on Windows, for
+/// the arguments of azcopy; URLs are left alone
+pub fn native_path(location: &str) -> Cow<'_, str> {
+    if cfg!(windows) && !location.contains("://") {
+        Cow::Owned(location.replace('/', "\\"))
+    } else {
+        Cow::Borrowed(location)
+    }
+}
+
 /// Format file size in human readable format
 pub fn format_size(size: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
@@ -95,6 +285,37 @@ pub fn format_size(size: u64) -> String {
     }
 }
 
+/// Format a count with thousands separators, e.g. 1,532
+pub fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// An Azure URI without its blob `path`, e.g. az://acct/container
+pub fn uri_base<'a>(uri: &'a str, path: Option<&str>) -> &'a str {
+    let uri = uri.trim_end_matches('/');
+    match path.map(|path| path.trim_end_matches('/')) {
+        Some(path) if !path.is_empty() => uri.strip_suffix(path).unwrap_or(uri),
+        _ => uri,
+    }
+    .trim_end_matches('/')
+}
+
+/// A blob path treated as a directory: with a trailing slash, or empty for
+/// the whole container
+pub fn directory_prefix(path: Option<&str>) -> String {
+    path.map(|p| format!("{}/", p.trim_end_matches('/')))
+        .filter(|p| p != "/")
+        .unwrap_or_default()
+}
+
 /// Get the filename from a path (works with both local and Azure paths)
 pub fn get_filename(path: &str) -> String {
     if is_azure_uri(path) {
@@ -171,6 +392,112 @@ pub fn split_wildcard_path(path: &str) -> Option<(String, String)> {
     Some((prefix, pattern))
 }
 
+/// Options wildcards match names with: `*`, `?` and `[...]` never match a
+/// slash, so only `**` reaches across directories
+const GLOB_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// A path with wildcards, matched against names the way gsutil matches
+/// object names: `*`, `?` and `[...]` stay within a path segment, `**`
+/// spans any number of them, and a trailing slash only matches directories
+///
+/// Every command taking wildcards in az:// paths matches names with it.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    /// The part of the path before the segment with the first wildcard
+    prefix: String,
+    pattern: glob::Pattern,
+    /// Whether a name can match below the pattern's first path segment
+    spans_directories: bool,
+    /// Whether the pattern ended with a slash
+    dirs_only: bool,
+}
+
+/// How a name matches a `Glob`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobMatch<'a> {
+    /// The name itself matches
+    Name,
+    /// The name is inside a matching directory, given with its slash
+    Under(&'a str),
+}
+
+impl Glob {
+    /// The glob of a path, or None when the path has no wildcards
+    pub fn new(path: &str) -> Result<Option<Self>> {
+        let Some((prefix, pattern)) = split_wildcard_path(path) else {
+            return Ok(None);
+        };
+        let dirs_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        let compiled = glob::Pattern::new(pattern)
+            .map_err(|e| anyhow!("Invalid wildcard pattern '{}': {}", path, e.msg))?;
+        Ok(Some(Self {
+            prefix,
+            pattern: compiled,
+            spans_directories: pattern.contains('/') || contains_recursive_wildcard(pattern),
+            dirs_only,
+        }))
+    }
+
+    /// The part of the path before the first wildcard, which every match
+    /// starts with: the prefix to list
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Whether matches can lie deeper than the prefix's own entries, so a
+    /// listing of them can't stop at the next slash
+    pub fn spans_directories(&self) -> bool {
+        self.spans_directories
+    }
+
+    /// How `name`, a full name starting with the prefix, matches: itself,
+    /// or as a name inside the shallowest directory that matches
+    ///
+    /// Directory names end with a slash, as listings give them.
+    pub fn matches<'a>(&self, name: &'a str) -> Option<GlobMatch<'a>> {
+        let relative = name.strip_prefix(self.prefix.as_str())?;
+        let is_dir = relative.ends_with('/');
+        let relative = relative.trim_end_matches('/');
+        if self.pattern.matches_with(relative, GLOB_OPTIONS) && (is_dir || !self.dirs_only) {
+            return Some(GlobMatch::Name);
+        }
+        relative
+            .match_indices('/')
+            .map(|(slash, _)| slash)
+            .find(|&slash| self.pattern.matches_with(&relative[..slash], GLOB_OPTIONS))
+            .map(|slash| GlobMatch::Under(&name[..self.prefix.len() + slash + 1]))
+    }
+
+    /// Whether a command selects `name`: a matching name, or with
+    /// `recursive` anything inside a matching directory too
+    pub fn selects(&self, name: &str, recursive: bool) -> bool {
+        match self.matches(name) {
+            Some(GlobMatch::Name) => true,
+            Some(GlobMatch::Under(_)) => recursive,
+            None => false,
+        }
+    }
+}
+
+/// Split `az://account/<container pattern>/[rest]` into its account, the
+/// pattern and the rest, when the container segment has wildcards
+///
+/// Examples:
+/// - "az://acct/logs-*/2024/" -> Some(("acct", "logs-*", "2024/"))
+/// - "az://acct/backup-2024-??" -> Some(("acct", "backup-2024-??", ""))
+/// - "az://acct/logs/*.txt" -> None
+pub fn split_container_pattern(uri: &str) -> Option<(&str, &str, &str)> {
+    let (account, rest) = uri.strip_prefix("az://")?.split_once('/')?;
+    let (pattern, rest) = rest.split_once('/').unwrap_or((rest, ""));
+    (is_storage_account_name(account) && contains_wildcard(pattern))
+        .then_some((account, pattern, rest))
+}
+
 /// Match a path against a glob pattern
 /// Returns true if the path matches the pattern
 pub fn matches_pattern(path: &str, pattern: &str) -> bool {
@@ -183,10 +510,173 @@ pub fn matches_pattern(path: &str, pattern: &str) -> bool {
     }
 }
 
+/// Match a file name against `;`-separated wildcards, as taken by
+/// --include-pattern and --exclude-pattern
+pub fn matches_any_pattern(name: &str, patterns: &str) -> bool {
+    patterns
+        .split(';')
+        .any(|pattern| matches_pattern(name, pattern))
+}
+
+/// Compute the MD5 of a local file as a lowercase hex string
+pub fn file_md5(path: &Path) -> Result<String> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        context.consume(&buffer[..read]);
+    }
+    Ok(format!("{:x}", context.finalize()))
+}
+
+/// Write a file only the current user can read, for tokens and other secrets
+pub fn write_private_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // mode() only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(path)?;
+    std::io::Write::write_all(&mut file, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Parse a human-friendly duration such as "90s", "15m", "1h" or "7d"
+/// A bare number is interpreted as seconds
+pub fn parse_duration(value: &str) -> Result<std::time::Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => value.split_at(pos),
+        None => (value, "s"),
+    };
+
+    let amount: u64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid duration '{}'. Expected e.g. 30m, 1h or 7d", value))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 24 * 3600,
+        _ => {
+            return Err(anyhow!(
+                "Invalid duration unit '{}' in '{}'. Use s, m, h or d",
+                unit,
+                value
+            ))
+        }
+    };
+
+    // Kept within i64, as durations are compared with Unix timestamps
+    let seconds = amount
+        .checked_mul(multiplier)
+        .filter(|seconds| i64::try_from(*seconds).is_ok())
+        .ok_or_else(|| anyhow!("Duration '{}' is too long", value))?;
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+/// Parse a human-friendly size such as "500M", "10GiB" or "1.5T" into bytes
+/// Units are binary (1K = 1024 bytes); a bare number is interpreted as bytes
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(pos) => value.split_at(pos),
+        None => (value, ""),
+    };
+
+    let amount: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid size '{}'. Expected e.g. 500M, 10G or 1T", value))?;
+    let exponent = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        _ => {
+            return Err(anyhow!(
+                "Invalid size unit '{}' in '{}'. Use B, K, M, G or T",
+                unit,
+                value
+            ))
+        }
+    };
+
+    Ok((amount * 1024f64.powi(exponent)) as u64)
+}
+
+/// Check a `;`-separated list of regular expressions, as taken by
+/// --include-regex and --exclude-regex
+pub fn parse_regex_list(value: &str) -> Result<String> {
+    for pattern in value.split(';') {
+        regex::Regex::new(pattern)
+            .map_err(|e| anyhow!("Invalid regular expression '{}': {}", pattern, e))?;
+    }
+    Ok(value.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_file_md5() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.txt");
+        std::fs::write(&path, "hello world").unwrap();
+        assert_eq!(file_md5(&path).unwrap(), "5eb63bbbe01eeed093cb22bb8f5acdc3");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        use std::time::Duration;
+
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn test_parse_regex_list() {
+        assert_eq!(parse_regex_list(r"\.log$;^tmp/").unwrap(), r"\.log$;^tmp/");
+        assert!(parse_regex_list("(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1000").unwrap(), 1000);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("4k").unwrap(), 4096);
+        assert_eq!(parse_size("500M").unwrap(), 500 * 1024 * 1024);
+        assert_eq!(parse_size("10GiB").unwrap(), 10 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5 TB").unwrap(), 3 * 512 * 1024 * 1024 * 1024);
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("").is_err());
+    }
+
     #[test]
     fn test_parse_azure_uri_new_format() {
         // New format with storage account
@@ -239,12 +729,84 @@ mod tests {
         assert_eq!(path, None);
     }
 
+    #[test]
+    fn test_expand_account_shorthand() {
+        assert_eq!(
+            expand_account_shorthand("az:data/2024/x.csv", "myaccount").as_deref(),
+            Some("az://myaccount/data/2024/x.csv")
+        );
+        assert_eq!(
+            expand_account_shorthand("az:", "myaccount").as_deref(),
+            Some("az://myaccount/")
+        );
+        assert_eq!(
+            expand_account_shorthand("az://acct/data/", "myaccount"),
+            None
+        );
+        assert_eq!(expand_account_shorthand("data/", "myaccount"), None);
+    }
+
     #[test]
     fn test_parse_azure_uri_invalid() {
         assert!(parse_azure_uri("invalid://uri").is_err());
         assert!(parse_azure_uri("az://").is_err());
     }
 
+    #[test]
+    fn test_parse_azure_uri_suggestions() {
+        let err = parse_azure_uri("gs://myaccount/data/file.txt")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("must start with 'az://'"));
+        assert!(err.contains("Did you mean: az://myaccount/data/file.txt"));
+
+        let err = parse_azure_uri("https://myaccount.blob.core.windows.net/data/x.csv")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Did you mean: az://myaccount/data/x.csv"));
+
+        let err = parse_azure_uri("az:/myaccount/data")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Did you mean: az://myaccount/data"));
+
+        let err = parse_azure_uri("az:///myaccount/data")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("name is required"));
+        assert!(err.contains("Did you mean: az://myaccount/data"));
+    }
+
+    #[test]
+    fn test_parse_azure_uri_invalid_container() {
+        let err = parse_azure_uri("az://myaccount/My_Data/file.txt")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("container names must be"));
+        assert!(err.contains("\n                 ^^^^^^^ "));
+        assert!(err.contains("Did you mean: az://myaccount/my-data/file.txt"));
+
+        let err = parse_azure_uri("az://myaccount//file.txt")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("container name is missing"));
+
+        // Special containers are allowed
+        assert!(parse_azure_uri("az://myaccount/$web/index.html").is_ok());
+    }
+
+    #[test]
+    fn test_is_container_name() {
+        assert!(is_container_name("data"));
+        assert!(is_container_name("my-data-2024"));
+        assert!(is_container_name("$web"));
+        assert!(!is_container_name("ab"));
+        assert!(!is_container_name("My-Data"));
+        assert!(!is_container_name("-data"));
+        assert!(!is_container_name("my--data"));
+        assert!(!is_container_name("my_data"));
+    }
+
     #[test]
     fn test_is_storage_account_name() {
         assert!(is_storage_account_name("myaccount"));
@@ -256,6 +818,23 @@ mod tests {
         assert!(!is_storage_account_name("a".repeat(25).as_str())); // too long
     }
 
+    #[test]
+    fn test_uri_base() {
+        assert_eq!(
+            uri_base("az://acct/c/logs/2024/", Some("logs/2024/")),
+            "az://acct/c"
+        );
+        assert_eq!(uri_base("az://acct/c/", None), "az://acct/c");
+    }
+
+    #[test]
+    fn test_directory_prefix() {
+        assert_eq!(directory_prefix(Some("logs/2024")), "logs/2024/");
+        assert_eq!(directory_prefix(Some("logs//")), "logs/");
+        assert_eq!(directory_prefix(Some("/")), "");
+        assert_eq!(directory_prefix(None), "");
+    }
+
     #[test]
     fn test_is_azure_uri() {
         assert!(is_azure_uri("az://container/path"));
@@ -274,6 +853,14 @@ mod tests {
         assert_eq!(format_size(0), "0 B");
     }
 
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1532), "1,532");
+        assert_eq!(format_count(19756849152), "19,756,849,152");
+    }
+
     #[test]
     fn test_get_filename() {
         // Local paths
@@ -292,6 +879,25 @@ mod tests {
         assert_eq!(get_filename("az://account/container"), "");
     }
 
+    #[test]
+    fn test_windows_path_to_slashes() {
+        assert_eq!(windows_path_to_slashes(r"C:\data\"), "C:/data/");
+        assert_eq!(windows_path_to_slashes(r"D:\a b\c.txt"), "D:/a b/c.txt");
+        assert_eq!(
+            windows_path_to_slashes(r"\\fileserver\share\in"),
+            "//fileserver/share/in"
+        );
+        assert_eq!(
+            windows_path_to_slashes(r"\\?\C:\very\long\path"),
+            "C:/very/long/path"
+        );
+        assert_eq!(
+            windows_path_to_slashes(r"\\?\UNC\fileserver\share\in"),
+            "//fileserver/share/in"
+        );
+        assert_eq!(windows_path_to_slashes("relative/dir/"), "relative/dir/");
+    }
+
     #[test]
     fn test_get_parent_dir() {
         assert_eq!(
@@ -365,6 +971,24 @@ mod tests {
         assert!(!contains_recursive_wildcard("foo/bar"));
     }
 
+    #[test]
+    fn test_split_container_pattern() {
+        assert_eq!(
+            split_container_pattern("az://acct/logs-*/2024/"),
+            Some(("acct", "logs-*", "2024/"))
+        );
+        assert_eq!(
+            split_container_pattern("az://acct/backup-2024-??"),
+            Some(("acct", "backup-2024-??", ""))
+        );
+        assert_eq!(split_container_pattern("az://acct/logs/*.txt"), None);
+        assert_eq!(split_container_pattern("az://acct/"), None);
+        assert!(parse_azure_uri("az://acct/logs-*/x")
+            .unwrap_err()
+            .to_string()
+            .contains("only work with ls and du"));
+    }
+
     #[test]
     fn test_split_wildcard_path() {
         // Single level wildcard
@@ -395,6 +1019,53 @@ mod tests {
         assert_eq!(split_wildcard_path("foo/bar.txt"), None);
     }
 
+    #[test]
+    fn test_glob_segments() {
+        let glob = Glob::new("logs/*.csv").unwrap().unwrap();
+        assert_eq!(glob.prefix(), "logs/");
+        assert!(!glob.spans_directories());
+        assert_eq!(glob.matches("logs/a.csv"), Some(GlobMatch::Name));
+        assert_eq!(glob.matches("logs/2024/a.csv"), None);
+        assert_eq!(glob.matches("other/a.csv"), None);
+
+        let glob = Glob::new("logs/2024-*/").unwrap().unwrap();
+        assert_eq!(glob.matches("logs/2024-01/"), Some(GlobMatch::Name));
+        assert_eq!(glob.matches("logs/2024-01"), None);
+        assert_eq!(
+            glob.matches("logs/2024-01/a/b.csv"),
+            Some(GlobMatch::Under("logs/2024-01/"))
+        );
+        assert!(glob.selects("logs/2024-01/a/b.csv", true));
+        assert!(!glob.selects("logs/2024-01/a/b.csv", false));
+
+        let glob = Glob::new("*/data?.csv").unwrap().unwrap();
+        assert!(glob.spans_directories());
+        assert_eq!(glob.matches("a/data1.csv"), Some(GlobMatch::Name));
+        assert_eq!(glob.matches("a/b/data1.csv"), None);
+        assert!(Glob::new("logs/a.csv").unwrap().is_none());
+        assert!(Glob::new("logs/a**b").is_err());
+    }
+
+    #[test]
+    fn test_glob_recursive_wildcard() {
+        let glob = Glob::new("data/**/*.csv").unwrap().unwrap();
+        assert_eq!(glob.prefix(), "data/");
+        assert!(glob.spans_directories());
+        assert_eq!(glob.matches("data/a.csv"), Some(GlobMatch::Name));
+        assert_eq!(glob.matches("data/x/y/z.csv"), Some(GlobMatch::Name));
+        assert_eq!(glob.matches("data/x/y/z.json"), None);
+
+        let glob = Glob::new("**/2024/").unwrap().unwrap();
+        assert_eq!(glob.prefix(), "");
+        assert_eq!(
+            glob.matches("a/b/2024/c.csv"),
+            Some(GlobMatch::Under("a/b/2024/"))
+        );
+
+        let glob = Glob::new("logs/**").unwrap().unwrap();
+        assert_eq!(glob.matches("logs/a/b/c"), Some(GlobMatch::Name));
+    }
+
     #[test]
     fn test_matches_pattern() {
         // Simple wildcard
@@ -46,82 +46,2214 @@ mod cli_parsing_tests {
             .failure()
             .stderr(predicate::str::contains("unrecognized subcommand"));
     }
+
+    #[test]
+    fn test_cli_quiet_suppresses_status_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        fs::write(&source_file, "test content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "-q",
+            source_file.to_str().unwrap(),
+            temp_dir.path().join("dest.txt").to_str().unwrap(),
+        ]);
+        cmd.assert().success().stdout(predicate::str::is_empty());
+    }
+
+    #[test]
+    fn test_cli_quiet_conflicts_with_verbose() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["-q", "-v", "ls"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_cli_invalid_retries() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "--retries", "many", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value"));
+    }
+
+    #[test]
+    fn test_cli_progress_rejects_unknown_mode() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["--progress", "per-blob", "ls", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("per-file"));
+    }
+
+    #[test]
+    fn test_cli_color_follows_flag_and_no_color() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let dest_file = temp_dir.path().join("dest.txt");
+        fs::write(&source_file, "test content").unwrap();
+
+        let copy = |color: &str, no_color: Option<&str>| {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.args(["--color", color, "cp"])
+                .arg(&source_file)
+                .arg(&dest_file)
+                .env_remove("NO_COLOR")
+                .env("CLICOLOR_FORCE", "1");
+            if let Some(value) = no_color {
+                cmd.env("NO_COLOR", value);
+            }
+            let output = cmd.output().unwrap();
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap().contains('\x1b')
+        };
+        assert!(copy("always", Some("1")));
+        assert!(copy("auto", None));
+        assert!(!copy("auto", Some("1")));
+        assert!(!copy("never", None));
+    }
+
+    #[test]
+    fn test_cli_strict_succeeds_without_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        fs::write(&source_file, "test content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "--strict",
+            "cp",
+            source_file.to_str().unwrap(),
+            temp_dir.path().join("dest.txt").to_str().unwrap(),
+        ]);
+        cmd.assert().code(0);
+    }
+
+    #[test]
+    fn test_cli_invalid_uri_exits_with_usage_code() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cat", "az://"]);
+        cmd.assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid Azure URI"));
+    }
+
+    #[test]
+    fn test_cli_timeout_exits_with_its_own_code() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["--timeout", "1s", "serve", "--port", "0", "az://acct/site/"]);
+        cmd.assert()
+            .code(124)
+            .stderr(predicate::str::contains("Timed out after 1s"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["--timeout", "soon", "parse", "az://acct/data/"]);
+        cmd.assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid duration"));
+    }
+
+    #[test]
+    fn test_cli_invalid_proxy() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["--proxy", "proxy.corp:3128", "parse", "az://acct/data/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid --proxy"));
+    }
+
+    #[test]
+    fn test_cli_ca_cert_must_hold_certificates() {
+        let temp_dir = TempDir::new().unwrap();
+        let bundle = temp_dir.path().join("ca.pem");
+        fs::write(&bundle, "not a certificate").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "--ca-cert",
+            bundle.to_str().unwrap(),
+            "parse",
+            "az://acct/data/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("No PEM certificates"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "--insecure",
+            "--ca-cert",
+            "ca.pem",
+            "parse",
+            "az://acct/data/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+}
+
+#[cfg(test)]
+mod account_tests {
+    use super::*;
+
+    #[test]
+    fn test_account_set_and_show() {
+        let config = TempDir::new().unwrap();
+        let config_file = config.path().join("azst/config.toml");
+        fs::create_dir_all(config_file.parent().unwrap()).unwrap();
+        fs::write(&config_file, "# mine\n[retry]\nmax_retries = 3\n").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path())
+            .args(["account", "show"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("No default account"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path())
+            .args(["account", "set", "az://myaccount/"]);
+        cmd.assert().success();
+        let contents = fs::read_to_string(&config_file).unwrap();
+        assert!(contents.starts_with("default_account = \"myaccount\"\n# mine\n"));
+        assert!(contents.contains("max_retries = 3"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path())
+            .args(["account", "show"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Default account: myaccount"));
+    }
+
+    #[test]
+    fn test_account_set_rejects_invalid_name() {
+        let config = TempDir::new().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path())
+            .args(["account", "set", "My_Account"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("isn't a storage account name"));
+        assert!(!config.path().join("azst/config.toml").exists());
+    }
+
+    #[test]
+    fn test_shorthand_uses_default_account() {
+        let config = TempDir::new().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path())
+            .args(["parse", "az:data/2024/x.csv"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("azst account set"));
+
+        fs::create_dir_all(config.path().join("azst")).unwrap();
+        fs::write(
+            config.path().join("azst/config.toml"),
+            "default_account = \"myaccount\"\n",
+        )
+        .unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path())
+            .args(["parse", "az:data/2024/x.csv"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("myaccount"))
+            .stdout(predicate::str::contains("data"))
+            .stdout(predicate::str::contains("2024/x.csv"));
+    }
+}
+
+#[cfg(test)]
+mod alias_tests {
+    use super::*;
+
+    fn azst(config: &TempDir) -> Command {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path());
+        cmd
+    }
+
+    #[test]
+    fn test_alias_expands_in_commands() {
+        let config = TempDir::new().unwrap();
+        azst(&config)
+            .args([
+                "alias",
+                "add",
+                "curated",
+                "az://prodaccount/datasets/curated/",
+            ])
+            .assert()
+            .success();
+
+        azst(&config)
+            .args(["parse", "curated://2024/x.csv"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("prodaccount"))
+            .stdout(predicate::str::contains("curated/2024/x.csv"));
+
+        azst(&config)
+            .args(["alias", "list"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("curated://"))
+            .stdout(predicate::str::contains(
+                "az://prodaccount/datasets/curated/",
+            ));
+
+        azst(&config)
+            .args(["alias", "remove", "curated"])
+            .assert()
+            .success();
+        azst(&config)
+            .args(["parse", "curated://2024/x.csv"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("azst alias add"));
+        azst(&config)
+            .args(["alias", "remove", "curated"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("No alias 'curated'"));
+    }
+
+    #[test]
+    fn test_alias_add_validates() {
+        let config = TempDir::new().unwrap();
+        azst(&config)
+            .args(["alias", "add", "az", "az://acct/data/"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("can't be an alias"));
+        azst(&config)
+            .args(["alias", "add", "data", "/local/dir"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("az://"));
+        assert!(!config.path().join("azst/config.toml").exists());
+    }
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["audit", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("--sample"))
+            .stdout(predicate::str::contains("--cap-mbps"));
+    }
+
+    #[test]
+    fn test_audit_rejects_invalid_sample() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["audit", "--sample", "150", "az://a1/c1/", "az://a2/c2/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("between 0 and 100"));
+    }
+
+    #[test]
+    fn test_security_audit_rejects_sampling_options() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["audit", "--cap-mbps", "10", "az://myaccount/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("<DESTINATION>"));
+    }
+
+    #[test]
+    fn test_security_audit_requires_whole_account() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["audit", "az://myaccount/data/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("covers a whole account"));
+    }
+}
+
+#[cfg(test)]
+mod auth_tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_auth_logout_removes_cached_tokens() {
+        let config_dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let tokens = cache_dir.path().join("azst").join("tokens.json");
+        fs::create_dir_all(tokens.parent().unwrap()).unwrap();
+        fs::write(&tokens, "{}").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config_dir.path())
+            .env("XDG_CACHE_HOME", cache_dir.path())
+            .args(["auth", "logout"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Removed cached Azure tokens"));
+        assert!(!tokens.exists());
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config_dir.path())
+            .env("XDG_CACHE_HOME", cache_dir.path())
+            .args(["auth", "logout"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("No cached Azure tokens"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_auth_logout_removes_login() {
+        let config_dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let login = config_dir.path().join("azst").join("login.json");
+        fs::create_dir_all(login.parent().unwrap()).unwrap();
+        fs::write(
+            &login,
+            r#"{"tenant":"organizations","flow":"device-code","refresh_token":"secret"}"#,
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config_dir.path())
+            .env("XDG_CACHE_HOME", cache_dir.path())
+            .args(["auth", "logout"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Signed out"))
+            .stdout(predicate::str::contains("No cached Azure tokens").not());
+        assert!(!login.exists());
+    }
+
+    #[test]
+    fn test_auth_token_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["auth", "token", "--help"]);
+        cmd.assert().success().stdout(predicate::str::contains(
+            "https://storage.azure.com/.default",
+        ));
+    }
 }
 
 #[cfg(test)]
+mod azcopy_command_tests {
+    use super::*;
+
+    #[test]
+    fn test_azcopy_help_lists_actions() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["azcopy", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("install"))
+            .stdout(predicate::str::contains("update"))
+            .stdout(predicate::str::contains("which"));
+    }
+
+    #[test]
+    fn test_azcopy_requires_action() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("azcopy");
+        cmd.assert().failure();
+    }
+}
+
 mod cat_command_tests {
     use super::*;
 
     #[test]
     fn test_cat_help() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cat", "--help"]);
+        cmd.args(["cat", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Concatenate object content"));
+    }
+
+    #[test]
+    fn test_cat_missing_args() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("cat");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("No URLs provided"));
+    }
+
+    #[test]
+    fn test_cat_invalid_url() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cat", "invalid-url"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid URL"));
+    }
+
+    #[test]
+    fn test_cat_header_flag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cat", "--header", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("header"));
+    }
+
+    #[test]
+    fn test_cat_range_flag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cat", "--range", "0-100", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("range"));
+    }
+
+    #[test]
+    fn test_cat_parallel_ranges_must_be_positive() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cat", "--parallel-ranges", "0", "az://acct/container/blob"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--parallel-ranges"));
+    }
+
+    #[test]
+    fn test_cat_decompress_conflicts_with_raw_and_range() {
+        for conflicting in [&["--raw"][..], &["--range", "0-100"]] {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.args(["cat", "--decompress", "az://acct/container/logs.gz"])
+                .args(conflicting);
+            cmd.assert()
+                .failure()
+                .stderr(predicate::str::contains("cannot be used with"));
+        }
+    }
+
+    #[test]
+    fn test_cat_rejects_invalid_url_before_downloading() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cat", "az://acct/container/blob", "invalid-url"]);
+        cmd.assert()
+            .failure()
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::contains("Invalid URL 'invalid-url'"));
+    }
+
+    #[test]
+    fn test_cat_rejects_short_customer_key_from_env() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        // 16 bytes: an AES-128 key
+        cmd.env("AZST_CUSTOMER_KEY", "MDEyMzQ1Njc4OWFiY2RlZg==")
+            .args(["cat", "az://acct/container/blob"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("expected 32 bytes"));
+    }
+}
+
+#[cfg(test)]
+mod complete_tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_is_hidden_from_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("--help");
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("__complete").not());
+    }
+
+    #[test]
+    fn test_complete_ignores_local_paths() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["__complete", "/local/pa"]);
+        cmd.assert().success().stdout(predicate::str::is_empty());
+    }
+}
+
+mod compose_tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_requires_source_and_destination() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["compose", "az://myaccount/c1/out.csv"]);
+        cmd.assert().failure();
+    }
+
+    #[test]
+    fn test_compose_destination_must_be_blob() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "compose",
+            "az://myaccount/data/part-1.csv",
+            "az://myaccount/data/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("must name a blob"));
+    }
+}
+
+#[cfg(test)]
+mod cp_command_tests {
+    use super::*;
+
+    #[test]
+    fn test_cp_missing_args() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("cp");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("required arguments"));
+    }
+
+    #[test]
+    fn test_cp_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Copy files"));
+    }
+
+    #[test]
+    fn test_cp_local_to_local() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let dest_file = temp_dir.path().join("dest.txt");
+
+        // Create source file
+        fs::write(&source_file, "test content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            source_file.to_str().unwrap(),
+            dest_file.to_str().unwrap(),
+        ]);
+
+        cmd.assert().success();
+
+        // Verify file was copied
+        assert!(dest_file.exists());
+        let content = fs::read_to_string(&dest_file).unwrap();
+        assert_eq!(content, "test content");
+    }
+
+    #[test]
+    fn test_cp_quiet_prints_one_summary_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let dest_file = temp_dir.path().join("dest.txt");
+        fs::write(&source_file, "test content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("-q").arg("cp").arg(&source_file).arg(&dest_file);
+        cmd.assert().success().stdout("").stderr("");
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("-q")
+            .arg("cp")
+            .arg(temp_dir.path().join("missing.txt"))
+            .arg(&dest_file);
+        cmd.assert()
+            .failure()
+            .stdout("")
+            .stderr(predicate::str::starts_with(
+                "azst cp: files=0 bytes=0 failed=0 exit=",
+            ))
+            .stderr(predicate::str::contains(" error=\""))
+            .stderr(predicate::function(|err: &str| err.lines().count() == 1));
+    }
+
+    #[test]
+    fn test_cp_appends_to_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let dest_file = temp_dir.path().join("dest.txt");
+        let audit_log = temp_dir.path().join("audit.jsonl");
+        fs::write(&source_file, "test content").unwrap();
+
+        for dry_run in [true, false] {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.arg("cp").arg("--audit-log").arg(&audit_log);
+            if dry_run {
+                cmd.arg("--dry-run");
+            }
+            cmd.arg(&source_file).arg(&dest_file);
+            cmd.assert().success();
+        }
+
+        // Only the real copy is logged
+        let log = fs::read_to_string(&audit_log).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.contains("\"command\":\"cp\""));
+        assert!(log.contains("\"files\":1,\"bytes\":12"));
+        assert!(log.contains("\"exit_code\":0"));
+    }
+
+    #[test]
+    fn test_cp_no_clobber_keeps_existing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let dest_file = temp_dir.path().join("dest.txt");
+        fs::write(&source_file, "new content").unwrap();
+        fs::write(&dest_file, "old content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--no-clobber",
+            source_file.to_str().unwrap(),
+            dest_file.to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Skipping existing"));
+
+        assert_eq!(fs::read_to_string(&dest_file).unwrap(), "old content");
+    }
+
+    #[test]
+    fn test_cp_preserve_local_timestamps() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        let dest_file = temp_dir.path().join("dest.txt");
+        fs::write(&source_file, "content").unwrap();
+
+        let mtime =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_234_567_890);
+        fs::File::options()
+            .write(true)
+            .open(&source_file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--preserve",
+            source_file.to_str().unwrap(),
+            dest_file.to_str().unwrap(),
+        ]);
+        cmd.assert().success();
+
+        assert_eq!(fs::metadata(&dest_file).unwrap().modified().unwrap(), mtime);
+    }
+
+    #[test]
+    fn test_cp_no_clobber_conflicts_with_overwrite() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "-n", "--overwrite", "always", "a.txt", "b.txt"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_cp_overwrite_prompt_is_local_only() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--overwrite", "prompt", "a.txt", "az://acct/c/a.txt"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("only applies to local copies"));
+    }
+
+    #[test]
+    fn test_cp_flatten_requires_recursive() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--flatten", "az://acct/c/dir/", "/tmp/out/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--recursive"));
+    }
+
+    #[test]
+    fn test_cp_flatten_rejects_local_copies() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("src");
+        fs::create_dir_all(source_dir.join("nested")).unwrap();
+        fs::write(source_dir.join("nested/a.txt"), "a").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "-r",
+            "--flatten",
+            source_dir.to_str().unwrap(),
+            temp_dir.path().join("out").to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("uploads and downloads"));
+    }
+
+    #[test]
+    fn test_cp_as_subdir_takes_a_value() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--as-subdir", "maybe", "a.txt", "az://acct/c/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value"));
+    }
+
+    #[test]
+    fn test_cp_rejects_invalid_bandwidth_schedule() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--bandwidth-schedule",
+            "09:00-18:00",
+            "a.txt",
+            "az://acct/c/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("expected HH:MM-HH:MM=MBPS"));
+    }
+
+    #[test]
+    fn test_cp_invalid_metadata() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--metadata", "novalue", "a.txt", "az://acct/c/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("expected key=value"));
+    }
+
+    #[test]
+    fn test_cp_metadata_value_with_semicolon() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--metadata", "tags=a;b", "a.txt", "az://acct/c/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("';' can't be used"));
+    }
+
+    #[test]
+    fn test_cp_invalid_tier() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--tier", "frozen", "a.txt", "az://acct/c/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value"));
+    }
+
+    #[test]
+    fn test_cp_headers_require_azure_destination() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("index.html");
+        let dest_file = temp_dir.path().join("copy.html");
+        fs::write(&source_file, "<html></html>").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--content-type",
+            "text/html",
+            source_file.to_str().unwrap(),
+            dest_file.to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("destination is Azure"));
+        assert!(!dest_file.exists());
+    }
+
+    #[test]
+    fn test_cp_check_md5_invalid_policy() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--check-md5=sometimes", "a.txt", "az://acct/c/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value"));
+    }
+
+    #[test]
+    fn test_cp_help_mentions_preserve_empty_dirs() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("--preserve-empty-dirs"));
+    }
+
+    #[test]
+    fn test_cp_help_mentions_allow_cross_region() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("--allow-cross-region"));
+    }
+
+    #[test]
+    fn test_cp_help_mentions_failures_out() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("--failures-out"));
+    }
+
+    #[test]
+    fn test_cp_max_upload_bytes_invalid_size() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--max-upload-bytes", "lots", "a.txt", "az://acct/c/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid size"));
+    }
+
+    #[test]
+    fn test_cp_compress_requires_upload() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("source.txt");
+        fs::write(&source_file, "content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--compress",
+            "zstd",
+            source_file.to_str().unwrap(),
+            temp_dir.path().join("dest.txt").to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("only applies to uploads"));
+    }
+
+    #[test]
+    fn test_cp_compress_conflicts_with_content_encoding() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--compress",
+            "gzip",
+            "--content-encoding",
+            "br",
+            "a.txt",
+            "az://acct/c/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_cp_tar_requires_upload() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--tar",
+            temp_dir.path().to_str().unwrap(),
+            temp_dir.path().join("copy.tar").to_str().unwrap(),
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "--tar uploads a local directory to a blob",
+        ));
+    }
+
+    #[test]
+    fn test_cp_untar_conflicts_with_filters() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--untar",
+            "--include-pattern",
+            "*.csv",
+            "az://acct/c/data.tar.gz",
+            "data/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_cp_pack_requires_upload() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "-r",
+            "--pack",
+            "64K",
+            temp_dir.path().to_str().unwrap(),
+            temp_dir.path().join("copy").to_str().unwrap(),
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "--pack uploads a local directory to Azure",
+        ));
+    }
+
+    #[test]
+    fn test_cp_unpack_requires_recursive() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--unpack", "az://acct/c/tiles/", "tiles/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--recursive"));
+    }
+
+    #[test]
+    fn test_cp_unpack_conflicts_with_pack() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "-r",
+            "--unpack",
+            "--pack",
+            "64K",
+            "az://acct/c/tiles/",
+            "tiles/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_cp_manifest_rejects_another_copy() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest = temp_dir.path().join("job.json");
+        fs::write(
+            &manifest,
+            r#"{"source": "/other/", "destination": "az://acct/c/", "completed": {}}"#,
+        )
+        .unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "-r", "--manifest"])
+            .arg(&manifest)
+            .arg(temp_dir.path())
+            .arg("az://acct/c/");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("records a copy from /other/"));
+    }
+
+    #[test]
+    fn test_cp_url_requires_azure_destination() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "https://example.com/data.csv"])
+            .arg(temp_dir.path());
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "URLs can only be copied into Azure",
+        ));
+    }
+
+    #[test]
+    fn test_cp_url_list_rejects_non_urls() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "-", "az://myaccount/assets/web/"])
+            .write_stdin("https://example.com/a.png\n/local/b.png\n");
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "'/local/b.png' is not an http(s) URL",
+        ));
+    }
+
+    #[test]
+    fn test_cp_async_requires_azure_to_azure() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--async"])
+            .arg(temp_dir.path())
+            .arg("az://myaccount/restore/");
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "--async only applies to copies between Azure locations",
+        ));
+    }
+
+    #[test]
+    fn test_cp_async_conflicts_with_poll_and_manifest() {
+        for conflicting in [&["--poll"][..], &["-r", "--manifest", "job.json"]] {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.args(["cp", "--async"])
+                .args(conflicting)
+                .args(["az://coldaccount/archive/", "az://myaccount/restore/"]);
+            cmd.assert()
+                .failure()
+                .stderr(predicate::str::contains("cannot be used with"));
+        }
+    }
+
+    #[test]
+    fn test_cp_symlink_flags_apply_to_local_sources() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "-r", "--follow-symlinks", "az://myaccount/data/"])
+            .arg(temp_dir.path());
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "--follow-symlinks and --preserve-symlinks apply to local sources",
+        ));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "-r", "--follow-symlinks", "--preserve-symlinks"])
+            .arg(temp_dir.path())
+            .arg("az://myaccount/data/");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_cp_page_blobs_need_whole_pages() {
+        let temp_dir = TempDir::new().unwrap();
+        let disk = temp_dir.path().join("disk.vhd");
+        fs::write(&disk, vec![0u8; 1000]).unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--blob-type", "page"])
+            .arg(&disk)
+            .arg("az://myaccount/vhds/disk.vhd");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("padding would move its footer"));
+
+        let image = temp_dir.path().join("image.bin");
+        fs::write(&image, vec![1u8; 1000]).unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--dry-run", "--blob-type", "page"])
+            .arg(&image)
+            .arg("az://myaccount/images/");
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("az://myaccount/images/image.bin"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "--blob-type", "append"])
+            .arg(&image)
+            .arg(temp_dir.path().join("copy.bin"));
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "--blob-type only applies to uploads",
+        ));
+    }
+
+    #[test]
+    fn test_cp_status_needs_blob_or_recursive() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp-status", "az://myaccount/restore"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("must name a blob, or use -r"));
+    }
+
+    #[test]
+    fn test_cp_gzip_local_requires_upload() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("index.html");
+        fs::write(&source_file, "<p>content</p>").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "-z",
+            "html,css",
+            source_file.to_str().unwrap(),
+            temp_dir.path().join("copy.html").to_str().unwrap(),
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "--gzip-local only applies to uploads",
+        ));
+    }
+
+    #[test]
+    fn test_cp_preserve_rejects_staged_uploads() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("events.json");
+        fs::write(&source_file, "{}").unwrap();
+
+        for flags in [&["--compress", "gzip"][..], &["-z", "json"], &["-Z"]] {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.args(["cp", "--preserve"])
+                .args(flags)
+                .arg(&source_file)
+                .arg("az://myaccount/logs/");
+            cmd.assert().failure().stderr(predicate::str::contains(
+                "--preserve can't be combined with --tar, --pack, --compress or --gzip-local",
+            ));
+        }
+    }
+
+    #[test]
+    fn test_cp_no_decompress_conflicts_with_decompress() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--decompress",
+            "--no-decompress",
+            "az://acct/c/a.txt.gz",
+            "a.txt",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_cp_recursive_flag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "-r", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("recursive"));
+    }
+
+    #[test]
+    fn test_cp_nonexistent_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let dest_file = temp_dir.path().join("dest.txt");
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["cp", "/nonexistent/file.txt", dest_file.to_str().unwrap()]);
+
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("No such file or directory").or(
+                predicate::str::contains("The system cannot find the path specified"),
+            ));
+    }
+
+    #[test]
+    fn test_cp_customer_key_requires_azure() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("a.txt");
+        fs::write(&source, "secret").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "cp",
+            "--customer-key",
+            "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=",
+            source.to_str().unwrap(),
+            temp_dir.path().join("b.txt").to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--customer-key only applies"));
+    }
+}
+
+#[cfg(test)]
+mod hash_tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_local_file_hex() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("hello.txt");
+        fs::write(&file, "hello world").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["hash", "-m", "--hex", file.to_str().unwrap()]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("5eb63bbbe01eeed093cb22bb8f5acdc3"))
+            .stdout(predicate::str::contains("crc64").not());
+    }
+
+    #[test]
+    fn test_hash_local_file_base64_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("hello.txt");
+        fs::write(&file, "hello world").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["hash", file.to_str().unwrap()]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("XrY7u+Ae7tCTyyK7j1rNww=="))
+            .stdout(predicate::str::contains("Hash (crc64)"));
+    }
+
+    #[test]
+    fn test_hash_missing_file() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["hash", "/nonexistent/file.txt"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Failed to open"));
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+
+    /// A cache directory holding a listing of az://acct/data/
+    fn cache_with_listing() -> TempDir {
+        let cache = TempDir::new().unwrap();
+        let dir = cache.path().join("azst").join("listings").join("acct");
+        fs::create_dir_all(&dir).unwrap();
+        let blob = |name: &str, size: u64| {
+            format!(
+                r#"{{"name":"{}","properties":{{"contentLength":{},"lastModified":"Mon, 01 Jan 2024 00:00:00 GMT","accessTier":"Hot"}}}}"#,
+                name, size
+            )
+        };
+        let created = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        fs::write(
+            dir.join("data.json"),
+            format!(
+                r#"{{"account":"acct","container":"data","prefix":"","created":{},"blobs":[{},{},{}]}}"#,
+                created,
+                blob("a.txt", 1),
+                blob("logs/x.log", 10),
+                blob("logs/y.log", 100)
+            ),
+        )
+        .unwrap();
+        cache
+    }
+
+    #[test]
+    fn test_ls_cached_lists_without_network() {
+        let cache = cache_with_listing();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache.path())
+            .args(["ls", "--cached", "az://acct/data/"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("az://acct/data/a.txt"))
+            .stdout(predicate::str::contains("az://acct/data/logs/"))
+            .stdout(predicate::str::contains("x.log").not());
+    }
+
+    #[test]
+    fn test_du_cached_adds_up_listing() {
+        let cache = cache_with_listing();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache.path()).args([
+            "du",
+            "-s",
+            "--cached",
+            "az://acct/data/logs/",
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("110"));
+    }
+
+    #[test]
+    fn test_container_patterns_fan_out() {
+        let cache = cache_with_listing();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache.path())
+            .args(["du", "-s", "--cached", "az://acct/da*/"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("111"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache.path())
+            .args(["ls", "--cached", "az://acct/d?ta/logs/"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("az://acct/data/logs/x.log"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache.path())
+            .args(["ls", "--cached", "az://acct/backup-*/"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "No containers in az://acct/ match 'backup-*'",
+        ));
+    }
+
+    #[test]
+    fn test_ls_cached_without_listing_suggests_refresh() {
+        let cache = TempDir::new().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache.path())
+            .args(["ls", "--cached", "az://acct/data/"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "azst cache refresh az://acct/data/",
+        ));
+    }
+
+    #[test]
+    fn test_cache_clear() {
+        let cache = cache_with_listing();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache.path())
+            .args(["cache", "clear", "az://acct/"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Removed 1 cached listing(s)"));
+    }
+}
+
+#[cfg(test)]
+mod inventory_tests {
+    use super::*;
+
+    #[test]
+    fn test_inventory_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["inventory", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(".resume"));
+    }
+
+    #[test]
+    fn test_inventory_rejects_unknown_extension() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "inventory",
+            "az://acct/container/",
+            "--out",
+            "inventory.txt",
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "name it .csv, .tsv, .jsonl or .parquet",
+        ));
+    }
+
+    #[test]
+    fn test_inventory_requires_out() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["inventory", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--out"));
+    }
+}
+
+#[cfg(test)]
+mod lease_tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_rejects_short_duration() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "lease",
+            "acquire",
+            "--duration",
+            "5",
+            "az://myaccount/backups/latest.tar",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("15 to 60 seconds"));
+    }
+
+    #[test]
+    fn test_lease_rejects_invalid_lease_id() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "lease",
+            "release",
+            "--lease-id",
+            "not-a-guid",
+            "az://myaccount/backups/latest.tar",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("expected a GUID"));
+    }
+
+    #[test]
+    fn test_rm_lease_id_requires_blob() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("keep.txt");
+        fs::write(&file, "content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "rm",
+            "-f",
+            "--lease-id",
+            "0f8fad5b-d9cb-469f-a165-70867728950e",
+            file.to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("only applies to blobs"));
+        assert!(file.exists());
+    }
+}
+
+#[cfg(test)]
+mod lifecycle_tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle_set_dry_run_reads_yaml() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = temp_dir.path().join("policy.yaml");
+        fs::write(
+            &policy,
+            "rules:
+  - name: age-out-logs
+    enabled: true
+    type: Lifecycle
+    definition:
+      actions:
+        baseBlob:
+          tierToCool:
+            daysAfterModificationGreaterThan: 30
+      filters:
+        blobTypes: [blockBlob]
+        prefixMatch: [logs/]
+",
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "lifecycle",
+            "set",
+            "--dry-run",
+            "az://myaccount/",
+            policy.to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Would set 1 rule(s) on myaccount"));
+    }
+
+    #[test]
+    fn test_lifecycle_requires_account_uri() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["lifecycle", "get", "az://myaccount/logs/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("whole account"));
+    }
+}
+
+#[cfg(test)]
+mod ls_command_tests {
+    use super::*;
+
+    #[test]
+    fn test_ls_account_filters_need_no_path() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "--resource-group", "analytics", "az://acct/data/"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "only apply when listing storage accounts",
+        ));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "--all-subscriptions", "--subscription", "0000"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_ls_rejects_recursive_wildcard_inside_a_segment() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "az://acct/data/logs/a**b"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "recursive wildcards must form a single path component",
+        ));
+    }
+
+    #[test]
+    fn test_ls_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("List objects"));
+    }
+
+    #[test]
+    fn test_ls_local_directory() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Create some test files
+        fs::write(temp_dir.path().join("file1.txt"), "content1").unwrap();
+        fs::write(temp_dir.path().join("file2.txt"), "content2").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", temp_dir.path().to_str().unwrap()]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("file1.txt"))
+            .stdout(predicate::str::contains("file2.txt"));
+    }
+
+    #[test]
+    fn test_ls_directory_itself() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("inside.txt"), "content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "-d", temp_dir.path().to_str().unwrap()]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "{}/",
+                temp_dir.path().display()
+            )))
+            .stdout(predicate::str::contains("inside.txt").not());
+    }
+
+    #[test]
+    fn test_ls_directory_conflicts_with_recursive() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "-d", "-r", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_ls_summary_conflicts_with_since_state() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--summary",
+            "--since-state",
+            "state.json",
+            "az://acct/container/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_ls_flat_requires_recursive() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "--flat", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--recursive"));
+    }
+
+    #[test]
+    fn test_ls_local_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        fs::write(&test_file, "content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", test_file.to_str().unwrap()]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("test.txt"));
+    }
+
+    #[test]
+    fn test_ls_long_format() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "-l", temp_dir.path().to_str().unwrap()]);
+
+        cmd.assert()
+            .success()
+            // When not outputting to TTY, headers are not shown
+            // Check for the actual file listing instead
+            .stdout(predicate::str::contains("test.txt"));
+    }
+
+    #[test]
+    fn test_ls_human_readable() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("test.txt"), "x".repeat(2048)).unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "-lH", temp_dir.path().to_str().unwrap()]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("KB").or(predicate::str::contains("B")));
+    }
+
+    #[test]
+    fn test_ls_nonexistent_path() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "/nonexistent/path"]);
+
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("does not exist"));
+    }
+
+    // Note: The following tests require Azure CLI to be installed and authenticated
+    // They are documented tests that verify the expected behavior
+
+    #[test]
+    #[ignore] // Requires Azure CLI authentication
+    fn test_ls_storage_accounts() {
+        // Test: azst ls
+        // Expected: List all storage accounts in the current subscription
+        // Output format: az://accountname/
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("ls");
+
+        // This test will fail if not authenticated, which is expected
+        // When authenticated, it should list storage accounts with az:// prefix
+        let result = cmd.assert();
+
+        // Check that either we get storage accounts or an authentication error
+        result.stdout(
+            predicate::str::contains("Azure Storage Accounts")
+                .or(predicate::str::contains("az://")),
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires Azure CLI authentication
+    fn test_ls_storage_accounts_long_format() {
+        // Test: azst ls -l
+        // Expected: List storage accounts with location and resource group
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "-l"]);
+
+        let result = cmd.assert();
+
+        // Should show accounts with additional columns (location, resource group)
+        result.stdout(
+            predicate::str::contains("Azure Storage Accounts")
+                .or(predicate::str::contains("az://")),
+        );
+    }
+
+    #[test]
+    #[ignore] // Requires Azure CLI authentication
+    fn test_ls_containers_for_account() {
+        // Test: azst ls az://accountname/
+        // Expected: List all containers in the specified storage account
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "az://testaccount/"]);
+
+        // This will fail with an error if the account doesn't exist or user isn't authenticated
+        // When successful, it should list containers
+        let result = cmd.assert();
+
+        // Either shows containers or gives an error about the account
+        result.stdout(
+            predicate::str::contains("Azure Storage Containers")
+                .or(predicate::str::contains("az://")),
+        );
+    }
+
+    #[test]
+    fn test_ls_azure_uri_format_validation() {
+        // Test that Azure URI format is recognized correctly
+        // This test doesn't require Azure CLI as it tests parsing only
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "az://invalid-account-that-does-not-exist/"]);
+
+        // Should fail with Azure-related error (not path error)
+        // Case-insensitive check for "storage" (can be "Storage" or "storage")
+        cmd.assert().failure().stderr(
+            predicate::str::contains("Azure")
+                .or(predicate::str::contains("storage"))
+                .or(predicate::str::contains("Storage")),
+        );
+    }
+
+    #[test]
+    fn test_ls_signed_urls_invalid_duration() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "--signed-urls", "1w", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid duration unit"));
+    }
+
+    #[test]
+    fn test_ls_signed_urls_duration_too_long() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--signed-urls",
+            "999999999999999999d",
+            "az://acct/container/",
+        ]);
+        cmd.assert()
+            .code(2)
+            .stderr(predicate::str::contains("is too long"));
+    }
+
+    #[test]
+    fn test_ls_signed_urls_requires_azure_path() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--signed-urls",
+            "1h",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("az:// paths"));
+    }
+
+    #[test]
+    fn test_ls_since_state_requires_azure_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = temp_dir.path().join("state.json");
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--since-state",
+            state.to_str().unwrap(),
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--since-state can only be used"));
+        assert!(!state.exists());
+    }
+
+    #[test]
+    fn test_ls_rejects_unknown_column() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "--columns", "size,owner", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value 'owner'"));
+    }
+
+    #[test]
+    fn test_ls_etag_conflicts_with_columns() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "-e", "--columns", "size,name", "az://acct/container/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_ls_deterministic_sorts_recursive_listing() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("b")).unwrap();
+        for name in ["c.txt", "a.txt", "b/z.txt", "b/m.txt"] {
+            fs::write(temp_dir.path().join(name), "content").unwrap();
+        }
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--deterministic",
+            "-r",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .success()
+            .stdout("a.txt\nb/\nb/m.txt\nb/z.txt\nc.txt\n");
+    }
+
+    #[test]
+    fn test_ls_null_terminated() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["a b.txt", "line\nbreak.txt"] {
+            fs::write(temp_dir.path().join(name), "content").unwrap();
+        }
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--deterministic",
+            "-0",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        cmd.assert().success().stdout("a b.txt\0line\nbreak.txt\0");
+    }
+
+    #[test]
+    fn test_ls_null_conflicts_with_long() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ls", "-0", "-l", "."]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    fn test_ls_csv_quotes_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a,b.txt"), "content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["--format", "csv", "ls", temp_dir.path().to_str().unwrap()]);
+        cmd.assert().success().stdout("name\n\"a,b.txt\"\n");
+    }
+
+    #[test]
+    fn test_ls_tsv_long_listing() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("file.txt"), "content").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--format",
+            "tsv",
+            "-l",
+            temp_dir.path().to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::starts_with("size\ttype\tname\n"))
+            .stdout(predicate::str::contains("\tfile.txt\n"));
+    }
+
+    #[test]
+    fn test_ls_signed_urls_rejected_with_csv() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "ls",
+            "--format",
+            "csv",
+            "--signed-urls",
+            "1h",
+            "az://account/container/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--signed-urls"));
+    }
+}
+
+#[cfg(test)]
+mod parse_command_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_blob_uri() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["parse", "az://myaccount/mycontainer/data/file.csv"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("myaccount"))
+            .stdout(predicate::str::contains("data/file.csv"))
+            .stdout(predicate::str::contains(
+                "https://myaccount.blob.core.windows.net/mycontainer/data/file.csv",
+            ));
+    }
+
+    #[test]
+    fn test_parse_invalid_uri_suggests_fix() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["parse", "az://myaccount/My_Container/file.csv"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("container names must be"))
+            .stderr(predicate::str::contains(
+                "Did you mean: az://myaccount/my-container/file.csv",
+            ));
+    }
+}
+
+#[cfg(test)]
+mod immutability_tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_set_requires_days_or_until() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["retention", "set", "az://myaccount/records/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--days"));
+    }
+
+    #[test]
+    fn test_retention_until_must_be_in_future() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "retention",
+            "set",
+            "--until",
+            "2020-01-01",
+            "az://myaccount/records/ledger.csv",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("in the future"));
+    }
+
+    #[test]
+    fn test_container_legal_hold_requires_tag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["legalhold", "set", "az://myaccount/records/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--tag"));
+    }
+}
+
+#[cfg(test)]
+mod retry_failed_tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_failed_nothing_to_retry() {
+        let temp_dir = TempDir::new().unwrap();
+        let failures = temp_dir.path().join("failures.json");
+        fs::write(&failures, r#"{"operation": "copy", "failures": []}"#).unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["retry-failed", failures.to_str().unwrap()]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("No failed transfers to retry"));
+    }
+
+    #[test]
+    fn test_retry_failed_rejects_other_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let failures = temp_dir.path().join("failures.json");
+        fs::write(&failures, "[]").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["retry-failed", failures.to_str().unwrap()]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("is not a failures file"));
+    }
+}
+
+#[cfg(test)]
+mod rm_command_tests {
+    use super::*;
+
+    #[test]
+    fn test_rm_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Remove objects"));
+    }
+
+    #[test]
+    fn test_rm_missing_path() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("rm");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("required"));
+    }
+
+    #[test]
+    fn test_rm_age_and_size_filters_validate() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("keep.txt");
+        fs::write(&file, "data").unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "-f", "--older-than", "30d"]).arg(&file);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("only apply to blobs"));
+        assert!(file.exists());
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "rm",
+            "-rf",
+            "--older-than",
+            "60d",
+            "--newer-than",
+            "30d",
+            "az://acct/logs/",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--older-than must be shorter"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "-r", "--older-than", "30 days", "az://acct/logs/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid duration"));
+    }
+
+    #[test]
+    fn test_rm_max_deletes_flag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("--max-deletes"))
+            .stdout(predicate::str::contains("container name"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "-rf", "--max-deletes", "many", "az://acct/logs/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value"));
+    }
+
+    #[test]
+    fn test_rm_recursive_flag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "-r", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("recursive"));
+    }
+
+    #[test]
+    fn test_rm_force_flag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "-f", "--help"]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("Concatenate object content"));
+            .stdout(predicate::str::contains("force"));
     }
 
     #[test]
-    fn test_cat_missing_args() {
+    fn test_rm_nonexistent_file() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.arg("cat");
+        cmd.args(["rm", "-f", "/nonexistent/file.txt"]);
+
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("No URLs provided"));
+            .stderr(predicate::str::contains("does not exist"));
     }
 
     #[test]
-    fn test_cat_invalid_url() {
+    fn test_rm_stdin_removes_nul_terminated_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let names = ["with space.txt", "with\nnewline.txt"];
+        let mut input = String::new();
+        for name in names {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, "content").unwrap();
+            input.push_str(path.to_str().unwrap());
+            input.push('\0');
+        }
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cat", "invalid-url"]);
-        cmd.assert()
-            .failure()
-            .stderr(predicate::str::contains("Invalid URL"));
+        cmd.args(["rm", "-f", "-I"]).write_stdin(input);
+        cmd.assert().success();
+        for name in names {
+            assert!(!temp_dir.path().join(name).exists());
+        }
     }
 
     #[test]
-    fn test_cat_header_flag() {
+    fn test_rm_dry_run_keeps_local_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("keep.txt");
+        fs::write(&file, "content").unwrap();
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cat", "--header", "--help"]);
+        cmd.args(["rm", "-f", "--dry-run", file.to_str().unwrap()]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("header"));
+            .stdout(predicate::str::contains(format!(
+                "- {}",
+                file.to_str().unwrap()
+            )))
+            .stdout(predicate::str::contains("Would delete 1 file(s)"));
+        assert!(file.exists());
     }
 
     #[test]
-    fn test_cat_range_flag() {
+    fn test_rm_refuses_to_prompt_without_a_terminal() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("keep.txt");
+        fs::write(&file, "content").unwrap();
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cat", "--range", "0-100", "--help"]);
+        cmd.args(["rm", file.to_str().unwrap()]).write_stdin("y\n");
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("range"));
+            .code(2)
+            .stderr(predicate::str::contains("stdin is not a terminal"))
+            .stderr(predicate::str::contains("--force"));
+        assert!(file.exists());
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["--non-interactive", "rm", file.to_str().unwrap()]);
+        cmd.assert()
+            .code(2)
+            .stderr(predicate::str::contains("--non-interactive was given"));
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn test_rm_stdin_requires_force() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["rm", "-I"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--force"));
     }
 }
 
 #[cfg(test)]
-mod cp_command_tests {
+mod mv_command_tests {
     use super::*;
 
     #[test]
-    fn test_cp_missing_args() {
+    fn test_mv_help() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.arg("cp");
+        cmd.args(["mv", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Move files"));
+    }
+
+    #[test]
+    fn test_mv_missing_args() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.arg("mv");
         cmd.assert()
             .failure()
             .stderr(predicate::str::contains("required arguments"));
     }
 
     #[test]
-    fn test_cp_help() {
+    fn test_mv_recursive_flag() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cp", "--help"]);
+        cmd.args(["mv", "-r", "--help"]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("Copy files"));
+            .stdout(predicate::str::contains("recursive"));
     }
 
     #[test]
-    fn test_cp_local_to_local() {
+    fn test_mv_force_flag() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["mv", "-f", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("force"));
+    }
+
+    #[test]
+    fn test_mv_local_to_local_error() {
+        // mv should reject purely local operations
         let temp_dir = TempDir::new().unwrap();
         let source_file = temp_dir.path().join("source.txt");
         let dest_file = temp_dir.path().join("dest.txt");
@@ -131,422 +2263,777 @@ mod cp_command_tests {
 
         let mut cmd = Command::cargo_bin("azst").unwrap();
         cmd.args([
-            "cp",
+            "mv",
             source_file.to_str().unwrap(),
             dest_file.to_str().unwrap(),
         ]);
 
-        cmd.assert().success();
-
-        // Verify file was copied
-        assert!(dest_file.exists());
-        let content = fs::read_to_string(&dest_file).unwrap();
-        assert_eq!(content, "test content");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Azure path"));
     }
 
     #[test]
-    fn test_cp_recursive_flag() {
+    fn test_mv_help_mentions_copy_options() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cp", "-r", "--help"]);
+        cmd.args(["mv", "--help"]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("recursive"));
+            .stdout(predicate::str::contains("--dry-run"))
+            .stdout(predicate::str::contains("--include-pattern"))
+            .stdout(predicate::str::contains("--cap-mbps"))
+            .stdout(predicate::str::contains(
+                "Only files that were copied are removed",
+            ));
     }
 
     #[test]
-    fn test_cp_nonexistent_source() {
-        let temp_dir = TempDir::new().unwrap();
-        let dest_file = temp_dir.path().join("dest.txt");
+    fn test_mv_azure_uri_format_in_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["mv", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("az://"));
+    }
+}
 
+#[cfg(test)]
+mod ctl_tests {
+    use super::*;
+
+    #[test]
+    fn test_set_public_access_rejects_unknown_level() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cp", "/nonexistent/file.txt", dest_file.to_str().unwrap()]);
+        cmd.args([
+            "ctl",
+            "set-public-access",
+            "az://myaccount/mycontainer/",
+            "public",
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "possible values: off, blob, container",
+        ));
+    }
 
+    #[test]
+    fn test_set_public_access_requires_container() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["ctl", "set-public-access", "az://myaccount/", "off"]);
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("No such file or directory").or(
-                predicate::str::contains("The system cannot find the path specified"),
-            ));
+            .stderr(predicate::str::contains("must name a container"));
     }
 }
 
 #[cfg(test)]
-mod ls_command_tests {
+mod du_tests {
     use super::*;
 
     #[test]
-    fn test_ls_help() {
+    fn test_du_csv_output() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("test.txt"), "Hello, world!").unwrap();
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "--help"]);
+        cmd.args(["--format", "csv", "du", temp_dir.path().to_str().unwrap()]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("List objects"));
+            .stdout(predicate::str::starts_with("size,path\n13,"));
     }
 
     #[test]
-    fn test_ls_local_directory() {
+    fn test_du_max_depth_and_top() {
         let temp_dir = TempDir::new().unwrap();
+        let deep = temp_dir.path().join("big").join("deep");
+        fs::create_dir_all(&deep).unwrap();
+        fs::create_dir_all(temp_dir.path().join("small")).unwrap();
+        fs::write(deep.join("data.bin"), "x".repeat(100)).unwrap();
+        fs::write(temp_dir.path().join("small").join("a.txt"), "x").unwrap();
 
-        // Create some test files
-        fs::write(temp_dir.path().join("file1.txt"), "content1").unwrap();
-        fs::write(temp_dir.path().join("file2.txt"), "content2").unwrap();
+        let root = temp_dir.path().to_str().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "-d", "1", root]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("small"))
+            .stdout(predicate::str::contains("deep").not());
 
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", temp_dir.path().to_str().unwrap()]);
+        cmd.args(["du", "--top", "1", "-d", "1", root]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(format!("101\t{}\n", root)))
+            .stdout(predicate::str::contains("small").not());
+    }
+
+    #[test]
+    fn test_du_objects_column() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("one.txt"), "1").unwrap();
+        fs::write(temp_dir.path().join("two.txt"), "22").unwrap();
 
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "--format",
+            "csv",
+            "du",
+            "--objects",
+            temp_dir.path().to_str().unwrap(),
+        ]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("file1.txt"))
-            .stdout(predicate::str::contains("file2.txt"));
+            .stdout(predicate::str::starts_with("size,objects,path\n3,2,"));
     }
 
     #[test]
-    fn test_ls_local_file() {
+    fn test_du_tier_breakdown_requires_azure_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "--tier-breakdown", temp_dir.path().to_str().unwrap()]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("az:// paths"));
+    }
+
+    #[test]
+    fn test_du_partitions_must_be_positive() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "--partitions", "0", "az://acct/container/"]);
+        cmd.assert().failure().code(2);
+    }
+
+    #[test]
+    fn test_du_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("disk usage statistics"));
+    }
+
+    #[test]
+    fn test_du_azure_uri_format_in_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("az://"));
+    }
+
+    #[test]
+    fn test_du_local_file() {
         let temp_dir = TempDir::new().unwrap();
         let test_file = temp_dir.path().join("test.txt");
-        fs::write(&test_file, "content").unwrap();
+        let content = "Hello, world!";
+        fs::write(&test_file, content).unwrap();
 
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", test_file.to_str().unwrap()]);
+        cmd.args(["du", test_file.to_str().unwrap()]);
 
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("test.txt"));
+            .stdout(predicate::str::contains(content.len().to_string()));
     }
 
     #[test]
-    fn test_ls_long_format() {
+    fn test_du_local_file_human_readable() {
         let temp_dir = TempDir::new().unwrap();
-        fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+        let test_file = temp_dir.path().join("test.txt");
+        let content = "x".repeat(2048); // 2KB
+        fs::write(&test_file, content).unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "-H", test_file.to_str().unwrap()]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("KB"));
+    }
+
+    #[test]
+    fn test_du_local_directory() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Create a simple directory structure
+        fs::write(temp_dir.path().join("file1.txt"), "content1").unwrap();
+        fs::write(temp_dir.path().join("file2.txt"), "content2").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "-s", temp_dir.path().to_str().unwrap()]);
+
+        cmd.assert().success();
+    }
+
+    #[test]
+    fn test_du_nonexistent_path() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["du", "/nonexistent/path"]);
+
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("does not exist"));
+    }
+}
+
+#[cfg(test)]
+mod serve_tests {
+    use super::*;
+
+    #[test]
+    fn test_serve_help() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["serve", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("--list"))
+            .stdout(predicate::str::contains("--bind"));
+    }
+
+    #[test]
+    fn test_serve_rejects_invalid_bind_address() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["serve", "--bind", "not an address", "az://acct/site/"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid address to listen on"));
+    }
+
+    #[test]
+    fn test_serve_accepts_ipv6_bind_address() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["--timeout", "1s", "serve", "--bind", "::1", "--port", "0"])
+            .arg("az://acct/site/");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid address to listen on").not());
+    }
+}
+
+#[cfg(test)]
+mod shell_tests {
+    use super::*;
+
+    fn azst_shell(home: &TempDir) -> Command {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", home.path().join("config"))
+            .env("XDG_DATA_HOME", home.path().join("data"))
+            .arg("shell");
+        cmd
+    }
+
+    #[test]
+    fn test_shell_cd_and_pwd() {
+        let home = TempDir::new().unwrap();
+        azst_shell(&home)
+            .write_stdin("pwd\ncd az://acct/data/\ncd 2024/../logs\npwd\ncd\npwd\nexit\n")
+            .assert()
+            .success()
+            .stdout("az://\naz://acct/data/logs/\naz://\n");
+        assert!(home.path().join("data/azst/shell_history").exists());
+    }
+
+    #[test]
+    fn test_shell_runs_commands_and_survives_errors() {
+        let home = TempDir::new().unwrap();
+        azst_shell(&home)
+            .write_stdin("alias add d az://acct/data/\nnot-a-command\ncd d://2024\npwd\n")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("d:// now means az://acct/data/"))
+            .stdout(predicate::str::contains("az://acct/data/2024/"))
+            .stderr(predicate::str::contains("unrecognized subcommand"));
+    }
+}
+
+#[cfg(test)]
+mod sync_tests {
+    use super::*;
 
+    #[test]
+    fn test_sync_help_mentions_conflicts() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "-l", temp_dir.path().to_str().unwrap()]);
-
+        cmd.args(["sync", "--help"]);
         cmd.assert()
             .success()
-            // When not outputting to TTY, headers are not shown
-            // Check for the actual file listing instead
-            .stdout(predicate::str::contains("test.txt"));
+            .stdout(predicate::str::contains("--track-state"))
+            .stdout(predicate::str::contains("--conflict"));
     }
 
     #[test]
-    fn test_ls_human_readable() {
-        let temp_dir = TempDir::new().unwrap();
-        fs::write(temp_dir.path().join("test.txt"), "x".repeat(2048)).unwrap();
-
+    fn test_sync_help_mentions_preserve_empty_dirs() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "-lH", temp_dir.path().to_str().unwrap()]);
-
+        cmd.args(["sync", "--help"]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("KB").or(predicate::str::contains("B")));
+            .stdout(predicate::str::contains("--preserve-empty-dirs"));
     }
 
     #[test]
-    fn test_ls_nonexistent_path() {
+    fn test_sync_rejects_unknown_compare_mode() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "/nonexistent/path"]);
-
+        cmd.args([
+            "sync",
+            "--compare",
+            "etag",
+            "/tmp/src",
+            "az://myaccount/mycontainer/",
+        ]);
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("does not exist"));
+            .stderr(predicate::str::contains("checksum"));
     }
 
-    // Note: The following tests require Azure CLI to be installed and authenticated
-    // They are documented tests that verify the expected behavior
-
     #[test]
-    #[ignore] // Requires Azure CLI authentication
-    fn test_ls_storage_accounts() {
-        // Test: azst ls
-        // Expected: List all storage accounts in the current subscription
-        // Output format: az://accountname/
-        let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.arg("ls");
-
-        // This test will fail if not authenticated, which is expected
-        // When authenticated, it should list storage accounts with az:// prefix
-        let result = cmd.assert();
+    fn test_sync_dry_run_json_plan() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        let destination = temp.path().join("dst");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(destination.join("stale.txt"), "old").unwrap();
 
-        // Check that either we get storage accounts or an authentication error
-        result.stdout(
-            predicate::str::contains("Azure Storage Accounts")
-                .or(predicate::str::contains("az://")),
-        );
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["sync", "--delete", "-f", "--dry-run", "--format", "json"])
+            .arg(&source)
+            .arg(&destination);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::starts_with("{"))
+            .stdout(predicate::str::contains("\"copies\": 1"))
+            .stdout(predicate::str::contains("\"deletions\": 1"))
+            .stdout(predicate::str::contains("\"bytes\": 5"));
+        assert!(!destination.join("a.txt").exists());
+        assert!(destination.join("stale.txt").exists());
     }
 
     #[test]
-    #[ignore] // Requires Azure CLI authentication
-    fn test_ls_storage_accounts_long_format() {
-        // Test: azst ls -l
-        // Expected: List storage accounts with location and resource group
-        let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "-l"]);
+    fn test_sync_local_to_local() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        let destination = temp.path().join("dst");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("nested/b.txt"), "beta").unwrap();
+        fs::write(source.join("skip.tmp"), "scratch").unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(destination.join("stale.txt"), "old").unwrap();
 
-        let result = cmd.assert();
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["sync", "--delete", "-f", "--exclude-pattern", "*.tmp"])
+            .arg(&source)
+            .arg(&destination);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Syncing local to local"));
 
-        // Should show accounts with additional columns (location, resource group)
-        result.stdout(
-            predicate::str::contains("Azure Storage Accounts")
-                .or(predicate::str::contains("az://")),
+        assert_eq!(
+            fs::read_to_string(destination.join("a.txt")).unwrap(),
+            "alpha"
         );
-    }
+        assert_eq!(
+            fs::read_to_string(destination.join("nested/b.txt")).unwrap(),
+            "beta"
+        );
+        assert!(!destination.join("skip.tmp").exists());
+        assert!(!destination.join("stale.txt").exists());
 
-    #[test]
-    #[ignore] // Requires Azure CLI authentication
-    fn test_ls_containers_for_account() {
-        // Test: azst ls az://accountname/
-        // Expected: List all containers in the specified storage account
+        // Copies keep their modification time, so a second run has nothing to do
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "az://testaccount/"]);
-
-        // This will fail with an error if the account doesn't exist or user isn't authenticated
-        // When successful, it should list containers
-        let result = cmd.assert();
+        cmd.args(["sync", "--exclude-pattern", "*.tmp"])
+            .arg(&source)
+            .arg(&destination);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("already in sync"));
+    }
 
-        // Either shows containers or gives an error about the account
-        result.stdout(
-            predicate::str::contains("Azure Storage Containers")
-                .or(predicate::str::contains("az://")),
+    #[test]
+    fn test_sync_checksum_keeps_hashes_between_runs() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        let destination = temp.path().join("dst");
+        let cache = temp.path().join("cache");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("nested/b.txt"), "beta").unwrap();
+
+        for expected in ["Syncing local to local", "already in sync"] {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.env("XDG_CACHE_HOME", &cache)
+                .args(["sync", "--compare", "checksum"])
+                .arg(&source)
+                .arg(&destination);
+            cmd.assert()
+                .success()
+                .stdout(predicate::str::contains(expected));
+        }
+        assert_eq!(
+            fs::read_to_string(destination.join("nested/b.txt")).unwrap(),
+            "beta"
         );
+        // One cache file per hashed tree: the source and the destination
+        assert_eq!(fs::read_dir(cache.join("azst/hashes")).unwrap().count(), 2);
     }
 
     #[test]
-    fn test_ls_azure_uri_format_validation() {
-        // Test that Azure URI format is recognized correctly
-        // This test doesn't require Azure CLI as it tests parsing only
+    fn test_sync_mirror_refuses_to_empty_the_destination() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        let destination = temp.path().join("dst");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(destination.join(name), "kept").unwrap();
+        }
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "az://invalid-account-that-does-not-exist/"]);
+        cmd.args(["sync", "--mirror"])
+            .arg(&source)
+            .arg(&destination);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("would delete 3 of the 3 file(s)"))
+            .stderr(predicate::str::contains("c.txt"))
+            .stderr(predicate::str::contains("Mirror aborted"));
+        assert!(destination.join("a.txt").exists());
 
-        // Should fail with Azure-related error (not path error)
-        // Case-insensitive check for "storage" (can be "Storage" or "storage")
-        cmd.assert().failure().stderr(
-            predicate::str::contains("Azure")
-                .or(predicate::str::contains("storage"))
-                .or(predicate::str::contains("Storage")),
-        );
+        // --force goes ahead anyway
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["sync", "--mirror", "--force"])
+            .arg(&source)
+            .arg(&destination);
+        cmd.assert().success();
+        assert!(!destination.join("a.txt").exists());
     }
-}
-
-#[cfg(test)]
-mod rm_command_tests {
-    use super::*;
 
     #[test]
-    fn test_rm_help() {
+    fn test_sync_mirror_deletes_within_the_limit() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        let destination = temp.path().join("dst");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(source.join(name), "new").unwrap();
+        }
+        fs::write(destination.join("stale.txt"), "old").unwrap();
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["rm", "--help"]);
+        cmd.args(["sync", "--mirror", "--max-delete", "1"])
+            .arg(&source)
+            .arg(&destination);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("Remove objects"));
+            .stdout(predicate::str::contains("mirror"));
+        assert!(destination.join("a.txt").exists());
+        assert!(!destination.join("stale.txt").exists());
     }
 
     #[test]
-    fn test_rm_missing_path() {
-        let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.arg("rm");
-        cmd.assert()
-            .failure()
-            .stderr(predicate::str::contains("required"));
+    fn test_sync_two_way_flags_conflicts() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a");
+        let b = temp.path().join("b");
+        let state = temp.path().join("state.json");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        fs::write(a.join("doc.txt"), "v1").unwrap();
+        fs::write(b.join("notes.txt"), "from b").unwrap();
+
+        let two_way = || {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.args(["sync", "--two-way", "--conflict", "flag", "--state"])
+                .arg(&state)
+                .arg(&a)
+                .arg(&b);
+            cmd
+        };
+        two_way().assert().success();
+        assert_eq!(fs::read_to_string(b.join("doc.txt")).unwrap(), "v1");
+        assert_eq!(fs::read_to_string(a.join("notes.txt")).unwrap(), "from b");
+
+        // Edited on both sides: neither copy is overwritten, on every run
+        // until someone settles it
+        fs::write(a.join("doc.txt"), "edited in a").unwrap();
+        fs::write(b.join("doc.txt"), "edited in b, longer").unwrap();
+        for _ in 0..2 {
+            two_way()
+                .assert()
+                .success()
+                .stderr(predicate::str::contains("1 file(s) changed on both sides"))
+                .stderr(predicate::str::contains("doc.txt"));
+            assert_eq!(
+                fs::read_to_string(a.join("doc.txt")).unwrap(),
+                "edited in a"
+            );
+            assert_eq!(
+                fs::read_to_string(b.join("doc.txt")).unwrap(),
+                "edited in b, longer"
+            );
+        }
     }
 
     #[test]
-    fn test_rm_recursive_flag() {
+    fn test_sync_rejects_zero_concurrency() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["rm", "-r", "--help"]);
-        cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("recursive"));
+        cmd.args(["sync", "--concurrency", "0", "a", "b"]);
+        cmd.assert().failure().code(2);
     }
 
     #[test]
-    fn test_rm_force_flag() {
+    fn test_unknown_profile_is_an_error() {
+        let config = TempDir::new().unwrap();
+        fs::create_dir_all(config.path().join("azst")).unwrap();
+        fs::write(
+            config.path().join("azst/config.toml"),
+            "[profiles.fast.transfer]\nconcurrency = 512\n",
+        )
+        .unwrap();
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["rm", "-f", "--help"]);
+        cmd.env("XDG_CONFIG_HOME", config.path())
+            .env("AZST_PROFILE", "slow")
+            .args(["parse", "az://acct/data/"]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("force"));
+            .failure()
+            .stderr(predicate::str::contains("No profile 'slow'"))
+            .stderr(predicate::str::contains("profiles: fast"));
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.env("XDG_CONFIG_HOME", config.path()).args([
+            "--profile",
+            "fast",
+            "parse",
+            "az://acct/data/",
+        ]);
+        cmd.assert().success();
     }
 
     #[test]
-    fn test_rm_nonexistent_file() {
+    fn test_sync_mirror_rejects_bad_limit() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["rm", "-f", "/nonexistent/file.txt"]);
-
+        cmd.args(["sync", "--mirror", "--max-delete", "120%", "a", "b"]);
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("does not exist"));
+            .stderr(predicate::str::contains("percentage"));
     }
-}
 
-#[cfg(test)]
-mod mv_command_tests {
-    use super::*;
+    #[test]
+    fn test_sync_local_path_filters() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        let destination = temp.path().join("dst");
+        fs::create_dir_all(source.join("node_modules/pkg")).unwrap();
+        fs::create_dir_all(source.join("lib")).unwrap();
+        fs::write(source.join("node_modules/pkg/index.js"), "dep").unwrap();
+        fs::write(source.join("lib/main.js"), "main").unwrap();
+        fs::write(source.join("lib/main.js.bak"), "backup").unwrap();
+
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args([
+            "sync",
+            "--exclude-path",
+            "node_modules",
+            "--exclude-regex",
+            r"\.bak$",
+        ])
+        .arg(&source)
+        .arg(&destination);
+        cmd.assert().success();
+
+        assert!(destination.join("lib/main.js").exists());
+        assert!(!destination.join("lib/main.js.bak").exists());
+        assert!(!destination.join("node_modules").exists());
+    }
 
     #[test]
-    fn test_mv_help() {
+    fn test_sync_rejects_invalid_regex() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["mv", "--help"]);
+        cmd.args([
+            "sync",
+            "--include-regex",
+            "(unclosed",
+            "/tmp/src",
+            "az://myaccount/mycontainer/",
+        ]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("Move files"));
+            .failure()
+            .stderr(predicate::str::contains("Invalid regular expression"));
     }
 
     #[test]
-    fn test_mv_missing_args() {
+    fn test_sync_local_missing_source() {
+        let temp = TempDir::new().unwrap();
+
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.arg("mv");
+        cmd.arg("sync")
+            .arg(temp.path().join("missing"))
+            .arg(temp.path().join("dst"));
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("required arguments"));
+            .stderr(predicate::str::contains("does not exist"));
     }
 
     #[test]
-    fn test_mv_recursive_flag() {
+    fn test_sync_conflict_requires_track_state() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["mv", "-r", "--help"]);
+        cmd.args([
+            "sync",
+            "--conflict",
+            "newer",
+            "/tmp/src",
+            "az://myaccount/mycontainer/",
+        ]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("recursive"));
+            .failure()
+            .stderr(predicate::str::contains("--track-state"));
     }
 
     #[test]
-    fn test_mv_force_flag() {
+    fn test_sync_bidirectional_requires_track_state() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["mv", "-f", "--help"]);
+        cmd.args([
+            "sync",
+            "--bidirectional",
+            "/tmp/src",
+            "az://myaccount/mycontainer/",
+        ]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("force"));
+            .failure()
+            .stderr(predicate::str::contains("--track-state"));
     }
 
     #[test]
-    fn test_mv_local_to_local_error() {
-        // mv should reject purely local operations
-        let temp_dir = TempDir::new().unwrap();
-        let source_file = temp_dir.path().join("source.txt");
-        let dest_file = temp_dir.path().join("dest.txt");
-
-        // Create source file
-        fs::write(&source_file, "test content").unwrap();
-
+    fn test_sync_bidirectional_conflicts_with_delete() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
         cmd.args([
-            "mv",
-            source_file.to_str().unwrap(),
-            dest_file.to_str().unwrap(),
+            "sync",
+            "--bidirectional",
+            "--delete",
+            "--track-state",
+            "state.json",
+            "/tmp/src",
+            "az://myaccount/mycontainer/",
         ]);
-
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("Azure path"));
+            .stderr(predicate::str::contains("cannot be used with"));
     }
 
     #[test]
-    fn test_mv_azure_uri_format_in_help() {
+    fn test_sync_invalid_conflict_policy() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["mv", "--help"]);
+        cmd.args([
+            "sync",
+            "--track-state",
+            "state.json",
+            "--conflict",
+            "whatever",
+            "/tmp/src",
+            "az://myaccount/mycontainer/",
+        ]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("az://"));
+            .failure()
+            .stderr(predicate::str::contains("invalid value"));
     }
 }
 
 #[cfg(test)]
-mod du_tests {
+mod utils_integration_tests {
     use super::*;
 
     #[test]
-    fn test_du_help() {
+    fn test_uri_validation_in_commands() {
+        // Test that invalid URIs result in file not found error
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["du", "--help"]);
+        cmd.args(["ls", "invalid://uri"]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("disk usage statistics"));
+            .failure()
+            .stderr(predicate::str::contains("does not exist"));
     }
 
     #[test]
-    fn test_du_azure_uri_format_in_help() {
+    fn test_azure_uri_format_in_help() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["du", "--help"]);
+        cmd.args(["cp", "--help"]);
         cmd.assert()
             .success()
             .stdout(predicate::str::contains("az://"));
     }
+}
 
-    #[test]
-    fn test_du_local_file() {
-        let temp_dir = TempDir::new().unwrap();
-        let test_file = temp_dir.path().join("test.txt");
-        let content = "Hello, world!";
-        fs::write(&test_file, content).unwrap();
+#[cfg(test)]
+mod bench_tests {
+    use super::*;
 
+    #[test]
+    fn test_bench_help() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["du", test_file.to_str().unwrap()]);
-
+        cmd.args(["bench", "--help"]);
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains(content.len().to_string()));
+            .stdout(predicate::str::contains("--size-per-file"))
+            .stdout(predicate::str::contains("--download"));
     }
 
     #[test]
-    fn test_du_local_file_human_readable() {
-        let temp_dir = TempDir::new().unwrap();
-        let test_file = temp_dir.path().join("test.txt");
-        let content = "x".repeat(2048); // 2KB
-        fs::write(&test_file, content).unwrap();
-
+    fn test_bench_upload_conflicts_with_download() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["du", "-H", test_file.to_str().unwrap()]);
-
+        cmd.args(["bench", "--upload", "--download", "az://acct/scratch/"]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("KB"));
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
     }
 
     #[test]
-    fn test_du_local_directory() {
-        let temp_dir = TempDir::new().unwrap();
-
-        // Create a simple directory structure
-        fs::write(temp_dir.path().join("file1.txt"), "content1").unwrap();
-        fs::write(temp_dir.path().join("file2.txt"), "content2").unwrap();
-
+    fn test_bench_rejects_zero_files() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["du", "-s", temp_dir.path().to_str().unwrap()]);
-
-        cmd.assert().success();
+        cmd.args(["bench", "--file-count", "0", "az://acct/scratch/"]);
+        cmd.assert().failure().code(2);
     }
 
     #[test]
-    fn test_du_nonexistent_path() {
-        let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["du", "/nonexistent/path"]);
-
-        cmd.assert()
-            .failure()
-            .stderr(predicate::str::contains("does not exist"));
+    fn test_bench_needs_container() {
+        for uri in ["/tmp/scratch", "az://acct/"] {
+            let mut cmd = Command::cargo_bin("azst").unwrap();
+            cmd.args(["bench", uri]);
+            cmd.assert()
+                .failure()
+                .stderr(predicate::str::contains("bench needs a container"));
+        }
     }
 }
 
 #[cfg(test)]
-mod utils_integration_tests {
+mod watch_tests {
     use super::*;
 
     #[test]
-    fn test_uri_validation_in_commands() {
-        // Test that invalid URIs result in file not found error
+    fn test_watch_help() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["ls", "invalid://uri"]);
+        cmd.args(["watch", "--help"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("AZST_EVENT"))
+            .stdout(predicate::str::contains("--change-feed"));
+    }
+
+    #[test]
+    fn test_watch_needs_container() {
+        let mut cmd = Command::cargo_bin("azst").unwrap();
+        cmd.args(["watch", "az://acct/"]);
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("does not exist"));
+            .stderr(predicate::str::contains("watch needs a container"));
     }
 
     #[test]
-    fn test_azure_uri_format_in_help() {
+    fn test_watch_rejects_zero_interval() {
         let mut cmd = Command::cargo_bin("azst").unwrap();
-        cmd.args(["cp", "--help"]);
+        cmd.args(["watch", "--interval", "0s", "az://acct/data/"]);
         cmd.assert()
-            .success()
-            .stdout(predicate::str::contains("az://"));
+            .failure()
+            .stderr(predicate::str::contains("--interval"));
     }
 }